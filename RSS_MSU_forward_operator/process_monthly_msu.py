@@ -6,12 +6,12 @@ import argparse
 import logging
 import sys
 from collections.abc import Sequence
-from dataclasses import dataclass
+from dataclasses import dataclass, field
 from datetime import datetime, timezone
 import calendar
 from pathlib import Path
 from time import perf_counter_ns
-from typing import Optional, Union
+from typing import Any, Optional, Union
 
 import numpy as np
 from netCDF4 import Dataset, getlibversion, num2date
@@ -95,6 +95,9 @@ class RtmMonthlyData:
     # lats, lons, freq).
     tb_down: NDArray[np.float32]
 
+    # Units and provenance of the RTM outputs, from `AtmoParameters.metadata()`.
+    metadata: dict[str, Any] = field(default_factory=dict)
+
 
 
     def write_nc(self, rtm_output: Path) -> None:
@@ -132,6 +135,13 @@ class RtmMonthlyData:
             f.setncattr_string("time_coverage_start", time_start)
             f.setncattr_string("time_coverage_end", time_end)
             f.setncattr_string("standard_name_vocabulary", "CF Standard Name Table v78")
+            if self.metadata:
+                f.setncattr_string("rtm_version", self.metadata["version"])
+                f.setncattr_string("rtm_git_describe", self.metadata["git_describe"])
+                f.setncattr_string("rtm_emission", self.metadata["emission"])
+                for absorber, model in self.metadata["spectroscopy"].items():
+                    f.setncattr_string(f"rtm_spectroscopy_{absorber}", model)
+            units = self.metadata.get("units", {})
 
             # ----------
             # Dimensions
@@ -183,6 +193,7 @@ class RtmMonthlyData:
             )
             v[...] = self.transmissivity
             v.setncattr_string("long_name", "atmospheric transmissivity")
+            v.setncattr_string("units", units.get("tran", "1"))
             v.setncattr_string("coordinates", "lat lon")
 
             v = f.createVariable(
@@ -190,7 +201,7 @@ class RtmMonthlyData:
             )
             v[...] = self.tb_up
             v.setncattr_string("long_name", "upwelling brightness temperature")
-            v.setncattr_string("units", "kelvin")
+            v.setncattr_string("units", units.get("tb_up", "K"))
             v.setncattr_string("coordinates", "lat lon")
 
             v = f.createVariable(
@@ -198,7 +209,7 @@ class RtmMonthlyData:
             )
             v[...] = self.tb_down
             v.setncattr_string("long_name", "downwelling brightness temperature")
-            v.setncattr_string("units", "kelvin")
+            v.setncattr_string("units", units.get("tb_down", "K"))
             v.setncattr_string("coordinates", "lat lon")
 
 
@@ -263,6 +274,7 @@ def run_rtm_msu(
         tran,
         tb_up,
         tb_down,
+        atmo_results.metadata(),
     )
 
 
@@ -0,0 +1,51 @@
+//! Time batches of points with the default number of threads, which adapts to
+//! the size of the batch, against a pool with one thread for each CPU, for a
+//! small granule and a large one:
+//!
+//! ```text
+//! cargo bench --bench adaptive_threads
+//! ```
+//!
+//! The small granule runs on fewer threads, so it's faster, and the large one
+//! runs on every thread either way, so it's the same.
+
+use std::{hint::black_box, num::NonZeroUsize, time::Instant};
+
+use rss_atmos_rtm::rtm::{
+    adaptive_num_threads, compute_rtm_batch, standard_atmospheres::MID_LATITUDE_SUMMER,
+    RtmParameters, DEFAULT_ITEMS_PER_THREAD,
+};
+
+const SMALL_GRANULE: usize = 200;
+const LARGE_GRANULE: usize = 100_000;
+const REPEATS: usize = 50;
+
+/// Time `REPEATS` batches of `num_points` points, for `num_threads` threads, in
+/// points per second.
+fn throughput(num_points: usize, num_threads: Option<NonZeroUsize>) -> f64 {
+    let parameters = RtmParameters::new(&[23.8, 31.4, 50.3, 52.8], &[53.1; 4]).unwrap();
+    let inputs = vec![MID_LATITUDE_SUMMER.profile().inputs().unwrap(); num_points];
+    let repeats = (REPEATS * SMALL_GRANULE / num_points).max(1);
+
+    // Warm up
+    black_box(compute_rtm_batch(&inputs, &parameters, num_threads).unwrap());
+    let start = Instant::now();
+    for _ in 0..repeats {
+        black_box(compute_rtm_batch(black_box(&inputs), &parameters, num_threads).unwrap());
+    }
+    (repeats * num_points) as f64 / start.elapsed().as_secs_f64()
+}
+
+fn main() {
+    let target = NonZeroUsize::new(DEFAULT_ITEMS_PER_THREAD).unwrap();
+    let max_threads = adaptive_num_threads(usize::MAX, 1, target);
+    for num_points in [SMALL_GRANULE, LARGE_GRANULE] {
+        let adaptive = adaptive_num_threads(num_points, 4, target);
+        println!(
+            "{num_points} points and 4 channels: {:.0} points/s on {adaptive} adaptive threads, \
+             {:.0} points/s on {max_threads} threads",
+            throughput(num_points, None),
+            throughput(num_points, Some(max_threads)),
+        );
+    }
+}
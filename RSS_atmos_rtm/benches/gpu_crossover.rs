@@ -0,0 +1,70 @@
+//! Time the RTM with the gas absorption on the GPU against the CPU, for
+//! increasing batch sizes of a standard atmosphere and 200 channels, to find
+//! the batch size above which the GPU is faster:
+//!
+//! ```text
+//! cargo bench --bench gpu_crossover --no-default-features --features gpu
+//! ```
+
+use std::{hint::black_box, time::Instant};
+
+use rss_atmos_rtm::rtm::{
+    compute_rtm_batch, gpu::GpuAbsorption, standard_atmospheres::MID_LATITUDE_SUMMER, RtmParameters,
+};
+
+const NUM_CHANNELS: usize = 200;
+const BATCH_SIZES: [usize; 5] = [1, 10, 100, 1000, 10_000];
+
+/// Time `run` in seconds per point, repeating it for at least a second (but
+/// at least once) to smooth out small batches.
+fn seconds_per_point(batch_size: usize, mut run: impl FnMut()) -> f64 {
+    run();
+    let start = Instant::now();
+    let mut repeats = 0;
+    while repeats == 0 || start.elapsed().as_secs_f64() < 1. {
+        run();
+        repeats += 1;
+    }
+    start.elapsed().as_secs_f64() / (repeats * batch_size) as f64
+}
+
+fn main() {
+    let Some(gpu) = GpuAbsorption::shared() else {
+        println!("no GPU adapter was found");
+        return;
+    };
+    println!("GPU adapter: {}", gpu.adapter_name());
+
+    // Channels from 20 to 200 GHz, as for a hyperspectral sounder
+    let frequency: Vec<f32> = (0..NUM_CHANNELS)
+        .map(|i| 20. + 180. * i as f32 / NUM_CHANNELS as f32)
+        .collect();
+    let parameters = RtmParameters::new(&frequency, &vec![30.; NUM_CHANNELS]).unwrap();
+    let point = MID_LATITUDE_SUMMER.profile().inputs().unwrap();
+
+    let mut crossover = None;
+    println!(
+        "{:>10} {:>14} {:>14}",
+        "points", "CPU µs/point", "GPU µs/point"
+    );
+    for batch_size in BATCH_SIZES {
+        let inputs = vec![point.clone(); batch_size];
+        let cpu = seconds_per_point(batch_size, || {
+            black_box(compute_rtm_batch(black_box(&inputs), &parameters, None).unwrap());
+        });
+        let gpu = seconds_per_point(batch_size, || {
+            black_box(
+                gpu.compute_rtm_batch(black_box(&inputs), &parameters, None)
+                    .unwrap(),
+            );
+        });
+        println!("{batch_size:>10} {:>14.1} {:>14.1}", cpu * 1e6, gpu * 1e6);
+        if gpu < cpu && crossover.is_none() {
+            crossover = Some(batch_size);
+        }
+    }
+    match crossover {
+        Some(batch_size) => println!("the GPU is faster from {batch_size} points"),
+        None => println!("the GPU was not faster for any batch size"),
+    }
+}
@@ -0,0 +1,69 @@
+//! Time the RTM for a 120-level profile and 40 channels, to compare the
+//! default inline capacities, with which these spill to the heap, against the
+//! larger ones:
+//!
+//! ```text
+//! cargo bench --bench inline_capacity
+//! cargo bench --bench inline_capacity --features many-channels,many-levels
+//! ```
+
+use std::{hint::black_box, time::Instant};
+
+use rss_atmos_rtm::rtm::{RtmInputs, RtmParameters, CHANNEL_CAPACITY, LEVEL_CAPACITY};
+
+const NUM_LEVELS: usize = 120;
+const NUM_CHANNELS: usize = 40;
+const NUM_POINTS: usize = 2000;
+
+fn main() {
+    // Levels evenly spaced in ln(p) from 1050 hPa to 0.1 hPa
+    let levels: Vec<f32> = (0..NUM_LEVELS)
+        .map(|i| 1050. * (0.1f32 / 1050.).powf(i as f32 / (NUM_LEVELS - 1) as f32))
+        .collect();
+    let height: Vec<f32> = levels.iter().map(|p| -7500. * (p / 1013.25).ln()).collect();
+    let temperature: Vec<f32> = height
+        .iter()
+        .map(|z| f32::max(288. - 6.5e-3 * z, 216.65))
+        .collect();
+    let specific_humidity: Vec<f32> = height.iter().map(|z| 0.01 * (-z / 2000.).exp()).collect();
+    let liquid_content = vec![0.; NUM_LEVELS];
+    let inputs = RtmInputs::new(
+        &levels,
+        288.,
+        &temperature,
+        0.,
+        &height,
+        283.,
+        &specific_humidity,
+        &liquid_content,
+        Some(1013.),
+        None,
+    )
+    .unwrap();
+
+    // Channels from 50 to 58 GHz, at two incidence angles
+    let frequency: Vec<f32> = (0..NUM_CHANNELS)
+        .map(|i| 50. + 8. * (i / 2) as f32 / (NUM_CHANNELS / 2) as f32)
+        .collect();
+    let incidence: Vec<f32> = (0..NUM_CHANNELS)
+        .map(|i| if i % 2 == 0 { 0. } else { 53. })
+        .collect();
+    let parameters = RtmParameters::new(&frequency, &incidence).unwrap();
+
+    // Warm up
+    for _ in 0..NUM_POINTS / 10 {
+        black_box(inputs.run(black_box(&parameters)));
+    }
+
+    let start = Instant::now();
+    for _ in 0..NUM_POINTS {
+        black_box(black_box(&inputs).run(black_box(&parameters)));
+    }
+    let elapsed = start.elapsed();
+
+    println!(
+        "{NUM_LEVELS} levels (inline capacity {LEVEL_CAPACITY}), {NUM_CHANNELS} channels \
+         (inline capacity {CHANNEL_CAPACITY}): {:.1} µs per point",
+        elapsed.as_secs_f64() * 1e6 / NUM_POINTS as f64
+    );
+}
@@ -0,0 +1,100 @@
+//! Time a batch where a cluster of the points is much more expensive than the
+//! rest, for several chunk sizes:
+//!
+//! ```text
+//! cargo bench --bench work_chunks
+//! ```
+//!
+//! The last 5% of the points have 10 times the levels of the others, which is
+//! 10 times the work, as for 10 times the channels. With Rayon's own splitting,
+//! the thread that starts on the expensive points is still running them after
+//! the others have finished. Smaller chunks spread them over every thread, so
+//! the batch finishes sooner. With a single CPU, every chunk size takes about
+//! the same time, so the default chunk size should be checked with this on a
+//! machine with several CPUs.
+
+use std::{hint::black_box, num::NonZeroUsize, time::Instant};
+
+use rss_atmos_rtm::rtm::{
+    adaptive_num_threads, compute_rtm_batch,
+    standard_atmospheres::{StandardProfile, MID_LATITUDE_SUMMER},
+    RtmInputs, RtmParameters, DEFAULT_ITEMS_PER_THREAD, DEFAULT_WORK_CHUNK_SIZE,
+};
+
+const NUM_POINTS: usize = 10_000;
+const EXPENSIVE_FRACTION: f64 = 0.05;
+const LEVEL_FACTOR: usize = 10;
+const REPEATS: usize = 3;
+
+/// Linearly interpolate `factor - 1` levels between each pair of levels.
+fn refine(values: &[f32], factor: usize) -> Vec<f32> {
+    let mut refined: Vec<f32> = values
+        .windows(2)
+        .flat_map(|pair| {
+            (0..factor).map(move |i| pair[0] + (pair[1] - pair[0]) * i as f32 / factor as f32)
+        })
+        .collect();
+    refined.extend(values.last());
+    refined
+}
+
+/// The profile on `factor` times as many levels.
+fn refined(profile: &StandardProfile, factor: usize) -> StandardProfile {
+    StandardProfile {
+        pressure: refine(&profile.pressure, factor),
+        temperature: refine(&profile.temperature, factor),
+        height: refine(&profile.height, factor),
+        specific_humidity: refine(&profile.specific_humidity, factor),
+        liquid_content: refine(&profile.liquid_content, factor),
+        ..profile.clone()
+    }
+}
+
+/// The batch, with the expensive points at the end.
+fn inputs() -> Vec<RtmInputs> {
+    let profile = MID_LATITUDE_SUMMER.profile();
+    let cheap = profile.inputs().unwrap();
+    let expensive = refined(&profile, LEVEL_FACTOR).inputs().unwrap();
+    let num_expensive = (NUM_POINTS as f64 * EXPENSIVE_FRACTION) as usize;
+    let mut inputs = vec![cheap; NUM_POINTS - num_expensive];
+    inputs.resize(NUM_POINTS, expensive);
+    inputs
+}
+
+fn main() {
+    let parameters = RtmParameters::new(&[23.8, 31.4, 50.3, 52.8], &[53.1; 4]).unwrap();
+    let inputs = inputs();
+    let num_threads = adaptive_num_threads(
+        usize::MAX,
+        1,
+        NonZeroUsize::new(DEFAULT_ITEMS_PER_THREAD).unwrap(),
+    );
+    println!(
+        "{NUM_POINTS} points on {num_threads} threads, the last {}% with {LEVEL_FACTOR} times \
+         the levels",
+        EXPENSIVE_FRACTION * 100.
+    );
+
+    let chunk_sizes = [
+        (NonZeroUsize::MAX, "Rayon's splitting".to_string()),
+        (NonZeroUsize::new(256).unwrap(), "256 points".to_string()),
+        (
+            NonZeroUsize::new(DEFAULT_WORK_CHUNK_SIZE).unwrap(),
+            format!("{DEFAULT_WORK_CHUNK_SIZE} points (default)"),
+        ),
+        (NonZeroUsize::new(4).unwrap(), "4 points".to_string()),
+    ];
+    for (chunk_size, name) in chunk_sizes {
+        let parameters = parameters.clone().with_work_chunk_size(chunk_size);
+        // Warm up
+        black_box(compute_rtm_batch(&inputs, &parameters, Some(num_threads)).unwrap());
+        let start = Instant::now();
+        for _ in 0..REPEATS {
+            black_box(
+                compute_rtm_batch(black_box(&inputs), &parameters, Some(num_threads)).unwrap(),
+            );
+        }
+        let elapsed = start.elapsed().as_secs_f64() / REPEATS as f64;
+        println!("chunks of at most {name}: {:.0} ms", elapsed * 1e3);
+    }
+}
@@ -0,0 +1,54 @@
+//! Bake the `git describe` string into the build, for provenance metadata, and
+//! with the `capi` feature, generate the C header.
+
+use std::{path::Path, process::Command};
+
+fn main() {
+    let describe = Command::new("git")
+        .args(["describe", "--always", "--dirty", "--tags"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|describe| describe.trim().to_string())
+        .filter(|describe| !describe.is_empty())
+        .unwrap_or_else(|| String::from("unknown"));
+    println!("cargo:rustc-env=RSS_ATMOS_RTM_GIT_DESCRIBE={describe}");
+
+    // Only rerun when the checked-out commit or the index changes, but only if
+    // this is being built from a git checkout (e.g., not from an sdist)
+    for path in ["../.git/HEAD", "../.git/index"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={path}");
+        }
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+
+    #[cfg(feature = "capi")]
+    generate_header();
+}
+
+/// Generate the C header for `src/capi.rs` with cbindgen, and write it next to
+/// the library in the target directory, e.g., `target/release/rss_atmos_rtm.h`.
+#[cfg(feature = "capi")]
+fn generate_header() {
+    use std::{env, path::PathBuf};
+
+    let out_dir = PathBuf::from(env::var_os("OUT_DIR").expect("OUT_DIR is set by Cargo"));
+    // OUT_DIR is `<target>/<profile>/build/<package>-<hash>/out`
+    let header_dir = out_dir.ancestors().nth(3).unwrap_or(&out_dir);
+    let config = cbindgen::Config {
+        language: cbindgen::Language::C,
+        header: Some("/* Generated by cbindgen from src/capi.rs. Do not edit. */".to_string()),
+        include_guard: Some("RSS_ATMOS_RTM_H".to_string()),
+        usize_is_size_t: true,
+        ..Default::default()
+    };
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src("src/capi.rs")
+        .generate()
+        .expect("src/capi.rs can be parsed by cbindgen")
+        .write_to_file(header_dir.join("rss_atmos_rtm.h"));
+    println!("cargo:rerun-if-changed=src/capi.rs");
+}
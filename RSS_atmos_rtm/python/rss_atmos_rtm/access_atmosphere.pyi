@@ -1,56 +1,540 @@
-from typing import Optional, final
+import os
+from typing import (
+    Any,
+    Callable,
+    Literal,
+    Optional,
+    Sequence,
+    TypeVar,
+    Union,
+    final,
+    overload,
+)
 
 import numpy as np
 from numpy.typing import NDArray
 
+from . import conversions as conversions
+
+# The floating-point dtypes supported by `compute_rtm`
+_Float = TypeVar("_Float", np.float32, np.float64)
+
+@final
+class Diagnostics:
+    """Counts of how often the adjustments and checks of the RTM fired in a
+    call, and how long it took, to monitor a production run without scraping
+    the logs or inspecting the quality flags of every point.
+
+    If any point fails, the call raises, and the exception has the
+    `Diagnostics` of the points run so far as its `diagnostics` attribute, so
+    the failed and skipped points can be counted.
+    """
+
+    @property
+    def num_points(self) -> int:
+        """Number of points that were run."""
+    @property
+    def points_failed(self) -> int:
+        """Number of points whose RTM failed. This is only nonzero for the
+        `diagnostics` of the exception raised for them.
+        """
+    @property
+    def points_skipped(self) -> int:
+        """Number of points that weren't run since the call was cancelled, by an
+        exception from the absorption model. This is only nonzero for the
+        `diagnostics` of the exception raised for it.
+        """
+    @property
+    def clamped(self) -> dict[str, int]:
+        """Number of points with a value clamped, by the name of the variable.
+        This is `surface_vapor_pressure`, for the points whose surface vapor
+        pressure was clamped to saturation with `clamp_surface_saturation`.
+        """
+    @property
+    def layers_fixed(self) -> int:
+        """Number of zero-thickness (or inverted) bottom layers that were fixed,
+        by moving the surface below the first level above it. There is at most
+        one per point.
+        """
+    @property
+    def elapsed_seconds(self) -> float:
+        """Wall-clock time of the call in seconds."""
+    @property
+    def counts(self) -> dict[str, int]:
+        """Number of points with each quality flag set, by the lowercase name
+        of the flag, for instance `surface_vapor_clamped` for the points whose
+        surface vapor pressure was clamped to saturation and
+        `surface_height_adjusted` for those whose surface was moved below the
+        first level above it. The flags are counted whether or not
+        `quality_flags` were requested.
+        """
+
 @final
 class AtmoParameters:
     """Atmospheric radiative parameters.
 
-    This is the output of the RTM.
+    This is the output of the RTM. The floating-point outputs have the same
+    dtype as the inputs.
     """
 
     @property
-    def tran(self) -> NDArray[np.float32]:
+    def tran(self) -> Union[NDArray[np.float32], NDArray[np.float64]]:
         """Transmissivity, from 0 to 1.
 
         Dimensioned as (`num_points`, `num_freq`).
         """
     @property
-    def tb_up(self) -> NDArray[np.float32]:
+    def tb_up(self) -> Union[NDArray[np.float32], NDArray[np.float64]]:
         """Upwelling TB, in K.
 
         Dimensioned as (`num_points`, `num_freq`).
         """
     @property
-    def tb_down(self) -> NDArray[np.float32]:
+    def tb_down(self) -> Union[NDArray[np.float32], NDArray[np.float64]]:
         """Downwelling TB, in K.
 
         Dimensioned as (`num_points`, `num_freq`).
         """
+    @property
+    def tran_to_space(
+        self,
+    ) -> Optional[Union[NDArray[np.float32], NDArray[np.float64]]]:
+        """Transmissivity from each diagnostic level to space, from 0 to 1.
+
+        Dimensioned as (`num_points`, `num_freq`, `num_diagnostic_levels`), or
+        `None` if no diagnostic levels were requested.
+        """
+    @property
+    def tb_up_above(
+        self,
+    ) -> Optional[Union[NDArray[np.float32], NDArray[np.float64]]]:
+        """Upwelling TB from the atmosphere above each diagnostic level, in K.
+
+        Dimensioned as (`num_points`, `num_freq`, `num_diagnostic_levels`), or
+        `None` if no diagnostic levels were requested.
+        """
+    @property
+    def t_eff_up(
+        self,
+    ) -> Optional[Union[NDArray[np.float32], NDArray[np.float64]]]:
+        """Effective temperature of the upwelling, `tb_up / (1 - tran)`, in K.
+
+        Dimensioned as (`num_points`, `num_freq`), or `None` if the effective
+        temperatures were not requested. See `compute_rtm`.
+        """
+    @property
+    def t_eff_down(
+        self,
+    ) -> Optional[Union[NDArray[np.float32], NDArray[np.float64]]]:
+        """Effective temperature of the downwelling, `tb_down / (1 - tran)`, in K.
+
+        Dimensioned as (`num_points`, `num_freq`), or `None` if the effective
+        temperatures were not requested. See `compute_rtm`.
+        """
+    @property
+    def tb_up_unnoised(
+        self,
+    ) -> Optional[Union[NDArray[np.float32], NDArray[np.float64]]]:
+        """Atmospheric upwelling in K before the instrument noise was added.
+
+        Dimensioned as (`num_points`, `num_freq`), or `None` if there's no
+        noise or the unnoised values were not requested. See `compute_rtm`.
+        """
+    @property
+    def air_mass_factor(
+        self,
+    ) -> Optional[Union[NDArray[np.float32], NDArray[np.float64]]]:
+        """Ratio of the slant optical depth to the zenith optical depth.
+
+        Dimensioned as (`num_points`, `num_freq`), or `None` if it was not
+        requested. See `compute_rtm`.
+        """
+    @property
+    def tb_down_level(
+        self,
+    ) -> Optional[Union[NDArray[np.float32], NDArray[np.float64]]]:
+        """Atmospheric downwelling in K at the level `down_level`.
+
+        Dimensioned as (`num_points`, `num_freq`), or `None` if `down_level`
+        was not set in the options. See `compute_rtm`.
+        """
+    @property
+    def quality_flags(self) -> Optional[NDArray[np.uint32]]:
+        """Bitfield of quality flags.
+
+        Dimensioned as (`num_points`, ), or `None` if the quality flags were not
+        requested. See `compute_rtm` for the bit assignments.
+        """
+    @property
+    def num_levels_integrated(self) -> NDArray[np.int32]:
+        """Number of profile levels above the surface that were integrated.
+
+        Dimensioned as (`num_points`, ). See `compute_rtm`.
+        """
+    @property
+    def profile_time(self) -> Optional[NDArray[np.float32]]:
+        """Wall-clock time to prepare and run each point, in microseconds.
+
+        Dimensioned as (`num_points`, ), or `None` if `profile_timing` was not
+        set in the options. See `compute_rtm`.
+        """
+    @property
+    def diagnostics(self) -> Diagnostics:
+        """Counts of the quality flags of the points and the wall-clock time of
+        the call."""
+    def metadata(self) -> dict[str, Any]:
+        """Units and provenance of the outputs.
+
+        This is the same as `get_model_info()`, with the addition of `units`,
+        which maps each output field to its units, `emission`, the emission
+        convention used for the radiative transfer, and `tb_convention`, the
+        convention of the output brightness temperatures.
+        """
+    def flat_records(self) -> dict[str, NDArray[Any]]:
+        """The outputs as flat records, with one record for each point and
+        channel.
+
+        The records are returned as a dict of 1d columns, each dimensioned as
+        (`num_points` * `num_freq`, ), with the channels varying fastest. The
+        columns are `point` and `channel`, the indices of the record, followed
+        by `tran`, `tb_up`, `tb_down`, `t_eff_up` and `t_eff_down` (if they
+        were requested), `quality_flags` (if it was requested), and
+        `num_levels_integrated`, where the last two are repeated for each
+        channel of a point. This is the layout of a table, so it can be passed
+        directly to, e.g., `pyarrow.table()`. The diagnostic level outputs are not
+        included.
+        """
+    def to_parquet(
+        self,
+        path: Union[str, os.PathLike[str]],
+        coords: Optional[dict[str, NDArray[Any]]] = None,
+        layout: Literal["wide", "long"] = "wide",
+    ) -> None:
+        """Write the outputs to a Parquet file at `path`, with coordinates of
+        the points.
+
+        Optionally, `coords` is a dict of 1d arrays with shape (`num_points`,
+        ), such as the time, latitude, longitude, and scan position of each
+        point, which are written first, in order. They can be any numeric or
+        bool dtype, or `datetime64` in s, ms, us, or ns. The per-point outputs
+        (`quality_flags`, `num_levels_integrated`, and `profile_time`, if they
+        were requested) follow them, and then the outputs of each channel.
+
+        With `layout="wide"`, there's a row for each point and a column for
+        each output and channel, named `{output}_{channel}`, e.g., `tb_up_0`.
+        With `layout="long"`, there's a row for each point and channel, as for
+        `flat_records()`, with `point` and `channel` columns and the per-point
+        columns repeated for each channel. The diagnostic level outputs are not
+        included.
+
+        This is only available if the package was built with the `arrow`
+        feature.
+        """
+
+@final
+class RtmParameters:
+    """The channels to compute the RTM for: each is a frequency and an
+    incidence angle.
+
+    These are stored in double precision and converted to the precision of the
+    profiles when they are used, so the same parameters can be used for many
+    calls to `compute_rtm`. They can be pickled, e.g., to send them to the
+    workers of a distributed run.
+    """
+
+    def __init__(
+        self,
+        frequency: Union[NDArray[np.floating], list[float]],
+        incidence_angle: Union[NDArray[np.floating], list[float]],
+        frequency_offset: Optional[Union[NDArray[np.floating], list[float]]] = None,
+    ) -> None:
+        """Create the parameters from the microwave `frequency` in GHz and the
+        Earth `incidence_angle` in degrees of each channel. Both must have the
+        same non-zero length, and each incidence angle must be within 0° to
+        90°, inclusive.
+
+        Optionally, `frequency_offset` shifts the frequency of each channel by
+        that many MHz, for instance to correct for the drift of a channel's
+        passband centroid. The `frequency` property is then the shifted
+        frequencies.
+        """
+    @staticmethod
+    def frequency_range(
+        start: float, stop: float, step: float, incidence_angle: float
+    ) -> RtmParameters:
+        """Create the parameters for a sweep of frequencies in GHz, all at the
+        same Earth `incidence_angle` in degrees, without building the array of
+        frequencies in Python.
+
+        The frequencies are the same as from `numpy.arange(start, stop, step)`:
+        they go from `start` up to but not including `stop`, though as with
+        `numpy.arange`, rounding can add a frequency just past `stop` (for
+        instance, 1 to 1.3 in steps of 0.1 gives 1, 1.1, 1.2, and
+        1.3000000000000003), so a `stop` between two steps is more predictable.
+        The step can be negative for a decreasing sweep. The range must be
+        finite and have at least one frequency.
+        """
+    @staticmethod
+    def for_scan_position(
+        sensor: str, channels: list[int], position: int
+    ) -> RtmParameters:
+        """Create the parameters for the `channels` of the cross-track sounder
+        `sensor` at the scan position `position`, with the Earth incidence
+        angle of that position.
+
+        The `sensor` is `"msu"` or `"amsu_a"`. The channels are numbered from 1,
+        as in the instrument documentation, and the scan positions from 0, from
+        one edge of the swath to the other. See the Rust documentation of the
+        `scan_geometry` module for the tables.
+        """
+    @staticmethod
+    def from_channels(
+        channels: list[Channel],
+        incidence_angle: Union[NDArray[np.floating], list[float]],
+    ) -> RtmParameters:
+        """Create the parameters from the passband of each of `channels`, made
+        with `Channel.from_srf`, and the Earth `incidence_angle` in degrees of
+        each channel.
+
+        The outputs of `compute_rtm` for each channel, including those on
+        diagnostic levels, the effective temperatures, and the brightness
+        temperatures at the top of the atmosphere, are integrated over the
+        nodes of its passband. The `frequency` property is the centroid
+        frequency of each passband.
+        """
+    @property
+    def frequency(self) -> NDArray[np.float64]:
+        """Microwave frequency of each channel, in GHz."""
+    @property
+    def incidence_angle(self) -> NDArray[np.float64]:
+        """Earth incidence angle of each channel, in degrees."""
+    def __len__(self) -> int: ...
+
+@final
+class RtmOptions:
+    """The model and numerical choices of `compute_rtm`, gathered so the same
+    choices can be reused across calls and passed as its `options`.
+
+    Each option has the same meaning and default as the keyword of
+    `compute_rtm` with the same name. The absorption scale factors are only
+    options: they multiply the absorption of each absorber, by 1 by default,
+    e.g., to fit the biases of observations. So is `profile_timing`, which
+    outputs the compute time of each point as `profile_time`, and so is
+    `down_level`, which outputs the downwelling at that level as
+    `tb_down_level`, and so are `items_per_thread` and `work_chunk_size`,
+    which choose how the points are split between the worker threads, and
+    `layer_sum_reference`, the temperature that the layer emission is summed
+    relative to: `"first_level"` above the surface (the default, as in the
+    original Fortran), `"surface"`, or `"lowest_layer_mean"`. It cancels in the
+    sums, so it only changes the outputs by rounding. The options can be
+    pickled.
+    """
+
+    def __init__(
+        self,
+        *,
+        emission: str = "rayleigh_jeans",
+        tb_convention: Optional[str] = None,
+        integration_scheme: Literal[
+            "trapezoidal", "linear_in_optical_depth"
+        ] = "trapezoidal",
+        layer_sum_reference: Literal[
+            "first_level", "surface", "lowest_layer_mean"
+        ] = "first_level",
+        clamp_surface_saturation: bool = False,
+        top_pressure: Optional[float] = None,
+        quality_flags: bool = False,
+        effective_temperature: bool = False,
+        air_mass_factor: bool = False,
+        max_failure_logs: int = 20,
+        verbosity: Literal["quiet", "progress", "debug"] = "progress",
+        oxygen_scale: float = 1.0,
+        water_vapor_line_scale: float = 1.0,
+        water_vapor_continuum_scale: float = 1.0,
+        cloud_scale: float = 1.0,
+        profile_timing: bool = False,
+        down_level: Optional[int] = None,
+        items_per_thread: int = 512,
+        work_chunk_size: int = 32,
+    ) -> None:
+        """Create the options from keywords, with the same defaults as
+        `compute_rtm`.
+        """
+    @property
+    def emission(self) -> str:
+        """How the emission of each layer is computed."""
+    @property
+    def tb_convention(self) -> Optional[str]:
+        """Convention of the output TBs, or `None` for the same as the emission."""
+    @property
+    def integration_scheme(self) -> str:
+        """How the layer emission is integrated."""
+    @property
+    def layer_sum_reference(self) -> str:
+        """The temperature that the layer emission is summed relative to."""
+    @property
+    def clamp_surface_saturation(self) -> bool:
+        """Whether the surface vapor pressure is clamped to saturation."""
+    @property
+    def top_pressure(self) -> Optional[float]:
+        """Pressure in hPa above which levels are discarded, or `None` for all levels."""
+    @property
+    def quality_flags(self) -> bool:
+        """Whether the quality flags are output."""
+    @property
+    def effective_temperature(self) -> bool:
+        """Whether the effective temperatures are output."""
+    @property
+    def air_mass_factor(self) -> bool:
+        """Whether the air-mass factors are output."""
+    @property
+    def max_failure_logs(self) -> int:
+        """Maximum number of warnings logged for failed points."""
+    @property
+    def verbosity(self) -> str:
+        """How much a call logs about its progress."""
+    @property
+    def oxygen_scale(self) -> float:
+        """Factor on the oxygen absorption."""
+    @property
+    def water_vapor_line_scale(self) -> float:
+        """Factor on the water vapor line absorption."""
+    @property
+    def water_vapor_continuum_scale(self) -> float:
+        """Factor on the water vapor continuum absorption."""
+    @property
+    def cloud_scale(self) -> float:
+        """Factor on the cloud liquid water absorption."""
+    @property
+    def profile_timing(self) -> bool:
+        """Whether the compute time of each point is output."""
+    @property
+    def down_level(self) -> Optional[int]:
+        """Index of the profile level whose downwelling is output, or `None`."""
+    @property
+    def items_per_thread(self) -> int:
+        """Target number of (point, channel) pairs for each worker thread,
+        which chooses the number of threads when `num_threads` is `None`.
+
+        The number of threads is the number of pairs divided by the target,
+        rounded up to a power of 2, and at most one for each CPU (or
+        `RAYON_NUM_THREADS`). A larger target uses fewer threads for small
+        batches, e.g., when many granules are run at once by a scheduler,
+        while large batches use every CPU either way. The default is 512.
+        """
+    @property
+    def work_chunk_size(self) -> int:
+        """Most points that a worker thread runs as one chunk of work.
+
+        A thread can't hand off the rest of a chunk that it has started, so
+        smaller chunks balance the load better when some points are much more
+        expensive than the others, and a very large chunk size leaves the
+        splitting to Rayon. The outputs are the same for any chunk size. The
+        default is 32.
+        """
+
+@final
+class Channel:
+    """The passband of a channel, as frequency nodes in GHz and their
+    quadrature weights, which sum to 1. Use `RtmParameters.from_channels` to
+    compute the RTM for these channels.
+    """
+
+    @staticmethod
+    def from_srf(
+        frequency: NDArray[np.float64],
+        response: NDArray[np.float64],
+        num_nodes: int = 16,
+    ) -> Channel:
+        """Create a channel from a measured spectral response function: the
+        relative `response` at each of `frequency` in GHz, which must be
+        strictly increasing.
+
+        The response is linear between the tabulated frequencies, and it's
+        resampled to at most `num_nodes` quadrature nodes: the range of the
+        frequencies is split into `num_nodes` bins of equal width, and each bin
+        with a non-zero response becomes a node at its response-weighted mean
+        frequency, weighted by the integral of the response over it. A single
+        frequency is a delta function, i.e., a monochromatic channel.
+        """
+    @staticmethod
+    def from_quadrature(nodes: list[float], weights: list[float]) -> Channel:
+        """Create a channel from the frequency `nodes` in GHz of its quadrature
+        and their `weights`, as in the `nodes` and `weights` properties. The
+        weights are normalized to sum to 1.
+        """
+    @property
+    def nodes(self) -> NDArray[np.float64]:
+        """Frequency of each quadrature node, in GHz."""
+    @property
+    def weights(self) -> NDArray[np.float64]:
+        """Weight of each quadrature node. These sum to 1."""
+    @property
+    def centroid(self) -> float:
+        """Centroid frequency of the passband, in GHz."""
+
+def get_model_info() -> dict[str, Any]:
+    """Get information about the model, for logging or provenance.
+
+    The returned dict has the crate `version`, the `git_describe` string of the
+    source it was built from, `spectroscopy`, a dict of the absorption model in
+    use for each absorber, and `frequency_limits`, a dict of the highest
+    frequency in GHz of the validated range of each absorption model.
+    """
 
 def compute_rtm(
-    pressure: NDArray[np.float32],
-    temperature: NDArray[np.float32],
-    height: NDArray[np.float32],
-    specific_humidity: NDArray[np.float32],
-    liquid_content: NDArray[np.float32],
-    surface_temperature: NDArray[np.float32],
-    surface_height: NDArray[np.float32],
-    surface_dewpoint: NDArray[np.float32],
-    surface_pressure: NDArray[np.float32],
-    incidence_angle: NDArray[np.float32],
-    frequency: NDArray[np.float32],
+    pressure: NDArray[_Float],
+    temperature: NDArray[_Float],
+    height: NDArray[_Float],
+    specific_humidity: NDArray[_Float],
+    liquid_content: Optional[NDArray[_Float]],
+    surface_temperature: NDArray[_Float],
+    surface_height: NDArray[_Float],
+    surface_dewpoint: NDArray[_Float],
+    surface_pressure: Optional[NDArray[_Float]],
+    incidence_angle: Optional[NDArray[_Float]],
+    frequency: Optional[NDArray[_Float]],
     num_threads: Optional[int],
+    diagnostic_levels: Optional[NDArray[_Float]] = None,
+    top_pressure: Optional[float] = None,
+    emission: str = "rayleigh_jeans",
+    quality_flags: bool = False,
+    skin_temperature: Optional[NDArray[_Float]] = None,
+    observer_height: Optional[NDArray[_Float]] = None,
+    parameters: Optional[RtmParameters] = None,
+    tb_convention: Optional[str] = None,
+    sort_key: Optional[NDArray[np.int64]] = None,
+    clamp_surface_saturation: bool = False,
+    absorption_model: Optional[
+        Callable[[float, float, float, float, float], float]
+    ] = None,
+    effective_temperature: bool = False,
+    scan_angle: Optional[NDArray[_Float]] = None,
+    satellite_altitude: Optional[float] = None,
+    sensor: Optional[str] = None,
+    channels: Optional[list[int]] = None,
+    scan_position: Optional[NDArray[np.int64]] = None,
+    frequency_offset: Optional[NDArray[_Float]] = None,
+    noise_nedt: Optional[NDArray[_Float]] = None,
+    noise_seed: Optional[int] = None,
+    return_unnoised: bool = False,
+    air_mass_factor: bool = False,
+    integration_scheme: Literal["trapezoidal", "linear_in_optical_depth"] = "trapezoidal",
+    max_failure_logs: int = 20,
+    verbosity: Literal["quiet", "progress", "debug"] = "progress",
+    options: Optional[RtmOptions] = None,
 ) -> AtmoParameters:
     """Compute the radiative transfer model for the atmosphere.
 
     Most of the inputs are numpy arrays and are either 1d or 2d. The
-    `pressure` parameter is the pressure levels in hPa and has shape
-    (`num_levels`, ). It is treated as a constant (i.e., not a function of
-    `num_points`).
+    `pressure` parameter is the pressure levels in hPa. It usually has shape
+    (`num_levels`, ) and is shared by all of the points, but for model levels
+    whose pressure varies from point to point (such as hybrid sigma-pressure
+    levels), it can instead have shape (`num_points`, `num_levels`) like the
+    profiles.
 
-    `pressure`: pressure levels, in hPa
+    `pressure`: pressure levels, in hPa, strictly decreasing (from the surface
+    upward) for each point
 
     The following are input profiles and have shape (`num_points`,
     `num_levels`):
@@ -61,7 +545,8 @@ def compute_rtm(
 
     `specific_humidity`: specific humidity in kg/kg
 
-    `liquid_content`: liquid water content (from clouds) in kg/kg
+    `liquid_content`: liquid water content (from clouds) in kg/kg, or `None`
+    for clear sky, which is the same as all zeros but without the array
 
     The following are surface parameters and have shape (`num_points`, ):
 
@@ -71,7 +556,9 @@ def compute_rtm(
 
     `surface_dewpoint`: 2 meter dewpoint in K
 
-    `surface_pressure`: surface pressure in hPa
+    `surface_pressure`: surface pressure in hPa, or `None` to take the first
+    pressure level as the surface, in which case the other surface
+    parameters should be the values at that level
 
     The following are RTM parameters and have shape (`num_freq`, ):
 
@@ -79,9 +566,1273 @@ def compute_rtm(
 
     `frequency`: microwave frequency in GHz
 
+    Alternatively, the channels can be given as an `RtmParameters` with the
+    `parameters` keyword argument, in which case `incidence_angle` and
+    `frequency` must be `None`. Or, in place of `incidence_angle` (which must
+    then be `None`), the channels can be given by `scan_angle`, the scan angle
+    from nadir in degrees with shape (`num_freq`, ), and `satellite_altitude`,
+    the height of the satellite in m, which are converted as in
+    `eia_from_scan`.
+
+    For a swath of a cross-track sounder, the channels can instead be given by
+    `sensor` (`"msu"` or `"amsu_a"`), the channel numbers `channels` (from 1,
+    or all of the channels if `None`), and `scan_position`, the scan position of
+    each point from 0 with shape (`num_points`, ). Each point then has the
+    Earth incidence angle of its scan position, as in `scan_incidence_angle`.
+
+    However the channels are given, `frequency_offset` optionally shifts the
+    frequency of each channel by that many MHz, with shape (`num_freq`, ), for
+    instance to correct for the drift of a channel's passband centroid. It's
+    added before the absorption models are evaluated. The default is no shift.
+
     The returned atmospheric parameters are each dimensioned as (`num_points`,
     `num_freq`).
 
     The number of worker threads is controlled by `num_threads`. It must be a
-    positive integer, or `None` to automatically choose the number of threads.
+    positive integer, or `None` to automatically choose the number of threads
+    from the number of points and channels (see the `items_per_thread` of
+    `RtmOptions`).
+
+    The outputs are deterministic: they are bit-for-bit identical for any
+    `num_threads` and on repeated runs with the same inputs (on the same
+    machine and build). Each point is computed independently and in the same
+    order of operations, regardless of which thread computes it.
+
+    A `ValueError` is raised if any incidence angle is outside of 0° to 90°
+    (90° itself is allowed).
+
+    If any points fail, for instance with no profile levels above the
+    surface, a `ValueError` is raised for the first of them. Before that, a
+    warning is logged for each failed point with its index (in the input
+    order), the kind of error, and its surface pressure and temperature, up
+    to `max_failure_logs` messages, followed by a summary with the number of
+    failed points and of suppressed messages.
+
+    The `verbosity` of the log messages is set for each call, independent of
+    the level of the Python logger: `"quiet"` only logs the warnings,
+    `"progress"` (the default) also logs the number of completed points every
+    5 seconds, and `"debug"` also logs each phase of the call at the debug
+    level. For any verbosity, a one-line summary with the number of points
+    and channels, the elapsed time, and the points per second is logged at
+    the info level when the call finishes.
+
+    Optionally, `diagnostic_levels` is a small array of pressures in hPa, with
+    shape (`num_diagnostic_levels`, ). When given, the transmissivity from each
+    diagnostic level to space and the upwelling TB from the atmosphere above
+    each level are also output, interpolated in ln(p) from the profile levels.
+    Diagnostic levels with a higher pressure than a point's surface pressure
+    are NaN for that point.
+
+    Optionally, `top_pressure` is a pressure in hPa above which the profile
+    levels are discarded, for instance to exclude the very low pressures where
+    the absorption models are poorly constrained. By default, all levels are
+    used.
+
+    The `emission` selects how layer emission is computed: `"rayleigh_jeans"`
+    (the default) is linear in the physical temperature, as in the original
+    Fortran, while `"planck"` does the radiative transfer in Planck radiance
+    and outputs Planck-equivalent brightness temperatures. The difference is
+    largest for cold scenes at high frequencies.
+
+    The `integration_scheme` selects how the layer emission is integrated:
+    `"trapezoidal"` (the default) uses the mean temperature of each layer, as
+    in the original Fortran, while `"linear_in_optical_depth"` takes the
+    temperature as linear in optical depth across each layer. The two agree
+    for optically thin layers. The scheme applies to all the outputs,
+    including `tb_up_above` and the effective temperatures.
+
+    The `tb_convention` selects the convention of the output brightness
+    temperatures `tb_up`, `tb_down`, and `tb_up_above`, and is the same as
+    `emission` by default. `"rayleigh_jeans"` is linear in radiance, as in the
+    original Fortran, while `"planck"` is the temperature of a blackbody with
+    the same radiance at the channel frequency, for comparison with instruments
+    that report those. At the MSU frequencies, Planck brightness temperatures
+    are about 1.3 K warmer.
+
+    Optionally, `sort_key` is an int64 array with shape (`num_points`, ). The
+    points are still computed in the order given, but the output rows are
+    sorted by the key (stably, so points with equal keys stay in their input
+    order). This is the same as indexing each output with
+    `np.argsort(sort_key, kind="stable")`, but without the extra copy.
+
+    The number of profile levels integrated for each point, those at or above
+    the surface pressure and below `top_pressure`, is always output as
+    `num_levels_integrated`, an int32 array with shape (`num_points`, ). The
+    levels below the surface are discarded, so this varies with the surface
+    pressure, and a small value flags a point where most of the profile was
+    discarded.
+
+    If `quality_flags` is true, a bitfield of quality flags is also output for
+    each point. The bits are:
+
+    - 1: the surface height was not below the first level above it, so it was
+      moved 0.1 m below
+    - 2: an input value used by the RTM was NaN or infinite
+    - 4: a temperature used by the RTM was outside of 150 K to 350 K
+    - 8: cloud water was present outside of the valid temperature range of the
+      dielectric model
+    - 16: an output value was NaN or infinite
+    - 32: the surface vapor pressure was above saturation, so it was clamped
+      (only with `clamp_surface_saturation`)
+    - 64: a frequency was above the validated range of an absorption model
+      that was used, which is extrapolated (a warning is also logged once per
+      call; see `frequency_limits` in `get_model_info()`)
+
+    If `clamp_surface_saturation` is true, the surface vapor pressure from
+    `surface_dewpoint` is clamped so that it does not exceed saturation at
+    `surface_temperature`. A dewpoint above the air temperature, which is a
+    common inconsistency in reanalyses, otherwise makes the surface
+    supersaturated and overestimates the absorption in the lowest layer. Each
+    clamped point is logged at the debug level.
+
+    Optionally, `absorption_model` is a Python callable that replaces the
+    absorption models of this package, for experimenting with other
+    spectroscopy. It is called as `absorption_model(p, t, pv, rho_l, freq)` for
+    each level of each point and each distinct frequency, with the pressure in
+    hPa, temperature in K, water vapor pressure in hPa, liquid water density in
+    g/m³, and frequency in GHz, and returns the absorption coefficient in Np/m
+    as a float. Since the callable holds the GIL, the points are effectively
+    computed one at a time and this is much slower than the built-in models.
+    If it raises an exception, the remaining points are cancelled and the
+    exception is re-raised. This cannot be combined with `diagnostic_levels`.
+
+    If `effective_temperature` is true, the effective temperatures of the
+    atmosphere for vicarious calibration, `t_eff_up = tb_up / (1 - tran)` and
+    `t_eff_down = tb_down / (1 - tran)`, are also output for each point and
+    channel. These are computed from the same layer sums as `tran`, `tb_up`,
+    and `tb_down`, so they keep their precision when the atmosphere is nearly
+    transparent, where dividing the outputs loses it. As `tran` goes to 1 they
+    go to the mean layer temperature weighted by the layer opacities, and as
+    `tran` goes to 0 they go to `tb_up` and `tb_down`. They are NaN only if
+    there is no absorption at all. With `observer_height`, `t_eff_down` is
+    divided by `1 - tran` for the whole atmosphere rather than the `tran` to
+    the observer. With `emission="planck"`, the ratio is taken in radiance
+    before converting to the `tb_convention`. This cannot be combined with
+    `diagnostic_levels` or `absorption_model`.
+
+    Optionally, `skin_temperature` is the radiometric skin temperature of the
+    surface in K, with shape (`num_points`, ). This is distinct from
+    `surface_temperature`, the 2 meter air temperature, which is the
+    temperature of the lowest level of the atmosphere. The skin temperature is
+    only for the surface emission, and the atmospheric outputs here do not
+    depend on it. Using the air temperature in place of the skin temperature
+    biases the window-channel TBs.
+
+    Optionally, `observer_height` is the geometric height in m of the observer
+    for each point, with shape (`num_points`, ), for an airborne or mountaintop
+    sensor. The upwelling integration stops at the profile level nearest to the
+    observer, so `tran` and `tb_up` are for the path between the surface and the
+    observer. `tb_down` is still the downwelling at the surface from the whole
+    atmosphere, since that is what the surface reflects up to the observer. By
+    default, the observer is at the top of the atmosphere. The diagnostic level
+    outputs are always for an observer at the top of the atmosphere.
+
+    Optionally, `noise_nedt` is the NEΔT in K of each channel, with shape
+    (`num_freq`, ), for simulated observations. Independent Gaussian noise with
+    that standard deviation is added to `tb_up` for each point and channel. The
+    noise is from a random number generator seeded with `noise_seed`, so the same
+    seed gives the same noise, or a random seed if it's `None`. If
+    `return_unnoised` is true, the values before the noise was added are also
+    output as `tb_up_unnoised`.
+
+    If `air_mass_factor` is true, the air-mass factor of each point and channel,
+    the ratio of the slant optical depth to the zenith optical depth, is also
+    output as `air_mass_factor`. The RTM lengthens the path through every layer by
+    the same factor, which only depends on the incidence angle. For a
+    plane-parallel atmosphere, it reduces to `sec(incidence_angle)`, and the
+    spherical Earth keeps it within 0.5% of that up to 75°, and finite at 90°.
+
+    Optionally, `options` is an `RtmOptions` with the model and numerical
+    choices: `emission`, `tb_convention`, `integration_scheme`,
+    `clamp_surface_saturation`, `top_pressure`, `quality_flags`,
+    `effective_temperature`, `air_mass_factor`, `max_failure_logs`, and
+    `verbosity`. The keywords of the same names are deprecated, and setting any
+    of them to other than its default emits a `DeprecationWarning`; they cannot
+    be combined with `options`, which raises a `ValueError`.
+
+    With `options=RtmOptions(down_level=k)`, the downwelling brightness
+    temperature at the level with index `k` of the profiles, from the
+    atmosphere above it, is also output as `tb_down_level`, with shape
+    (`num_points`, `num_freq`), e.g., to couple another model at that level.
+    It's what `tb_down` would be if the surface were at that level, so it's 0 K
+    at the top level, and NaN where the level is below the surface. The level
+    must be one of the profile levels, which raises a `ValueError` otherwise,
+    and points whose `top_pressure` discards it fail. This is not supported with
+    `diagnostic_levels`, `absorption_model` or `effective_temperature`.
+
+    With `options=RtmOptions(profile_timing=True)`, the wall-clock time in
+    microseconds to prepare and run each point is also output as
+    `profile_time`, a float32 array with shape (`num_points`, ), for instance
+    to see how imbalanced the work is across a field of deep and shallow
+    profiles. The times include any waiting for other threads, such as for the
+    GIL with an `absorption_model`, and are noisy for small points.
+
+    With no points (empty profile arrays), the RTM isn't run, and the outputs
+    are empty, with shape (0, `num_freq`). The inputs are still checked, so the
+    shapes must be consistent, and there must be at least one channel, which
+    raises a `ValueError` otherwise.
+
+    The floating-point arrays must either all be float32 or all be float64, and
+    the outputs have the same dtype. The RTM is computed in that precision,
+    though the absorption model coefficients themselves are single precision
+    and the layers are always integrated in double precision.
+    The two agree to a relative difference of 1e-5 (a few mK at most).
+    """
+
+def compute_rtm_dict(
+    inputs: dict[str, Optional[NDArray[Any]]],
+    num_threads: Optional[int] = None,
+    parameters: Optional[RtmParameters] = None,
+    options: Optional[RtmOptions] = None,
+) -> AtmoParameters:
+    """Compute the RTM for the arrays in a dict, by name rather than position.
+
+    The keys of `inputs` are the names of the array arguments of
+    `compute_rtm`, which have the same meanings and shapes: `pressure`,
+    `temperature`, `height`, `specific_humidity`, `surface_temperature`,
+    `surface_height`, and `surface_dewpoint` are required, and
+    `liquid_content`, `surface_pressure`, `skin_temperature`,
+    `observer_height`, `incidence_angle`, and `frequency` are optional. So the
+    dicts from `standard_atmosphere` and `read_rttov_profiles` can be passed
+    directly, as can the variables of an xarray Dataset, e.g., `{name:
+    ds[name].values for name in ds.data_vars}`.
+
+    A `KeyError` is raised that lists all of the missing required keys, and a
+    `ValueError` for any key that isn't one of the inputs, such as a
+    misspelling. The channels are either the `incidence_angle` and `frequency`
+    of the dict, or `parameters`, an `RtmParameters`, and the other arguments
+    are as for `compute_rtm`.
+    """
+
+def compute_rtm_block(
+    pressure: NDArray[_Float],
+    temperature: NDArray[_Float],
+    height: NDArray[_Float],
+    specific_humidity: NDArray[_Float],
+    liquid_content: Optional[NDArray[_Float]],
+    surface_temperature: NDArray[_Float],
+    surface_height: NDArray[_Float],
+    surface_dewpoint: NDArray[_Float],
+    surface_pressure: Optional[NDArray[_Float]],
+    parameters: RtmParameters,
+    options: Optional[RtmOptions] = None,
+    num_threads: Optional[int] = None,
+) -> NDArray[_Float]:
+    """Compute the RTM for a block of profiles, with a fixed signature of plain
+    arrays in and out for `dask.array.map_blocks` and the like.
+
+    The profile arrays are the same as for `compute_rtm`, and `parameters` and
+    `options` are an `RtmParameters` and an `RtmOptions`, which can be pickled
+    to send them to the workers. With no `options`, nothing is logged, since
+    progress for each block is noise in a distributed run. The worker threads
+    are shared by all of the calls in a process (and started on the first), so
+    the overhead of a call is small next to the RTM for a block of thousands of
+    points. The number of threads is controlled by `num_threads`, as for
+    `compute_rtm`; with several workers in a process, fewer threads each may
+    be faster.
+
+    The output is an array with shape (`num_points`, `num_freq`, 3) of the
+    same dtype as the inputs, with `tran`, `tb_up`, and `tb_down` along the
+    last axis. The other outputs of the options, such as the quality flags,
+    aren't included. A point that fails has NaN outputs, as in `compute_rtm`.
+    """
+
+def compute_rtm_npy(
+    pressure: Union[str, os.PathLike[str], np.memmap],
+    temperature: Union[str, os.PathLike[str], np.memmap],
+    height: Union[str, os.PathLike[str], np.memmap],
+    specific_humidity: Union[str, os.PathLike[str], np.memmap],
+    liquid_content: Optional[Union[str, os.PathLike[str], np.memmap]],
+    surface_temperature: Union[str, os.PathLike[str], np.memmap],
+    surface_height: Union[str, os.PathLike[str], np.memmap],
+    surface_dewpoint: Union[str, os.PathLike[str], np.memmap],
+    surface_pressure: Optional[Union[str, os.PathLike[str], np.memmap]],
+    incidence_angle: NDArray[np.float32],
+    frequency: NDArray[np.float32],
+    num_threads: Optional[int],
+    chunk_size: int = 100_000,
+    options: Optional[RtmOptions] = None,
+) -> AtmoParameters:
+    """Compute the RTM for profiles in `.npy` files that may be larger than
+    memory, a chunk of points at a time.
+
+    The profile arguments are the same as for `compute_rtm`, except that each
+    is the path of a `.npy` file, as written by `numpy.save`, or a memmap of
+    one from `numpy.load(path, mmap_mode="r")`, whose file is used. The
+    `pressure` is either (`num_levels`, ) or (`num_points`, `num_levels`). The
+    files must be little-endian float32 or float64 in C order, and the RTM is
+    computed in float32.
+
+    The points are read from the files `chunk_size` at a time, in order, with
+    plain sequential reads of each file, so the memory use is bounded by one
+    chunk of the inputs plus the outputs, which are (`num_points`, `num_freq`)
+    arrays. The pages of the inputs are never touched through numpy, so they
+    aren't kept resident by a memmap.
+
+    Optionally, `options` is an `RtmOptions`, as for `compute_rtm`, except that
+    `effective_temperature`, `air_mass_factor` and `down_level` aren't
+    supported. The progress is logged after each chunk. The outputs are the
+    same as from `compute_rtm`, and if any point fails, a `ValueError` is
+    raised.
+    """
+
+def compute_rtm_npy_into(
+    tran: NDArray[np.float32],
+    tb_up: NDArray[np.float32],
+    tb_down: NDArray[np.float32],
+    pressure: Union[str, os.PathLike[str], np.memmap],
+    temperature: Union[str, os.PathLike[str], np.memmap],
+    height: Union[str, os.PathLike[str], np.memmap],
+    specific_humidity: Union[str, os.PathLike[str], np.memmap],
+    liquid_content: Optional[Union[str, os.PathLike[str], np.memmap]],
+    surface_temperature: Union[str, os.PathLike[str], np.memmap],
+    surface_height: Union[str, os.PathLike[str], np.memmap],
+    surface_dewpoint: Union[str, os.PathLike[str], np.memmap],
+    surface_pressure: Optional[Union[str, os.PathLike[str], np.memmap]],
+    incidence_angle: NDArray[np.float32],
+    frequency: NDArray[np.float32],
+    num_threads: Optional[int] = None,
+    chunk_size: int = 100_000,
+    options: Optional[RtmOptions] = None,
+) -> Diagnostics:
+    """Compute the RTM for profiles in `.npy` files, as in `compute_rtm_npy`,
+    and write the outputs into the arrays `tran`, `tb_up`, and `tb_down`
+    instead of returning them, so that neither the inputs nor the outputs need
+    to fit in memory.
+
+    The outputs are writable float32 arrays of shape (`num_points`,
+    `num_freq`), typically memmaps of `.npy` files on disk from
+    `numpy.lib.format.open_memmap(path, mode="w+", dtype=np.float32,
+    shape=(num_points, num_freq))`. The points are run `chunk_size` at a time,
+    and the worker threads write the row of each point directly into the
+    arrays as soon as it's done, so only one chunk of the inputs is in memory
+    at once, and the written pages of the outputs are left for the operating
+    system to write back to disk. Call `flush` on the memmaps afterwards to
+    make sure they're on disk.
+
+    The workers write concurrently without any locking, which is safe since
+    each point has its own row, so no two workers ever write to the same
+    memory. For that to hold, the three arrays must not overlap each other
+    (which raises an error), and no other thread or process may read or write
+    them during the call; the rows are only complete once it returns.
+
+    Optionally, `options` is an `RtmOptions`, as for `compute_rtm`, except that
+    `quality_flags`, `effective_temperature`, `air_mass_factor`, `down_level`
+    and `profile_timing` aren't supported, since they have no array to write
+    to. The progress is logged after each chunk, and if any point fails, a
+    `ValueError` is raised, with the rows of the previous chunks written. The
+    `Diagnostics` of the run are returned.
+    """
+
+def compute_rtm_to_zarr(
+    store_path: Union[str, os.PathLike[str]],
+    pressure: NDArray[_Float],
+    temperature: NDArray[_Float],
+    height: NDArray[_Float],
+    specific_humidity: NDArray[_Float],
+    liquid_content: Optional[NDArray[_Float]],
+    surface_temperature: NDArray[_Float],
+    surface_height: NDArray[_Float],
+    surface_dewpoint: NDArray[_Float],
+    surface_pressure: Optional[NDArray[_Float]],
+    parameters: RtmParameters,
+    num_threads: Optional[int] = None,
+    chunk_points: int = 100_000,
+    options: Optional[RtmOptions] = None,
+) -> Diagnostics:
+    """Compute the RTM and write the outputs to a Zarr store a chunk of points
+    at a time, so that runs with more points than fit in memory can be analyzed
+    with xarray and dask.
+
+    `store_path` is the directory of the store, which is created if needed,
+    and the profile arguments and `parameters` are the same as for
+    `compute_rtm_block`. The points are run `chunk_points` at a time, in
+    order, and each chunk is written as soon as it's done, so only one chunk
+    of the outputs is in memory at once.
+
+    The store is a Zarr (format 2) group with the arrays `tran`, `tb_up`, and
+    `tb_down`, with shape (`num_points`, `num_freq`) and chunks of
+    (`chunk_points`, `num_freq`), `num_levels_integrated` and, if requested,
+    `quality_flags`, with shape (`num_points`, ), and the coordinates
+    `frequency` and `incidence_angle` of the channels. The dimensions are
+    named `point` and `channel` for xarray, and the arrays have `units`
+    attributes. The attributes of the group are the model info from
+    `get_model_info`, and the `emission` and `tb_convention`. The metadata is
+    consolidated, so `xarray.open_zarr(store_path)` opens it directly.
+
+    Optionally, `options` is an `RtmOptions`, as for `compute_rtm`, except that
+    `effective_temperature`, `air_mass_factor`, `down_level` and
+    `profile_timing` aren't supported. The progress is logged after each
+    chunk, and if any point fails, a `ValueError` is raised, leaving the
+    chunks before it in the store. The `Diagnostics` of the run are returned.
+    """
+
+def compute_rtm_from_absorption(
+    temperature: NDArray[np.float32],
+    height: NDArray[np.float32],
+    absorption: NDArray[np.float32],
+    incidence_angle: NDArray[np.float32],
+    num_threads: Optional[int],
+) -> AtmoParameters:
+    """Compute the radiative transfer model with the absorption given externally.
+
+    This skips the absorption models in this package and only does the
+    radiative transfer integration, for instance to use absorption from a
+    line-by-line model. The levels start at the surface and go upward, so any
+    levels below the surface need to be removed beforehand. At least two levels
+    are needed.
+
+    `temperature`: physical temperature in K, with shape (`num_points`,
+    `num_levels`)
+
+    `height`: geometric height in m, with shape (`num_points`, `num_levels`)
+
+    `absorption`: absorption coefficient in Np/m, with shape (`num_points`,
+    `num_freq`, `num_levels`)
+
+    `incidence_angle`: Earth incidence angle in degrees, with shape
+    (`num_freq`, )
+
+    The number of worker threads is controlled by `num_threads`, as for
+    `compute_rtm`. The emission is Rayleigh-Jeans.
+    """
+
+def compute_contribution_functions(
+    pressure: NDArray[np.float32],
+    temperature: NDArray[np.float32],
+    height: NDArray[np.float32],
+    specific_humidity: NDArray[np.float32],
+    liquid_content: Optional[NDArray[np.float32]],
+    surface_temperature: NDArray[np.float32],
+    surface_height: NDArray[np.float32],
+    surface_dewpoint: NDArray[np.float32],
+    surface_pressure: Optional[NDArray[np.float32]],
+    incidence_angle: NDArray[np.float32],
+    frequency: NDArray[np.float32],
+    num_threads: Optional[int],
+    top_pressure: Optional[float] = None,
+) -> NDArray[np.float32]:
+    """Compute the contribution of each layer to the upwelling TB.
+
+    The inputs are the same as for `compute_rtm`. This is intended for a
+    modest number of selected points, since the output is dimensioned as
+    (`num_points`, `num_freq`, `num_levels`).
+
+    The value at level index `k` is the contribution, in K, of the layer whose
+    top is at pressure level `k`: the layer emission times its transmissivity
+    to space. Layers below the surface, or above `top_pressure`, have no
+    contribution. Summing over the levels reproduces the `tb_up` output of
+    `compute_rtm` with the default `emission` and `integration_scheme` and no
+    `observer_height`.
+    """
+
+def validate_rtm(
+    pressure: NDArray[np.float32],
+    temperature: NDArray[np.float32],
+    height: NDArray[np.float32],
+    specific_humidity: NDArray[np.float32],
+    liquid_content: Optional[NDArray[np.float32]],
+    surface_temperature: NDArray[np.float32],
+    surface_height: NDArray[np.float32],
+    surface_dewpoint: NDArray[np.float32],
+    surface_pressure: Optional[NDArray[np.float32]],
+    incidence_angle: NDArray[np.float32],
+    frequency: NDArray[np.float32],
+    num_threads: Optional[int],
+    top_pressure: Optional[float] = None,
+) -> NDArray[np.bool_]:
+    """Check whether the RTM gives valid outputs for each point, for a fast
+    quality control pass over a dataset.
+
+    The inputs are the same as for `compute_rtm`. The full RTM is run for
+    each point, but the outputs aren't kept, so this needs much less memory.
+    A point is valid if each `tran` is finite and from 0 to 1, and each
+    `tb_up` and `tb_down` is finite and not negative. Points that fail, for
+    instance with no profile levels above the surface, are not valid, rather
+    than raising an error.
+
+    Returns a boolean array with shape (`num_points`, ).
+    """
+
+def compute_column_stats(
+    pressure: NDArray[np.float32],
+    temperature: NDArray[np.float32],
+    height: NDArray[np.float32],
+    specific_humidity: NDArray[np.float32],
+    liquid_content: Optional[NDArray[np.float32]],
+    surface_temperature: NDArray[np.float32],
+    surface_height: NDArray[np.float32],
+    surface_dewpoint: NDArray[np.float32],
+    surface_pressure: Optional[NDArray[np.float32]],
+    num_threads: Optional[int],
+    top_pressure: Optional[float] = None,
+) -> dict[str, NDArray[np.float32]]:
+    """Compute summary statistics of the atmospheric column for each point.
+
+    The profile inputs are the same as for `compute_rtm`. The statistics are
+    computed from the same profiles that the RTM uses, after the surface values
+    are inserted and the levels below the surface (or above `top_pressure`) are
+    removed, so they are consistent with it.
+
+    The returned dict has the following arrays, each with shape
+    (`num_points`, ):
+
+    - `pressure_weighted_temperature`: mean temperature in K, weighted by the
+      pressure thickness of each layer
+    - `vapor_weighted_temperature`: mean temperature in K, weighted by the
+      water vapor mass in each layer
+    - `water_vapor`: total column water vapor in kg/m²
+    - `liquid_water`: total column cloud liquid water in kg/m²
+    """
+
+def compute_optical_depths(
+    pressure: NDArray[np.float32],
+    temperature: NDArray[np.float32],
+    height: NDArray[np.float32],
+    specific_humidity: NDArray[np.float32],
+    liquid_content: Optional[NDArray[np.float32]],
+    surface_temperature: NDArray[np.float32],
+    surface_height: NDArray[np.float32],
+    surface_dewpoint: NDArray[np.float32],
+    surface_pressure: Optional[NDArray[np.float32]],
+    frequency: NDArray[np.float32],
+    num_threads: Optional[int],
+    top_pressure: Optional[float] = None,
+    options: Optional[RtmOptions] = None,
+) -> dict[str, NDArray[np.float32]]:
+    """Compute the zenith optical depth of each absorber for each point and
+    channel.
+
+    The profile inputs are the same as for `compute_rtm`. The optical depths
+    are integrated straight up from the surface to the top of the profile, so
+    they don't depend on the incidence angle, and they sum to the total zenith
+    optical depth.
+
+    The returned dict has the following arrays, each with shape
+    (`num_points`, `num_freq`):
+
+    - `oxygen`: optical depth of oxygen in Np
+    - `water_vapor`: optical depth of water vapor in Np
+    - `cloud`: optical depth of cloud liquid water in Np
+
+    Optionally, `options` is an `RtmOptions`, of which only the absorption
+    scale factors are used, so the optical depths are consistent with
+    `compute_rtm` with the same options.
+    """
+
+def compute_rtm_sst_jacobian(
+    pressure: NDArray[np.float32],
+    temperature: NDArray[np.float32],
+    height: NDArray[np.float32],
+    specific_humidity: NDArray[np.float32],
+    liquid_content: Optional[NDArray[np.float32]],
+    surface_temperature: NDArray[np.float32],
+    surface_height: NDArray[np.float32],
+    surface_dewpoint: NDArray[np.float32],
+    surface_pressure: Optional[NDArray[np.float32]],
+    incidence_angle: NDArray[np.float32],
+    frequency: NDArray[np.float32],
+    num_threads: Optional[int],
+    top_pressure: Optional[float] = None,
+) -> dict[str, NDArray[np.float32]]:
+    """Compute the derivatives of the RTM outputs with respect to the surface
+    temperature.
+
+    The inputs are the same as for `compute_rtm`. The surface temperature (the
+    2 m air temperature) is the lowest level of the profile, so it affects the
+    emission and absorption of the lowest layer. The derivatives through the
+    radiative transfer are analytic, while the derivative of the absorption at
+    the surface is from a central difference of the absorption models.
+
+    The returned dict has the following arrays, each with shape
+    (`num_points`, `num_freq`):
+
+    - `tran`: derivative of the atmospheric transmissivity, in 1/K
+    - `tb_up`: derivative of the atmospheric upwelling brightness temperature
+    - `tb_down`: derivative of the atmospheric downwelling brightness
+      temperature
+    """
+
+def compute_rtm_uniform_temp_sensitivity(
+    pressure: NDArray[np.float32],
+    temperature: NDArray[np.float32],
+    height: NDArray[np.float32],
+    specific_humidity: NDArray[np.float32],
+    liquid_content: Optional[NDArray[np.float32]],
+    surface_temperature: NDArray[np.float32],
+    surface_height: NDArray[np.float32],
+    surface_dewpoint: NDArray[np.float32],
+    surface_pressure: Optional[NDArray[np.float32]],
+    incidence_angle: NDArray[np.float32],
+    frequency: NDArray[np.float32],
+    num_threads: Optional[int],
+    top_pressure: Optional[float] = None,
+    delta: float = 0.1,
+) -> NDArray[np.float32]:
+    """Compute the derivative of the atmospheric upwelling brightness
+    temperature with respect to a uniform shift of the temperature profile.
+
+    The inputs are the same as for `compute_rtm_sst_jacobian`. The temperature
+    of every level, including the surface, is shifted by `delta` K each way,
+    and the RTM is rerun for a central difference, so this is a cheap bulk
+    temperature response rather than a per-level Jacobian. The vapor pressure
+    is held fixed, and the liquid water density varies inversely with the
+    temperature.
+
+    The returned array has shape (`num_points`, `num_freq`), in K/K. A
+    `ValueError` is raised if `delta` is not positive and finite.
+    """
+
+def compute_rtm_footprints(
+    pressure: NDArray[np.float32],
+    temperature: NDArray[np.float32],
+    height: NDArray[np.float32],
+    specific_humidity: NDArray[np.float32],
+    liquid_content: Optional[NDArray[np.float32]],
+    surface_temperature: NDArray[np.float32],
+    surface_height: NDArray[np.float32],
+    surface_dewpoint: NDArray[np.float32],
+    surface_pressure: Optional[NDArray[np.float32]],
+    incidence_angle: NDArray[np.float32],
+    frequency: NDArray[np.float32],
+    member_index: NDArray[np.int64],
+    member_weight: NDArray[np.float32],
+    num_threads: Optional[int],
+    top_pressure: Optional[float] = None,
+) -> dict[str, NDArray[Any]]:
+    """Compute the RTM outputs averaged over the points in each sensor
+    footprint.
+
+    The profile and channel inputs are the same as for `compute_rtm`, with one
+    point for each member of all of the footprints, for example the profiles of
+    a finer model grid. The RTM is run for each point, and the outputs of the
+    members of each footprint are averaged, weighted by the antenna pattern.
+
+    `member_index` is an integer array with shape (`num_footprints`,
+    `max_members`), with the index of each member point, and `member_weight`
+    has the same shape with its weight. The weights are normalized for each
+    footprint, so they only need to be relative. Footprints with fewer members
+    are padded with a negative index or a weight of zero or NaN. A footprint
+    with no valid members has outputs of NaN. A `ValueError` is raised if an
+    index is out of range or a weight is negative or infinite.
+
+    The returned dict has the following arrays, each with shape
+    (`num_footprints`, `num_freq`) unless noted:
+
+    - `tran`: mean atmospheric transmissivity
+    - `tb_up`: mean atmospheric upwelling brightness temperature in K
+    - `tb_down`: mean atmospheric downwelling brightness temperature in K
+    - `quality_flags`: combined quality flags of the members, with shape
+      (`num_footprints`, )
+    """
+
+def compute_tb_toa(
+    pressure: NDArray[np.float32],
+    temperature: NDArray[np.float32],
+    height: NDArray[np.float32],
+    specific_humidity: NDArray[np.float32],
+    liquid_content: Optional[NDArray[np.float32]],
+    surface_temperature: NDArray[np.float32],
+    surface_height: NDArray[np.float32],
+    surface_dewpoint: NDArray[np.float32],
+    surface_pressure: Optional[NDArray[np.float32]],
+    incidence_angle: NDArray[np.float32],
+    frequency: NDArray[np.float32],
+    polarization: Sequence[str],
+    num_threads: Optional[int],
+    top_pressure: Optional[float] = None,
+    wind_speed: Optional[NDArray[np.float32]] = None,
+    surface_type: Optional[NDArray[np.int64]] = None,
+    land_emissivity: Optional[NDArray[np.float32]] = None,
+    ice_emissivity: Optional[NDArray[np.float32]] = None,
+    sea_ice_concentration: Optional[NDArray[np.float32]] = None,
+    polarization_angle: Optional[NDArray[np.float32]] = None,
+    reflection: str = "specular",
+    quadrature_order: int = 8,
+    skin_temperature: Optional[NDArray[np.float32]] = None,
+    salinity: Optional[NDArray[np.float32]] = None,
+    jacobians: bool = False,
+    noise_nedt: Optional[NDArray[np.float32]] = None,
+    noise_seed: Optional[int] = None,
+    return_unnoised: bool = False,
+    ocean_model: Literal["rough", "specular_foam"] = "rough",
+) -> dict[str, NDArray[np.float32]]:
+    """Compute the brightness temperature at the top of the atmosphere.
+
+    The profile and channel inputs are the same as for `compute_rtm`, and
+    `polarization` is a list with the polarization of each channel at the
+    surface, either `"v"` or `"h"`. The atmosphere is computed as in
+    `compute_rtm` and combined with the emission of the surface and its
+    reflection of the downwelling and the cosmic background. The brightness
+    temperatures are in the Rayleigh-Jeans convention.
+
+    The surface emits at `skin_temperature`, the radiometric skin temperature
+    in K with shape (`num_points`, ), if it's given, and otherwise at
+    `surface_temperature`. As in `compute_rtm`, the atmosphere always uses
+    `surface_temperature`, the 2 meter air temperature, and not the skin
+    temperature. The ocean emissivity is also computed at the skin temperature.
+
+    The surface is ocean unless `surface_type`, an integer array with shape
+    (`num_points`, ), is given, with the type of each point: 0 for ocean, 1 for
+    land, or 2 for sea ice. The ocean has a salinity in ppt of `salinity`, with
+    shape (`num_points`, ), or 35 ppt if it's not given, and its emissivity is
+    as in `ocean_emissivity`: for a flat sea, or for a
+    wind-roughened sea if `wind_speed`, the 10 m wind speed in m/s with shape
+    (`num_points`, ), is given, with the `ocean_model` of the wind-roughened
+    sea. The emissivities of land and sea ice are given
+    by `land_emissivity` and `ice_emissivity`, each with shape (`num_points`,
+    `num_freq`), for example from an atlas, and are only needed if there are
+    points of that type. The ocean points may be partly covered by sea ice, with
+    the fraction given by `sea_ice_concentration` with shape (`num_points`, ),
+    in which case the emissivity is blended linearly between the open water and
+    `ice_emissivity`.
+
+    The surface inputs are all checked before any point is computed: the
+    emissivities and the sea ice concentration must be from 0 to 1 and the
+    salinity can't be negative, and a `ValueError` with the index of the first
+    bad point is raised otherwise.
+
+    The surface reflects the downwelling specularly by default. With
+    `reflection="lambertian"`, it instead reflects the average of the
+    downwelling over the hemisphere, which is more realistic for rough land.
+    The average is by Gaussian quadrature over `quadrature_order` zenith
+    angles, each of which adds another integration of the radiative transfer.
+    The reflection model applies to every point, including the ocean points.
+
+    The feedhorn of a cross-track sounder, such as MSU, measures a mix of V-
+    and H-pol at the surface that rotates with the scan position. If
+    `polarization_angle`, the rotation in degrees of each point's polarization
+    relative to the plane of incidence with shape (`num_points`, ), is given,
+    then `tb_toa` is `cos²φ tb_toa_v + sin²φ tb_toa_h` for the `"v"` channels
+    and `cos²φ tb_toa_h + sin²φ tb_toa_v` for the `"h"` channels. For a
+    cross-track scanner, this angle is the scan angle from nadir.
+
+    The returned dict has the following arrays, each with shape (`num_points`,
+    `num_freq`):
+
+    - `tb_toa`: brightness temperature at the top of the atmosphere, in K
+    - `tb_toa_v`, `tb_toa_h`: brightness temperature at the top of the
+      atmosphere for V- and H-pol at the surface, in K
+    - `emissivity`: surface emissivity of the channel's polarization, mixed as
+      for `tb_toa`
+    - `tran`, `tb_up`, `tb_down`: the atmospheric outputs, as from
+      `compute_rtm`
+
+    If `jacobians` is true, the dict also has the derivatives of `tb_toa`, from
+    the same quantities used to compute it:
+
+    - `dtb_demis`: with respect to `emissivity`, `tran (T_s - sky)`, where
+      `T_s` is the surface temperature and `sky` is the reflected sky
+      brightness temperature
+    - `dtb_dtskin`: with respect to the surface temperature, `tran
+      emissivity`, with the emissivity held fixed (for the ocean, this leaves
+      out the change of the emissivity with the SST)
+
+    Optionally, `noise_nedt` is the NEΔT in K of each channel, with shape
+    (`num_freq`, ), for simulated observations. Independent Gaussian noise with
+    that standard deviation is added to `tb_toa` for each point and channel. The
+    noise is from a random number generator seeded with `noise_seed`, so the same
+    seed gives the same noise, or a random seed if it's `None`. If
+    `return_unnoised` is true, the dict also has `tb_toa` before the noise was
+    added, as `tb_toa_unnoised`.
+    """
+
+def compute_tb_toa_polarized(
+    pressure: NDArray[np.float32],
+    temperature: NDArray[np.float32],
+    height: NDArray[np.float32],
+    specific_humidity: NDArray[np.float32],
+    liquid_content: Optional[NDArray[np.float32]],
+    surface_temperature: NDArray[np.float32],
+    surface_height: NDArray[np.float32],
+    surface_dewpoint: NDArray[np.float32],
+    surface_pressure: Optional[NDArray[np.float32]],
+    incidence_angle: NDArray[np.float32],
+    frequency: NDArray[np.float32],
+    num_threads: Optional[int],
+    top_pressure: Optional[float] = None,
+    wind_speed: Optional[NDArray[np.float32]] = None,
+    surface_type: Optional[NDArray[np.int64]] = None,
+    land_emissivity: Optional[NDArray[np.float32]] = None,
+    ice_emissivity: Optional[NDArray[np.float32]] = None,
+    sea_ice_concentration: Optional[NDArray[np.float32]] = None,
+    reflection: str = "specular",
+    quadrature_order: int = 8,
+    skin_temperature: Optional[NDArray[np.float32]] = None,
+    salinity: Optional[NDArray[np.float32]] = None,
+    ocean_model: Literal["rough", "specular_foam"] = "rough",
+) -> NDArray[np.float32]:
+    """Compute the brightness temperature at the top of the atmosphere for both
+    V- and H-pol at the surface.
+
+    The inputs are the same as for `compute_tb_toa`, except that there's no
+    `polarization` or `polarization_angle`, since both polarizations are
+    returned. The atmosphere is computed once for each point and combined with
+    the V- and H-pol emissivity of the surface (from the Fresnel equations for
+    the ocean) and its reflection of the downwelling.
+
+    Returns the brightness temperatures in K with shape (`num_points`,
+    `num_freq`, 2), where the last axis is the polarization at the surface: V
+    at index 0 and H at index 1. These are the same as `tb_toa_v` and
+    `tb_toa_h` from `compute_tb_toa`.
+    """
+
+def remove_atmosphere(
+    tb_observed: NDArray[_Float],
+    atmosphere: AtmoParameters,
+    emissivity: NDArray[_Float],
+) -> NDArray[_Float]:
+    """Remove the atmosphere from observed brightness temperatures at the top of
+    the atmosphere, for an atmospheric correction.
+
+    `tb_observed` is the observed brightness temperature in K with shape
+    (`num_points`, `num_freq`), and `atmosphere` is the output of
+    `compute_rtm` for the same points and channels. `emissivity` is the surface
+    emissivity, and is broadcast to (`num_points`, `num_freq`), so it can also
+    be given for each channel, with shape (`num_freq`, ).
+    All of the arrays must have the same dtype as the outputs in `atmosphere`.
+
+    Returns the brightness temperature in K emitted by the surface,
+    `emissivity * surface_temperature`, with shape (`num_points`, `num_freq`).
+    This is the inverse of the combination in `compute_tb_toa`:
+
+    `(tb_observed - tb_up) / tran - (1 - emissivity) * (tb_down + tran * 2.73)`
+
+    which removes the atmospheric upwelling and the surface's reflection of the
+    downwelling and the cosmic background, and corrects for the attenuation of
+    the atmosphere. It's undefined where the atmosphere is opaque (`tran` is
+    0). The brightness temperatures must be in the Rayleigh-Jeans convention
+    (the default `tb_convention` of `compute_rtm`), where they add linearly.
+    """
+
+def estimate_cost(
+    num_points: int,
+    num_levels: int,
+    num_freq: int,
+    num_threads: Optional[int] = None,
+) -> dict[str, Any]:
+    """Estimate the memory and run time of `compute_rtm` for a batch of
+    points with `num_levels` levels and `num_freq` channels, e.g., to choose
+    chunk sizes and node types before scheduling a job.
+
+    The time per point is calibrated by running the US Standard Atmosphere
+    1976, resampled to `num_levels` levels, 100 times on one thread, with
+    `num_freq` distinct frequencies. The wall time is extrapolated to
+    `num_points` points on `num_threads` threads (or one per logical CPU if
+    `None`), assuming perfect scaling. Use `estimate_cost_from_inputs` to
+    calibrate with the actual profiles instead.
+
+    Returns a dict with the estimates, `output_bytes` (for `tran`, `tb_up`,
+    and `tb_down` in float32), `peak_working_bytes` (the per-point outputs
+    held before they're copied, and the working profiles of each thread), and
+    `wall_seconds`, along with the values they're based on, `num_points`,
+    `seconds_per_point`, `num_calibration_points`, `num_threads`, and a
+    description of the `assumptions`.
+    """
+
+def estimate_cost_from_inputs(
+    pressure: NDArray[np.float32],
+    temperature: NDArray[np.float32],
+    height: NDArray[np.float32],
+    specific_humidity: NDArray[np.float32],
+    liquid_content: Optional[NDArray[np.float32]],
+    surface_temperature: NDArray[np.float32],
+    surface_height: NDArray[np.float32],
+    surface_dewpoint: NDArray[np.float32],
+    surface_pressure: Optional[NDArray[np.float32]],
+    incidence_angle: NDArray[np.float32],
+    frequency: NDArray[np.float32],
+    num_threads: Optional[int] = None,
+    top_pressure: Optional[float] = None,
+) -> dict[str, Any]:
+    """Estimate the memory and run time of `compute_rtm` for the actual
+    inputs.
+
+    The arguments are the same as for `compute_rtm`. This is the same as
+    `estimate_cost`, but the time per point is calibrated by running up to
+    100 evenly spaced points of the inputs on one thread, so it includes the
+    effect of clouds and of the levels below the surface, and the returned
+    dict is the same.
+    """
+
+def find_opacity_extremum(
+    pressure: NDArray[np.float32],
+    temperature: NDArray[np.float32],
+    height: NDArray[np.float32],
+    specific_humidity: NDArray[np.float32],
+    liquid_content: Optional[NDArray[np.float32]],
+    surface_temperature: NDArray[np.float32],
+    surface_height: NDArray[np.float32],
+    surface_dewpoint: NDArray[np.float32],
+    surface_pressure: Optional[NDArray[np.float32]],
+    point_index: int,
+    freq_min: float,
+    freq_max: float,
+    maximize: bool = True,
+    top_pressure: Optional[float] = None,
+) -> tuple[float, float]:
+    """Find the frequency in a band where the zenith optical depth of a
+    single point is largest or smallest, for placing a channel.
+
+    The profile arguments are the same as for `compute_rtm`, for all of the
+    points, and `point_index` selects the point. The band from `freq_min` to
+    `freq_max` in GHz is swept in 200 steps, and the best frequency is then
+    refined with a golden-section search, so the band should be narrow enough
+    for a step to resolve the lines of interest. If `maximize` is true, the
+    largest optical depth is found, and otherwise the smallest.
+
+    Returns a tuple of the frequency in GHz and the zenith optical depth at
+    it.
+    """
+
+def prepare_point(
+    pressure: NDArray[np.float32],
+    temperature: NDArray[np.float32],
+    height: NDArray[np.float32],
+    specific_humidity: NDArray[np.float32],
+    liquid_content: Optional[NDArray[np.float32]],
+    surface_temperature: NDArray[np.float32],
+    surface_height: NDArray[np.float32],
+    surface_dewpoint: NDArray[np.float32],
+    surface_pressure: Optional[NDArray[np.float32]],
+    point_index: int,
+    top_pressure: Optional[float] = None,
+    skin_temperature: Optional[NDArray[np.float32]] = None,
+    observer_height: Optional[NDArray[np.float32]] = None,
+) -> str:
+    """Prepare the RTM inputs for a single point and serialize them to JSON.
+
+    The arguments are the same as for `compute_rtm`, for all of the points, and
+    `point_index` selects the point to prepare. The JSON has the inputs after
+    the surface is inserted into the profile, exactly as the RTM sees them.
+    With the `serde` feature of the Rust crate, it can be deserialized to an
+    `RtmInputs` to reproduce the point, e.g., in a unit test.
+    """
+
+def standard_atmosphere(
+    name: str,
+    num_points: int = 1,
+) -> dict[str, NDArray[np.float32]]:
+    """Get a standard atmosphere as arrays for `compute_rtm`.
+
+    The `name` is one of `"us_standard_1976"`, `"tropical"`,
+    `"mid_latitude_summer"`, `"mid_latitude_winter"`, `"subarctic_summer"`, or
+    `"subarctic_winter"`. The profile is on 50 levels from 0.25 km to 80 km
+    above the surface, and it has no clouds. See the Rust documentation of the
+    `standard_atmospheres` module for how the profiles are defined.
+
+    The returned dict has the profile arguments of `compute_rtm`, all float32:
+    `pressure` with shape (`num_levels`, ), the profiles with shape
+    (`num_points`, `num_levels`), and the surface values with shape
+    (`num_points`, ). Each point is the same atmosphere.
+    """
+
+def isothermal_atmosphere(
+    temperature: float,
+    num_points: int = 1,
+) -> dict[str, NDArray[np.float32]]:
+    """Get an isothermal atmosphere at `temperature` in K as arrays for
+    `compute_rtm`.
+
+    The profile is on the same levels as `standard_atmosphere`, with the
+    surface pressure and water vapor of `"us_standard_1976"` (with the
+    relative humidity at most 80%). For any absorption, both `tb_up` and
+    `tb_down` of the atmosphere are `compute_isothermal_tb(temperature, tran)`,
+    which makes it an analytic check of the RTM.
+
+    The returned dict is the same as for `standard_atmosphere`.
+    """
+
+def compute_isothermal_tb(
+    t: NDArray[np.float32],
+    tran: NDArray[np.float32],
+) -> NDArray[np.float32]:
+    """The brightness temperature in K of an isothermal atmosphere at
+    temperature `t` in K with a transmissivity of `tran`, which is
+    `t * (1 - tran)`.
+
+    This is both `tb_up` and `tb_down` from `compute_rtm` for an
+    `isothermal_atmosphere`, in the default Rayleigh-Jeans convention. The
+    inputs are broadcast against each other.
+    """
+
+def read_rttov_profiles(
+    path: Union[str, os.PathLike[str]],
+    num_levels: int,
+) -> dict[str, NDArray[np.float32]]:
+    """Read the profiles from an RTTOV profile file as arrays for `compute_rtm`.
+
+    The file is in the plain-text format of the profiles for RTTOV's example
+    programs (such as `prof.dat`), and `num_levels` is the number of levels of
+    each profile, which isn't in the file. See the Rust documentation of the
+    `rttov` module for the format and how it's converted.
+
+    The returned dict has the profile arguments of `compute_rtm`, all float32:
+    `pressure` with shape (`num_levels`, ) if all of the profiles are on the
+    same pressure levels, or otherwise (`num_points`, `num_levels`), the
+    profiles with shape (`num_points`, `num_levels`), and the surface values
+    (including `skin_temperature`) with shape (`num_points`, ). The satellite
+    zenith angles are not included.
+    """
+
+def load_sensor(path: Union[str, os.PathLike[str]]) -> dict[str, Any]:
+    """Load a sensor definition file, with the channels and scan positions of a
+    sensor in TOML. See the Rust documentation of the `sensor_file` module for
+    the format; examples for MSU and AMSU-A are in the `data` directory of the
+    source.
+
+    The returned dict has the `name` of the sensor, the center `frequency` in
+    GHz and `polarization` (`"v"` or `"h"`) of each channel, the
+    `incidence_angle` and `scan_angle` (or `None`) in degrees of each scan
+    position, and `channels`, a list with the passband of each channel as a
+    `Channel`. The channels are numbered from 1 in the file, so channel `n` is
+    at index `n - 1`. Use `RtmParameters.from_channels` with the channels and
+    the incidence angle of a scan position to compute the RTM for them.
+    """
+
+def compare_outputs(
+    a: AtmoParameters,
+    b: AtmoParameters,
+    tol: Union[float, dict[str, float]],
+) -> dict[str, dict[str, NDArray[Any]]]:
+    """Compare two sets of RTM outputs, such as from `compute_rtm` with different
+    settings, or from this RTM and another model on the same profiles.
+
+    `a` and `b` are `AtmoParameters` with the same shape and dtype. For each of
+    `tran`, `tb_up`, and `tb_down`, the returned dict has a dict of float64
+    arrays with shape (`num_freq`, ), over the points of each channel:
+
+    - `max`: the maximum absolute difference
+    - `rms`: the root-mean-square difference
+    - `bias`: the mean difference, `a - b`
+    - `within_tolerance` (bool): whether `max` is at most `tol`
+
+    The tolerance `tol` is either a float for all three outputs, or a dict with
+    a float for each of `"tran"`, `"tb_up"`, and `"tb_down"`. NaN in either
+    input makes the statistics of that channel NaN.
+    """
+
+def planck_radiance(
+    frequency: float,
+    temperature: NDArray[np.float32],
+) -> NDArray[np.float32]:
+    """Compute the Planck spectral radiance in mW/m²/sr/cm⁻¹ for a microwave
+    `frequency` in GHz and an array of physical `temperature` in K.
+    """
+
+def planck_brightness_temperature(
+    frequency: float,
+    radiance: NDArray[np.float32],
+) -> NDArray[np.float32]:
+    """Compute the Planck-equivalent brightness temperature in K for a
+    microwave `frequency` in GHz and an array of spectral `radiance` in
+    mW/m²/sr/cm⁻¹.
+
+    This is the inverse of `planck_radiance`.
+    """
+
+def ocean_emissivity(
+    frequency: NDArray[np.float32],
+    incidence_angle: NDArray[np.float32],
+    sst: NDArray[np.float32],
+    salinity: NDArray[np.float32],
+    wind_speed: Optional[NDArray[np.float32]] = None,
+    ocean_model: Literal["rough", "specular_foam"] = "rough",
+) -> tuple[NDArray[np.float32], NDArray[np.float32]]:
+    """Compute the emissivity of the sea surface for each point and channel.
+
+    The channels are given by `frequency` in GHz and Earth `incidence_angle` in
+    degrees, as in `compute_rtm`, and the points by `sst` in K and `salinity` in
+    parts per thousand, with shape (`num_points`, ). Returns the tuple
+    `(emis_v, emis_h)` of the vertical and horizontal polarization
+    emissivities, each with shape (`num_points`, `num_freq`).
+
+    The sea water dielectric constant is from Meissner and Wentz, and is fit
+    for SSTs from 271.16 K to 307.16 K and salinities from 0 to 40 ppt. The sea
+    is flat unless `wind_speed`, the 10 m wind speed in m/s with shape
+    (`num_points`, ), is given. With the default `ocean_model="rough"`, the
+    wind adds large-scale roughness, from the Cox and Munk slope distribution,
+    and foam, from the Monahan and O'Muircheartaigh whitecap coverage. With
+    `ocean_model="specular_foam"`, the sea stays flat and the wind only adds
+    the foam. The foam covers a fraction `3.84e-6 * wind_speed**3.41` of the
+    surface and is taken to be a blackbody, so the emissivity is `e + f (1 -
+    e)` for the emissivity `e` of the open water and foam fraction `f`. This is
+    a physical model rather than a fit to observations like the Meissner and
+    Wentz (2012) model, so it should be expected to be off by a few hundredths
+    in emissivity at high wind speeds.
+    It's meant for wind speeds from 0 to 40 m/s, but is extrapolated above
+    about 20 m/s, and the sea is all foam above 39 m/s. A wind speed of 0 is
+    the same as a flat sea.
+    """
+
+@overload
+def cloud_absorption_spectrum(
+    frequency: NDArray[np.float32],
+    temperature: float,
+    liquid_water_density: float,
+    derivative: Literal[False] = False,
+) -> NDArray[np.float32]:
+    """Compute the absorption coefficient of cloud liquid water for each
+    frequency.
+
+    For `frequency` in GHz with shape (`num_freq`, ), a cloud `temperature` in
+    K, and a liquid water density `liquid_water_density` in g/m³, returns the
+    absorption coefficient in Np/km with shape (`num_freq`, ). If `derivative`
+    is true, returns the tuple `(absorption, d_absorption)` instead, where
+    `d_absorption` is the derivative of the absorption with respect to the
+    liquid water density, in (Np/km)/(g/m³).
+
+    This is the same cloud absorption as in `compute_rtm`: the droplets are in
+    the Rayleigh limit, so the absorption is proportional to the liquid water
+    density, and the derivative (the mass absorption coefficient) only depends
+    on the frequency and temperature. The dielectric constant of water is from
+    Meissner and Wentz, and is fit from 1 to 400 GHz and from 248.16 K to
+    313.16 K.
+    """
+
+@overload
+def cloud_absorption_spectrum(
+    frequency: NDArray[np.float32],
+    temperature: float,
+    liquid_water_density: float,
+    derivative: Literal[True],
+) -> tuple[NDArray[np.float32], NDArray[np.float32]]: ...
+
+def meissner_grid(
+    frequency: NDArray[np.float32],
+    temperature: NDArray[np.float32],
+    salinity: float = 0.0,
+) -> tuple[NDArray[np.float32], NDArray[np.float32]]:
+    """Compute the complex dielectric constant of water over a grid of
+    frequencies and temperatures, e.g., to build emissivity lookup tables.
+
+    For `frequency` in GHz with shape (`num_freq`, ), `temperature` in K with
+    shape (`num_temp`, ), and a `salinity` in parts per thousand (0 for pure
+    water), returns the tuple `(real, imag)` of the real and imaginary parts,
+    each with shape (`num_freq`, `num_temp`). The imaginary part is negative,
+    as in the Wentz convention.
+
+    This is the Meissner and Wentz dielectric constant used for cloud liquid
+    water and the ocean surface. It is fit from 1 to 400 GHz, from 248.16 K to
+    313.16 K for pure water, and from 271.16 K to 307.16 K and 0 to 40 ppt for
+    sea water.
+    """
+
+def eia_from_scan(
+    scan_angle: NDArray[np.float32],
+    satellite_altitude: float,
+    earth_radius: float = 6371e3,
+) -> NDArray[np.float32]:
+    """Convert the scan angle from nadir `scan_angle` in degrees, an array of any
+    shape, of a satellite at a height `satellite_altitude` in m to the Earth
+    incidence angle in degrees, for a spherical Earth with a radius
+    `earth_radius` in m.
+
+    The default radius is the one used elsewhere in the RTM. From the law of
+    sines, `sin(eia) = (earth_radius + satellite_altitude) / earth_radius *
+    sin(scan_angle)`. A scan angle past the limb, where the line of sight misses
+    the Earth, gives NaN.
+    """
+
+def scan_from_eia(
+    incidence_angle: NDArray[np.float32],
+    satellite_altitude: float,
+    earth_radius: float = 6371e3,
+) -> NDArray[np.float32]:
+    """Convert the Earth incidence angle `incidence_angle` in degrees, an array of
+    any shape, to the scan angle from nadir in degrees of a satellite at a
+    height `satellite_altitude` in m, for a spherical Earth with a radius
+    `earth_radius` in m. This is the inverse of `eia_from_scan`.
+    """
+
+def scan_incidence_angle(
+    sensor: str, scan_position: NDArray[np.int64]
+) -> NDArray[np.float32]:
+    """Look up the Earth incidence angle in degrees of each scan position in
+    `scan_position`, an integer array of any shape, for the cross-track sounder
+    `sensor` (`"msu"` or `"amsu_a"`).
+
+    The scan positions are numbered from 0, from one edge of the swath to the
+    other. A `ValueError` is raised for a position that the sensor doesn't have.
+    """
+
+def specific_humidity_to_vapor_pressure(
+    q: NDArray[np.float32],
+    p: NDArray[np.float32],
+) -> NDArray[np.float32]:
+    """Convert specific humidity `q` in kg/kg to water vapor pressure in hPa,
+    at a total pressure `p` in hPa.
+
+    The inputs are broadcast against each other.
+    """
+
+def vapor_pressure_to_specific_humidity(
+    pv: NDArray[np.float32],
+    p: NDArray[np.float32],
+) -> NDArray[np.float32]:
+    """Convert water vapor pressure `pv` in hPa to specific humidity in kg/kg,
+    at a total pressure `p` in hPa.
+
+    The inputs are broadcast against each other.
+    """
+
+def mixing_ratio_to_vapor_pressure(
+    w: NDArray[np.float32],
+    p: NDArray[np.float32],
+) -> NDArray[np.float32]:
+    """Convert the mass mixing ratio `w` in kg/kg to water vapor pressure in
+    hPa, at a total pressure `p` in hPa.
+
+    The inputs are broadcast against each other.
+    """
+
+def vapor_pressure_to_mixing_ratio(
+    pv: NDArray[np.float32],
+    p: NDArray[np.float32],
+) -> NDArray[np.float32]:
+    """Convert water vapor pressure `pv` in hPa to the mass mixing ratio in
+    kg/kg, at a total pressure `p` in hPa.
+
+    The inputs are broadcast against each other.
+    """
+
+def specific_humidity_to_mixing_ratio(
+    q: NDArray[np.float32],
+) -> NDArray[np.float32]:
+    """Convert specific humidity `q` in kg/kg to the mass mixing ratio in
+    kg/kg.
+    """
+
+def mixing_ratio_to_specific_humidity(
+    w: NDArray[np.float32],
+) -> NDArray[np.float32]:
+    """Convert the mass mixing ratio `w` in kg/kg to specific humidity in
+    kg/kg.
+    """
+
+def dewpoint_to_vapor_pressure(
+    td: NDArray[np.float32],
+) -> NDArray[np.float32]:
+    """Convert the dewpoint `td` in K to water vapor pressure in hPa, using the
+    Buck equation.
+    """
+
+def relative_humidity_to_vapor_pressure(
+    rh: NDArray[np.float32],
+    t: NDArray[np.float32],
+) -> NDArray[np.float32]:
+    """Convert relative humidity `rh` (a fraction, over water) to water vapor
+    pressure in hPa, at an air temperature `t` in K.
+
+    The inputs are broadcast against each other.
+    """
+
+def vapor_pressure_to_relative_humidity(
+    pv: NDArray[np.float32],
+    t: NDArray[np.float32],
+) -> NDArray[np.float32]:
+    """Convert water vapor pressure `pv` in hPa to relative humidity (a
+    fraction, over water), at an air temperature `t` in K.
+
+    The inputs are broadcast against each other.
     """
@@ -0,0 +1,107 @@
+"""Humidity conversions, which are the same ones used to prepare the RTM inputs."""
+
+import numpy as np
+from numpy.typing import NDArray
+
+def buck_vap(
+    temp: NDArray[np.float32],
+) -> NDArray[np.float32]:
+    """The Buck equation: convert the temperature `temp` in K to the
+    saturation water vapor pressure (over water) in hPa.
+    """
+
+def buck_dewpoint(
+    vapor_pressure: NDArray[np.float32],
+) -> NDArray[np.float32]:
+    """The inverse of the Buck equation: convert the water vapor pressure
+    `vapor_pressure` in hPa to the temperature in K at which it is the
+    saturation pressure (the dewpoint). The vapor pressure must be positive.
+    """
+
+def specific_humidity_to_vapor_pressure(
+    q: NDArray[np.float32],
+    p: NDArray[np.float32],
+) -> NDArray[np.float32]:
+    """Convert specific humidity `q` in kg/kg to water vapor pressure in hPa,
+    at a total pressure `p` in hPa.
+
+    The inputs are broadcast against each other.
+    """
+
+def vapor_pressure_to_specific_humidity(
+    pv: NDArray[np.float32],
+    p: NDArray[np.float32],
+) -> NDArray[np.float32]:
+    """Convert water vapor pressure `pv` in hPa to specific humidity in kg/kg,
+    at a total pressure `p` in hPa.
+
+    The inputs are broadcast against each other.
+    """
+
+def mixing_ratio_to_vapor_pressure(
+    w: NDArray[np.float32],
+    p: NDArray[np.float32],
+) -> NDArray[np.float32]:
+    """Convert the mass mixing ratio `w` in kg/kg to water vapor pressure in
+    hPa, at a total pressure `p` in hPa.
+
+    The inputs are broadcast against each other.
+    """
+
+def vapor_pressure_to_mixing_ratio(
+    pv: NDArray[np.float32],
+    p: NDArray[np.float32],
+) -> NDArray[np.float32]:
+    """Convert water vapor pressure `pv` in hPa to the mass mixing ratio in
+    kg/kg, at a total pressure `p` in hPa.
+
+    The inputs are broadcast against each other.
+    """
+
+def specific_humidity_to_mixing_ratio(
+    q: NDArray[np.float32],
+) -> NDArray[np.float32]:
+    """Convert specific humidity `q` in kg/kg to the mass mixing ratio in
+    kg/kg.
+    """
+
+def mixing_ratio_to_specific_humidity(
+    w: NDArray[np.float32],
+) -> NDArray[np.float32]:
+    """Convert the mass mixing ratio `w` in kg/kg to specific humidity in
+    kg/kg.
+    """
+
+def dewpoint_to_vapor_pressure(
+    td: NDArray[np.float32],
+) -> NDArray[np.float32]:
+    """Convert the dewpoint `td` in K to water vapor pressure in hPa, using the
+    Buck equation.
+    """
+
+def vapor_pressure_to_dewpoint(
+    pv: NDArray[np.float32],
+) -> NDArray[np.float32]:
+    """Convert water vapor pressure `pv` in hPa to the dewpoint in K, using the
+    inverse of the Buck equation.
+    """
+
+def relative_humidity_to_vapor_pressure(
+    rh: NDArray[np.float32],
+    t: NDArray[np.float32],
+) -> NDArray[np.float32]:
+    """Convert relative humidity `rh` (a fraction, over water) to water vapor
+    pressure in hPa, at an air temperature `t` in K.
+
+    The inputs are broadcast against each other.
+    """
+
+def vapor_pressure_to_relative_humidity(
+    pv: NDArray[np.float32],
+    t: NDArray[np.float32],
+) -> NDArray[np.float32]:
+    """Convert water vapor pressure `pv` in hPa to relative humidity (a
+    fraction, over water), at an air temperature `t` in K.
+
+    The inputs are broadcast against each other.
+    """
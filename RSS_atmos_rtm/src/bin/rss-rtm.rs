@@ -0,0 +1,794 @@
+//! `rss-rtm`: run the atmospheric RTM for the profiles in a netCDF file, and
+//! write `tran`, `tb_up`, and `tb_down` to another netCDF file.
+//!
+//! The channels are from a sensor definition file (see
+//! `rss_atmos_rtm::rtm::sensor_file`), at one scan position. The profiles are
+//! read from a netCDF-3 file (see `rss_atmos_rtm::rtm::netcdf`; a netCDF-4
+//! file is an input error that says how to convert it), with any
+//! dimensions: the profile variables have a level dimension, and the rest of
+//! their dimensions, in order, are the dimensions of the points, which the
+//! surface variables also have. The pressure has either only the level
+//! dimension or the same dimensions as the profiles. The units are the same
+//! as for `compute_rtm`: hPa, K, m, kg/kg, and K for the dewpoint.
+//!
+//! The names of the variables, and of the level dimension, are the names of
+//! the inputs by default, and can be changed with a TOML mapping file:
+//!
+//! ```toml
+//! level_dimension = "level"
+//!
+//! [variables]
+//! pressure = "level"
+//! temperature = "t"
+//! height = "z"
+//! specific_humidity = "q"
+//! liquid_content = "clwc"
+//! surface_temperature = "t2m"
+//! surface_height = "z_surface"
+//! surface_dewpoint = "d2m"
+//! surface_pressure = "sp"
+//! ```
+//!
+//! `liquid_content` and `surface_pressure` are optional, and are only read if
+//! they're in the mapping or the file has a variable with their name.
+//!
+//! The outputs have the dimensions of the points and then `channel`, along
+//! with the channels' `frequency`, `incidence_angle`, and `channel_number`,
+//! the `quality_flags` of each point, and any coordinate variables of the
+//! point dimensions from the input. Points that fail have NaN outputs.
+//!
+//! The exit code is 0 on success, 2 for invalid inputs, including any point
+//! that fails, and 1 if the RTM couldn't be run or the outputs couldn't be
+//! written. With `--validate-only`, the inputs of every point are prepared, as
+//! for a run, but the RTM isn't run and nothing is written.
+
+use std::{
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::Mutex,
+    time::Instant,
+};
+
+use clap::{ArgAction, Parser};
+use log::{debug, error, info, warn, LevelFilter, Log, Metadata, Record};
+use ndarray::{Array2, ArrayD, Axis, Ix2};
+use rayon::prelude::*;
+use rss_atmos_rtm::rtm::{
+    self,
+    netcdf::{NetcdfAttribute, NetcdfDefinition, NetcdfFile, NetcdfType, NetcdfWriter},
+    sensor_file, ArrayProfiles, ProfileSource, RtmError, RtmOutputs, RtmParameters,
+};
+
+/// Exit code for invalid arguments or inputs, which is also clap's.
+const EXIT_INPUT: u8 = 2;
+
+/// Exit code for a failure to run the RTM or write the outputs.
+const EXIT_RUNTIME: u8 = 1;
+
+/// The number of failed points that are logged individually.
+const MAX_FAILURE_LOGS: usize = 20;
+
+/// Run the atmospheric RTM for the profiles in a netCDF file.
+///
+/// Only netCDF-3 files (the classic and 64-bit offset formats) are read, since
+/// the reader doesn't use the netCDF C library. Convert a netCDF-4 file, such
+/// as an ERA5 file from CDS, with `nccopy -k 64-bit-offset in.nc out.nc`.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Args {
+    /// The netCDF-3 file (classic or 64-bit offset format) of the profiles;
+    /// netCDF-4 isn't supported.
+    input: PathBuf,
+
+    /// The netCDF file to write, which is replaced if it exists.
+    #[arg(required_unless_present = "validate_only")]
+    output: Option<PathBuf>,
+
+    /// The sensor definition file of the channels.
+    #[arg(long)]
+    sensor: PathBuf,
+
+    /// The channel numbers to run, separated by commas, by default all of
+    /// them.
+    #[arg(long, value_delimiter = ',')]
+    channels: Vec<usize>,
+
+    /// The scan position of the incidence angle, numbered from 0.
+    #[arg(long, default_value_t = 0)]
+    scan_position: usize,
+
+    /// The TOML file with the names of the inputs in the netCDF file.
+    #[arg(long)]
+    mapping: Option<PathBuf>,
+
+    /// The number of worker threads, by default one for each CPU, or fewer
+    /// for a small chunk of points (see `rtm::adaptive_num_threads`).
+    #[arg(long, short = 'j')]
+    threads: Option<NonZeroUsize>,
+
+    /// The number of profiles to run at a time, after each of which the
+    /// progress is logged and the outputs are written.
+    #[arg(long, default_value = "100000")]
+    chunk_size: NonZeroUsize,
+
+    /// The most profiles that a worker thread runs as one piece of work, for
+    /// an even load (see `rtm::DEFAULT_WORK_CHUNK_SIZE`).
+    #[arg(long, default_value_t = NonZeroUsize::new(rtm::DEFAULT_WORK_CHUNK_SIZE).unwrap())]
+    work_chunk_size: NonZeroUsize,
+
+    /// Log the details of each chunk.
+    #[arg(short, long, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Only log warnings and errors.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Check the inputs of every point without running the RTM.
+    #[arg(long)]
+    validate_only: bool,
+}
+
+/// Why a run failed, which decides the exit code.
+#[derive(Debug)]
+enum Failure {
+    /// The arguments or inputs are invalid.
+    Input(String),
+    /// The RTM couldn't be run, or the outputs couldn't be written.
+    Runtime(String),
+}
+
+impl Failure {
+    fn input(e: impl std::fmt::Display) -> Self {
+        Failure::Input(e.to_string())
+    }
+
+    fn runtime(e: impl std::fmt::Display) -> Self {
+        Failure::Runtime(e.to_string())
+    }
+}
+
+/// Logs to standard error.
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if self.enabled(record.metadata()) {
+            eprintln!("{}: {}", record.level(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+/// The inputs of the RTM, in the order of the `[variables]` of a mapping
+/// file, and whether each is required.
+const INPUTS: [(&str, bool); 9] = [
+    ("pressure", true),
+    ("temperature", true),
+    ("height", true),
+    ("specific_humidity", true),
+    ("liquid_content", false),
+    ("surface_temperature", true),
+    ("surface_height", true),
+    ("surface_dewpoint", true),
+    ("surface_pressure", false),
+];
+
+/// The names of the level dimension and of each input in the netCDF file.
+#[derive(Debug)]
+struct Mapping {
+    level_dimension: String,
+    /// The variable of each of the [`INPUTS`], where the optional ones are
+    /// `None` if they weren't mapped
+    variables: Vec<(&'static str, Option<String>)>,
+}
+
+impl Mapping {
+    /// Parse a mapping file, where the names that aren't given are the
+    /// names of the inputs.
+    fn parse(text: &str) -> Result<Self, String> {
+        let table: toml::Table = text
+            .parse()
+            .map_err(|e: toml::de::Error| e.message().to_string())?;
+        let mut mapping = Self::default();
+        for (key, value) in &table {
+            match (key.as_str(), value) {
+                ("level_dimension", toml::Value::String(name)) => {
+                    mapping.level_dimension.clone_from(name);
+                }
+                ("variables", toml::Value::Table(variables)) => {
+                    for (input, name) in variables {
+                        let entry = mapping
+                            .variables
+                            .iter_mut()
+                            .find(|(key, _)| key == input)
+                            .ok_or_else(|| format!("variables.{input} is not a known input"))?;
+                        let toml::Value::String(name) = name else {
+                            return Err(format!("variables.{input} must be a string"));
+                        };
+                        entry.1 = Some(name.clone());
+                    }
+                }
+                ("level_dimension", _) => return Err("level_dimension must be a string".into()),
+                ("variables", _) => return Err("variables must be a table".into()),
+                (key, _) => return Err(format!("{key} is not a known key")),
+            }
+        }
+        Ok(mapping)
+    }
+
+    /// The variable of `input`, if it was mapped or is required.
+    fn variable(&self, input: &str) -> Option<&str> {
+        self.variables
+            .iter()
+            .find(|(key, _)| *key == input)
+            .and_then(|(_, name)| name.as_deref())
+    }
+}
+
+impl Default for Mapping {
+    fn default() -> Self {
+        Self {
+            level_dimension: "level".to_string(),
+            variables: INPUTS
+                .iter()
+                .map(|&(input, required)| (input, required.then(|| input.to_string())))
+                .collect(),
+        }
+    }
+}
+
+/// The names and lengths of dimensions.
+type Dimensions = Vec<(String, usize)>;
+
+/// The names and values of coordinate variables.
+type Coordinates = Vec<(String, Vec<f64>)>;
+
+/// The profiles from the input file, with one row for each point.
+struct Profiles {
+    /// The names and lengths of the dimensions of the points
+    point_dimensions: Dimensions,
+    pressure: ArrayD<f32>,
+    temperature: Array2<f32>,
+    height: Array2<f32>,
+    specific_humidity: Array2<f32>,
+    liquid_content: Array2<f32>,
+    surface_temperature: Vec<f32>,
+    surface_height: Vec<f32>,
+    surface_dewpoint: Vec<f32>,
+    surface_pressure: Option<Vec<f32>>,
+}
+
+/// Read a profile variable, with the level dimension moved last and the rest
+/// flattened into one row for each point, and the names and lengths of the
+/// dimensions of the points.
+fn read_profile(
+    input: &mut NetcdfFile,
+    level: &str,
+    name: &str,
+) -> Result<(Array2<f32>, Dimensions), Failure> {
+    let variable = input
+        .variable(name)
+        .ok_or_else(|| Failure::Input(format!("there's no variable named {name} in the input")))?;
+    let axis = variable
+        .dimensions
+        .iter()
+        .position(|dim| dim == level)
+        .ok_or_else(|| {
+            Failure::Input(format!(
+                "variable {name} doesn't have the dimension {level}"
+            ))
+        })?;
+    let dimensions: Vec<_> = variable
+        .dimensions
+        .iter()
+        .zip(&variable.shape)
+        .enumerate()
+        .filter(|&(index, _)| index != axis)
+        .map(|(_, (dim, &len))| (dim.clone(), len))
+        .collect();
+
+    let values = input.read::<f32>(name).map_err(Failure::input)?;
+    let mut order: Vec<_> = (0..values.ndim()).filter(|&i| i != axis).collect();
+    order.push(axis);
+    let num_levels = values.shape()[axis];
+    let num_points = values.len().checked_div(num_levels).unwrap_or(0);
+    let values = values
+        .permuted_axes(order)
+        .as_standard_layout()
+        .into_owned()
+        .into_shape_with_order((num_points, num_levels))
+        .expect("the shape has the same number of values");
+    Ok((values, dimensions))
+}
+
+impl Profiles {
+    /// Read the profiles from the input file.
+    fn read(input: &mut NetcdfFile, mapping: &Mapping) -> Result<Self, Failure> {
+        let level = mapping.level_dimension.as_str();
+        // The optional inputs are read if they're mapped, or the file has
+        // them by the name of the input
+        let name = |input_name: &str| -> Option<String> {
+            mapping
+                .variable(input_name)
+                .map(str::to_string)
+                .or_else(|| {
+                    input
+                        .variable(input_name)
+                        .is_some()
+                        .then(|| input_name.to_string())
+                })
+        };
+        let required = |input_name: &str| name(input_name).expect("required inputs are mapped");
+        let temperature_name = required("temperature");
+        let height_name = required("height");
+        let specific_humidity_name = required("specific_humidity");
+        let pressure_name = required("pressure");
+        let liquid_content_name = name("liquid_content");
+        let surface_pressure_name = name("surface_pressure");
+        let surface_names = [
+            required("surface_temperature"),
+            required("surface_height"),
+            required("surface_dewpoint"),
+        ];
+
+        let (temperature, point_dimensions) = read_profile(input, level, &temperature_name)?;
+        let same_points = |input: &mut NetcdfFile, name: &str| -> Result<Array2<f32>, Failure> {
+            let (values, dimensions) = read_profile(input, level, name)?;
+            if dimensions != point_dimensions || values.ncols() != temperature.ncols() {
+                return Err(Failure::Input(format!(
+                    "variable {name} doesn't have the same dimensions as {temperature_name}"
+                )));
+            }
+            Ok(values)
+        };
+        let height = same_points(input, &height_name)?;
+        let specific_humidity = same_points(input, &specific_humidity_name)?;
+        let liquid_content = match liquid_content_name {
+            Some(name) => same_points(input, &name)?,
+            None => Array2::zeros((temperature.nrows(), 0)),
+        };
+        let shared_pressure = input
+            .variable(&pressure_name)
+            .is_some_and(|variable| variable.dimensions == [level]);
+        let pressure = if shared_pressure {
+            input.read::<f32>(&pressure_name).map_err(Failure::input)?
+        } else {
+            same_points(input, &pressure_name)?.into_dyn()
+        };
+
+        let surface = |input: &mut NetcdfFile, name: &str| -> Result<Vec<f32>, Failure> {
+            let variable = input.variable(name).ok_or_else(|| {
+                Failure::Input(format!("there's no variable named {name} in the input"))
+            })?;
+            let dimensions: Vec<_> = variable
+                .dimensions
+                .iter()
+                .cloned()
+                .zip(variable.shape.iter().copied())
+                .collect();
+            if dimensions != point_dimensions {
+                return Err(Failure::Input(format!(
+                    "variable {name} doesn't have the point dimensions of {temperature_name}"
+                )));
+            }
+            let values = input.read::<f32>(name).map_err(Failure::input)?;
+            Ok(values.into_raw_vec_and_offset().0)
+        };
+        let [surface_temperature, surface_height, surface_dewpoint] = surface_names;
+        let surface_temperature = surface(input, &surface_temperature)?;
+        let surface_height = surface(input, &surface_height)?;
+        let surface_dewpoint = surface(input, &surface_dewpoint)?;
+        let surface_pressure = surface_pressure_name
+            .map(|name| surface(input, &name))
+            .transpose()?;
+        Ok(Self {
+            pressure,
+            temperature,
+            height,
+            specific_humidity,
+            liquid_content,
+            surface_temperature,
+            surface_height,
+            surface_dewpoint,
+            surface_pressure,
+            point_dimensions,
+        })
+    }
+
+    /// The profiles as a source for the RTM, which checks their shapes.
+    fn source(&self) -> Result<ArrayProfiles<'_>, RtmError> {
+        let pressure = self.pressure.view();
+        let source = match pressure.ndim() {
+            1 => ArrayProfiles::new(
+                pressure.to_slice().expect("read in standard layout"),
+                self.temperature.view(),
+                self.height.view(),
+                self.specific_humidity.view(),
+                self.liquid_content.view(),
+                &self.surface_temperature,
+                &self.surface_height,
+                &self.surface_dewpoint,
+                self.surface_pressure.as_deref(),
+                None,
+                None,
+                None,
+            ),
+            _ => ArrayProfiles::new_per_point_pressure(
+                pressure
+                    .into_dimensionality::<Ix2>()
+                    .expect("per-point pressure is 2d"),
+                self.temperature.view(),
+                self.height.view(),
+                self.specific_humidity.view(),
+                self.liquid_content.view(),
+                &self.surface_temperature,
+                &self.surface_height,
+                &self.surface_dewpoint,
+                self.surface_pressure.as_deref(),
+                None,
+                None,
+                None,
+            ),
+        }?;
+        Ok(source)
+    }
+}
+
+/// The channels to run, from the sensor definition file.
+struct Channels {
+    sensor: sensor_file::SensorDefinition,
+    numbers: Vec<usize>,
+    parameters: RtmParameters,
+}
+
+impl Channels {
+    fn read(args: &Args) -> Result<Self, Failure> {
+        let sensor = sensor_file::read_sensor(&args.sensor)
+            .map_err(|e| Failure::Input(format!("couldn't read {}: {e}", args.sensor.display())))?;
+        let numbers = if args.channels.is_empty() {
+            (1..=sensor.channels.len()).collect()
+        } else {
+            args.channels.clone()
+        };
+        let parameters = sensor
+            .parameters(&numbers, args.scan_position)
+            .map_err(Failure::input)?
+            .with_work_chunk_size(args.work_chunk_size);
+        Ok(Self {
+            sensor,
+            numbers,
+            parameters,
+        })
+    }
+}
+
+/// Log each failed point, up to [`MAX_FAILURE_LOGS`], and return the number
+/// of them.
+fn log_failures<'a>(failures: impl Iterator<Item = (usize, &'a RtmError)>) -> usize {
+    let mut count = 0;
+    for (index, e) in failures {
+        if count < MAX_FAILURE_LOGS {
+            warn!("point {index} failed: {e}");
+        }
+        count += 1;
+    }
+    if count > MAX_FAILURE_LOGS {
+        warn!("{} more points failed", count - MAX_FAILURE_LOGS);
+    }
+    count
+}
+
+/// Prepare the inputs of every point, without running the RTM, split between
+/// the threads as for `parameters`.
+fn validate(
+    source: &ArrayProfiles<'_>,
+    parameters: &RtmParameters,
+    threads: Option<NonZeroUsize>,
+) -> Result<(), Failure> {
+    // Preparing a point is about as much work as running one channel
+    let threads = threads.unwrap_or_else(|| {
+        rtm::adaptive_num_threads(source.len(), 1, parameters.items_per_thread())
+    });
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads.get())
+        .build()
+        .map_err(Failure::runtime)?;
+    let results: Vec<_> = pool.install(|| {
+        (0..source.len())
+            .into_par_iter()
+            .with_max_len(parameters.work_chunk_size().get())
+            .map(|index| source.profile(index).and_then(|profile| profile.inputs()))
+            .collect()
+    });
+    let failures = log_failures(
+        results
+            .iter()
+            .enumerate()
+            .filter_map(|(index, result)| result.as_ref().err().map(|e| (index, e))),
+    );
+    if failures > 0 {
+        return Err(Failure::Input(format!(
+            "{failures} of {} points have invalid inputs",
+            source.len()
+        )));
+    }
+    info!("the inputs of all {} points are valid", source.len());
+    Ok(())
+}
+
+/// Define the output file, with the point dimensions and coordinates of the
+/// input.
+fn define_output(
+    input: &mut NetcdfFile,
+    profiles: &Profiles,
+    channels: &Channels,
+    args: &Args,
+) -> Result<(NetcdfDefinition, Coordinates), Failure> {
+    let text = |text: &str| NetcdfAttribute::Text(text.to_string());
+    let mut definition = NetcdfDefinition::new();
+    definition.add_attribute(
+        "source",
+        text(&format!(
+            "rss_atmos_rtm {} ({})",
+            rtm::VERSION,
+            rtm::GIT_DESCRIBE
+        )),
+    );
+    definition.add_attribute("sensor", text(&channels.sensor.name));
+    definition.add_attribute(
+        "scan_position",
+        NetcdfAttribute::Int(vec![args.scan_position as i32]),
+    );
+    definition.add_attribute("input_file", text(&args.input.display().to_string()));
+    for (absorber, model) in rtm::SPECTROSCOPY {
+        definition.add_attribute(&format!("spectroscopy_{absorber}"), text(model));
+    }
+
+    let mut coordinates = Vec::new();
+    for (dim, len) in &profiles.point_dimensions {
+        definition
+            .add_dimension(dim, *len)
+            .map_err(Failure::input)?;
+        // A coordinate variable of the dimension is copied, without packing
+        let Some(variable) = input
+            .variable(dim)
+            .filter(|v| v.dimensions == [dim.as_str()])
+        else {
+            continue;
+        };
+        let attributes = variable
+            .attributes
+            .iter()
+            .filter(|(key, _)| {
+                !["scale_factor", "add_offset", "_FillValue", "missing_value"]
+                    .contains(&key.as_str())
+            })
+            .cloned()
+            .collect();
+        definition
+            .add_variable(dim, &[dim.as_str()], NetcdfType::Double, attributes)
+            .map_err(Failure::input)?;
+        let values = input.read::<f64>(dim).map_err(Failure::input)?;
+        coordinates.push((dim.clone(), values.into_raw_vec_and_offset().0));
+    }
+
+    let num_freq = channels.numbers.len();
+    definition
+        .add_dimension("channel", num_freq)
+        .map_err(|_| Failure::Input("the input already has a channel dimension".into()))?;
+    let units = |units: &str| vec![("units".to_string(), text(units))];
+    for (name, nc_type, units) in [
+        ("channel_number", NetcdfType::Int, units("1")),
+        ("frequency", NetcdfType::Double, units("GHz")),
+        ("incidence_angle", NetcdfType::Double, units("degree")),
+    ] {
+        definition
+            .add_variable(name, &["channel"], nc_type, units)
+            .map_err(Failure::input)?;
+    }
+    let mut dimensions: Vec<_> = profiles
+        .point_dimensions
+        .iter()
+        .map(|(dim, _)| dim.as_str())
+        .collect();
+    definition
+        .add_variable("quality_flags", &dimensions, NetcdfType::Int, units("1"))
+        .map_err(Failure::input)?;
+    dimensions.push("channel");
+    for (name, units) in [("tran", "1"), ("tb_up", "K"), ("tb_down", "K")] {
+        let mut attributes = vec![
+            ("units".to_string(), text(units)),
+            (
+                "_FillValue".to_string(),
+                NetcdfAttribute::Float(vec![f32::NAN]),
+            ),
+        ];
+        attributes.push(("coordinates".to_string(), text("frequency incidence_angle")));
+        definition
+            .add_variable(name, &dimensions, NetcdfType::Float, attributes)
+            .map_err(|_| Failure::Input(format!("the input already has a variable {name}")))?;
+    }
+    Ok((definition, coordinates))
+}
+
+/// Run the RTM a chunk of points at a time, writing the outputs of each
+/// chunk, and return the number of points that failed.
+fn run_chunks(
+    source: &ArrayProfiles<'_>,
+    parameters: &RtmParameters,
+    output: &mut NetcdfWriter,
+    args: &Args,
+) -> Result<usize, Failure> {
+    let num_points = source.len();
+    let num_freq = parameters.len();
+    let start = Instant::now();
+    let mut failures = 0;
+    for chunk_start in (0..num_points).step_by(args.chunk_size.get()) {
+        let range = chunk_start..(chunk_start + args.chunk_size.get()).min(num_points);
+        let chunk = source.points(range.clone()).map_err(Failure::runtime)?;
+        let results: Mutex<Vec<Option<Result<RtmOutputs, RtmError>>>> =
+            Mutex::new((0..chunk.len()).map(|_| None).collect());
+        rtm::compute_rtm_source(
+            &chunk,
+            parameters,
+            &|index, result| results.lock().expect("not poisoned")[index] = Some(result),
+            args.threads,
+        )
+        .map_err(Failure::runtime)?;
+        let results: Vec<_> = results
+            .into_inner()
+            .expect("not poisoned")
+            .into_iter()
+            .map(|result| result.expect("every point has a result"))
+            .collect();
+
+        failures +=
+            log_failures(results.iter().enumerate().filter_map(|(index, result)| {
+                result.as_ref().err().map(|e| (chunk_start + index, e))
+            }));
+        let mut outputs = [
+            Array2::from_elem((chunk.len(), num_freq), f32::NAN),
+            Array2::from_elem((chunk.len(), num_freq), f32::NAN),
+            Array2::from_elem((chunk.len(), num_freq), f32::NAN),
+        ];
+        let mut quality_flags = vec![0; chunk.len()];
+        for (index, result) in results.iter().enumerate() {
+            if let Ok(result) = result {
+                for (array, values) in
+                    outputs
+                        .iter_mut()
+                        .zip([&result.tran, &result.tb_up, &result.tb_down])
+                {
+                    array
+                        .index_axis_mut(Axis(0), index)
+                        .assign(&ndarray::ArrayView1::from(values.as_slice()));
+                }
+                quality_flags[index] = result.quality.bits() as i32;
+            }
+        }
+        for (name, values) in ["tran", "tb_up", "tb_down"].iter().zip(&outputs) {
+            output
+                .write(
+                    name,
+                    chunk_start * num_freq,
+                    values.as_slice().expect("standard layout"),
+                )
+                .map_err(Failure::runtime)?;
+        }
+        output
+            .write("quality_flags", chunk_start, &quality_flags)
+            .map_err(Failure::runtime)?;
+
+        debug!("wrote profiles {} to {}", range.start, range.end);
+        info!(
+            "processed {} of {num_points} profiles in {:.1} s",
+            range.end,
+            start.elapsed().as_secs_f64()
+        );
+    }
+    Ok(failures)
+}
+
+fn run(args: &Args) -> Result<(), Failure> {
+    let mapping = match &args.mapping {
+        Some(path) => {
+            let text = std::fs::read_to_string(path)
+                .map_err(|e| Failure::Input(format!("couldn't read {}: {e}", path.display())))?;
+            Mapping::parse(&text).map_err(|e| {
+                Failure::Input(format!("invalid mapping file {}: {e}", path.display()))
+            })?
+        }
+        None => Mapping::default(),
+    };
+    let channels = Channels::read(args)?;
+    let mut input = NetcdfFile::open(&args.input)
+        .map_err(|e| Failure::Input(format!("couldn't read {}: {e}", args.input.display())))?;
+    let profiles = Profiles::read(&mut input, &mapping)?;
+    let source = profiles.source().map_err(Failure::input)?;
+    info!(
+        "read {} profiles with {} levels from {}",
+        source.len(),
+        source.num_levels(),
+        args.input.display()
+    );
+
+    if args.validate_only {
+        return validate(&source, &channels.parameters, args.threads);
+    }
+
+    let path: &Path = args
+        .output
+        .as_deref()
+        .expect("required without --validate-only");
+    let (definition, coordinates) = define_output(&mut input, &profiles, &channels, args)?;
+    let mut output = definition
+        .create(path)
+        .map_err(|e| Failure::Runtime(format!("couldn't create {}: {e}", path.display())))?;
+    let numbers: Vec<_> = channels.numbers.iter().map(|&n| n as i32).collect();
+    let frequency: Vec<_> = channels
+        .numbers
+        .iter()
+        .map(|&n| channels.sensor.channels[n - 1].frequency)
+        .collect();
+    let incidence_angle = vec![channels.sensor.incidence_angles[args.scan_position]; numbers.len()];
+    output
+        .write("channel_number", 0, &numbers)
+        .map_err(Failure::runtime)?;
+    output
+        .write("frequency", 0, &frequency)
+        .map_err(Failure::runtime)?;
+    output
+        .write("incidence_angle", 0, &incidence_angle)
+        .map_err(Failure::runtime)?;
+    for (name, values) in &coordinates {
+        output.write(name, 0, values).map_err(Failure::runtime)?;
+    }
+
+    info!(
+        "running {} channels of {} for {} profiles",
+        numbers.len(),
+        channels.sensor.name,
+        source.len()
+    );
+    let failures = run_chunks(&source, &channels.parameters, &mut output, args)?;
+    info!("wrote {}", path.display());
+    if failures > 0 {
+        return Err(Failure::Input(format!(
+            "{failures} of {} points failed, and have NaN outputs",
+            source.len()
+        )));
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let level = match (args.quiet, args.verbose) {
+        (true, _) => LevelFilter::Warn,
+        (false, 0) => LevelFilter::Info,
+        (false, _) => LevelFilter::Debug,
+    };
+    log::set_logger(&LOGGER).expect("the only logger");
+    log::set_max_level(level);
+
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(Failure::Input(message)) => {
+            error!("{message}");
+            ExitCode::from(EXIT_INPUT)
+        }
+        Err(Failure::Runtime(message)) => {
+            error!("{message}");
+            ExitCode::from(EXIT_RUNTIME)
+        }
+    }
+}
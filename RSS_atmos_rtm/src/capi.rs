@@ -0,0 +1,330 @@
+//! C interface
+//!
+//! NOTE: like the Python interface, this module is only the interface between
+//! Rust and C (or Fortran, with `bind(C)`). The real work happens in the
+//! [`rtm`](crate::rtm) module.
+//!
+//! With the `capi` feature, the build generates the header `rss_atmos_rtm.h`
+//! with cbindgen, and writes it next to the library in the target directory
+//! (e.g., `target/release/`). The arrays are plain row-major `float` arrays
+//! owned by the caller, with the profiles having one row per point, and the
+//! outputs are written into arrays the caller has allocated. The only thing the
+//! library allocates is the `RtmParams`, which is freed with
+//! `rtm_params_destroy`.
+//!
+//! The functions return a status or `NULL` on failure, and write a
+//! NUL-terminated message into the caller's `error_buf` of `error_buf_len`
+//! bytes, truncated if necessary. `error_buf` may be `NULL` to skip the
+//! message.
+
+use std::{
+    ffi::{c_char, c_int},
+    num::NonZeroUsize,
+    panic::{self, AssertUnwindSafe},
+    ptr, slice,
+    sync::Mutex,
+};
+
+use ndarray::ArrayView2;
+
+use crate::rtm::{self, ArrayProfiles, RtmError, RtmOutputs, RtmParameters};
+
+/// Status of success.
+pub const RTM_OK: c_int = 0;
+
+/// Status for invalid arguments or inputs, including any point that failed.
+pub const RTM_ERROR_INPUT: c_int = 1;
+
+/// Status for a failure to run the RTM, such as the worker threads not
+/// starting, or an internal error.
+pub const RTM_ERROR_RUNTIME: c_int = 2;
+
+/// The channels for `rtm_compute`: an opaque handle created by
+/// `rtm_params_create` and freed by `rtm_params_destroy`.
+#[derive(Debug)]
+pub struct RtmParams(RtmParameters);
+
+/// A failure with its status and message for the caller.
+struct Failure {
+    status: c_int,
+    message: String,
+}
+
+impl Failure {
+    /// A failure with [`RTM_ERROR_INPUT`].
+    fn input(message: impl Into<String>) -> Self {
+        Self {
+            status: RTM_ERROR_INPUT,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<RtmError> for Failure {
+    fn from(e: RtmError) -> Self {
+        let status = match e {
+            RtmError::ThreadPool(_) | RtmError::Io(_) => RTM_ERROR_RUNTIME,
+            _ => RTM_ERROR_INPUT,
+        };
+        Self {
+            status,
+            message: e.to_string(),
+        }
+    }
+}
+
+/// Write `message` into the caller's `error_buf`, truncated to fit with its
+/// NUL terminator. The truncation is at a character boundary, so the message
+/// is still valid UTF-8.
+///
+/// # Safety
+///
+/// `error_buf` is `NULL` or valid for writes of `error_buf_len` bytes.
+pub(crate) unsafe fn write_error(error_buf: *mut c_char, error_buf_len: usize, message: &str) {
+    if error_buf.is_null() || error_buf_len == 0 {
+        return;
+    }
+    let mut len = message.len().min(error_buf_len - 1);
+    // Back off to the start of a character split by the truncation
+    while !message.is_char_boundary(len) {
+        len -= 1;
+    }
+    ptr::copy_nonoverlapping(message.as_ptr().cast::<c_char>(), error_buf, len);
+    *error_buf.add(len) = 0;
+}
+
+/// Run `f`, catching any panic so it doesn't unwind into the caller, and
+/// write the message of a failure into `error_buf`.
+///
+/// # Safety
+///
+/// As for [`write_error`].
+unsafe fn call<V>(
+    error_buf: *mut c_char,
+    error_buf_len: usize,
+    f: impl FnOnce() -> Result<V, Failure>,
+) -> Result<V, c_int> {
+    let failure = match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => return Ok(value),
+        Ok(Err(failure)) => failure,
+        Err(_) => Failure {
+            status: RTM_ERROR_RUNTIME,
+            message: "internal error (panic) in the RTM".to_string(),
+        },
+    };
+    write_error(error_buf, error_buf_len, &failure.message);
+    Err(failure.status)
+}
+
+/// Borrow the caller's array of `len` values, named `name` in the error if
+/// it's `NULL`.
+///
+/// # Safety
+///
+/// `values` is `NULL` or valid for reads of `len` values.
+unsafe fn input<'a>(values: *const f32, len: usize, name: &str) -> Result<&'a [f32], Failure> {
+    if len == 0 {
+        Ok(&[])
+    } else if values.is_null() {
+        Err(Failure::input(format!("{name} is NULL")))
+    } else {
+        Ok(slice::from_raw_parts(values, len))
+    }
+}
+
+/// Borrow the caller's output array of `len` values, as in [`input`].
+///
+/// # Safety
+///
+/// `values` is `NULL` or valid for writes of `len` values.
+unsafe fn output<'a>(values: *mut f32, len: usize, name: &str) -> Result<&'a mut [f32], Failure> {
+    if len == 0 {
+        Ok(&mut [])
+    } else if values.is_null() {
+        Err(Failure::input(format!("{name} is NULL")))
+    } else {
+        Ok(slice::from_raw_parts_mut(values, len))
+    }
+}
+
+/// Create the channels for `rtm_compute`, from the `n_freq` frequencies in
+/// GHz and Earth incidence angles in degrees.
+///
+/// Returns `NULL`, with the message in `error_buf`, if the incidence angles
+/// are out of range or a pointer is `NULL`. Otherwise, the result must be
+/// freed with `rtm_params_destroy`.
+///
+/// # Safety
+///
+/// `frequency` and `incidence_angle` are valid for reads of `n_freq` values,
+/// and `error_buf` is `NULL` or valid for writes of `error_buf_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rtm_params_create(
+    frequency: *const f32,
+    incidence_angle: *const f32,
+    n_freq: usize,
+    error_buf: *mut c_char,
+    error_buf_len: usize,
+) -> *mut RtmParams {
+    call(error_buf, error_buf_len, || {
+        let frequency = input(frequency, n_freq, "frequency")?;
+        let incidence_angle = input(incidence_angle, n_freq, "incidence_angle")?;
+        let parameters = RtmParameters::new(frequency, incidence_angle)?;
+        Ok(Box::into_raw(Box::new(RtmParams(parameters))))
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Free the channels created by `rtm_params_create`. `NULL` is ignored.
+///
+/// # Safety
+///
+/// `params` is `NULL` or was returned by `rtm_params_create` and not already
+/// freed.
+#[no_mangle]
+pub unsafe extern "C" fn rtm_params_destroy(params: *mut RtmParams) {
+    if !params.is_null() {
+        drop(Box::from_raw(params));
+    }
+}
+
+/// Run the RTM for `n_points` profiles, each with `n_levels` levels, for the
+/// channels in `params`.
+///
+/// The inputs are the same as for the Python `compute_rtm`, in the same
+/// units:
+///
+/// - `pressure`: the `n_levels` pressure levels in hPa, shared by all of the
+///   points, sorted from high to low pressure
+/// - `temperature`, `height`, `specific_humidity`, and `liquid_content`:
+///   (`n_points`, `n_levels`) arrays of the temperature in K, geopotential
+///   height in m, specific humidity in kg/kg, and liquid water content in
+///   kg/kg. `liquid_content` may be `NULL` for clear sky.
+/// - `surface_temperature`, `surface_height`, `surface_dewpoint`, and
+///   `surface_pressure`: `n_points` arrays of the 2 m air temperature in K,
+///   the geopotential height in m, the 2 m dewpoint in K, and the surface
+///   pressure in hPa. `surface_pressure` may be `NULL` for the first level.
+///
+/// The outputs `tran`, `tb_up`, and `tb_down` are (`n_points`, `n_freq`)
+/// arrays allocated by the caller, where `n_freq` is from `rtm_params_create`.
+///
+/// The points are run on `n_threads` worker threads, or if it's 0, on a number
+/// adapted to the batch: one for each 512 (point, channel) pairs, but at least
+/// one and at most one for each CPU (or `RAYON_NUM_THREADS`), so that a small
+/// batch doesn't start threads it can't use.
+///
+/// Returns `RTM_OK` on success. If any point fails, such as for a surface
+/// pressure of NaN, its outputs are NaN, the others are still computed, and
+/// the status is `RTM_ERROR_INPUT` with the first failure in `error_buf`.
+/// Otherwise, for invalid arguments (`RTM_ERROR_INPUT`), such as a `NULL`
+/// array, or a failure to run (`RTM_ERROR_RUNTIME`), no outputs are written.
+///
+/// # Safety
+///
+/// `params` was returned by `rtm_params_create`, each array is `NULL` or valid
+/// for its length above (reads for the inputs and writes for the outputs),
+/// and `error_buf` is `NULL` or valid for writes of `error_buf_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rtm_compute(
+    params: *const RtmParams,
+    n_points: usize,
+    n_levels: usize,
+    pressure: *const f32,
+    temperature: *const f32,
+    height: *const f32,
+    specific_humidity: *const f32,
+    liquid_content: *const f32,
+    surface_temperature: *const f32,
+    surface_height: *const f32,
+    surface_dewpoint: *const f32,
+    surface_pressure: *const f32,
+    tran: *mut f32,
+    tb_up: *mut f32,
+    tb_down: *mut f32,
+    n_threads: usize,
+    error_buf: *mut c_char,
+    error_buf_len: usize,
+) -> c_int {
+    let result = call(error_buf, error_buf_len, || {
+        let RtmParams(parameters) = params
+            .as_ref()
+            .ok_or_else(|| Failure::input("params is NULL"))?;
+        let n_freq = parameters.len();
+        let size = |n: usize| {
+            n_points
+                .checked_mul(n)
+                .ok_or_else(|| Failure::input("the arrays are too large"))
+        };
+        let (profile_size, output_size) = (size(n_levels)?, size(n_freq)?);
+
+        let profile = |values: *const f32, name: &str| {
+            let values = input(values, profile_size, name)?;
+            Ok::<_, Failure>(ArrayView2::from_shape((n_points, n_levels), values).unwrap())
+        };
+        let liquid_content = if liquid_content.is_null() {
+            ArrayView2::from_shape((n_points, 0), &[]).unwrap()
+        } else {
+            profile(liquid_content, "liquid_content")?
+        };
+        let surface_pressure = if surface_pressure.is_null() {
+            None
+        } else {
+            Some(input(surface_pressure, n_points, "surface_pressure")?)
+        };
+        let source = ArrayProfiles::new(
+            input(pressure, n_levels, "pressure")?,
+            profile(temperature, "temperature")?,
+            profile(height, "height")?,
+            profile(specific_humidity, "specific_humidity")?,
+            liquid_content,
+            input(surface_temperature, n_points, "surface_temperature")?,
+            input(surface_height, n_points, "surface_height")?,
+            input(surface_dewpoint, n_points, "surface_dewpoint")?,
+            surface_pressure,
+            None,
+            None,
+            None,
+        )?;
+        let outputs = Mutex::new((
+            output(tran, output_size, "tran")?,
+            output(tb_up, output_size, "tb_up")?,
+            output(tb_down, output_size, "tb_down")?,
+        ));
+
+        // The failed point with the lowest index, and the number of failures
+        let failures = Mutex::new((None, 0));
+        let sink = |index: usize, result: Result<RtmOutputs, RtmError>| {
+            let (tran, tb_up, tb_down) = &mut *outputs.lock().unwrap();
+            let rows = index * n_freq..(index + 1) * n_freq;
+            match result {
+                Ok(point) => {
+                    tran[rows.clone()].copy_from_slice(&point.tran);
+                    tb_up[rows.clone()].copy_from_slice(&point.tb_up);
+                    tb_down[rows].copy_from_slice(&point.tb_down);
+                }
+                Err(e) => {
+                    for values in [tran, tb_up, tb_down] {
+                        values[rows.clone()].fill(f32::NAN);
+                    }
+                    let (first, count) = &mut *failures.lock().unwrap();
+                    if first.as_ref().map_or(true, |&(first, _)| index < first) {
+                        *first = Some((index, e));
+                    }
+                    *count += 1;
+                }
+            }
+        };
+        rtm::compute_rtm_source(&source, parameters, &sink, NonZeroUsize::new(n_threads))?;
+
+        match failures.into_inner().unwrap() {
+            (Some((index, e)), count) => Err(Failure::input(format!(
+                "{count} of {n_points} points failed, the first at index {index}: {e}"
+            ))),
+            _ => Ok(()),
+        }
+    });
+    match result {
+        Ok(()) => RTM_OK,
+        Err(status) => status,
+    }
+}
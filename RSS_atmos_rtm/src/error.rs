@@ -1,3 +1,5 @@
+//! Error type for the RTM.
+
 /// Possible RTM errors.
 #[derive(Debug)]
 pub enum RtmError {
@@ -5,10 +7,107 @@ pub enum RtmError {
     InconsistentInputs,
     /// Couldn't find the surface index
     NoSurface,
+    /// The pressure levels are not sorted from high to low pressure
+    UnsortedLevels,
     /// An array is not contiguous when it was assumed to be
     NotContiguous,
     /// The operation was aborted early
     Cancelled,
+    /// An Earth incidence angle is outside of the physical range, 0° to 90°
+    InvalidIncidence {
+        /// Channel index of the offending value
+        index: usize,
+        /// The offending incidence angle in degrees
+        value: f32,
+    },
+    /// An NEΔT for the instrument noise is negative or not finite
+    InvalidNedt {
+        /// Channel index of the offending value
+        index: usize,
+        /// The offending NEΔT in K
+        value: f32,
+    },
+    /// A required input wasn't given
+    MissingInput(&'static str),
+    /// A range of frequencies is empty or not finite
+    InvalidFrequencyRange {
+        /// The first frequency in GHz
+        start: f64,
+        /// The end of the range in GHz, which is excluded
+        stop: f64,
+        /// The increment in GHz
+        step: f64,
+    },
+    /// A surface input is outside of its valid range, as described by the
+    /// message
+    InvalidSurface(String),
+    /// A scan position or channel number doesn't exist for a sensor, as
+    /// described by the message
+    InvalidScanGeometry(String),
+    /// A spectral response function or passband is not valid, as described by
+    /// the message
+    InvalidSpectralResponse(String),
+    /// A footprint's members or weights are not valid, as described by the
+    /// message
+    InvalidFootprint(String),
+    /// The worker thread pool couldn't be created
+    #[cfg(feature = "parallel")]
+    ThreadPool(rayon::ThreadPoolBuildError),
+    /// A file couldn't be read or written
+    Io(std::io::Error),
+    /// A profile file couldn't be parsed
+    InvalidProfileFile {
+        /// Line number of the offending value, starting at 1
+        line: usize,
+        /// What was wrong
+        message: String,
+    },
+    /// A sensor definition file couldn't be parsed or isn't valid
+    InvalidSensorFile {
+        /// Path of the offending key, e.g., `channels[2].bandwidth`, or `None`
+        /// if the file isn't valid TOML
+        key: Option<String>,
+        /// What was wrong
+        message: String,
+    },
+    /// A `.npy` file couldn't be parsed or has an unsupported dtype or layout,
+    /// as described by the message
+    InvalidNpyFile(String),
+    /// A netCDF file couldn't be parsed or is in an unsupported format, as
+    /// described by the message
+    InvalidNetcdfFile(String),
+    /// The outputs couldn't be arranged as an Arrow record batch or written as
+    /// Parquet, as described by the message
+    Parquet(String),
+}
+
+impl RtmError {
+    /// The name of the kind of error, without its details, e.g., for logging.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RtmError::InconsistentInputs => "InconsistentInputs",
+            RtmError::NoSurface => "NoSurface",
+            RtmError::UnsortedLevels => "UnsortedLevels",
+            RtmError::NotContiguous => "NotContiguous",
+            RtmError::Cancelled => "Cancelled",
+            RtmError::InvalidIncidence { .. } => "InvalidIncidence",
+            RtmError::InvalidNedt { .. } => "InvalidNedt",
+            RtmError::MissingInput(_) => "MissingInput",
+            RtmError::InvalidFrequencyRange { .. } => "InvalidFrequencyRange",
+            RtmError::InvalidSurface(_) => "InvalidSurface",
+            RtmError::InvalidScanGeometry(_) => "InvalidScanGeometry",
+            RtmError::InvalidSpectralResponse(_) => "InvalidSpectralResponse",
+            RtmError::InvalidFootprint(_) => "InvalidFootprint",
+            #[cfg(feature = "parallel")]
+            RtmError::ThreadPool(_) => "ThreadPool",
+            RtmError::Io(_) => "Io",
+            RtmError::InvalidProfileFile { .. } => "InvalidProfileFile",
+            RtmError::InvalidSensorFile { .. } => "InvalidSensorFile",
+            RtmError::InvalidNpyFile(_) => "InvalidNpyFile",
+            RtmError::InvalidNetcdfFile(_) => "InvalidNetcdfFile",
+            RtmError::Parquet(_) => "Parquet",
+        }
+    }
 }
 
 impl std::fmt::Display for RtmError {
@@ -20,10 +119,52 @@ impl std::fmt::Display for RtmError {
             RtmError::NoSurface => {
                 write!(f, "couldn't find the surface index")
             }
+            RtmError::UnsortedLevels => {
+                write!(f, "pressure levels are not strictly decreasing")
+            }
             RtmError::NotContiguous => write!(f, "array slice not contiguous in memory"),
             RtmError::Cancelled => write!(f, "operation cancelled early"),
+            RtmError::InvalidIncidence { index, value } => write!(
+                f,
+                "incidence angle {value} at index {index} is outside of the range 0 to 90 degrees"
+            ),
+            RtmError::InvalidNedt { index, value } => {
+                write!(f, "NEDT {value} at index {index} is negative or not finite")
+            }
+            RtmError::MissingInput(name) => write!(f, "missing required input: {name}"),
+            RtmError::InvalidFrequencyRange { start, stop, step } => write!(
+                f,
+                "no frequencies from {start} to {stop} GHz in steps of {step} GHz"
+            ),
+            RtmError::InvalidSurface(message) => write!(f, "invalid surface: {message}"),
+            RtmError::InvalidScanGeometry(message) => {
+                write!(f, "invalid scan geometry: {message}")
+            }
+            RtmError::InvalidSpectralResponse(message) => {
+                write!(f, "invalid spectral response: {message}")
+            }
+            RtmError::InvalidFootprint(message) => write!(f, "invalid footprint: {message}"),
+            #[cfg(feature = "parallel")]
+            RtmError::ThreadPool(e) => write!(f, "couldn't create the thread pool: {e}"),
+            RtmError::Io(e) => write!(f, "I/O error: {e}"),
+            RtmError::InvalidProfileFile { line, message } => {
+                write!(f, "invalid profile file at line {line}: {message}")
+            }
+            RtmError::InvalidSensorFile { key, message } => match key {
+                Some(key) => write!(f, "invalid sensor file: {key} {message}"),
+                None => write!(f, "invalid sensor file: {message}"),
+            },
+            RtmError::InvalidNpyFile(message) => write!(f, "invalid .npy file: {message}"),
+            RtmError::InvalidNetcdfFile(message) => write!(f, "invalid netCDF file: {message}"),
+            RtmError::Parquet(message) => write!(f, "couldn't write Parquet: {message}"),
         }
     }
 }
 
 impl std::error::Error for RtmError {}
+
+impl From<std::io::Error> for RtmError {
+    fn from(e: std::io::Error) -> Self {
+        RtmError::Io(e)
+    }
+}
@@ -0,0 +1,5999 @@
+//! Python interface
+//!
+//! NOTE: this module is intended for the interface between Rust and Python. The
+//! real work happens in the other modules, and they do not use `pyo3`, its
+//! only used here.
+
+use std::{
+    ffi::CString,
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::rtm::{
+    self,
+    scan_geometry::{self, ScanSensor},
+    standard_atmospheres, AbsorptionModel, AbsorptionScale, ArrayProfiles, Channel, ChannelVec,
+    Emission, Float, IntegrationScheme, LayerSumReference, OceanModel, Polarization, ProfileSource,
+    QualityFlags, Reflection, RtmError, RtmInputs, RtmOptions, RtmParameters, TbConvention,
+};
+use log::{debug, info, warn};
+use ndarray::{s, Array1, Array2, Array3, ArrayD, ArrayView1, Axis, Zip};
+use numpy::prelude::*;
+use numpy::{
+    Element, PyArray1, PyArray2, PyArray3, PyArrayDyn, PyReadonlyArray1, PyReadonlyArray2,
+    PyReadonlyArray3, PyReadonlyArrayDyn, PyReadwriteArray2, ToPyArray,
+};
+use pyo3::exceptions::{PyDeprecationWarning, PyIndexError, PyKeyError, PyOSError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use pyo3::IntoPyObjectExt;
+use rayon::{prelude::*, ThreadPool};
+
+impl From<RtmError> for PyErr {
+    fn from(e: RtmError) -> Self {
+        match e {
+            RtmError::InconsistentInputs => PyValueError::new_err(e.to_string()),
+            RtmError::NoSurface => PyValueError::new_err(e.to_string()),
+            RtmError::UnsortedLevels => PyValueError::new_err(e.to_string()),
+            RtmError::NotContiguous => PyValueError::new_err(e.to_string()),
+            RtmError::Cancelled => PyValueError::new_err(e.to_string()),
+            RtmError::InvalidIncidence { .. } => PyValueError::new_err(e.to_string()),
+            RtmError::InvalidNedt { .. } => PyValueError::new_err(e.to_string()),
+            RtmError::MissingInput(_) => PyValueError::new_err(e.to_string()),
+            RtmError::InvalidFrequencyRange { .. } => PyValueError::new_err(e.to_string()),
+            RtmError::InvalidSurface(_) => PyValueError::new_err(e.to_string()),
+            RtmError::InvalidScanGeometry(_) => PyValueError::new_err(e.to_string()),
+            RtmError::InvalidSpectralResponse(_) => PyValueError::new_err(e.to_string()),
+            RtmError::InvalidFootprint(_) => PyValueError::new_err(e.to_string()),
+            RtmError::ThreadPool(_) => PyValueError::new_err(e.to_string()),
+            RtmError::Io(_) => PyOSError::new_err(e.to_string()),
+            RtmError::InvalidProfileFile { .. } => PyValueError::new_err(e.to_string()),
+            RtmError::InvalidSensorFile { .. } => PyValueError::new_err(e.to_string()),
+            RtmError::InvalidNpyFile(_) => PyValueError::new_err(e.to_string()),
+            RtmError::InvalidNetcdfFile(_) => PyValueError::new_err(e.to_string()),
+            RtmError::Parquet(_) => PyValueError::new_err(e.to_string()),
+        }
+    }
+}
+
+/// Atmospheric parameters.
+///
+/// This is just a container of multiple numpy arrays, each dimensioned as
+/// (`num_points`, `num_freq`). The optional outputs on diagnostic levels are
+/// dimensioned as (`num_points`, `num_freq`, `num_diagnostic_levels`), and the
+/// optional quality flags and the number of levels integrated are dimensioned
+/// as (`num_points`, ). The optional effective temperatures are dimensioned as
+/// (`num_points`, `num_freq`). The floating-point outputs have the same dtype
+/// as the inputs. With instrument noise, the noise-free upwelling is optionally
+/// also output, dimensioned as (`num_points`, `num_freq`), as is the optional
+/// air-mass factor and downwelling at a chosen level. The optional compute
+/// time of each point is dimensioned as (`num_points`, ).
+#[pyclass]
+struct AtmoParameters {
+    arrays: OutputArrays,
+    quality_flags: Option<Array1<u32>>,
+    num_levels_integrated: Array1<i32>,
+    profile_time: Option<Array1<f32>>,
+    emission: Emission,
+    tb_convention: TbConvention,
+    diagnostics: Diagnostics,
+}
+
+/// Counts of how often the adjustments and checks of the RTM fired in a call,
+/// and how long it took, to monitor a production run without scraping the
+/// logs or inspecting the quality flags of every point.
+///
+/// If any point fails, the call raises, and the exception has the
+/// `Diagnostics` of the points run so far as its `diagnostics` attribute, so
+/// the failed and skipped points can be counted.
+#[pyclass(frozen)]
+#[derive(Debug, Clone, Default)]
+struct Diagnostics {
+    counts: rtm::QualityCounts,
+    /// Number of points whose RTM failed.
+    num_failed: usize,
+    /// Number of points that weren't run since the call was cancelled.
+    num_skipped: usize,
+    elapsed: Duration,
+}
+
+impl Diagnostics {
+    /// Count one more point, from its quality flags or the error it failed
+    /// with.
+    fn add(&mut self, result: Result<QualityFlags, &RtmError>) {
+        match result {
+            Ok(quality) => self.counts.add(quality),
+            Err(RtmError::Cancelled) => self.num_skipped += 1,
+            Err(_) => self.num_failed += 1,
+        }
+    }
+
+    /// The diagnostics of the per-point `results`, with the quality flags of
+    /// each point from `quality`.
+    fn from_results<R>(
+        results: &[Result<R, RtmError>],
+        quality: impl Fn(&R) -> QualityFlags,
+    ) -> Self {
+        let mut diagnostics = Self::default();
+        for result in results {
+            diagnostics.add(result.as_ref().map(&quality));
+        }
+        diagnostics
+    }
+
+    /// Attach a copy of these diagnostics, with the time since `start`, to
+    /// the exception `e` as its `diagnostics` attribute.
+    fn attach(&self, py: Python<'_>, e: PyErr, start: Instant) -> PyErr {
+        let diagnostics = Self {
+            elapsed: start.elapsed(),
+            ..self.clone()
+        };
+        if let Err(attach_error) =
+            Py::new(py, diagnostics).and_then(|d| e.value(py).setattr("diagnostics", d))
+        {
+            debug!("couldn't attach the diagnostics to the exception: {attach_error}");
+        }
+        e
+    }
+}
+
+#[pymethods]
+impl Diagnostics {
+    /// Number of points that were run.
+    #[getter]
+    fn num_points(&self) -> usize {
+        self.counts.num_profiles()
+    }
+
+    /// Number of points whose RTM failed. This is only nonzero for the
+    /// `diagnostics` of the exception raised for them.
+    #[getter]
+    fn points_failed(&self) -> usize {
+        self.num_failed
+    }
+
+    /// Number of points that weren't run since the call was cancelled, by an
+    /// exception from the absorption model. This is only nonzero for the
+    /// `diagnostics` of the exception raised for it.
+    #[getter]
+    fn points_skipped(&self) -> usize {
+        self.num_skipped
+    }
+
+    /// Number of points with a value clamped, by the name of the variable.
+    /// This is `surface_vapor_pressure`, for the points whose surface vapor
+    /// pressure was clamped to saturation with `clamp_surface_saturation`.
+    #[getter]
+    fn clamped<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let clamped = PyDict::new(py);
+        clamped.set_item(
+            "surface_vapor_pressure",
+            self.counts.count(QualityFlags::SURFACE_VAPOR_CLAMPED),
+        )?;
+        Ok(clamped)
+    }
+
+    /// Number of zero-thickness (or inverted) bottom layers that were fixed,
+    /// by moving the surface below the first level above it. There is at most
+    /// one per point.
+    #[getter]
+    fn layers_fixed(&self) -> usize {
+        self.counts.count(QualityFlags::SURFACE_HEIGHT_ADJUSTED)
+    }
+
+    /// Wall-clock time of the call in seconds.
+    #[getter]
+    fn elapsed_seconds(&self) -> f64 {
+        self.elapsed.as_secs_f64()
+    }
+
+    /// Number of points with each quality flag set, by the lowercase name of
+    /// the flag, for instance `surface_vapor_clamped` for the points whose
+    /// surface vapor pressure was clamped to saturation and
+    /// `surface_height_adjusted` for those whose surface was moved below the
+    /// first level above it. The flags are counted whether or not
+    /// `quality_flags` were requested.
+    #[getter]
+    fn counts<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let counts = PyDict::new(py);
+        for (name, count) in self.counts.iter() {
+            counts.set_item(name, count)?;
+        }
+        Ok(counts)
+    }
+
+    fn __repr__(&self) -> String {
+        let counts: Vec<_> = self
+            .counts
+            .iter()
+            .map(|(name, count)| format!("{name}={count}"))
+            .collect();
+        format!(
+            "Diagnostics(num_points={}, points_failed={}, points_skipped={}, \
+             elapsed_seconds={:.3}, {})",
+            self.num_points(),
+            self.num_failed,
+            self.num_skipped,
+            self.elapsed_seconds(),
+            counts.join(", ")
+        )
+    }
+}
+
+/// The floating-point output arrays, in one of the supported precisions.
+enum OutputArrays {
+    F32(Outputs<f32>),
+    F64(Outputs<f64>),
+}
+
+/// Evaluate `$body` with `$outputs` bound to the [`Outputs`] in `$arrays`,
+/// whatever its precision.
+macro_rules! with_outputs {
+    ($arrays:expr, |$outputs:ident| $body:expr) => {
+        match $arrays {
+            OutputArrays::F32($outputs) => $body,
+            OutputArrays::F64($outputs) => $body,
+        }
+    };
+}
+
+/// The floating-point output arrays for a precision `T`.
+struct Outputs<T> {
+    tran: Array2<T>,
+    tb_up: Array2<T>,
+    tb_down: Array2<T>,
+    tran_to_space: Option<Array3<T>>,
+    tb_up_above: Option<Array3<T>>,
+    t_eff_up: Option<Array2<T>>,
+    t_eff_down: Option<Array2<T>>,
+    tb_up_unnoised: Option<Array2<T>>,
+    air_mass_factor: Option<Array2<T>>,
+    tb_down_level: Option<Array2<T>>,
+}
+
+impl<T: PyFloat> Outputs<T> {
+    fn new(num_points: usize, num_freq: usize) -> Self {
+        Self {
+            tran: Array2::zeros([num_points, num_freq]),
+            tb_up: Array2::zeros([num_points, num_freq]),
+            tb_down: Array2::zeros([num_points, num_freq]),
+            tran_to_space: None,
+            tb_up_above: None,
+            t_eff_up: None,
+            t_eff_down: None,
+            tb_up_unnoised: None,
+            air_mass_factor: None,
+            tb_down_level: None,
+        }
+    }
+}
+
+/// A floating-point precision that can be used from Python.
+trait PyFloat: Float + Element {
+    /// Wrap the output arrays for this precision.
+    fn wrap(outputs: Outputs<Self>) -> OutputArrays;
+}
+
+impl PyFloat for f32 {
+    fn wrap(outputs: Outputs<Self>) -> OutputArrays {
+        OutputArrays::F32(outputs)
+    }
+}
+
+impl PyFloat for f64 {
+    fn wrap(outputs: Outputs<Self>) -> OutputArrays {
+        OutputArrays::F64(outputs)
+    }
+}
+
+/// The channels to compute the RTM for: each is a frequency and an incidence
+/// angle.
+///
+/// These are stored in double precision and converted to the precision of the
+/// profiles when they are used, so the same parameters can be used for many
+/// calls to `compute_rtm`.
+#[pyclass(name = "RtmParameters", frozen)]
+struct PyRtmParameters(RtmParameters<f64>);
+
+#[pymethods]
+impl PyRtmParameters {
+    /// Create the parameters from the microwave `frequency` in GHz and the
+    /// Earth `incidence_angle` in degrees of each channel. Both must have the
+    /// same non-zero length, and each incidence angle must be within 0° to
+    /// 90°, inclusive.
+    ///
+    /// Optionally, `frequency_offset` shifts the frequency of each channel by
+    /// that many MHz, for instance to correct for the drift of a channel's
+    /// passband centroid. The `frequency` property is then the shifted
+    /// frequencies.
+    #[new]
+    #[pyo3(signature = (frequency, incidence_angle, frequency_offset=None))]
+    fn new(
+        frequency: Vec<f64>,
+        incidence_angle: Vec<f64>,
+        frequency_offset: Option<Vec<f64>>,
+    ) -> PyResult<Self> {
+        let parameters = RtmParameters::new(&frequency, &incidence_angle)?;
+        Ok(Self(match frequency_offset {
+            Some(offset) => parameters.with_frequency_offset(&offset)?,
+            None => parameters,
+        }))
+    }
+
+    /// Create the parameters for a sweep of frequencies in GHz, all at the
+    /// same Earth `incidence_angle` in degrees, without building the array of
+    /// frequencies in Python.
+    ///
+    /// The frequencies are the same as from `numpy.arange(start, stop, step)`:
+    /// they go from `start` up to but not including `stop`, though as with
+    /// `numpy.arange`, rounding can add a frequency just past `stop` (for
+    /// instance, 1 to 1.3 in steps of 0.1 gives 1, 1.1, 1.2, and
+    /// 1.3000000000000003), so a `stop` between two steps is more predictable.
+    /// The step can be negative for a decreasing sweep. The range must be
+    /// finite and have at least one frequency.
+    #[staticmethod]
+    fn frequency_range(start: f64, stop: f64, step: f64, incidence_angle: f64) -> PyResult<Self> {
+        Ok(Self(RtmParameters::frequency_range(
+            start,
+            stop,
+            step,
+            incidence_angle,
+        )?))
+    }
+
+    /// Create the parameters for the `channels` of the cross-track sounder
+    /// `sensor` at the scan position `position`, with the Earth incidence
+    /// angle of that position.
+    ///
+    /// The `sensor` is `"msu"` or `"amsu_a"`. The channels are numbered from 1,
+    /// as in the instrument documentation, and the scan positions from 0, from
+    /// one edge of the swath to the other. See the Rust documentation of the
+    /// `scan_geometry` module for the tables.
+    #[staticmethod]
+    fn for_scan_position(sensor: &str, channels: Vec<usize>, position: usize) -> PyResult<Self> {
+        Ok(Self(RtmParameters::for_scan_position(
+            &parse_sensor(sensor)?,
+            &channels,
+            position,
+        )?))
+    }
+
+    /// Create the parameters from the passband of each of `channels`, made
+    /// with `Channel.from_srf`, and the Earth `incidence_angle` in degrees of
+    /// each channel.
+    ///
+    /// The outputs of `compute_rtm` for each channel, including those on
+    /// diagnostic levels, the effective temperatures, and the brightness
+    /// temperatures at the top of the atmosphere, are integrated over the
+    /// nodes of its passband. The `frequency` property is the centroid
+    /// frequency of each passband.
+    #[staticmethod]
+    fn from_channels(
+        channels: Vec<PyRef<'_, PyChannel>>,
+        incidence_angle: Vec<f64>,
+    ) -> PyResult<Self> {
+        let channels: Vec<_> = channels.iter().map(|channel| channel.0.clone()).collect();
+        Ok(Self(RtmParameters::from_channels(
+            &channels,
+            &incidence_angle,
+        )?))
+    }
+
+    /// Microwave frequency of each channel, in GHz.
+    #[getter]
+    fn frequency<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        self.0.frequencies().to_pyarray(py)
+    }
+
+    /// Earth incidence angle of each channel, in degrees.
+    #[getter]
+    fn incidence_angle<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        self.0.incidence_angles().to_pyarray(py)
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("RtmParameters({})", self.0)
+    }
+
+    /// Pickle the parameters as the constructor call that makes them again.
+    fn __reduce__<'py>(slf: &Bound<'py, Self>) -> PyResult<(Bound<'py, PyAny>, PyObject)> {
+        let py = slf.py();
+        let parameters = &slf.get().0;
+        let incidence_angle = parameters.incidence_angles().to_vec();
+        match parameters.passbands() {
+            Some(passbands) => {
+                let channels: Vec<_> = passbands.iter().cloned().map(PyChannel).collect();
+                Ok((
+                    slf.get_type().getattr("from_channels")?,
+                    (channels, incidence_angle).into_py_any(py)?,
+                ))
+            }
+            None => Ok((
+                slf.get_type().into_any(),
+                (parameters.frequencies().to_vec(), incidence_angle).into_py_any(py)?,
+            )),
+        }
+    }
+}
+
+impl PyRtmParameters {
+    /// Convert the parameters to the precision `T`.
+    fn to_precision<T: Float>(&self) -> Result<RtmParameters<T>, RtmError> {
+        let convert = |values: &[f64]| -> Vec<T> {
+            values
+                .iter()
+                .map(|&value| T::from_f64_lossy(value))
+                .collect()
+        };
+        let incidence = convert(self.0.incidence_angles());
+        match self.0.passbands() {
+            Some(passbands) => {
+                let channels = passbands
+                    .iter()
+                    .map(|channel| {
+                        Channel::from_quadrature(
+                            &convert(channel.nodes()),
+                            &convert(channel.weights()),
+                        )
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                RtmParameters::from_channels(&channels, &incidence)
+            }
+            None => RtmParameters::new(&convert(self.0.frequencies()), &incidence),
+        }
+    }
+}
+
+/// The model and numerical choices of `compute_rtm`, gathered so the same
+/// choices can be reused across calls and passed as its `options`.
+///
+/// Each option has the same meaning and default as the keyword of
+/// `compute_rtm` with the same name. The absorption scale factors are only
+/// options: they multiply the absorption of each absorber, by 1 by default,
+/// e.g., to fit the biases of observations. So is `profile_timing`, which
+/// outputs the compute time of each point as `profile_time`, and so is
+/// `down_level`, which outputs the downwelling at that level as
+/// `tb_down_level`, and so are `items_per_thread` and `work_chunk_size`,
+/// which choose how the points are split between the worker threads, and
+/// `layer_sum_reference`, the temperature that the layer emission is summed
+/// relative to: `"first_level"` above the surface (the default, as in the
+/// original Fortran), `"surface"`, or `"lowest_layer_mean"`. It cancels in the
+/// sums, so it only changes the outputs by rounding.
+#[pyclass(name = "RtmOptions", frozen)]
+#[derive(Debug, Clone, PartialEq)]
+struct PyRtmOptions {
+    rtm: RtmOptions,
+    top_pressure: Option<f64>,
+    quality_flags: bool,
+    effective_temperature: bool,
+    air_mass_factor: bool,
+    max_failure_logs: usize,
+    verbosity: Verbosity,
+    profile_timing: bool,
+    down_level: Option<usize>,
+}
+
+impl Default for PyRtmOptions {
+    fn default() -> Self {
+        Self {
+            rtm: RtmOptions::default(),
+            top_pressure: None,
+            quality_flags: false,
+            effective_temperature: false,
+            air_mass_factor: false,
+            max_failure_logs: 20,
+            verbosity: Verbosity::Progress,
+            profile_timing: false,
+            down_level: None,
+        }
+    }
+}
+
+#[pymethods]
+impl PyRtmOptions {
+    /// Create the options from keywords, with the same defaults as
+    /// `compute_rtm`.
+    #[new]
+    #[pyo3(signature = (*, emission="rayleigh_jeans", tb_convention=None, integration_scheme="trapezoidal", layer_sum_reference="first_level", clamp_surface_saturation=false, top_pressure=None, quality_flags=false, effective_temperature=false, air_mass_factor=false, max_failure_logs=20, verbosity="progress", oxygen_scale=1.0, water_vapor_line_scale=1.0, water_vapor_continuum_scale=1.0, cloud_scale=1.0, profile_timing=false, down_level=None, items_per_thread=rtm::DEFAULT_ITEMS_PER_THREAD, work_chunk_size=rtm::DEFAULT_WORK_CHUNK_SIZE))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        emission: &str,
+        tb_convention: Option<&str>,
+        integration_scheme: &str,
+        layer_sum_reference: &str,
+        clamp_surface_saturation: bool,
+        top_pressure: Option<f64>,
+        quality_flags: bool,
+        effective_temperature: bool,
+        air_mass_factor: bool,
+        max_failure_logs: usize,
+        verbosity: &str,
+        oxygen_scale: f64,
+        water_vapor_line_scale: f64,
+        water_vapor_continuum_scale: f64,
+        cloud_scale: f64,
+        profile_timing: bool,
+        down_level: Option<usize>,
+        items_per_thread: usize,
+        work_chunk_size: usize,
+    ) -> PyResult<Self> {
+        let positive = |value: usize, name: &str| {
+            NonZeroUsize::new(value)
+                .ok_or_else(|| PyValueError::new_err(format!("{name} must be positive")))
+        };
+        Ok(Self {
+            rtm: RtmOptions {
+                emission: parse_emission(emission)?,
+                tb_convention: tb_convention.map(parse_tb_convention).transpose()?,
+                integration_scheme: parse_integration_scheme(integration_scheme)?,
+                layer_sum_reference: parse_layer_sum_reference(layer_sum_reference)?,
+                clamp_surface_saturation,
+                absorption_scale: AbsorptionScale {
+                    oxygen: oxygen_scale,
+                    water_vapor_lines: water_vapor_line_scale,
+                    water_vapor_continuum: water_vapor_continuum_scale,
+                    cloud: cloud_scale,
+                },
+                items_per_thread: positive(items_per_thread, "items_per_thread")?,
+                work_chunk_size: positive(work_chunk_size, "work_chunk_size")?,
+                ..RtmOptions::default()
+            },
+            top_pressure,
+            quality_flags,
+            effective_temperature,
+            air_mass_factor,
+            max_failure_logs,
+            verbosity: parse_verbosity(verbosity)?,
+            profile_timing,
+            down_level,
+        })
+    }
+
+    /// How the emission of each layer is computed.
+    #[getter]
+    fn emission(&self) -> &'static str {
+        self.rtm.emission.name()
+    }
+
+    /// Convention of the output TBs, or `None` for the same as the emission.
+    #[getter]
+    fn tb_convention(&self) -> Option<&'static str> {
+        self.rtm.tb_convention.map(TbConvention::name)
+    }
+
+    /// How the layer emission is integrated.
+    #[getter]
+    fn integration_scheme(&self) -> &'static str {
+        self.rtm.integration_scheme.name()
+    }
+
+    /// The temperature that the layer emission is summed relative to.
+    #[getter]
+    fn layer_sum_reference(&self) -> &'static str {
+        self.rtm.layer_sum_reference.name()
+    }
+
+    /// Whether the surface vapor pressure is clamped to saturation.
+    #[getter]
+    fn clamp_surface_saturation(&self) -> bool {
+        self.rtm.clamp_surface_saturation
+    }
+
+    /// Pressure in hPa above which levels are discarded, or `None` for all levels.
+    #[getter]
+    fn top_pressure(&self) -> Option<f64> {
+        self.top_pressure
+    }
+
+    /// Whether the quality flags are output.
+    #[getter]
+    fn quality_flags(&self) -> bool {
+        self.quality_flags
+    }
+
+    /// Whether the effective temperatures are output.
+    #[getter]
+    fn effective_temperature(&self) -> bool {
+        self.effective_temperature
+    }
+
+    /// Whether the air-mass factors are output.
+    #[getter]
+    fn air_mass_factor(&self) -> bool {
+        self.air_mass_factor
+    }
+
+    /// Maximum number of warnings logged for failed points.
+    #[getter]
+    fn max_failure_logs(&self) -> usize {
+        self.max_failure_logs
+    }
+
+    /// How much a call logs about its progress.
+    #[getter]
+    fn verbosity(&self) -> &'static str {
+        self.verbosity.name()
+    }
+
+    /// Factor on the oxygen absorption.
+    #[getter]
+    fn oxygen_scale(&self) -> f64 {
+        self.rtm.absorption_scale.oxygen
+    }
+
+    /// Factor on the water vapor line absorption.
+    #[getter]
+    fn water_vapor_line_scale(&self) -> f64 {
+        self.rtm.absorption_scale.water_vapor_lines
+    }
+
+    /// Factor on the water vapor continuum absorption.
+    #[getter]
+    fn water_vapor_continuum_scale(&self) -> f64 {
+        self.rtm.absorption_scale.water_vapor_continuum
+    }
+
+    /// Factor on the cloud liquid water absorption.
+    #[getter]
+    fn cloud_scale(&self) -> f64 {
+        self.rtm.absorption_scale.cloud
+    }
+
+    /// Whether the compute time of each point is output.
+    #[getter]
+    fn profile_timing(&self) -> bool {
+        self.profile_timing
+    }
+
+    /// Index of the profile level whose downwelling is output, or `None`.
+    #[getter]
+    fn down_level(&self) -> Option<usize> {
+        self.down_level
+    }
+
+    /// Target number of (point, channel) pairs for each worker thread, which
+    /// chooses the number of threads when `num_threads` is `None`.
+    ///
+    /// The number of threads is the number of pairs divided by the target,
+    /// rounded up to a power of 2, and at most one for each CPU (or
+    /// `RAYON_NUM_THREADS`). A larger target uses fewer threads for small
+    /// batches, e.g., when many granules are run at once by a scheduler, while
+    /// large batches use every CPU either way. The default is 512.
+    #[getter]
+    fn items_per_thread(&self) -> usize {
+        self.rtm.items_per_thread.get()
+    }
+
+    /// Most points that a worker thread runs as one chunk of work.
+    ///
+    /// A thread can't hand off the rest of a chunk that it has started, so
+    /// smaller chunks balance the load better when some points are much more
+    /// expensive than the others, and a very large chunk size leaves the
+    /// splitting to Rayon. The outputs are the same for any chunk size. The
+    /// default is 32.
+    #[getter]
+    fn work_chunk_size(&self) -> usize {
+        self.rtm.work_chunk_size.get()
+    }
+
+    fn __eq__(&self, other: &Self) -> bool {
+        self == other
+    }
+
+    /// Pickle the options as the keywords of the constructor.
+    fn __getnewargs_ex__<'py>(&self, py: Python<'py>) -> PyResult<((), Bound<'py, PyDict>)> {
+        let kwargs = PyDict::new(py);
+        kwargs.set_item("emission", self.emission())?;
+        kwargs.set_item("tb_convention", self.tb_convention())?;
+        kwargs.set_item("integration_scheme", self.integration_scheme())?;
+        kwargs.set_item("layer_sum_reference", self.layer_sum_reference())?;
+        kwargs.set_item("clamp_surface_saturation", self.clamp_surface_saturation())?;
+        kwargs.set_item("top_pressure", self.top_pressure)?;
+        kwargs.set_item("quality_flags", self.quality_flags)?;
+        kwargs.set_item("effective_temperature", self.effective_temperature)?;
+        kwargs.set_item("air_mass_factor", self.air_mass_factor)?;
+        kwargs.set_item("max_failure_logs", self.max_failure_logs)?;
+        kwargs.set_item("verbosity", self.verbosity())?;
+        kwargs.set_item("oxygen_scale", self.oxygen_scale())?;
+        kwargs.set_item("water_vapor_line_scale", self.water_vapor_line_scale())?;
+        kwargs.set_item(
+            "water_vapor_continuum_scale",
+            self.water_vapor_continuum_scale(),
+        )?;
+        kwargs.set_item("cloud_scale", self.cloud_scale())?;
+        kwargs.set_item("profile_timing", self.profile_timing)?;
+        kwargs.set_item("down_level", self.down_level)?;
+        kwargs.set_item("items_per_thread", self.items_per_thread())?;
+        kwargs.set_item("work_chunk_size", self.work_chunk_size())?;
+        Ok(((), kwargs))
+    }
+
+    /// There's no state besides the keywords of the constructor.
+    fn __getstate__(&self) -> Option<()> {
+        None
+    }
+
+    fn __repr__(&self) -> String {
+        let top_pressure = self
+            .top_pressure
+            .map_or_else(|| "None".to_string(), |p| p.to_string());
+        let tb_convention = self
+            .tb_convention()
+            .map_or_else(|| "None".to_string(), |name| format!("'{name}'"));
+        let down_level = self
+            .down_level
+            .map_or_else(|| "None".to_string(), |level| level.to_string());
+        format!(
+            "RtmOptions(emission='{}', tb_convention={tb_convention}, integration_scheme='{}', \
+             layer_sum_reference='{}', clamp_surface_saturation={}, top_pressure={top_pressure}, quality_flags={}, \
+             effective_temperature={}, air_mass_factor={}, max_failure_logs={}, verbosity='{}', \
+             oxygen_scale={:?}, water_vapor_line_scale={:?}, water_vapor_continuum_scale={:?}, \
+             cloud_scale={:?}, profile_timing={}, down_level={down_level}, \
+             items_per_thread={}, work_chunk_size={})",
+            self.emission(),
+            self.integration_scheme(),
+            self.layer_sum_reference(),
+            python_bool(self.rtm.clamp_surface_saturation),
+            python_bool(self.quality_flags),
+            python_bool(self.effective_temperature),
+            python_bool(self.air_mass_factor),
+            self.max_failure_logs,
+            self.verbosity(),
+            self.oxygen_scale(),
+            self.water_vapor_line_scale(),
+            self.water_vapor_continuum_scale(),
+            self.cloud_scale(),
+            python_bool(self.profile_timing),
+            self.items_per_thread(),
+            self.work_chunk_size(),
+        )
+    }
+}
+
+impl PyRtmOptions {
+    /// Names of the options that differ from the defaults, of those that are
+    /// also keywords of `compute_rtm`.
+    fn non_default(&self) -> Vec<&'static str> {
+        let default = Self::default();
+        [
+            ("emission", self.rtm.emission != default.rtm.emission),
+            (
+                "tb_convention",
+                self.rtm.tb_convention != default.rtm.tb_convention,
+            ),
+            (
+                "integration_scheme",
+                self.rtm.integration_scheme != default.rtm.integration_scheme,
+            ),
+            (
+                "clamp_surface_saturation",
+                self.rtm.clamp_surface_saturation != default.rtm.clamp_surface_saturation,
+            ),
+            ("top_pressure", self.top_pressure != default.top_pressure),
+            ("quality_flags", self.quality_flags != default.quality_flags),
+            (
+                "effective_temperature",
+                self.effective_temperature != default.effective_temperature,
+            ),
+            (
+                "air_mass_factor",
+                self.air_mass_factor != default.air_mass_factor,
+            ),
+            (
+                "max_failure_logs",
+                self.max_failure_logs != default.max_failure_logs,
+            ),
+            ("verbosity", self.verbosity != default.verbosity),
+        ]
+        .into_iter()
+        .filter_map(|(name, differs)| differs.then_some(name))
+        .collect()
+    }
+}
+
+/// Format a bool as Python does.
+fn python_bool(value: bool) -> &'static str {
+    if value {
+        "True"
+    } else {
+        "False"
+    }
+}
+
+/// The passband of a channel, as frequency nodes in GHz and their quadrature
+/// weights, which sum to 1. Use `RtmParameters.from_channels` to compute the
+/// RTM for these channels.
+#[pyclass(name = "Channel", frozen)]
+struct PyChannel(Channel<f64>);
+
+#[pymethods]
+impl PyChannel {
+    /// Create a channel from a measured spectral response function: the
+    /// relative `response` at each of `frequency` in GHz, which must be
+    /// strictly increasing.
+    ///
+    /// The response is linear between the tabulated frequencies, and it's
+    /// resampled to at most `num_nodes` quadrature nodes: the range of the
+    /// frequencies is split into `num_nodes` bins of equal width, and each bin
+    /// with a non-zero response becomes a node at its response-weighted mean
+    /// frequency, weighted by the integral of the response over it. A single
+    /// frequency is a delta function, i.e., a monochromatic channel.
+    #[staticmethod]
+    #[pyo3(signature = (frequency, response, num_nodes=16))]
+    fn from_srf(
+        frequency: PyReadonlyArray1<'_, f64>,
+        response: PyReadonlyArray1<'_, f64>,
+        num_nodes: usize,
+    ) -> PyResult<Self> {
+        Ok(Self(Channel::from_srf(
+            frequency.as_slice()?,
+            response.as_slice()?,
+            num_nodes,
+        )?))
+    }
+
+    /// Create a channel from the frequency `nodes` in GHz of its quadrature
+    /// and their `weights`, as in the `nodes` and `weights` properties. The
+    /// weights are normalized to sum to 1.
+    #[staticmethod]
+    fn from_quadrature(nodes: Vec<f64>, weights: Vec<f64>) -> PyResult<Self> {
+        Ok(Self(Channel::from_quadrature(&nodes, &weights)?))
+    }
+
+    /// Frequency of each quadrature node, in GHz.
+    #[getter]
+    fn nodes<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        self.0.nodes().to_pyarray(py)
+    }
+
+    /// Weight of each quadrature node. These sum to 1.
+    #[getter]
+    fn weights<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f64>> {
+        self.0.weights().to_pyarray(py)
+    }
+
+    /// Centroid frequency of the passband, in GHz.
+    #[getter]
+    fn centroid(&self) -> f64 {
+        self.0.centroid()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Channel(centroid={} GHz, {} nodes)",
+            self.0.centroid(),
+            self.0.nodes().len()
+        )
+    }
+
+    /// Pickle the channel as its quadrature.
+    fn __reduce__<'py>(slf: &Bound<'py, Self>) -> PyResult<(Bound<'py, PyAny>, PyObject)> {
+        let channel = &slf.get().0;
+        Ok((
+            slf.get_type().getattr("from_quadrature")?,
+            (channel.nodes().to_vec(), channel.weights().to_vec()).into_py_any(slf.py())?,
+        ))
+    }
+}
+
+/// Implement all the "getters" for the Python properties
+#[pymethods]
+impl AtmoParameters {
+    #[getter]
+    fn tran<'py>(&self, py: Python<'py>) -> Bound<'py, PyAny> {
+        with_outputs!(&self.arrays, |o| o.tran.to_pyarray(py).into_any())
+    }
+
+    #[getter]
+    fn tb_up<'py>(&self, py: Python<'py>) -> Bound<'py, PyAny> {
+        with_outputs!(&self.arrays, |o| o.tb_up.to_pyarray(py).into_any())
+    }
+
+    #[getter]
+    fn tb_down<'py>(&self, py: Python<'py>) -> Bound<'py, PyAny> {
+        with_outputs!(&self.arrays, |o| o.tb_down.to_pyarray(py).into_any())
+    }
+
+    #[getter]
+    fn tran_to_space<'py>(&self, py: Python<'py>) -> Option<Bound<'py, PyAny>> {
+        with_outputs!(&self.arrays, |o| o
+            .tran_to_space
+            .as_ref()
+            .map(|a| a.to_pyarray(py).into_any()))
+    }
+
+    #[getter]
+    fn tb_up_above<'py>(&self, py: Python<'py>) -> Option<Bound<'py, PyAny>> {
+        with_outputs!(&self.arrays, |o| o
+            .tb_up_above
+            .as_ref()
+            .map(|a| a.to_pyarray(py).into_any()))
+    }
+
+    #[getter]
+    fn t_eff_up<'py>(&self, py: Python<'py>) -> Option<Bound<'py, PyAny>> {
+        with_outputs!(&self.arrays, |o| o
+            .t_eff_up
+            .as_ref()
+            .map(|a| a.to_pyarray(py).into_any()))
+    }
+
+    #[getter]
+    fn t_eff_down<'py>(&self, py: Python<'py>) -> Option<Bound<'py, PyAny>> {
+        with_outputs!(&self.arrays, |o| o
+            .t_eff_down
+            .as_ref()
+            .map(|a| a.to_pyarray(py).into_any()))
+    }
+
+    #[getter]
+    fn tb_up_unnoised<'py>(&self, py: Python<'py>) -> Option<Bound<'py, PyAny>> {
+        with_outputs!(&self.arrays, |o| o
+            .tb_up_unnoised
+            .as_ref()
+            .map(|a| a.to_pyarray(py).into_any()))
+    }
+
+    #[getter]
+    fn air_mass_factor<'py>(&self, py: Python<'py>) -> Option<Bound<'py, PyAny>> {
+        with_outputs!(&self.arrays, |o| o
+            .air_mass_factor
+            .as_ref()
+            .map(|a| a.to_pyarray(py).into_any()))
+    }
+
+    #[getter]
+    fn tb_down_level<'py>(&self, py: Python<'py>) -> Option<Bound<'py, PyAny>> {
+        with_outputs!(&self.arrays, |o| o
+            .tb_down_level
+            .as_ref()
+            .map(|a| a.to_pyarray(py).into_any()))
+    }
+
+    #[getter]
+    fn quality_flags<'py>(&self, py: Python<'py>) -> Option<Bound<'py, PyArray1<u32>>> {
+        self.quality_flags.as_ref().map(|a| a.to_pyarray(py))
+    }
+
+    #[getter]
+    fn num_levels_integrated<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<i32>> {
+        self.num_levels_integrated.to_pyarray(py)
+    }
+
+    #[getter]
+    fn profile_time<'py>(&self, py: Python<'py>) -> Option<Bound<'py, PyArray1<f32>>> {
+        self.profile_time.as_ref().map(|a| a.to_pyarray(py))
+    }
+
+    #[getter]
+    fn diagnostics(&self) -> Diagnostics {
+        self.diagnostics.clone()
+    }
+
+    /// Units and provenance of the outputs.
+    ///
+    /// This is the same as `get_model_info()`, with the addition of `units`,
+    /// which maps each output field to its units, `emission`, the emission
+    /// convention used for the radiative transfer, and `tb_convention`, the
+    /// convention of the output brightness temperatures.
+    fn metadata<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let units = PyDict::new(py);
+        units.set_item("tran", "1")?;
+        units.set_item("tb_up", "K")?;
+        units.set_item("tb_down", "K")?;
+        if with_outputs!(&self.arrays, |o| o.tran_to_space.is_some()) {
+            units.set_item("tran_to_space", "1")?;
+        }
+        if with_outputs!(&self.arrays, |o| o.tb_up_above.is_some()) {
+            units.set_item("tb_up_above", "K")?;
+        }
+        if with_outputs!(&self.arrays, |o| o.t_eff_up.is_some()) {
+            units.set_item("t_eff_up", "K")?;
+            units.set_item("t_eff_down", "K")?;
+        }
+        if with_outputs!(&self.arrays, |o| o.tb_up_unnoised.is_some()) {
+            units.set_item("tb_up_unnoised", "K")?;
+        }
+        if with_outputs!(&self.arrays, |o| o.air_mass_factor.is_some()) {
+            units.set_item("air_mass_factor", "1")?;
+        }
+        if with_outputs!(&self.arrays, |o| o.tb_down_level.is_some()) {
+            units.set_item("tb_down_level", "K")?;
+        }
+        if self.quality_flags.is_some() {
+            units.set_item("quality_flags", "1")?;
+        }
+        units.set_item("num_levels_integrated", "1")?;
+        if self.profile_time.is_some() {
+            units.set_item("profile_time", "us")?;
+        }
+
+        let metadata = get_model_info(py)?;
+        metadata.set_item("units", units)?;
+        metadata.set_item("emission", self.emission.name())?;
+        metadata.set_item("tb_convention", self.tb_convention.name())?;
+        Ok(metadata)
+    }
+
+    /// The outputs as flat records, with one record for each point and
+    /// channel.
+    ///
+    /// The records are returned as a dict of 1d columns, each dimensioned as
+    /// (`num_points` * `num_freq`, ), with the channels varying fastest. The
+    /// columns are `point` and `channel`, the indices of the record, followed
+    /// by `tran`, `tb_up`, `tb_down`, `t_eff_up` and `t_eff_down` (if they
+    /// were requested), `quality_flags` (if it was requested), and
+    /// `num_levels_integrated`, where the last two are repeated for each
+    /// channel of a point. This is the layout of a table, so it can be passed
+    /// directly to, e.g., `pyarrow.table()`. The diagnostic level outputs are not
+    /// included.
+    fn flat_records<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let (num_points, num_freq) = with_outputs!(&self.arrays, |o| o.tran.dim());
+        let records = PyDict::new(py);
+        records.set_item(
+            "point",
+            Array1::from_shape_fn(num_points * num_freq, |i| (i / num_freq) as u64)
+                .into_pyarray(py),
+        )?;
+        records.set_item(
+            "channel",
+            Array1::from_shape_fn(num_points * num_freq, |i| (i % num_freq) as u64)
+                .into_pyarray(py),
+        )?;
+        with_outputs!(&self.arrays, |o| {
+            records.set_item("tran", o.tran.flatten().to_pyarray(py))?;
+            records.set_item("tb_up", o.tb_up.flatten().to_pyarray(py))?;
+            records.set_item("tb_down", o.tb_down.flatten().to_pyarray(py))?;
+            if let (Some(t_eff_up), Some(t_eff_down)) = (&o.t_eff_up, &o.t_eff_down) {
+                records.set_item("t_eff_up", t_eff_up.flatten().to_pyarray(py))?;
+                records.set_item("t_eff_down", t_eff_down.flatten().to_pyarray(py))?;
+            }
+        });
+        if let Some(quality_flags) = &self.quality_flags {
+            let repeated =
+                Array1::from_shape_fn(num_points * num_freq, |i| quality_flags[i / num_freq]);
+            records.set_item("quality_flags", repeated.into_pyarray(py))?;
+        }
+        let repeated = Array1::from_shape_fn(num_points * num_freq, |i| {
+            self.num_levels_integrated[i / num_freq]
+        });
+        records.set_item("num_levels_integrated", repeated.into_pyarray(py))?;
+        Ok(records)
+    }
+
+    /// Write the outputs to a Parquet file at `path`, with coordinates of the
+    /// points.
+    ///
+    /// Optionally, `coords` is a dict of 1d arrays with shape (`num_points`,
+    /// ), such as the time, latitude, longitude, and scan position of each
+    /// point, which are written first, in order. They can be any numeric or
+    /// bool dtype, or `datetime64` in s, ms, us, or ns. The per-point outputs
+    /// (`quality_flags`, `num_levels_integrated`, and `profile_time`, if they
+    /// were requested) follow them, and then the outputs of each channel.
+    ///
+    /// With `layout="wide"`, there's a row for each point and a column for
+    /// each output and channel, named `{output}_{channel}`, e.g., `tb_up_0`.
+    /// With `layout="long"`, there's a row for each point and channel, as for
+    /// `flat_records()`, with `point` and `channel` columns and the per-point
+    /// columns repeated for each channel. The diagnostic level outputs are not
+    /// included.
+    #[cfg(feature = "arrow")]
+    #[pyo3(signature = (path, coords=None, layout="wide"))]
+    fn to_parquet(
+        &self,
+        path: std::path::PathBuf,
+        coords: Option<&Bound<'_, PyDict>>,
+        layout: &str,
+    ) -> PyResult<()> {
+        use rtm::parquet::{record_batch, write_parquet, Layout};
+
+        let layout = Layout::from_name(layout).ok_or_else(|| {
+            PyValueError::new_err(format!(
+                "unknown layout '{layout}', expected 'wide' or 'long'"
+            ))
+        })?;
+        let mut point_columns = Vec::new();
+        for (name, values) in coords.into_iter().flatten() {
+            point_columns.push((name.extract::<String>()?, arrow_coordinate(&values)?));
+        }
+        if let Some(quality_flags) = &self.quality_flags {
+            point_columns.push((
+                "quality_flags".to_string(),
+                Arc::new(arrow_array::UInt32Array::from(quality_flags.to_vec())) as _,
+            ));
+        }
+        point_columns.push((
+            "num_levels_integrated".to_string(),
+            Arc::new(arrow_array::Int32Array::from(
+                self.num_levels_integrated.to_vec(),
+            )) as _,
+        ));
+        if let Some(profile_time) = &self.profile_time {
+            point_columns.push((
+                "profile_time".to_string(),
+                Arc::new(arrow_array::Float32Array::from(profile_time.to_vec())) as _,
+            ));
+        }
+
+        let batch = with_outputs!(&self.arrays, |o| {
+            let optional = [
+                ("t_eff_up", &o.t_eff_up),
+                ("t_eff_down", &o.t_eff_down),
+                ("tb_up_unnoised", &o.tb_up_unnoised),
+                ("air_mass_factor", &o.air_mass_factor),
+                ("tb_down_level", &o.tb_down_level),
+            ];
+            let outputs: Vec<_> = [
+                ("tran", &o.tran),
+                ("tb_up", &o.tb_up),
+                ("tb_down", &o.tb_down),
+            ]
+            .into_iter()
+            .chain(
+                optional
+                    .into_iter()
+                    .filter_map(|(name, array)| Some((name, array.as_ref()?))),
+            )
+            .map(|(name, array)| (name.to_string(), array.view()))
+            .collect();
+            record_batch(&point_columns, &outputs, layout)?
+        });
+        write_parquet(&path, &batch)?;
+        Ok(())
+    }
+}
+
+/// Convert a coordinate of `AtmoParameters.to_parquet` to an Arrow array.
+#[cfg(feature = "arrow")]
+fn arrow_coordinate(values: &Bound<'_, PyAny>) -> PyResult<arrow_array::ArrayRef> {
+    use arrow_array::{
+        ArrayRef, BooleanArray, Float32Array, Float64Array, Int16Array, Int32Array, Int64Array,
+        Int8Array, TimestampMicrosecondArray, TimestampMillisecondArray, TimestampNanosecondArray,
+        TimestampSecondArray, UInt16Array, UInt32Array, UInt64Array, UInt8Array,
+    };
+
+    let numpy = values.py().import("numpy")?;
+    let values = numpy.call_method1("asarray", (values,))?;
+    if values.getattr("ndim")?.extract::<usize>()? != 1 {
+        return Err(PyValueError::new_err("the coords must be 1d arrays"));
+    }
+    let dtype = values.getattr("dtype")?;
+    if dtype.getattr("kind")?.extract::<String>()? == "M" {
+        let (unit, count): (String, usize) =
+            numpy.call_method1("datetime_data", (&dtype,))?.extract()?;
+        // NaT is the smallest int64, and is written as null
+        let ticks: Vec<Option<i64>> = values
+            .call_method1("view", ("int64",))?
+            .extract::<PyReadonlyArray1<'_, i64>>()?
+            .as_array()
+            .iter()
+            .map(|&tick| (tick != i64::MIN).then_some(tick))
+            .collect();
+        return match (unit.as_str(), count) {
+            ("s", 1) => Ok(Arc::new(TimestampSecondArray::from(ticks))),
+            ("ms", 1) => Ok(Arc::new(TimestampMillisecondArray::from(ticks))),
+            ("us", 1) => Ok(Arc::new(TimestampMicrosecondArray::from(ticks))),
+            ("ns", 1) => Ok(Arc::new(TimestampNanosecondArray::from(ticks))),
+            _ => Err(PyValueError::new_err(format!(
+                "unsupported datetime64 unit '{unit}', expected s, ms, us, or ns"
+            ))),
+        };
+    }
+
+    macro_rules! convert {
+        ($($element:ty => $array:ty),* $(,)?) => {
+            $(
+                if let Ok(values) = values.extract::<PyReadonlyArray1<'_, $element>>() {
+                    let values: Vec<$element> = values.as_array().to_vec();
+                    return Ok(Arc::new(<$array>::from(values)) as ArrayRef);
+                }
+            )*
+        };
+    }
+    convert!(
+        f64 => Float64Array,
+        f32 => Float32Array,
+        i64 => Int64Array,
+        i32 => Int32Array,
+        i16 => Int16Array,
+        i8 => Int8Array,
+        u64 => UInt64Array,
+        u32 => UInt32Array,
+        u16 => UInt16Array,
+        u8 => UInt8Array,
+        bool => BooleanArray,
+    );
+    Err(PyValueError::new_err(format!(
+        "unsupported dtype for a coordinate: {dtype}"
+    )))
+}
+
+/// The pressure levels: either 1d and shared by all points, or 2d with a row
+/// for each point.
+enum PressureArray<'py, T: Element> {
+    Shared(PyReadonlyArray1<'py, T>),
+    PerPoint(PyReadonlyArray2<'py, T>),
+}
+
+impl<'py, T: Element> FromPyObject<'py> for PressureArray<'py, T> {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        match ob.extract() {
+            Ok(pressure) => Ok(Self::Shared(pressure)),
+            Err(_) => Ok(Self::PerPoint(ob.extract()?)),
+        }
+    }
+}
+
+/// Check the shapes of the input arrays and borrow them as the profiles for
+/// all points.
+///
+/// The 2d arrays (including the pressure, if it's 2d) must all be
+/// (`num_points`, `num_levels`), and the 1d surface arrays (including the
+/// optional surface pressure, skin temperature, and observer height) must all
+/// be (`num_points`, ).
+#[allow(clippy::too_many_arguments)]
+fn profiles<'a, T: PyFloat>(
+    pressure: &'a PressureArray<'_, T>,
+    temperature: &'a PyReadonlyArray2<'_, T>,
+    height: &'a PyReadonlyArray2<'_, T>,
+    specific_humidity: &'a PyReadonlyArray2<'_, T>,
+    liquid_content: Option<&'a PyReadonlyArray2<'_, T>>,
+    surface_temperature: &'a PyReadonlyArray1<'_, T>,
+    surface_height: &'a PyReadonlyArray1<'_, T>,
+    surface_dewpoint: &'a PyReadonlyArray1<'_, T>,
+    surface_pressure: Option<&'a PyReadonlyArray1<'_, T>>,
+    skin_temperature: Option<&'a PyReadonlyArray1<'_, T>>,
+    observer_height: Option<&'a PyReadonlyArray1<'_, T>>,
+    top_pressure: Option<T>,
+) -> PyResult<ArrayProfiles<'a, T>> {
+    let surface_temperature = surface_temperature.as_slice()?;
+    let surface_height = surface_height.as_slice()?;
+    let surface_dewpoint = surface_dewpoint.as_slice()?;
+    let surface_pressure = surface_pressure.map(|p| p.as_slice()).transpose()?;
+    let skin_temperature = skin_temperature.map(|t| t.as_slice()).transpose()?;
+    let observer_height = observer_height.map(|z| z.as_slice()).transpose()?;
+    // Clear sky is an empty row for each point
+    let liquid_content = match liquid_content {
+        Some(liquid_content) => liquid_content.as_array(),
+        None => temperature.as_array().slice_move(s![.., ..0]),
+    };
+    let profiles = match pressure {
+        PressureArray::Shared(pressure) => ArrayProfiles::new(
+            pressure.as_slice()?,
+            temperature.as_array(),
+            height.as_array(),
+            specific_humidity.as_array(),
+            liquid_content,
+            surface_temperature,
+            surface_height,
+            surface_dewpoint,
+            surface_pressure,
+            skin_temperature,
+            observer_height,
+            top_pressure,
+        ),
+        PressureArray::PerPoint(pressure) => ArrayProfiles::new_per_point_pressure(
+            pressure.as_array(),
+            temperature.as_array(),
+            height.as_array(),
+            specific_humidity.as_array(),
+            liquid_content,
+            surface_temperature,
+            surface_height,
+            surface_dewpoint,
+            surface_pressure,
+            skin_temperature,
+            observer_height,
+            top_pressure,
+        ),
+    }?;
+    debug!("input shapes are consistent");
+    Ok(profiles)
+}
+
+/// Prepare the RTM inputs for each point of `profiles` in parallel, in chunks
+/// of at most the `work_chunk_size` of `options`.
+///
+/// Once `cancelled` is set, the remaining points are not prepared and are
+/// instead the [`RtmError::Cancelled`] error.
+fn par_inputs<'a, T: PyFloat, S: ProfileSource<T>>(
+    profiles: &'a S,
+    options: &RtmOptions,
+    cancelled: &'a AtomicBool,
+) -> impl IndexedParallelIterator<Item = Result<RtmInputs<T>, RtmError>> + 'a {
+    par_timed_inputs(profiles, options, cancelled, false).map(|(_, inputs)| inputs)
+}
+
+/// Prepare the RTM inputs for each point of `profiles` in parallel, as in
+/// [`par_inputs`], along with the time the point was started if `timed`.
+///
+/// Rayon runs the rest of the chain for a point on the same thread right
+/// after it's prepared, so [`elapsed_us`] of the start time at the end of the
+/// chain is the compute time of the point, for load-balancing analysis.
+fn par_timed_inputs<'a, T: PyFloat, S: ProfileSource<T>>(
+    profiles: &'a S,
+    options: &RtmOptions,
+    cancelled: &'a AtomicBool,
+    timed: bool,
+) -> impl IndexedParallelIterator<Item = (Option<Instant>, Result<RtmInputs<T>, RtmError>)> + 'a {
+    (0..profiles.len())
+        .into_par_iter()
+        .with_max_len(options.work_chunk_size.get())
+        .map(move |point| {
+            let start = timed.then(Instant::now);
+            if cancelled.load(Ordering::Relaxed) {
+                return (start, Err(RtmError::Cancelled));
+            }
+            (
+                start,
+                profiles.profile(point).and_then(|profile| profile.inputs()),
+            )
+        })
+}
+
+/// Time since `start` in microseconds, if the point was timed.
+fn elapsed_us(start: Option<Instant>) -> Option<f32> {
+    start.map(|start| start.elapsed().as_secs_f32() * 1e6)
+}
+
+/// Log a warning for each point in `results` that failed, with its index, the
+/// kind of error, and the surface inputs from `profiles`, so the bad points
+/// can be found from the logs alone.
+///
+/// Only the first `max_messages` failures are logged, so that a completely bad
+/// input doesn't flood the logs, and a final line gives the number of failed
+/// points and of suppressed messages. Points that were cancelled are skipped,
+/// since they didn't fail themselves.
+fn log_failures<T: Float, R>(
+    results: &[Result<R, RtmError>],
+    profiles: &impl ProfileSource<T>,
+    max_messages: usize,
+) {
+    let mut num_failed = 0;
+    for (point, result) in results.iter().enumerate() {
+        let Err(e) = result else {
+            continue;
+        };
+        if matches!(e, RtmError::Cancelled) {
+            continue;
+        }
+        num_failed += 1;
+        if num_failed > max_messages {
+            continue;
+        }
+        match profiles.profile(point) {
+            Ok(profile) => warn!(
+                "RTM failed for point={point} kind={} surface_pressure={} \
+                 surface_temperature={}: {e}",
+                e.kind(),
+                // The first level is the surface if there's no surface pressure
+                profile
+                    .surface_pressure
+                    .or(profile.pressure.first().copied())
+                    .unwrap_or(T::nan()),
+                profile.surface_temperature,
+            ),
+            Err(_) => warn!("RTM failed for point={point} kind={}: {e}", e.kind()),
+        }
+    }
+    if num_failed > 0 {
+        let num_suppressed = num_failed.saturating_sub(max_messages);
+        warn!(
+            "RTM failed for {num_failed}/{} points ({num_suppressed} messages suppressed, \
+             max_failure_logs={max_messages})",
+            results.len()
+        );
+    }
+}
+
+/// Check the channel arrays and build the RTM parameters.
+fn parameters<T: PyFloat>(
+    frequency: &PyReadonlyArray1<'_, T>,
+    incidence_angle: &PyReadonlyArray1<'_, T>,
+) -> PyResult<RtmParameters<T>> {
+    if incidence_angle.len() != frequency.len() {
+        return Err(RtmError::InconsistentInputs.into());
+    }
+    Ok(RtmParameters::new(
+        frequency.as_slice()?,
+        incidence_angle.as_slice()?,
+    )?)
+}
+
+/// Look up a cross-track sounder by name.
+fn parse_sensor(name: &str) -> PyResult<ScanSensor> {
+    scan_geometry::by_name(name).ok_or_else(|| {
+        let names: Vec<_> = scan_geometry::ALL.iter().map(|s| s.name).collect();
+        PyValueError::new_err(format!(
+            "unknown sensor \"{name}\", expected one of: {}",
+            names.join(", ")
+        ))
+    })
+}
+
+/// Check that each of the `num_points` points has a scan position of `sensor`.
+fn scan_positions(
+    sensor: &ScanSensor,
+    scan_position: &PyReadonlyArray1<'_, i64>,
+    num_points: usize,
+) -> PyResult<Vec<usize>> {
+    if scan_position.len() != num_points {
+        return Err(RtmError::InconsistentInputs.into());
+    }
+    let positions = scan_position
+        .as_array()
+        .iter()
+        .map(|&position| scan_position_index(sensor, position))
+        .collect::<Result<_, _>>()?;
+    Ok(positions)
+}
+
+/// Convert a scan position of `sensor` to an index into its tables.
+fn scan_position_index(sensor: &ScanSensor, position: i64) -> Result<usize, RtmError> {
+    let index = usize::try_from(position).map_err(|_| {
+        RtmError::InvalidScanGeometry(format!("scan position {position} is negative"))
+    })?;
+    sensor.incidence_angle(index)?;
+    Ok(index)
+}
+
+/// Parse the name of an emission convention.
+fn parse_emission(name: &str) -> PyResult<Emission> {
+    match name {
+        _ if name == Emission::RayleighJeans.name() => Ok(Emission::RayleighJeans),
+        _ if name == Emission::Planck.name() => Ok(Emission::Planck),
+        _ => Err(PyValueError::new_err(format!(
+            "unknown emission \"{name}\", expected \"rayleigh_jeans\" or \"planck\""
+        ))),
+    }
+}
+
+/// Parse the name of a layer integration scheme.
+fn parse_integration_scheme(name: &str) -> PyResult<IntegrationScheme> {
+    match name {
+        _ if name == IntegrationScheme::Trapezoidal.name() => Ok(IntegrationScheme::Trapezoidal),
+        _ if name == IntegrationScheme::LinearInOpticalDepth.name() => {
+            Ok(IntegrationScheme::LinearInOpticalDepth)
+        }
+        _ => Err(PyValueError::new_err(format!(
+            "unknown integration_scheme \"{name}\", expected \"trapezoidal\" or \"linear_in_optical_depth\""
+        ))),
+    }
+}
+
+/// Parse the name of a reference temperature of the layer sums.
+fn parse_layer_sum_reference(name: &str) -> PyResult<LayerSumReference> {
+    [
+        LayerSumReference::FirstLevel,
+        LayerSumReference::Surface,
+        LayerSumReference::LowestLayerMean,
+    ]
+    .into_iter()
+    .find(|reference| reference.name() == name)
+    .ok_or_else(|| {
+        PyValueError::new_err(format!(
+            "unknown layer_sum_reference \"{name}\", expected \"first_level\", \"surface\" \
+             or \"lowest_layer_mean\""
+        ))
+    })
+}
+
+/// How much a call logs about its progress, independent of the level of the
+/// Python logger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Verbosity {
+    /// Only warnings and the summary at the end of the call.
+    Quiet,
+    /// Also the progress of the points every 5 seconds.
+    Progress,
+    /// Also the debug messages of each phase of the call.
+    Debug,
+}
+
+impl Verbosity {
+    /// The name of the verbosity, as parsed by [`parse_verbosity`].
+    fn name(self) -> &'static str {
+        match self {
+            Verbosity::Quiet => "quiet",
+            Verbosity::Progress => "progress",
+            Verbosity::Debug => "debug",
+        }
+    }
+}
+
+/// Parse the name of a log verbosity.
+fn parse_verbosity(name: &str) -> PyResult<Verbosity> {
+    match name {
+        "quiet" => Ok(Verbosity::Quiet),
+        "progress" => Ok(Verbosity::Progress),
+        "debug" => Ok(Verbosity::Debug),
+        _ => Err(PyValueError::new_err(format!(
+            "unknown verbosity \"{name}\", expected \"quiet\", \"progress\", or \"debug\""
+        ))),
+    }
+}
+
+/// Parse the name of a polarization.
+fn parse_polarization(name: &str) -> PyResult<Polarization> {
+    match name {
+        _ if name == Polarization::Vertical.name() => Ok(Polarization::Vertical),
+        _ if name == Polarization::Horizontal.name() => Ok(Polarization::Horizontal),
+        _ => Err(PyValueError::new_err(format!(
+            "unknown polarization \"{name}\", expected \"v\" or \"h\""
+        ))),
+    }
+}
+
+/// Parse the name of a reflection model, with the quadrature order for a
+/// Lambertian surface.
+fn parse_reflection(name: &str, quadrature_order: usize) -> PyResult<Reflection> {
+    match name {
+        _ if name == Reflection::Specular.name() => Ok(Reflection::Specular),
+        _ if name == (Reflection::Lambertian { quadrature_order }).name() => {
+            if quadrature_order == 0 {
+                return Err(PyValueError::new_err("quadrature_order must be at least 1"));
+            }
+            Ok(Reflection::Lambertian { quadrature_order })
+        }
+        _ => Err(PyValueError::new_err(format!(
+            "unknown reflection \"{name}\", expected \"specular\" or \"lambertian\""
+        ))),
+    }
+}
+
+/// Parse the name of a model of the wind-roughened ocean.
+fn parse_ocean_model(name: &str) -> PyResult<OceanModel> {
+    [OceanModel::Rough, OceanModel::SpecularFoam]
+        .into_iter()
+        .find(|model| model.name() == name)
+        .ok_or_else(|| {
+            PyValueError::new_err(format!(
+                "unknown ocean model \"{name}\", expected \"rough\" or \"specular_foam\""
+            ))
+        })
+}
+
+/// Parse the name of a brightness temperature convention.
+fn parse_tb_convention(name: &str) -> PyResult<TbConvention> {
+    match name {
+        _ if name == TbConvention::RayleighJeans.name() => Ok(TbConvention::RayleighJeans),
+        _ if name == TbConvention::Planck.name() => Ok(TbConvention::Planck),
+        _ => Err(PyValueError::new_err(format!(
+            "unknown tb_convention \"{name}\", expected \"rayleigh_jeans\" or \"planck\""
+        ))),
+    }
+}
+
+/// A Python callable used as the absorption model, as for the
+/// `absorption_model` argument of [`compute_rtm`].
+struct PyAbsorptionModel<'a> {
+    callable: Py<PyAny>,
+    /// Set when the callable raises, to stop work on the remaining points.
+    cancelled: &'a AtomicBool,
+    /// The first exception raised by the callable, if any.
+    error: Mutex<Option<PyErr>>,
+}
+
+impl<T: PyFloat> AbsorptionModel<T> for PyAbsorptionModel<'_> {
+    fn layer_absorption(
+        &self,
+        pressure: T,
+        temperature: T,
+        vapor_pressure: T,
+        liquid_water_density: T,
+        frequency: T,
+    ) -> T {
+        let args = (
+            pressure.into_f64(),
+            temperature.into_f64(),
+            vapor_pressure.into_f64(),
+            liquid_water_density.into_f64(),
+            frequency.into_f64(),
+        );
+        let result = Python::with_gil(|py| self.callable.call1(py, args)?.extract::<f64>(py));
+        match result {
+            Ok(absorption) => T::from_f64_lossy(absorption),
+            Err(e) => {
+                self.cancelled.store(true, Ordering::Relaxed);
+                self.error
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .get_or_insert(e);
+                T::nan()
+            }
+        }
+    }
+}
+
+/// Evaluate the per-point `results` in parallel and collect them.
+///
+/// The work is done in the pool of `num_threads` worker threads from
+/// [`thread_pool`], or if it's `None`, about the [`rtm::adaptive_num_threads`]
+/// for the points with `num_freq` channels and the `items_per_thread` of
+/// `options`. Meanwhile, the calling thread periodically logs progress
+/// (unless `verbosity` is [`Verbosity::Quiet`]) and checks for Python signals. When a signal is raised, `cancelled` is set,
+/// and `results` is expected to stop doing work for any remaining points (see
+/// [`par_inputs`]). The results are in the same order as the points.
+///
+/// The callers copy the results to the output arrays by their position in the
+/// returned `Vec`, so this order is what ties each output row to its input
+/// profile. It holds since `results` is an *indexed* parallel iterator and
+/// `collect_into_vec` writes each item to its own index, no matter which
+/// thread computes it or when it finishes. Collecting with an unindexed
+/// iterator (e.g., after `filter` or `flat_map`) or in completion order (like
+/// [`rtm::compute_rtm_source`]) would silently scramble the rows.
+fn run_points<T, I>(
+    py: Python<'_>,
+    num_threads: Option<usize>,
+    num_freq: usize,
+    options: &RtmOptions,
+    verbosity: Verbosity,
+    cancelled: &AtomicBool,
+    results: I,
+) -> PyResult<Vec<Result<T, RtmError>>>
+where
+    T: Send,
+    I: IndexedParallelIterator<Item = Result<T, RtmError>>,
+{
+    let num_points = results.len();
+    let mut collected = Vec::new();
+    // There's nothing to run or report progress on, so don't start the pool
+    if num_points == 0 {
+        return Ok(collected);
+    }
+
+    // The adaptive number is rounded up to a power of 2 (up to the maximum),
+    // so that only a few sizes of pools are kept
+    let num_threads = num_threads.unwrap_or_else(|| {
+        let max_threads = rtm::adaptive_num_threads(usize::MAX, 1, options.items_per_thread).get();
+        let adaptive =
+            rtm::adaptive_num_threads(num_points, num_freq, options.items_per_thread).get();
+        adaptive.next_power_of_two().min(max_threads)
+    });
+    let pool = thread_pool(num_threads)?;
+
+    // This atomic keeps track of how many points have finished
+    let num_completed = AtomicUsize::new(0);
+    let start = Instant::now();
+
+    // The GIL is only held to check for signals, so that the workers can take
+    // it (for a Python absorption model) until the scope has joined them
+    py.allow_threads(|| {
+        pool.in_place_scope(|s| -> Result<(), PyErr> {
+            s.spawn(|_| {
+                results
+                    .inspect(|_| {
+                        num_completed.fetch_add(1, Ordering::Relaxed);
+                    })
+                    // Keeps the input order (see above)
+                    .collect_into_vec(&mut collected);
+            });
+
+            // The work is done in the thread pool, but back here in the main
+            // thread, handle progress reporting and checking for early
+            // cancellation
+            while !cancelled.load(Ordering::Relaxed) {
+                if let Err(e) = Python::with_gil(|py| py.check_signals()) {
+                    cancelled.store(true, Ordering::Relaxed);
+                    return Err(e);
+                }
+
+                let num_completed = num_completed.load(Ordering::Relaxed);
+                if verbosity >= Verbosity::Progress {
+                    log_progress(num_completed, num_points, start.elapsed());
+                }
+
+                // All finished without cancelling early
+                if num_completed == num_points {
+                    break;
+                }
+
+                std::thread::sleep(Duration::from_secs(5));
+            }
+
+            Ok(())
+        })
+    })?;
+
+    Ok(collected)
+}
+
+/// The worker thread pools of [`run_points`].
+struct ThreadPools {
+    /// ID of the process that started the pools.
+    pid: u32,
+    /// The pools by their number of threads.
+    pools: Vec<(usize, Arc<ThreadPool>)>,
+}
+
+static THREAD_POOLS: Mutex<ThreadPools> = Mutex::new(ThreadPools {
+    pid: 0,
+    pools: Vec::new(),
+});
+
+/// The pool of `num_threads` worker threads.
+///
+/// The pools are started on first use and kept for the life of the process,
+/// so that calls on small blocks of points, e.g., from `dask.array.map_blocks`,
+/// don't each pay for starting the threads. A process forked from this one has
+/// none of the threads, so it starts its own pools.
+fn thread_pool(num_threads: usize) -> Result<Arc<ThreadPool>, RtmError> {
+    let mut guard = THREAD_POOLS.lock().unwrap_or_else(|e| e.into_inner());
+    let ThreadPools { pid, pools } = &mut *guard;
+    if *pid != std::process::id() {
+        // Dropping the pools of the parent would wait on threads that don't
+        // exist in this process
+        std::mem::forget(std::mem::take(pools));
+        *pid = std::process::id();
+    }
+    if let Some((_, pool)) = pools.iter().find(|(size, _)| *size == num_threads) {
+        return Ok(Arc::clone(pool));
+    }
+    let pool = Arc::new(
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .build()
+            .map_err(RtmError::ThreadPool)?,
+    );
+    pools.push((num_threads, Arc::clone(&pool)));
+    Ok(pool)
+}
+
+/// Log the progress of [`run_points`] after `elapsed` time.
+///
+/// The numbers are also attached as structured key-value fields (`completed`,
+/// `total`, `percent`, `elapsed_s`, and `eta_s`), which [`KeyValueLogger`]
+/// passes to the `extra` of the Python record.
+/// The estimated time remaining assumes the points finish at the same rate as
+/// so far, so it's only known once some have finished.
+fn log_progress(num_completed: usize, num_points: usize, elapsed: Duration) {
+    let percent = num_completed as f64 / num_points as f64 * 100.;
+    let elapsed_s = elapsed.as_secs_f64();
+    let eta_s = (num_completed > 0)
+        .then(|| elapsed_s / num_completed as f64 * (num_points - num_completed) as f64);
+    let eta = match eta_s {
+        Some(eta_s) => format!(", {eta_s:.0} s remaining"),
+        None => String::new(),
+    };
+    info!(
+        completed = num_completed,
+        total = num_points,
+        percent,
+        elapsed_s,
+        eta_s;
+        "Completed RTM for {num_completed}/{num_points} profiles ({percent:0.2}%{eta})"
+    );
+}
+
+/// Copy the per-channel `rows` for a single point into `output` at `index`.
+///
+/// The rows may be shorter than the last axis of `output`, in which case only
+/// the leading values are assigned.
+fn assign_rows<T: PyFloat>(output: &mut Array3<T>, index: usize, rows: &[Vec<T>]) {
+    for (mut lhs, rhs) in output
+        .index_axis_mut(Axis(0), index)
+        .outer_iter_mut()
+        .zip(rows)
+    {
+        lhs.slice_mut(s![..rhs.len()])
+            .assign(&ArrayView1::from(rhs.as_slice()));
+    }
+}
+
+/// The output row of each point when the points are sorted by `keys`. The
+/// sort is stable, so points with equal keys stay in their input order.
+fn sorted_rows(keys: &[i64]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..keys.len()).collect();
+    order.sort_by_key(|&index| keys[index]);
+    let mut rows = vec![0; keys.len()];
+    for (row, index) in order.into_iter().enumerate() {
+        rows[index] = row;
+    }
+    rows
+}
+
+/// Compute the radiative transfer model for the atmosphere.
+///
+/// Most of the inputs are numpy arrays and are either 1d or 2d. The `pressure`
+/// parameter is the pressure levels in hPa. It usually has shape
+/// (`num_levels`, ) and is shared by all of the points, but for model levels
+/// whose pressure varies from point to point (such as hybrid sigma-pressure
+/// levels), it can instead have shape (`num_points`, `num_levels`) like the
+/// profiles.
+///
+/// `pressure`: pressure levels, in hPa, strictly decreasing (from the surface
+/// upward) for each point
+///
+/// The following are input profiles and have shape (`num_points`,
+/// `num_levels`):
+///
+/// `temperature`: physical temperature in K
+///
+/// `height`: geometric height above the geoid in m
+///
+/// `specific_humidity`: specific humidity in kg/kg
+///
+/// `liquid_content`: liquid water content (from clouds) in kg/kg, or `None`
+/// for clear sky, which is the same as all zeros but without the array
+///
+/// The following are surface parameters and have shape (`num_points`, ):
+///
+/// `surface_temperature`: 2 meter air temperature in K
+///
+/// `surface_height`: geopotential height at the surface in m
+///
+/// `surface_dewpoint`: 2 meter dewpoint in K
+///
+/// `surface_pressure`: surface pressure in hPa, or `None` to take the first
+/// pressure level as the surface, in which case the other surface parameters
+/// should be the values at that level
+///
+/// The following are RTM parameters and have shape (`num_freq`, ):
+///
+/// `incidence_angle`: Earth incidence angle in degrees
+///
+/// `frequency`: microwave frequency in GHz
+///
+/// Alternatively, the channels can be given as an `RtmParameters` with the
+/// `parameters` keyword argument, in which case `incidence_angle` and
+/// `frequency` must be `None`. Or, in place of `incidence_angle` (which must
+/// then be `None`), the channels can be given by `scan_angle`, the scan angle
+/// from nadir in degrees with shape (`num_freq`, ), and `satellite_altitude`,
+/// the height of the satellite in m, which are converted as in
+/// `eia_from_scan`.
+///
+/// For a swath of a cross-track sounder, the channels can instead be given by
+/// `sensor` (`"msu"` or `"amsu_a"`), the channel numbers `channels` (from 1,
+/// or all of the channels if `None`), and `scan_position`, the scan position of
+/// each point from 0 with shape (`num_points`, ). Each point then has the
+/// Earth incidence angle of its scan position, as in `scan_incidence_angle`.
+///
+/// However the channels are given, `frequency_offset` optionally shifts the
+/// frequency of each channel by that many MHz, with shape (`num_freq`, ), for
+/// instance to correct for the drift of a channel's passband centroid. It's
+/// added before the absorption models are evaluated. The default is no shift.
+///
+/// The returned atmospheric parameters are each dimensioned as (`num_points`,
+/// `num_freq`).
+///
+/// The number of worker threads is controlled by `num_threads`. It must be a
+/// positive integer, or `None` to automatically choose the number of threads
+/// from the number of points and channels (see the `items_per_thread` of
+/// `RtmOptions`).
+///
+/// The outputs are deterministic: they are bit-for-bit identical for any
+/// `num_threads` and on repeated runs with the same inputs (on the same
+/// machine and build). Each point is computed independently and in the same
+/// order of operations, regardless of which thread computes it.
+///
+/// A `ValueError` is raised if any incidence angle is outside of 0° to 90°
+/// (90° itself is allowed).
+///
+/// If any points fail, for instance with no profile levels above the surface, a
+/// `ValueError` is raised for the first of them. Before that, a warning is
+/// logged for each failed point with its index (in the input order), the kind
+/// of error, and its surface pressure and temperature, up to
+/// `max_failure_logs` messages, followed by a summary with the number of
+/// failed points and of suppressed messages.
+///
+/// The `verbosity` of the log messages is set for each call, independent of
+/// the level of the Python logger: `"quiet"` only logs the warnings,
+/// `"progress"` (the default) also logs the number of completed points every
+/// 5 seconds, and `"debug"` also logs each phase of the call at the debug
+/// level. For any verbosity, a one-line summary with the number of points and
+/// channels, the elapsed time, and the points per second is logged at the info
+/// level when the call finishes.
+///
+/// Optionally, `diagnostic_levels` is a small array of pressures in hPa, with
+/// shape (`num_diagnostic_levels`, ). When given, the transmissivity from each
+/// diagnostic level to space and the upwelling TB from the atmosphere above
+/// each level are also output, interpolated in ln(p) from the profile levels.
+/// Diagnostic levels with a higher pressure than a point's surface pressure
+/// are NaN for that point.
+///
+/// Optionally, `top_pressure` is a pressure in hPa above which the profile
+/// levels are discarded, for instance to exclude the very low pressures where
+/// the absorption models are poorly constrained. By default, all levels are
+/// used.
+///
+/// The `emission` selects how layer emission is computed: `"rayleigh_jeans"`
+/// (the default) is linear in the physical temperature, as in the original
+/// Fortran, while `"planck"` does the radiative transfer in Planck radiance
+/// and outputs Planck-equivalent brightness temperatures. The difference is
+/// largest for cold scenes at high frequencies.
+///
+/// The `integration_scheme` selects how the layer emission is integrated:
+/// `"trapezoidal"` (the default) uses the mean temperature of each layer, as in
+/// the original Fortran, while `"linear_in_optical_depth"` takes the
+/// temperature as linear in optical depth across each layer. The two agree for
+/// optically thin layers. The scheme applies to all the outputs, including
+/// `tb_up_above` and the effective temperatures.
+///
+/// The `tb_convention` selects the convention of the output brightness
+/// temperatures `tb_up`, `tb_down`, and `tb_up_above`, and is the same as
+/// `emission` by default. `"rayleigh_jeans"` is linear in radiance, as in the
+/// original Fortran, while `"planck"` is the temperature of a blackbody with
+/// the same radiance at the channel frequency, for comparison with instruments
+/// that report those. At the MSU frequencies, Planck brightness temperatures
+/// are about 1.3 K warmer.
+///
+/// Optionally, `sort_key` is an int64 array with shape (`num_points`, ). The
+/// points are still computed in the order given, but the output rows are
+/// sorted by the key (stably, so points with equal keys stay in their input
+/// order). This is the same as indexing each output with
+/// `np.argsort(sort_key, kind="stable")`, but without the extra copy.
+///
+/// The number of profile levels integrated for each point, those at or above
+/// the surface pressure and below `top_pressure`, is always output as
+/// `num_levels_integrated`, an int32 array with shape (`num_points`, ). The
+/// levels below the surface are discarded, so this varies with the surface
+/// pressure, and a small value flags a point where most of the profile was
+/// discarded.
+///
+/// If `quality_flags` is true, a bitfield of quality flags is also output for
+/// each point. The bits are:
+///
+/// - 1: the surface height was not below the first level above it, so it was
+///   moved 0.1 m below
+/// - 2: an input value used by the RTM was NaN or infinite
+/// - 4: a temperature used by the RTM was outside of 150 K to 350 K
+/// - 8: cloud water was present outside of the valid temperature range of the
+///   dielectric model
+/// - 16: an output value was NaN or infinite
+/// - 32: the surface vapor pressure was above saturation, so it was clamped
+///   (only with `clamp_surface_saturation`)
+/// - 64: a frequency was above the validated range of an absorption model
+///   that was used, which is extrapolated (a warning is also logged once per
+///   call; see `frequency_limits` in `get_model_info()`)
+///
+/// If `clamp_surface_saturation` is true, the surface vapor pressure from
+/// `surface_dewpoint` is clamped so that it does not exceed saturation at
+/// `surface_temperature`. A dewpoint above the air temperature, which is a
+/// common inconsistency in reanalyses, otherwise makes the surface
+/// supersaturated and overestimates the absorption in the lowest layer. Each
+/// clamped point is logged at the debug level.
+///
+/// Optionally, `absorption_model` is a Python callable that replaces the
+/// absorption models of this package, for experimenting with other
+/// spectroscopy. It is called as `absorption_model(p, t, pv, rho_l, freq)` for
+/// each level of each point and each distinct frequency, with the pressure in
+/// hPa, temperature in K, water vapor pressure in hPa, liquid water density in
+/// g/m³, and frequency in GHz, and returns the absorption coefficient in Np/m
+/// as a float. Since the callable holds the GIL, the points are effectively
+/// computed one at a time and this is much slower than the built-in models.
+/// If it raises an exception, the remaining points are cancelled and the
+/// exception is re-raised. This cannot be combined with `diagnostic_levels`.
+///
+/// If `effective_temperature` is true, the effective temperatures of the
+/// atmosphere for vicarious calibration, `t_eff_up = tb_up / (1 - tran)` and
+/// `t_eff_down = tb_down / (1 - tran)`, are also output for each point and
+/// channel. These are computed from the same layer sums as `tran`, `tb_up`,
+/// and `tb_down`, so they keep their precision when the atmosphere is nearly
+/// transparent, where dividing the outputs loses it. As `tran` goes to 1 they
+/// go to the mean layer temperature weighted by the layer opacities, and as
+/// `tran` goes to 0 they go to `tb_up` and `tb_down`. They are NaN only if
+/// there is no absorption at all. With `observer_height`, `t_eff_down` is
+/// divided by `1 - tran` for the whole atmosphere rather than the `tran` to
+/// the observer. With `emission="planck"`, the ratio is taken in radiance
+/// before converting to the `tb_convention`. This cannot be combined with
+/// `diagnostic_levels` or `absorption_model`.
+///
+/// Optionally, `skin_temperature` is the radiometric skin temperature of the
+/// surface in K, with shape (`num_points`, ). This is distinct from
+/// `surface_temperature`, the 2 meter air temperature, which is the
+/// temperature of the lowest level of the atmosphere. The skin temperature is
+/// only for the surface emission, and the atmospheric outputs here do not
+/// depend on it. Using the air temperature in place of the skin temperature
+/// biases the window-channel TBs.
+///
+/// Optionally, `observer_height` is the geometric height in m of the observer
+/// for each point, with shape (`num_points`, ), for an airborne or mountaintop
+/// sensor. The upwelling integration stops at the profile level nearest to the
+/// observer, so `tran` and `tb_up` are for the path between the surface and the
+/// observer. `tb_down` is still the downwelling at the surface from the whole
+/// atmosphere, since that is what the surface reflects up to the observer. By
+/// default, the observer is at the top of the atmosphere. The diagnostic level
+/// outputs are always for an observer at the top of the atmosphere.
+///
+/// Optionally, `noise_nedt` is the NEΔT in K of each channel, with shape
+/// (`num_freq`, ), for simulated observations. Independent Gaussian noise with
+/// that standard deviation is added to `tb_up` for each point and channel. The
+/// noise is from a random number generator seeded with `noise_seed`, so the same
+/// seed gives the same noise, or a random seed if it's `None`. If
+/// `return_unnoised` is true, the values before the noise was added are also
+/// output as `tb_up_unnoised`.
+///
+/// If `air_mass_factor` is true, the air-mass factor of each point and channel,
+/// the ratio of the slant optical depth to the zenith optical depth, is also
+/// output as `air_mass_factor`. The RTM lengthens the path through every layer by
+/// the same factor, which only depends on the incidence angle. For a
+/// plane-parallel atmosphere, it reduces to `sec(incidence_angle)`, and the
+/// spherical Earth keeps it within 0.5% of that up to 75°, and finite at 90°.
+///
+/// Optionally, `options` is an `RtmOptions` with the model and numerical
+/// choices: `emission`, `tb_convention`, `integration_scheme`,
+/// `clamp_surface_saturation`, `top_pressure`, `quality_flags`,
+/// `effective_temperature`, `air_mass_factor`, `max_failure_logs`, and
+/// `verbosity`. The keywords of the same names are deprecated, and setting any
+/// of them to other than its default emits a `DeprecationWarning`; they cannot
+/// be combined with `options`, which raises a `ValueError`.
+///
+/// With `options=RtmOptions(down_level=k)`, the downwelling brightness
+/// temperature at the level with index `k` of the profiles, from the
+/// atmosphere above it, is also output as `tb_down_level`, with shape
+/// (`num_points`, `num_freq`), e.g., to couple another model at that level.
+/// It's what `tb_down` would be if the surface were at that level, so it's 0 K
+/// at the top level, and NaN where the level is below the surface. The level
+/// must be one of the profile levels, which raises a `ValueError` otherwise,
+/// and points whose `top_pressure` discards it fail. This is not supported with
+/// `diagnostic_levels`, `absorption_model` or `effective_temperature`.
+///
+/// With `options=RtmOptions(profile_timing=True)`, the wall-clock time in
+/// microseconds to prepare and run each point is also output as
+/// `profile_time`, a float32 array with shape (`num_points`, ), for instance
+/// to see how imbalanced the work is across a field of deep and shallow
+/// profiles. The times include any waiting for other threads, such as for the
+/// GIL with an `absorption_model`, and are noisy for small points.
+///
+/// With no points (empty profile arrays), the RTM isn't run, and the outputs
+/// are empty, with shape (0, `num_freq`). The inputs are still checked, so the
+/// shapes must be consistent, and there must be at least one channel, which
+/// raises a `ValueError` otherwise.
+///
+/// The floating-point arrays must either all be float32 or all be float64, and
+/// the outputs have the same dtype. The RTM is computed in that precision,
+/// though the absorption model coefficients themselves are single precision
+/// and the layers are always integrated in double precision.
+/// The two agree to a relative difference of 1e-5 (a few mK at most).
+#[pyfunction]
+#[pyo3(signature = (pressure, temperature, height, specific_humidity, liquid_content, surface_temperature, surface_height, surface_dewpoint, surface_pressure, incidence_angle, frequency, num_threads, diagnostic_levels=None, top_pressure=None, emission="rayleigh_jeans", quality_flags=false, skin_temperature=None, observer_height=None, parameters=None, tb_convention=None, sort_key=None, clamp_surface_saturation=false, absorption_model=None, effective_temperature=false, scan_angle=None, satellite_altitude=None, sensor=None, channels=None, scan_position=None, frequency_offset=None, noise_nedt=None, noise_seed=None, return_unnoised=false, air_mass_factor=false, integration_scheme="trapezoidal", max_failure_logs=20, verbosity="progress", options=None))]
+#[allow(clippy::too_many_arguments)]
+fn compute_rtm<'py>(
+    py: Python<'py>,
+    pressure: &Bound<'py, PyAny>,
+    temperature: &Bound<'py, PyAny>,
+    height: &Bound<'py, PyAny>,
+    specific_humidity: &Bound<'py, PyAny>,
+    liquid_content: Option<&Bound<'py, PyAny>>,
+    surface_temperature: &Bound<'py, PyAny>,
+    surface_height: &Bound<'py, PyAny>,
+    surface_dewpoint: &Bound<'py, PyAny>,
+    surface_pressure: Option<&Bound<'py, PyAny>>,
+    incidence_angle: Option<&Bound<'py, PyAny>>,
+    frequency: Option<&Bound<'py, PyAny>>,
+    num_threads: Option<usize>,
+    diagnostic_levels: Option<&Bound<'py, PyAny>>,
+    top_pressure: Option<f64>,
+    emission: &str,
+    quality_flags: bool,
+    skin_temperature: Option<&Bound<'py, PyAny>>,
+    observer_height: Option<&Bound<'py, PyAny>>,
+    parameters: Option<&Bound<'py, PyRtmParameters>>,
+    tb_convention: Option<&str>,
+    sort_key: Option<PyReadonlyArray1<'py, i64>>,
+    clamp_surface_saturation: bool,
+    absorption_model: Option<&Bound<'py, PyAny>>,
+    effective_temperature: bool,
+    scan_angle: Option<&Bound<'py, PyAny>>,
+    satellite_altitude: Option<f64>,
+    sensor: Option<&str>,
+    channels: Option<Vec<usize>>,
+    scan_position: Option<PyReadonlyArray1<'py, i64>>,
+    frequency_offset: Option<&Bound<'py, PyAny>>,
+    noise_nedt: Option<&Bound<'py, PyAny>>,
+    noise_seed: Option<u64>,
+    return_unnoised: bool,
+    air_mass_factor: bool,
+    integration_scheme: &str,
+    max_failure_logs: usize,
+    verbosity: &str,
+    options: Option<&Bound<'py, PyRtmOptions>>,
+) -> PyResult<AtmoParameters> {
+    // The individual keywords are deprecated in favor of the options, but
+    // still work when no options are given
+    let keywords = PyRtmOptions::new(
+        emission,
+        tb_convention,
+        integration_scheme,
+        LayerSumReference::default().name(),
+        clamp_surface_saturation,
+        top_pressure,
+        quality_flags,
+        effective_temperature,
+        air_mass_factor,
+        max_failure_logs,
+        verbosity,
+        1.,
+        1.,
+        1.,
+        1.,
+        false,
+        None,
+        rtm::DEFAULT_ITEMS_PER_THREAD,
+        rtm::DEFAULT_WORK_CHUNK_SIZE,
+    )?;
+    let deprecated = keywords.non_default();
+    let options = match options {
+        Some(options) => {
+            if !deprecated.is_empty() {
+                return Err(PyValueError::new_err(format!(
+                    "{} cannot be given as keywords together with options",
+                    deprecated.join(", ")
+                )));
+            }
+            options.get().clone()
+        }
+        None => {
+            if !deprecated.is_empty() {
+                let message = format!(
+                    "the {} keywords of compute_rtm are deprecated, use options=RtmOptions(...) instead",
+                    deprecated.join(", ")
+                );
+                PyErr::warn(
+                    py,
+                    &py.get_type::<PyDeprecationWarning>(),
+                    &CString::new(message).expect("the message has no NUL bytes"),
+                    1,
+                )?;
+            }
+            keywords
+        }
+    };
+
+    // Dispatch on the dtype of the temperature, and then the rest of the arrays
+    // must match it
+    let compute_rtm_typed = if temperature.downcast::<PyArray2<f64>>().is_ok() {
+        compute_rtm_typed::<f64>
+    } else {
+        compute_rtm_typed::<f32>
+    };
+    compute_rtm_typed(
+        py,
+        ProfileArrays {
+            pressure,
+            temperature,
+            height,
+            specific_humidity,
+            liquid_content,
+            surface_temperature,
+            surface_height,
+            surface_dewpoint,
+            surface_pressure,
+            skin_temperature,
+            observer_height,
+        },
+        ComputeRtmArgs {
+            incidence_angle,
+            frequency,
+            num_threads,
+            diagnostic_levels,
+            parameters,
+            sort_key,
+            absorption_model,
+            scan_angle,
+            satellite_altitude,
+            sensor,
+            channels,
+            scan_position,
+            frequency_offset,
+            noise_nedt,
+            noise_seed,
+            return_unnoised,
+        },
+        &options,
+    )
+}
+
+/// Compute the RTM for a block of profiles, with a fixed signature of plain
+/// arrays in and out for `dask.array.map_blocks` and the like.
+///
+/// The profile arrays are the same as for `compute_rtm`, and `parameters` and
+/// `options` are an `RtmParameters` and an `RtmOptions`, which can be pickled
+/// to send them to the workers. With no `options`, nothing is logged, since
+/// progress for each block is noise in a distributed run. The worker threads
+/// are shared by all of the calls in a process (and started on the first), so
+/// the overhead of a call is small next to the RTM for a block of thousands of
+/// points. The number of threads is controlled by `num_threads`, as for
+/// `compute_rtm`; with several workers in a process, fewer threads each may
+/// be faster.
+///
+/// The output is an array with shape (`num_points`, `num_freq`, 3) of the
+/// same dtype as the inputs, with `tran`, `tb_up`, and `tb_down` along the
+/// last axis. The other outputs of the options, such as the quality flags,
+/// aren't included. A point that fails has NaN outputs, as in `compute_rtm`.
+#[pyfunction]
+#[pyo3(signature = (pressure, temperature, height, specific_humidity, liquid_content, surface_temperature, surface_height, surface_dewpoint, surface_pressure, parameters, options=None, num_threads=None))]
+#[allow(clippy::too_many_arguments)]
+fn compute_rtm_block<'py>(
+    py: Python<'py>,
+    pressure: &Bound<'py, PyAny>,
+    temperature: &Bound<'py, PyAny>,
+    height: &Bound<'py, PyAny>,
+    specific_humidity: &Bound<'py, PyAny>,
+    liquid_content: Option<&Bound<'py, PyAny>>,
+    surface_temperature: &Bound<'py, PyAny>,
+    surface_height: &Bound<'py, PyAny>,
+    surface_dewpoint: &Bound<'py, PyAny>,
+    surface_pressure: Option<&Bound<'py, PyAny>>,
+    parameters: &Bound<'py, PyRtmParameters>,
+    options: Option<&Bound<'py, PyRtmOptions>>,
+    num_threads: Option<usize>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let options = options.map_or_else(
+        || PyRtmOptions {
+            verbosity: Verbosity::Quiet,
+            ..PyRtmOptions::default()
+        },
+        |options| options.get().clone(),
+    );
+    let compute_rtm_typed = if temperature.downcast::<PyArray2<f64>>().is_ok() {
+        compute_rtm_typed::<f64>
+    } else {
+        compute_rtm_typed::<f32>
+    };
+    let outputs = compute_rtm_typed(
+        py,
+        ProfileArrays {
+            pressure,
+            temperature,
+            height,
+            specific_humidity,
+            liquid_content,
+            surface_temperature,
+            surface_height,
+            surface_dewpoint,
+            surface_pressure,
+            skin_temperature: None,
+            observer_height: None,
+        },
+        ComputeRtmArgs {
+            num_threads,
+            parameters: Some(parameters),
+            ..ComputeRtmArgs::default()
+        },
+        &options,
+    )?;
+    with_outputs!(outputs.arrays, |outputs| {
+        let stacked = ndarray::stack(
+            Axis(2),
+            &[
+                outputs.tran.view(),
+                outputs.tb_up.view(),
+                outputs.tb_down.view(),
+            ],
+        )
+        .expect("the outputs have the same shape");
+        Ok(stacked.into_pyarray(py).into_any())
+    })
+}
+
+/// The keys of the inputs of `compute_rtm_dict` that must be given.
+const REQUIRED_INPUTS: [&str; 7] = [
+    "pressure",
+    "temperature",
+    "height",
+    "specific_humidity",
+    "surface_temperature",
+    "surface_height",
+    "surface_dewpoint",
+];
+
+/// The keys of the inputs of `compute_rtm_dict` that may be given.
+const OPTIONAL_INPUTS: [&str; 6] = [
+    "liquid_content",
+    "surface_pressure",
+    "skin_temperature",
+    "observer_height",
+    "incidence_angle",
+    "frequency",
+];
+
+/// Compute the RTM for the arrays in a dict, by name rather than position.
+///
+/// The keys of `inputs` are the names of the array arguments of
+/// `compute_rtm`, which have the same meanings and shapes: `pressure`,
+/// `temperature`, `height`, `specific_humidity`, `surface_temperature`,
+/// `surface_height`, and `surface_dewpoint` are required, and
+/// `liquid_content`, `surface_pressure`, `skin_temperature`,
+/// `observer_height`, `incidence_angle`, and `frequency` are optional. So the
+/// dicts from `standard_atmosphere` and `read_rttov_profiles` can be passed
+/// directly, as can the variables of an xarray Dataset, e.g., `{name:
+/// ds[name].values for name in ds.data_vars}`.
+///
+/// A `KeyError` is raised that lists all of the missing required keys, and a
+/// `ValueError` for any key that isn't one of the inputs, such as a
+/// misspelling. The channels are either the `incidence_angle` and `frequency`
+/// of the dict, or `parameters`, an `RtmParameters`, and the other arguments
+/// are as for `compute_rtm`.
+#[pyfunction]
+#[pyo3(signature = (inputs, num_threads=None, parameters=None, options=None))]
+fn compute_rtm_dict<'py>(
+    py: Python<'py>,
+    inputs: &Bound<'py, PyDict>,
+    num_threads: Option<usize>,
+    parameters: Option<&Bound<'py, PyRtmParameters>>,
+    options: Option<&Bound<'py, PyRtmOptions>>,
+) -> PyResult<AtmoParameters> {
+    let mut unknown = Vec::new();
+    for key in inputs.keys() {
+        let key: String = key.extract()?;
+        if !REQUIRED_INPUTS.contains(&key.as_str()) && !OPTIONAL_INPUTS.contains(&key.as_str()) {
+            unknown.push(format!("'{key}'"));
+        }
+    }
+    if !unknown.is_empty() {
+        return Err(PyValueError::new_err(format!(
+            "unknown inputs {}, expected some of {}",
+            unknown.join(", "),
+            REQUIRED_INPUTS
+                .iter()
+                .chain(&OPTIONAL_INPUTS)
+                .map(|key| format!("'{key}'"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )));
+    }
+    let missing: Vec<_> = REQUIRED_INPUTS
+        .iter()
+        .filter(|&&key| !inputs.contains(key).unwrap_or(false))
+        .map(|key| format!("'{key}'"))
+        .collect();
+    if !missing.is_empty() {
+        return Err(PyKeyError::new_err(format!(
+            "missing required inputs {}",
+            missing.join(", ")
+        )));
+    }
+
+    let required = |key: &str| -> PyResult<Bound<'py, PyAny>> {
+        inputs
+            .get_item(key)?
+            .ok_or_else(|| PyKeyError::new_err(format!("missing required input '{key}'")))
+    };
+    let optional = |key: &str| -> PyResult<Option<Bound<'py, PyAny>>> {
+        Ok(inputs.get_item(key)?.filter(|value| !value.is_none()))
+    };
+    let temperature = required("temperature")?;
+    let options = options.map_or_else(PyRtmOptions::default, |options| options.get().clone());
+
+    let compute_rtm_typed = if temperature.downcast::<PyArray2<f64>>().is_ok() {
+        compute_rtm_typed::<f64>
+    } else {
+        compute_rtm_typed::<f32>
+    };
+    let pressure = required("pressure")?;
+    let height = required("height")?;
+    let specific_humidity = required("specific_humidity")?;
+    let liquid_content = optional("liquid_content")?;
+    let surface_temperature = required("surface_temperature")?;
+    let surface_height = required("surface_height")?;
+    let surface_dewpoint = required("surface_dewpoint")?;
+    let surface_pressure = optional("surface_pressure")?;
+    let skin_temperature = optional("skin_temperature")?;
+    let observer_height = optional("observer_height")?;
+    let incidence_angle = optional("incidence_angle")?;
+    let frequency = optional("frequency")?;
+    compute_rtm_typed(
+        py,
+        ProfileArrays {
+            pressure: &pressure,
+            temperature: &temperature,
+            height: &height,
+            specific_humidity: &specific_humidity,
+            liquid_content: liquid_content.as_ref(),
+            surface_temperature: &surface_temperature,
+            surface_height: &surface_height,
+            surface_dewpoint: &surface_dewpoint,
+            surface_pressure: surface_pressure.as_ref(),
+            skin_temperature: skin_temperature.as_ref(),
+            observer_height: observer_height.as_ref(),
+        },
+        ComputeRtmArgs {
+            incidence_angle: incidence_angle.as_ref(),
+            frequency: frequency.as_ref(),
+            num_threads,
+            parameters,
+            ..ComputeRtmArgs::default()
+        },
+        &options,
+    )
+}
+
+/// The profile arrays of [`compute_rtm`], before they're extracted as a
+/// precision.
+struct ProfileArrays<'a, 'py> {
+    pressure: &'a Bound<'py, PyAny>,
+    temperature: &'a Bound<'py, PyAny>,
+    height: &'a Bound<'py, PyAny>,
+    specific_humidity: &'a Bound<'py, PyAny>,
+    liquid_content: Option<&'a Bound<'py, PyAny>>,
+    surface_temperature: &'a Bound<'py, PyAny>,
+    surface_height: &'a Bound<'py, PyAny>,
+    surface_dewpoint: &'a Bound<'py, PyAny>,
+    surface_pressure: Option<&'a Bound<'py, PyAny>>,
+    skin_temperature: Option<&'a Bound<'py, PyAny>>,
+    observer_height: Option<&'a Bound<'py, PyAny>>,
+}
+
+/// The other arguments of [`compute_rtm`] that aren't in the options, which
+/// are all optional, so the callers with fewer arguments can leave the rest as
+/// the defaults.
+#[derive(Default)]
+struct ComputeRtmArgs<'a, 'py> {
+    incidence_angle: Option<&'a Bound<'py, PyAny>>,
+    frequency: Option<&'a Bound<'py, PyAny>>,
+    num_threads: Option<usize>,
+    diagnostic_levels: Option<&'a Bound<'py, PyAny>>,
+    parameters: Option<&'a Bound<'py, PyRtmParameters>>,
+    sort_key: Option<PyReadonlyArray1<'py, i64>>,
+    absorption_model: Option<&'a Bound<'py, PyAny>>,
+    scan_angle: Option<&'a Bound<'py, PyAny>>,
+    satellite_altitude: Option<f64>,
+    sensor: Option<&'a str>,
+    channels: Option<Vec<usize>>,
+    scan_position: Option<PyReadonlyArray1<'py, i64>>,
+    frequency_offset: Option<&'a Bound<'py, PyAny>>,
+    noise_nedt: Option<&'a Bound<'py, PyAny>>,
+    noise_seed: Option<u64>,
+    return_unnoised: bool,
+}
+
+/// Implementation of [`compute_rtm`] for a precision `T`, which all of the
+/// arrays must have.
+fn compute_rtm_typed<'py, T: PyFloat>(
+    py: Python<'py>,
+    arrays: ProfileArrays<'_, 'py>,
+    args: ComputeRtmArgs<'_, 'py>,
+    options: &PyRtmOptions,
+) -> PyResult<AtmoParameters> {
+    let ProfileArrays {
+        pressure,
+        temperature,
+        height,
+        specific_humidity,
+        liquid_content,
+        surface_temperature,
+        surface_height,
+        surface_dewpoint,
+        surface_pressure,
+        skin_temperature,
+        observer_height,
+    } = arrays;
+    let ComputeRtmArgs {
+        incidence_angle,
+        frequency,
+        num_threads,
+        diagnostic_levels,
+        parameters,
+        sort_key,
+        absorption_model,
+        scan_angle,
+        satellite_altitude,
+        sensor,
+        channels,
+        scan_position,
+        frequency_offset,
+        noise_nedt,
+        noise_seed,
+        return_unnoised,
+    } = args;
+    let PyRtmOptions {
+        rtm: rtm_options,
+        top_pressure,
+        quality_flags,
+        effective_temperature,
+        air_mass_factor,
+        max_failure_logs,
+        verbosity,
+        profile_timing,
+        down_level,
+    } = options.clone();
+    let pressure: PressureArray<'_, T> = pressure.extract()?;
+    let temperature: PyReadonlyArray2<'_, T> = temperature.extract()?;
+    let height: PyReadonlyArray2<'_, T> = height.extract()?;
+    let specific_humidity: PyReadonlyArray2<'_, T> = specific_humidity.extract()?;
+    let liquid_content: Option<PyReadonlyArray2<'_, T>> =
+        liquid_content.map(|a| a.extract()).transpose()?;
+    let surface_temperature: PyReadonlyArray1<'_, T> = surface_temperature.extract()?;
+    let surface_height: PyReadonlyArray1<'_, T> = surface_height.extract()?;
+    let surface_dewpoint: PyReadonlyArray1<'_, T> = surface_dewpoint.extract()?;
+    let surface_pressure: Option<PyReadonlyArray1<'_, T>> =
+        surface_pressure.map(|a| a.extract()).transpose()?;
+    let diagnostic_levels: Option<PyReadonlyArray1<'_, T>> =
+        diagnostic_levels.map(|a| a.extract()).transpose()?;
+    let skin_temperature: Option<PyReadonlyArray1<'_, T>> =
+        skin_temperature.map(|a| a.extract()).transpose()?;
+    let observer_height: Option<PyReadonlyArray1<'_, T>> =
+        observer_height.map(|a| a.extract()).transpose()?;
+
+    let profiles = profiles(
+        &pressure,
+        &temperature,
+        &height,
+        &specific_humidity,
+        liquid_content.as_ref(),
+        &surface_temperature,
+        &surface_height,
+        &surface_dewpoint,
+        surface_pressure.as_ref(),
+        skin_temperature.as_ref(),
+        observer_height.as_ref(),
+        top_pressure.map(T::from_f64_lossy),
+    )?;
+    let incidence_angle = match (incidence_angle, scan_angle, satellite_altitude) {
+        (incidence_angle, None, None) => incidence_angle.cloned(),
+        (None, Some(scan_angle), Some(satellite_altitude)) => {
+            let scan_angle: PyReadonlyArray1<'_, T> = scan_angle.extract()?;
+            let satellite_altitude = T::from_f64_lossy(satellite_altitude);
+            let earth_radius = T::lit(rtm::EARTH_RADIUS);
+            let incidence_angle = scan_angle.as_array().mapv(|scan_angle| {
+                rtm::eia_from_scan(scan_angle, satellite_altitude, earth_radius)
+            });
+            Some(incidence_angle.into_pyarray(py).into_any())
+        }
+        _ => return Err(PyValueError::new_err(
+            "scan_angle and satellite_altitude must be given together, instead of incidence_angle",
+        )),
+    };
+    let frequency_offset: Option<PyReadonlyArray1<'_, T>> =
+        frequency_offset.map(|a| a.extract()).transpose()?;
+    let configure = |parameters: RtmParameters<T>| -> Result<_, RtmError> {
+        let parameters = match &frequency_offset {
+            Some(offset) => parameters.with_frequency_offset(&offset.as_array().to_vec())?,
+            None => parameters,
+        };
+        Ok(parameters.with_options(&rtm_options))
+    };
+
+    let num_points = profiles.len();
+    if down_level.is_some_and(|level| level >= profiles.num_levels()) {
+        return Err(PyValueError::new_err(
+            "down_level must be the index of one of the profile levels",
+        ));
+    }
+    // For a swath, there are parameters for each scan position, and the
+    // position of each point selects them
+    let swath = match (sensor, scan_position) {
+        (Some(sensor), Some(scan_position)) => {
+            let sensor = parse_sensor(sensor)?;
+            let channels = channels.unwrap_or_else(|| (1..=sensor.num_channels()).collect());
+            let position_parameters = (0..sensor.num_positions())
+                .map(|position| {
+                    RtmParameters::for_scan_position(&sensor, &channels, position)
+                        .and_then(configure)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Some((
+                position_parameters,
+                scan_positions(&sensor, &scan_position, num_points)?,
+            ))
+        }
+        (None, None) if channels.is_none() => None,
+        _ => {
+            return Err(PyValueError::new_err(
+                "sensor and scan_position must be given together, and channels needs both",
+            ))
+        }
+    };
+
+    let parameters = match (parameters, frequency, incidence_angle, &swath) {
+        (Some(parameters), None, None, None) => parameters.get().to_precision()?,
+        (None, Some(frequency), Some(incidence_angle), None) => {
+            let frequency: PyReadonlyArray1<'_, T> = frequency.extract()?;
+            let incidence_angle: PyReadonlyArray1<'_, T> = incidence_angle.extract()?;
+            self::parameters(&frequency, &incidence_angle)?
+        }
+        (None, None, None, Some((position_parameters, _))) => position_parameters[0].clone(),
+        _ => {
+            return Err(PyValueError::new_err(
+                "either parameters, both incidence_angle and frequency, or a sensor and \
+                 scan_position are required",
+            ))
+        }
+    };
+    // The parameters of a swath are already configured
+    let parameters = if swath.is_some() {
+        parameters
+    } else {
+        configure(parameters)?
+    };
+    let parameters_at = |point: usize| match &swath {
+        Some((position_parameters, positions)) => &position_parameters[positions[point]],
+        None => &parameters,
+    };
+
+    let num_freq = parameters.len();
+    let sorted_rows = match &sort_key {
+        Some(sort_key) if sort_key.len() != num_points => {
+            return Err(RtmError::InconsistentInputs.into())
+        }
+        Some(sort_key) => Some(sorted_rows(sort_key.as_slice()?)),
+        None => None,
+    };
+
+    let noise_nedt: Option<PyReadonlyArray1<'_, T>> =
+        noise_nedt.map(|a| a.extract()).transpose()?;
+    let mut noise = noise(noise_nedt.as_ref(), noise_seed, return_unnoised, num_freq)?;
+
+    if verbosity >= Verbosity::Progress {
+        info!("Processing atmosphere RTM for {num_points} profiles and {num_freq} channels");
+    }
+    let start = Instant::now();
+    if absorption_model.is_none() {
+        let extrapolated: Vec<_> = parameters
+            .extrapolated_models()
+            .map(|(absorber, limit)| format!("{absorber} (validated up to {limit} GHz)"))
+            .collect();
+        if !extrapolated.is_empty() {
+            warn!(
+                "Extrapolating absorption models above their validated frequency range: {}",
+                extrapolated.join(", ")
+            );
+        }
+    }
+
+    let diagnostic_levels = diagnostic_levels
+        .as_ref()
+        .map(|levels| levels.as_slice())
+        .transpose()?;
+
+    if effective_temperature && (diagnostic_levels.is_some() || absorption_model.is_some()) {
+        return Err(PyValueError::new_err(
+            "effective_temperature is not supported with diagnostic_levels or absorption_model",
+        ));
+    }
+    if down_level.is_some()
+        && (diagnostic_levels.is_some() || absorption_model.is_some() || effective_temperature)
+    {
+        return Err(PyValueError::new_err(
+            "down_level is not supported with diagnostic_levels, absorption_model or effective_temperature",
+        ));
+    }
+
+    let cancelled = AtomicBool::new(false);
+    let absorption_model = match absorption_model {
+        Some(_) if diagnostic_levels.is_some() => {
+            return Err(PyValueError::new_err(
+                "absorption_model is not supported with diagnostic_levels",
+            ));
+        }
+        Some(callable) if !callable.is_callable() => {
+            return Err(PyValueError::new_err("absorption_model must be callable"))
+        }
+        Some(callable) => Some(PyAbsorptionModel {
+            callable: callable.clone().unbind(),
+            cancelled: &cancelled,
+            error: Mutex::new(None),
+        }),
+        None => None,
+    };
+    let inputs = par_timed_inputs(&profiles, &rtm_options, &cancelled, profile_timing).map(
+        |(start, inputs)| {
+            (
+                start,
+                inputs.map(|inputs| inputs.with_options(&rtm_options)),
+            )
+        },
+    );
+    let results = match diagnostic_levels {
+        Some(levels) => run_points(
+            py,
+            num_threads,
+            num_freq,
+            &rtm_options,
+            verbosity,
+            &cancelled,
+            inputs.enumerate().map(|(point, (start, inputs))| {
+                let inputs = inputs?;
+                let (outputs, level_outputs) = inputs.run_with_levels(parameters_at(point), levels);
+                Ok((
+                    outputs,
+                    Some(level_outputs),
+                    None,
+                    inputs.num_levels_integrated(),
+                    elapsed_us(start),
+                    None,
+                ))
+            }),
+        )?,
+        None => match &absorption_model {
+            Some(model) => run_points(
+                py,
+                num_threads,
+                num_freq,
+                &rtm_options,
+                verbosity,
+                &cancelled,
+                inputs.enumerate().map(|(point, (start, inputs))| {
+                    let inputs = inputs?;
+                    let outputs = inputs.run_with_absorption_model(parameters_at(point), model);
+                    Ok((
+                        outputs,
+                        None,
+                        None,
+                        inputs.num_levels_integrated(),
+                        elapsed_us(start),
+                        None,
+                    ))
+                }),
+            )?,
+            None if effective_temperature => run_points(
+                py,
+                num_threads,
+                num_freq,
+                &rtm_options,
+                verbosity,
+                &cancelled,
+                inputs.enumerate().map(|(point, (start, inputs))| {
+                    let inputs = inputs?;
+                    let (outputs, effective) =
+                        inputs.run_with_effective_temperatures(parameters_at(point));
+                    Ok((
+                        outputs,
+                        None,
+                        Some(effective),
+                        inputs.num_levels_integrated(),
+                        elapsed_us(start),
+                        None,
+                    ))
+                }),
+            )?,
+            None => run_points(
+                py,
+                num_threads,
+                num_freq,
+                &rtm_options,
+                verbosity,
+                &cancelled,
+                inputs.enumerate().map(|(point, (start, inputs))| {
+                    let inputs = inputs?;
+                    let (outputs, down) = match down_level {
+                        Some(level) => {
+                            let (outputs, down) =
+                                inputs.run_with_down_level(parameters_at(point), level)?;
+                            (outputs, Some(down))
+                        }
+                        None => (inputs.run(parameters_at(point)), None),
+                    };
+                    Ok((
+                        outputs,
+                        None,
+                        None,
+                        inputs.num_levels_integrated(),
+                        elapsed_us(start),
+                        down,
+                    ))
+                }),
+            )?,
+        },
+    };
+    let diagnostics = Diagnostics::from_results(&results, |(outputs, ..)| outputs.quality);
+    // An exception from the absorption model takes precedence over the
+    // cancellation it caused
+    if let Some(e) = absorption_model
+        .and_then(|model| model.error.into_inner().unwrap_or_else(|e| e.into_inner()))
+    {
+        return Err(diagnostics.attach(py, e, start));
+    }
+    log_failures(&results, &profiles, max_failure_logs);
+
+    // Copy the intermediate results to the output arrays
+    if verbosity >= Verbosity::Debug {
+        debug!("copying RTM output");
+    }
+    let mut output = Outputs::new(num_points, num_freq);
+    if let Some(levels) = diagnostic_levels {
+        output.tran_to_space = Some(Array3::zeros([num_points, num_freq, levels.len()]));
+        output.tb_up_above = Some(Array3::zeros([num_points, num_freq, levels.len()]));
+    }
+    if effective_temperature {
+        output.t_eff_up = Some(Array2::zeros([num_points, num_freq]));
+        output.t_eff_down = Some(Array2::zeros([num_points, num_freq]));
+    }
+    if air_mass_factor {
+        output.air_mass_factor = Some(Array2::zeros([num_points, num_freq]));
+    }
+    if down_level.is_some() {
+        output.tb_down_level = Some(Array2::from_elem([num_points, num_freq], T::nan()));
+    }
+    let mut quality_flags = quality_flags.then(|| Array1::zeros(num_points));
+    let mut num_levels_integrated = Array1::zeros(num_points);
+    let mut profile_time = profile_timing.then(|| Array1::zeros(num_points));
+    results
+        .into_iter()
+        .enumerate()
+        .try_for_each(|(index, rtm_output)| -> Result<_, RtmError> {
+            let (
+                rtm::RtmOutputs {
+                    tran,
+                    tb_up,
+                    tb_down,
+                    quality,
+                },
+                level_outputs,
+                effective,
+                num_levels,
+                elapsed,
+                down,
+            ) = rtm_output?;
+            if let Some(air_mass_factor) = output.air_mass_factor.as_mut() {
+                let row = sorted_rows.as_ref().map_or(index, |rows| rows[index]);
+                let factors = parameters_at(index)
+                    .incidence_angles()
+                    .iter()
+                    .map(|&inc| rtm::air_mass_factor(inc));
+                for (value, factor) in air_mass_factor.row_mut(row).iter_mut().zip(factors) {
+                    *value = factor;
+                }
+            }
+            // The results are in input order, and are permuted here if needed
+            let index = sorted_rows.as_ref().map_or(index, |rows| rows[index]);
+
+            let rhs = ArrayView1::from(tran.as_slice());
+            output.tran.index_axis_mut(Axis(0), index).assign(&rhs);
+
+            let rhs = ArrayView1::from(tb_up.as_slice());
+            output.tb_up.index_axis_mut(Axis(0), index).assign(&rhs);
+
+            let rhs = ArrayView1::from(tb_down.as_slice());
+            output.tb_down.index_axis_mut(Axis(0), index).assign(&rhs);
+
+            if let Some(quality_flags) = quality_flags.as_mut() {
+                quality_flags[index] = quality.bits();
+            }
+            // This is much less than `i32::MAX`, since the levels fit in memory
+            num_levels_integrated[index] = num_levels as i32;
+            if let (Some(profile_time), Some(elapsed)) = (profile_time.as_mut(), elapsed) {
+                profile_time[index] = elapsed;
+            }
+
+            if let (Some(level_outputs), Some(tran_to_space), Some(tb_up_above)) = (
+                level_outputs,
+                output.tran_to_space.as_mut(),
+                output.tb_up_above.as_mut(),
+            ) {
+                assign_rows(tran_to_space, index, &level_outputs.tran_to_space);
+                assign_rows(tb_up_above, index, &level_outputs.tb_up_above);
+            }
+
+            if let (Some(effective), Some(t_eff_up), Some(t_eff_down)) = (
+                effective,
+                output.t_eff_up.as_mut(),
+                output.t_eff_down.as_mut(),
+            ) {
+                let rhs = ArrayView1::from(effective.up.as_slice());
+                t_eff_up.index_axis_mut(Axis(0), index).assign(&rhs);
+
+                let rhs = ArrayView1::from(effective.down.as_slice());
+                t_eff_down.index_axis_mut(Axis(0), index).assign(&rhs);
+            }
+
+            if let (Some(down), Some(tb_down_level)) = (down, output.tb_down_level.as_mut()) {
+                let rhs = ArrayView1::from(down.as_slice());
+                tb_down_level.index_axis_mut(Axis(0), index).assign(&rhs);
+            }
+
+            Ok(())
+        })
+        .map_err(|e| diagnostics.attach(py, e.into(), start))?;
+
+    if let Some(noise) = noise.as_mut() {
+        if return_unnoised {
+            output.tb_up_unnoised = Some(output.tb_up.clone());
+        }
+        add_noise(noise, &mut output.tb_up)?;
+    }
+
+    let elapsed = start.elapsed();
+    info!(
+        "Finished RTM for {num_points} profiles and {num_freq} channels in {:.2} s ({:.0} profiles/s)",
+        elapsed.as_secs_f64(),
+        num_points as f64 / elapsed.as_secs_f64()
+    );
+
+    Ok(AtmoParameters {
+        arrays: T::wrap(output),
+        quality_flags,
+        num_levels_integrated,
+        profile_time,
+        emission: parameters.emission(),
+        tb_convention: parameters.tb_convention(),
+        diagnostics: Diagnostics {
+            elapsed,
+            ..diagnostics
+        },
+    })
+}
+
+/// Compute the RTM for profiles in `.npy` files that may be larger than
+/// memory, a chunk of points at a time.
+///
+/// The profile arguments are the same as for `compute_rtm`, except that each
+/// is the path of a `.npy` file, as written by `numpy.save`, or a memmap of
+/// one from `numpy.load(path, mmap_mode="r")`, whose file is used. The
+/// `pressure` is either (`num_levels`, ) or (`num_points`, `num_levels`). The
+/// files must be little-endian float32 or float64 in C order, and the RTM is
+/// computed in float32.
+///
+/// The points are read from the files `chunk_size` at a time, in order, with
+/// plain sequential reads of each file in Rust, so the memory use is bounded
+/// by one chunk of the inputs plus the outputs, which are (`num_points`,
+/// `num_freq`) arrays. The pages of the inputs are never touched through
+/// numpy, so they aren't kept resident by a memmap.
+///
+/// Optionally, `options` is an `RtmOptions`, as for `compute_rtm`, except that
+/// `effective_temperature`, `air_mass_factor` and `down_level` aren't
+/// supported. The progress is logged after each chunk. The outputs are the
+/// same as from `compute_rtm`, and if any point fails, a `ValueError` is
+/// raised.
+#[cfg(feature = "io")]
+#[pyfunction]
+#[pyo3(signature = (pressure, temperature, height, specific_humidity, liquid_content, surface_temperature, surface_height, surface_dewpoint, surface_pressure, incidence_angle, frequency, num_threads, chunk_size=100_000, options=None))]
+#[allow(clippy::too_many_arguments)]
+fn compute_rtm_npy(
+    py: Python<'_>,
+    pressure: &Bound<'_, PyAny>,
+    temperature: &Bound<'_, PyAny>,
+    height: &Bound<'_, PyAny>,
+    specific_humidity: &Bound<'_, PyAny>,
+    liquid_content: Option<&Bound<'_, PyAny>>,
+    surface_temperature: &Bound<'_, PyAny>,
+    surface_height: &Bound<'_, PyAny>,
+    surface_dewpoint: &Bound<'_, PyAny>,
+    surface_pressure: Option<&Bound<'_, PyAny>>,
+    incidence_angle: PyReadonlyArray1<'_, f32>,
+    frequency: PyReadonlyArray1<'_, f32>,
+    num_threads: Option<usize>,
+    chunk_size: usize,
+    options: Option<&Bound<'_, PyRtmOptions>>,
+) -> PyResult<AtmoParameters> {
+    use rtm::npy::{NpyProfileFiles, NpyProfiles};
+
+    let options = options.map_or_else(PyRtmOptions::default, |options| options.get().clone());
+    if options.effective_temperature || options.air_mass_factor || options.down_level.is_some() {
+        return Err(PyValueError::new_err(
+            "effective_temperature, air_mass_factor and down_level are not supported by compute_rtm_npy",
+        ));
+    }
+    let chunk_size = NonZeroUsize::new(chunk_size)
+        .ok_or_else(|| PyValueError::new_err("chunk_size must be at least 1"))?;
+    let files = NpyProfileFiles {
+        pressure: npy_path(pressure)?,
+        temperature: npy_path(temperature)?,
+        height: npy_path(height)?,
+        specific_humidity: npy_path(specific_humidity)?,
+        liquid_content: liquid_content.map(npy_path).transpose()?,
+        surface_temperature: npy_path(surface_temperature)?,
+        surface_height: npy_path(surface_height)?,
+        surface_dewpoint: npy_path(surface_dewpoint)?,
+        surface_pressure: surface_pressure.map(npy_path).transpose()?,
+    };
+    let mut profiles = NpyProfiles::open(&files)?;
+    let parameters = parameters(&frequency, &incidence_angle)?.with_options(&options.rtm);
+    let top_pressure = options.top_pressure.map(f32::from_f64_lossy);
+
+    let num_points = profiles.num_points();
+    let num_freq = parameters.len();
+    if options.verbosity >= Verbosity::Progress {
+        info!(
+            "Processing atmosphere RTM for {num_points} profiles from .npy files and {num_freq} \
+             channels, {chunk_size} profiles at a time"
+        );
+    }
+    let start = Instant::now();
+
+    let mut output = Outputs::<f32>::new(num_points, num_freq);
+    let mut quality_flags = options.quality_flags.then(|| Array1::zeros(num_points));
+    let mut num_levels_integrated = Array1::zeros(num_points);
+    let mut profile_time = options.profile_timing.then(|| Array1::zeros(num_points));
+    let mut diagnostics = Diagnostics::default();
+    for chunk_start in (0..num_points).step_by(chunk_size.get()) {
+        let chunk_len = chunk_size.get().min(num_points - chunk_start);
+        let chunk = py.allow_threads(|| profiles.read_chunk::<f32>(chunk_start, chunk_len))?;
+        if options.verbosity >= Verbosity::Debug {
+            debug!("read profiles {chunk_start} to {}", chunk_start + chunk_len);
+        }
+        let source = chunk.profiles(top_pressure)?;
+        let cancelled = AtomicBool::new(false);
+        let results = run_points(
+            py,
+            num_threads,
+            num_freq,
+            &options.rtm,
+            Verbosity::Quiet,
+            &cancelled,
+            par_timed_inputs(&source, &options.rtm, &cancelled, options.profile_timing).map(
+                |(start, inputs)| {
+                    let inputs = inputs?.with_options(&options.rtm);
+                    let outputs = inputs.run(&parameters);
+                    Ok((outputs, inputs.num_levels_integrated(), elapsed_us(start)))
+                },
+            ),
+        )?;
+
+        for result in &results {
+            diagnostics.add(result.as_ref().map(|(outputs, ..)| outputs.quality));
+        }
+        for (offset, result) in results.into_iter().enumerate() {
+            let (outputs, num_levels, elapsed) =
+                result.map_err(|e| diagnostics.attach(py, e.into(), start))?;
+            let index = chunk_start + offset;
+            for (array, row) in [
+                (&mut output.tran, &outputs.tran),
+                (&mut output.tb_up, &outputs.tb_up),
+                (&mut output.tb_down, &outputs.tb_down),
+            ] {
+                array
+                    .index_axis_mut(Axis(0), index)
+                    .assign(&ArrayView1::from(row.as_slice()));
+            }
+            if let Some(quality_flags) = quality_flags.as_mut() {
+                quality_flags[index] = outputs.quality.bits();
+            }
+            num_levels_integrated[index] = num_levels as i32;
+            if let (Some(profile_time), Some(elapsed)) = (profile_time.as_mut(), elapsed) {
+                profile_time[index] = elapsed;
+            }
+        }
+        if options.verbosity >= Verbosity::Progress {
+            log_progress(chunk_start + chunk_len, num_points, start.elapsed());
+        }
+    }
+
+    let elapsed = start.elapsed();
+    info!(
+        "Finished RTM for {num_points} profiles and {num_freq} channels in {:.2} s ({:.0} profiles/s)",
+        elapsed.as_secs_f64(),
+        num_points as f64 / elapsed.as_secs_f64()
+    );
+    Ok(AtmoParameters {
+        arrays: f32::wrap(output),
+        quality_flags,
+        num_levels_integrated,
+        profile_time,
+        emission: parameters.emission(),
+        tb_convention: parameters.tb_convention(),
+        diagnostics: Diagnostics {
+            elapsed,
+            ..diagnostics
+        },
+    })
+}
+
+/// Compute the RTM for profiles in `.npy` files, as in `compute_rtm_npy`, and
+/// write the outputs into the arrays `tran`, `tb_up`, and `tb_down` instead of
+/// returning them, so that neither the inputs nor the outputs need to fit in
+/// memory.
+///
+/// The outputs are writable float32 arrays of shape (`num_points`,
+/// `num_freq`), typically memmaps of `.npy` files on disk from
+/// `numpy.lib.format.open_memmap(path, mode="w+", dtype=np.float32,
+/// shape=(num_points, num_freq))`. The points are run `chunk_size` at a time,
+/// and the worker threads write the row of each point directly into the
+/// arrays as soon as it's done, so only one chunk of the inputs is in memory
+/// at once, and the written pages of the outputs are left for the operating
+/// system to write back to disk. Call `flush` on the memmaps afterwards to
+/// make sure they're on disk.
+///
+/// The workers write concurrently without any locking, which is safe since
+/// each point has its own row, so no two workers ever write to the same
+/// memory. For that to hold, the three arrays must not overlap each other
+/// (which raises an error), and no other thread or process may read or write
+/// them during the call; the rows are only complete once it returns.
+///
+/// Optionally, `options` is an `RtmOptions`, as for `compute_rtm`, except that
+/// `quality_flags`, `effective_temperature`, `air_mass_factor`, `down_level`
+/// and `profile_timing` aren't supported, since they have no array to write
+/// to. The progress is logged after each chunk, and if any point fails, a
+/// `ValueError` is raised, with the rows of the previous chunks written. The
+/// `Diagnostics` of the run are returned.
+#[cfg(feature = "io")]
+#[pyfunction]
+#[pyo3(signature = (tran, tb_up, tb_down, pressure, temperature, height, specific_humidity, liquid_content, surface_temperature, surface_height, surface_dewpoint, surface_pressure, incidence_angle, frequency, num_threads=None, chunk_size=100_000, options=None))]
+#[allow(clippy::too_many_arguments)]
+fn compute_rtm_npy_into(
+    py: Python<'_>,
+    mut tran: PyReadwriteArray2<'_, f32>,
+    mut tb_up: PyReadwriteArray2<'_, f32>,
+    mut tb_down: PyReadwriteArray2<'_, f32>,
+    pressure: &Bound<'_, PyAny>,
+    temperature: &Bound<'_, PyAny>,
+    height: &Bound<'_, PyAny>,
+    specific_humidity: &Bound<'_, PyAny>,
+    liquid_content: Option<&Bound<'_, PyAny>>,
+    surface_temperature: &Bound<'_, PyAny>,
+    surface_height: &Bound<'_, PyAny>,
+    surface_dewpoint: &Bound<'_, PyAny>,
+    surface_pressure: Option<&Bound<'_, PyAny>>,
+    incidence_angle: PyReadonlyArray1<'_, f32>,
+    frequency: PyReadonlyArray1<'_, f32>,
+    num_threads: Option<usize>,
+    chunk_size: usize,
+    options: Option<&Bound<'_, PyRtmOptions>>,
+) -> PyResult<Diagnostics> {
+    use rtm::npy::{NpyProfileFiles, NpyProfiles};
+
+    let options = options.map_or_else(PyRtmOptions::default, |options| options.get().clone());
+    if options.quality_flags
+        || options.effective_temperature
+        || options.air_mass_factor
+        || options.down_level.is_some()
+        || options.profile_timing
+    {
+        return Err(PyValueError::new_err(
+            "quality_flags, effective_temperature, air_mass_factor, down_level and \
+             profile_timing are not supported by compute_rtm_npy_into",
+        ));
+    }
+    let chunk_size = NonZeroUsize::new(chunk_size)
+        .ok_or_else(|| PyValueError::new_err("chunk_size must be at least 1"))?;
+    let files = NpyProfileFiles {
+        pressure: npy_path(pressure)?,
+        temperature: npy_path(temperature)?,
+        height: npy_path(height)?,
+        specific_humidity: npy_path(specific_humidity)?,
+        liquid_content: liquid_content.map(npy_path).transpose()?,
+        surface_temperature: npy_path(surface_temperature)?,
+        surface_height: npy_path(surface_height)?,
+        surface_dewpoint: npy_path(surface_dewpoint)?,
+        surface_pressure: surface_pressure.map(npy_path).transpose()?,
+    };
+    let mut profiles = NpyProfiles::open(&files)?;
+    let parameters = parameters(&frequency, &incidence_angle)?.with_options(&options.rtm);
+    let top_pressure = options.top_pressure.map(f32::from_f64_lossy);
+
+    let num_points = profiles.num_points();
+    let num_freq = parameters.len();
+    let (mut tran, mut tb_up, mut tb_down) = (
+        tran.as_array_mut(),
+        tb_up.as_array_mut(),
+        tb_down.as_array_mut(),
+    );
+    for (name, array) in [("tran", &tran), ("tb_up", &tb_up), ("tb_down", &tb_down)] {
+        if array.dim() != (num_points, num_freq) {
+            return Err(PyValueError::new_err(format!(
+                "{name} has shape {:?}, but the outputs are ({num_points}, {num_freq})",
+                array.shape()
+            )));
+        }
+    }
+    if options.verbosity >= Verbosity::Progress {
+        info!(
+            "Processing atmosphere RTM for {num_points} profiles from .npy files and {num_freq} \
+             channels into the output arrays, {chunk_size} profiles at a time"
+        );
+    }
+    let start = Instant::now();
+
+    let mut diagnostics = Diagnostics::default();
+    for chunk_start in (0..num_points).step_by(chunk_size.get()) {
+        let chunk_len = chunk_size.get().min(num_points - chunk_start);
+        let chunk = py.allow_threads(|| profiles.read_chunk::<f32>(chunk_start, chunk_len))?;
+        if options.verbosity >= Verbosity::Debug {
+            debug!("read profiles {chunk_start} to {}", chunk_start + chunk_len);
+        }
+        let source = chunk.profiles(top_pressure)?;
+
+        // The disjoint output rows of the points in the chunk, each of which
+        // is handed to the worker that runs its point
+        let rows = s![chunk_start..chunk_start + chunk_len, ..];
+        let (mut tran, mut tb_up, mut tb_down) = (
+            tran.slice_mut(rows),
+            tb_up.slice_mut(rows),
+            tb_down.slice_mut(rows),
+        );
+        let rows: Vec<_> = tran
+            .outer_iter_mut()
+            .zip(tb_up.outer_iter_mut())
+            .zip(tb_down.outer_iter_mut())
+            .collect();
+
+        let cancelled = AtomicBool::new(false);
+        let results = run_points(
+            py,
+            num_threads,
+            num_freq,
+            &options.rtm,
+            Verbosity::Quiet,
+            &cancelled,
+            par_inputs(&source, &options.rtm, &cancelled).zip(rows).map(
+                |(inputs, ((mut tran, mut tb_up), mut tb_down))| {
+                    let inputs = inputs?.with_options(&options.rtm);
+                    let outputs = inputs.run(&parameters);
+                    tran.assign(&ArrayView1::from(outputs.tran.as_slice()));
+                    tb_up.assign(&ArrayView1::from(outputs.tb_up.as_slice()));
+                    tb_down.assign(&ArrayView1::from(outputs.tb_down.as_slice()));
+                    Ok(outputs.quality)
+                },
+            ),
+        )?;
+        for result in &results {
+            diagnostics.add(result.as_ref().copied());
+        }
+        if let Some(Err(e)) = results.into_iter().find(Result::is_err) {
+            return Err(diagnostics.attach(py, e.into(), start));
+        }
+        if options.verbosity >= Verbosity::Progress {
+            log_progress(chunk_start + chunk_len, num_points, start.elapsed());
+        }
+    }
+
+    let elapsed = start.elapsed();
+    info!(
+        "Finished RTM for {num_points} profiles and {num_freq} channels in {:.2} s ({:.0} profiles/s)",
+        elapsed.as_secs_f64(),
+        num_points as f64 / elapsed.as_secs_f64()
+    );
+    Ok(Diagnostics {
+        elapsed,
+        ..diagnostics
+    })
+}
+
+/// The path of a `.npy` file given as a path, or as a numpy memmap of the file.
+#[cfg(feature = "io")]
+fn npy_path(file: &Bound<'_, PyAny>) -> PyResult<std::path::PathBuf> {
+    match file.getattr("filename") {
+        Ok(filename) if !filename.is_none() => filename.extract(),
+        _ => file.extract(),
+    }
+}
+
+/// Compute the RTM and write the outputs to a Zarr store a chunk of points at
+/// a time, so that runs with more points than fit in memory can be analyzed
+/// with xarray and dask.
+///
+/// `store_path` is the directory of the store, which is created if needed,
+/// and the profile arguments and `parameters` are the same as for
+/// `compute_rtm_block`. The points are run `chunk_points` at a time, in
+/// order, and each chunk is written as soon as it's done, so only one chunk
+/// of the outputs is in memory at once.
+///
+/// The store is a Zarr (format 2) group with the arrays `tran`, `tb_up`, and
+/// `tb_down`, with shape (`num_points`, `num_freq`) and chunks of
+/// (`chunk_points`, `num_freq`), `num_levels_integrated` and, if requested,
+/// `quality_flags`, with shape (`num_points`, ), and the coordinates
+/// `frequency` and `incidence_angle` of the channels. The dimensions are
+/// named `point` and `channel` for xarray, and the arrays have `units`
+/// attributes. The attributes of the group are the model info from
+/// `get_model_info`, and the `emission` and `tb_convention`. The metadata is
+/// consolidated, so `xarray.open_zarr(store_path)` opens it directly.
+///
+/// Optionally, `options` is an `RtmOptions`, as for `compute_rtm`, except that
+/// `effective_temperature`, `air_mass_factor`, `down_level` and
+/// `profile_timing` aren't supported. The progress is logged after each
+/// chunk, and if any point fails, a `ValueError` is raised, leaving the
+/// chunks before it in the store. The `Diagnostics` of the run are returned.
+#[cfg(feature = "io")]
+#[pyfunction]
+#[pyo3(signature = (store_path, pressure, temperature, height, specific_humidity, liquid_content, surface_temperature, surface_height, surface_dewpoint, surface_pressure, parameters, num_threads=None, chunk_points=100_000, options=None))]
+#[allow(clippy::too_many_arguments)]
+fn compute_rtm_to_zarr<'py>(
+    py: Python<'py>,
+    store_path: std::path::PathBuf,
+    pressure: &Bound<'py, PyAny>,
+    temperature: &Bound<'py, PyAny>,
+    height: &Bound<'py, PyAny>,
+    specific_humidity: &Bound<'py, PyAny>,
+    liquid_content: Option<&Bound<'py, PyAny>>,
+    surface_temperature: &Bound<'py, PyAny>,
+    surface_height: &Bound<'py, PyAny>,
+    surface_dewpoint: &Bound<'py, PyAny>,
+    surface_pressure: Option<&Bound<'py, PyAny>>,
+    parameters: &Bound<'py, PyRtmParameters>,
+    num_threads: Option<usize>,
+    chunk_points: usize,
+    options: Option<&Bound<'py, PyRtmOptions>>,
+) -> PyResult<Diagnostics> {
+    let options = options.map_or_else(PyRtmOptions::default, |options| options.get().clone());
+    if options.effective_temperature
+        || options.air_mass_factor
+        || options.down_level.is_some()
+        || options.profile_timing
+    {
+        return Err(PyValueError::new_err(
+            "effective_temperature, air_mass_factor, down_level and profile_timing are not \
+             supported by compute_rtm_to_zarr",
+        ));
+    }
+    let chunk_points = NonZeroUsize::new(chunk_points)
+        .ok_or_else(|| PyValueError::new_err("chunk_points must be at least 1"))?;
+    let compute_rtm_to_zarr_typed = if temperature.downcast::<PyArray2<f64>>().is_ok() {
+        compute_rtm_to_zarr_typed::<f64>
+    } else {
+        compute_rtm_to_zarr_typed::<f32>
+    };
+    compute_rtm_to_zarr_typed(
+        py,
+        &store_path,
+        pressure,
+        temperature,
+        height,
+        specific_humidity,
+        liquid_content,
+        surface_temperature,
+        surface_height,
+        surface_dewpoint,
+        surface_pressure,
+        parameters.get(),
+        num_threads,
+        chunk_points,
+        &options,
+    )
+}
+
+/// `compute_rtm_to_zarr` in the precision `T`.
+#[cfg(feature = "io")]
+#[allow(clippy::too_many_arguments)]
+fn compute_rtm_to_zarr_typed<'py, T: PyFloat + rtm::zarr::ZarrElement>(
+    py: Python<'py>,
+    store_path: &std::path::Path,
+    pressure: &Bound<'py, PyAny>,
+    temperature: &Bound<'py, PyAny>,
+    height: &Bound<'py, PyAny>,
+    specific_humidity: &Bound<'py, PyAny>,
+    liquid_content: Option<&Bound<'py, PyAny>>,
+    surface_temperature: &Bound<'py, PyAny>,
+    surface_height: &Bound<'py, PyAny>,
+    surface_dewpoint: &Bound<'py, PyAny>,
+    surface_pressure: Option<&Bound<'py, PyAny>>,
+    parameters: &PyRtmParameters,
+    num_threads: Option<usize>,
+    chunk_points: NonZeroUsize,
+    options: &PyRtmOptions,
+) -> PyResult<Diagnostics> {
+    use rtm::zarr::{ZarrAttribute, ZarrGroup};
+
+    let pressure: PressureArray<'_, T> = pressure.extract()?;
+    let temperature: PyReadonlyArray2<'_, T> = temperature.extract()?;
+    let height: PyReadonlyArray2<'_, T> = height.extract()?;
+    let specific_humidity: PyReadonlyArray2<'_, T> = specific_humidity.extract()?;
+    let liquid_content: Option<PyReadonlyArray2<'_, T>> =
+        liquid_content.map(|a| a.extract()).transpose()?;
+    let surface_temperature: PyReadonlyArray1<'_, T> = surface_temperature.extract()?;
+    let surface_height: PyReadonlyArray1<'_, T> = surface_height.extract()?;
+    let surface_dewpoint: PyReadonlyArray1<'_, T> = surface_dewpoint.extract()?;
+    let surface_pressure: Option<PyReadonlyArray1<'_, T>> =
+        surface_pressure.map(|a| a.extract()).transpose()?;
+    let profiles = profiles(
+        &pressure,
+        &temperature,
+        &height,
+        &specific_humidity,
+        liquid_content.as_ref(),
+        &surface_temperature,
+        &surface_height,
+        &surface_dewpoint,
+        surface_pressure.as_ref(),
+        None,
+        None,
+        options.top_pressure.map(T::from_f64_lossy),
+    )?;
+    let parameters = parameters.to_precision::<T>()?.with_options(&options.rtm);
+
+    let num_points = profiles.len();
+    let num_freq = parameters.len();
+    let text = |text: &str| ZarrAttribute::Text(text.to_string());
+    let attributes = |units: &str| {
+        vec![
+            ("units".to_string(), text(units)),
+            ("coordinates".to_string(), text("frequency incidence_angle")),
+        ]
+    };
+    let model_info = vec![
+        ("version".to_string(), text(rtm::VERSION)),
+        ("git_describe".to_string(), text(rtm::GIT_DESCRIBE)),
+        (
+            "spectroscopy".to_string(),
+            ZarrAttribute::Map(
+                rtm::SPECTROSCOPY
+                    .iter()
+                    .map(|(absorber, model)| (absorber.to_string(), text(model)))
+                    .collect(),
+            ),
+        ),
+        (
+            "frequency_limits".to_string(),
+            ZarrAttribute::Map(
+                rtm::FREQUENCY_LIMITS
+                    .iter()
+                    .map(|&(absorber, limit)| (absorber.to_string(), ZarrAttribute::Number(limit)))
+                    .collect(),
+            ),
+        ),
+        ("emission".to_string(), text(parameters.emission().name())),
+        (
+            "tb_convention".to_string(),
+            text(parameters.tb_convention().name()),
+        ),
+    ];
+
+    let mut group = ZarrGroup::create(store_path, model_info)?;
+    let channel_rows = NonZeroUsize::new(num_freq.max(1)).expect("at least 1");
+    for (name, values, units) in [
+        ("frequency", parameters.frequencies(), "GHz"),
+        ("incidence_angle", parameters.incidence_angles(), "degree"),
+    ] {
+        let array = group.create_array::<T>(
+            name,
+            &[num_freq],
+            channel_rows,
+            &["channel"],
+            vec![("units".to_string(), text(units))],
+        )?;
+        array.write_all(ArrayView1::from(values).into_dyn())?;
+    }
+    let shape = [num_points, num_freq];
+    let dimensions = ["point", "channel"];
+    let tran =
+        group.create_array::<T>("tran", &shape, chunk_points, &dimensions, attributes("1"))?;
+    let tb_up =
+        group.create_array::<T>("tb_up", &shape, chunk_points, &dimensions, attributes("K"))?;
+    let tb_down = group.create_array::<T>(
+        "tb_down",
+        &shape,
+        chunk_points,
+        &dimensions,
+        attributes("K"),
+    )?;
+    let quality_flags = options
+        .quality_flags
+        .then(|| {
+            group.create_array::<u32>(
+                "quality_flags",
+                &[num_points],
+                chunk_points,
+                &["point"],
+                vec![("units".to_string(), text("1"))],
+            )
+        })
+        .transpose()?;
+    let num_levels_integrated = group.create_array::<i32>(
+        "num_levels_integrated",
+        &[num_points],
+        chunk_points,
+        &["point"],
+        vec![("units".to_string(), text("1"))],
+    )?;
+
+    if options.verbosity >= Verbosity::Progress {
+        info!(
+            "Processing atmosphere RTM for {num_points} profiles and {num_freq} channels to the \
+             Zarr store {}, {chunk_points} profiles at a time",
+            store_path.display()
+        );
+    }
+    let start = Instant::now();
+    let mut diagnostics = Diagnostics::default();
+    for chunk in 0..tran.num_chunks() {
+        let rows = tran.chunk_range(chunk);
+        let source = profiles.points(rows.clone())?;
+        let cancelled = AtomicBool::new(false);
+        let results = run_points(
+            py,
+            num_threads,
+            num_freq,
+            &options.rtm,
+            Verbosity::Quiet,
+            &cancelled,
+            par_timed_inputs(&source, &options.rtm, &cancelled, false).map(|(_, inputs)| {
+                let inputs = inputs?.with_options(&options.rtm);
+                let outputs = inputs.run(&parameters);
+                Ok((outputs, inputs.num_levels_integrated()))
+            }),
+        )?;
+
+        let mut output = Outputs::<T>::new(rows.len(), num_freq);
+        let mut chunk_flags = Array1::zeros(rows.len());
+        let mut chunk_levels = Array1::zeros(rows.len());
+        for result in &results {
+            diagnostics.add(result.as_ref().map(|(outputs, _)| outputs.quality));
+        }
+        for (index, result) in results.into_iter().enumerate() {
+            let (outputs, num_levels) =
+                result.map_err(|e| diagnostics.attach(py, e.into(), start))?;
+            for (array, row) in [
+                (&mut output.tran, &outputs.tran),
+                (&mut output.tb_up, &outputs.tb_up),
+                (&mut output.tb_down, &outputs.tb_down),
+            ] {
+                array
+                    .index_axis_mut(Axis(0), index)
+                    .assign(&ArrayView1::from(row.as_slice()));
+            }
+            chunk_flags[index] = outputs.quality.bits();
+            chunk_levels[index] = num_levels as i32;
+        }
+
+        py.allow_threads(|| -> Result<(), RtmError> {
+            tran.write_chunk(chunk, output.tran.view().into_dyn())?;
+            tb_up.write_chunk(chunk, output.tb_up.view().into_dyn())?;
+            tb_down.write_chunk(chunk, output.tb_down.view().into_dyn())?;
+            if let Some(quality_flags) = &quality_flags {
+                quality_flags.write_chunk(chunk, chunk_flags.view().into_dyn())?;
+            }
+            num_levels_integrated.write_chunk(chunk, chunk_levels.view().into_dyn())
+        })?;
+        if options.verbosity >= Verbosity::Debug {
+            debug!("wrote profiles {} to {}", rows.start, rows.end);
+        }
+        if options.verbosity >= Verbosity::Progress {
+            log_progress(rows.end, num_points, start.elapsed());
+        }
+    }
+
+    let elapsed = start.elapsed();
+    info!(
+        "Finished RTM for {num_points} profiles and {num_freq} channels in {:.2} s ({:.0} profiles/s)",
+        elapsed.as_secs_f64(),
+        num_points as f64 / elapsed.as_secs_f64()
+    );
+    Ok(Diagnostics {
+        elapsed,
+        ..diagnostics
+    })
+}
+
+/// Compute the radiative transfer model with the absorption given externally.
+///
+/// This skips the absorption models in this package and only does the
+/// radiative transfer integration, for instance to use absorption from a
+/// line-by-line model. The levels start at the surface and go upward, so any
+/// levels below the surface need to be removed beforehand. At least two levels
+/// are needed.
+///
+/// `temperature`: physical temperature in K, with shape (`num_points`,
+/// `num_levels`)
+///
+/// `height`: geometric height in m, with shape (`num_points`, `num_levels`)
+///
+/// `absorption`: absorption coefficient in Np/m, with shape (`num_points`,
+/// `num_freq`, `num_levels`)
+///
+/// `incidence_angle`: Earth incidence angle in degrees, with shape
+/// (`num_freq`, )
+///
+/// The number of worker threads is controlled by `num_threads`, as for
+/// `compute_rtm`. The emission is Rayleigh-Jeans.
+#[pyfunction]
+#[pyo3(signature = (temperature, height, absorption, incidence_angle, num_threads))]
+fn compute_rtm_from_absorption(
+    py: Python<'_>,
+    temperature: PyReadonlyArray2<'_, f32>,
+    height: PyReadonlyArray2<'_, f32>,
+    absorption: PyReadonlyArray3<'_, f32>,
+    incidence_angle: PyReadonlyArray1<'_, f32>,
+    num_threads: Option<usize>,
+) -> PyResult<AtmoParameters> {
+    let temperature = temperature.as_array();
+    let height = height.as_array();
+    let absorption = absorption.as_array();
+    let incidence_angle = incidence_angle.as_slice()?;
+
+    let (num_points, num_levels) = temperature.dim();
+    let num_freq = incidence_angle.len();
+    if num_levels < 2
+        || height.dim() != (num_points, num_levels)
+        || absorption.dim() != (num_points, num_freq, num_levels)
+    {
+        return Err(RtmError::InconsistentInputs.into());
+    }
+
+    info!("Processing atmosphere RTM from absorption for {num_points} profiles");
+    let start = Instant::now();
+
+    let options = RtmOptions::default();
+    let cancelled = AtomicBool::new(false);
+    let results = run_points(
+        py,
+        num_threads,
+        num_freq,
+        &options,
+        Verbosity::Progress,
+        &cancelled,
+        (0..num_points)
+            .into_par_iter()
+            .with_max_len(options.work_chunk_size.get())
+            .map(|point| {
+                if cancelled.load(Ordering::Relaxed) {
+                    return Err(RtmError::Cancelled);
+                }
+                let absorption: ChannelVec<&[f32]> = absorption
+                    .view()
+                    .index_axis_move(Axis(0), point)
+                    .to_slice()
+                    .ok_or(RtmError::NotContiguous)?
+                    .chunks_exact(num_levels)
+                    .collect();
+                rtm::run_from_absorption(
+                    incidence_angle,
+                    temperature
+                        .row(point)
+                        .to_slice()
+                        .ok_or(RtmError::NotContiguous)?,
+                    height
+                        .row(point)
+                        .to_slice()
+                        .ok_or(RtmError::NotContiguous)?,
+                    &absorption,
+                )
+            }),
+    )?;
+
+    debug!("copying RTM output");
+    let mut output = Outputs::<f32>::new(num_points, num_freq);
+    let diagnostics = Diagnostics::from_results(&results, |outputs| outputs.quality);
+    results
+        .into_iter()
+        .enumerate()
+        .try_for_each(|(index, rtm_output)| -> Result<_, RtmError> {
+            let rtm_output = rtm_output?;
+            let rhs = ArrayView1::from(rtm_output.tran.as_slice());
+            output.tran.index_axis_mut(Axis(0), index).assign(&rhs);
+            let rhs = ArrayView1::from(rtm_output.tb_up.as_slice());
+            output.tb_up.index_axis_mut(Axis(0), index).assign(&rhs);
+            let rhs = ArrayView1::from(rtm_output.tb_down.as_slice());
+            output.tb_down.index_axis_mut(Axis(0), index).assign(&rhs);
+            Ok(())
+        })
+        .map_err(|e| diagnostics.attach(py, e.into(), start))?;
+
+    Ok(AtmoParameters {
+        arrays: f32::wrap(output),
+        quality_flags: None,
+        // All of the given levels are integrated
+        num_levels_integrated: Array1::from_elem(num_points, num_levels as i32),
+        profile_time: None,
+        emission: Emission::RayleighJeans,
+        tb_convention: TbConvention::RayleighJeans,
+        diagnostics: Diagnostics {
+            elapsed: start.elapsed(),
+            ..diagnostics
+        },
+    })
+}
+
+/// Compute the contribution of each layer to the upwelling brightness
+/// temperature.
+///
+/// The inputs are the same as for `compute_rtm`. This is intended for a
+/// modest number of selected points, since the output is dimensioned as
+/// (`num_points`, `num_freq`, `num_levels`).
+///
+/// The value at level index `k` is the contribution, in K, of the layer whose
+/// top is at pressure level `k`: the layer emission times its transmissivity
+/// to space. Layers below the surface, or above `top_pressure`, have no
+/// contribution. Summing over the levels reproduces the `tb_up` output of
+/// `compute_rtm` with the default `emission` and `integration_scheme` and no
+/// `observer_height`.
+#[pyfunction]
+#[pyo3(signature = (pressure, temperature, height, specific_humidity, liquid_content, surface_temperature, surface_height, surface_dewpoint, surface_pressure, incidence_angle, frequency, num_threads, top_pressure=None))]
+#[allow(clippy::too_many_arguments)]
+fn compute_contribution_functions<'py>(
+    py: Python<'py>,
+    pressure: PressureArray<'_, f32>,
+    temperature: PyReadonlyArray2<'_, f32>,
+    height: PyReadonlyArray2<'_, f32>,
+    specific_humidity: PyReadonlyArray2<'_, f32>,
+    liquid_content: Option<PyReadonlyArray2<'_, f32>>,
+    surface_temperature: PyReadonlyArray1<'_, f32>,
+    surface_height: PyReadonlyArray1<'_, f32>,
+    surface_dewpoint: PyReadonlyArray1<'_, f32>,
+    surface_pressure: Option<PyReadonlyArray1<'_, f32>>,
+    incidence_angle: PyReadonlyArray1<'_, f32>,
+    frequency: PyReadonlyArray1<'_, f32>,
+    num_threads: Option<usize>,
+    top_pressure: Option<f32>,
+) -> PyResult<Bound<'py, PyArray3<f32>>> {
+    let profiles = profiles(
+        &pressure,
+        &temperature,
+        &height,
+        &specific_humidity,
+        liquid_content.as_ref(),
+        &surface_temperature,
+        &surface_height,
+        &surface_dewpoint,
+        surface_pressure.as_ref(),
+        None,
+        None,
+        top_pressure,
+    )?;
+    let parameters = parameters(&frequency, &incidence_angle)?;
+
+    let num_points = profiles.len();
+    let num_levels = profiles.num_levels();
+    let num_freq = frequency.len();
+
+    info!("Processing contribution functions for {num_points} profiles and {num_freq} channels");
+
+    let cancelled = AtomicBool::new(false);
+    let results = run_points(
+        py,
+        num_threads,
+        num_freq,
+        &RtmOptions::default(),
+        Verbosity::Progress,
+        &cancelled,
+        par_inputs(&profiles, &RtmOptions::default(), &cancelled)
+            .map(|inputs| Ok(inputs?.contributions(&parameters))),
+    )?;
+
+    debug!("copying contribution functions");
+    let mut output = Array3::zeros([num_points, num_freq, num_levels]);
+    results.into_iter().enumerate().try_for_each(
+        |(index, contributions)| -> Result<_, RtmError> {
+            assign_rows(&mut output, index, &contributions?);
+            Ok(())
+        },
+    )?;
+
+    Ok(output.into_pyarray(py))
+}
+
+/// Check whether the RTM gives valid outputs for each point, for a fast
+/// quality control pass over a dataset.
+///
+/// The inputs are the same as for `compute_rtm`. The full RTM is run for each
+/// point, but the outputs aren't kept, so this needs much less memory. A point
+/// is valid if each `tran` is finite and from 0 to 1, and each `tb_up` and
+/// `tb_down` is finite and not negative. Points that fail, for instance with no
+/// profile levels above the surface, are not valid, rather than raising an
+/// error.
+///
+/// Returns a boolean array with shape (`num_points`, ).
+#[pyfunction]
+#[pyo3(signature = (pressure, temperature, height, specific_humidity, liquid_content, surface_temperature, surface_height, surface_dewpoint, surface_pressure, incidence_angle, frequency, num_threads, top_pressure=None))]
+#[allow(clippy::too_many_arguments)]
+fn validate_rtm<'py>(
+    py: Python<'py>,
+    pressure: PressureArray<'_, f32>,
+    temperature: PyReadonlyArray2<'_, f32>,
+    height: PyReadonlyArray2<'_, f32>,
+    specific_humidity: PyReadonlyArray2<'_, f32>,
+    liquid_content: Option<PyReadonlyArray2<'_, f32>>,
+    surface_temperature: PyReadonlyArray1<'_, f32>,
+    surface_height: PyReadonlyArray1<'_, f32>,
+    surface_dewpoint: PyReadonlyArray1<'_, f32>,
+    surface_pressure: Option<PyReadonlyArray1<'_, f32>>,
+    incidence_angle: PyReadonlyArray1<'_, f32>,
+    frequency: PyReadonlyArray1<'_, f32>,
+    num_threads: Option<usize>,
+    top_pressure: Option<f32>,
+) -> PyResult<Bound<'py, PyArray1<bool>>> {
+    let profiles = profiles(
+        &pressure,
+        &temperature,
+        &height,
+        &specific_humidity,
+        liquid_content.as_ref(),
+        &surface_temperature,
+        &surface_height,
+        &surface_dewpoint,
+        surface_pressure.as_ref(),
+        None,
+        None,
+        top_pressure,
+    )?;
+    let parameters = parameters(&frequency, &incidence_angle)?;
+    let num_points = profiles.len();
+
+    info!(
+        "Validating atmosphere RTM for {num_points} profiles and {} channels",
+        parameters.len()
+    );
+
+    let cancelled = AtomicBool::new(false);
+    let results = run_points(
+        py,
+        num_threads,
+        parameters.len(),
+        &RtmOptions::default(),
+        Verbosity::Progress,
+        &cancelled,
+        par_inputs(&profiles, &RtmOptions::default(), &cancelled).map(|inputs| match inputs {
+            Ok(inputs) => Ok(inputs.run(&parameters).is_valid()),
+            Err(RtmError::Cancelled) => Err(RtmError::Cancelled),
+            Err(_) => Ok(false),
+        }),
+    )?;
+    let valid = results.into_iter().collect::<Result<Array1<bool>, _>>()?;
+    Ok(valid.into_pyarray(py))
+}
+
+/// Compute summary statistics of the atmospheric column for each point.
+///
+/// The profile inputs are the same as for `compute_rtm`. The statistics are
+/// computed from the same profiles that the RTM uses, after the surface values
+/// are inserted and the levels below the surface (or above `top_pressure`) are
+/// removed, so they are consistent with it.
+///
+/// The returned dict has the following arrays, each with shape (`num_points`,
+/// ):
+///
+/// - `pressure_weighted_temperature`: mean temperature in K, weighted by the
+///   pressure thickness of each layer
+/// - `vapor_weighted_temperature`: mean temperature in K, weighted by the water
+///   vapor mass in each layer
+/// - `water_vapor`: total column water vapor in kg/m²
+/// - `liquid_water`: total column cloud liquid water in kg/m²
+#[pyfunction]
+#[pyo3(signature = (pressure, temperature, height, specific_humidity, liquid_content, surface_temperature, surface_height, surface_dewpoint, surface_pressure, num_threads, top_pressure=None))]
+#[allow(clippy::too_many_arguments)]
+fn compute_column_stats<'py>(
+    py: Python<'py>,
+    pressure: PressureArray<'_, f32>,
+    temperature: PyReadonlyArray2<'_, f32>,
+    height: PyReadonlyArray2<'_, f32>,
+    specific_humidity: PyReadonlyArray2<'_, f32>,
+    liquid_content: Option<PyReadonlyArray2<'_, f32>>,
+    surface_temperature: PyReadonlyArray1<'_, f32>,
+    surface_height: PyReadonlyArray1<'_, f32>,
+    surface_dewpoint: PyReadonlyArray1<'_, f32>,
+    surface_pressure: Option<PyReadonlyArray1<'_, f32>>,
+    num_threads: Option<usize>,
+    top_pressure: Option<f32>,
+) -> PyResult<Bound<'py, PyDict>> {
+    let profiles = profiles(
+        &pressure,
+        &temperature,
+        &height,
+        &specific_humidity,
+        liquid_content.as_ref(),
+        &surface_temperature,
+        &surface_height,
+        &surface_dewpoint,
+        surface_pressure.as_ref(),
+        None,
+        None,
+        top_pressure,
+    )?;
+    let num_points = profiles.len();
+
+    info!("Processing column statistics for {num_points} profiles");
+
+    let cancelled = AtomicBool::new(false);
+    let results = run_points(
+        py,
+        num_threads,
+        1,
+        &RtmOptions::default(),
+        Verbosity::Progress,
+        &cancelled,
+        par_inputs(&profiles, &RtmOptions::default(), &cancelled)
+            .map(|inputs| Ok(inputs?.column_stats())),
+    )?;
+    let stats = results.into_iter().collect::<Result<Vec<_>, _>>()?;
+
+    let column = |field: fn(&rtm::ColumnStats) -> f32| {
+        stats
+            .iter()
+            .map(field)
+            .collect::<Array1<f32>>()
+            .into_pyarray(py)
+    };
+    let output = PyDict::new(py);
+    output.set_item(
+        "pressure_weighted_temperature",
+        column(|s| s.pressure_weighted_temperature),
+    )?;
+    output.set_item(
+        "vapor_weighted_temperature",
+        column(|s| s.vapor_weighted_temperature),
+    )?;
+    output.set_item("water_vapor", column(|s| s.water_vapor))?;
+    output.set_item("liquid_water", column(|s| s.liquid_water))?;
+    Ok(output)
+}
+
+/// Compute the zenith optical depth of each absorber for each point and
+/// channel.
+///
+/// The profile inputs are the same as for `compute_rtm`. The optical depths are
+/// integrated straight up from the surface to the top of the profile, so they
+/// don't depend on the incidence angle, and they sum to the total zenith
+/// optical depth.
+///
+/// The returned dict has the following arrays, each with shape (`num_points`,
+/// `num_freq`):
+///
+/// - `oxygen`: optical depth of oxygen in Np
+/// - `water_vapor`: optical depth of water vapor in Np
+/// - `cloud`: optical depth of cloud liquid water in Np
+///
+/// Optionally, `options` is an `RtmOptions`, of which only the absorption
+/// scale factors are used, so the optical depths are consistent with
+/// `compute_rtm` with the same options.
+#[pyfunction]
+#[pyo3(signature = (pressure, temperature, height, specific_humidity, liquid_content, surface_temperature, surface_height, surface_dewpoint, surface_pressure, frequency, num_threads, top_pressure=None, options=None))]
+#[allow(clippy::too_many_arguments)]
+fn compute_optical_depths<'py>(
+    py: Python<'py>,
+    pressure: PressureArray<'_, f32>,
+    temperature: PyReadonlyArray2<'_, f32>,
+    height: PyReadonlyArray2<'_, f32>,
+    specific_humidity: PyReadonlyArray2<'_, f32>,
+    liquid_content: Option<PyReadonlyArray2<'_, f32>>,
+    surface_temperature: PyReadonlyArray1<'_, f32>,
+    surface_height: PyReadonlyArray1<'_, f32>,
+    surface_dewpoint: PyReadonlyArray1<'_, f32>,
+    surface_pressure: Option<PyReadonlyArray1<'_, f32>>,
+    frequency: PyReadonlyArray1<'_, f32>,
+    num_threads: Option<usize>,
+    top_pressure: Option<f32>,
+    options: Option<PyRef<'_, PyRtmOptions>>,
+) -> PyResult<Bound<'py, PyDict>> {
+    let profiles = profiles(
+        &pressure,
+        &temperature,
+        &height,
+        &specific_humidity,
+        liquid_content.as_ref(),
+        &surface_temperature,
+        &surface_height,
+        &surface_dewpoint,
+        surface_pressure.as_ref(),
+        None,
+        None,
+        top_pressure,
+    )?;
+    let incidence_angle = vec![0.; frequency.len()];
+    let rtm_options = options.map(|o| o.rtm).unwrap_or_default();
+    let parameters = RtmParameters::new(frequency.as_slice()?, &incidence_angle)?
+        .with_absorption_scale(rtm_options.absorption_scale);
+
+    let num_points = profiles.len();
+    let num_freq = frequency.len();
+
+    info!("Processing optical depths for {num_points} profiles and {num_freq} channels");
+
+    let cancelled = AtomicBool::new(false);
+    let results = run_points(
+        py,
+        num_threads,
+        num_freq,
+        &rtm_options,
+        Verbosity::Progress,
+        &cancelled,
+        par_inputs(&profiles, &rtm_options, &cancelled)
+            .map(|inputs| Ok(inputs?.zenith_optical_depths(&parameters))),
+    )?;
+    let depths = results.into_iter().collect::<Result<Vec<_>, _>>()?;
+
+    let absorber = |field: fn(&rtm::OpticalDepths) -> &[f32]| {
+        Array2::from_shape_fn((num_points, num_freq), |(i, j)| field(&depths[i])[j])
+            .into_pyarray(py)
+    };
+    let output = PyDict::new(py);
+    output.set_item("oxygen", absorber(|d| &d.oxygen))?;
+    output.set_item("water_vapor", absorber(|d| &d.water_vapor))?;
+    output.set_item("cloud", absorber(|d| &d.cloud))?;
+    Ok(output)
+}
+
+/// Compute the derivatives of the RTM outputs with respect to the surface
+/// temperature.
+///
+/// The inputs are the same as for `compute_rtm`. The surface temperature (the
+/// 2 m air temperature) is the lowest level of the profile, so it affects the
+/// emission and absorption of the lowest layer. The derivatives through the
+/// radiative transfer are analytic, while the derivative of the absorption at
+/// the surface is from a central difference of the absorption models.
+///
+/// The returned dict has the following arrays, each with shape (`num_points`,
+/// `num_freq`):
+///
+/// - `tran`: derivative of the atmospheric transmissivity, in 1/K
+/// - `tb_up`: derivative of the atmospheric upwelling brightness temperature
+/// - `tb_down`: derivative of the atmospheric downwelling brightness
+///   temperature
+#[pyfunction]
+#[pyo3(signature = (pressure, temperature, height, specific_humidity, liquid_content, surface_temperature, surface_height, surface_dewpoint, surface_pressure, incidence_angle, frequency, num_threads, top_pressure=None))]
+#[allow(clippy::too_many_arguments)]
+fn compute_rtm_sst_jacobian<'py>(
+    py: Python<'py>,
+    pressure: PressureArray<'_, f32>,
+    temperature: PyReadonlyArray2<'_, f32>,
+    height: PyReadonlyArray2<'_, f32>,
+    specific_humidity: PyReadonlyArray2<'_, f32>,
+    liquid_content: Option<PyReadonlyArray2<'_, f32>>,
+    surface_temperature: PyReadonlyArray1<'_, f32>,
+    surface_height: PyReadonlyArray1<'_, f32>,
+    surface_dewpoint: PyReadonlyArray1<'_, f32>,
+    surface_pressure: Option<PyReadonlyArray1<'_, f32>>,
+    incidence_angle: PyReadonlyArray1<'_, f32>,
+    frequency: PyReadonlyArray1<'_, f32>,
+    num_threads: Option<usize>,
+    top_pressure: Option<f32>,
+) -> PyResult<Bound<'py, PyDict>> {
+    let profiles = profiles(
+        &pressure,
+        &temperature,
+        &height,
+        &specific_humidity,
+        liquid_content.as_ref(),
+        &surface_temperature,
+        &surface_height,
+        &surface_dewpoint,
+        surface_pressure.as_ref(),
+        None,
+        None,
+        top_pressure,
+    )?;
+    let parameters = parameters(&frequency, &incidence_angle)?;
+
+    let num_points = profiles.len();
+    let num_freq = frequency.len();
+
+    info!(
+        "Processing surface temperature Jacobian for {num_points} profiles and {num_freq} channels"
+    );
+
+    let cancelled = AtomicBool::new(false);
+    let results = run_points(
+        py,
+        num_threads,
+        num_freq,
+        &RtmOptions::default(),
+        Verbosity::Progress,
+        &cancelled,
+        par_inputs(&profiles, &RtmOptions::default(), &cancelled)
+            .map(|inputs| Ok(inputs?.surface_temperature_jacobian(&parameters))),
+    )?;
+    let jacobians = results.into_iter().collect::<Result<Vec<_>, _>>()?;
+
+    let array = |field: fn(&rtm::SurfaceTemperatureJacobian) -> &[f32]| {
+        let mut array = Array2::zeros([num_points, num_freq]);
+        for (index, jacobian) in jacobians.iter().enumerate() {
+            let rhs = ArrayView1::from(field(jacobian));
+            array.index_axis_mut(Axis(0), index).assign(&rhs);
+        }
+        array.into_pyarray(py)
+    };
+    let output = PyDict::new(py);
+    output.set_item("tran", array(|j| &j.tran))?;
+    output.set_item("tb_up", array(|j| &j.tb_up))?;
+    output.set_item("tb_down", array(|j| &j.tb_down))?;
+    Ok(output)
+}
+
+/// Compute the derivative of the atmospheric upwelling brightness temperature
+/// with respect to a uniform shift of the temperature profile.
+///
+/// The inputs are the same as for `compute_rtm_sst_jacobian`. The temperature
+/// of every level, including the surface, is shifted by `delta` K each way,
+/// and the RTM is rerun for a central difference, so this is a cheap bulk
+/// temperature response rather than a per-level Jacobian. The vapor pressure
+/// is held fixed, and the liquid water density varies inversely with the
+/// temperature.
+///
+/// The returned array has shape (`num_points`, `num_freq`), in K/K. A
+/// `ValueError` is raised if `delta` is not positive and finite.
+#[pyfunction]
+#[pyo3(signature = (pressure, temperature, height, specific_humidity, liquid_content, surface_temperature, surface_height, surface_dewpoint, surface_pressure, incidence_angle, frequency, num_threads, top_pressure=None, delta=0.1))]
+#[allow(clippy::too_many_arguments)]
+fn compute_rtm_uniform_temp_sensitivity<'py>(
+    py: Python<'py>,
+    pressure: PressureArray<'_, f32>,
+    temperature: PyReadonlyArray2<'_, f32>,
+    height: PyReadonlyArray2<'_, f32>,
+    specific_humidity: PyReadonlyArray2<'_, f32>,
+    liquid_content: Option<PyReadonlyArray2<'_, f32>>,
+    surface_temperature: PyReadonlyArray1<'_, f32>,
+    surface_height: PyReadonlyArray1<'_, f32>,
+    surface_dewpoint: PyReadonlyArray1<'_, f32>,
+    surface_pressure: Option<PyReadonlyArray1<'_, f32>>,
+    incidence_angle: PyReadonlyArray1<'_, f32>,
+    frequency: PyReadonlyArray1<'_, f32>,
+    num_threads: Option<usize>,
+    top_pressure: Option<f32>,
+    delta: f32,
+) -> PyResult<Bound<'py, PyArray2<f32>>> {
+    if !(delta.is_finite() && delta > 0.) {
+        return Err(PyValueError::new_err(format!(
+            "delta must be positive and finite, not {delta}"
+        )));
+    }
+    let profiles = profiles(
+        &pressure,
+        &temperature,
+        &height,
+        &specific_humidity,
+        liquid_content.as_ref(),
+        &surface_temperature,
+        &surface_height,
+        &surface_dewpoint,
+        surface_pressure.as_ref(),
+        None,
+        None,
+        top_pressure,
+    )?;
+    let parameters = parameters(&frequency, &incidence_angle)?;
+
+    let num_points = profiles.len();
+    let num_freq = frequency.len();
+
+    info!(
+        "Processing uniform temperature sensitivity for {num_points} profiles and {num_freq} channels"
+    );
+
+    let cancelled = AtomicBool::new(false);
+    let results = run_points(
+        py,
+        num_threads,
+        num_freq,
+        &RtmOptions::default(),
+        Verbosity::Progress,
+        &cancelled,
+        par_inputs(&profiles, &RtmOptions::default(), &cancelled)
+            .map(|inputs| Ok(inputs?.uniform_temperature_sensitivity(&parameters, delta))),
+    )?;
+    let sensitivities = results.into_iter().collect::<Result<Vec<_>, _>>()?;
+
+    let mut array = Array2::zeros([num_points, num_freq]);
+    for (mut row, sensitivity) in array.outer_iter_mut().zip(&sensitivities) {
+        row.assign(&ArrayView1::from(sensitivity.as_slice()));
+    }
+    Ok(array.into_pyarray(py))
+}
+
+/// Compute the RTM outputs averaged over the points in each sensor footprint.
+///
+/// The profile and channel inputs are the same as for `compute_rtm`, with one
+/// point for each member of all of the footprints, for example the profiles of
+/// a finer model grid. The RTM is run for each point, and the outputs of the
+/// members of each footprint are averaged, weighted by the antenna pattern.
+///
+/// `member_index` is an integer array with shape (`num_footprints`,
+/// `max_members`), with the index of each member point, and `member_weight`
+/// has the same shape with its weight. The weights are normalized for each
+/// footprint, so they only need to be relative. Footprints with fewer members
+/// are padded with a negative index or a weight of zero or NaN. A footprint
+/// with no valid members has outputs of NaN. A `ValueError` is raised if an
+/// index is out of range or a weight is negative or infinite.
+///
+/// The returned dict has the following arrays, each with shape
+/// (`num_footprints`, `num_freq`) unless noted:
+///
+/// - `tran`: mean atmospheric transmissivity
+/// - `tb_up`: mean atmospheric upwelling brightness temperature in K
+/// - `tb_down`: mean atmospheric downwelling brightness temperature in K
+/// - `quality_flags`: combined quality flags of the members, with shape
+///   (`num_footprints`, )
+#[pyfunction]
+#[pyo3(signature = (pressure, temperature, height, specific_humidity, liquid_content, surface_temperature, surface_height, surface_dewpoint, surface_pressure, incidence_angle, frequency, member_index, member_weight, num_threads, top_pressure=None))]
+#[allow(clippy::too_many_arguments)]
+fn compute_rtm_footprints<'py>(
+    py: Python<'py>,
+    pressure: PressureArray<'_, f32>,
+    temperature: PyReadonlyArray2<'_, f32>,
+    height: PyReadonlyArray2<'_, f32>,
+    specific_humidity: PyReadonlyArray2<'_, f32>,
+    liquid_content: Option<PyReadonlyArray2<'_, f32>>,
+    surface_temperature: PyReadonlyArray1<'_, f32>,
+    surface_height: PyReadonlyArray1<'_, f32>,
+    surface_dewpoint: PyReadonlyArray1<'_, f32>,
+    surface_pressure: Option<PyReadonlyArray1<'_, f32>>,
+    incidence_angle: PyReadonlyArray1<'_, f32>,
+    frequency: PyReadonlyArray1<'_, f32>,
+    member_index: PyReadonlyArray2<'_, i64>,
+    member_weight: PyReadonlyArray2<'_, f32>,
+    num_threads: Option<usize>,
+    top_pressure: Option<f32>,
+) -> PyResult<Bound<'py, PyDict>> {
+    let member_index = member_index.as_array();
+    let member_weight = member_weight.as_array();
+    if member_index.shape() != member_weight.shape() {
+        return Err(RtmError::InconsistentInputs.into());
+    }
+
+    let profiles = profiles(
+        &pressure,
+        &temperature,
+        &height,
+        &specific_humidity,
+        liquid_content.as_ref(),
+        &surface_temperature,
+        &surface_height,
+        &surface_dewpoint,
+        surface_pressure.as_ref(),
+        None,
+        None,
+        top_pressure,
+    )?;
+    let parameters = parameters(&frequency, &incidence_angle)?;
+
+    let num_points = profiles.len();
+    let num_freq = frequency.len();
+    let num_footprints = member_index.nrows();
+
+    info!(
+        "Processing {num_footprints} footprints from {num_points} profiles and {num_freq} channels"
+    );
+
+    let cancelled = AtomicBool::new(false);
+    let results = run_points(
+        py,
+        num_threads,
+        num_freq,
+        &RtmOptions::default(),
+        Verbosity::Progress,
+        &cancelled,
+        par_inputs(&profiles, &RtmOptions::default(), &cancelled)
+            .map(|inputs| Ok(inputs?.run(&parameters))),
+    )?;
+    let outputs = results.into_iter().collect::<Result<Vec<_>, _>>()?;
+
+    let mut tran = Array2::from_elem([num_footprints, num_freq], f32::NAN);
+    let mut tb_up = tran.clone();
+    let mut tb_down = tran.clone();
+    let mut quality_flags = Array1::<u32>::zeros(num_footprints);
+    for (footprint, (indices, weights)) in member_index
+        .outer_iter()
+        .zip(member_weight.outer_iter())
+        .enumerate()
+    {
+        let members = indices
+            .iter()
+            .zip(&weights)
+            .filter(|(&index, &weight)| index >= 0 && !weight.is_nan())
+            .map(|(&index, &weight)| (index as usize, weight))
+            .collect::<Vec<_>>();
+        if let Some(mean) = rtm::footprint_mean(&outputs, &members)? {
+            tran.index_axis_mut(Axis(0), footprint)
+                .assign(&ArrayView1::from(&mean.tran[..]));
+            tb_up
+                .index_axis_mut(Axis(0), footprint)
+                .assign(&ArrayView1::from(&mean.tb_up[..]));
+            tb_down
+                .index_axis_mut(Axis(0), footprint)
+                .assign(&ArrayView1::from(&mean.tb_down[..]));
+            quality_flags[footprint] = mean.quality.bits();
+        }
+    }
+
+    let output = PyDict::new(py);
+    output.set_item("tran", tran.into_pyarray(py))?;
+    output.set_item("tb_up", tb_up.into_pyarray(py))?;
+    output.set_item("tb_down", tb_down.into_pyarray(py))?;
+    output.set_item("quality_flags", quality_flags.into_pyarray(py))?;
+    Ok(output)
+}
+
+/// Compute the brightness temperature at the top of the atmosphere.
+///
+/// The profile and channel inputs are the same as for `compute_rtm`, and
+/// `polarization` is a list with the polarization of each channel at the
+/// surface, either `"v"` or `"h"`. The atmosphere is computed as in
+/// `compute_rtm` and combined with the emission of the surface and its
+/// reflection of the downwelling and the cosmic background. The brightness
+/// temperatures are in the Rayleigh-Jeans convention.
+///
+/// The surface emits at `skin_temperature`, the radiometric skin temperature
+/// in K with shape (`num_points`, ), if it's given, and otherwise at
+/// `surface_temperature`. As in `compute_rtm`, the atmosphere always uses
+/// `surface_temperature`, the 2 meter air temperature, and not the skin
+/// temperature. The ocean emissivity is also computed at the skin temperature.
+///
+/// The surface is ocean unless `surface_type`, an integer array with shape
+/// (`num_points`, ), is given, with the type of each point: 0 for ocean, 1 for
+/// land, or 2 for sea ice. The ocean has a salinity in ppt of `salinity`, with
+/// shape (`num_points`, ), or 35 ppt if it's not given, and its emissivity is
+/// as in `ocean_emissivity`: for a flat sea, or for a
+/// wind-roughened sea if `wind_speed`, the 10 m wind speed in m/s with shape
+/// (`num_points`, ), is given, with the `ocean_model` of the wind-roughened
+/// sea. The emissivities of land and sea ice are given
+/// by `land_emissivity` and `ice_emissivity`, each with shape (`num_points`,
+/// `num_freq`), for example from an atlas, and are only needed if there are
+/// points of that type. The ocean points may be partly covered by sea ice, with
+/// the fraction given by `sea_ice_concentration` with shape (`num_points`, ),
+/// in which case the emissivity is blended linearly between the open water and
+/// `ice_emissivity`.
+///
+/// The surface inputs are all checked before any point is computed: the
+/// emissivities and the sea ice concentration must be from 0 to 1 and the
+/// salinity can't be negative, and a `ValueError` with the index of the first
+/// bad point is raised otherwise.
+///
+/// The surface reflects the downwelling specularly by default. With
+/// `reflection="lambertian"`, it instead reflects the average of the
+/// downwelling over the hemisphere, which is more realistic for rough land.
+/// The average is by Gaussian quadrature over `quadrature_order` zenith
+/// angles, each of which adds another integration of the radiative transfer.
+/// The reflection model applies to every point, including the ocean points.
+///
+/// The feedhorn of a cross-track sounder, such as MSU, measures a mix of V-
+/// and H-pol at the surface that rotates with the scan position. If
+/// `polarization_angle`, the rotation in degrees of each point's polarization
+/// relative to the plane of incidence with shape (`num_points`, ), is given,
+/// then `tb_toa` is `cos²φ tb_toa_v + sin²φ tb_toa_h` for the `"v"` channels
+/// and `cos²φ tb_toa_h + sin²φ tb_toa_v` for the `"h"` channels. For a
+/// cross-track scanner, this angle is the scan angle from nadir.
+///
+/// The returned dict has the following arrays, each with shape (`num_points`,
+/// `num_freq`):
+///
+/// - `tb_toa`: brightness temperature at the top of the atmosphere, in K
+/// - `tb_toa_v`, `tb_toa_h`: brightness temperature at the top of the
+///   atmosphere for V- and H-pol at the surface, in K
+/// - `emissivity`: surface emissivity of the channel's polarization, mixed as
+///   for `tb_toa`
+/// - `tran`, `tb_up`, `tb_down`: the atmospheric outputs, as from
+///   `compute_rtm`
+///
+/// If `jacobians` is true, the dict also has the derivatives of `tb_toa`, from
+/// the same quantities used to compute it:
+///
+/// - `dtb_demis`: with respect to `emissivity`, `tran (T_s - sky)`, where
+///   `T_s` is the surface temperature and `sky` is the reflected sky
+///   brightness temperature
+/// - `dtb_dtskin`: with respect to the surface temperature, `tran
+///   emissivity`, with the emissivity held fixed (for the ocean, this leaves
+///   out the change of the emissivity with the SST)
+///
+/// Optionally, `noise_nedt` is the NEΔT in K of each channel, with shape
+/// (`num_freq`, ), for simulated observations. Independent Gaussian noise with
+/// that standard deviation is added to `tb_toa` for each point and channel. The
+/// noise is from a random number generator seeded with `noise_seed`, so the same
+/// seed gives the same noise, or a random seed if it's `None`. If
+/// `return_unnoised` is true, the dict also has `tb_toa` before the noise was
+/// added, as `tb_toa_unnoised`.
+#[pyfunction]
+#[pyo3(signature = (pressure, temperature, height, specific_humidity, liquid_content, surface_temperature, surface_height, surface_dewpoint, surface_pressure, incidence_angle, frequency, polarization, num_threads, top_pressure=None, wind_speed=None, surface_type=None, land_emissivity=None, ice_emissivity=None, sea_ice_concentration=None, polarization_angle=None, reflection="specular", quadrature_order=8, skin_temperature=None, salinity=None, jacobians=false, noise_nedt=None, noise_seed=None, return_unnoised=false, ocean_model="rough"))]
+#[allow(clippy::too_many_arguments)]
+fn compute_tb_toa<'py>(
+    py: Python<'py>,
+    pressure: PressureArray<'_, f32>,
+    temperature: PyReadonlyArray2<'_, f32>,
+    height: PyReadonlyArray2<'_, f32>,
+    specific_humidity: PyReadonlyArray2<'_, f32>,
+    liquid_content: Option<PyReadonlyArray2<'_, f32>>,
+    surface_temperature: PyReadonlyArray1<'_, f32>,
+    surface_height: PyReadonlyArray1<'_, f32>,
+    surface_dewpoint: PyReadonlyArray1<'_, f32>,
+    surface_pressure: Option<PyReadonlyArray1<'_, f32>>,
+    incidence_angle: PyReadonlyArray1<'_, f32>,
+    frequency: PyReadonlyArray1<'_, f32>,
+    polarization: Vec<String>,
+    num_threads: Option<usize>,
+    top_pressure: Option<f32>,
+    wind_speed: Option<PyReadonlyArray1<'_, f32>>,
+    surface_type: Option<PyReadonlyArray1<'_, i64>>,
+    land_emissivity: Option<PyReadonlyArray2<'_, f32>>,
+    ice_emissivity: Option<PyReadonlyArray2<'_, f32>>,
+    sea_ice_concentration: Option<PyReadonlyArray1<'_, f32>>,
+    polarization_angle: Option<PyReadonlyArray1<'_, f32>>,
+    reflection: &str,
+    quadrature_order: usize,
+    skin_temperature: Option<PyReadonlyArray1<'_, f32>>,
+    salinity: Option<PyReadonlyArray1<'_, f32>>,
+    jacobians: bool,
+    noise_nedt: Option<PyReadonlyArray1<'_, f32>>,
+    noise_seed: Option<u64>,
+    return_unnoised: bool,
+    ocean_model: &str,
+) -> PyResult<Bound<'py, PyDict>> {
+    let profiles = profiles(
+        &pressure,
+        &temperature,
+        &height,
+        &specific_humidity,
+        liquid_content.as_ref(),
+        &surface_temperature,
+        &surface_height,
+        &surface_dewpoint,
+        surface_pressure.as_ref(),
+        skin_temperature.as_ref(),
+        None,
+        top_pressure,
+    )?;
+    let parameters = parameters(&frequency, &incidence_angle)?
+        .with_reflection(parse_reflection(reflection, quadrature_order)?)
+        .with_ocean_model(parse_ocean_model(ocean_model)?);
+    let polarization = polarization
+        .iter()
+        .map(|name| parse_polarization(name))
+        .collect::<PyResult<Vec<_>>>()?;
+
+    let num_points = profiles.len();
+    let num_freq = parameters.len();
+    if polarization.len() != num_freq {
+        return Err(RtmError::InconsistentInputs.into());
+    }
+    let mut noise = noise(noise_nedt.as_ref(), noise_seed, return_unnoised, num_freq)?;
+
+    let wind_speed = point_values(wind_speed.as_ref(), num_points)?;
+    let sea_ice_concentration = point_values(sea_ice_concentration.as_ref(), num_points)?;
+    let polarization_angle = point_values(polarization_angle.as_ref(), num_points)?;
+    let salinity = point_values(salinity.as_ref(), num_points)?;
+    let land_emissivity = channel_values(land_emissivity.as_ref(), num_points, num_freq)?;
+    let ice_emissivity = channel_values(ice_emissivity.as_ref(), num_points, num_freq)?;
+    let surface_type = point_values(surface_type.as_ref(), num_points)?;
+    let surfaces = toa_surfaces(
+        num_points,
+        num_freq,
+        surface_type,
+        salinity,
+        wind_speed,
+        sea_ice_concentration,
+        land_emissivity.as_deref(),
+        ice_emissivity.as_deref(),
+    )?;
+
+    info!(
+        "Processing TOA brightness temperatures for {num_points} profiles and {num_freq} channels"
+    );
+
+    let cancelled = AtomicBool::new(false);
+    let results = run_points(
+        py,
+        num_threads,
+        num_freq,
+        &RtmOptions::default(),
+        Verbosity::Progress,
+        &cancelled,
+        par_inputs(&profiles, &RtmOptions::default(), &cancelled)
+            .zip(&surfaces)
+            .enumerate()
+            .map(|(point, (inputs, surface))| {
+                let angle = polarization_angle.map_or(0., |angle| angle[point]);
+                inputs?.run_toa_rotated(&parameters, &polarization, angle, surface)
+            }),
+    )?;
+    let results = results.into_iter().collect::<Result<Vec<_>, _>>()?;
+
+    let names = [
+        "tb_toa",
+        "tb_toa_v",
+        "tb_toa_h",
+        "emissivity",
+        "tran",
+        "tb_up",
+        "tb_down",
+        "dtb_demis",
+        "dtb_dtskin",
+    ];
+    // The Jacobians are last, so they're left out by truncating
+    let num_outputs = if jacobians {
+        names.len()
+    } else {
+        names.len() - 2
+    };
+    let mut arrays: Vec<_> = (0..num_outputs)
+        .map(|_| Array2::<f32>::zeros([num_points, num_freq]))
+        .collect();
+    for (index, outputs) in results.iter().enumerate() {
+        let rows: [&[f32]; 9] = [
+            &outputs.tb_toa,
+            &outputs.tb_toa_v,
+            &outputs.tb_toa_h,
+            &outputs.emissivity,
+            &outputs.atmosphere.tran,
+            &outputs.atmosphere.tb_up,
+            &outputs.atmosphere.tb_down,
+            &outputs.dtb_demis,
+            &outputs.dtb_dtskin,
+        ];
+        for (array, row) in arrays.iter_mut().zip(rows) {
+            array
+                .index_axis_mut(Axis(0), index)
+                .assign(&ArrayView1::from(row));
+        }
+    }
+    let output = PyDict::new(py);
+    if let Some(noise) = noise.as_mut() {
+        if return_unnoised {
+            output.set_item("tb_toa_unnoised", arrays[0].to_pyarray(py))?;
+        }
+        add_noise(noise, &mut arrays[0])?;
+    }
+    for (name, array) in names.into_iter().zip(arrays) {
+        output.set_item(name, array.into_pyarray(py))?;
+    }
+    Ok(output)
+}
+
+/// Compute the brightness temperature at the top of the atmosphere for both V-
+/// and H-pol at the surface.
+///
+/// The inputs are the same as for `compute_tb_toa`, except that there's no
+/// `polarization` or `polarization_angle`, since both polarizations are
+/// returned. The atmosphere is computed once for each point and combined with
+/// the V- and H-pol emissivity of the surface (from the Fresnel equations for
+/// the ocean) and its reflection of the downwelling.
+///
+/// Returns the brightness temperatures in K with shape (`num_points`,
+/// `num_freq`, 2), where the last axis is the polarization at the surface: V
+/// at index 0 and H at index 1. These are the same as `tb_toa_v` and
+/// `tb_toa_h` from `compute_tb_toa`.
+#[pyfunction]
+#[pyo3(signature = (pressure, temperature, height, specific_humidity, liquid_content, surface_temperature, surface_height, surface_dewpoint, surface_pressure, incidence_angle, frequency, num_threads, top_pressure=None, wind_speed=None, surface_type=None, land_emissivity=None, ice_emissivity=None, sea_ice_concentration=None, reflection="specular", quadrature_order=8, skin_temperature=None, salinity=None, ocean_model="rough"))]
+#[allow(clippy::too_many_arguments)]
+fn compute_tb_toa_polarized<'py>(
+    py: Python<'py>,
+    pressure: PressureArray<'_, f32>,
+    temperature: PyReadonlyArray2<'_, f32>,
+    height: PyReadonlyArray2<'_, f32>,
+    specific_humidity: PyReadonlyArray2<'_, f32>,
+    liquid_content: Option<PyReadonlyArray2<'_, f32>>,
+    surface_temperature: PyReadonlyArray1<'_, f32>,
+    surface_height: PyReadonlyArray1<'_, f32>,
+    surface_dewpoint: PyReadonlyArray1<'_, f32>,
+    surface_pressure: Option<PyReadonlyArray1<'_, f32>>,
+    incidence_angle: PyReadonlyArray1<'_, f32>,
+    frequency: PyReadonlyArray1<'_, f32>,
+    num_threads: Option<usize>,
+    top_pressure: Option<f32>,
+    wind_speed: Option<PyReadonlyArray1<'_, f32>>,
+    surface_type: Option<PyReadonlyArray1<'_, i64>>,
+    land_emissivity: Option<PyReadonlyArray2<'_, f32>>,
+    ice_emissivity: Option<PyReadonlyArray2<'_, f32>>,
+    sea_ice_concentration: Option<PyReadonlyArray1<'_, f32>>,
+    reflection: &str,
+    quadrature_order: usize,
+    skin_temperature: Option<PyReadonlyArray1<'_, f32>>,
+    salinity: Option<PyReadonlyArray1<'_, f32>>,
+    ocean_model: &str,
+) -> PyResult<Bound<'py, PyArray3<f32>>> {
+    let profiles = profiles(
+        &pressure,
+        &temperature,
+        &height,
+        &specific_humidity,
+        liquid_content.as_ref(),
+        &surface_temperature,
+        &surface_height,
+        &surface_dewpoint,
+        surface_pressure.as_ref(),
+        skin_temperature.as_ref(),
+        None,
+        top_pressure,
+    )?;
+    let parameters = parameters(&frequency, &incidence_angle)?
+        .with_reflection(parse_reflection(reflection, quadrature_order)?)
+        .with_ocean_model(parse_ocean_model(ocean_model)?);
+
+    let num_points = profiles.len();
+    let num_freq = parameters.len();
+    let land_emissivity = channel_values(land_emissivity.as_ref(), num_points, num_freq)?;
+    let ice_emissivity = channel_values(ice_emissivity.as_ref(), num_points, num_freq)?;
+    let surfaces = toa_surfaces(
+        num_points,
+        num_freq,
+        point_values(surface_type.as_ref(), num_points)?,
+        point_values(salinity.as_ref(), num_points)?,
+        point_values(wind_speed.as_ref(), num_points)?,
+        point_values(sea_ice_concentration.as_ref(), num_points)?,
+        land_emissivity.as_deref(),
+        ice_emissivity.as_deref(),
+    )?;
+
+    info!(
+        "Processing polarized TOA brightness temperatures for {num_points} profiles and \
+         {num_freq} channels"
+    );
+
+    // Both polarizations come from the same run of the atmosphere, so the
+    // polarization of the channels only matters for `tb_toa`, which isn't used
+    let polarization = vec![Polarization::Vertical; num_freq];
+    let cancelled = AtomicBool::new(false);
+    let results = run_points(
+        py,
+        num_threads,
+        num_freq,
+        &RtmOptions::default(),
+        Verbosity::Progress,
+        &cancelled,
+        par_inputs(&profiles, &RtmOptions::default(), &cancelled)
+            .zip(&surfaces)
+            .map(|(inputs, surface)| inputs?.run_toa(&parameters, &polarization, surface)),
+    )?;
+    let results = results.into_iter().collect::<Result<Vec<_>, _>>()?;
+
+    let mut tb_toa = Array3::<f32>::zeros([num_points, num_freq, 2]);
+    for (index, outputs) in results.iter().enumerate() {
+        let mut point = tb_toa.index_axis_mut(Axis(0), index);
+        point
+            .index_axis_mut(Axis(1), 0)
+            .assign(&ArrayView1::from(&outputs.tb_toa_v[..]));
+        point
+            .index_axis_mut(Axis(1), 1)
+            .assign(&ArrayView1::from(&outputs.tb_toa_h[..]));
+    }
+    Ok(tb_toa.into_pyarray(py))
+}
+
+/// Remove the atmosphere from observed brightness temperatures at the top of
+/// the atmosphere, for an atmospheric correction.
+///
+/// `tb_observed` is the observed brightness temperature in K with shape
+/// (`num_points`, `num_freq`), and `atmosphere` is the output of
+/// `compute_rtm` for the same points and channels. `emissivity` is the surface
+/// emissivity, and is broadcast to (`num_points`, `num_freq`), so it can also
+/// be given for each channel, with shape (`num_freq`, ).
+/// All of the arrays must have the same dtype as the outputs in `atmosphere`.
+///
+/// Returns the brightness temperature in K emitted by the surface,
+/// `emissivity * surface_temperature`, with shape (`num_points`, `num_freq`).
+/// This is the inverse of the combination in `compute_tb_toa`:
+///
+/// `(tb_observed - tb_up) / tran - (1 - emissivity) * (tb_down + tran * 2.73)`
+///
+/// which removes the atmospheric upwelling and the surface's reflection of the
+/// downwelling and the cosmic background, and corrects for the attenuation of
+/// the atmosphere. It's undefined where the atmosphere is opaque (`tran` is
+/// 0). The brightness temperatures must be in the Rayleigh-Jeans convention
+/// (the default `tb_convention` of `compute_rtm`), where they add linearly.
+#[pyfunction]
+fn remove_atmosphere<'py>(
+    py: Python<'py>,
+    tb_observed: &Bound<'py, PyAny>,
+    atmosphere: PyRef<'py, AtmoParameters>,
+    emissivity: &Bound<'py, PyAny>,
+) -> PyResult<Bound<'py, PyAny>> {
+    if atmosphere.tb_convention != TbConvention::RayleighJeans {
+        return Err(PyValueError::new_err(
+            "the atmosphere can only be removed from Rayleigh-Jeans brightness temperatures",
+        ));
+    }
+    with_outputs!(&atmosphere.arrays, |o| remove_atmosphere_typed(
+        py,
+        o,
+        tb_observed,
+        emissivity
+    ))
+}
+
+/// Implementation of [`remove_atmosphere`] for the precision `T` of the
+/// atmosphere `outputs`.
+fn remove_atmosphere_typed<'py, T: PyFloat>(
+    py: Python<'py>,
+    outputs: &Outputs<T>,
+    tb_observed: &Bound<'py, PyAny>,
+    emissivity: &Bound<'py, PyAny>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let tb_observed: PyReadonlyArray2<'_, T> = tb_observed.extract()?;
+    let emissivity: PyReadonlyArrayDyn<'_, T> = emissivity.extract()?;
+    let tb_observed = tb_observed.as_array();
+    let emissivity = emissivity.as_array();
+    let emissivity = emissivity
+        .broadcast(outputs.tran.raw_dim())
+        .ok_or(RtmError::InconsistentInputs)?;
+    if tb_observed.dim() != outputs.tran.dim() {
+        return Err(RtmError::InconsistentInputs.into());
+    }
+
+    let tb_surface = Zip::from(&tb_observed)
+        .and(&outputs.tran)
+        .and(&outputs.tb_up)
+        .and(&outputs.tb_down)
+        .and(&emissivity)
+        .map_collect(|&tb, &tran, &tb_up, &tb_down, &emissivity| {
+            rtm::remove_atmosphere(tb, tran, tb_up, tb_down, emissivity)
+        });
+    Ok(tb_surface.into_pyarray(py).into_any())
+}
+
+/// The instrument noise for `noise_nedt`, if it's given, checking that it has
+/// one value per channel. Without `noise_seed`, the seed is random.
+fn noise<T: PyFloat>(
+    noise_nedt: Option<&PyReadonlyArray1<'_, T>>,
+    noise_seed: Option<u64>,
+    return_unnoised: bool,
+    num_freq: usize,
+) -> PyResult<Option<rtm::NedtNoise<T>>> {
+    let Some(nedt) = noise_nedt else {
+        if return_unnoised {
+            return Err(PyValueError::new_err("return_unnoised requires noise_nedt"));
+        }
+        return Ok(None);
+    };
+    if nedt.len() != num_freq {
+        return Err(RtmError::InconsistentInputs.into());
+    }
+    let seed = noise_seed.unwrap_or_else(rand::random);
+    Ok(Some(rtm::NedtNoise::new(nedt.as_slice()?, seed)?))
+}
+
+/// Add the instrument noise to each row of `tb`, in order.
+fn add_noise<T: Float>(noise: &mut rtm::NedtNoise<T>, tb: &mut Array2<T>) -> Result<(), RtmError> {
+    for mut row in tb.rows_mut() {
+        noise.add_to(row.as_slice_mut().ok_or(RtmError::NotContiguous)?)?;
+    }
+    Ok(())
+}
+
+/// The values of a (`num_points`, ) array, if it's given.
+fn point_values<'a, T: Element>(
+    array: Option<&'a PyReadonlyArray1<'_, T>>,
+    num_points: usize,
+) -> PyResult<Option<&'a [T]>> {
+    let values = array.map(|array| array.as_slice()).transpose()?;
+    if values.is_some_and(|values| values.len() != num_points) {
+        return Err(RtmError::InconsistentInputs.into());
+    }
+    Ok(values)
+}
+
+/// The values of a (`num_points`, `num_freq`) array in row-major order, if
+/// it's given.
+fn channel_values(
+    array: Option<&PyReadonlyArray2<'_, f32>>,
+    num_points: usize,
+    num_freq: usize,
+) -> PyResult<Option<Vec<f32>>> {
+    let Some(array) = array else {
+        return Ok(None);
+    };
+    let array = array.as_array();
+    if array.dim() != (num_points, num_freq) {
+        return Err(RtmError::InconsistentInputs.into());
+    }
+    Ok(Some(array.iter().copied().collect()))
+}
+
+/// The surface of each point for `compute_tb_toa` and
+/// `compute_tb_toa_polarized`, checked before any point is computed.
+///
+/// `land_emissivity` and `ice_emissivity` are (`num_points`, `num_freq`) in
+/// row-major order.
+#[allow(clippy::too_many_arguments)]
+fn toa_surfaces<'a>(
+    num_points: usize,
+    num_freq: usize,
+    surface_type: Option<&[i64]>,
+    salinity: Option<&[f32]>,
+    wind_speed: Option<&[f32]>,
+    sea_ice_concentration: Option<&[f32]>,
+    land_emissivity: Option<&'a [f32]>,
+    ice_emissivity: Option<&'a [f32]>,
+) -> Result<Vec<rtm::Surface<'a, f32>>, RtmError> {
+    (0..num_points)
+        .map(|point| {
+            let row = point * num_freq..(point + 1) * num_freq;
+            let ice_row = ice_emissivity.map(|values| &values[row.clone()]);
+            let land_row = land_emissivity.map(|values| &values[row]);
+            let surface =
+                match surface_type.map_or(SURFACE_OCEAN, |surface_type| surface_type[point]) {
+                    SURFACE_OCEAN => rtm::Surface::Ocean {
+                        salinity: salinity.map_or(35., |salinity| salinity[point]),
+                        wind_speed: wind_speed.map(|wind_speed| wind_speed[point]),
+                        ice_concentration: sea_ice_concentration
+                            .map_or(0., |concentration| concentration[point]),
+                        ice_emissivity: ice_row,
+                    },
+                    SURFACE_LAND => rtm::Surface::Land {
+                        emissivity: land_row.ok_or(RtmError::MissingInput("land_emissivity"))?,
+                    },
+                    SURFACE_SEA_ICE => rtm::Surface::SeaIce {
+                        emissivity: ice_row.ok_or(RtmError::MissingInput("ice_emissivity"))?,
+                    },
+                    code => {
+                        return Err(RtmError::InvalidSurface(format!(
+                            "surface type {code} at point {point} is not {SURFACE_OCEAN} (ocean), \
+                         {SURFACE_LAND} (land), or {SURFACE_SEA_ICE} (sea ice)"
+                        )))
+                    }
+                };
+            surface.check(num_freq).map_err(|e| match e {
+                RtmError::InvalidSurface(message) => {
+                    RtmError::InvalidSurface(format!("{message} at point {point}"))
+                }
+                e => e,
+            })?;
+            Ok(surface)
+        })
+        .collect()
+}
+
+/// `surface_type` code for ocean in `compute_tb_toa` and
+/// `compute_tb_toa_polarized`.
+const SURFACE_OCEAN: i64 = 0;
+/// `surface_type` code for land in `compute_tb_toa`.
+const SURFACE_LAND: i64 = 1;
+/// `surface_type` code for sea ice in `compute_tb_toa`.
+const SURFACE_SEA_ICE: i64 = 2;
+
+/// Estimate the memory and run time of `compute_rtm` for a batch of points
+/// with `num_levels` levels and `num_freq` channels, e.g., to choose chunk
+/// sizes and node types before scheduling a job.
+///
+/// The time per point is calibrated by running the US Standard Atmosphere 1976,
+/// resampled to `num_levels` levels, 100 times on one thread, with `num_freq`
+/// distinct frequencies. The wall time is extrapolated to `num_points` points
+/// on `num_threads` threads (or one per logical CPU if `None`), assuming
+/// perfect scaling. Use `estimate_cost_from_inputs` to calibrate with the
+/// actual profiles instead.
+///
+/// Returns a dict with the estimates, `output_bytes` (for `tran`, `tb_up`, and
+/// `tb_down` in float32), `peak_working_bytes` (the per-point outputs held
+/// before they're copied, and the working profiles of each thread), and
+/// `wall_seconds`, along with the values they're based on, `num_points`,
+/// `seconds_per_point`, `num_calibration_points`, `num_threads`, and a
+/// description of the `assumptions`.
+#[pyfunction]
+#[pyo3(signature = (num_points, num_levels, num_freq, num_threads=None))]
+fn estimate_cost<'py>(
+    py: Python<'py>,
+    num_points: usize,
+    num_levels: usize,
+    num_freq: usize,
+    num_threads: Option<usize>,
+) -> PyResult<Bound<'py, PyDict>> {
+    let num_threads = num_threads.and_then(NonZeroUsize::new);
+    let estimate = py.allow_threads(|| {
+        rtm::estimate_cost_for_shape(num_points, num_levels, num_freq, num_threads)
+    })?;
+    cost_dict(
+        py,
+        &estimate,
+        "calibrated with a clear-sky standard atmosphere; perfect scaling with threads",
+    )
+}
+
+/// Estimate the memory and run time of `compute_rtm` for the actual inputs.
+///
+/// The arguments are the same as for `compute_rtm`. This is the same as
+/// `estimate_cost`, but the time per point is calibrated by running up to 100
+/// evenly spaced points of the inputs on one thread, so it includes the
+/// effect of clouds and of the levels below the surface, and the returned dict
+/// is the same.
+#[pyfunction]
+#[pyo3(signature = (pressure, temperature, height, specific_humidity, liquid_content, surface_temperature, surface_height, surface_dewpoint, surface_pressure, incidence_angle, frequency, num_threads=None, top_pressure=None))]
+#[allow(clippy::too_many_arguments)]
+fn estimate_cost_from_inputs<'py>(
+    py: Python<'py>,
+    pressure: PressureArray<'_, f32>,
+    temperature: PyReadonlyArray2<'_, f32>,
+    height: PyReadonlyArray2<'_, f32>,
+    specific_humidity: PyReadonlyArray2<'_, f32>,
+    liquid_content: Option<PyReadonlyArray2<'_, f32>>,
+    surface_temperature: PyReadonlyArray1<'_, f32>,
+    surface_height: PyReadonlyArray1<'_, f32>,
+    surface_dewpoint: PyReadonlyArray1<'_, f32>,
+    surface_pressure: Option<PyReadonlyArray1<'_, f32>>,
+    incidence_angle: PyReadonlyArray1<'_, f32>,
+    frequency: PyReadonlyArray1<'_, f32>,
+    num_threads: Option<usize>,
+    top_pressure: Option<f32>,
+) -> PyResult<Bound<'py, PyDict>> {
+    let profiles = profiles(
+        &pressure,
+        &temperature,
+        &height,
+        &specific_humidity,
+        liquid_content.as_ref(),
+        &surface_temperature,
+        &surface_height,
+        &surface_dewpoint,
+        surface_pressure.as_ref(),
+        None,
+        None,
+        top_pressure,
+    )?;
+    let parameters = parameters(&frequency, &incidence_angle)?;
+    let num_threads = num_threads.and_then(NonZeroUsize::new);
+    let estimate = rtm::estimate_cost(&profiles, &parameters, num_threads)?;
+    cost_dict(
+        py,
+        &estimate,
+        "calibrated with evenly spaced points of the inputs; perfect scaling with threads",
+    )
+}
+
+/// Convert a cost estimate to a dict for Python.
+fn cost_dict<'py>(
+    py: Python<'py>,
+    estimate: &rtm::CostEstimate,
+    assumptions: &str,
+) -> PyResult<Bound<'py, PyDict>> {
+    let output = PyDict::new(py);
+    output.set_item("output_bytes", estimate.output_bytes)?;
+    output.set_item("peak_working_bytes", estimate.peak_working_bytes)?;
+    output.set_item("wall_seconds", estimate.wall_seconds)?;
+    output.set_item("num_points", estimate.num_points)?;
+    output.set_item("seconds_per_point", estimate.seconds_per_point)?;
+    output.set_item("num_calibration_points", estimate.num_calibration_points)?;
+    output.set_item("num_threads", estimate.num_threads)?;
+    output.set_item("assumptions", assumptions)?;
+    Ok(output)
+}
+
+/// Find the frequency in a band where the zenith optical depth of a single
+/// point is largest or smallest, for placing a channel.
+///
+/// The profile arguments are the same as for `compute_rtm`, for all of the
+/// points, and `point_index` selects the point. The band from `freq_min` to
+/// `freq_max` in GHz is swept in 200 steps, and the best frequency is then
+/// refined with a golden-section search, so the band should be narrow enough
+/// for a step to resolve the lines of interest. If `maximize` is true, the
+/// largest optical depth is found, and otherwise the smallest.
+///
+/// Returns a tuple of the frequency in GHz and the zenith optical depth at it.
+#[pyfunction]
+#[pyo3(signature = (pressure, temperature, height, specific_humidity, liquid_content, surface_temperature, surface_height, surface_dewpoint, surface_pressure, point_index, freq_min, freq_max, maximize=true, top_pressure=None))]
+#[allow(clippy::too_many_arguments)]
+fn find_opacity_extremum(
+    pressure: PressureArray<'_, f32>,
+    temperature: PyReadonlyArray2<'_, f32>,
+    height: PyReadonlyArray2<'_, f32>,
+    specific_humidity: PyReadonlyArray2<'_, f32>,
+    liquid_content: Option<PyReadonlyArray2<'_, f32>>,
+    surface_temperature: PyReadonlyArray1<'_, f32>,
+    surface_height: PyReadonlyArray1<'_, f32>,
+    surface_dewpoint: PyReadonlyArray1<'_, f32>,
+    surface_pressure: Option<PyReadonlyArray1<'_, f32>>,
+    point_index: usize,
+    freq_min: f32,
+    freq_max: f32,
+    maximize: bool,
+    top_pressure: Option<f32>,
+) -> PyResult<(f32, f32)> {
+    let profiles = profiles(
+        &pressure,
+        &temperature,
+        &height,
+        &specific_humidity,
+        liquid_content.as_ref(),
+        &surface_temperature,
+        &surface_height,
+        &surface_dewpoint,
+        surface_pressure.as_ref(),
+        None,
+        None,
+        top_pressure,
+    )?;
+    if point_index >= profiles.len() {
+        return Err(PyIndexError::new_err(format!(
+            "point_index {point_index} is out of range for {} points",
+            profiles.len()
+        )));
+    }
+    let inputs = profiles.profile(point_index)?.inputs()?;
+    let extremum = inputs.opacity_extremum(freq_min, freq_max, maximize)?;
+    Ok((extremum.frequency, extremum.optical_depth))
+}
+
+/// Prepare the RTM inputs for a single point and serialize them to JSON.
+///
+/// The arguments are the same as for `compute_rtm`, for all of the points, and
+/// `point_index` selects the point to prepare. The JSON has the inputs after
+/// the surface is inserted into the profile, exactly as the RTM sees them. With
+/// the `serde` feature of the Rust crate, it can be deserialized to an
+/// `RtmInputs` to reproduce the point, e.g., in a unit test.
+#[pyfunction]
+#[pyo3(signature = (pressure, temperature, height, specific_humidity, liquid_content, surface_temperature, surface_height, surface_dewpoint, surface_pressure, point_index, top_pressure=None, skin_temperature=None, observer_height=None))]
+#[allow(clippy::too_many_arguments)]
+fn prepare_point<'py>(
+    py: Python<'py>,
+    pressure: PressureArray<'_, f32>,
+    temperature: PyReadonlyArray2<'_, f32>,
+    height: PyReadonlyArray2<'_, f32>,
+    specific_humidity: PyReadonlyArray2<'_, f32>,
+    liquid_content: Option<PyReadonlyArray2<'_, f32>>,
+    surface_temperature: PyReadonlyArray1<'_, f32>,
+    surface_height: PyReadonlyArray1<'_, f32>,
+    surface_dewpoint: PyReadonlyArray1<'_, f32>,
+    surface_pressure: Option<PyReadonlyArray1<'_, f32>>,
+    point_index: usize,
+    top_pressure: Option<f32>,
+    skin_temperature: Option<PyReadonlyArray1<'_, f32>>,
+    observer_height: Option<PyReadonlyArray1<'_, f32>>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let profiles = profiles(
+        &pressure,
+        &temperature,
+        &height,
+        &specific_humidity,
+        liquid_content.as_ref(),
+        &surface_temperature,
+        &surface_height,
+        &surface_dewpoint,
+        surface_pressure.as_ref(),
+        skin_temperature.as_ref(),
+        observer_height.as_ref(),
+        top_pressure,
+    )?;
+    if point_index >= profiles.len() {
+        return Err(PyIndexError::new_err(format!(
+            "point_index {point_index} is out of range for {} points",
+            profiles.len()
+        )));
+    }
+    let inputs = profiles.profile(point_index)?.inputs()?;
+
+    // The same fields and layout as the serde serialization of `RtmInputs`
+    let fields = PyDict::new(py);
+    fields.set_item("num_levels", inputs.num_levels.get())?;
+    fields.set_item("surface_index", inputs.surface_index)?;
+    fields.set_item("pressure", &inputs.pressure)?;
+    fields.set_item("temperature", &inputs.temperature)?;
+    fields.set_item("vapor_pressure", &inputs.vapor_pressure)?;
+    fields.set_item("rho_l", &inputs.rho_l)?;
+    fields.set_item("height", &inputs.height)?;
+    fields.set_item("skin_temperature", inputs.skin_temperature)?;
+    fields.set_item("observer_height", inputs.observer_height)?;
+    fields.set_item("quality", inputs.quality.bits())?;
+    py.import("json")?.call_method1("dumps", (fields,))
+}
+
+/// Get a standard atmosphere as arrays for `compute_rtm`.
+///
+/// The `name` is one of `"us_standard_1976"`, `"tropical"`,
+/// `"mid_latitude_summer"`, `"mid_latitude_winter"`, `"subarctic_summer"`, or
+/// `"subarctic_winter"`. The profile is on 50 levels from 0.25 km to 80 km
+/// above the surface, and it has no clouds. See the Rust documentation of the
+/// `standard_atmospheres` module for how the profiles are defined.
+///
+/// The returned dict has the profile arguments of `compute_rtm`, all float32:
+/// `pressure` with shape (`num_levels`, ), the profiles with shape
+/// (`num_points`, `num_levels`), and the surface values with shape
+/// (`num_points`, ). Each point is the same atmosphere.
+#[pyfunction]
+#[pyo3(signature = (name, num_points=1))]
+fn standard_atmosphere<'py>(
+    py: Python<'py>,
+    name: &str,
+    num_points: usize,
+) -> PyResult<Bound<'py, PyDict>> {
+    let atmosphere = standard_atmospheres::by_name(name).ok_or_else(|| {
+        let names: Vec<_> = standard_atmospheres::ALL.iter().map(|a| a.name).collect();
+        PyValueError::new_err(format!(
+            "unknown standard atmosphere \"{name}\", expected one of: {}",
+            names.join(", ")
+        ))
+    })?;
+    profile_arrays(py, &atmosphere.profile(), num_points)
+}
+
+/// Get an isothermal atmosphere at `temperature` in K as arrays for
+/// `compute_rtm`.
+///
+/// The profile is on the same levels as `standard_atmosphere`, with the
+/// surface pressure and water vapor of `"us_standard_1976"` (with the
+/// relative humidity at most 80%). For any absorption, both `tb_up` and
+/// `tb_down` of the atmosphere are `compute_isothermal_tb(temperature, tran)`,
+/// which makes it an analytic check of the RTM.
+///
+/// The returned dict is the same as for `standard_atmosphere`.
+#[pyfunction]
+#[pyo3(signature = (temperature, num_points=1))]
+fn isothermal_atmosphere<'py>(
+    py: Python<'py>,
+    temperature: f32,
+    num_points: usize,
+) -> PyResult<Bound<'py, PyDict>> {
+    profile_arrays(
+        py,
+        &standard_atmospheres::isothermal(temperature),
+        num_points,
+    )
+}
+
+/// The arrays of `profile` for `compute_rtm`, with each of `num_points` points
+/// the same.
+fn profile_arrays<'py>(
+    py: Python<'py>,
+    profile: &standard_atmospheres::StandardProfile,
+    num_points: usize,
+) -> PyResult<Bound<'py, PyDict>> {
+    let levels = |values: &[f32]| {
+        ArrayView1::from(values)
+            .broadcast((num_points, values.len()))
+            .expect("a 1d array can be broadcast to 2d")
+            .to_pyarray(py)
+    };
+    let surface = |value: f32| Array1::from_elem(num_points, value).into_pyarray(py);
+
+    let arrays = PyDict::new(py);
+    arrays.set_item("pressure", profile.pressure.to_pyarray(py))?;
+    arrays.set_item("temperature", levels(&profile.temperature))?;
+    arrays.set_item("height", levels(&profile.height))?;
+    arrays.set_item("specific_humidity", levels(&profile.specific_humidity))?;
+    arrays.set_item("liquid_content", levels(&profile.liquid_content))?;
+    arrays.set_item("surface_temperature", surface(profile.surface_temperature))?;
+    arrays.set_item("surface_height", surface(profile.surface_height))?;
+    arrays.set_item("surface_dewpoint", surface(profile.surface_dewpoint))?;
+    arrays.set_item("surface_pressure", surface(profile.surface_pressure))?;
+    Ok(arrays)
+}
+
+/// Read the profiles from an RTTOV profile file as arrays for `compute_rtm`.
+///
+/// The file is in the plain-text format of the profiles for RTTOV's example
+/// programs (such as `prof.dat`), and `num_levels` is the number of levels of
+/// each profile, which isn't in the file. See the Rust documentation of the
+/// `rttov` module for the format and how it's converted.
+///
+/// The returned dict has the profile arguments of `compute_rtm`, all float32:
+/// `pressure` with shape (`num_levels`, ) if all of the profiles are on the
+/// same pressure levels, or otherwise (`num_points`, `num_levels`), the
+/// profiles with shape (`num_points`, `num_levels`), and the surface values
+/// (including `skin_temperature`) with shape (`num_points`, ). The satellite
+/// zenith angles are not included.
+#[cfg(feature = "io")]
+#[pyfunction]
+fn read_rttov_profiles<'py>(
+    py: Python<'py>,
+    path: std::path::PathBuf,
+    num_levels: usize,
+) -> PyResult<Bound<'py, PyDict>> {
+    let profiles = rtm::rttov::read_profiles(path, num_levels)?;
+    let first = profiles
+        .first()
+        .ok_or_else(|| PyValueError::new_err("the file has no profiles"))?;
+    let shared_pressure = profiles
+        .iter()
+        .all(|profile| profile.pressure == first.pressure);
+
+    let levels = |values: fn(&rtm::rttov::RttovProfile) -> &[f32]| {
+        Array2::from_shape_fn((profiles.len(), num_levels), |(i, j)| {
+            values(&profiles[i])[j]
+        })
+        .into_pyarray(py)
+    };
+    let surface = |value: fn(&rtm::rttov::RttovProfile) -> f32| {
+        profiles
+            .iter()
+            .map(value)
+            .collect::<Array1<f32>>()
+            .into_pyarray(py)
+    };
+
+    let arrays = PyDict::new(py);
+    if shared_pressure {
+        arrays.set_item("pressure", first.pressure.to_pyarray(py))?;
+    } else {
+        arrays.set_item("pressure", levels(|p| &p.pressure))?;
+    }
+    arrays.set_item("temperature", levels(|p| &p.temperature))?;
+    arrays.set_item("height", levels(|p| &p.height))?;
+    arrays.set_item("specific_humidity", levels(|p| &p.specific_humidity))?;
+    arrays.set_item("liquid_content", levels(|p| &p.liquid_content))?;
+    arrays.set_item("surface_temperature", surface(|p| p.surface_temperature))?;
+    arrays.set_item("surface_height", surface(|p| p.surface_height))?;
+    arrays.set_item("surface_dewpoint", surface(|p| p.surface_dewpoint))?;
+    arrays.set_item("surface_pressure", surface(|p| p.surface_pressure))?;
+    arrays.set_item("skin_temperature", surface(|p| p.skin_temperature))?;
+    Ok(arrays)
+}
+
+/// Load a sensor definition file, with the channels and scan positions of a
+/// sensor in TOML. See the Rust documentation of the `sensor_file` module for
+/// the format; examples for MSU and AMSU-A are in the `data` directory of the
+/// source.
+///
+/// The returned dict has the `name` of the sensor, the center `frequency` in
+/// GHz and `polarization` (`"v"` or `"h"`) of each channel, the
+/// `incidence_angle` and `scan_angle` (or `None`) in degrees of each scan
+/// position, and `channels`, a list with the passband of each channel as a
+/// `Channel`. The channels are numbered from 1 in the file, so channel `n` is
+/// at index `n - 1`. Use `RtmParameters.from_channels` with the channels and
+/// the incidence angle of a scan position to compute the RTM for them.
+#[cfg(feature = "io")]
+#[pyfunction]
+fn load_sensor(py: Python<'_>, path: std::path::PathBuf) -> PyResult<Bound<'_, PyDict>> {
+    let sensor = rtm::sensor_file::read_sensor(path)?;
+    let channels = (1..=sensor.num_channels())
+        .map(|channel| Ok(PyChannel(sensor.channel(channel)?)))
+        .collect::<Result<Vec<_>, RtmError>>()?;
+
+    let dict = PyDict::new(py);
+    dict.set_item("name", &sensor.name)?;
+    dict.set_item(
+        "frequency",
+        sensor
+            .channels
+            .iter()
+            .map(|channel| channel.frequency)
+            .collect::<Array1<f64>>()
+            .into_pyarray(py),
+    )?;
+    dict.set_item(
+        "polarization",
+        sensor
+            .channels
+            .iter()
+            .map(|channel| channel.polarization.name())
+            .collect::<Vec<_>>(),
+    )?;
+    dict.set_item("incidence_angle", sensor.incidence_angles.to_pyarray(py))?;
+    dict.set_item(
+        "scan_angle",
+        sensor
+            .scan_angles
+            .as_ref()
+            .map(|scan_angles| scan_angles.to_pyarray(py)),
+    )?;
+    dict.set_item("channels", channels)?;
+    Ok(dict)
+}
+
+/// Compare two sets of RTM outputs, such as from `compute_rtm` with different
+/// settings, or from this RTM and another model on the same profiles.
+///
+/// `a` and `b` are `AtmoParameters` with the same shape and dtype. For each of
+/// `tran`, `tb_up`, and `tb_down`, the returned dict has a dict of float64
+/// arrays with shape (`num_freq`, ), over the points of each channel:
+///
+/// - `max`: the maximum absolute difference
+/// - `rms`: the root-mean-square difference
+/// - `bias`: the mean difference, `a - b`
+/// - `within_tolerance` (bool): whether `max` is at most `tol`
+///
+/// The tolerance `tol` is either a float for all three outputs, or a dict with
+/// a float for each of `"tran"`, `"tb_up"`, and `"tb_down"`. NaN in either
+/// input makes the statistics of that channel NaN.
+#[pyfunction]
+fn compare_outputs<'py>(
+    py: Python<'py>,
+    a: &AtmoParameters,
+    b: &AtmoParameters,
+    tol: &Bound<'py, PyAny>,
+) -> PyResult<Bound<'py, PyDict>> {
+    let tolerance = |name: &str| -> PyResult<f64> {
+        match tol.downcast::<PyDict>() {
+            Ok(tol) => tol
+                .get_item(name)?
+                .ok_or_else(|| PyValueError::new_err(format!("tol has no tolerance for {name}")))?
+                .extract(),
+            Err(_) => tol.extract(),
+        }
+    };
+    let pairs = match (&a.arrays, &b.arrays) {
+        (OutputArrays::F32(a), OutputArrays::F32(b)) => output_pairs(a, b),
+        (OutputArrays::F64(a), OutputArrays::F64(b)) => output_pairs(a, b),
+        _ => return Err(PyValueError::new_err("a and b have different dtypes")),
+    };
+
+    let comparison = PyDict::new(py);
+    for (name, a, b) in pairs {
+        if a.dim() != b.dim() {
+            return Err(PyValueError::new_err(format!(
+                "{name} has different shapes: {:?} and {:?}",
+                a.dim(),
+                b.dim()
+            )));
+        }
+        let difference = &a - &b;
+        let num_points = difference.nrows() as f64;
+        let max = difference.fold_axis(Axis(0), 0., |&max: &f64, &d| {
+            // Propagate NaN, which `f64::max` would skip
+            if d.is_nan() || max.is_nan() {
+                f64::NAN
+            } else {
+                max.max(d.abs())
+            }
+        });
+        let rms = difference
+            .mapv(|d| d * d)
+            .sum_axis(Axis(0))
+            .mapv(|sum| (sum / num_points).sqrt());
+        let bias = difference.sum_axis(Axis(0)) / num_points;
+        let tolerance = tolerance(name)?;
+        let within_tolerance = max.mapv(|max| max <= tolerance);
+
+        let stats = PyDict::new(py);
+        stats.set_item("max", max.into_pyarray(py))?;
+        stats.set_item("rms", rms.into_pyarray(py))?;
+        stats.set_item("bias", bias.into_pyarray(py))?;
+        stats.set_item("within_tolerance", within_tolerance.into_pyarray(py))?;
+        comparison.set_item(name, stats)?;
+    }
+    Ok(comparison)
+}
+
+/// The (name, a, b) of each output array to compare, in double precision.
+fn output_pairs<T: PyFloat>(
+    a: &Outputs<T>,
+    b: &Outputs<T>,
+) -> [(&'static str, Array2<f64>, Array2<f64>); 3] {
+    let widen = |values: &Array2<T>| values.mapv(T::into_f64);
+    [
+        ("tran", widen(&a.tran), widen(&b.tran)),
+        ("tb_up", widen(&a.tb_up), widen(&b.tb_up)),
+        ("tb_down", widen(&a.tb_down), widen(&b.tb_down)),
+    ]
+}
+
+/// Compute the Planck spectral radiance in mW/m²/sr/cm⁻¹ for a microwave
+/// `frequency` in GHz and an array of physical `temperature` in K.
+#[pyfunction]
+fn planck_radiance<'py>(
+    py: Python<'py>,
+    frequency: f32,
+    temperature: PyReadonlyArrayDyn<'py, f32>,
+) -> Bound<'py, PyArrayDyn<f32>> {
+    temperature
+        .as_array()
+        .mapv(|t| rtm::planck_radiance(frequency, t))
+        .into_pyarray(py)
+}
+
+/// Compute the Planck-equivalent brightness temperature in K for a microwave
+/// `frequency` in GHz and an array of spectral `radiance` in mW/m²/sr/cm⁻¹.
+///
+/// This is the inverse of `planck_radiance`.
+#[pyfunction]
+fn planck_brightness_temperature<'py>(
+    py: Python<'py>,
+    frequency: f32,
+    radiance: PyReadonlyArrayDyn<'py, f32>,
+) -> Bound<'py, PyArrayDyn<f32>> {
+    radiance
+        .as_array()
+        .mapv(|r| rtm::planck_brightness_temperature(frequency, r))
+        .into_pyarray(py)
+}
+
+/// A pair of (`num_points`, `num_freq`) arrays for V- and H-pol.
+type PolarizationPair<'py> = (Bound<'py, PyArray2<f32>>, Bound<'py, PyArray2<f32>>);
+
+/// Compute the emissivity of the sea surface for each point and channel.
+///
+/// The channels are given by `frequency` in GHz and Earth `incidence_angle` in
+/// degrees, as in `compute_rtm`, and the points by `sst` in K and `salinity` in
+/// parts per thousand, with shape (`num_points`, ). Returns the tuple
+/// `(emis_v, emis_h)` of the vertical and horizontal polarization
+/// emissivities, each with shape (`num_points`, `num_freq`).
+///
+/// The sea water dielectric constant is from Meissner and Wentz, and is fit
+/// for SSTs from 271.16 K to 307.16 K and salinities from 0 to 40 ppt. The sea
+/// is flat unless `wind_speed`, the 10 m wind speed in m/s with shape
+/// (`num_points`, ), is given. With the default `ocean_model="rough"`, the
+/// wind adds large-scale roughness, from the Cox and Munk slope distribution,
+/// and foam, from the Monahan and O'Muircheartaigh whitecap coverage. With
+/// `ocean_model="specular_foam"`, the sea stays flat and the wind only adds
+/// the foam. The foam covers a fraction `3.84e-6 * wind_speed**3.41` of the
+/// surface and is taken to be a blackbody, so the emissivity is `e + f (1 -
+/// e)` for the emissivity `e` of the open water and foam fraction `f`. This is
+/// a physical model rather than a fit to observations like the Meissner and
+/// Wentz (2012) model, so it should be expected to be off by a few hundredths
+/// in emissivity at high wind speeds.
+/// It's meant for wind speeds from 0 to 40 m/s, but is extrapolated above
+/// about 20 m/s, and the sea is all foam above 39 m/s. A wind speed of 0 is
+/// the same as a flat sea.
+#[pyfunction]
+#[pyo3(signature = (frequency, incidence_angle, sst, salinity, wind_speed=None, ocean_model="rough"))]
+fn ocean_emissivity<'py>(
+    py: Python<'py>,
+    frequency: PyReadonlyArray1<'py, f32>,
+    incidence_angle: PyReadonlyArray1<'py, f32>,
+    sst: PyReadonlyArray1<'py, f32>,
+    salinity: PyReadonlyArray1<'py, f32>,
+    wind_speed: Option<PyReadonlyArray1<'py, f32>>,
+    ocean_model: &str,
+) -> PyResult<PolarizationPair<'py>> {
+    let parameters = parameters(&frequency, &incidence_angle)?;
+    let ocean_model = parse_ocean_model(ocean_model)?;
+    let (sst, salinity) = (sst.as_array(), salinity.as_array());
+    let wind_speed = wind_speed.as_ref().map(|wind_speed| wind_speed.as_array());
+    if salinity.len() != sst.len()
+        || wind_speed.is_some_and(|wind_speed| wind_speed.len() != sst.len())
+    {
+        return Err(RtmError::InconsistentInputs.into());
+    }
+
+    let shape = (sst.len(), parameters.len());
+    let mut emis_v = Array2::zeros(shape);
+    let mut emis_h = Array2::zeros(shape);
+    Zip::indexed(emis_v.rows_mut())
+        .and(emis_h.rows_mut())
+        .and(&sst)
+        .and(&salinity)
+        .for_each(|point, mut emis_v, mut emis_h, &sst, &salinity| {
+            let wind_speed = wind_speed.map(|wind_speed| wind_speed[point]);
+            let channels = parameters
+                .frequencies()
+                .iter()
+                .zip(parameters.incidence_angles());
+            for ((emis_v, emis_h), (&freq, &inc)) in
+                emis_v.iter_mut().zip(emis_h.iter_mut()).zip(channels)
+            {
+                (*emis_v, *emis_h) = ocean_model.emissivity(freq, inc, sst, salinity, wind_speed);
+            }
+        });
+    Ok((emis_v.into_pyarray(py), emis_h.into_pyarray(py)))
+}
+
+/// Compute the absorption coefficient of cloud liquid water for each
+/// frequency.
+///
+/// For `frequency` in GHz with shape (`num_freq`, ), a cloud `temperature` in
+/// K, and a liquid water density `liquid_water_density` in g/m³, returns the
+/// absorption coefficient in Np/km with shape (`num_freq`, ). If `derivative`
+/// is true, returns the tuple `(absorption, d_absorption)` instead, where
+/// `d_absorption` is the derivative of the absorption with respect to the
+/// liquid water density, in (Np/km)/(g/m³).
+///
+/// This is the same cloud absorption as in `compute_rtm`: the droplets are in
+/// the Rayleigh limit, so the absorption is proportional to the liquid water
+/// density, and the derivative (the mass absorption coefficient) only depends
+/// on the frequency and temperature. The dielectric constant of water is from
+/// Meissner and Wentz, and is fit from 1 to 400 GHz and from 248.16 K to
+/// 313.16 K.
+#[pyfunction]
+#[pyo3(signature = (frequency, temperature, liquid_water_density, derivative=false))]
+fn cloud_absorption_spectrum<'py>(
+    py: Python<'py>,
+    frequency: PyReadonlyArray1<'py, f32>,
+    temperature: f32,
+    liquid_water_density: f32,
+    derivative: bool,
+) -> PyResult<Bound<'py, PyAny>> {
+    let (absorption, d_absorption): (Vec<_>, Vec<_>) = frequency
+        .as_array()
+        .iter()
+        .map(|&freq| rtm::cloud_absorption(freq, temperature, liquid_water_density))
+        .unzip();
+    let absorption = absorption.into_pyarray(py);
+    if derivative {
+        (absorption, d_absorption.into_pyarray(py)).into_bound_py_any(py)
+    } else {
+        Ok(absorption.into_any())
+    }
+}
+
+/// Compute the complex dielectric constant of water over a grid of
+/// frequencies and temperatures, e.g., to build emissivity lookup tables.
+///
+/// For `frequency` in GHz with shape (`num_freq`, ), `temperature` in K with
+/// shape (`num_temp`, ), and a `salinity` in parts per thousand (0 for pure
+/// water), returns the tuple `(real, imag)` of the real and imaginary parts,
+/// each with shape (`num_freq`, `num_temp`). The imaginary part is negative,
+/// as in the Wentz convention.
+///
+/// This is the Meissner and Wentz dielectric constant used for cloud liquid
+/// water and the ocean surface. It is fit from 1 to 400 GHz, from 248.16 K to
+/// 313.16 K for pure water, and from 271.16 K to 307.16 K and 0 to 40 ppt for
+/// sea water.
+#[pyfunction]
+#[pyo3(signature = (frequency, temperature, salinity=0.0))]
+fn meissner_grid<'py>(
+    py: Python<'py>,
+    frequency: PyReadonlyArray1<'py, f32>,
+    temperature: PyReadonlyArray1<'py, f32>,
+    salinity: f32,
+) -> (Bound<'py, PyArray2<f32>>, Bound<'py, PyArray2<f32>>) {
+    let (frequency, temperature) = (frequency.as_array(), temperature.as_array());
+    let shape = (frequency.len(), temperature.len());
+    let mut real = Array2::zeros(shape);
+    let mut imag = Array2::zeros(shape);
+    Zip::from(real.rows_mut())
+        .and(imag.rows_mut())
+        .and(&frequency)
+        .for_each(|mut real, mut imag, &freq| {
+            for ((real, imag), &t) in real.iter_mut().zip(imag.iter_mut()).zip(&temperature) {
+                let permittivity = rtm::meissner(freq, t, salinity);
+                (*real, *imag) = (permittivity.re, permittivity.im);
+            }
+        });
+    (real.into_pyarray(py), imag.into_pyarray(py))
+}
+
+/// Convert the scan angle from nadir `scan_angle` in degrees, an array of any
+/// shape, of a satellite at a height `satellite_altitude` in m to the Earth
+/// incidence angle in degrees, for a spherical Earth with a radius
+/// `earth_radius` in m.
+///
+/// The default radius is the one used elsewhere in the RTM. From the law of
+/// sines, `sin(eia) = (earth_radius + satellite_altitude) / earth_radius *
+/// sin(scan_angle)`. A scan angle past the limb, where the line of sight misses
+/// the Earth, gives NaN.
+#[pyfunction]
+#[pyo3(signature = (scan_angle, satellite_altitude, earth_radius=rtm::EARTH_RADIUS))]
+fn eia_from_scan<'py>(
+    py: Python<'py>,
+    scan_angle: PyReadonlyArrayDyn<'py, f32>,
+    satellite_altitude: f32,
+    earth_radius: f32,
+) -> Bound<'py, PyArrayDyn<f32>> {
+    scan_angle
+        .as_array()
+        .mapv(|scan_angle| rtm::eia_from_scan(scan_angle, satellite_altitude, earth_radius))
+        .into_pyarray(py)
+}
+
+/// Convert the Earth incidence angle `incidence_angle` in degrees, an array of
+/// any shape, to the scan angle from nadir in degrees of a satellite at a
+/// height `satellite_altitude` in m, for a spherical Earth with a radius
+/// `earth_radius` in m. This is the inverse of `eia_from_scan`.
+#[pyfunction]
+#[pyo3(signature = (incidence_angle, satellite_altitude, earth_radius=rtm::EARTH_RADIUS))]
+fn scan_from_eia<'py>(
+    py: Python<'py>,
+    incidence_angle: PyReadonlyArrayDyn<'py, f32>,
+    satellite_altitude: f32,
+    earth_radius: f32,
+) -> Bound<'py, PyArrayDyn<f32>> {
+    incidence_angle
+        .as_array()
+        .mapv(|eia| rtm::scan_from_eia(eia, satellite_altitude, earth_radius))
+        .into_pyarray(py)
+}
+
+/// Look up the Earth incidence angle in degrees of each scan position in
+/// `scan_position`, an integer array of any shape, for the cross-track sounder
+/// `sensor` (`"msu"` or `"amsu_a"`).
+///
+/// The scan positions are numbered from 0, from one edge of the swath to the
+/// other. A `ValueError` is raised for a position that the sensor doesn't have.
+#[pyfunction]
+fn scan_incidence_angle<'py>(
+    py: Python<'py>,
+    sensor: &str,
+    scan_position: PyReadonlyArrayDyn<'py, i64>,
+) -> PyResult<Bound<'py, PyArrayDyn<f32>>> {
+    let sensor = parse_sensor(sensor)?;
+    let scan_position = scan_position.as_array();
+    let incidence_angle = scan_position
+        .iter()
+        .map(|&position| Ok(sensor.incidence_angles[scan_position_index(&sensor, position)?]))
+        .collect::<Result<Vec<_>, RtmError>>()?;
+    let incidence_angle = ArrayD::from_shape_vec(scan_position.raw_dim(), incidence_angle)
+        .expect("there's an angle for each position");
+    Ok(incidence_angle.into_pyarray(py))
+}
+
+/// Apply `f` elementwise to two arrays, broadcasting one to the shape of the
+/// other if needed.
+fn map_binary<'py>(
+    py: Python<'py>,
+    a: &PyReadonlyArrayDyn<'py, f32>,
+    b: &PyReadonlyArrayDyn<'py, f32>,
+    f: impl Fn(f32, f32) -> f32,
+) -> PyResult<Bound<'py, PyArrayDyn<f32>>> {
+    let (a, b) = (a.as_array(), b.as_array());
+    let output = if let Some(b) = b.broadcast(a.raw_dim()) {
+        Zip::from(&a).and(&b).map_collect(|&a, &b| f(a, b))
+    } else if let Some(a) = a.broadcast(b.raw_dim()) {
+        Zip::from(&a).and(&b).map_collect(|&a, &b| f(a, b))
+    } else {
+        return Err(RtmError::InconsistentInputs.into());
+    };
+    Ok(output.into_pyarray(py))
+}
+
+/// The brightness temperature in K of an isothermal atmosphere at temperature
+/// `t` in K with a transmissivity of `tran`, which is `t * (1 - tran)`.
+///
+/// This is both `tb_up` and `tb_down` from `compute_rtm` for an
+/// `isothermal_atmosphere`, in the default Rayleigh-Jeans convention. The
+/// inputs are broadcast against each other.
+#[pyfunction]
+fn compute_isothermal_tb<'py>(
+    py: Python<'py>,
+    t: PyReadonlyArrayDyn<'py, f32>,
+    tran: PyReadonlyArrayDyn<'py, f32>,
+) -> PyResult<Bound<'py, PyArrayDyn<f32>>> {
+    map_binary(py, &t, &tran, standard_atmospheres::isothermal_tb)
+}
+
+/// Convert specific humidity `q` in kg/kg to water vapor pressure in hPa, at a
+/// total pressure `p` in hPa.
+///
+/// The inputs are broadcast against each other.
+#[pyfunction]
+fn specific_humidity_to_vapor_pressure<'py>(
+    py: Python<'py>,
+    q: PyReadonlyArrayDyn<'py, f32>,
+    p: PyReadonlyArrayDyn<'py, f32>,
+) -> PyResult<Bound<'py, PyArrayDyn<f32>>> {
+    map_binary(py, &q, &p, rtm::specific_humidity_to_vapor_pressure)
+}
+
+/// Convert water vapor pressure `pv` in hPa to specific humidity in kg/kg, at
+/// a total pressure `p` in hPa.
+///
+/// The inputs are broadcast against each other.
+#[pyfunction]
+fn vapor_pressure_to_specific_humidity<'py>(
+    py: Python<'py>,
+    pv: PyReadonlyArrayDyn<'py, f32>,
+    p: PyReadonlyArrayDyn<'py, f32>,
+) -> PyResult<Bound<'py, PyArrayDyn<f32>>> {
+    map_binary(py, &pv, &p, rtm::vapor_pressure_to_specific_humidity)
+}
+
+/// Convert the mass mixing ratio `w` in kg/kg to water vapor pressure in hPa,
+/// at a total pressure `p` in hPa.
+///
+/// The inputs are broadcast against each other.
+#[pyfunction]
+fn mixing_ratio_to_vapor_pressure<'py>(
+    py: Python<'py>,
+    w: PyReadonlyArrayDyn<'py, f32>,
+    p: PyReadonlyArrayDyn<'py, f32>,
+) -> PyResult<Bound<'py, PyArrayDyn<f32>>> {
+    map_binary(py, &w, &p, rtm::mixing_ratio_to_vapor_pressure)
+}
+
+/// Convert water vapor pressure `pv` in hPa to the mass mixing ratio in kg/kg,
+/// at a total pressure `p` in hPa.
+///
+/// The inputs are broadcast against each other.
+#[pyfunction]
+fn vapor_pressure_to_mixing_ratio<'py>(
+    py: Python<'py>,
+    pv: PyReadonlyArrayDyn<'py, f32>,
+    p: PyReadonlyArrayDyn<'py, f32>,
+) -> PyResult<Bound<'py, PyArrayDyn<f32>>> {
+    map_binary(py, &pv, &p, rtm::vapor_pressure_to_mixing_ratio)
+}
+
+/// Convert specific humidity `q` in kg/kg to the mass mixing ratio in kg/kg.
+#[pyfunction]
+fn specific_humidity_to_mixing_ratio<'py>(
+    py: Python<'py>,
+    q: PyReadonlyArrayDyn<'py, f32>,
+) -> Bound<'py, PyArrayDyn<f32>> {
+    q.as_array()
+        .mapv(rtm::specific_humidity_to_mixing_ratio)
+        .into_pyarray(py)
+}
+
+/// Convert the mass mixing ratio `w` in kg/kg to specific humidity in kg/kg.
+#[pyfunction]
+fn mixing_ratio_to_specific_humidity<'py>(
+    py: Python<'py>,
+    w: PyReadonlyArrayDyn<'py, f32>,
+) -> Bound<'py, PyArrayDyn<f32>> {
+    w.as_array()
+        .mapv(rtm::mixing_ratio_to_specific_humidity)
+        .into_pyarray(py)
+}
+
+/// Convert the dewpoint `td` in K to water vapor pressure in hPa, using the
+/// Buck equation.
+#[pyfunction]
+fn dewpoint_to_vapor_pressure<'py>(
+    py: Python<'py>,
+    td: PyReadonlyArrayDyn<'py, f32>,
+) -> Bound<'py, PyArrayDyn<f32>> {
+    td.as_array()
+        .mapv(rtm::dewpoint_to_vapor_pressure)
+        .into_pyarray(py)
+}
+
+/// Convert water vapor pressure `pv` in hPa to the dewpoint in K, using the
+/// inverse of the Buck equation.
+#[pyfunction]
+fn vapor_pressure_to_dewpoint<'py>(
+    py: Python<'py>,
+    pv: PyReadonlyArrayDyn<'py, f32>,
+) -> Bound<'py, PyArrayDyn<f32>> {
+    pv.as_array()
+        .mapv(rtm::vapor_pressure_to_dewpoint)
+        .into_pyarray(py)
+}
+
+/// The Buck equation: convert the temperature `temp` in K to the saturation
+/// water vapor pressure (over water) in hPa.
+#[pyfunction]
+fn buck_vap<'py>(
+    py: Python<'py>,
+    temp: PyReadonlyArrayDyn<'py, f32>,
+) -> Bound<'py, PyArrayDyn<f32>> {
+    temp.as_array().mapv(rtm::buck_vap).into_pyarray(py)
+}
+
+/// The inverse of the Buck equation: convert the water vapor pressure
+/// `vapor_pressure` in hPa to the temperature in K at which it is the
+/// saturation pressure (the dewpoint). The vapor pressure must be positive.
+#[pyfunction]
+fn buck_dewpoint<'py>(
+    py: Python<'py>,
+    vapor_pressure: PyReadonlyArrayDyn<'py, f32>,
+) -> Bound<'py, PyArrayDyn<f32>> {
+    vapor_pressure
+        .as_array()
+        .mapv(rtm::buck_dewpoint)
+        .into_pyarray(py)
+}
+
+/// Convert relative humidity `rh` (a fraction, over water) to water vapor
+/// pressure in hPa, at an air temperature `t` in K.
+///
+/// The inputs are broadcast against each other.
+#[pyfunction]
+fn relative_humidity_to_vapor_pressure<'py>(
+    py: Python<'py>,
+    rh: PyReadonlyArrayDyn<'py, f32>,
+    t: PyReadonlyArrayDyn<'py, f32>,
+) -> PyResult<Bound<'py, PyArrayDyn<f32>>> {
+    map_binary(py, &rh, &t, rtm::relative_humidity_to_vapor_pressure)
+}
+
+/// Convert water vapor pressure `pv` in hPa to relative humidity (a fraction,
+/// over water), at an air temperature `t` in K.
+///
+/// The inputs are broadcast against each other.
+#[pyfunction]
+fn vapor_pressure_to_relative_humidity<'py>(
+    py: Python<'py>,
+    pv: PyReadonlyArrayDyn<'py, f32>,
+    t: PyReadonlyArrayDyn<'py, f32>,
+) -> PyResult<Bound<'py, PyArrayDyn<f32>>> {
+    map_binary(py, &pv, &t, rtm::vapor_pressure_to_relative_humidity)
+}
+
+/// Get information about the model, for logging or provenance.
+///
+/// The returned dict has the crate `version`, the `git_describe` string of the
+/// source it was built from, `spectroscopy`, a dict of the absorption model in
+/// use for each absorber, and `frequency_limits`, a dict of the highest
+/// frequency in GHz of the validated range of each absorption model.
+#[pyfunction]
+fn get_model_info(py: Python<'_>) -> PyResult<Bound<'_, PyDict>> {
+    let spectroscopy = PyDict::new(py);
+    for (absorber, model) in rtm::SPECTROSCOPY {
+        spectroscopy.set_item(absorber, model)?;
+    }
+    let frequency_limits = PyDict::new(py);
+    for (absorber, limit) in rtm::FREQUENCY_LIMITS {
+        frequency_limits.set_item(absorber, limit)?;
+    }
+
+    let info = PyDict::new(py);
+    info.set_item("version", rtm::VERSION)?;
+    info.set_item("git_describe", rtm::GIT_DESCRIBE)?;
+    info.set_item("spectroscopy", spectroscopy)?;
+    info.set_item("frequency_limits", frequency_limits)?;
+    Ok(info)
+}
+
+/// The `conversions` submodule, with the Buck equation and its inverse, and
+/// the humidity conversions. These are the same functions the RTM uses to
+/// prepare its inputs.
+fn conversions(py: Python<'_>) -> PyResult<Bound<'_, PyModule>> {
+    let m = PyModule::new(py, "conversions")?;
+    m.add(
+        "__doc__",
+        "Humidity conversions, which are the same ones used to prepare the RTM inputs.",
+    )?;
+    m.add_function(wrap_pyfunction!(buck_vap, &m)?)?;
+    m.add_function(wrap_pyfunction!(buck_dewpoint, &m)?)?;
+    m.add_function(wrap_pyfunction!(dewpoint_to_vapor_pressure, &m)?)?;
+    m.add_function(wrap_pyfunction!(vapor_pressure_to_dewpoint, &m)?)?;
+    m.add_function(wrap_pyfunction!(specific_humidity_to_vapor_pressure, &m)?)?;
+    m.add_function(wrap_pyfunction!(vapor_pressure_to_specific_humidity, &m)?)?;
+    m.add_function(wrap_pyfunction!(mixing_ratio_to_vapor_pressure, &m)?)?;
+    m.add_function(wrap_pyfunction!(vapor_pressure_to_mixing_ratio, &m)?)?;
+    m.add_function(wrap_pyfunction!(specific_humidity_to_mixing_ratio, &m)?)?;
+    m.add_function(wrap_pyfunction!(mixing_ratio_to_specific_humidity, &m)?)?;
+    m.add_function(wrap_pyfunction!(relative_humidity_to_vapor_pressure, &m)?)?;
+    m.add_function(wrap_pyfunction!(vapor_pressure_to_relative_humidity, &m)?)?;
+    Ok(m)
+}
+
+/// A logger that forwards records to Python logging with `pyo3_log`, and also
+/// passes the key-value fields of a record, such as those of the progress, to
+/// the `extra` of the Python record, which `pyo3_log` leaves out.
+struct KeyValueLogger(pyo3_log::Logger);
+
+impl log::Log for KeyValueLogger {
+    fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+        self.0.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record<'_>) {
+        // The records with fields are rare (the progress is every few
+        // seconds), so their level is checked with Python each time rather
+        // than cached
+        if record.key_values().count() == 0 {
+            self.0.log(record);
+        } else {
+            Python::with_gil(|py| {
+                if let Err(err) = log_with_extra(py, record) {
+                    err.print(py);
+                }
+            });
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Log `record` to the Python logger for its target, in the same way as
+/// `pyo3_log`, with its key-value fields as the `extra` of the Python record.
+fn log_with_extra(py: Python<'_>, record: &log::Record<'_>) -> PyResult<()> {
+    let name = record.target().replace("::", ".");
+    let logger = py.import("logging")?.call_method1("getLogger", (&name,))?;
+    let level = match record.level() {
+        log::Level::Error => 40,
+        log::Level::Warn => 30,
+        log::Level::Info => 20,
+        log::Level::Debug => 10,
+        log::Level::Trace => 5,
+    };
+    if !logger.call_method1("isEnabledFor", (level,))?.is_truthy()? {
+        return Ok(());
+    }
+
+    let mut extra = ExtraFields {
+        dict: PyDict::new(py),
+        error: None,
+    };
+    // The visitor only fails with the error it keeps
+    let _ = record.key_values().visit(&mut extra);
+    if let Some(err) = extra.error {
+        return Err(err);
+    }
+    let kwargs = PyDict::new(py);
+    kwargs.set_item("extra", extra.dict)?;
+    let python_record = logger.call_method(
+        "makeRecord",
+        (
+            name,
+            level,
+            record.file(),
+            record.line().unwrap_or_default(),
+            record.args().to_string(),
+            pyo3::types::PyTuple::empty(py),
+            py.None(),
+        ),
+        Some(&kwargs),
+    )?;
+    logger.call_method1("handle", (python_record,))?;
+    Ok(())
+}
+
+/// Collects the key-value fields of a log record into a Python dict.
+struct ExtraFields<'py> {
+    dict: Bound<'py, PyDict>,
+    error: Option<PyErr>,
+}
+
+impl<'kvs> log::kv::VisitSource<'kvs> for ExtraFields<'_> {
+    fn visit_pair(
+        &mut self,
+        key: log::kv::Key<'kvs>,
+        value: log::kv::Value<'kvs>,
+    ) -> Result<(), log::kv::Error> {
+        let mut field = FieldValue {
+            py: self.dict.py(),
+            object: None,
+        };
+        value.visit(&mut field)?;
+        let result = match field.object {
+            Some(object) => object.and_then(|object| self.dict.set_item(key.as_str(), object)),
+            None => Ok(()),
+        };
+        result.map_err(|err| {
+            self.error = Some(err);
+            log::kv::Error::msg("can't convert a field to Python")
+        })
+    }
+}
+
+/// Converts the value of a key-value field to Python: numbers and booleans as
+/// themselves, a missing value (`None` in Rust) as `None`, and anything else as
+/// a string.
+struct FieldValue<'py> {
+    py: Python<'py>,
+    object: Option<PyResult<PyObject>>,
+}
+
+impl<'v> log::kv::VisitValue<'v> for FieldValue<'_> {
+    fn visit_any(&mut self, value: log::kv::Value<'_>) -> Result<(), log::kv::Error> {
+        self.object = Some(value.to_string().into_py_any(self.py));
+        Ok(())
+    }
+
+    fn visit_null(&mut self) -> Result<(), log::kv::Error> {
+        self.object = Some(Ok(self.py.None()));
+        Ok(())
+    }
+
+    fn visit_u64(&mut self, value: u64) -> Result<(), log::kv::Error> {
+        self.object = Some(value.into_py_any(self.py));
+        Ok(())
+    }
+
+    fn visit_i64(&mut self, value: i64) -> Result<(), log::kv::Error> {
+        self.object = Some(value.into_py_any(self.py));
+        Ok(())
+    }
+
+    fn visit_f64(&mut self, value: f64) -> Result<(), log::kv::Error> {
+        self.object = Some(value.into_py_any(self.py));
+        Ok(())
+    }
+
+    fn visit_bool(&mut self, value: bool) -> Result<(), log::kv::Error> {
+        self.object = Some(value.into_py_any(self.py));
+        Ok(())
+    }
+}
+
+/// A Python module implemented in Rust.
+#[pymodule]
+fn rss_atmos_rtm(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    // As `pyo3_log::init`, but with the key-value fields
+    let logger = KeyValueLogger(pyo3_log::Logger::new(
+        m.py(),
+        pyo3_log::Caching::LoggersAndLevels,
+    )?);
+    log::set_boxed_logger(Box::new(logger))
+        .map_err(|err| pyo3::exceptions::PyRuntimeError::new_err(err.to_string()))?;
+    log::set_max_level(log::LevelFilter::Debug);
+
+    m.add_function(wrap_pyfunction!(compute_rtm, m)?)?;
+    m.add_function(wrap_pyfunction!(compute_rtm_block, m)?)?;
+    m.add_function(wrap_pyfunction!(compute_rtm_dict, m)?)?;
+    m.add_function(wrap_pyfunction!(compute_contribution_functions, m)?)?;
+    m.add_function(wrap_pyfunction!(compute_rtm_from_absorption, m)?)?;
+    m.add_function(wrap_pyfunction!(validate_rtm, m)?)?;
+    m.add_function(wrap_pyfunction!(compute_column_stats, m)?)?;
+    m.add_function(wrap_pyfunction!(compute_optical_depths, m)?)?;
+    m.add_function(wrap_pyfunction!(find_opacity_extremum, m)?)?;
+    m.add_function(wrap_pyfunction!(estimate_cost, m)?)?;
+    m.add_function(wrap_pyfunction!(estimate_cost_from_inputs, m)?)?;
+    m.add_function(wrap_pyfunction!(compute_rtm_sst_jacobian, m)?)?;
+    m.add_function(wrap_pyfunction!(compute_rtm_uniform_temp_sensitivity, m)?)?;
+    m.add_function(wrap_pyfunction!(compute_rtm_footprints, m)?)?;
+    m.add_function(wrap_pyfunction!(compute_tb_toa, m)?)?;
+    m.add_function(wrap_pyfunction!(compute_tb_toa_polarized, m)?)?;
+    m.add_function(wrap_pyfunction!(remove_atmosphere, m)?)?;
+    m.add_function(wrap_pyfunction!(prepare_point, m)?)?;
+    m.add_function(wrap_pyfunction!(standard_atmosphere, m)?)?;
+    m.add_function(wrap_pyfunction!(isothermal_atmosphere, m)?)?;
+    m.add_function(wrap_pyfunction!(compute_isothermal_tb, m)?)?;
+    #[cfg(feature = "io")]
+    m.add_function(wrap_pyfunction!(read_rttov_profiles, m)?)?;
+    #[cfg(feature = "io")]
+    m.add_function(wrap_pyfunction!(load_sensor, m)?)?;
+    #[cfg(feature = "io")]
+    m.add_function(wrap_pyfunction!(compute_rtm_npy, m)?)?;
+    #[cfg(feature = "io")]
+    m.add_function(wrap_pyfunction!(compute_rtm_npy_into, m)?)?;
+    #[cfg(feature = "io")]
+    m.add_function(wrap_pyfunction!(compute_rtm_to_zarr, m)?)?;
+    m.add_function(wrap_pyfunction!(compare_outputs, m)?)?;
+    m.add_function(wrap_pyfunction!(get_model_info, m)?)?;
+    m.add_function(wrap_pyfunction!(planck_radiance, m)?)?;
+    m.add_function(wrap_pyfunction!(planck_brightness_temperature, m)?)?;
+    m.add_function(wrap_pyfunction!(ocean_emissivity, m)?)?;
+    m.add_function(wrap_pyfunction!(cloud_absorption_spectrum, m)?)?;
+    m.add_function(wrap_pyfunction!(meissner_grid, m)?)?;
+    m.add_function(wrap_pyfunction!(eia_from_scan, m)?)?;
+    m.add_function(wrap_pyfunction!(scan_from_eia, m)?)?;
+    m.add_function(wrap_pyfunction!(scan_incidence_angle, m)?)?;
+    m.add_function(wrap_pyfunction!(specific_humidity_to_vapor_pressure, m)?)?;
+    m.add_function(wrap_pyfunction!(vapor_pressure_to_specific_humidity, m)?)?;
+    m.add_function(wrap_pyfunction!(mixing_ratio_to_vapor_pressure, m)?)?;
+    m.add_function(wrap_pyfunction!(vapor_pressure_to_mixing_ratio, m)?)?;
+    m.add_function(wrap_pyfunction!(specific_humidity_to_mixing_ratio, m)?)?;
+    m.add_function(wrap_pyfunction!(mixing_ratio_to_specific_humidity, m)?)?;
+    m.add_function(wrap_pyfunction!(dewpoint_to_vapor_pressure, m)?)?;
+    m.add_function(wrap_pyfunction!(relative_humidity_to_vapor_pressure, m)?)?;
+    m.add_function(wrap_pyfunction!(vapor_pressure_to_relative_humidity, m)?)?;
+    m.add_class::<AtmoParameters>()?;
+    m.add_class::<Diagnostics>()?;
+    m.add_class::<PyRtmParameters>()?;
+    m.add_class::<PyRtmOptions>()?;
+    m.add_class::<PyChannel>()?;
+    // Pickle finds the classes by their module, which is only known on import
+    // (e.g., `rss_atmos_rtm.rss_atmos_rtm` in the package)
+    for class in ["RtmParameters", "RtmOptions", "Channel"] {
+        m.getattr(class)?.setattr("__module__", m.name()?)?;
+    }
+
+    // Register the submodule so that it can be imported by its full name too
+    let conversions = conversions(m.py())?;
+    m.add_submodule(&conversions)?;
+    m.py()
+        .import("sys")?
+        .getattr("modules")?
+        .set_item(format!("{}.conversions", m.name()?), &conversions)?;
+    Ok(())
+}
@@ -1,117 +1,1124 @@
 //! Atmospheric radiative transfer model for the ACCESS project
 
+mod absorption;
+mod builder;
+mod column;
 mod core;
+mod cost;
+mod float;
+mod footprint;
+mod geometry;
+#[cfg(feature = "gpu")]
+pub mod gpu;
+mod humidity;
+mod jacobian;
 mod liquid_cloud;
+#[cfg(feature = "io")]
+pub mod netcdf;
+#[cfg(feature = "noise")]
+mod noise;
+#[cfg(feature = "io")]
+pub mod npy;
+mod optical_depth;
+mod options;
 mod oxygen;
+#[cfg(feature = "arrow")]
+pub mod parquet;
+mod partial;
+mod passband;
+mod planck;
+mod quality;
+#[cfg(feature = "io")]
+pub mod rttov;
+pub mod scan_geometry;
+#[cfg(feature = "io")]
+pub mod sensor_file;
+mod source;
+pub mod standard_atmospheres;
+mod surface;
+mod threads;
 mod water_vapor;
+#[cfg(feature = "io")]
+pub mod zarr;
 
 #[cfg(test)]
 mod tests;
 
-use self::core::{atm_tran, layer_absorption};
-use crate::error::RtmError;
+use self::core::{
+    atm_tran, atm_tran_with_scheme, down_at_level, effective_temperatures, level_profiles,
+    up_contributions,
+};
+use self::humidity::{EPS_SCALE, R_DRY};
+use self::planck::planck_radiance_derivative;
+use self::scan_geometry::ScanSensor;
+use self::surface::{gauss_legendre, specular_sky, toa_from_sky};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use smallvec::SmallVec;
-use std::num::NonZeroUsize;
-/// use log::{info};
+use std::{borrow::Cow, fmt, num::NonZeroUsize};
+
+pub use crate::error::RtmError;
+
+pub use self::absorption::{
+    AbsorptionModel, AbsorptionScale, BuiltinAbsorption, PerturbedAbsorption,
+    SpectroscopyPerturbation,
+};
+pub use self::builder::RtmInputsBuilder;
+pub use self::column::ColumnStats;
+pub use self::cost::{estimate_cost, estimate_cost_for_shape, CostEstimate};
+pub use self::float::Float;
+pub use self::footprint::footprint_mean;
+pub use self::geometry::{air_mass_factor, eia_from_scan, scan_from_eia};
+pub use self::humidity::{
+    buck_dewpoint, buck_vap, dewpoint_to_vapor_pressure, mixing_ratio_to_specific_humidity,
+    mixing_ratio_to_vapor_pressure, relative_humidity_to_vapor_pressure,
+    specific_humidity_to_mixing_ratio, specific_humidity_to_vapor_pressure,
+    vapor_pressure_to_dewpoint, vapor_pressure_to_mixing_ratio,
+    vapor_pressure_to_relative_humidity, vapor_pressure_to_specific_humidity,
+};
+pub use self::jacobian::SurfaceTemperatureJacobian;
+pub use self::liquid_cloud::{cloud_absorption, meissner};
+#[cfg(feature = "noise")]
+pub use self::noise::NedtNoise;
+pub use self::optical_depth::{OpacityExtremum, OpticalDepths};
+pub use self::options::RtmOptions;
+pub use self::partial::LowerAtmosphere;
+pub use self::passband::Channel;
+pub use self::planck::{
+    planck_brightness_temperature, planck_radiance, rayleigh_jeans_brightness_temperature,
+    rayleigh_jeans_radiance,
+};
+pub use self::quality::{QualityCounts, QualityFlags};
+pub use self::source::{ArrayProfiles, ProfileRef, ProfileSource, ResultSink};
+pub use self::surface::{
+    foam_fraction, foam_ocean_emissivity, ocean_emissivity, remove_atmosphere,
+    rough_ocean_emissivity, toa_brightness_temperature, OceanModel, Polarization, Reflection,
+    Surface,
+};
+pub use self::threads::{adaptive_num_threads, DEFAULT_ITEMS_PER_THREAD, DEFAULT_WORK_CHUNK_SIZE};
+
+/// Mean radius of the Earth in m, for converting geopotential height to
+/// geometric height and for the scan geometry of [`eia_from_scan`].
+pub const EARTH_RADIUS: f32 = 6371e3;
+
+/// Version of this crate.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The output of `git describe` for the source this was built from, or
+/// "unknown" if it wasn't built from a git checkout.
+pub const GIT_DESCRIBE: &str = env!("RSS_ATMOS_RTM_GIT_DESCRIBE");
+
+/// Identifiers of the absorption models in use, as pairs of (absorber, model).
+pub const SPECTROSCOPY: [(&str, &str); 3] = [
+    ("oxygen", "Liebe et al. 1992, modified by Wentz"),
+    ("water_vapor", "Rosenkranz 1998, modified by Wentz"),
+    (
+        "liquid_cloud",
+        "Meissner and Wentz 2004 dielectric, Rayleigh absorption",
+    ),
+];
+
+/// The reference temperature in K that the line parameters of the oxygen and
+/// water vapor models of [`SPECTROSCOPY`] are tabulated at. Their temperature
+/// dependence is in powers of `REFERENCE_TEMPERATURE / t`.
+///
+/// A line catalog tabulated at another reference, such as the 296 K of HITRAN,
+/// is used with [`PerturbedAbsorption::with_reference_temperature`].
+pub const REFERENCE_TEMPERATURE: f32 = 300.;
+
+/// The highest frequency in GHz of the validated range of each absorption
+/// model, as pairs of (absorber, frequency), in the same order as
+/// [`SPECTROSCOPY`].
+///
+/// - oxygen: Liebe et al. specify their model up to 1000 GHz
+/// - water vapor: the line catalog ends at the 916 GHz line, and the lines
+///   above it are missing
+/// - liquid cloud: the Meissner and Wentz dielectric model is fit from 1 to 400
+///   GHz
+///
+/// The models are extrapolated above these frequencies, and the outputs are
+/// flagged with [`QualityFlags::EXTRAPOLATED_FREQUENCY`]. The liquid cloud
+/// model only counts if there's cloud water.
+pub const FREQUENCY_LIMITS: [(&str, f64); 3] = [
+    ("oxygen", 1000.),
+    ("water_vapor", 916.),
+    ("liquid_cloud", 400.),
+];
+
+/// The entries of `limits` that are below one of the frequencies.
+fn extrapolated<'a, 's: 'a, T: Float>(
+    limits: &'a [(&'s str, f64)],
+    frequency: &'a [T],
+) -> impl Iterator<Item = (&'s str, f64)> + 'a {
+    limits
+        .iter()
+        .copied()
+        .filter(|&(_, limit)| frequency.iter().any(|freq| freq.into_f64() > limit))
+}
 
 /// Input parameters for the RTM that are constant.
-#[derive(Debug)]
-pub struct RtmParameters {
+///
+/// This and the other RTM types are generic over the floating-point precision
+/// `T` ([`Float`]), which is `f32` by default.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RtmParameters<T: Float = f32> {
     /// Microwave frequencies in GHz, with a length of `num_freqs`.
-    frequency: SmallVec<[f32; 8]>,
+    frequency: ChannelVec<T>,
     /// Earth incidence angle in degrees, with a length of `num_freqs`.
-    incidence: SmallVec<[f32; 8]>,
+    incidence: ChannelVec<T>,
+    /// How the layer emission is computed.
+    emission: Emission,
+    /// The convention of the output brightness temperatures, if different from
+    /// that of the emission.
+    tb_convention: Option<TbConvention>,
+    /// How the surface reflects the downwelling, for
+    /// [`RtmInputs::run_toa`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    reflection: Reflection,
+    /// How the emissivity of a wind-roughened ocean is modeled, for
+    /// [`RtmInputs::run_toa`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    ocean_model: OceanModel,
+    /// The passband of each channel, if the outputs are integrated over it.
+    #[cfg_attr(feature = "serde", serde(default))]
+    passbands: Option<Vec<Channel<T>>>,
+    /// How the layer emission is integrated.
+    #[cfg_attr(feature = "serde", serde(default))]
+    integration_scheme: IntegrationScheme,
+    /// The temperature that the layer emission is summed relative to.
+    #[cfg_attr(feature = "serde", serde(default))]
+    layer_sum_reference: LayerSumReference,
+    /// Factors on the absorption of each absorber.
+    #[cfg_attr(feature = "serde", serde(default))]
+    absorption_scale: AbsorptionScale,
+    /// Where the gas absorption is computed for a batch.
+    #[cfg_attr(feature = "serde", serde(default))]
+    absorption_backend: AbsorptionBackend,
+    /// The target number of (point, channel) pairs for each worker thread of
+    /// a batch.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default = "threads::default_items_per_thread")
+    )]
+    items_per_thread: NonZeroUsize,
+    /// The most points that a worker thread of a batch runs as one chunk.
+    #[cfg_attr(feature = "serde", serde(default = "threads::default_work_chunk_size"))]
+    work_chunk_size: NonZeroUsize,
+}
+
+/// Inline capacity of [`ChannelVec`]: the number of channels that the
+/// per-channel vectors, such as the outputs in [`RtmOutputs`], hold without a
+/// heap allocation.
+///
+/// This is 8 by default, or 64 with the `many-channels` feature. Runs with more
+/// channels than this still work, but allocate on the heap for each point. A
+/// larger capacity makes [`RtmOutputs`] and [`RtmParameters`] larger, which
+/// costs memory when many outputs are collected at once.
+pub const CHANNEL_CAPACITY: usize = if cfg!(feature = "many-channels") {
+    64
+} else {
+    8
+};
+
+/// Inline capacity of the per-level vectors used while integrating the RTM:
+/// the number of levels in a profile that are handled without a heap
+/// allocation.
+///
+/// This is 64 by default, or 128 with the `many-levels` feature. A larger
+/// capacity uses more stack space in each worker thread, but saves several
+/// heap allocations per channel and point for longer profiles.
+///
+/// The absorption models dominate the run time, so the allocations are a
+/// small part of it. In the `inline_capacity` benchmark (120 levels, 40
+/// channels), the difference between the default capacities and the larger
+/// ones is within the noise.
+pub const LEVEL_CAPACITY: usize = if cfg!(feature = "many-levels") {
+    128
+} else {
+    64
+};
+
+/// A vector with one value for each channel, stored inline for up to
+/// [`CHANNEL_CAPACITY`] channels.
+pub type ChannelVec<T> = SmallVec<[T; CHANNEL_CAPACITY]>;
+
+/// A vector with one value for each profile level, stored inline for up to
+/// [`LEVEL_CAPACITY`] levels.
+pub(crate) type LevelVec<T> = SmallVec<[T; LEVEL_CAPACITY]>;
+
+/// [`RtmParameters`] in double precision.
+pub type RtmParametersF64 = RtmParameters<f64>;
+
+/// [`RtmInputs`] in double precision.
+pub type RtmInputsF64 = RtmInputs<f64>;
+
+/// [`RtmOutputs`] in double precision.
+pub type RtmOutputsF64 = RtmOutputs<f64>;
+
+/// [`LevelOutputs`] in double precision.
+pub type LevelOutputsF64 = LevelOutputs<f64>;
+
+/// [`EffectiveTemperatures`] in double precision.
+pub type EffectiveTemperaturesF64 = EffectiveTemperatures<f64>;
+
+/// How the emission of each atmospheric layer is computed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Emission {
+    /// Emission is linear in the physical temperature, which is the
+    /// Rayleigh-Jeans approximation. By default, the outputs are brightness
+    /// temperatures in this same convention (see [`TbConvention`]). This
+    /// matches the original Fortran.
+    #[default]
+    RayleighJeans,
+    /// Emission is the Planck spectral radiance of the physical temperature at
+    /// the channel frequency, and the radiative transfer is done in radiance.
+    /// By default, the outputs are converted to Planck-equivalent brightness
+    /// temperatures, which can be converted back to radiance with
+    /// [`planck_radiance`].
+    Planck,
+}
+
+impl Emission {
+    /// A short name for the convention, as used by the Python interface.
+    pub fn name(self) -> &'static str {
+        match self {
+            Emission::RayleighJeans => "rayleigh_jeans",
+            Emission::Planck => "planck",
+        }
+    }
+
+    /// Convert a physical temperature in K to the units the radiative transfer
+    /// is done in.
+    fn source<T: Float>(self, freq: T, t: T) -> T {
+        match self {
+            Emission::RayleighJeans => t,
+            Emission::Planck => planck_radiance(freq, t),
+        }
+    }
+
+    /// Convert a radiative transfer result back to a brightness temperature
+    /// in K, in the given output convention.
+    fn brightness_temperature<T: Float>(self, convention: TbConvention, freq: T, value: T) -> T {
+        match (self, convention) {
+            (Emission::RayleighJeans, TbConvention::RayleighJeans) => value,
+            (Emission::RayleighJeans, TbConvention::Planck) => {
+                planck_brightness_temperature(freq, rayleigh_jeans_radiance(freq, value))
+            }
+            (Emission::Planck, TbConvention::RayleighJeans) => {
+                rayleigh_jeans_brightness_temperature(freq, value)
+            }
+            (Emission::Planck, TbConvention::Planck) => planck_brightness_temperature(freq, value),
+        }
+    }
+
+    /// The derivative of [`source`](Self::source) with respect to the
+    /// temperature.
+    fn source_derivative<T: Float>(self, freq: T, t: T) -> T {
+        match self {
+            Emission::RayleighJeans => T::one(),
+            Emission::Planck => planck_radiance_derivative(freq, t),
+        }
+    }
+
+    /// The derivative of [`brightness_temperature`](Self::brightness_temperature)
+    /// with respect to the radiative transfer result, given the resulting
+    /// brightness temperature `tb`.
+    fn brightness_temperature_derivative<T: Float>(
+        self,
+        convention: TbConvention,
+        freq: T,
+        tb: T,
+    ) -> T {
+        match (self, convention) {
+            (Emission::RayleighJeans, TbConvention::RayleighJeans) => T::one(),
+            (Emission::RayleighJeans, TbConvention::Planck) => {
+                rayleigh_jeans_radiance(freq, T::one()) / planck_radiance_derivative(freq, tb)
+            }
+            (Emission::Planck, TbConvention::RayleighJeans) => {
+                rayleigh_jeans_radiance(freq, T::one()).recip()
+            }
+            (Emission::Planck, TbConvention::Planck) => {
+                planck_radiance_derivative(freq, tb).recip()
+            }
+        }
+    }
+}
+
+/// The convention of the output brightness temperatures, `tb_up` and `tb_down`.
+///
+/// A brightness temperature is a radiance expressed as a temperature. With
+/// [`Emission::RayleighJeans`], the radiative transfer treats radiance as
+/// proportional to temperature, so its results are Rayleigh-Jeans brightness
+/// temperatures. Instruments that report Planck brightness temperatures differ
+/// from these by roughly `h ν / 2 k`, which is about 1.3 K at the MSU
+/// frequencies.
+///
+/// By default, the outputs are in the same convention as the [`Emission`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TbConvention {
+    /// Rayleigh-Jeans brightness temperature, which is linear in radiance (see
+    /// [`rayleigh_jeans_radiance`]). This matches the original Fortran.
+    RayleighJeans,
+    /// Planck brightness temperature, the temperature of a blackbody with the
+    /// same radiance at the channel frequency (see [`planck_radiance`]).
+    Planck,
+}
+
+impl TbConvention {
+    /// A short name for the convention, as used by the Python interface.
+    pub fn name(self) -> &'static str {
+        match self {
+            TbConvention::RayleighJeans => "rayleigh_jeans",
+            TbConvention::Planck => "planck",
+        }
+    }
+}
+
+/// How the emission of each atmospheric layer is integrated along the path.
+///
+/// Both schemes use the same layer opacities, from the mean of the absorption
+/// coefficients at the two levels, and they agree for optically thin layers.
+/// They differ by how the temperature varies within a layer, which matters
+/// for layers that are not thin. The scheme applies to all the outputs of
+/// [`RtmInputs`], including the contributions, the outputs on diagnostic
+/// levels, the effective temperatures, the surface temperature Jacobian, and
+/// [`LowerAtmosphere`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IntegrationScheme {
+    /// Each layer emits at the mean of the temperatures of its two levels,
+    /// with an emissivity of `1 - exp(-τ)` for its optical depth `τ`. This
+    /// matches the original Fortran.
+    #[default]
+    Trapezoidal,
+    /// The temperature is linear in optical depth across each layer, and the
+    /// emission is integrated exactly against the attenuation within the
+    /// layer. An optically thick layer emits at the temperature of the side
+    /// it's seen from, rather than at its mean temperature. Both schemes are
+    /// second order in the layer thickness, but their errors for coarse
+    /// levels differ.
+    LinearInOpticalDepth,
+}
+
+impl IntegrationScheme {
+    /// A short name for the scheme, as used by the Python interface.
+    pub fn name(self) -> &'static str {
+        match self {
+            IntegrationScheme::Trapezoidal => "trapezoidal",
+            IntegrationScheme::LinearInOpticalDepth => "linear_in_optical_depth",
+        }
+    }
+}
+
+/// The temperature that the layer emission is summed relative to.
+///
+/// The brightness temperatures are `(1 - tran) * t_ref` plus the sum over the
+/// layers of `t_avg - t_ref` times the layer emissivity and the transmissivity
+/// from the layer to the end of the path. Those weights sum to exactly `1 -
+/// tran`, so the reference `t_ref` cancels, and the outputs only depend on it
+/// through rounding, even with a strong surface inversion where the surface
+/// and the first level above it differ by several K. It is only there to keep
+/// the summed terms small. With the sums in double precision, the references
+/// agree to better than 1e-9 K for an 8 K inversion, so the choice is mostly
+/// for comparing with other implementations.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LayerSumReference {
+    /// The temperature of the first level above the surface. This matches the
+    /// original Fortran.
+    #[default]
+    FirstLevel,
+    /// The temperature at the surface (the 2 m air temperature).
+    Surface,
+    /// The mean temperature of the lowest layer, between the surface and the
+    /// first level above it.
+    LowestLayerMean,
+}
+
+impl LayerSumReference {
+    /// A short name for the reference, as used by the Python interface.
+    pub fn name(self) -> &'static str {
+        match self {
+            LayerSumReference::FirstLevel => "first_level",
+            LayerSumReference::Surface => "surface",
+            LayerSumReference::LowestLayerMean => "lowest_layer_mean",
+        }
+    }
+
+    /// The reference temperature in K for the temperatures `t` from the
+    /// surface upward.
+    pub(crate) fn temperature<T: Float>(self, t: &[T]) -> f64 {
+        match self {
+            LayerSumReference::FirstLevel => t[1].into_f64(),
+            LayerSumReference::Surface => t[0].into_f64(),
+            LayerSumReference::LowestLayerMean => 0.5 * (t[0].into_f64() + t[1].into_f64()),
+        }
+    }
+}
+
+/// Where the absorption coefficients of the gases are computed for
+/// [`compute_rtm_batch`].
+///
+/// The GPU is experimental. With [`Gpu`](Self::Gpu) and the `gpu` feature,
+/// [`compute_rtm_batch`] computes the oxygen and water vapor absorption of the
+/// whole batch on the GPU (see the `gpu` module), and the cloud absorption and
+/// the radiative transfer integration on the CPU as usual. Without the
+/// feature, or if there's no GPU adapter, it falls back to the CPU. Everything
+/// else, including [`RtmInputs::run`], always runs on the CPU.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AbsorptionBackend {
+    /// The absorption is computed on the CPU, for each point as it's run.
+    #[default]
+    Cpu,
+    /// The gas absorption is computed on the GPU, for a batch of points at
+    /// once. The outputs agree with the CPU within the tolerance given in
+    /// the `gpu` module, but aren't bit-for-bit the same.
+    Gpu,
+}
+
+impl AbsorptionBackend {
+    /// A short name for the backend.
+    pub fn name(self) -> &'static str {
+        match self {
+            AbsorptionBackend::Cpu => "cpu",
+            AbsorptionBackend::Gpu => "gpu",
+        }
+    }
+}
+
+/// What [`RtmInputs`] does when the surface height is not below the height of
+/// the first level above the surface, which would make the lowest layer empty
+/// or inverted.
+///
+/// This happens when the surface height and the level heights are from
+/// different sources, e.g., a model orography and interpolated geopotential,
+/// or when they're equal, as with a surface at the first level of pressure
+/// level data (see [`RtmInputs::new`]). The heights are compared after the
+/// conversion to geometric height.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SurfaceHeightPolicy {
+    /// Move the surface to `offset` m below the first level, and set
+    /// [`QualityFlags::SURFACE_HEIGHT_ADJUSTED`]. The offset must be positive
+    /// and finite.
+    Adjust {
+        /// Distance in m of the adjusted surface below the first level.
+        offset: f32,
+    },
+    /// Reject the inputs with [`RtmError::InvalidSurface`].
+    Error,
+}
+
+impl SurfaceHeightPolicy {
+    /// The offset in m of the default policy.
+    pub const DEFAULT_OFFSET: f32 = 0.1;
+}
+
+impl Default for SurfaceHeightPolicy {
+    /// Move the surface to [`DEFAULT_OFFSET`](Self::DEFAULT_OFFSET) below the
+    /// first level, which matches the original Fortran.
+    fn default() -> Self {
+        SurfaceHeightPolicy::Adjust {
+            offset: Self::DEFAULT_OFFSET,
+        }
+    }
 }
 
 /// Inputs for the RTM for a single point. Unlike [`RtmParameters`], these
 /// values may vary over location/time.
-#[derive(Debug)]
-pub struct RtmInputs {
+///
+/// With the `serde` feature, the prepared inputs (after the surface is
+/// inserted into the profile) can be serialized, e.g., to save a problematic
+/// point and reload it later. Deserialized inputs are used as they are, without
+/// repeating the checks in [`new`](Self::new).
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RtmInputs<T: Float = f32> {
     /// Number of atmosphere profile levels.
-    num_levels: NonZeroUsize,
+    pub(crate) num_levels: NonZeroUsize,
     /// Starting index for the surface, aka `ibegin`.
-    surface_index: usize,
+    pub(crate) surface_index: usize,
     /// Pressure profile in hPa. This has length `num_levels+1` since the first
     /// element is for the surface.
-    pressure: Vec<f32>,
+    pub(crate) pressure: Vec<T>,
     /// Temperature profile in K. This has length `num_levels+1` since the first
     /// element is for the surface.
-    temperature: Vec<f32>,
+    pub(crate) temperature: Vec<T>,
     /// Water vapor pressure profile in hPa. This has length `num_levels+1` since the first
     /// element is for the surface.
-    vapor_pressure: Vec<f32>,
+    pub(crate) vapor_pressure: Vec<T>,
     /// Liquid water density in g/m³. This has length `num_levels+1` since the
     /// first element is for the surface.
-    rho_l: Vec<f32>,
+    pub(crate) rho_l: Vec<T>,
     /// Geometric height in m. This has length `num_levels+1` since the first
     /// element is for the surface.
-    height: Vec<f32>,
+    pub(crate) height: Vec<T>,
+    /// Radiometric skin temperature of the surface in K, if it is given
+    /// separately from the 2 m air temperature.
+    pub(crate) skin_temperature: Option<T>,
+    /// Geometric height of the observer in m, if it is not at the top of the
+    /// atmosphere.
+    pub(crate) observer_height: Option<T>,
+    /// Quality flags found while preparing the inputs.
+    pub(crate) quality: QualityFlags,
 }
 
 /// Outputs from the RTM for a single point.
 #[derive(Debug)]
-pub struct RtmOutputs {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RtmOutputs<T: Float = f32> {
     /// Atmospheric transmissivity as a function of frequency index.
-    pub tran: SmallVec<[f32; 8]>,
+    pub tran: ChannelVec<T>,
     /// Atmospheric upwelling in K as a function of frequency index.
-    pub tb_up: SmallVec<[f32; 8]>,
+    pub tb_up: ChannelVec<T>,
     /// Atmospheric downwelling in K as a function of frequency index.
-    pub tb_down: SmallVec<[f32; 8]>,
+    pub tb_down: ChannelVec<T>,
+    /// Quality flags for the inputs and outputs.
+    pub quality: QualityFlags,
+}
+
+impl<T: Float> RtmOutputs<T> {
+    /// Create empty outputs with the quality flags from the inputs.
+    fn new(quality: QualityFlags) -> Self {
+        Self {
+            tran: SmallVec::new(),
+            tb_up: SmallVec::new(),
+            tb_down: SmallVec::new(),
+            quality,
+        }
+    }
+
+    /// Check whether the outputs are equal to `other` within a tolerance.
+    ///
+    /// The outputs must have the same number of channels and the same quality
+    /// flags, and each value of `tran`, `tb_up`, and `tb_down` must be within
+    /// either `abs` of the other value, or `rel` times the larger of their
+    /// magnitudes. NaN is never equal to anything, so outputs with a NaN are
+    /// never approximately equal.
+    pub fn approx_eq(&self, other: &Self, rel: T, abs: T) -> bool {
+        let close = |a: &ChannelVec<T>, b: &ChannelVec<T>| {
+            a.len() == b.len()
+                && a.iter().zip(b).all(|(&a, &b)| {
+                    let difference = (a - b).abs();
+                    difference <= abs || difference <= rel * a.abs().max(b.abs())
+                })
+        };
+        self.quality == other.quality
+            && close(&self.tran, &other.tran)
+            && close(&self.tb_up, &other.tb_up)
+            && close(&self.tb_down, &other.tb_down)
+    }
+
+    /// Whether the outputs are physically valid: each `tran` is finite and
+    /// from 0 to 1, and each `tb_up` and `tb_down` is finite and not negative.
+    pub fn is_valid(&self) -> bool {
+        self.tran
+            .iter()
+            .all(|&tran| tran >= T::zero() && tran <= T::one())
+            && self
+                .tb_up
+                .iter()
+                .chain(&self.tb_down)
+                .all(|&tb| tb >= T::zero() && tb.is_finite())
+    }
+
+    /// Flag any non-finite outputs.
+    fn check_finite(&mut self) {
+        let not_finite = self
+            .tran
+            .iter()
+            .chain(&self.tb_up)
+            .chain(&self.tb_down)
+            .any(|value| !value.is_finite());
+        self.quality
+            .set_if(QualityFlags::OUTPUT_NOT_FINITE, not_finite);
+    }
 }
 
-impl RtmParameters {
-    pub fn new(freqs: &[f32], eia: &[f32]) -> Result<Self, RtmError> {
+/// Outputs from the RTM on diagnostic pressure levels for a single point.
+///
+/// Each has one row per channel, and each row has one value per diagnostic
+/// level. Levels that are below the surface are NaN.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LevelOutputs<T: Float = f32> {
+    /// Atmospheric transmissivity from the level to space.
+    pub tran_to_space: Vec<Vec<T>>,
+    /// Atmospheric upwelling in K from the atmosphere above the level.
+    pub tb_up_above: Vec<Vec<T>>,
+}
+
+/// Effective temperatures of the atmosphere for a single point, for
+/// vicarious calibration.
+///
+/// These are the brightness temperatures divided by the emissivity of the
+/// atmosphere, `1 - tran`. See
+/// [`run_with_effective_temperatures`](RtmInputs::run_with_effective_temperatures).
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EffectiveTemperatures<T: Float = f32> {
+    /// Effective temperature of the upwelling in K, `tb_up / (1 - tran)`, as a
+    /// function of frequency index.
+    pub up: ChannelVec<T>,
+    /// Effective temperature of the downwelling in K, `tb_down / (1 - tran)`,
+    /// as a function of frequency index.
+    pub down: ChannelVec<T>,
+}
+
+/// Brightness temperatures at the top of the atmosphere for a single point,
+/// from [`run_toa`](RtmInputs::run_toa).
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ToaOutputs<T: Float = f32> {
+    /// Brightness temperature in K at the top of the atmosphere as a function
+    /// of frequency index, for the polarization of each channel.
+    pub tb_toa: ChannelVec<T>,
+    /// Brightness temperature in K at the top of the atmosphere for vertical
+    /// polarization at the surface, as a function of frequency index.
+    pub tb_toa_v: ChannelVec<T>,
+    /// Brightness temperature in K at the top of the atmosphere for horizontal
+    /// polarization at the surface, as a function of frequency index.
+    pub tb_toa_h: ChannelVec<T>,
+    /// Surface emissivity for the polarization of each channel as a function
+    /// of frequency index.
+    pub emissivity: ChannelVec<T>,
+    /// Derivative of `tb_toa` with respect to `emissivity` in K, `tran (T_s -
+    /// sky)`, where `sky` is the reflected sky brightness temperature, as a
+    /// function of frequency index.
+    pub dtb_demis: ChannelVec<T>,
+    /// Derivative of `tb_toa` with respect to the surface emission
+    /// temperature, `tran emissivity`, as a function of frequency index. The
+    /// emissivity is held fixed, so for the ocean this leaves out the change of
+    /// the emissivity with the SST.
+    pub dtb_dtskin: ChannelVec<T>,
+    /// Outputs for the atmosphere alone, as from [`run`](RtmInputs::run).
+    pub atmosphere: RtmOutputs<T>,
+}
+
+impl<T: Float> RtmParameters<T> {
+    /// Create new RTM parameters from the channel frequencies (GHz) and Earth
+    /// incidence angles (degrees).
+    ///
+    /// The two slices must be the same non-zero length. Each incidence angle
+    /// must be within 0° to 90°, inclusive. Exactly 90° is permitted: the
+    /// slant path factor in the RTM is large there but remains finite.
+    pub fn new(freqs: &[T], eia: &[T]) -> Result<Self, RtmError> {
         if freqs.len() != eia.len() || freqs.is_empty() {
             return Err(RtmError::InconsistentInputs);
         }
+        check_incidence(eia)?;
         Ok(Self {
             frequency: SmallVec::from_slice(freqs),
             incidence: SmallVec::from_slice(eia),
+            emission: Emission::default(),
+            tb_convention: None,
+            reflection: Reflection::default(),
+            ocean_model: OceanModel::default(),
+            passbands: None,
+            integration_scheme: IntegrationScheme::default(),
+            layer_sum_reference: LayerSumReference::default(),
+            absorption_scale: AbsorptionScale::default(),
+            absorption_backend: AbsorptionBackend::default(),
+            items_per_thread: threads::default_items_per_thread(),
+            work_chunk_size: threads::default_work_chunk_size(),
+        })
+    }
+
+    /// Create new RTM parameters for a sweep of frequencies in GHz, all at the
+    /// same Earth incidence angle in degrees.
+    ///
+    /// The frequencies go from `start` up to but not including `stop` in
+    /// increments of `step`, as with `numpy.arange`: there are `ceil((stop -
+    /// start) / step)` of them, and the one at index `i` is `start + i *
+    /// ((start + step) - start)`, computed in double precision. As with
+    /// `numpy.arange`, rounding can add a frequency at the end, just past
+    /// `stop` (for instance, 1 to 1.3 in steps of 0.1 gives 1, 1.1, 1.2, and
+    /// 1.3000000000000003), so a `stop` between two steps is more predictable.
+    /// The step can be negative for a decreasing sweep. The range must be
+    /// finite and have at least one frequency (and fewer than 2³²), or else
+    /// [`RtmError::InvalidFrequencyRange`] is returned. In single precision,
+    /// the range is for the `f32` values of the arguments, so it can have a
+    /// different number of frequencies than with the same `f64` literals.
+    pub fn frequency_range(start: T, stop: T, step: T, eia: T) -> Result<Self, RtmError> {
+        let (start, stop, step) = (start.into_f64(), stop.into_f64(), step.into_f64());
+        let len = ((stop - start) / step).ceil();
+        // Written so that NaN is also rejected, as is a zero step
+        if !((1.0..=f64::from(u32::MAX)).contains(&len) && start.is_finite() && stop.is_finite()) {
+            return Err(RtmError::InvalidFrequencyRange { start, stop, step });
+        }
+        let delta = (start + step) - start;
+        let freqs: ChannelVec<T> = (0..len as usize)
+            .map(|i| T::from_f64_lossy(start + i as f64 * delta))
+            .collect();
+        Self::new(&freqs, &vec![eia; freqs.len()])
+    }
+
+    /// Number of channels.
+    pub fn len(&self) -> usize {
+        self.frequency.len()
+    }
+
+    /// Whether there are no channels. This is always false, since
+    /// [`new`](Self::new) requires at least one.
+    pub fn is_empty(&self) -> bool {
+        self.frequency.is_empty()
+    }
+
+    /// Microwave frequencies in GHz, one for each channel.
+    pub fn frequencies(&self) -> &[T] {
+        &self.frequency
+    }
+
+    /// Earth incidence angles in degrees, one for each channel.
+    pub fn incidence_angles(&self) -> &[T] {
+        &self.incidence
+    }
+
+    /// Parameters for the channels `channels` of a cross-track sounder at the
+    /// scan position `position`, from the tables of [`scan_geometry`].
+    ///
+    /// The channels are numbered from 1 and the scan positions from 0. Returns
+    /// [`RtmError::InvalidScanGeometry`] if a channel or the position doesn't
+    /// exist for the sensor, or [`RtmError::InconsistentInputs`] if there are
+    /// no channels.
+    pub fn for_scan_position(
+        sensor: &ScanSensor,
+        channels: &[usize],
+        position: usize,
+    ) -> Result<Self, RtmError> {
+        let freqs = channels
+            .iter()
+            .map(|&channel| sensor.frequency(channel).map(T::lit))
+            .collect::<Result<ChannelVec<T>, _>>()?;
+        let eia = T::lit(sensor.incidence_angle(position)?);
+        Self::new(&freqs, &vec![eia; freqs.len()])
+    }
+
+    /// The absorption models that are extrapolated for at least one channel,
+    /// since its frequency is above the validated range in
+    /// [`FREQUENCY_LIMITS`], as pairs of (absorber, frequency limit in GHz).
+    pub fn extrapolated_models(&self) -> impl Iterator<Item = (&'static str, f64)> + '_ {
+        extrapolated(&FREQUENCY_LIMITS, &self.frequency)
+    }
+
+    /// Shift the frequency of each channel by `offset` in MHz, for instance to
+    /// correct for the drift of a channel's passband centroid. There must be one
+    /// offset for each channel, or else [`RtmError::InconsistentInputs`] is
+    /// returned.
+    ///
+    /// The offsets are added to the frequencies, so they are used for the
+    /// absorption models and the other frequency-dependent parts of the RTM,
+    /// and [`frequencies`](Self::frequencies) returns the shifted values. The
+    /// nodes of any [`passbands`](Self::passbands) are shifted too.
+    pub fn with_frequency_offset(mut self, offset: &[T]) -> Result<Self, RtmError> {
+        if offset.len() != self.frequency.len() {
+            return Err(RtmError::InconsistentInputs);
+        }
+        for (freq, &offset) in self.frequency.iter_mut().zip(offset) {
+            *freq = *freq + offset / T::lit(1000.);
+        }
+        for (channel, &offset) in self.passbands.iter_mut().flatten().zip(offset) {
+            channel.shift(offset / T::lit(1000.));
+        }
+        Ok(self)
+    }
+
+    /// The reflection model of the surface in use.
+    pub fn reflection(&self) -> Reflection {
+        self.reflection
+    }
+
+    /// Use a different reflection model of the surface than the default
+    /// (specular). This only affects [`RtmInputs::run_toa`] and the other
+    /// methods that add the surface.
+    pub fn with_reflection(mut self, reflection: Reflection) -> Self {
+        self.reflection = reflection;
+        self
+    }
+
+    /// The model of the emissivity of a wind-roughened ocean in use.
+    pub fn ocean_model(&self) -> OceanModel {
+        self.ocean_model
+    }
+
+    /// Use a different model of the emissivity of a wind-roughened ocean than
+    /// the default (facets and foam). Like the reflection, this only affects
+    /// [`RtmInputs::run_toa`] and the other methods that add the surface.
+    pub fn with_ocean_model(mut self, ocean_model: OceanModel) -> Self {
+        self.ocean_model = ocean_model;
+        self
+    }
+
+    /// The scheme for integrating the layer emission in use.
+    pub fn integration_scheme(&self) -> IntegrationScheme {
+        self.integration_scheme
+    }
+
+    /// Use a different scheme for integrating the layer emission than the
+    /// default (trapezoidal).
+    pub fn with_integration_scheme(mut self, integration_scheme: IntegrationScheme) -> Self {
+        self.integration_scheme = integration_scheme;
+        self
+    }
+
+    /// The temperature that the layer emission is summed relative to in use.
+    pub fn layer_sum_reference(&self) -> LayerSumReference {
+        self.layer_sum_reference
+    }
+
+    /// Sum the layer emission relative to a different temperature than the
+    /// default (the first level above the surface). This only changes the
+    /// outputs by rounding; see [`LayerSumReference`].
+    pub fn with_layer_sum_reference(mut self, layer_sum_reference: LayerSumReference) -> Self {
+        self.layer_sum_reference = layer_sum_reference;
+        self
+    }
+
+    /// The factors on the absorption of each absorber in use.
+    pub fn absorption_scale(&self) -> AbsorptionScale {
+        self.absorption_scale
+    }
+
+    /// Scale the absorption of each absorber of the builtin models by the
+    /// factors of `absorption_scale`, rather than using the models as
+    /// published. This applies to all of the outputs, including
+    /// [`RtmInputs::zenith_optical_depths`].
+    pub fn with_absorption_scale(mut self, absorption_scale: AbsorptionScale) -> Self {
+        self.absorption_scale = absorption_scale;
+        self
+    }
+
+    /// Where the gas absorption is computed for a batch.
+    pub fn absorption_backend(&self) -> AbsorptionBackend {
+        self.absorption_backend
+    }
+
+    /// Compute the gas absorption for [`compute_rtm_batch`] with a different
+    /// backend than the default (the CPU). See [`AbsorptionBackend`].
+    pub fn with_absorption_backend(mut self, absorption_backend: AbsorptionBackend) -> Self {
+        self.absorption_backend = absorption_backend;
+        self
+    }
+
+    /// The target number of (point, channel) pairs for each worker thread of
+    /// a batch.
+    pub fn items_per_thread(&self) -> NonZeroUsize {
+        self.items_per_thread
+    }
+
+    /// Choose the number of worker threads of a batch, when it isn't given,
+    /// for a different target number of (point, channel) pairs for each
+    /// thread than the default ([`DEFAULT_ITEMS_PER_THREAD`]). See
+    /// [`adaptive_num_threads`].
+    ///
+    /// A larger target uses fewer threads for small batches. Batches that are
+    /// large enough to use every thread are unaffected.
+    pub fn with_items_per_thread(mut self, items_per_thread: NonZeroUsize) -> Self {
+        self.items_per_thread = items_per_thread;
+        self
+    }
+
+    /// The most points that a worker thread of a batch runs as one chunk.
+    pub fn work_chunk_size(&self) -> NonZeroUsize {
+        self.work_chunk_size
+    }
+
+    /// Split the points of a batch between the worker threads in chunks of a
+    /// different most number of points than the default
+    /// ([`DEFAULT_WORK_CHUNK_SIZE`], which explains the chunks).
+    ///
+    /// A smaller chunk balances the load better when the cost of the points
+    /// varies, and [`NonZeroUsize::MAX`] leaves the splitting to Rayon. The
+    /// outputs are the same for any chunk size.
+    pub fn with_work_chunk_size(mut self, work_chunk_size: NonZeroUsize) -> Self {
+        self.work_chunk_size = work_chunk_size;
+        self
+    }
+
+    /// The emission convention in use.
+    pub fn emission(&self) -> Emission {
+        self.emission
+    }
+
+    /// Use a different emission convention than the default (Rayleigh-Jeans).
+    pub fn with_emission(mut self, emission: Emission) -> Self {
+        self.emission = emission;
+        self
+    }
+
+    /// The convention of the output brightness temperatures. Unless set with
+    /// [`with_tb_convention`](Self::with_tb_convention), this is the same as
+    /// the emission convention.
+    pub fn tb_convention(&self) -> TbConvention {
+        self.tb_convention.unwrap_or(match self.emission {
+            Emission::RayleighJeans => TbConvention::RayleighJeans,
+            Emission::Planck => TbConvention::Planck,
         })
     }
+
+    /// Output brightness temperatures in the given convention, regardless of
+    /// the emission convention.
+    pub fn with_tb_convention(mut self, tb_convention: TbConvention) -> Self {
+        self.tb_convention = Some(tb_convention);
+        self
+    }
+
+    /// Convert a radiative transfer result for a channel at `freq` to an
+    /// output brightness temperature.
+    fn output_tb(&self, freq: T, value: T) -> T {
+        self.emission
+            .brightness_temperature(self.tb_convention(), freq, value)
+    }
+
+    /// The derivative of [`output_tb`](Self::output_tb) with respect to the
+    /// radiative transfer result, given the output brightness temperature.
+    fn output_tb_derivative(&self, freq: T, tb: T) -> T {
+        self.emission
+            .brightness_temperature_derivative(self.tb_convention(), freq, tb)
+    }
+}
+
+/// Lists the channels as "{frequency} GHz at {incidence}°", separated by
+/// commas.
+impl<T: Float> fmt::Display for RtmParameters<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, (freq, eia)) in self.frequency.iter().zip(&self.incidence).enumerate() {
+            if index > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{freq} GHz at {eia}°")?;
+        }
+        Ok(())
+    }
 }
 
-impl RtmInputs {
+impl<T: Float> RtmInputs<T> {
+    /// Start building the inputs with named setters. This is less error-prone
+    /// than the many positional arguments of [`new`](Self::new).
+    pub fn builder<'a>() -> RtmInputsBuilder<'a, T> {
+        RtmInputsBuilder::new()
+    }
+
     /// Prepare and convert values.
     ///
-    /// The slices (`levels`, `temperature`, etc) must all be the same length.
+    /// The slices (`levels`, `temperature`, etc) must all be the same length,
+    /// except that `liquid_content` may be empty for clear sky, which is the
+    /// same as no cloud water on every level.
+    ///
+    /// If `top_pressure` (in hPa) is given, the levels with a lower pressure
+    /// than it are discarded, so that the profile is truncated at the top
+    /// similarly to how the levels below the surface are not used.
+    ///
+    /// If `surface_pressure` is `None`, for data on pressure levels without a
+    /// separate surface field, the first level (with the highest pressure) is
+    /// taken as the surface. This is the same as passing that level's pressure:
+    /// the surface values are still prepended, at the same pressure as the
+    /// first level, so `surface_height` and `surface_temperature` should be
+    /// those of the first level, too. Otherwise the lowest layer is a thin
+    /// layer between the two heights.
+    ///
+    /// The profiles here are only the levels of the atmosphere, and the
+    /// surface values are always prepended to them. Some data instead has the
+    /// surface as the first element of each profile; for that, use
+    /// [`RtmInputsBuilder::input_includes_surface`] rather than passing the
+    /// first elements as the surface values here, which would put the surface
+    /// in twice, with a zero-thickness bottom layer.
+    ///
+    /// Whenever the surface height is not below the first
+    /// level above the surface, it's moved 0.1 m below that level with
+    /// [`QualityFlags::SURFACE_HEIGHT_ADJUSTED`]. Use
+    /// [`RtmInputsBuilder::surface_height_policy`] for a different offset or an
+    /// error instead.
+    ///
+    /// # Errors
+    ///
+    /// The inputs are rejected, rather than causing a panic, when:
+    ///
+    /// - the slices are empty or don't all have the same length, other than an
+    ///   empty `liquid_content` ([`RtmError::InconsistentInputs`])
+    /// - the pressure levels are not strictly decreasing, including when any
+    ///   of them is NaN ([`RtmError::UnsortedLevels`])
+    /// - every level is above `top_pressure` ([`RtmError::InconsistentInputs`])
+    /// - the surface pressure is lower than every kept level, or is NaN
+    ///   ([`RtmError::NoSurface`])
+    ///
+    /// Other non-finite or out-of-range values are not errors, but are
+    /// reported in the [`QualityFlags`] of the inputs and outputs.
     #[allow(clippy::too_many_arguments)]
     pub fn new(
-        levels: &[f32],
-        surface_temperature: f32,
-        temperature: &[f32],
-        surface_height: f32,
-        height: &[f32],
-        surface_dewpoint: f32,
-        specific_humidity: &[f32],
-        liquid_content: &[f32],
-        surface_pressure: f32,
+        levels: &[T],
+        surface_temperature: T,
+        temperature: &[T],
+        surface_height: T,
+        height: &[T],
+        surface_dewpoint: T,
+        specific_humidity: &[T],
+        liquid_content: &[T],
+        surface_pressure: Option<T>,
+        top_pressure: Option<T>,
     ) -> Result<Self, RtmError> {
-        #![allow(clippy::excessive_precision)]
-        /// Mean radius of the Earth in meters
-        const R_EARTH: f32 = 6371e3;
-
-        /// Ideal gas constant (J/mol/K)
-        const R: f32 = 8.3144598;
-        /// Mean molar mass of dry air (g/mol)
-        const M_DRY: f32 = 28.9644;
-        /// Mean molar mass of water (g/mol)
-        const M_H2O: f32 = 18.01528;
-        /// Specific gas constant for dry air (J/g/K)
-        const R_DRY: f32 = R / M_DRY;
-        /// Specific gas constant for water vapor (J/g/K)
-        const R_VAPOR: f32 = R / M_H2O;
-
-        /// Coefficient for ratio between molar masses
-        const EPSILON: f32 = M_H2O / M_DRY;
-        /// Scaling factor using EPSILON
-        const EPS_SCALE: f32 = (1. - EPSILON) / EPSILON;
+        Self::prepare(
+            levels,
+            surface_temperature,
+            temperature,
+            surface_height,
+            height,
+            surface_dewpoint,
+            specific_humidity,
+            liquid_content,
+            surface_pressure,
+            top_pressure,
+            SurfaceHeightPolicy::default(),
+        )
+    }
+
+    /// Implementation of [`new`](Self::new), with the `surface_height_policy`
+    /// of the builder.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn prepare(
+        levels: &[T],
+        surface_temperature: T,
+        temperature: &[T],
+        surface_height: T,
+        height: &[T],
+        surface_dewpoint: T,
+        specific_humidity: &[T],
+        liquid_content: &[T],
+        surface_pressure: Option<T>,
+        top_pressure: Option<T>,
+        surface_height_policy: SurfaceHeightPolicy,
+    ) -> Result<Self, RtmError> {
+        let clear_sky = check_profiles(
+            levels,
+            temperature,
+            height,
+            specific_humidity,
+            liquid_content,
+        )?;
+
+        // Discard the levels above the top, if any
+        let num_kept = num_levels_below_top(levels, top_pressure);
+        let levels = &levels[..num_kept];
+        let temperature = &temperature[..num_kept];
+        let height = &height[..num_kept];
+        let specific_humidity = &specific_humidity[..num_kept];
+        let liquid_content = if clear_sky {
+            liquid_content
+        } else {
+            &liquid_content[..num_kept]
+        };
 
         let num_levels: NonZeroUsize = levels
             .len()
             .try_into()
             .or(Err(RtmError::InconsistentInputs))?;
 
+        let surface_pressure = surface_pressure.unwrap_or(levels[0]);
+
         // Find the starting index for the surface (aka `ibegin`). Note this
         // assumes that the levels are sorted in descending order (from high to
         // low pressure).
@@ -120,7 +1127,7 @@ impl RtmInputs {
             .ok_or(RtmError::NoSurface)?;
 
         // Prepend the surface value to these vectors
-        let prepend_with = |level_data: &[f32], zero_value: f32, surface_value: f32| -> Vec<f32> {
+        let prepend_with = |level_data: &[T], zero_value: T, surface_value: T| -> Vec<T> {
             let mut prepended = Vec::with_capacity(num_levels.get() + 1);
             prepended.push(zero_value);
             prepended.extend_from_slice(level_data);
@@ -128,17 +1135,9 @@ impl RtmInputs {
             prepended[surface_index] = surface_value;
             prepended
         };
-        let pressure = prepend_with(levels, 0., surface_pressure);
+        let pressure = prepend_with(levels, T::zero(), surface_pressure);
         let temperature = prepend_with(temperature, surface_temperature, surface_temperature);
-        let mut height = prepend_with(height, surface_height, surface_height);
-
-        // Convert geopotential height to geometric height
-        for z in &mut height {
-            *z *= R_EARTH / (R_EARTH - *z);
-        }
-        if height[surface_index] >= height[surface_index + 1] {
-            height[surface_index] = height[surface_index + 1] - 0.1;
-        }
+        let height = prepend_with(height, surface_height, surface_height);
 
         // Convert specific humidity q to water vapor pressure P_v. The mass mixing
         // ratio w is:
@@ -151,15 +1150,13 @@ impl RtmInputs {
         //
         // For the surface value, convert dewpoint to vapor pressure using the Buck equation.
         let pv = {
-            let w = specific_humidity.iter().map(|q| q / (1. - q));
-
             let mut prepended = Vec::with_capacity(num_levels.get() + 1);
-            prepended.push(buck_vap(surface_dewpoint));
+            prepended.push(dewpoint_to_vapor_pressure(surface_dewpoint));
             prepended.extend(
                 levels
                     .iter()
-                    .zip(w)
-                    .map(|(p, w)| (w * p) / (R_DRY / R_VAPOR + w)),
+                    .zip(specific_humidity)
+                    .map(|(&p, &q)| specific_humidity_to_vapor_pressure(q, p)),
             );
 
             prepended[surface_index] = prepended[0];
@@ -169,13 +1166,128 @@ impl RtmInputs {
         // Specific liquid cloud mixing content
         let q_l = {
             let mut prepended = Vec::with_capacity(num_levels.get() + 1);
-            prepended.push(0.);
-            prepended.extend_from_slice(liquid_content);
+            prepended.push(T::zero());
+            if clear_sky {
+                prepended.resize(num_levels.get() + 1, T::zero());
+            } else {
+                prepended.extend_from_slice(liquid_content);
+            }
 
             prepended[surface_index] = prepended[surface_index + 1];
             prepended
         };
 
+        Self::finish(
+            num_levels,
+            surface_index,
+            pressure,
+            temperature,
+            height,
+            pv,
+            q_l,
+            surface_height_policy,
+        )
+    }
+
+    /// Implementation of [`RtmInputsBuilder::input_includes_surface`], where
+    /// the first element of each profile is the surface, so nothing is
+    /// prepended and the surface index is 0.
+    pub(crate) fn prepare_including_surface(
+        levels: &[T],
+        temperature: &[T],
+        height: &[T],
+        specific_humidity: &[T],
+        liquid_content: &[T],
+        top_pressure: Option<T>,
+        surface_height_policy: SurfaceHeightPolicy,
+    ) -> Result<Self, RtmError> {
+        let clear_sky = check_profiles(
+            levels,
+            temperature,
+            height,
+            specific_humidity,
+            liquid_content,
+        )?;
+
+        // The surface and at least one level above it are needed
+        let num_kept = num_levels_below_top(levels, top_pressure);
+        let num_levels: NonZeroUsize = num_kept
+            .saturating_sub(1)
+            .try_into()
+            .or(Err(RtmError::InconsistentInputs))?;
+
+        let pressure = levels[..num_kept].to_vec();
+        let pv = pressure
+            .iter()
+            .zip(specific_humidity)
+            .map(|(&p, &q)| specific_humidity_to_vapor_pressure(q, p))
+            .collect();
+        let q_l = if clear_sky {
+            vec![T::zero(); num_kept]
+        } else {
+            liquid_content[..num_kept].to_vec()
+        };
+
+        Self::finish(
+            num_levels,
+            0,
+            pressure,
+            temperature[..num_kept].to_vec(),
+            height[..num_kept].to_vec(),
+            pv,
+            q_l,
+            surface_height_policy,
+        )
+    }
+
+    /// The rest of the preparation, once the profiles have the surface at
+    /// `surface_index`: the conversion to geometric height and liquid water
+    /// density, the surface height check, and the quality flags.
+    ///
+    /// `height` is the geopotential height, `pv` is the vapor pressure, and
+    /// `q_l` is the liquid content in kg/kg.
+    #[allow(clippy::too_many_arguments)]
+    fn finish(
+        num_levels: NonZeroUsize,
+        surface_index: usize,
+        pressure: Vec<T>,
+        temperature: Vec<T>,
+        mut height: Vec<T>,
+        pv: Vec<T>,
+        q_l: Vec<T>,
+        surface_height_policy: SurfaceHeightPolicy,
+    ) -> Result<Self, RtmError> {
+        let c = T::lit;
+
+        if let SurfaceHeightPolicy::Adjust { offset } = surface_height_policy {
+            if !(offset.is_finite() && offset > 0.) {
+                return Err(RtmError::InvalidSurface(format!(
+                    "surface height offset {offset} m is not positive and finite"
+                )));
+            }
+        }
+
+        // Convert geopotential height to geometric height
+        for z in &mut height {
+            *z = *z * (c(EARTH_RADIUS) / (c(EARTH_RADIUS) - *z));
+        }
+        let mut quality = QualityFlags::empty();
+        if height[surface_index] >= height[surface_index + 1] {
+            match surface_height_policy {
+                SurfaceHeightPolicy::Adjust { offset } => {
+                    height[surface_index] = height[surface_index + 1] - c(offset);
+                    quality |= QualityFlags::SURFACE_HEIGHT_ADJUSTED;
+                }
+                SurfaceHeightPolicy::Error => {
+                    return Err(RtmError::InvalidSurface(format!(
+                        "surface height {} m is not below the first level above it at {} m",
+                        height[surface_index].into_f64(),
+                        height[surface_index + 1].into_f64()
+                    )));
+                }
+            }
+        }
+
         // Convert water mass mixing ratio to specific humidity
         // (https://earthscience.stackexchange.com/a/5077)
         //
@@ -184,11 +1296,10 @@ impl RtmInputs {
         // directly from dewpoint to vapor pressure) and also the `ibegin` above
         // modified the profile.
         let q_h2o = pressure.iter().zip(&pv).map(|(&p, &pv)| {
-            if p > 0. {
-                let w = (pv * R_DRY) / (R_VAPOR * (p - pv));
-                w / (w + 1.)
+            if p > T::zero() {
+                vapor_pressure_to_specific_humidity(pv, p)
             } else {
-                0.
+                T::zero()
             }
         });
 
@@ -198,15 +1309,40 @@ impl RtmInputs {
         // See here, section 4:
         // https://www.nwpsaf.eu/site/download/documentation/rtm/docs_rttov12/rttov_gas_cloud_aerosol_units.pdf
         // gas constant for humid air (J/gK)
-        let r_moist = q_h2o.map(|q_h2o| R_DRY * (1. + EPS_SCALE * q_h2o));
+        let r_moist = q_h2o.map(|q_h2o| c(R_DRY) * (c(1.) + c(EPS_SCALE) * q_h2o));
         let rho_l: Vec<_> = q_l
             .iter()
             .zip(&pressure)
             .zip(&temperature)
             .zip(r_moist)
-            .map(|(((q_l, p), t), r_moist)| q_l * (1e2 * p) / (r_moist * t))
+            .map(|(((&q_l, &p), &t), r_moist)| q_l * (c(1e2) * p) / (r_moist * t))
             .collect();
 
+        // Check the values that are actually used by the RTM
+        let used = surface_index..;
+        quality.set_if(
+            QualityFlags::INPUT_NOT_FINITE,
+            [&pressure, &temperature, &height, &pv, &rho_l]
+                .iter()
+                .flat_map(|values| &values[used.clone()])
+                .any(|value| !value.is_finite()),
+        );
+        quality.set_if(
+            QualityFlags::TEMPERATURE_OUT_OF_RANGE,
+            temperature[used.clone()]
+                .iter()
+                .any(|t| !QualityFlags::TEMPERATURE_RANGE.contains(&t.into_f64())),
+        );
+        quality.set_if(
+            QualityFlags::EXTRAPOLATED_DIELECTRIC,
+            temperature[used.clone()]
+                .iter()
+                .zip(&rho_l[used])
+                .any(|(t, &rho_l)| {
+                    rho_l > c(1.0e-7) && !QualityFlags::DIELECTRIC_RANGE.contains(&t.into_f64())
+                }),
+        );
+
         Ok(Self {
             num_levels,
             surface_index,
@@ -215,88 +1351,1011 @@ impl RtmInputs {
             height,
             vapor_pressure: pv,
             rho_l,
+            skin_temperature: None,
+            observer_height: None,
+            quality,
         })
     }
 
+    /// Use a radiometric skin temperature in K for the surface emission.
+    ///
+    /// The 2 m air temperature (`surface_temperature` in [`new`](Self::new))
+    /// is the temperature of the lowest level of the atmosphere, and is still
+    /// used as such. The skin temperature is the temperature of the surface
+    /// itself, which is what emits in the surface term of the top-of-atmosphere
+    /// TB. The two can differ by several K (e.g., over land during the day, or
+    /// over the ocean in a cold air outbreak), and using the air temperature in
+    /// place of the skin temperature biases the window-channel TBs.
+    ///
+    /// The atmospheric outputs ([`run`](Self::run), etc) do not depend on it.
+    pub fn with_skin_temperature(mut self, skin_temperature: T) -> Self {
+        self.quality.set_if(
+            QualityFlags::INPUT_NOT_FINITE,
+            !skin_temperature.is_finite(),
+        );
+        self.quality.set_if(
+            QualityFlags::TEMPERATURE_OUT_OF_RANGE,
+            !QualityFlags::TEMPERATURE_RANGE.contains(&skin_temperature.into_f64()),
+        );
+        self.skin_temperature = Some(skin_temperature);
+        self
+    }
+
+    /// The temperature in K of the surface emission: the skin temperature if
+    /// it was given with [`with_skin_temperature`](Self::with_skin_temperature),
+    /// otherwise the 2 m air temperature.
+    pub fn surface_emission_temperature(&self) -> T {
+        self.skin_temperature
+            .unwrap_or(self.temperature[self.surface_index])
+    }
+
+    /// The quality flags of the inputs, with
+    /// [`QualityFlags::EXTRAPOLATED_FREQUENCY`] set if a channel of
+    /// `parameters` is above one of the `limits` of the absorption model. The
+    /// liquid cloud limit only counts if there's cloud water.
+    pub(crate) fn quality_with(
+        &self,
+        parameters: &RtmParameters<T>,
+        limits: &[(&str, f64)],
+    ) -> QualityFlags {
+        let cloudy = self.rho_l[self.surface_index..]
+            .iter()
+            .any(|&rho_l| rho_l > T::zero());
+        let mut quality = self.quality;
+        quality.set_if(
+            QualityFlags::EXTRAPOLATED_FREQUENCY,
+            extrapolated(limits, &parameters.frequency)
+                .any(|(absorber, _)| cloudy || absorber != "liquid_cloud"),
+        );
+        quality
+    }
+
+    /// Clamp the surface vapor pressure so that it does not exceed saturation
+    /// at the surface temperature, [`buck_vap`] of `surface_temperature`.
+    ///
+    /// The surface vapor pressure is computed from `surface_dewpoint` in
+    /// [`new`](Self::new), and a dewpoint above the air temperature (a common
+    /// inconsistency at the surface in reanalyses) makes the surface
+    /// supersaturated, which overestimates the absorption in the lowest layer.
+    /// When the clamp changes the vapor pressure, it is logged and
+    /// [`QualityFlags::SURFACE_VAPOR_CLAMPED`] is set. The liquid water density
+    /// at the surface is updated for the change in the moist gas constant.
+    pub fn with_surface_saturation_clamp(mut self) -> Self {
+        let c = T::lit;
+        let i = self.surface_index;
+        let pv = self.vapor_pressure[i];
+        let saturation = buck_vap(self.temperature[i]);
+        if pv > saturation {
+            log::debug!(
+                "clamping surface vapor pressure of {} hPa to saturation of {} hPa",
+                pv.into_f64(),
+                saturation.into_f64()
+            );
+            // Gas constant for humid air, as in `new`
+            let p = self.pressure[i];
+            let r_moist =
+                |pv| c(R_DRY) * (c(1.) + c(EPS_SCALE) * vapor_pressure_to_specific_humidity(pv, p));
+            self.rho_l[i] = self.rho_l[i] * r_moist(pv) / r_moist(saturation);
+            self.vapor_pressure[0] = saturation;
+            self.vapor_pressure[i] = saturation;
+            self.quality |= QualityFlags::SURFACE_VAPOR_CLAMPED;
+        }
+        self
+    }
+
+    /// Put the observer at a geometric height in m, for an airborne or
+    /// mountaintop sensor instead of one at the top of the atmosphere.
+    ///
+    /// The upwelling integration is truncated at the profile level nearest to
+    /// the observer (but at least the first level above the surface), so that
+    /// `tran` and `tb_up` from [`run`](Self::run) and
+    /// [`run_with_levels`](Self::run_with_levels) are for the path between the
+    /// surface and the observer. `tb_down` is still the downwelling at the
+    /// surface from the whole atmosphere, including the part above the
+    /// observer, since that is what the surface reflects up into the
+    /// instrument. An observer at or above the top of the profile gives the
+    /// same outputs as no observer.
+    ///
+    /// The diagnostic level outputs and the [`contributions`](Self::contributions)
+    /// are still for an observer at the top of the atmosphere.
+    pub fn with_observer_height(mut self, observer_height: T) -> Self {
+        self.quality
+            .set_if(QualityFlags::INPUT_NOT_FINITE, !observer_height.is_finite());
+        self.observer_height = Some(observer_height);
+        self
+    }
+
+    /// Number of levels above the surface that are integrated by the RTM.
+    pub fn num_levels_integrated(&self) -> usize {
+        self.num_levels.get() - self.surface_index
+    }
+
     /// Apply the RTM on the inputs for the given parameters.
-    pub fn run(&self, parameters: &RtmParameters) -> RtmOutputs {
-        let mut tran = SmallVec::new();
-        let mut tb_up = SmallVec::new();
-        let mut tb_down = SmallVec::new();
-
-        // for (&freq, &inc) in parameters.frequency.iter().zip(&parameters.incidence) {
-        //     // Build up total absorption coefficient profile
-        //     let absorption_profile: SmallVec<[f32; 64]> = (self.surface_index
-        //         ..self.num_levels.get() + 1)
-        //         .map(|level_index| {
-        //             layer_absorption(
-        //                 self.pressure[level_index],
-        //                 self.temperature[level_index],
-        //                 self.vapor_pressure[level_index],
-        //                 self.rho_l[level_index],
-        //                 freq,
-        //             )
-        //         })
-        //         .collect();
-
-        //     let results = atm_tran(
-        //         inc,
-        //         &self.temperature[self.surface_index..],
-        //         &self.height[self.surface_index..],
-        //         &absorption_profile,
-        //     );
-
-
-        let freq: f32 = parameters.frequency[0];
-        // info!("Using fixed frequency: {} GHz", freq);
-        let absorption_profile: SmallVec<[f32; 64]> = (self.surface_index
-                ..self.num_levels.get() + 1)
-                .map(|level_index| {
-                    layer_absorption(
-                        self.pressure[level_index],
-                        self.temperature[level_index],
-                        self.vapor_pressure[level_index],
-                        self.rho_l[level_index],
-                        freq,
-                    )
-                })
+    pub fn run(&self, parameters: &RtmParameters<T>) -> RtmOutputs<T> {
+        self.run_with_absorption_model(parameters, &parameters.absorption_scale)
+    }
+
+    /// Apply the RTM on the inputs for the given parameters, and combine the
+    /// atmosphere with the emission and reflection of `surface` to give the
+    /// brightness temperatures at the top of the atmosphere.
+    ///
+    /// `polarization` is the polarization of each channel at the surface. The
+    /// surface is at [`surface_emission_temperature`](Self::surface_emission_temperature),
+    /// and the brightness temperatures are combined as in
+    /// [`toa_brightness_temperature`], which is exact in the Rayleigh-Jeans
+    /// convention. The downwelling is reflected as set by
+    /// [`RtmParameters::with_reflection`], and the emissivity of a
+    /// wind-roughened ocean is from [`RtmParameters::with_ocean_model`].
+    ///
+    /// Returns [`RtmError::InconsistentInputs`] if `polarization` or the
+    /// emissivities in `surface` do not have one value per channel, or another
+    /// error if `surface` is not valid (see [`Surface::check`]).
+    pub fn run_toa(
+        &self,
+        parameters: &RtmParameters<T>,
+        polarization: &[Polarization],
+        surface: &Surface<'_, T>,
+    ) -> Result<ToaOutputs<T>, RtmError> {
+        self.run_toa_rotated(parameters, polarization, T::zero(), surface)
+    }
+
+    /// Like [`run_toa`](Self::run_toa), for a feedhorn whose polarization is
+    /// rotated by `polarization_angle` in degrees relative to the plane of
+    /// incidence.
+    ///
+    /// The atmosphere is unpolarized, so the brightness temperatures at the top
+    /// of the atmosphere are computed for vertical and horizontal polarization
+    /// at the surface and then mixed as in [`Polarization::mix`]. For a
+    /// cross-track scanner, the angle is the scan angle from nadir.
+    pub fn run_toa_rotated(
+        &self,
+        parameters: &RtmParameters<T>,
+        polarization: &[Polarization],
+        polarization_angle: T,
+        surface: &Surface<'_, T>,
+    ) -> Result<ToaOutputs<T>, RtmError> {
+        if polarization.len() != parameters.len() {
+            return Err(RtmError::InconsistentInputs);
+        }
+        surface.check(parameters.len())?;
+
+        // The surface emission and reflection are combined with the atmosphere
+        // at each node of a passband, and then integrated
+        let Some(nodes) = parameters.passband_nodes() else {
+            return Ok(self.toa_at_nodes(
+                parameters,
+                &parameters.node_channels(),
+                polarization,
+                polarization_angle,
+                surface,
+            ));
+        };
+        let node_outputs = self.toa_at_nodes(
+            &nodes,
+            &parameters.node_channels(),
+            polarization,
+            polarization_angle,
+            surface,
+        );
+        Ok(ToaOutputs {
+            tb_toa: parameters.integrate_channels(&node_outputs.tb_toa),
+            tb_toa_v: parameters.integrate_channels(&node_outputs.tb_toa_v),
+            tb_toa_h: parameters.integrate_channels(&node_outputs.tb_toa_h),
+            emissivity: parameters.integrate_channels(&node_outputs.emissivity),
+            dtb_demis: parameters.integrate_channels(&node_outputs.dtb_demis),
+            dtb_dtskin: parameters.integrate_channels(&node_outputs.dtb_dtskin),
+            atmosphere: parameters.integrate_outputs(&node_outputs.atmosphere),
+        })
+    }
+
+    /// The outputs of [`run_toa_rotated`](Self::run_toa_rotated) for
+    /// monochromatic `nodes`, where `channels` is the index of the channel of
+    /// each node in `polarization` and `surface`.
+    fn toa_at_nodes(
+        &self,
+        nodes: &RtmParameters<T>,
+        channels: &[usize],
+        polarization: &[Polarization],
+        polarization_angle: T,
+        surface: &Surface<'_, T>,
+    ) -> ToaOutputs<T> {
+        let (atmosphere, sky) = self.run_with_sky(nodes);
+        let t_surface = self.surface_emission_temperature();
+        let mut outputs = ToaOutputs {
+            tb_toa: SmallVec::new(),
+            tb_toa_v: SmallVec::new(),
+            tb_toa_h: SmallVec::new(),
+            emissivity: SmallVec::new(),
+            dtb_demis: SmallVec::new(),
+            dtb_dtskin: SmallVec::new(),
+            atmosphere,
+        };
+        let node_channels = nodes
+            .frequencies()
+            .iter()
+            .zip(nodes.incidence_angles())
+            .zip(channels);
+        for (node, ((&freq, &inc), &channel)) in node_channels.enumerate() {
+            let (emis_v, emis_h) =
+                surface.emissivity(nodes.ocean_model, channel, freq, inc, t_surface);
+            let (tran, sky) = (outputs.atmosphere.tran[node], sky[node]);
+            let tb_toa = |emissivity| {
+                toa_from_sky(
+                    tran,
+                    outputs.atmosphere.tb_up[node],
+                    sky,
+                    emissivity,
+                    t_surface,
+                )
+            };
+            let (tb_toa_v, tb_toa_h) = (tb_toa(emis_v), tb_toa(emis_h));
+            // The TOA brightness temperature is linear in the emissivity, so
+            // mixing the emissivity is the same as mixing the TBs
+            let polarization = polarization[channel];
+            let emissivity = polarization.mix((emis_v, emis_h), polarization_angle);
+            outputs
+                .tb_toa
+                .push(polarization.mix((tb_toa_v, tb_toa_h), polarization_angle));
+            outputs.tb_toa_v.push(tb_toa_v);
+            outputs.tb_toa_h.push(tb_toa_h);
+            outputs.emissivity.push(emissivity);
+            outputs.dtb_demis.push(tran * (t_surface - sky));
+            outputs.dtb_dtskin.push(tran * emissivity);
+        }
+        outputs
+    }
+
+    /// Apply the RTM as in [`run`](Self::run), and also compute the brightness
+    /// temperature of the sky that a perfectly reflecting surface reflects
+    /// for each channel, following the [`Reflection`] of the parameters. The
+    /// parameters must not have passbands.
+    fn run_with_sky(&self, parameters: &RtmParameters<T>) -> (RtmOutputs<T>, ChannelVec<T>) {
+        let Reflection::Lambertian { quadrature_order } = parameters.reflection else {
+            let outputs = self.run(parameters);
+            let sky = outputs
+                .tran
+                .iter()
+                .zip(&outputs.tb_down)
+                .map(|(&tran, &tb_down)| specular_sky(tran, tb_down))
                 .collect();
+            return (outputs, sky);
+        };
+
+        // The zenith angles of the quadrature reuse the absorption profile
+        let nodes: Vec<_> = gauss_legendre(quadrature_order.max(1))
+            .into_iter()
+            .map(|(mu, weight)| {
+                (
+                    T::from_f64_lossy(mu.acos().to_degrees()),
+                    T::from_f64_lossy(2. * mu * weight),
+                )
+            })
+            .collect();
+        let mut outputs = RtmOutputs::new(self.quality_with(parameters, &FREQUENCY_LIMITS));
+        let mut sky = ChannelVec::new();
+        let emission = parameters.emission;
+        self.for_each_channel(parameters, |freq, inc, absorption_profile| {
+            let t = self.source_profile(emission, freq);
+            let z = &self.height[self.surface_index..];
+            let (tran, tb_up, tb_down) = self.observed_atm_tran(
+                parameters.integration_scheme,
+                parameters.layer_sum_reference,
+                inc,
+                &t,
+                z,
+                absorption_profile,
+            );
+            outputs.tran.push(tran);
+            outputs.tb_up.push(parameters.output_tb(freq, tb_up));
+            outputs.tb_down.push(parameters.output_tb(freq, tb_down));
+
+            sky.push(nodes.iter().fold(T::zero(), |sum, &(zenith, weight)| {
+                let (tran, _, tb_down) = atm_tran_with_scheme(
+                    parameters.integration_scheme,
+                    parameters.layer_sum_reference,
+                    zenith,
+                    &t,
+                    z,
+                    absorption_profile,
+                );
+                sum + weight * specular_sky(tran, parameters.output_tb(freq, tb_down))
+            }));
+        });
+        outputs.check_finite();
+        (outputs, sky)
+    }
+
+    /// Apply the RTM on the inputs for the given parameters, and additionally
+    /// compute outputs on a set of diagnostic pressure levels in hPa.
+    ///
+    /// The cumulative quantities are computed on the profile levels and then
+    /// linearly interpolated in ln(p) to the diagnostic levels. Diagnostic
+    /// levels above the top of the profile have nothing above them (so the
+    /// transmissivity is 1 and the upwelling is 0), while those with a higher
+    /// pressure than the surface pressure are NaN.
+    pub fn run_with_levels(
+        &self,
+        parameters: &RtmParameters<T>,
+        diagnostic_levels: &[T],
+    ) -> (RtmOutputs<T>, LevelOutputs<T>) {
+        if let Some(nodes) = parameters.passband_nodes() {
+            let (outputs, level_outputs) = self.run_with_levels(&nodes, diagnostic_levels);
+            let level_outputs = LevelOutputs {
+                tran_to_space: parameters.integrate_rows(&level_outputs.tran_to_space),
+                tb_up_above: parameters.integrate_rows(&level_outputs.tb_up_above),
+            };
+            return (parameters.integrate_outputs(&outputs), level_outputs);
+        }
+        let mut outputs = RtmOutputs::new(self.quality_with(parameters, &FREQUENCY_LIMITS));
+        let mut level_outputs = LevelOutputs {
+            tran_to_space: Vec::with_capacity(parameters.frequency.len()),
+            tb_up_above: Vec::with_capacity(parameters.frequency.len()),
+        };
+        let pressure = &self.pressure[self.surface_index..];
+        let emission = parameters.emission;
+
+        self.for_each_channel(parameters, |freq, inc, absorption_profile| {
+            let t = self.source_profile(emission, freq);
+            let z = &self.height[self.surface_index..];
+            let to_tb = |value| parameters.output_tb(freq, value);
+
+            let (tran, tb_up, tb_down) = self.observed_atm_tran(
+                parameters.integration_scheme,
+                parameters.layer_sum_reference,
+                inc,
+                &t,
+                z,
+                absorption_profile,
+            );
+            outputs.tran.push(tran);
+            outputs.tb_up.push(to_tb(tb_up));
+            outputs.tb_down.push(to_tb(tb_down));
+
+            let (tran_to_space, tb_up_above) = level_profiles(
+                parameters.integration_scheme,
+                inc,
+                &t,
+                z,
+                absorption_profile,
+            );
+            level_outputs.tran_to_space.push(
+                diagnostic_levels
+                    .iter()
+                    .map(|&p| interpolate_ln_p(pressure, &tran_to_space, p))
+                    .collect(),
+            );
+            level_outputs.tb_up_above.push(
+                diagnostic_levels
+                    .iter()
+                    .map(|&p| to_tb(interpolate_ln_p(pressure, &tb_up_above, p)))
+                    .collect(),
+            );
+        });
+
+        outputs.check_finite();
+        (outputs, level_outputs)
+    }
+
+    /// Apply the RTM on the inputs for the given parameters, and additionally
+    /// compute the effective temperatures of the atmosphere, `tb_up / (1 -
+    /// tran)` and `tb_down / (1 - tran)`, as used for vicarious calibration
+    /// over the ocean.
+    ///
+    /// These are computed from the same layer sums as the outputs, rather than
+    /// by dividing the outputs, so they keep their precision when the
+    /// atmosphere is nearly transparent. As `tran` goes to 1, they go to the
+    /// mean layer temperature weighted by the layer opacities, and as `tran`
+    /// goes to 0 they go to `tb_up` and `tb_down`. They are NaN only if there
+    /// is no absorption at all.
+    ///
+    /// With an observer height, the upwelling is for the path to the observer
+    /// and is divided by `1 - tran` for that path, as in the outputs, while the
+    /// downwelling is divided by `1 - tran` for the whole atmosphere. With the
+    /// Planck [`Emission`], the ratio is taken in radiance and then converted
+    /// to the output brightness temperature convention, so the effective
+    /// temperature is the temperature of a blackbody whose emission, times `1
+    /// - tran`, is the atmospheric emission.
+    ///
+    /// For a passband, the effective temperature is the integrated brightness
+    /// temperature divided by the integrated `1 - tran`, which is the harmonic
+    /// mean of the effective temperatures of the nodes weighted by their
+    /// brightness temperatures.
+    pub fn run_with_effective_temperatures(
+        &self,
+        parameters: &RtmParameters<T>,
+    ) -> (RtmOutputs<T>, EffectiveTemperatures<T>) {
+        if let Some(nodes) = parameters.passband_nodes() {
+            let (outputs, effective) = self.run_with_effective_temperatures(&nodes);
+            // `tb / effective` is `1 - tran` for each node, without the loss of
+            // precision of the subtraction. Nodes with no emission have no
+            // weight, and NaN effective temperatures
+            let integrate = |tb: &[T], effective: &[T]| {
+                parameters
+                    .integrate_nodes(|weights, nodes| {
+                        let (emission, emissivity) = weights
+                            .iter()
+                            .zip(&tb[nodes.clone()])
+                            .zip(&effective[nodes])
+                            .filter(|((_, &tb), _)| tb != T::zero())
+                            .fold(
+                                (T::zero(), T::zero()),
+                                |(emission, emissivity), ((&weight, &tb), &effective)| {
+                                    (emission + weight * tb, emissivity + weight * tb / effective)
+                                },
+                            );
+                        emission / emissivity
+                    })
+                    .collect()
+            };
+            let effective = EffectiveTemperatures {
+                up: integrate(&outputs.tb_up, &effective.up),
+                down: integrate(&outputs.tb_down, &effective.down),
+            };
+            return (parameters.integrate_outputs(&outputs), effective);
+        }
+        let mut outputs = RtmOutputs::new(self.quality_with(parameters, &FREQUENCY_LIMITS));
+        let mut effective = EffectiveTemperatures {
+            up: SmallVec::new(),
+            down: SmallVec::new(),
+        };
+        let emission = parameters.emission;
+
+        self.for_each_channel(parameters, |freq, inc, absorption_profile| {
+            let t = self.source_profile(emission, freq);
+            let z = &self.height[self.surface_index..];
+            let to_tb = |value| parameters.output_tb(freq, value);
+
+            let (tran, tb_up, tb_down) = self.observed_atm_tran(
+                parameters.integration_scheme,
+                parameters.layer_sum_reference,
+                inc,
+                &t,
+                z,
+                absorption_profile,
+            );
+            outputs.tran.push(tran);
+            outputs.tb_up.push(to_tb(tb_up));
+            outputs.tb_down.push(to_tb(tb_down));
+
+            let (up, down) = self.observed_effective_temperatures(
+                parameters.integration_scheme,
+                parameters.layer_sum_reference,
+                inc,
+                &t,
+                z,
+                absorption_profile,
+            );
+            effective.up.push(to_tb(up));
+            effective.down.push(to_tb(down));
+        });
+
+        outputs.check_finite();
+        (outputs, effective)
+    }
+
+    /// Apply the RTM on the inputs for the given parameters, and additionally
+    /// compute the downwelling brightness temperature at the profile level
+    /// with index `down_level`, from the atmosphere above it, for each
+    /// channel.
+    ///
+    /// The level indexes the input profiles from 0 for the first level, like
+    /// the [`contributions`](Self::contributions). The downwelling is the same
+    /// as `tb_down` would be if the surface were at that level, so it's 0 K
+    /// at the top level, and it's NaN if the level is below the surface. This
+    /// is for coupling to another model at that level, or for a second
+    /// boundary besides the surface.
+    ///
+    /// Returns [`RtmError::InconsistentInputs`] if `down_level` isn't one of
+    /// the levels, after any truncation at the top.
+    pub fn run_with_down_level(
+        &self,
+        parameters: &RtmParameters<T>,
+        down_level: usize,
+    ) -> Result<(RtmOutputs<T>, ChannelVec<T>), RtmError> {
+        if down_level >= self.num_levels.get() {
+            return Err(RtmError::InconsistentInputs);
+        }
+        if let Some(nodes) = parameters.passband_nodes() {
+            let (outputs, down) = self.run_with_down_level(&nodes, down_level)?;
+            return Ok((
+                parameters.integrate_outputs(&outputs),
+                parameters.integrate_channels(&down),
+            ));
+        }
+        let mut outputs = RtmOutputs::new(self.quality_with(parameters, &FREQUENCY_LIMITS));
+        let mut down = ChannelVec::new();
+        let emission = parameters.emission;
+
+        self.for_each_channel(parameters, |freq, inc, absorption_profile| {
+            let t = self.source_profile(emission, freq);
+            let z = &self.height[self.surface_index..];
+            let to_tb = |value| parameters.output_tb(freq, value);
+
+            let (tran, tb_up, tb_down) = self.observed_atm_tran(
+                parameters.integration_scheme,
+                parameters.layer_sum_reference,
+                inc,
+                &t,
+                z,
+                absorption_profile,
+            );
+            outputs.tran.push(tran);
+            outputs.tb_up.push(to_tb(tb_up));
+            outputs.tb_down.push(to_tb(tb_down));
 
-        for &inc in parameters.incidence.iter(){
-            // Build up total absorption coefficient profile
-            
+            // The profiles from the surface upward have the surface first,
+            // and then the levels above it
+            down.push(match down_level.checked_sub(self.surface_index) {
+                Some(level) => to_tb(down_at_level(
+                    parameters.integration_scheme,
+                    parameters.layer_sum_reference,
+                    inc,
+                    &t,
+                    z,
+                    absorption_profile,
+                    level + 1,
+                )),
+                None => T::nan(),
+            });
+        });
+
+        outputs.check_finite();
+        Ok((outputs, down))
+    }
+
+    /// Compute the contribution of each layer to the upwelling brightness
+    /// temperature.
+    ///
+    /// The output has one row per channel, and each row has a length of
+    /// `num_levels`, the same as the input profiles (after any truncation at
+    /// the top). The value at index `k` is for the layer whose top is level
+    /// `k`; that is, its emission times its transmissivity to space, in K.
+    /// Layers below the surface are 0.
+    ///
+    /// The contributions are always to space, ignoring any
+    /// [`observer_height`](Self::with_observer_height), and in the
+    /// Rayleigh-Jeans convention, regardless of the [`Emission`] in
+    /// `parameters`, since Planck-equivalent brightness temperatures are not
+    /// additive. They use the integration scheme of `parameters`. So with the
+    /// Rayleigh-Jeans emission and no observer height, summing a row over all
+    /// levels reproduces `tb_up` from [`run`](Self::run). Otherwise the sum is
+    /// the Rayleigh-Jeans upwelling at the top of the atmosphere, which differs
+    /// from `tb_up`.
+    pub fn contributions(&self, parameters: &RtmParameters<T>) -> Vec<Vec<T>> {
+        if let Some(nodes) = parameters.passband_nodes() {
+            return parameters.integrate_rows(&self.contributions(&nodes));
+        }
+        let mut contributions = Vec::with_capacity(parameters.frequency.len());
 
-            let results = atm_tran(
+        self.for_each_channel(parameters, |_freq, inc, absorption_profile| {
+            let mut row = vec![T::zero(); self.num_levels.get()];
+            let layers = up_contributions(
+                parameters.integration_scheme,
                 inc,
                 &self.temperature[self.surface_index..],
                 &self.height[self.surface_index..],
-                &absorption_profile,
+                absorption_profile,
             );
+            row[self.surface_index..].copy_from_slice(&layers);
+            contributions.push(row);
+        });
+
+        contributions
+    }
+
+    /// Apply [`atm_tran`] with an integration scheme and layer sum reference
+    /// to the profiles from the surface upward, with the upwelling truncated
+    /// at the observer, if any.
+    fn observed_atm_tran(
+        &self,
+        scheme: IntegrationScheme,
+        reference: LayerSumReference,
+        inc: T,
+        t: &[T],
+        z: &[T],
+        tabs: &[T],
+    ) -> (T, T, T) {
+        let (tran, tb_up, tb_down) = atm_tran_with_scheme(scheme, reference, inc, t, z, tabs);
+        match self.observer_levels() {
+            Some(num_observed) if num_observed < t.len() => {
+                let (tran, tb_up, _) = atm_tran_with_scheme(
+                    scheme,
+                    reference,
+                    inc,
+                    &t[..num_observed],
+                    &z[..num_observed],
+                    &tabs[..num_observed],
+                );
+                (tran, tb_up, tb_down)
+            }
+            _ => (tran, tb_up, tb_down),
+        }
+    }
+
+    /// Apply [`effective_temperatures`] with an integration scheme and layer
+    /// sum reference to the profiles from the surface upward, with the
+    /// upwelling truncated at the observer, if any.
+    fn observed_effective_temperatures(
+        &self,
+        scheme: IntegrationScheme,
+        reference: LayerSumReference,
+        inc: T,
+        t: &[T],
+        z: &[T],
+        tabs: &[T],
+    ) -> (T, T) {
+        let (up, down) = effective_temperatures(scheme, reference, inc, t, z, tabs);
+        match self.observer_levels() {
+            Some(num_observed) if num_observed < t.len() => {
+                let (up, _) = effective_temperatures(
+                    scheme,
+                    reference,
+                    inc,
+                    &t[..num_observed],
+                    &z[..num_observed],
+                    &tabs[..num_observed],
+                );
+                (up, down)
+            }
+            _ => (up, down),
+        }
+    }
+
+    /// The number of profile levels from the surface up to and including the
+    /// level nearest to the observer, if any.
+    fn observer_levels(&self) -> Option<usize> {
+        let observer_height = self.observer_height?;
+        let z = &self.height[self.surface_index..];
+        let nearest = (1..z.len()).min_by(|&a, &b| {
+            let distance = |i: usize| (z[i] - observer_height).abs();
+            distance(a)
+                .partial_cmp(&distance(b))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })?;
+        Some(nearest + 1)
+    }
 
-            tran.push(results.0);
-            tb_up.push(results.1);
-            tb_down.push(results.2);
+    /// Call `f` with the frequency, incidence angle, and absorption profile of
+    /// each channel, in order.
+    ///
+    /// The absorption profile only depends on frequency, so it's reused
+    /// between consecutive channels with the same frequency (as in the MSU
+    /// case, where each channel is the same frequency at a different
+    /// incidence angle).
+    fn for_each_channel(&self, parameters: &RtmParameters<T>, f: impl FnMut(T, T, &[T])) {
+        self.for_each_channel_with(parameters, &parameters.absorption_scale, f);
+    }
+
+    /// As [`for_each_channel`](Self::for_each_channel), but with the absorption
+    /// profiles from `model`.
+    fn for_each_channel_with<M: AbsorptionModel<T> + ?Sized>(
+        &self,
+        parameters: &RtmParameters<T>,
+        model: &M,
+        mut f: impl FnMut(T, T, &[T]),
+    ) {
+        let mut absorption_profile: LevelVec<T> = LevelVec::new();
+        let mut profile_freq = None;
+
+        for (&freq, &inc) in parameters.frequency.iter().zip(&parameters.incidence) {
+            if profile_freq != Some(freq) {
+                absorption_profile = self.absorption_from(self.surface_index, freq, model);
+                profile_freq = Some(freq);
+            }
+            f(freq, inc, &absorption_profile);
         }
+    }
 
-        RtmOutputs {
-            tran,
-            tb_up,
-            tb_down,
+    /// The temperature profile from the surface upward, converted for the
+    /// `emission` convention at a frequency in GHz.
+    fn source_profile(&self, emission: Emission, freq: T) -> Cow<'_, [T]> {
+        let temperature = &self.temperature[self.surface_index..];
+        match emission {
+            Emission::RayleighJeans => Cow::Borrowed(temperature),
+            Emission::Planck => Cow::Owned(
+                temperature
+                    .iter()
+                    .map(|&t| emission.source(freq, t))
+                    .collect(),
+            ),
         }
     }
+
+    /// Build up the total absorption coefficient profile (in Np/m) from the
+    /// surface upward for a frequency in GHz.
+    #[cfg(test)]
+    fn absorption_profile(&self, freq: T) -> LevelVec<T> {
+        self.absorption_from(self.surface_index, freq, &BuiltinAbsorption)
+    }
+
+    /// Build up the total absorption coefficient profile (in Np/m) from the
+    /// level at `first_index` upward for a frequency in GHz, using `model`.
+    fn absorption_from<M: AbsorptionModel<T> + ?Sized>(
+        &self,
+        first_index: usize,
+        freq: T,
+        model: &M,
+    ) -> LevelVec<T> {
+        (first_index..self.num_levels.get() + 1)
+            .map(|level_index| {
+                model.layer_absorption(
+                    self.pressure[level_index],
+                    self.temperature[level_index],
+                    self.vapor_pressure[level_index],
+                    self.rho_l[level_index],
+                    freq,
+                )
+            })
+            .collect()
+    }
+}
+
+/// Apply the RTM to a parallel stream of inputs.
+///
+/// Each item of `inputs` is the prepared inputs for a single point (or the
+/// error from preparing them), and each item of the result is the
+/// corresponding outputs. Nothing is collected, so downstream processing (such
+/// as writing each result out) can be fused into the same parallel pipeline.
+/// If `inputs` is an indexed parallel iterator, so is the result, and the
+/// order of the points is preserved by indexed operations such as
+/// `collect_into_vec`. The points are split between the threads as `inputs`
+/// is, so for an even load, limit the length of its chunks, e.g., with
+/// `with_max_len(parameters.work_chunk_size().get())` (see
+/// [`RtmParameters::with_work_chunk_size`]).
+#[cfg(feature = "parallel")]
+#[allow(clippy::type_complexity)]
+pub fn compute_rtm_iter<'a, T, I>(
+    parameters: &'a RtmParameters<T>,
+    inputs: I,
+) -> rayon::iter::Map<
+    I::Iter,
+    impl Fn(Result<RtmInputs<T>, RtmError>) -> Result<RtmOutputs<T>, RtmError> + Sync + Send + 'a,
+>
+where
+    T: Float,
+    I: IntoParallelIterator<Item = Result<RtmInputs<T>, RtmError>>,
+{
+    inputs
+        .into_par_iter()
+        .map(move |inputs| inputs.map(|inputs| inputs.run(parameters)))
 }
 
-/// The Buck equation.
+/// Apply the RTM to a batch of prepared inputs in parallel.
 ///
-/// Convert `temp`, the temperature in K, into water vapor saturation pressure
-/// in hPa. The equation is from [1], which cites Buck 1996.
+/// The work is done in a new thread pool with `num_threads` worker threads, or
+/// if it's `None`, a number chosen from the size of the batch by
+/// [`adaptive_num_threads`] with the [`items_per_thread`] of `parameters`, in
+/// chunks of at most its [`work_chunk_size`] points. The outputs are in the
+/// same order as `inputs`, and are bit-for-bit identical for any number of
+/// threads and chunk size, since each point is computed independently.
+///
+/// [`items_per_thread`]: RtmParameters::items_per_thread
+/// [`work_chunk_size`]: RtmParameters::work_chunk_size
+///
+/// Without the `parallel` feature, the points are run one at a time in the
+/// calling thread, and `num_threads` is ignored.
+///
+/// With the `gpu` feature and [`AbsorptionBackend::Gpu`] in the parameters,
+/// the gas absorption is computed on the GPU instead, if there is one (see
+/// `gpu::GpuAbsorption::compute_rtm_batch`).
+pub fn compute_rtm_batch<T: Float>(
+    inputs: &[RtmInputs<T>],
+    parameters: &RtmParameters<T>,
+    num_threads: Option<NonZeroUsize>,
+) -> Result<Vec<RtmOutputs<T>>, RtmError> {
+    #[cfg(feature = "gpu")]
+    if parameters.absorption_backend == AbsorptionBackend::Gpu {
+        if let Some(gpu) = gpu::GpuAbsorption::shared() {
+            return gpu.compute_rtm_batch(inputs, parameters, num_threads);
+        }
+    }
+    #[cfg(feature = "parallel")]
+    {
+        let num_threads = threads::num_threads_or_adaptive(
+            num_threads,
+            inputs.len(),
+            parameters.len(),
+            parameters.items_per_thread,
+        );
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(num_threads.get())
+            .build()
+            .map_err(RtmError::ThreadPool)?;
+
+        Ok(pool.install(|| {
+            inputs
+                .par_iter()
+                .with_max_len(parameters.work_chunk_size.get())
+                .map(|inputs| inputs.run(parameters))
+                .collect()
+        }))
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        let _ = num_threads;
+        Ok(inputs.iter().map(|inputs| inputs.run(parameters)).collect())
+    }
+}
+
+/// Apply the RTM in parallel to each profile from `source`, writing the results
+/// to `sink`.
+///
+/// Each profile is borrowed from the source, prepared, and run in a worker
+/// thread, and its result (or the error from preparing it) is written to the
+/// sink with its index. The results are written as they finish, so they are
+/// not in order. The thread pool is the same as for [`compute_rtm_batch`], and
+/// so is the sequential fallback without the `parallel` feature, in which case
+/// the results are in order.
+pub fn compute_rtm_source<T, S, K>(
+    source: &S,
+    parameters: &RtmParameters<T>,
+    sink: &K,
+    num_threads: Option<NonZeroUsize>,
+) -> Result<(), RtmError>
+where
+    T: Float,
+    S: ProfileSource<T> + ?Sized,
+    K: ResultSink<T> + ?Sized,
+{
+    let run = |index: usize| {
+        let result = source
+            .profile(index)
+            .and_then(|profile| profile.inputs())
+            .map(|inputs| inputs.run(parameters));
+        sink.write(index, result);
+    };
+
+    #[cfg(feature = "parallel")]
+    {
+        let num_threads = threads::num_threads_or_adaptive(
+            num_threads,
+            source.len(),
+            parameters.len(),
+            parameters.items_per_thread,
+        );
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(num_threads.get())
+            .build()
+            .map_err(RtmError::ThreadPool)?;
+        pool.install(|| {
+            (0..source.len())
+                .into_par_iter()
+                .with_max_len(parameters.work_chunk_size.get())
+                .for_each(run)
+        });
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        let _ = num_threads;
+        (0..source.len()).for_each(run);
+    }
+    Ok(())
+}
+
+/// Apply only the radiative transfer integration, using absorption that is
+/// computed externally instead of by this crate's absorption models.
 ///
-/// To convert to water vapor partial pressure, multiply the result by the
-/// relative humidity.
+/// The `temperature` (K) and geometric `height` (m) profiles start at the
+/// surface and go upward, and need at least two levels. Each row of
+/// `absorption` is the absorption coefficient in Np/m on those same levels for
+/// one channel, whose Earth incidence angle in degrees is in `incidence`. The
+/// emission is Rayleigh-Jeans, like [`Emission::RayleighJeans`].
+pub fn run_from_absorption<T: Float, A: AsRef<[T]>>(
+    incidence: &[T],
+    temperature: &[T],
+    height: &[T],
+    absorption: &[A],
+) -> Result<RtmOutputs<T>, RtmError> {
+    let num_levels = temperature.len();
+    if num_levels < 2
+        || height.len() != num_levels
+        || incidence.is_empty()
+        || absorption.len() != incidence.len()
+        || absorption
+            .iter()
+            .any(|row| row.as_ref().len() != num_levels)
+    {
+        return Err(RtmError::InconsistentInputs);
+    }
+    check_incidence(incidence)?;
+
+    let mut quality = QualityFlags::empty();
+    quality.set_if(
+        QualityFlags::INPUT_NOT_FINITE,
+        temperature
+            .iter()
+            .chain(height)
+            .chain(absorption.iter().flat_map(AsRef::as_ref))
+            .any(|value| !value.is_finite()),
+    );
+    quality.set_if(
+        QualityFlags::TEMPERATURE_OUT_OF_RANGE,
+        temperature
+            .iter()
+            .any(|t| !QualityFlags::TEMPERATURE_RANGE.contains(&t.into_f64())),
+    );
+
+    let mut outputs = RtmOutputs::new(quality);
+    for (&inc, absorption_profile) in incidence.iter().zip(absorption) {
+        let (tran, tb_up, tb_down) =
+            atm_tran(inc, temperature, height, absorption_profile.as_ref());
+        outputs.tran.push(tran);
+        outputs.tb_up.push(tb_up);
+        outputs.tb_down.push(tb_down);
+    }
+    outputs.check_finite();
+    Ok(outputs)
+}
+
+/// Check that each Earth incidence angle in `eia` is within 0° to 90°,
+/// inclusive.
+fn check_incidence<T: Float>(eia: &[T]) -> Result<(), RtmError> {
+    // Written so that NaN is also rejected
+    match eia
+        .iter()
+        .enumerate()
+        .find(|(_, &value)| !(0.0..=90.0).contains(&value.into_f64()))
+    {
+        Some((index, &value)) => Err(RtmError::InvalidIncidence {
+            index,
+            value: value.into_f64() as f32,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Check that the profiles all have the same length as `levels`, other than
+/// an empty `liquid_content`, and that the levels are strictly decreasing.
+/// Returns whether the profile is clear sky.
+fn check_profiles<T: Float>(
+    levels: &[T],
+    temperature: &[T],
+    height: &[T],
+    specific_humidity: &[T],
+    liquid_content: &[T],
+) -> Result<bool, RtmError> {
+    let clear_sky = liquid_content.is_empty();
+    if [temperature, height, specific_humidity]
+        .iter()
+        .chain((!clear_sky).then_some(&liquid_content))
+        .any(|values| values.len() != levels.len())
+    {
+        return Err(RtmError::InconsistentInputs);
+    }
+    // The surface and top are found by assuming this order
+    if levels.iter().any(|p| p.is_nan()) || !levels.windows(2).all(|pair| pair[0] > pair[1]) {
+        return Err(RtmError::UnsortedLevels);
+    }
+    Ok(clear_sky)
+}
+
+/// The number of `levels`, sorted in descending order (from high to low
+/// pressure), that are not above `top_pressure`.
+fn num_levels_below_top<T: Float>(levels: &[T], top_pressure: Option<T>) -> usize {
+    match top_pressure {
+        Some(top_pressure) => levels.iter().take_while(|&&p| p >= top_pressure).count(),
+        None => levels.len(),
+    }
+}
+
+/// Interpolate `values` on the `pressure` levels to the `target` pressure,
+/// linearly in ln(p).
 ///
-/// [1]: https://en.wikipedia.org/wiki/Arden_Buck_equation
-fn buck_vap(temp: f32) -> f32 {
-    // Temperature in degrees Celsius
-    let temp_c = temp - 273.15;
-    6.1121 * f32::exp((18.678 - temp_c / 234.5) * (temp_c / (257.14 + temp_c)))
+/// The pressures are in descending order, starting from the surface. A target
+/// with a higher pressure than the first level is NaN, and one with a lower
+/// pressure than the last level takes the last value.
+fn interpolate_ln_p<T: Float>(pressure: &[T], values: &[T], target: T) -> T {
+    if target > pressure[0] || target.is_nan() {
+        return T::nan();
+    }
+
+    match pressure.iter().position(|&p| p <= target) {
+        Some(0) => values[0],
+        Some(above) => {
+            let below = above - 1;
+            let weight = (pressure[below] / target).ln() / (pressure[below] / pressure[above]).ln();
+            values[below] + weight * (values[above] - values[below])
+        }
+        None => values[values.len() - 1],
+    }
 }
@@ -0,0 +1,332 @@
+//! Replacing the absorption model.
+
+use super::{
+    core::{layer_absorption, layer_absorption_with, LineCoefficients},
+    oxygen::OxygenCoefficients,
+    water_vapor::WaterVaporCoefficients,
+    Float, RtmInputs, RtmOutputs, RtmParameters, FREQUENCY_LIMITS, REFERENCE_TEMPERATURE,
+};
+
+/// A model of the absorption coefficient of an atmospheric layer.
+///
+/// The RTM normally uses the absorption models of this crate
+/// ([`BuiltinAbsorption`]), but another model can be given to
+/// [`RtmInputs::run_with_absorption_model`], for instance to try out different
+/// spectroscopy. Closures with the same arguments as
+/// [`layer_absorption`](Self::layer_absorption) implement this trait.
+///
+/// The model is evaluated on every level of the profile, from the surface
+/// upward, and for each distinct frequency (consecutive channels with the same
+/// frequency share the absorption profile). It must be `Sync` so that points
+/// can be run in parallel.
+pub trait AbsorptionModel<T: Float = f32>: Sync {
+    /// The absorption coefficient in Np/m for a pressure in hPa, temperature in
+    /// K, water vapor partial pressure in hPa, liquid water density in g/m³,
+    /// and frequency in GHz.
+    fn layer_absorption(
+        &self,
+        pressure: T,
+        temperature: T,
+        vapor_pressure: T,
+        liquid_water_density: T,
+        frequency: T,
+    ) -> T;
+
+    /// The highest frequency in GHz of the validated range of each absorber
+    /// in the model, as in [`FREQUENCY_LIMITS`]. The outputs are flagged with
+    /// [`QualityFlags::EXTRAPOLATED_FREQUENCY`](super::QualityFlags::EXTRAPOLATED_FREQUENCY) if a channel is above one of
+    /// them. A custom model has no limits by default.
+    fn frequency_limits(&self) -> &[(&str, f64)] {
+        &[]
+    }
+}
+
+/// The absorption models of this crate: oxygen, water vapor, and cloud liquid
+/// water (see [`SPECTROSCOPY`](super::SPECTROSCOPY)).
+///
+/// These are the models as published. To scale the absorbers, use an
+/// [`AbsorptionScale`], which is also a model.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BuiltinAbsorption;
+
+/// Multiplicative factors on the absorption of each absorber of the builtin
+/// models, e.g., to fit the biases of observations in calibration and
+/// validation work.
+///
+/// The factors are 1 by default, which is bit-for-bit the same as the
+/// unscaled models. Set them for a run with
+/// [`RtmParameters::with_absorption_scale`] or [`RtmOptions`](super::RtmOptions).
+/// This is also an [`AbsorptionModel`], the builtin models with these factors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct AbsorptionScale {
+    /// Factor on the oxygen absorption.
+    pub oxygen: f64,
+    /// Factor on the water vapor line absorption.
+    pub water_vapor_lines: f64,
+    /// Factor on the water vapor continuum, both the foreign and the
+    /// self-broadened terms.
+    pub water_vapor_continuum: f64,
+    /// Factor on the cloud liquid water absorption.
+    pub cloud: f64,
+}
+
+impl Default for AbsorptionScale {
+    fn default() -> Self {
+        Self {
+            oxygen: 1.,
+            water_vapor_lines: 1.,
+            water_vapor_continuum: 1.,
+            cloud: 1.,
+        }
+    }
+}
+
+impl<T: Float> AbsorptionModel<T> for AbsorptionScale {
+    fn layer_absorption(
+        &self,
+        pressure: T,
+        temperature: T,
+        vapor_pressure: T,
+        liquid_water_density: T,
+        frequency: T,
+    ) -> T {
+        layer_absorption(
+            pressure,
+            temperature,
+            vapor_pressure,
+            liquid_water_density,
+            frequency,
+            self,
+        )
+    }
+
+    fn frequency_limits(&self) -> &[(&str, f64)] {
+        &FREQUENCY_LIMITS
+    }
+}
+
+impl<T: Float> AbsorptionModel<T> for BuiltinAbsorption {
+    fn layer_absorption(
+        &self,
+        pressure: T,
+        temperature: T,
+        vapor_pressure: T,
+        liquid_water_density: T,
+        frequency: T,
+    ) -> T {
+        layer_absorption(
+            pressure,
+            temperature,
+            vapor_pressure,
+            liquid_water_density,
+            frequency,
+            &AbsorptionScale::default(),
+        )
+    }
+
+    fn frequency_limits(&self) -> &[(&str, f64)] {
+        &FREQUENCY_LIMITS
+    }
+}
+
+/// Fractional changes to groups of the line coefficients of the builtin
+/// absorption models, for propagating the spectroscopic uncertainty to the
+/// outputs, e.g., by Monte Carlo.
+///
+/// Each field is the fraction by which every line of a group is changed, so
+/// 0.01 is 1% larger and -0.01 is 1% smaller. They're 0 by default, which is
+/// the published coefficients. Unlike an [`AbsorptionScale`], which scales the
+/// total absorption, these change the line shapes: a wider line absorbs less
+/// at its center and more in its wings.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct SpectroscopyPerturbation {
+    /// Change in the oxygen line intensities (`a1` in the model).
+    pub oxygen_intensity: f64,
+    /// Change in the oxygen line widths (`a3`).
+    pub oxygen_width: f64,
+    /// Change in the water vapor line intensities (`b1`).
+    pub water_vapor_intensity: f64,
+    /// Change in the water vapor line widths (`b3`), both the air- and
+    /// self-broadened widths.
+    pub water_vapor_width: f64,
+}
+
+/// The builtin absorption models with their own set of line coefficients,
+/// perturbed by a [`SpectroscopyPerturbation`].
+///
+/// The builtin models share one set of coefficients, built on first use.
+/// Instead, each of these builds its own set when it's created, so any number
+/// of perturbed models can be used at the same time, e.g., one for each member
+/// of an ensemble. Run them with [`RtmInputs::run_with_absorption_model`]. The
+/// continua and the cloud absorption aren't perturbed.
+///
+/// ```
+/// use rss_atmos_rtm::rtm::{
+///     standard_atmospheres::US_STANDARD_1976, PerturbedAbsorption, RtmParameters,
+///     SpectroscopyPerturbation,
+/// };
+///
+/// let inputs = US_STANDARD_1976.profile().inputs()?;
+/// let parameters = RtmParameters::new(&[53.74], &[0.])?;
+/// let model = PerturbedAbsorption::new(SpectroscopyPerturbation {
+///     oxygen_width: 0.02,
+///     ..Default::default()
+/// });
+/// let perturbed = inputs.run_with_absorption_model(&parameters, &model);
+/// let unperturbed = inputs.run(&parameters);
+/// println!("ΔTB = {} K", perturbed.tb_up[0] - unperturbed.tb_up[0]);
+/// # Ok::<(), rss_atmos_rtm::rtm::RtmError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct PerturbedAbsorption {
+    perturbation: SpectroscopyPerturbation,
+    oxygen: OxygenCoefficients,
+    water_vapor: WaterVaporCoefficients,
+    reference_temperature: f32,
+}
+
+impl PerturbedAbsorption {
+    /// Build the line coefficients with the `perturbation`.
+    pub fn new(perturbation: SpectroscopyPerturbation) -> Self {
+        Self {
+            perturbation,
+            oxygen: OxygenCoefficients::perturbed(
+                perturbation.oxygen_intensity,
+                perturbation.oxygen_width,
+            ),
+            water_vapor: WaterVaporCoefficients::perturbed(
+                perturbation.water_vapor_intensity,
+                perturbation.water_vapor_width,
+            ),
+            reference_temperature: REFERENCE_TEMPERATURE,
+        }
+    }
+
+    /// Evaluate the lines with their parameters tabulated at
+    /// `reference_temperature` in K instead of [`REFERENCE_TEMPERATURE`].
+    ///
+    /// The coefficients are the same, so this is for experimenting with the
+    /// temperature dependence of the models, such as for a line catalog
+    /// tabulated at another reference. At a temperature equal to the
+    /// reference, the absorption is the same as at the builtin reference with
+    /// the builtin temperature.
+    pub fn with_reference_temperature(mut self, reference_temperature: f32) -> Self {
+        self.reference_temperature = reference_temperature;
+        self
+    }
+
+    /// The perturbation of the coefficients.
+    pub fn perturbation(&self) -> SpectroscopyPerturbation {
+        self.perturbation
+    }
+
+    /// The reference temperature in K of the line parameters, which is
+    /// [`REFERENCE_TEMPERATURE`] unless it was changed with
+    /// [`with_reference_temperature`](Self::with_reference_temperature).
+    pub fn reference_temperature(&self) -> f32 {
+        self.reference_temperature
+    }
+}
+
+impl<T: Float> AbsorptionModel<T> for PerturbedAbsorption {
+    fn layer_absorption(
+        &self,
+        pressure: T,
+        temperature: T,
+        vapor_pressure: T,
+        liquid_water_density: T,
+        frequency: T,
+    ) -> T {
+        layer_absorption_with(
+            pressure,
+            temperature,
+            vapor_pressure,
+            liquid_water_density,
+            frequency,
+            &AbsorptionScale::default(),
+            LineCoefficients {
+                oxygen: &self.oxygen,
+                water_vapor: &self.water_vapor,
+                reference_temperature: self.reference_temperature,
+            },
+        )
+    }
+
+    fn frequency_limits(&self) -> &[(&str, f64)] {
+        &FREQUENCY_LIMITS
+    }
+}
+
+impl<T: Float, F> AbsorptionModel<T> for F
+where
+    F: Fn(T, T, T, T, T) -> T + Sync,
+{
+    fn layer_absorption(
+        &self,
+        pressure: T,
+        temperature: T,
+        vapor_pressure: T,
+        liquid_water_density: T,
+        frequency: T,
+    ) -> T {
+        self(
+            pressure,
+            temperature,
+            vapor_pressure,
+            liquid_water_density,
+            frequency,
+        )
+    }
+}
+
+impl<T: Float> RtmInputs<T> {
+    /// Apply the RTM on the inputs for the given parameters, as in
+    /// [`run`](Self::run), but with the absorption coefficient of each level
+    /// from `model` instead of the absorption models of this crate.
+    ///
+    /// Only this method takes a custom model. The other ways of running the
+    /// RTM, including the cached lower atmosphere of
+    /// [`cache_lower`](Self::cache_lower) and the
+    /// [`surface_temperature_jacobian`](Self::surface_temperature_jacobian),
+    /// always use the builtin models, scaled by the
+    /// [`absorption_scale`](RtmParameters::absorption_scale) of the parameters.
+    /// The scale isn't applied to `model`.
+    pub fn run_with_absorption_model<M>(
+        &self,
+        parameters: &RtmParameters<T>,
+        model: &M,
+    ) -> RtmOutputs<T>
+    where
+        M: AbsorptionModel<T> + ?Sized,
+    {
+        if let Some(outputs) = self.run_passbands(parameters, |nodes| {
+            self.run_with_absorption_model(nodes, model)
+        }) {
+            return outputs;
+        }
+        let mut outputs = RtmOutputs::new(self.quality_with(parameters, model.frequency_limits()));
+
+        let emission = parameters.emission;
+        self.for_each_channel_with(parameters, model, |freq, inc, absorption_profile| {
+            let results = self.observed_atm_tran(
+                parameters.integration_scheme,
+                parameters.layer_sum_reference,
+                inc,
+                &self.source_profile(emission, freq),
+                &self.height[self.surface_index..],
+                absorption_profile,
+            );
+
+            outputs.tran.push(results.0);
+            outputs.tb_up.push(parameters.output_tb(freq, results.1));
+            outputs.tb_down.push(parameters.output_tb(freq, results.2));
+        });
+
+        outputs.check_finite();
+        outputs
+    }
+}
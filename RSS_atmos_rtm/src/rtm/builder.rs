@@ -0,0 +1,254 @@
+//! Builder for [`RtmInputs`] with named setters.
+
+use super::{Float, RtmError, RtmInputs, SurfaceHeightPolicy};
+
+/// Build [`RtmInputs`] using named setters instead of positional arguments.
+///
+/// Every input is required except for the liquid content (which is clear sky
+/// if it's not given), surface pressure (which is the first level if it's not
+/// given), top pressure, skin temperature, and observer height, and the
+/// surface values when the profiles include the surface (see
+/// [`input_includes_surface`](Self::input_includes_surface)). The profiles
+/// are borrowed until [`build`](Self::build) is
+/// called, which does the same preparation and validation as
+/// [`RtmInputs::new`].
+///
+/// # Example
+///
+/// ```
+/// use rss_atmos_rtm::rtm::RtmInputs;
+///
+/// let inputs = RtmInputs::builder()
+///     .pressure_levels(&[1000., 850., 700., 500., 300.])
+///     .temperature_profile(&[288., 280., 272., 255., 230.])
+///     .height_profile(&[110., 1460., 3010., 5570., 9160.])
+///     .specific_humidity_profile(&[8e-3, 5e-3, 3e-3, 1e-3, 1e-4])
+///     .liquid_content_profile(&[0.; 5])
+///     .surface_temperature(288.)
+///     .surface_height(0.)
+///     .surface_dewpoint(280.)
+///     .surface_pressure(1010.)
+///     .build()?;
+/// assert_eq!(inputs.num_levels_integrated(), 5);
+/// # Ok::<(), rss_atmos_rtm::rtm::RtmError>(())
+/// ```
+///
+/// A missing input is an error:
+///
+/// ```
+/// use rss_atmos_rtm::rtm::{RtmError, RtmInputs};
+///
+/// let result = RtmInputs::builder()
+///     .pressure_levels(&[1000., 850.])
+///     .temperature_profile(&[288., 280.])
+///     .build();
+/// assert!(matches!(result, Err(RtmError::MissingInput("height_profile"))));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RtmInputsBuilder<'a, T: Float = f32> {
+    levels: Option<&'a [T]>,
+    temperature: Option<&'a [T]>,
+    height: Option<&'a [T]>,
+    specific_humidity: Option<&'a [T]>,
+    liquid_content: Option<&'a [T]>,
+    surface_temperature: Option<T>,
+    surface_height: Option<T>,
+    surface_dewpoint: Option<T>,
+    surface_pressure: Option<T>,
+    top_pressure: Option<T>,
+    skin_temperature: Option<T>,
+    observer_height: Option<T>,
+    clamp_surface_saturation: bool,
+    surface_height_policy: SurfaceHeightPolicy,
+    input_includes_surface: bool,
+}
+
+impl<'a, T: Float> RtmInputsBuilder<'a, T> {
+    /// Create a builder with no inputs set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pressure levels in hPa, sorted from high to low pressure.
+    pub fn pressure_levels(mut self, levels: &'a [T]) -> Self {
+        self.levels = Some(levels);
+        self
+    }
+
+    /// Physical temperature in K on each level.
+    pub fn temperature_profile(mut self, temperature: &'a [T]) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    /// Geopotential height in m on each level.
+    pub fn height_profile(mut self, height: &'a [T]) -> Self {
+        self.height = Some(height);
+        self
+    }
+
+    /// Specific humidity in kg/kg on each level.
+    pub fn specific_humidity_profile(mut self, specific_humidity: &'a [T]) -> Self {
+        self.specific_humidity = Some(specific_humidity);
+        self
+    }
+
+    /// Optional liquid water content (from clouds) in kg/kg on each level. By
+    /// default there's no cloud water.
+    pub fn liquid_content_profile(mut self, liquid_content: &'a [T]) -> Self {
+        self.liquid_content = Some(liquid_content);
+        self
+    }
+
+    /// 2 meter air temperature in K.
+    pub fn surface_temperature(mut self, surface_temperature: T) -> Self {
+        self.surface_temperature = Some(surface_temperature);
+        self
+    }
+
+    /// Geopotential height at the surface in m.
+    pub fn surface_height(mut self, surface_height: T) -> Self {
+        self.surface_height = Some(surface_height);
+        self
+    }
+
+    /// 2 meter dewpoint in K.
+    pub fn surface_dewpoint(mut self, surface_dewpoint: T) -> Self {
+        self.surface_dewpoint = Some(surface_dewpoint);
+        self
+    }
+
+    /// Optional surface pressure in hPa. By default, the first level is the
+    /// surface (see [`RtmInputs::new`]).
+    pub fn surface_pressure(mut self, surface_pressure: T) -> Self {
+        self.surface_pressure = Some(surface_pressure);
+        self
+    }
+
+    /// Optional pressure in hPa above which the levels are discarded.
+    pub fn top_pressure(mut self, top_pressure: T) -> Self {
+        self.top_pressure = Some(top_pressure);
+        self
+    }
+
+    /// Optional radiometric skin temperature of the surface in K, used for the
+    /// surface emission instead of the 2 m air temperature. See
+    /// [`RtmInputs::with_skin_temperature`].
+    pub fn skin_temperature(mut self, skin_temperature: T) -> Self {
+        self.skin_temperature = Some(skin_temperature);
+        self
+    }
+
+    /// Optional geometric height of the observer in m, for a sensor that is
+    /// not at the top of the atmosphere. See
+    /// [`RtmInputs::with_observer_height`].
+    pub fn observer_height(mut self, observer_height: T) -> Self {
+        self.observer_height = Some(observer_height);
+        self
+    }
+
+    /// Whether to clamp the surface vapor pressure to saturation at the
+    /// surface temperature. See [`RtmInputs::with_surface_saturation_clamp`].
+    pub fn clamp_surface_saturation(mut self, clamp_surface_saturation: bool) -> Self {
+        self.clamp_surface_saturation = clamp_surface_saturation;
+        self
+    }
+
+    /// What to do if the surface height is not below the first level above
+    /// the surface. By default, it's moved 0.1 m below (see
+    /// [`SurfaceHeightPolicy`]).
+    pub fn surface_height_policy(mut self, surface_height_policy: SurfaceHeightPolicy) -> Self {
+        self.surface_height_policy = surface_height_policy;
+        self
+    }
+
+    /// Whether the first element of each profile is the surface, rather than
+    /// the first level of the atmosphere.
+    ///
+    /// There are two conventions for the surface:
+    ///
+    /// - By default, the profiles are only the atmosphere, and the surface is
+    ///   given separately with [`surface_temperature`](Self::surface_temperature),
+    ///   [`surface_height`](Self::surface_height),
+    ///   [`surface_dewpoint`](Self::surface_dewpoint), and optionally
+    ///   [`surface_pressure`](Self::surface_pressure). These are prepended to
+    ///   the profiles, and any levels below the surface are not used.
+    /// - With `true`, the first element of each profile is the surface, and
+    ///   nothing is prepended. The surface humidity is then the first
+    ///   specific humidity rather than a dewpoint, and the surface liquid
+    ///   content is the first liquid content rather than that of the level
+    ///   above. Every level is above the surface, since the pressures are
+    ///   decreasing.
+    ///
+    /// Mixing the conventions would put the surface in twice, so with `true`,
+    /// setting any of the separate surface values is an
+    /// [`RtmError::InvalidSurface`]. The profiles need at least two elements:
+    /// the surface and one level.
+    pub fn input_includes_surface(mut self, input_includes_surface: bool) -> Self {
+        self.input_includes_surface = input_includes_surface;
+        self
+    }
+
+    /// Check that all of the required inputs are present and prepare them.
+    pub fn build(self) -> Result<RtmInputs<T>, RtmError> {
+        fn required<V>(value: Option<V>, name: &'static str) -> Result<V, RtmError> {
+            value.ok_or(RtmError::MissingInput(name))
+        }
+
+        let levels = required(self.levels, "pressure_levels")?;
+        let temperature = required(self.temperature, "temperature_profile")?;
+        let height = required(self.height, "height_profile")?;
+        let specific_humidity = required(self.specific_humidity, "specific_humidity_profile")?;
+        let liquid_content = self.liquid_content.unwrap_or_default();
+
+        let inputs = if self.input_includes_surface {
+            let separate = [
+                ("surface_temperature", self.surface_temperature),
+                ("surface_height", self.surface_height),
+                ("surface_dewpoint", self.surface_dewpoint),
+                ("surface_pressure", self.surface_pressure),
+            ];
+            if let Some((name, _)) = separate.iter().find(|(_, value)| value.is_some()) {
+                return Err(RtmError::InvalidSurface(format!(
+                    "{name} is given, but the profiles include the surface"
+                )));
+            }
+            RtmInputs::prepare_including_surface(
+                levels,
+                temperature,
+                height,
+                specific_humidity,
+                liquid_content,
+                self.top_pressure,
+                self.surface_height_policy,
+            )?
+        } else {
+            RtmInputs::prepare(
+                levels,
+                required(self.surface_temperature, "surface_temperature")?,
+                temperature,
+                required(self.surface_height, "surface_height")?,
+                height,
+                required(self.surface_dewpoint, "surface_dewpoint")?,
+                specific_humidity,
+                liquid_content,
+                self.surface_pressure,
+                self.top_pressure,
+                self.surface_height_policy,
+            )?
+        };
+        let inputs = match self.skin_temperature {
+            Some(skin_temperature) => inputs.with_skin_temperature(skin_temperature),
+            None => inputs,
+        };
+        let inputs = if self.clamp_surface_saturation {
+            inputs.with_surface_saturation_clamp()
+        } else {
+            inputs
+        };
+        Ok(match self.observer_height {
+            Some(observer_height) => inputs.with_observer_height(observer_height),
+            None => inputs,
+        })
+    }
+}
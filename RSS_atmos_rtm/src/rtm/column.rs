@@ -0,0 +1,73 @@
+//! Column summary statistics of the prepared profile.
+
+use super::{vapor_pressure_to_specific_humidity, Float, RtmInputs};
+
+/// Summary statistics of the atmospheric column, from the surface to the top
+/// of the profile.
+///
+/// These are computed from the same profile as the RTM uses, after the surface
+/// values are inserted and the levels below the surface (or above the top
+/// pressure) are removed. Each layer between two adjacent levels takes the
+/// mean of the values on them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ColumnStats<T: Float = f32> {
+    /// Mean temperature in K, weighted by the pressure thickness of each layer
+    /// (i.e., by mass).
+    pub pressure_weighted_temperature: T,
+    /// Mean temperature in K, weighted by the water vapor mass in each layer.
+    /// This is NaN for a completely dry column.
+    pub vapor_weighted_temperature: T,
+    /// Total column water vapor in kg/m².
+    pub water_vapor: T,
+    /// Total column cloud liquid water in kg/m².
+    pub liquid_water: T,
+}
+
+impl<T: Float> RtmInputs<T> {
+    /// Compute the column summary statistics.
+    pub fn column_stats(&self) -> ColumnStats<T> {
+        /// Standard acceleration of gravity in m/s²
+        const G: f32 = 9.80665;
+        let c = T::lit;
+        let half = c(0.5);
+
+        let levels = self.surface_index..self.num_levels.get() + 1;
+        let p = &self.pressure[levels.clone()];
+        let t = &self.temperature[levels.clone()];
+        let z = &self.height[levels.clone()];
+        let rho_l = &self.rho_l[levels.clone()];
+        let q: Vec<T> = self.vapor_pressure[levels]
+            .iter()
+            .zip(p)
+            .map(|(&pv, &p)| vapor_pressure_to_specific_humidity(pv, p))
+            .collect();
+
+        let mut mass = T::zero();
+        let mut mass_temperature = T::zero();
+        let mut water_vapor = T::zero();
+        let mut vapor_temperature = T::zero();
+        let mut liquid_water = T::zero();
+        for i in 1..p.len() {
+            let t_avg = half * (t[i - 1] + t[i]);
+            // Layer mass per unit area in kg/m², from hPa
+            let layer_mass = (p[i - 1] - p[i]) * c(100.) / c(G);
+            let layer_vapor = half * (q[i - 1] + q[i]) * layer_mass;
+
+            mass = mass + layer_mass;
+            mass_temperature = mass_temperature + t_avg * layer_mass;
+            water_vapor = water_vapor + layer_vapor;
+            vapor_temperature = vapor_temperature + t_avg * layer_vapor;
+            // From g/m³ to kg/m³, times the layer thickness in m
+            liquid_water =
+                liquid_water + half * (rho_l[i - 1] + rho_l[i]) * c(1e-3) * (z[i] - z[i - 1]);
+        }
+
+        ColumnStats {
+            pressure_weighted_temperature: mass_temperature / mass,
+            vapor_weighted_temperature: vapor_temperature / water_vapor,
+            water_vapor,
+            liquid_water,
+        }
+    }
+}
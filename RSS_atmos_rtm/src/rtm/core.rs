@@ -1,42 +1,151 @@
 //! Core atmosphere RTM functions.
 
-use smallvec::SmallVec;
-
 use super::{
-    liquid_cloud::fdcldabs, oxygen::fdabsoxy_1992_modified, water_vapor::abh2o_rk_modified,
+    geometry::slant_path_factor,
+    liquid_cloud::fdcldabs,
+    oxygen::{fdabsoxy_1992_modified, OxygenCoefficients},
+    water_vapor::{abh2o_rk_modified, WaterVaporCoefficients},
+    AbsorptionScale, Float, IntegrationScheme, LayerSumReference, LevelVec, REFERENCE_TEMPERATURE,
 };
 
+/// The line coefficients of the oxygen and water vapor absorption models, and
+/// the reference temperature in K that they're tabulated at.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LineCoefficients<'a> {
+    pub(crate) oxygen: &'a OxygenCoefficients,
+    pub(crate) water_vapor: &'a WaterVaporCoefficients,
+    pub(crate) reference_temperature: f32,
+}
+
+impl LineCoefficients<'static> {
+    /// The coefficients as published.
+    pub(crate) fn builtin() -> Self {
+        Self {
+            oxygen: OxygenCoefficients::builtin(),
+            water_vapor: WaterVaporCoefficients::builtin(),
+            reference_temperature: REFERENCE_TEMPERATURE,
+        }
+    }
+}
+
+/// Scaling factor to convert from dB/km to Np/km: `0.1 * ln(10)`
+pub(crate) const NEP_SCALE: f32 = 0.1 * std::f32::consts::LN_10;
+
 /// Compute the absorption coefficient for an atmospheric layer.
 ///
 /// For a pressure (hPa), temperature (K), water vapor partial pressure (hPa),
 /// liquid water density (g/m³), compute the layer absorption coefficient in
-/// Np/m.
+/// Np/m, with each absorber scaled by `scale`.
 ///
 /// This is a wrapper to the lower-level absorption coefficient functions.
-pub(crate) fn layer_absorption(
-    pressure: f32,
-    temperature: f32,
-    vapor_pressure: f32,
-    liquid_water_density: f32,
-    frequency: f32,
-) -> f32 {
-    /// Scaling factor to convert from dB/km to Np/km: `0.1 * ln(10)`
-    const NEP_SCALE: f32 = 0.1 * std::f32::consts::LN_10;
+pub(crate) fn layer_absorption<T: Float>(
+    pressure: T,
+    temperature: T,
+    vapor_pressure: T,
+    liquid_water_density: T,
+    frequency: T,
+    scale: &AbsorptionScale,
+) -> T {
+    layer_absorption_with(
+        pressure,
+        temperature,
+        vapor_pressure,
+        liquid_water_density,
+        frequency,
+        scale,
+        LineCoefficients::builtin(),
+    )
+}
+
+/// [`layer_absorption`] with the given line coefficients.
+pub(crate) fn layer_absorption_with<T: Float>(
+    pressure: T,
+    temperature: T,
+    vapor_pressure: T,
+    liquid_water_density: T,
+    frequency: T,
+    scale: &AbsorptionScale,
+    lines: LineCoefficients<'_>,
+) -> T {
+    let [oxygen, water, cloud] = absorption_components_with(
+        pressure,
+        temperature,
+        vapor_pressure,
+        liquid_water_density,
+        frequency,
+        scale,
+        lines,
+    );
+
+    // Total absorption coefficient at this level, converting from Np/km to Np/m
+    (water + oxygen + cloud) * T::lit(1.0e-3)
+}
+
+/// Compute the absorption coefficients of oxygen, water vapor, and cloud
+/// liquid water for an atmospheric layer, in Np/km.
+///
+/// The inputs are the same as for [`layer_absorption`], which sums these. Each
+/// is scaled by its factor in `scale`, which is exact for a factor of 1.
+pub(crate) fn absorption_components<T: Float>(
+    pressure: T,
+    temperature: T,
+    vapor_pressure: T,
+    liquid_water_density: T,
+    frequency: T,
+    scale: &AbsorptionScale,
+) -> [T; 3] {
+    absorption_components_with(
+        pressure,
+        temperature,
+        vapor_pressure,
+        liquid_water_density,
+        frequency,
+        scale,
+        LineCoefficients::builtin(),
+    )
+}
+
+/// [`absorption_components`] with the given line coefficients.
+fn absorption_components_with<T: Float>(
+    pressure: T,
+    temperature: T,
+    vapor_pressure: T,
+    liquid_water_density: T,
+    frequency: T,
+    scale: &AbsorptionScale,
+    lines: LineCoefficients<'_>,
+) -> [T; 3] {
+    let c = T::lit;
 
     // Water vapor and oxygen absorption coefficients at this level converted to Np/km
-    let oxygen =
-        fdabsoxy_1992_modified(pressure, temperature, vapor_pressure, frequency) * NEP_SCALE;
-    let water = abh2o_rk_modified(pressure, temperature, vapor_pressure, frequency) * NEP_SCALE;
+    let oxygen = fdabsoxy_1992_modified(
+        lines.oxygen,
+        pressure,
+        temperature,
+        vapor_pressure,
+        frequency,
+        lines.reference_temperature,
+    ) * T::from_f64_lossy(scale.oxygen)
+        * c(NEP_SCALE);
+    let water = abh2o_rk_modified(
+        lines.water_vapor,
+        pressure,
+        temperature,
+        vapor_pressure,
+        frequency,
+        scale.water_vapor_lines,
+        scale.water_vapor_continuum,
+        lines.reference_temperature,
+    ) * c(NEP_SCALE);
 
     // Cloud absorption coefficient in Np/km
-    let cloud = if liquid_water_density > 1.0e-7 {
-        fdcldabs(frequency, temperature, liquid_water_density)
+    let cloud = if liquid_water_density > c(1.0e-7) {
+        fdcldabs(frequency, temperature, liquid_water_density) * T::from_f64_lossy(scale.cloud)
     } else {
-        0.0
+        T::zero()
     };
 
-    // Total absorption coefficient at this level, converting from Np/km to Np/m
-    (water + oxygen + cloud) * 1.0e-3
+    [oxygen, water, cloud]
 }
 
 /// Compute total atmospheric parameters from level data.
@@ -51,41 +160,506 @@ pub(crate) fn layer_absorption(
 /// The three profile inputs (`t`, `z`, and `tabs`) all have the same length,
 /// `num_levels + 1`, where the first index `0` is the value at the surface and
 /// indices from `1` to `num_levels` are profile data above the surface.
-pub(crate) fn atm_tran(inc: f32, t: &[f32], z: &[f32], tabs: &[f32]) -> (f32, f32, f32) {
-    const DELTA: f32 = 0.00035;
+/// The opacity of each layer is for its thickness in `z`, so the lowest layer
+/// only counts the part of the layer between the surface and the first level
+/// above it, however close the surface is to that level.
+///
+/// The layers are integrated in double precision whatever the precision `T`,
+/// and only the outputs are rounded to `T`. In single precision, the sums over
+/// a long profile otherwise lose a few significant digits.
+///
+/// The layer emission is summed relative to the default
+/// [`LayerSumReference`].
+pub(crate) fn atm_tran<T: Float>(inc: T, t: &[T], z: &[T], tabs: &[T]) -> (T, T, T) {
+    integrate(
+        IntegrationScheme::default(),
+        LayerSumReference::default().temperature(t),
+        inc,
+        t,
+        z,
+        tabs,
+    )
+}
+
+/// [`atm_tran`] with the layers integrated by the given [`IntegrationScheme`],
+/// and summed relative to the given [`LayerSumReference`].
+pub(crate) fn atm_tran_with_scheme<T: Float>(
+    scheme: IntegrationScheme,
+    reference: LayerSumReference,
+    inc: T,
+    t: &[T],
+    z: &[T],
+    tabs: &[T],
+) -> (T, T, T) {
+    integrate(scheme, reference.temperature(t), inc, t, z, tabs)
+}
 
-    // Differential slant height
-    let dsdh = (1.0 + DELTA) / f32::sqrt(inc.to_radians().cos().powi(2) + DELTA * (2.0 + DELTA));
+/// The downwelling brightness temperature in K at `level` from the layers
+/// above it, with the layers integrated by the given [`IntegrationScheme`].
+///
+/// The inputs are the same as for [`atm_tran_with_scheme`], and `level`
+/// indexes them, so at the surface (index `0`) this is the same as `tb_down`
+/// from [`atm_tran_with_scheme`], and at the top level it's 0 K. The layers
+/// above the level are integrated as if it were the surface.
+pub(crate) fn down_at_level<T: Float>(
+    scheme: IntegrationScheme,
+    reference: LayerSumReference,
+    inc: T,
+    t: &[T],
+    z: &[T],
+    tabs: &[T],
+    level: usize,
+) -> T {
+    if level + 1 >= t.len() {
+        return T::zero();
+    }
+    let (_, _, tb_down) = integrate(
+        scheme,
+        reference.temperature(&t[level..]),
+        inc,
+        &t[level..],
+        &z[level..],
+        &tabs[level..],
+    );
+    tb_down
+}
 
+/// The layer sums of [`atm_tran`], for a given [`IntegrationScheme`] and
+/// reference temperature `t_ref` in K.
+fn integrate<T: Float>(
+    scheme: IntegrationScheme,
+    t_ref: f64,
+    inc: T,
+    t: &[T],
+    z: &[T],
+    tabs: &[T],
+) -> (T, T, T) {
     // Number of levels *not* including the surface
     let num_levels = t.len() - 1;
+    let layers = Layers::new(inc, t, z, tabs);
+    let (t_up, t_down) = layers.source_temperatures(scheme, t);
+    let Layers { opacity, ems, .. } = layers;
 
-    let opacity: SmallVec<[f32; 64]> = (1..=num_levels)
-        .map(|i| -dsdh * 0.5 * (tabs[i - 1] + tabs[i]) * (z[i] - z[i - 1]))
-        .collect();
-    let t_avg: SmallVec<[f32; 64]> = (1..=num_levels).map(|i| 0.5 * (t[i - 1] + t[i])).collect();
-    let ems: SmallVec<[f32; 64]> = opacity.iter().map(|opacity| 1.0 - opacity.exp()).collect();
-
-    let (sum_down, _sum_op) = (1..=num_levels).fold((0., 0.), |(sum_down, sum_op), i| {
+    let (sum_down, _sum_op) = (1..=num_levels).fold((0_f64, 0_f64), |(sum_down, sum_op), i| {
         (
-            sum_down + (t_avg[i - 1] - t[1]) * ems[i - 1] * f32::exp(sum_op),
+            sum_down + (t_down[i - 1] - t_ref) * ems[i - 1] * sum_op.exp(),
             sum_op + opacity[i - 1],
         )
     });
 
     let (sum_up, sum_op) = (1..=num_levels)
         .rev()
-        .fold((0., 0.), |(sum_up, sum_op), i| {
+        .fold((0_f64, 0_f64), |(sum_up, sum_op), i| {
+            (
+                sum_up + (t_up[i - 1] - t_ref) * ems[i - 1] * sum_op.exp(),
+                sum_op + opacity[i - 1],
+            )
+        });
+
+    let tran = sum_op.exp();
+    let tb_avg = (1. - tran) * t_ref;
+    let tb_down = tb_avg + sum_down;
+    let tb_up = tb_avg + sum_up;
+
+    (
+        T::from_f64_lossy(tran),
+        T::from_f64_lossy(tb_up),
+        T::from_f64_lossy(tb_down),
+    )
+}
+
+/// Partial sums of [`atm_tran`] over the layers below a split level, in double
+/// precision.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PartialSums {
+    /// Reference temperature in K that the layer emission is relative to.
+    t_ref: f64,
+    /// Downwelling from the layers, before attenuation by any layers below.
+    sum_down: f64,
+    /// Total opacity of the layers.
+    sum_op: f64,
+    /// Upwelling from the layers, before attenuation by any layers above.
+    sum_up: f64,
+}
+
+/// Integrate the layers of [`atm_tran_with_scheme`] from the surface up to a
+/// split level.
+///
+/// The inputs are the same as for [`atm_tran_with_scheme`], but only up to
+/// and including the split level, which must be at least index `1`. The upper
+/// layers are then added by [`seeded_atm_tran`].
+pub(crate) fn lower_sums<T: Float>(
+    scheme: IntegrationScheme,
+    reference: LayerSumReference,
+    inc: T,
+    t: &[T],
+    z: &[T],
+    tabs: &[T],
+) -> PartialSums {
+    let num_levels = t.len() - 1;
+    let t_ref = reference.temperature(t);
+    let layers = Layers::new(inc, t, z, tabs);
+    let (t_up, t_down) = layers.source_temperatures(scheme, t);
+    let Layers { opacity, ems, .. } = layers;
+
+    let (sum_down, sum_op) = (1..=num_levels).fold((0_f64, 0_f64), |(sum_down, sum_op), i| {
+        (
+            sum_down + (t_down[i - 1] - t_ref) * ems[i - 1] * sum_op.exp(),
+            sum_op + opacity[i - 1],
+        )
+    });
+    let (sum_up, _sum_op) = (1..=num_levels)
+        .rev()
+        .fold((0_f64, 0_f64), |(sum_up, sum_op), i| {
             (
-                sum_up + (t_avg[i - 1] - t[1]) * ems[i - 1] * f32::exp(sum_op),
+                sum_up + (t_up[i - 1] - t_ref) * ems[i - 1] * sum_op.exp(),
                 sum_op + opacity[i - 1],
             )
         });
 
+    PartialSums {
+        t_ref,
+        sum_down,
+        sum_op,
+        sum_up,
+    }
+}
+
+/// Compute the outputs of [`atm_tran_with_scheme`] from the partial sums of
+/// the layers below a split level and the profiles from the split level
+/// upward.
+///
+/// The upper profiles start at the split level (the last level of the inputs
+/// to [`lower_sums`]) and go to the top, and the scheme must be the one the
+/// lower sums were computed with. The result is the same as
+/// [`atm_tran_with_scheme`] on the whole profile, to within the floating-point
+/// rounding from summing in a different order.
+pub(crate) fn seeded_atm_tran<T: Float>(
+    scheme: IntegrationScheme,
+    inc: T,
+    lower: &PartialSums,
+    t: &[T],
+    z: &[T],
+    tabs: &[T],
+) -> (T, T, T) {
+    let num_levels = t.len() - 1;
+    let t_ref = lower.t_ref;
+    let layers = Layers::new(inc, t, z, tabs);
+    let (t_up, t_down) = layers.source_temperatures(scheme, t);
+    let Layers { opacity, ems, .. } = layers;
+
+    let (sum_down, sum_op) =
+        (1..=num_levels).fold((lower.sum_down, lower.sum_op), |(sum_down, sum_op), i| {
+            (
+                sum_down + (t_down[i - 1] - t_ref) * ems[i - 1] * sum_op.exp(),
+                sum_op + opacity[i - 1],
+            )
+        });
+    let (sum_up, upper_op) = (1..=num_levels)
+        .rev()
+        .fold((0_f64, 0_f64), |(sum_up, sum_op), i| {
+            (
+                sum_up + (t_up[i - 1] - t_ref) * ems[i - 1] * sum_op.exp(),
+                sum_op + opacity[i - 1],
+            )
+        });
+    let sum_up = sum_up + lower.sum_up * upper_op.exp();
+
     let tran = sum_op.exp();
-    let tb_avg = (1. - tran) * t[1];
+    let tb_avg = (1. - tran) * t_ref;
     let tb_down = tb_avg + sum_down;
     let tb_up = tb_avg + sum_up;
 
-    (tran, tb_up, tb_down)
+    (
+        T::from_f64_lossy(tran),
+        T::from_f64_lossy(tb_up),
+        T::from_f64_lossy(tb_down),
+    )
+}
+
+/// Compute the derivatives of the outputs of [`atm_tran_with_scheme`] with
+/// respect to the temperature at the surface.
+///
+/// The inputs are the same as for [`atm_tran_with_scheme`], plus the
+/// derivatives with respect to the surface temperature of `t[0]` (`dt0`, which
+/// is 1 unless the emission is not in K) and `tabs[0]` (`dtabs0`, in Np/m/K).
+/// Only the lowest layer depends on the surface temperature, both through its
+/// source temperatures and through its opacity. The output tuple is the
+/// derivatives of (`tran`, `tb_up`, `tb_down`).
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn surface_derivatives<T: Float>(
+    scheme: IntegrationScheme,
+    reference: LayerSumReference,
+    inc: T,
+    t: &[T],
+    z: &[T],
+    tabs: &[T],
+    dt0: T,
+    dtabs0: T,
+) -> (T, T, T) {
+    let num_levels = t.len() - 1;
+    let layers = Layers::new(inc, t, z, tabs);
+    let (t_up, t_down) = layers.source_temperatures(scheme, t);
+    let Layers { opacity, ems, .. } = layers;
+    let t_ref = reference.temperature(t);
+    let (dt0, dtabs0) = (dt0.into_f64(), dtabs0.into_f64());
+
+    // The lowest layer's opacity is proportional to its mean absorption
+    let dopacity = opacity[0] * dtabs0 / (tabs[0].into_f64() + tabs[1].into_f64());
+    let dems = -opacity[0].exp() * dopacity;
+
+    let total_op: f64 = opacity.iter().sum();
+    let tran = total_op.exp();
+    let dtran = tran * dopacity;
+
+    // The derivatives of the upward and downward source temperatures of the
+    // lowest layer, through the surface temperature and, for a temperature
+    // linear in optical depth, through the weight of the far side
+    let (dt_up, dt_down) = match scheme {
+        IntegrationScheme::Trapezoidal => (0.5 * dt0, 0.5 * dt0),
+        IntegrationScheme::LinearInOpticalDepth => {
+            let (bottom, top) = (t[0].into_f64(), t[1].into_f64());
+            let weight = far_side_weight(-opacity[0]);
+            let dweight = -far_side_weight_derivative(-opacity[0]) * dopacity;
+            (
+                weight * dt0 + dweight * (bottom - top),
+                (1. - weight) * dt0 + dweight * (top - bottom),
+            )
+        }
+    };
+    // The lowest layer's term in each sum
+    let dlowest_up = dt_up * ems[0] + (t_up[0] - t_ref) * dems;
+    let dlowest_down = dt_down * ems[0] + (t_down[0] - t_ref) * dems;
+
+    // The downwelling from the layers above the lowest, which is attenuated by
+    // the lowest layer
+    let (sum_above, _sum_op) = (2..=num_levels).fold((0., opacity[0]), |(sum_down, sum_op), i| {
+        (
+            sum_down + (t_down[i - 1] - t_ref) * ems[i - 1] * sum_op.exp(),
+            sum_op + opacity[i - 1],
+        )
+    });
+
+    let dtb_up = -dtran * t_ref + dlowest_up * (total_op - opacity[0]).exp();
+    let dtb_down = -dtran * t_ref + dlowest_down + dopacity * sum_above;
+
+    (
+        T::from_f64_lossy(dtran),
+        T::from_f64_lossy(dtb_up),
+        T::from_f64_lossy(dtb_down),
+    )
+}
+
+/// Compute each layer's contribution to the upwelling brightness temperature.
+///
+/// The inputs are the same as for [`atm_tran_with_scheme`]. The output has a
+/// length of `num_levels`, where index `i` is for the layer between profile
+/// indices `i` and `i + 1`. Each value is the layer emission times the
+/// transmissivity from the top of the layer to space, in K. These sum to
+/// `tb_up` from [`atm_tran_with_scheme`] with the same scheme, since the sum of
+/// the layer emissivities weighted by their transmissivities to space is `1 -
+/// tran`.
+pub(crate) fn up_contributions<T: Float>(
+    scheme: IntegrationScheme,
+    inc: T,
+    t: &[T],
+    z: &[T],
+    tabs: &[T],
+) -> LevelVec<T> {
+    let num_levels = t.len() - 1;
+    let layers = Layers::new(inc, t, z, tabs);
+    let (t_up, _) = layers.source_temperatures(scheme, t);
+    let Layers { opacity, ems, .. } = layers;
+
+    let mut contributions: LevelVec<T> = LevelVec::from_elem(T::zero(), num_levels);
+    (1..=num_levels).rev().fold(0., |sum_op: f64, i| {
+        contributions[i - 1] = T::from_f64_lossy(t_up[i - 1] * ems[i - 1] * sum_op.exp());
+        sum_op + opacity[i - 1]
+    });
+
+    contributions
+}
+
+/// Compute the transmissivity to space and the upwelling brightness
+/// temperature from above at each profile level.
+///
+/// The inputs are the same as for [`atm_tran_with_scheme`]. The two outputs
+/// each have a length of `num_levels + 1`, matching the inputs. At the surface
+/// (index `0`), these are the same as `tran` and `tb_up` from
+/// [`atm_tran_with_scheme`] with the same scheme, and at the top level they
+/// are 1 and 0 K, respectively.
+pub(crate) fn level_profiles<T: Float>(
+    scheme: IntegrationScheme,
+    inc: T,
+    t: &[T],
+    z: &[T],
+    tabs: &[T],
+) -> (LevelVec<T>, LevelVec<T>) {
+    let num_levels = t.len() - 1;
+    let layers = Layers::new(inc, t, z, tabs);
+    let (t_up, _) = layers.source_temperatures(scheme, t);
+    let Layers { opacity, ems, .. } = layers;
+
+    let mut tran_to_space: LevelVec<T> = LevelVec::from_elem(T::one(), num_levels + 1);
+    let mut tb_up_above: LevelVec<T> = LevelVec::from_elem(T::zero(), num_levels + 1);
+    (1..=num_levels)
+        .rev()
+        .fold((0_f64, 0_f64), |(sum_up, sum_op): (f64, f64), i| {
+            let sum_up = sum_up + t_up[i - 1] * ems[i - 1] * sum_op.exp();
+            let sum_op = sum_op + opacity[i - 1];
+            tran_to_space[i - 1] = T::from_f64_lossy(sum_op.exp());
+            tb_up_above[i - 1] = T::from_f64_lossy(sum_up);
+            (sum_up, sum_op)
+        });
+
+    (tran_to_space, tb_up_above)
+}
+
+/// Compute the effective temperatures of the atmosphere: the upwelling and
+/// downwelling brightness temperatures divided by the total emissivity of the
+/// atmosphere, `1 - tran`.
+///
+/// The inputs are the same as for [`atm_tran_with_scheme`], and the output
+/// tuple is (`tb_up / (1 - tran)`, `tb_down / (1 - tran)`) in K. These are computed as
+/// the temperature of the first level plus the layer sums over `1 - tran`, so
+/// the cancellation in `tb_up` and `1 - tran` as `tran` goes to 1 doesn't
+/// happen. The layer emissivities and the total emissivity both use `expm1`
+/// for the same reason, so the effective temperatures go smoothly to the
+/// opacity-weighted mean layer temperature for a transparent atmosphere. With
+/// no absorption at all, they are NaN.
+pub(crate) fn effective_temperatures<T: Float>(
+    scheme: IntegrationScheme,
+    reference: LayerSumReference,
+    inc: T,
+    t: &[T],
+    z: &[T],
+    tabs: &[T],
+) -> (T, T) {
+    let num_levels = t.len() - 1;
+    let layers = Layers::new(inc, t, z, tabs);
+    let (t_up, t_down) = layers.source_temperatures(scheme, t);
+    let opacity = layers.opacity;
+    let ems: LevelVec<f64> = opacity.iter().map(|opacity| -opacity.exp_m1()).collect();
+    let t_ref = reference.temperature(t);
+
+    let (sum_down, _sum_op) = (1..=num_levels).fold((0_f64, 0_f64), |(sum_down, sum_op), i| {
+        (
+            sum_down + (t_down[i - 1] - t_ref) * ems[i - 1] * sum_op.exp(),
+            sum_op + opacity[i - 1],
+        )
+    });
+    let (sum_up, sum_op) = (1..=num_levels)
+        .rev()
+        .fold((0_f64, 0_f64), |(sum_up, sum_op), i| {
+            (
+                sum_up + (t_up[i - 1] - t_ref) * ems[i - 1] * sum_op.exp(),
+                sum_op + opacity[i - 1],
+            )
+        });
+
+    let emissivity = -sum_op.exp_m1();
+    (
+        T::from_f64_lossy(t_ref + sum_up / emissivity),
+        T::from_f64_lossy(t_ref + sum_down / emissivity),
+    )
+}
+
+/// Per-layer quantities along the slant path, in double precision.
+struct Layers {
+    /// Layer opacity (negative optical depth) along the slant path
+    opacity: LevelVec<f64>,
+    /// Mean layer temperature in K
+    t_avg: LevelVec<f64>,
+    /// Layer emissivity
+    ems: LevelVec<f64>,
+}
+
+impl Layers {
+    /// Compute the per-layer quantities. The inputs are the same as for
+    /// [`atm_tran`].
+    fn new<T: Float>(inc: T, t: &[T], z: &[T], tabs: &[T]) -> Self {
+        // Differential slant height
+        let dsdh = slant_path_factor(inc.into_f64());
+
+        // Number of levels *not* including the surface
+        let num_levels = t.len() - 1;
+
+        let opacity: LevelVec<f64> = (1..=num_levels)
+            .map(|i| {
+                -dsdh
+                    * 0.5
+                    * (tabs[i - 1].into_f64() + tabs[i].into_f64())
+                    * (z[i].into_f64() - z[i - 1].into_f64())
+            })
+            .collect();
+        let t_avg: LevelVec<f64> = (1..=num_levels)
+            .map(|i| 0.5 * (t[i - 1].into_f64() + t[i].into_f64()))
+            .collect();
+        let ems: LevelVec<f64> = opacity.iter().map(|opacity| 1.0 - opacity.exp()).collect();
+
+        Self {
+            opacity,
+            t_avg,
+            ems,
+        }
+    }
+
+    /// The temperatures in K that each layer emits upward and downward at,
+    /// for the profile `t` the layers were computed from.
+    ///
+    /// With [`IntegrationScheme::Trapezoidal`], these are both the mean layer
+    /// temperature. With [`IntegrationScheme::LinearInOpticalDepth`], the
+    /// temperature varies linearly in optical depth `τ` across the layer, and
+    /// integrating it against the attenuation within the layer weights the
+    /// temperature at the far side by `1/τ - 1/(exp(τ) - 1)` and the near side
+    /// by the rest. The weight goes to 1/2 as `τ` goes to 0, where the schemes
+    /// agree, and to 0 for an opaque layer, which emits at the temperature of
+    /// the side it's seen from.
+    fn source_temperatures<T: Float>(
+        &self,
+        scheme: IntegrationScheme,
+        t: &[T],
+    ) -> (LevelVec<f64>, LevelVec<f64>) {
+        match scheme {
+            IntegrationScheme::Trapezoidal => (self.t_avg.clone(), self.t_avg.clone()),
+            IntegrationScheme::LinearInOpticalDepth => self
+                .opacity
+                .iter()
+                .enumerate()
+                .map(|(i, &opacity)| {
+                    let (bottom, top) = (t[i].into_f64(), t[i + 1].into_f64());
+                    let weight = far_side_weight(-opacity);
+                    // Upward emission is seen from the top, downward from the
+                    // bottom
+                    (
+                        top + weight * (bottom - top),
+                        bottom + weight * (top - bottom),
+                    )
+                })
+                .unzip(),
+        }
+    }
+}
+
+/// The weight of the far side of a layer with optical depth `tau` when its
+/// temperature is linear in optical depth, `1/τ - 1/(exp(τ) - 1)`.
+///
+/// For a thin layer, the difference cancels, so it is replaced by its series,
+/// which is accurate to better than 1e-14 there.
+fn far_side_weight(tau: f64) -> f64 {
+    if tau < 1e-2 {
+        0.5 - tau / 12. + tau.powi(3) / 720.
+    } else {
+        tau.recip() - tau.exp_m1().recip()
+    }
+}
+
+/// The derivative of [`far_side_weight`] with respect to `tau`, `exp(τ) /
+/// (exp(τ) - 1)^2 - 1/τ^2`, with its series for a thin layer in the same way.
+fn far_side_weight_derivative(tau: f64) -> f64 {
+    if tau < 1e-2 {
+        -1. / 12. + tau.powi(2) / 240.
+    } else {
+        tau.exp() / tau.exp_m1().powi(2) - tau.powi(-2)
+    }
 }
@@ -0,0 +1,221 @@
+//! Estimates of the memory and run time of a batch before running it.
+
+use std::{mem::size_of, num::NonZeroUsize, time::Instant};
+
+use super::{
+    standard_atmospheres, Float, ProfileRef, ProfileSource, RtmError, RtmOutputs, RtmParameters,
+};
+
+/// Maximum number of points that are run to calibrate the time per point.
+const MAX_CALIBRATION_POINTS: usize = 100;
+
+/// The estimated cost of running the RTM for a batch of points, from
+/// [`estimate_cost`] or [`estimate_cost_for_shape`].
+///
+/// The memory is for the RTM itself, and doesn't include the inputs or any
+/// copies made by the caller.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CostEstimate {
+    /// Number of points in the batch.
+    pub num_points: usize,
+    /// Memory in bytes of the `tran`, `tb_up`, and `tb_down` output arrays.
+    pub output_bytes: usize,
+    /// Peak memory in bytes besides the outputs: the [`RtmOutputs`] of every
+    /// point, which are collected before they're copied to the output arrays,
+    /// and the prepared inputs and absorption profile of the point that each
+    /// thread is working on.
+    pub peak_working_bytes: usize,
+    /// Wall-clock time in seconds for the whole batch, assuming the time per
+    /// point scales perfectly with the number of threads.
+    pub wall_seconds: f64,
+    /// Time in seconds to prepare and run a single point on one thread, from
+    /// the calibration run.
+    pub seconds_per_point: f64,
+    /// Number of points in the calibration run.
+    pub num_calibration_points: usize,
+    /// Number of worker threads the wall time is for.
+    pub num_threads: usize,
+}
+
+/// Estimate the memory and run time of running the RTM for all of the
+/// `profiles` with `parameters`, on `num_threads` threads (or the
+/// [`adaptive_num_threads`](super::adaptive_num_threads) for the batch and the
+/// [`items_per_thread`](RtmParameters::items_per_thread) of `parameters`, if
+/// it's `None`, or one without the `parallel` feature), e.g., to choose chunk
+/// sizes.
+///
+/// Up to 100 evenly spaced points of `profiles` are run one at a time on the
+/// calling thread to calibrate the time per point, including the points that
+/// fail to prepare, as they would in the real run. The wall time is
+/// extrapolated from that, so it's only as representative as those points are,
+/// and is optimistic if the threads share cores or memory bandwidth.
+///
+/// Returns [`RtmError::InconsistentInputs`] if there are no profiles.
+pub fn estimate_cost<T, S>(
+    profiles: &S,
+    parameters: &RtmParameters<T>,
+    num_threads: Option<NonZeroUsize>,
+) -> Result<CostEstimate, RtmError>
+where
+    T: Float,
+    S: ProfileSource<T> + ?Sized,
+{
+    let num_points = profiles.len();
+    if num_points == 0 {
+        return Err(RtmError::InconsistentInputs);
+    }
+    let num_calibration_points = num_points.min(MAX_CALIBRATION_POINTS);
+    let indices = (0..num_calibration_points).map(|i| i * num_points / num_calibration_points);
+
+    let mut num_levels = 0;
+    let start = Instant::now();
+    for index in indices {
+        let profile = profiles.profile(index)?;
+        num_levels = num_levels.max(profile.pressure.len());
+        if let Ok(inputs) = profile.inputs() {
+            std::hint::black_box(inputs.run(parameters));
+        }
+    }
+    let seconds_per_point = start.elapsed().as_secs_f64() / num_calibration_points as f64;
+
+    Ok(extrapolate::<T>(
+        num_points,
+        num_levels,
+        parameters.len(),
+        num_threads,
+        parameters.items_per_thread(),
+        seconds_per_point,
+        num_calibration_points,
+    ))
+}
+
+/// Estimate the memory and run time of running the RTM, as in
+/// [`estimate_cost`], from only the shape of a batch of `num_points` points
+/// with `num_levels` levels and `num_freq` channels, in single precision.
+///
+/// The time per point is calibrated with 100 runs of the US Standard
+/// Atmosphere 1976 resampled to `num_levels` levels, with no clouds, and
+/// `num_freq` distinct frequencies from 20 GHz in steps of 1 GHz, so an
+/// absorption profile is computed for every channel. This is representative
+/// of clear-sky profiles from the surface to 80 km. Without `num_threads`, the
+/// number of threads is for the default
+/// [`DEFAULT_ITEMS_PER_THREAD`](super::DEFAULT_ITEMS_PER_THREAD).
+///
+/// Returns [`RtmError::InconsistentInputs`] if there are no points, fewer than
+/// 2 levels, or no channels.
+pub fn estimate_cost_for_shape(
+    num_points: usize,
+    num_levels: usize,
+    num_freq: usize,
+    num_threads: Option<NonZeroUsize>,
+) -> Result<CostEstimate, RtmError> {
+    if num_points == 0 || num_levels < 2 || num_freq == 0 {
+        return Err(RtmError::InconsistentInputs);
+    }
+    let frequency: Vec<f32> = (0..num_freq).map(|i| 20. + i as f32).collect();
+    let parameters = RtmParameters::new(&frequency, &vec![0.; num_freq])?;
+
+    // Resample the standard profile linearly in its level index, which keeps
+    // the pressures strictly decreasing
+    let profile = standard_atmospheres::US_STANDARD_1976.profile();
+    let resample = |values: &[f32]| -> Vec<f32> {
+        let last = values.len() - 1;
+        (0..num_levels)
+            .map(|j| {
+                let x = j as f64 * last as f64 / (num_levels - 1) as f64;
+                let i = (x.floor() as usize).min(last - 1);
+                let fraction = x - i as f64;
+                (f64::from(values[i]) * (1. - fraction) + f64::from(values[i + 1]) * fraction)
+                    as f32
+            })
+            .collect()
+    };
+    let pressure = resample(&profile.pressure);
+    let temperature = resample(&profile.temperature);
+    let height = resample(&profile.height);
+    let specific_humidity = resample(&profile.specific_humidity);
+    let calibration = RepeatedProfile {
+        profile: ProfileRef {
+            pressure: &pressure,
+            temperature: &temperature,
+            height: &height,
+            specific_humidity: &specific_humidity,
+            liquid_content: &[],
+            surface_temperature: profile.surface_temperature,
+            surface_height: profile.surface_height,
+            surface_dewpoint: profile.surface_dewpoint,
+            surface_pressure: Some(profile.surface_pressure),
+            skin_temperature: None,
+            observer_height: None,
+            top_pressure: None,
+        },
+        num_points: MAX_CALIBRATION_POINTS,
+    };
+    let estimate = estimate_cost(&calibration, &parameters, num_threads)?;
+
+    Ok(extrapolate::<f32>(
+        num_points,
+        num_levels,
+        num_freq,
+        num_threads,
+        parameters.items_per_thread(),
+        estimate.seconds_per_point,
+        estimate.num_calibration_points,
+    ))
+}
+
+/// Scale the calibrated time per point and the memory per point up to the
+/// whole batch.
+fn extrapolate<T: Float>(
+    num_points: usize,
+    num_levels: usize,
+    num_freq: usize,
+    num_threads: Option<NonZeroUsize>,
+    items_per_thread: NonZeroUsize,
+    seconds_per_point: f64,
+    num_calibration_points: usize,
+) -> CostEstimate {
+    // Without the `parallel` feature, the points are run one at a time
+    #[cfg(feature = "parallel")]
+    let num_threads = super::threads::num_threads_or_adaptive(
+        num_threads,
+        num_points,
+        num_freq,
+        items_per_thread,
+    )
+    .get();
+    #[cfg(not(feature = "parallel"))]
+    let num_threads = {
+        let _ = (num_threads, items_per_thread);
+        1
+    };
+    // The prepared pressure, temperature, vapor pressure, liquid water, and
+    // height profiles, plus the absorption profile, each with the surface
+    let per_thread = 6 * (num_levels + 1) * size_of::<T>();
+    CostEstimate {
+        num_points,
+        output_bytes: 3 * num_points * num_freq * size_of::<T>(),
+        peak_working_bytes: num_points * size_of::<RtmOutputs<T>>() + num_threads * per_thread,
+        wall_seconds: seconds_per_point * num_points as f64 / num_threads as f64,
+        seconds_per_point,
+        num_calibration_points,
+        num_threads,
+    }
+}
+
+/// The same profile for each of `num_points` points.
+struct RepeatedProfile<'a> {
+    profile: ProfileRef<'a>,
+    num_points: usize,
+}
+
+impl ProfileSource for RepeatedProfile<'_> {
+    fn len(&self) -> usize {
+        self.num_points
+    }
+
+    fn profile(&self, _index: usize) -> Result<ProfileRef<'_>, RtmError> {
+        Ok(self.profile)
+    }
+}
@@ -0,0 +1,64 @@
+//! Averages of the outputs over the points in a sensor footprint.
+
+use super::{Float, QualityFlags, RtmError, RtmOutputs};
+
+/// Average the outputs of the member points of a footprint, weighted by the
+/// antenna pattern.
+///
+/// Each of `members` is the index of a point in `outputs` and its weight. The
+/// weights are normalized to sum to 1, so they only need to be relative. The
+/// outputs are averaged rather than computed for the average profile, since
+/// the brightness temperatures are nonlinear in the profile. Members with a
+/// weight of zero are skipped, and the quality flags are those of all of the
+/// other members combined.
+///
+/// Returns `Ok(None)` if there are no members with a non-zero weight. Returns
+/// [`RtmError::InconsistentInputs`] if an index is out of range or the members
+/// don't all have the same number of channels, or
+/// [`RtmError::InvalidFootprint`] if a weight is negative or not finite.
+pub fn footprint_mean<T: Float>(
+    outputs: &[RtmOutputs<T>],
+    members: &[(usize, T)],
+) -> Result<Option<RtmOutputs<T>>, RtmError> {
+    let mut used = Vec::with_capacity(members.len());
+    for &(index, weight) in members {
+        // Written so that NaN is also rejected
+        if !(weight >= T::zero() && weight.is_finite()) {
+            return Err(RtmError::InvalidFootprint(format!(
+                "weight {weight} of point {index} is negative or not finite"
+            )));
+        }
+        let member = outputs.get(index).ok_or(RtmError::InconsistentInputs)?;
+        if weight > T::zero() {
+            used.push((member, weight.into_f64()));
+        }
+    }
+    let Some(&(first, _)) = used.first() else {
+        return Ok(None);
+    };
+    let num_freq = first.tran.len();
+    if used.iter().any(|(member, _)| member.tran.len() != num_freq) {
+        return Err(RtmError::InconsistentInputs);
+    }
+
+    let total: f64 = used.iter().map(|&(_, weight)| weight).sum();
+    let quality = used
+        .iter()
+        .fold(QualityFlags::empty(), |quality, (member, _)| {
+            quality | member.quality
+        });
+    let mut mean = RtmOutputs::new(quality);
+    let average = |channel: usize, field: fn(&RtmOutputs<T>) -> &[T]| {
+        let sum: f64 = used
+            .iter()
+            .map(|&(member, weight)| weight * field(member)[channel].into_f64())
+            .sum();
+        T::from_f64_lossy(sum / total)
+    };
+    for channel in 0..num_freq {
+        mean.tran.push(average(channel, |o| &o.tran));
+        mean.tb_up.push(average(channel, |o| &o.tb_up));
+        mean.tb_down.push(average(channel, |o| &o.tb_down));
+    }
+    Ok(Some(mean))
+}
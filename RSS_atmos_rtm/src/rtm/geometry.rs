@@ -0,0 +1,58 @@
+//! Scan geometry of a satellite sensor over a spherical Earth, and the slant
+//! path through the atmosphere.
+
+use super::Float;
+
+/// Convert the scan angle `scan_angle` from nadir in degrees of a satellite at
+/// a height `satellite_altitude` in m to the Earth incidence angle in degrees,
+/// for a spherical Earth with a radius `earth_radius` in m (normally
+/// [`EARTH_RADIUS`](super::EARTH_RADIUS)).
+///
+/// From the law of sines, `sin(eia) = (earth_radius + satellite_altitude) /
+/// earth_radius * sin(scan_angle)`. The incidence angle is always larger than
+/// the scan angle, and has the same sign. A scan angle past the limb, where
+/// the line of sight misses the Earth, gives NaN.
+pub fn eia_from_scan<T: Float>(scan_angle: T, satellite_altitude: T, earth_radius: T) -> T {
+    let ratio = orbit_ratio(satellite_altitude, earth_radius);
+    let sin_eia = ratio * scan_angle.into_f64().to_radians().sin();
+    T::from_f64_lossy(sin_eia.asin().to_degrees())
+}
+
+/// Convert the Earth incidence angle `incidence_angle` in degrees to the scan
+/// angle from nadir in degrees of a satellite at a height `satellite_altitude`
+/// in m, for a spherical Earth with a radius `earth_radius` in m. This is the
+/// inverse of [`eia_from_scan`].
+pub fn scan_from_eia<T: Float>(incidence_angle: T, satellite_altitude: T, earth_radius: T) -> T {
+    let ratio = orbit_ratio(satellite_altitude, earth_radius);
+    let sin_scan = incidence_angle.into_f64().to_radians().sin() / ratio;
+    T::from_f64_lossy(sin_scan.asin().to_degrees())
+}
+
+/// The ratio of the distance of the satellite from the center of the Earth to
+/// the radius of the Earth.
+fn orbit_ratio<T: Float>(satellite_altitude: T, earth_radius: T) -> f64 {
+    let earth_radius = earth_radius.into_f64();
+    (earth_radius + satellite_altitude.into_f64()) / earth_radius
+}
+
+/// The air-mass factor for an Earth incidence angle `incidence_angle` in
+/// degrees: the ratio of the slant optical depth to the zenith optical depth,
+/// as used by the RTM.
+///
+/// The RTM lengthens the path through every layer by the same factor, the
+/// slant path at a single effective height of about 2.2 km above a spherical
+/// Earth, so this doesn't depend on the profile or the frequency. For a
+/// plane-parallel atmosphere, it reduces to `sec(incidence_angle)`, and it's
+/// within 0.5% of that up to 75°. Unlike the secant, it stays finite at 90°.
+pub fn air_mass_factor<T: Float>(incidence_angle: T) -> T {
+    T::from_f64_lossy(slant_path_factor(incidence_angle.into_f64()))
+}
+
+/// The differential slant height `ds/dh` through a spherical shell at a height
+/// of `DELTA` times the Earth radius, for an Earth incidence angle `inc` in
+/// degrees.
+pub(crate) fn slant_path_factor(inc: f64) -> f64 {
+    const DELTA: f32 = 0.00035;
+    let delta = f64::from(DELTA);
+    (1.0 + delta) / (inc.to_radians().cos().powi(2) + delta * (2.0 + delta)).sqrt()
+}
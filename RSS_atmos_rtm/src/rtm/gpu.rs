@@ -0,0 +1,476 @@
+//! Computing the gas absorption on a GPU (experimental).
+//!
+//! For many channels, such as a hyperspectral simulation, the line-by-line
+//! oxygen and water vapor absorption dominates the run time. It's the same
+//! small computation for every level of every point at every frequency, with
+//! small constant tables of line coefficients, so with the `gpu` feature,
+//! [`GpuAbsorption`] evaluates it with a `wgpu` compute shader for a whole
+//! batch of points at once. The cloud absorption and the radiative transfer
+//! integration stay on the CPU.
+//!
+//! Select it with [`AbsorptionBackend::Gpu`] in the [`RtmOptions`] (or
+//! [`RtmParameters::with_absorption_backend`]), and [`compute_rtm_batch`] uses
+//! the GPU if there's an adapter, and otherwise falls back to the CPU. The
+//! outputs aren't bit-for-bit the same as on the CPU, since the shader is in
+//! single precision with the GPU's own `exp` and `pow`. They agree within:
+//!
+//! - a relative difference of 1e-4 in the gas absorption
+//! - 1e-4 in the transmissivity
+//! - 0.01 K in the brightness temperatures
+//!
+//! The GPU only pays off for large batches with many channels, since the
+//! inputs and outputs are copied to and from it. To find the crossover batch
+//! size on a machine, run:
+//!
+//! ```text
+//! cargo bench --bench gpu_crossover --no-default-features --features gpu
+//! ```
+//!
+//! A software adapter, such as Mesa's llvmpipe when there's no GPU, is used if
+//! it's the only one. It runs the shader on the CPU cores, so it's not much
+//! faster than the CPU path.
+//!
+//! [`RtmOptions`]: super::RtmOptions
+
+use std::{borrow::Cow, num::NonZeroUsize, sync::OnceLock};
+
+use rayon::prelude::*;
+use wgpu::util::DeviceExt;
+
+#[cfg(doc)]
+use super::{compute_rtm_batch, AbsorptionBackend};
+use super::{
+    core::NEP_SCALE, liquid_cloud::fdcldabs, oxygen::OxygenCoefficients, threads,
+    water_vapor::WaterVaporCoefficients, Float, LevelVec, RtmError, RtmInputs, RtmOutputs,
+    RtmParameters, FREQUENCY_LIMITS,
+};
+
+/// The compute shader.
+const SHADER: &str = include_str!("gpu.wgsl");
+
+/// Number of invocations in a workgroup of the shader.
+const WORKGROUP_SIZE: usize = 64;
+
+/// The most (level, frequency) pairs that [`GpuAbsorption::compute_rtm_batch`]
+/// computes the absorption for at once, which bounds the memory of a batch of
+/// points to a few hundred MB.
+const MAX_BATCH_PAIRS: usize = 1 << 24;
+
+/// The gas absorption models on a GPU, with the line coefficients uploaded.
+///
+/// Creating this finds an adapter and compiles the shader, so it should be
+/// reused; [`shared`](Self::shared) is the one used by [`compute_rtm_batch`].
+#[derive(Debug)]
+pub struct GpuAbsorption {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+    oxygen: wgpu::Buffer,
+    water_vapor: wgpu::Buffer,
+    adapter: wgpu::AdapterInfo,
+    /// The most pairs in one dispatch, for the size of the output buffer.
+    max_pairs: usize,
+    /// The most workgroups along each dimension of a dispatch.
+    max_workgroups: u32,
+}
+
+impl GpuAbsorption {
+    /// Find a GPU adapter, preferring a discrete GPU, and set up the shader.
+    ///
+    /// Returns `None` if there's no adapter that can run compute shaders, or
+    /// the device couldn't be created.
+    pub fn new() -> Option<Self> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            ..Default::default()
+        }))?;
+        let info = adapter.get_info();
+        if !adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+        {
+            log::debug!("GPU adapter {} can't run compute shaders", info.name);
+            return None;
+        }
+        let limits = adapter.limits();
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("rss_atmos_rtm"),
+                required_features: wgpu::Features::empty(),
+                required_limits: limits.clone(),
+                memory_hints: wgpu::MemoryHints::Performance,
+            },
+            None,
+        ))
+        .map_err(|e| {
+            log::debug!(
+                "couldn't create a device for GPU adapter {}: {e}",
+                info.name
+            )
+        })
+        .ok()?;
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("absorption"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(SHADER)),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("absorption"),
+            layout: None,
+            module: &module,
+            entry_point: Some("main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        let coefficients = |label, columns: [&[f32]; 7]| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents: bytemuck::cast_slice(&columns.concat()),
+                usage: wgpu::BufferUsages::STORAGE,
+            })
+        };
+        let oxygen = coefficients("oxygen", OxygenCoefficients::builtin().columns());
+        let water_vapor = coefficients("water_vapor", WaterVaporCoefficients::builtin().columns());
+
+        let max_binding =
+            u64::from(limits.max_storage_buffer_binding_size).min(limits.max_buffer_size);
+        log::info!("computing the gas absorption on GPU adapter {}", info.name);
+        Some(Self {
+            device,
+            queue,
+            pipeline,
+            oxygen,
+            water_vapor,
+            adapter: info,
+            max_pairs: usize::try_from(max_binding / 16).unwrap_or(usize::MAX),
+            max_workgroups: limits.max_compute_workgroups_per_dimension,
+        })
+    }
+
+    /// The GPU shared by every call to [`compute_rtm_batch`], which is
+    /// created on first use, or `None` if there's no adapter (which is logged
+    /// once).
+    pub fn shared() -> Option<&'static Self> {
+        static SHARED: OnceLock<Option<GpuAbsorption>> = OnceLock::new();
+        SHARED
+            .get_or_init(|| {
+                let gpu = Self::new();
+                if gpu.is_none() {
+                    log::warn!(
+                        "no GPU adapter was found, so the absorption is computed on the CPU"
+                    );
+                }
+                gpu
+            })
+            .as_ref()
+    }
+
+    /// The name of the GPU adapter.
+    pub fn adapter_name(&self) -> &str {
+        &self.adapter.name
+    }
+
+    /// Compute the oxygen absorption, water vapor line absorption, and water
+    /// vapor continuum absorption, in dB/km, for each of the `levels` at each
+    /// of the `frequencies` in GHz.
+    ///
+    /// Each level is the pressure in hPa, temperature in K, and water vapor
+    /// pressure in hPa. The result has a row for each level, with the
+    /// frequency varying fastest, and is unscaled: the water vapor absorption
+    /// of [`AbsorptionScale`](super::AbsorptionScale) is the line factor times
+    /// the line absorption plus the continuum factor times the continuum
+    /// absorption.
+    pub fn gas_absorption(&self, levels: &[[f32; 3]], frequencies: &[f32]) -> Vec<[f32; 3]> {
+        let num_freq = frequencies.len();
+        let mut absorption = Vec::with_capacity(levels.len() * num_freq);
+        if levels.is_empty() || num_freq == 0 {
+            return absorption;
+        }
+        let frequencies = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("frequencies"),
+                contents: bytemuck::cast_slice(frequencies),
+                usage: wgpu::BufferUsages::STORAGE,
+            });
+        let chunk_levels = (self.max_pairs / num_freq).max(1);
+        for levels in levels.chunks(chunk_levels) {
+            absorption.extend(
+                self.dispatch(levels, &frequencies, num_freq)
+                    .into_iter()
+                    .map(|[oxygen, lines, continuum, _]| [oxygen, lines, continuum]),
+            );
+        }
+        absorption
+    }
+
+    /// Run the shader for all of the `levels` at each frequency, and read back
+    /// the absorption.
+    fn dispatch(
+        &self,
+        levels: &[[f32; 3]],
+        frequencies: &wgpu::Buffer,
+        num_freq: usize,
+    ) -> Vec<[f32; 4]> {
+        let num_pairs = levels.len() * num_freq;
+        let levels: Vec<[f32; 4]> = levels.iter().map(|&[p, t, pv]| [p, t, pv, 0.]).collect();
+        let levels = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("levels"),
+                contents: bytemuck::cast_slice(&levels),
+                usage: wgpu::BufferUsages::STORAGE,
+            });
+        let params = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("params"),
+                contents: bytemuck::cast_slice(&[num_pairs as u32, num_freq as u32, 0, 0]),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+        let size = (num_pairs * 16) as u64;
+        let output = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("absorption"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let staging = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("staging"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("absorption"),
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                (0, &levels),
+                (1, frequencies),
+                (2, &self.oxygen),
+                (3, &self.water_vapor),
+                (4, &output),
+                (5, &params),
+            ]
+            .map(|(binding, buffer)| wgpu::BindGroupEntry {
+                binding,
+                resource: buffer.as_entire_binding(),
+            }),
+        });
+
+        // Spread the workgroups over two dimensions, since there may be more
+        // than fit in one
+        let num_workgroups = num_pairs.div_ceil(WORKGROUP_SIZE);
+        let x = num_workgroups.min(self.max_workgroups as usize);
+        let y = num_workgroups.div_ceil(x);
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(x as u32, y as u32, 1);
+        }
+        encoder.copy_buffer_to_buffer(&output, 0, &staging, 0, size);
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = staging.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |result| {
+            result.expect("couldn't read the absorption back from the GPU");
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        let absorption = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+        staging.unmap();
+        absorption
+    }
+
+    /// Apply the RTM to a batch of prepared inputs, as in
+    /// [`compute_rtm_batch`], with the gas absorption computed on the GPU.
+    ///
+    /// The points are run in batches of up to millions of levels and
+    /// frequencies, whose gas absorption is computed on the GPU at once. The
+    /// rest of each point is then run in parallel on `num_threads` worker
+    /// threads, as in [`compute_rtm_batch`]. A point with any non-finite
+    /// pressure, temperature, or vapor pressure is run entirely on the CPU.
+    ///
+    /// This uses the GPU whatever the
+    /// [`absorption_backend`](RtmParameters::absorption_backend) of the
+    /// parameters.
+    pub fn compute_rtm_batch<T: Float>(
+        &self,
+        inputs: &[RtmInputs<T>],
+        parameters: &RtmParameters<T>,
+        num_threads: Option<NonZeroUsize>,
+    ) -> Result<Vec<RtmOutputs<T>>, RtmError> {
+        let num_threads = threads::num_threads_or_adaptive(
+            num_threads,
+            inputs.len(),
+            parameters.len(),
+            parameters.items_per_thread(),
+        );
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(num_threads.get())
+            .build()
+            .map_err(RtmError::ThreadPool)?;
+
+        // The monochromatic channels, which are the nodes of the passbands if
+        // there are any, and the column of each in the distinct frequencies
+        let nodes = parameters.passband_nodes();
+        let channels = nodes.as_ref().unwrap_or(parameters);
+        let mut frequencies: Vec<T> = Vec::new();
+        let columns: Vec<usize> = channels
+            .frequency
+            .iter()
+            .map(|&freq| {
+                frequencies
+                    .iter()
+                    .position(|&f| f == freq)
+                    .unwrap_or_else(|| {
+                        frequencies.push(freq);
+                        frequencies.len() - 1
+                    })
+            })
+            .collect();
+        let gpu_frequencies: Vec<f32> = frequencies.iter().map(|f| f.into_f64() as f32).collect();
+        let num_freq = frequencies.len();
+        let num_pairs = |point: &RtmInputs<T>| point.num_gpu_levels() * num_freq;
+
+        let mut outputs = Vec::with_capacity(inputs.len());
+        let mut remaining = inputs;
+        while !remaining.is_empty() {
+            // Take points until the batch is full, but at least one
+            let mut batch_pairs = 0;
+            let batch_len = remaining
+                .iter()
+                .position(|point| {
+                    batch_pairs += num_pairs(point);
+                    batch_pairs > MAX_BATCH_PAIRS
+                })
+                .unwrap_or(remaining.len())
+                .max(1);
+            let (batch, rest) = remaining.split_at(batch_len);
+            remaining = rest;
+
+            // The offset of the absorption of each point that's run on the GPU
+            let mut levels = Vec::new();
+            let offsets: Vec<Option<usize>> = batch
+                .iter()
+                .map(|point| {
+                    let start = levels.len() * num_freq;
+                    let finite = point.gpu_levels().flatten().all(f32::is_finite);
+                    finite.then(|| {
+                        levels.extend(point.gpu_levels());
+                        start
+                    })
+                })
+                .collect();
+            let absorption = self.gas_absorption(&levels, &gpu_frequencies);
+
+            let run = |(point, offset): (&RtmInputs<T>, Option<usize>)| {
+                let Some(offset) = offset else {
+                    return point.run(parameters);
+                };
+                let gas = &absorption[offset..offset + num_pairs(point)];
+                let run_nodes = |channels: &RtmParameters<T>| {
+                    point.run_with_gas_absorption(channels, &columns, num_freq, gas)
+                };
+                point
+                    .run_passbands(parameters, run_nodes)
+                    .unwrap_or_else(|| run_nodes(parameters))
+            };
+            let batch_outputs: Vec<_> = pool.install(|| {
+                batch
+                    .par_iter()
+                    .zip(offsets)
+                    .with_max_len(parameters.work_chunk_size().get())
+                    .map(run)
+                    .collect()
+            });
+            outputs.extend(batch_outputs);
+        }
+        Ok(outputs)
+    }
+}
+
+impl<T: Float> RtmInputs<T> {
+    /// The number of levels from the surface upward.
+    fn num_gpu_levels(&self) -> usize {
+        self.num_levels.get() + 1 - self.surface_index
+    }
+
+    /// The pressure, temperature, and vapor pressure of each level from the
+    /// surface upward, for [`GpuAbsorption::gas_absorption`].
+    fn gpu_levels(&self) -> impl Iterator<Item = [f32; 3]> + '_ {
+        (self.surface_index..=self.num_levels.get()).map(|i| {
+            [
+                self.pressure[i],
+                self.temperature[i],
+                self.vapor_pressure[i],
+            ]
+            .map(|value| value.into_f64() as f32)
+        })
+    }
+
+    /// Apply the RTM on the inputs for monochromatic `parameters`, as in
+    /// [`run`](Self::run), with the unscaled gas absorption of each level in
+    /// `gas` from [`GpuAbsorption::gas_absorption`].
+    ///
+    /// `gas` has `num_freq` columns, and `columns` is the column of each
+    /// channel. The absorption is scaled, and the cloud absorption added, as
+    /// on the CPU.
+    fn run_with_gas_absorption(
+        &self,
+        parameters: &RtmParameters<T>,
+        columns: &[usize],
+        num_freq: usize,
+        gas: &[[f32; 3]],
+    ) -> RtmOutputs<T> {
+        let c = T::lit;
+        let scale = &parameters.absorption_scale;
+        let [oxygen, lines, continuum] = [
+            scale.oxygen,
+            scale.water_vapor_lines,
+            scale.water_vapor_continuum,
+        ]
+        .map(|factor| (factor * f64::from(NEP_SCALE)) as f32);
+        let mut outputs = RtmOutputs::new(self.quality_with(parameters, &FREQUENCY_LIMITS));
+
+        let emission = parameters.emission;
+        let channels = parameters.frequency.iter().zip(&parameters.incidence);
+        for ((&freq, &inc), &column) in channels.zip(columns) {
+            let levels = self.surface_index..=self.num_levels.get();
+            let absorption: LevelVec<T> = levels
+                .zip(gas[column..].iter().step_by(num_freq))
+                .map(|(i, &[o, wl, wc])| {
+                    let gas =
+                        T::from_f64_lossy(f64::from(o * oxygen + wl * lines + wc * continuum));
+                    let cloud = if self.rho_l[i] > c(1.0e-7) {
+                        fdcldabs(freq, self.temperature[i], self.rho_l[i])
+                            * T::from_f64_lossy(scale.cloud)
+                    } else {
+                        T::zero()
+                    };
+                    // Converting from Np/km to Np/m
+                    (gas + cloud) * c(1.0e-3)
+                })
+                .collect();
+            let (tran, tb_up, tb_down) = self.observed_atm_tran(
+                parameters.integration_scheme,
+                parameters.layer_sum_reference,
+                inc,
+                &self.source_profile(emission, freq),
+                &self.height[self.surface_index..],
+                &absorption,
+            );
+            outputs.tran.push(tran);
+            outputs.tb_up.push(parameters.output_tb(freq, tb_up));
+            outputs.tb_down.push(parameters.output_tb(freq, tb_down));
+        }
+
+        outputs.check_finite();
+        outputs
+    }
+}
@@ -0,0 +1,164 @@
+// Oxygen and water vapor absorption coefficients, for `gpu.rs`.
+//
+// These are the same models as `fdabsoxy_1992_modified` in `oxygen.rs` and
+// `abh2o_rk_modified` in `water_vapor.rs`, in single precision. Each
+// invocation computes one level at one frequency, and writes the oxygen
+// absorption, and the water vapor line and continuum absorption (unscaled),
+// in dB/km.
+
+struct Params {
+    // Number of (level, frequency) pairs in this dispatch
+    num_pairs: u32,
+    // Number of frequencies
+    num_freq: u32,
+}
+
+// The pressure in hPa, temperature in K, and water vapor pressure in hPa of
+// each level
+@group(0) @binding(0) var<storage, read> levels: array<vec4<f32>>;
+// The frequencies in GHz
+@group(0) @binding(1) var<storage, read> frequencies: array<f32>;
+// The oxygen coefficients, `f0`, then `a1` to `a6`, each for all of the lines
+@group(0) @binding(2) var<storage, read> oxygen_coef: array<f32>;
+// The water vapor coefficients, `f0`, then `b1` to `b6`
+@group(0) @binding(3) var<storage, read> water_vapor_coef: array<f32>;
+// The (oxygen, water vapor lines, water vapor continuum, 0) absorption of each
+// pair, with the frequency varying fastest
+@group(0) @binding(4) var<storage, read_write> absorption: array<vec4<f32>>;
+@group(0) @binding(5) var<uniform> params: Params;
+
+const OXYGEN_LINES: u32 = 44u;
+const WATER_VAPOR_LINES: u32 = 15u;
+// As `MODEL_TEMPERATURE_RANGE` and `MAX_ABSORPTION` in `oxygen.rs`
+const MIN_TEMPERATURE: f32 = 100.0;
+const MAX_TEMPERATURE: f32 = 400.0;
+const MAX_ABSORPTION: f32 = 1e4;
+// As `REFERENCE_TEMPERATURE` in `rtm.rs`, since only the builtin coefficients
+// are run on the GPU
+const REFERENCE_TEMPERATURE: f32 = 300.0;
+
+fn oxygen_absorption(p: f32, t_in: f32, pv: f32, freq: f32) -> f32 {
+    let t = clamp(t_in, MIN_TEMPERATURE, MAX_TEMPERATURE);
+    let tht = REFERENCE_TEMPERATURE / t;
+    let pwet = 0.1 * pv;
+    let pdry = 0.1 * p - pwet;
+    let xterm = 1.0 - tht;
+
+    var sum = 0.0;
+    for (var i = 0u; i < OXYGEN_LINES; i++) {
+        let f0 = oxygen_coef[i];
+        let a1 = oxygen_coef[OXYGEN_LINES + i];
+        let a2 = oxygen_coef[2u * OXYGEN_LINES + i];
+        let a3 = oxygen_coef[3u * OXYGEN_LINES + i];
+        let a4 = oxygen_coef[4u * OXYGEN_LINES + i];
+        let a5 = oxygen_coef[5u * OXYGEN_LINES + i];
+        let a6 = oxygen_coef[6u * OXYGEN_LINES + i];
+
+        let ga = a3 * (pdry * pow(tht, 0.8 - a4) + 1.1 * tht * pwet);
+        let delta = (a5 + a6 * tht) * p * pow(tht, 0.8);
+        let rnuneg = f0 - freq;
+        let rnupos = f0 + freq;
+        let ga_sq = ga * ga;
+        let ff = (ga - rnuneg * delta) / (ga_sq + rnuneg * rnuneg)
+            + (ga - rnupos * delta) / (ga_sq + rnupos * rnupos);
+        sum += ff * a1 * exp(a2 * xterm);
+    }
+    sum = max(sum, 0.0);
+
+    // Nonresonant contribution ("modification 1")
+    let ga = 5.6e-3 * (pdry + 1.1 * pwet) * pow(tht, 1.5);
+    let zterm = ga * (1.0 + (freq / ga) * (freq / ga));
+    let apterm = max(1.4e-10 * (1.0 - 1.2e-5 * pow(freq, 1.5)) * pdry * pow(tht, 1.5), 0.0);
+    let sftot = pdry * freq * tht * tht * (tht * sum + 6.14e-4 / zterm + apterm);
+
+    var gamoxy = 0.1820 * freq * sftot;
+    if freq > 37.0 {
+        gamoxy += 0.1820 * 26.0e-10 * pdry * pdry * tht * tht * tht * pow(freq - 37.0, 1.8);
+    }
+    return min(gamoxy, MAX_ABSORPTION);
+}
+
+// The (line, continuum) water vapor absorption
+fn water_vapor_absorption(p: f32, t: f32, pv: f32, freq: f32) -> vec2<f32> {
+    if pv <= 0.0 {
+        return vec2<f32>(0.0, 0.0);
+    }
+    let pwet = 0.1 * pv;
+    let pdry = 0.1 * p - pwet;
+    let tht = REFERENCE_TEMPERATURE / t;
+    let xterm = 1.0 - tht;
+    let freq_sq = freq * freq;
+
+    var sum = 0.0;
+    for (var i = 0u; i < WATER_VAPOR_LINES; i++) {
+        let f0 = water_vapor_coef[i];
+        let b1 = water_vapor_coef[WATER_VAPOR_LINES + i];
+        let b2 = water_vapor_coef[2u * WATER_VAPOR_LINES + i];
+        let b3 = water_vapor_coef[3u * WATER_VAPOR_LINES + i];
+        let b4 = water_vapor_coef[4u * WATER_VAPOR_LINES + i];
+        let b5 = water_vapor_coef[5u * WATER_VAPOR_LINES + i];
+        let b6 = water_vapor_coef[6u * WATER_VAPOR_LINES + i];
+
+        let f0sq = f0 * f0;
+        let ga = b3 * (pdry * pow(tht, b4) + b5 * pwet * pow(tht, b6));
+        let ga_sq = ga * ga;
+        let s = b1 * exp(b2 * xterm);
+        let rnuneg = f0 - freq;
+        let rnupos = f0 + freq;
+
+        // Clough's definition of the local line contribution
+        let base = ga / (562500.0 + ga_sq);
+
+        if i != 0u {
+            if abs(rnuneg) < 750.0 {
+                sum += s * (ga / (ga_sq + rnuneg * rnuneg) - base);
+            }
+            if abs(rnupos) <= 750.0 {
+                sum += s * (ga / (ga_sq + rnupos * rnupos) - base);
+            }
+        } else {
+            // Modification 2
+            var chi = 0.07 * ga;
+            if freq < 19.0 {
+                let u = clamp(abs(freq - 19.0) / 16.5, 0.0, 1.0);
+                chi += 0.93 * ga * u * u * (3.0 - 2.0 * u);
+            }
+            let chi_sq = chi * chi;
+            let d = freq_sq - f0sq - ga_sq + chi_sq;
+            sum += s * 2.0 * ((ga - chi) * freq_sq + (ga + chi) * (f0sq + ga_sq - chi_sq))
+                / (d * d + 4.0 * freq_sq * ga_sq);
+        }
+    }
+    sum = max(sum, 0.0);
+
+    var ffac = 1.0;
+    if freq < 90.0 {
+        ffac += 0.1 * pow((90.0 - freq) / 90.0, 1.4);
+    }
+
+    // Modification 3
+    let factor = 0.1820 * freq * pwet * freq * pow(tht, 3.5);
+    let continuum = ffac * 1.1 * 1.2957246e-6 * pdry / sqrt(tht)
+        + 0.348 * pow(freq, 0.15) * 4.2952193e-5 * pwet * tht * tht * tht * tht;
+    return vec2<f32>(factor * sum, factor * continuum);
+}
+
+@compute @workgroup_size(64)
+fn main(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(num_workgroups) num_workgroups: vec3<u32>,
+) {
+    let pair = id.y * num_workgroups.x * 64u + id.x;
+    if pair >= params.num_pairs {
+        return;
+    }
+    let level = levels[pair / params.num_freq];
+    let freq = frequencies[pair % params.num_freq];
+    let water_vapor = water_vapor_absorption(level.x, level.y, level.z, freq);
+    absorption[pair] = vec4<f32>(
+        oxygen_absorption(level.x, level.y, level.z, freq),
+        water_vapor.x,
+        water_vapor.y,
+        0.0,
+    );
+}
@@ -0,0 +1,114 @@
+//! Humidity conversions.
+//!
+//! These are the same conversions used internally by the RTM to prepare its
+//! inputs. Pressures are in hPa, temperatures in K, specific humidity and
+//! mixing ratio in kg/kg, and relative humidity is a fraction (0 to 1).
+#![allow(clippy::excessive_precision)]
+
+use super::Float;
+
+/// Ideal gas constant (J/mol/K)
+const R: f32 = 8.3144598;
+/// Mean molar mass of dry air (g/mol)
+const M_DRY: f32 = 28.9644;
+/// Mean molar mass of water (g/mol)
+const M_H2O: f32 = 18.01528;
+/// Specific gas constant for dry air (J/g/K)
+pub(super) const R_DRY: f32 = R / M_DRY;
+/// Specific gas constant for water vapor (J/g/K)
+pub(super) const R_VAPOR: f32 = R / M_H2O;
+
+/// Coefficient for ratio between molar masses
+const EPSILON: f32 = M_H2O / M_DRY;
+/// Scaling factor using EPSILON
+pub(super) const EPS_SCALE: f32 = (1. - EPSILON) / EPSILON;
+
+/// The Buck equation.
+///
+/// Convert `temp`, the temperature in K, into water vapor saturation pressure
+/// in hPa. The equation is from [1], which cites Buck 1996.
+///
+/// To convert to water vapor partial pressure, multiply the result by the
+/// relative humidity.
+///
+/// [1]: https://en.wikipedia.org/wiki/Arden_Buck_equation
+pub fn buck_vap<T: Float>(temp: T) -> T {
+    let c = T::lit;
+    // Temperature in degrees Celsius
+    let temp_c = temp - c(273.15);
+    c(6.1121) * ((c(18.678) - temp_c / c(234.5)) * (temp_c / (c(257.14) + temp_c))).exp()
+}
+
+/// The inverse of the Buck equation ([`buck_vap`]).
+///
+/// Convert `vapor_pressure`, the water vapor pressure in hPa, into the
+/// temperature in K at which it is the saturation pressure: the dewpoint. With
+/// `x` the log of the pressure relative to the saturation pressure at 0 °C,
+/// the Buck equation is a quadratic in the temperature in °C, and this is its
+/// lower root. The vapor pressure must be positive.
+pub fn buck_dewpoint<T: Float>(vapor_pressure: T) -> T {
+    let c = T::lit;
+    let x = (vapor_pressure / c(6.1121)).ln();
+    let a = c(18.678) - x;
+    // The lower root, in a form without cancellation when `x` is small
+    let (scale, offset) = (c(234.5), c(257.14));
+    let temp_c = c(2.) * offset * x / (a + (a * a - c(4.) * offset * x / scale).sqrt());
+    temp_c + c(273.15)
+}
+
+/// Convert specific humidity `q` to the mass mixing ratio.
+pub fn specific_humidity_to_mixing_ratio<T: Float>(q: T) -> T {
+    q / (T::one() - q)
+}
+
+/// Convert the mass mixing ratio `w` to specific humidity.
+pub fn mixing_ratio_to_specific_humidity<T: Float>(w: T) -> T {
+    w / (w + T::one())
+}
+
+/// Convert the mass mixing ratio `w` to water vapor pressure, at a total
+/// pressure `p`.
+pub fn mixing_ratio_to_vapor_pressure<T: Float>(w: T, p: T) -> T {
+    (w * p) / (T::lit(R_DRY / R_VAPOR) + w)
+}
+
+/// Convert the water vapor pressure `pv` to the mass mixing ratio, at a total
+/// pressure `p`.
+pub fn vapor_pressure_to_mixing_ratio<T: Float>(pv: T, p: T) -> T {
+    (pv * T::lit(R_DRY)) / (T::lit(R_VAPOR) * (p - pv))
+}
+
+/// Convert specific humidity `q` to water vapor pressure, at a total pressure
+/// `p`.
+pub fn specific_humidity_to_vapor_pressure<T: Float>(q: T, p: T) -> T {
+    mixing_ratio_to_vapor_pressure(specific_humidity_to_mixing_ratio(q), p)
+}
+
+/// Convert the water vapor pressure `pv` to specific humidity, at a total
+/// pressure `p`.
+pub fn vapor_pressure_to_specific_humidity<T: Float>(pv: T, p: T) -> T {
+    mixing_ratio_to_specific_humidity(vapor_pressure_to_mixing_ratio(pv, p))
+}
+
+/// Convert the dewpoint `td` to water vapor pressure, using the Buck equation.
+pub fn dewpoint_to_vapor_pressure<T: Float>(td: T) -> T {
+    buck_vap(td)
+}
+
+/// Convert water vapor pressure `pv` to the dewpoint, using the inverse of the
+/// Buck equation.
+pub fn vapor_pressure_to_dewpoint<T: Float>(pv: T) -> T {
+    buck_dewpoint(pv)
+}
+
+/// Convert relative humidity `rh` (over water) to water vapor pressure, at an
+/// air temperature `t`.
+pub fn relative_humidity_to_vapor_pressure<T: Float>(rh: T, t: T) -> T {
+    rh * buck_vap(t)
+}
+
+/// Convert the water vapor pressure `pv` to relative humidity (over water), at
+/// an air temperature `t`.
+pub fn vapor_pressure_to_relative_humidity<T: Float>(pv: T, t: T) -> T {
+    pv / buck_vap(t)
+}
@@ -0,0 +1,166 @@
+//! Derivatives of the RTM outputs with respect to the surface temperature, or
+//! to a uniform shift of the whole temperature profile.
+
+use super::{
+    core::{layer_absorption, surface_derivatives},
+    AbsorptionScale, Float, RtmInputs, RtmParameters,
+};
+
+/// Derivatives of the RTM outputs with respect to the surface temperature, for
+/// each channel.
+///
+/// These are computed by [`RtmInputs::surface_temperature_jacobian`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SurfaceTemperatureJacobian<T: Float = f32> {
+    /// Derivative of the atmospheric transmissivity in 1/K.
+    pub tran: Vec<T>,
+    /// Derivative of the atmospheric upwelling brightness temperature (K/K).
+    pub tb_up: Vec<T>,
+    /// Derivative of the atmospheric downwelling brightness temperature (K/K).
+    pub tb_down: Vec<T>,
+}
+
+impl<T: Float> RtmInputs<T> {
+    /// Compute the derivatives of the outputs of [`run`](Self::run) with
+    /// respect to the surface temperature (`surface_temperature` in
+    /// [`new`](Self::new), the 2 m air temperature).
+    ///
+    /// The surface temperature is the lowest level of the profile, so it
+    /// affects the emission of the lowest layer and, through the temperature
+    /// dependence of the absorption, its opacity. The derivatives through the
+    /// radiative transfer are analytic. The derivative of the absorption
+    /// coefficient at the surface is from a central difference of the
+    /// absorption models (in double precision), which are smooth in the
+    /// temperature.
+    ///
+    /// The skin temperature, if any, does not affect the atmospheric outputs,
+    /// so there is no derivative with respect to it here.
+    pub fn surface_temperature_jacobian(
+        &self,
+        parameters: &RtmParameters<T>,
+    ) -> SurfaceTemperatureJacobian<T> {
+        if let Some(nodes) = parameters.passband_nodes() {
+            let jacobian = self.surface_temperature_jacobian(&nodes);
+            return SurfaceTemperatureJacobian {
+                tran: parameters.integrate_channels(&jacobian.tran),
+                tb_up: parameters.integrate_channels(&jacobian.tb_up),
+                tb_down: parameters.integrate_channels(&jacobian.tb_down),
+            };
+        }
+        let num_freq = parameters.frequency.len();
+        let mut jacobian = SurfaceTemperatureJacobian {
+            tran: Vec::with_capacity(num_freq),
+            tb_up: Vec::with_capacity(num_freq),
+            tb_down: Vec::with_capacity(num_freq),
+        };
+        let emission = parameters.emission;
+        let t_surface = self.temperature[self.surface_index];
+        let z = &self.height[self.surface_index..];
+
+        self.for_each_channel(parameters, |freq, inc, absorption_profile| {
+            let t = self.source_profile(emission, freq);
+            let dt0 = emission.source_derivative(freq, t_surface);
+            let dtabs0 = self.surface_absorption_derivative(freq, &parameters.absorption_scale);
+            let derivatives = |num_levels: usize| {
+                surface_derivatives(
+                    parameters.integration_scheme,
+                    parameters.layer_sum_reference,
+                    inc,
+                    &t[..num_levels],
+                    &z[..num_levels],
+                    &absorption_profile[..num_levels],
+                    dt0,
+                    dtabs0,
+                )
+            };
+            // As in `observed_atm_tran`, the downwelling is always for the
+            // whole atmosphere
+            let (dtran, dtb_up, dtb_down) = derivatives(t.len());
+            let (dtran, dtb_up) = match self.observer_levels() {
+                Some(num_observed) if num_observed < t.len() => {
+                    let (dtran, dtb_up, _) = derivatives(num_observed);
+                    (dtran, dtb_up)
+                }
+                _ => (dtran, dtb_up),
+            };
+
+            // Chain rule through the conversion to the output convention
+            let (_, tb_up, tb_down) = self.observed_atm_tran(
+                parameters.integration_scheme,
+                parameters.layer_sum_reference,
+                inc,
+                &t,
+                z,
+                absorption_profile,
+            );
+            let to_tb_derivative =
+                |value| parameters.output_tb_derivative(freq, parameters.output_tb(freq, value));
+            jacobian.tran.push(dtran);
+            jacobian.tb_up.push(dtb_up * to_tb_derivative(tb_up));
+            jacobian.tb_down.push(dtb_down * to_tb_derivative(tb_down));
+        });
+
+        jacobian
+    }
+
+    /// Compute the derivative of the upwelling brightness temperature of
+    /// [`run`](Self::run) with respect to a uniform shift of the temperature
+    /// on every level, including the surface, for each channel.
+    ///
+    /// This is a central difference, running the RTM with the temperatures
+    /// shifted by `delta` K each way, so it costs two runs and needs no
+    /// per-level Jacobian. As in
+    /// [`surface_temperature_jacobian`](Self::surface_temperature_jacobian),
+    /// the vapor pressure is held fixed and the liquid water density varies
+    /// inversely with the temperature, for a fixed liquid content. The skin
+    /// temperature, if any, is not shifted. `delta` should be positive, and
+    /// around 0.1 K is a good balance of truncation and rounding errors in
+    /// single precision.
+    pub fn uniform_temperature_sensitivity(
+        &self,
+        parameters: &RtmParameters<T>,
+        delta: T,
+    ) -> Vec<T> {
+        let shifted = |dt: T| {
+            let mut inputs = self.clone();
+            for (t, rho_l) in inputs.temperature.iter_mut().zip(&mut inputs.rho_l) {
+                *rho_l = *rho_l * *t / (*t + dt);
+                *t = *t + dt;
+            }
+            inputs.run(parameters)
+        };
+        let plus = shifted(delta);
+        let minus = shifted(-delta);
+        plus.tb_up
+            .iter()
+            .zip(&minus.tb_up)
+            .map(|(&plus, &minus)| (plus - minus) / (delta + delta))
+            .collect()
+    }
+
+    /// The derivative of the absorption coefficient (in Np/m/K) at the surface
+    /// with respect to the surface temperature, for a frequency in GHz, with
+    /// the absorbers scaled by `scale`.
+    ///
+    /// The liquid water density at the surface is inversely proportional to
+    /// the temperature for a fixed liquid content, so it is varied too.
+    fn surface_absorption_derivative(&self, freq: T, scale: &AbsorptionScale) -> T {
+        /// Temperature step in K for the central difference
+        const STEP: f64 = 0.1;
+
+        let i = self.surface_index;
+        let t = self.temperature[i].into_f64();
+        let absorption = |dt: f64| {
+            layer_absorption(
+                self.pressure[i].into_f64(),
+                t + dt,
+                self.vapor_pressure[i].into_f64(),
+                self.rho_l[i].into_f64() * t / (t + dt),
+                freq.into_f64(),
+                scale,
+            )
+        };
+        T::from_f64_lossy((absorption(STEP) - absorption(-STEP)) / (2. * STEP))
+    }
+}
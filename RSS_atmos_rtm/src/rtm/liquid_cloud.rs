@@ -2,27 +2,46 @@
 //!
 //! These are pretty directly re-written from the original Fortran source.
 
-use num_complex::Complex32;
+use num_complex::Complex;
+
+use super::Float;
 
 /// Liquid cloud water absorption coefficient.
 ///
 /// For a frequency `freq` in GHz, a temperature `t` in K, and a liquid cloud
 /// water density `rhol` in g/m³, compute the cloud water absorption
 /// coefficient in Np/km.
-pub(crate) fn fdcldabs(freq: f32, t: f32, rhol: f32) -> f32 {
+pub(crate) fn fdcldabs<T: Float>(freq: T, t: T, rhol: T) -> T {
     const C: f32 = 29.979;
     use std::f32::consts::PI;
+    let c = T::lit;
 
     // Convert g/m^3 to g/cm^3
-    let rhol0 = 1.0e-6 * rhol;
+    let rhol0 = c(1.0e-6) * rhol;
 
-    let permit = meissner(freq, t, 0.0);
-    let wavlen = C / freq;
+    let permit = meissner(freq, t, T::zero());
+    let wavlen = c(C) / freq;
     // Np/cm
-    let al = (6.0 * PI * rhol0 / wavlen) * ((1.0 - permit) / (2.0 + permit)).im;
+    let one = Complex::from(T::one());
+    let two = Complex::from(c(2.0));
+    let al = (c(6.0) * c(PI) * rhol0 / wavlen) * ((one - permit) / (two + permit)).im;
 
     // Convert to Np/km
-    al * 1.0e5
+    al * c(1.0e5)
+}
+
+/// The absorption coefficient of cloud liquid water in Np/km, and its
+/// derivative with respect to the liquid water density in (Np/km)/(g/m³).
+///
+/// For a frequency `freq` in GHz, a temperature `t` in K, and a liquid water
+/// density `rhol` in g/m³. This is the same cloud absorption as in the RTM:
+/// the droplets are in the Rayleigh limit, so the absorption is proportional
+/// to `rhol`, and the derivative is the mass absorption coefficient, which
+/// only depends on the frequency and temperature. The dielectric constant of
+/// pure water is from [Meissner and Wentz](meissner), which is fit from 1 to
+/// 400 GHz and from 248.16 K to 313.16 K.
+pub fn cloud_absorption<T: Float>(freq: T, t: T, rhol: T) -> (T, T) {
+    (fdcldabs(freq, t, rhol), fdcldabs(freq, t, T::one()))
 }
 
 /// Compute the complex dielectric constant of water.
@@ -40,19 +59,22 @@ pub(crate) fn fdcldabs(freq: f32, t: f32, rhol: f32) -> f32 {
 /// From Thomas Meissner, February 2002 and October 2004.
 ///
 /// The imaginary part is negative to be consistent with "wentz1" convention.
-pub(super) fn meissner(freq: f32, t: f32, s: f32) -> Complex32 {
+/// This is the dielectric constant used for cloud liquid water and for the
+/// ocean surface.
+pub fn meissner<T: Float>(freq: T, t: T, s: T) -> Complex<T> {
     #![allow(clippy::excessive_precision)]
     const F0: f32 = 17.97510;
+    let c = T::lit;
 
     // Convert from K to °C
-    let sst = t - 273.15;
+    let sst = t - c(273.15);
     let (e0s, e1s, e2s, n1s, n2s, sig) = dielectric_meissner_wentz(sst, s);
 
     // Debye law (2 relaxation wavelengths)
-    let eps = (e0s - e1s) / Complex32::new(1.0, -(freq / n1s))
-        + (e1s - e2s) / Complex32::new(1.0, -(freq / n2s))
+    let eps = Complex::from(e0s - e1s) / Complex::new(T::one(), -(freq / n1s))
+        + Complex::from(e1s - e2s) / Complex::new(T::one(), -(freq / n2s))
         + e2s
-        + Complex32::new(0., sig * F0 / freq);
+        + Complex::new(T::zero(), sig * c(F0) / freq);
 
     eps.conj()
 }
@@ -75,7 +97,7 @@ pub(super) fn meissner(freq: f32, t: f32, s: f32) -> Complex32 {
 /// sea water from microwave satellite observations", in IEEE Transactions on
 /// Geoscience and Remote Sensing, vol. 42, no. 9, pp. 1836-1849, Sept. 2004,
 /// <https://doi.org/10.1109/TGRS.2004.831888>.
-fn dielectric_meissner_wentz(sst: f32, s: f32) -> (f32, f32, f32, f32, f32, f32) {
+fn dielectric_meissner_wentz<T: Float>(sst: T, s: T) -> (T, T, T, T, T, T) {
     #![allow(clippy::excessive_precision)]
     const X: [f32; 11] = [
         5.7230e+00,
@@ -116,8 +138,10 @@ fn dielectric_meissner_wentz(sst: f32, s: f32) -> (f32, f32, f32, f32, f32, f32)
         0.89795E-08,
     ];
 
+    let c = T::lit;
+
     // protects against n1 and n2 going zero for very cold water
-    let sst = sst.max(-30.16);
+    let sst = sst.max(c(-30.16));
     let sst2 = sst.powi(2);
     let sst3 = sst.powi(3);
     let sst4 = sst.powi(4);
@@ -125,46 +149,49 @@ fn dielectric_meissner_wentz(sst: f32, s: f32) -> (f32, f32, f32, f32, f32, f32)
     let s2 = s.powi(2);
 
     // Pure water. e0 is from Stogryn et al.
-    let e0 = (3.70886e4 - 8.2168e1 * sst) / (4.21854e2 + sst);
-    let e1 = X[0] + X[1] * sst + X[2] * sst2;
-    let n1 = (45.0 + sst) / (X[3] + X[4] * sst + X[5] * sst2);
-    let e2 = X[6] + X[7] * sst;
-    let n2 = (45.0 + sst) / (X[8] + X[9] * sst + X[10] * sst2);
+    let x = X.map(c);
+    let z = Z.map(c);
+    let e0 = (c(3.70886e4) - c(8.2168e1) * sst) / (c(4.21854e2) + sst);
+    let e1 = x[0] + x[1] * sst + x[2] * sst2;
+    let n1 = (c(45.0) + sst) / (x[3] + x[4] * sst + x[5] * sst2);
+    let e2 = x[6] + x[7] * sst;
+    let n2 = (c(45.0) + sst) / (x[8] + x[9] * sst + x[10] * sst2);
 
     // Saline water. Conductivity [s/m] taken from Stogryn et al.
-    let sig35 =
-        2.903602 + 8.60700e-2 * sst + 4.738817e-4 * sst2 - 2.9910e-6 * sst3 + 4.3047e-9 * sst4;
-    let r15 = s * (37.5109 + 5.45216 * s + 1.4409e-2 * s2) / (1004.75 + 182.283 * s + s2);
+    let sig35 = c(2.903602) + c(8.60700e-2) * sst + c(4.738817e-4) * sst2 - c(2.9910e-6) * sst3
+        + c(4.3047e-9) * sst4;
+    let r15 =
+        s * (c(37.5109) + c(5.45216) * s + c(1.4409e-2) * s2) / (c(1004.75) + c(182.283) * s + s2);
 
-    let alpha0 = (6.9431 + 3.2841 * s - 9.9486e-2 * s2) / (84.850 + 69.024 * s + s2);
-    let alpha1 = 49.843 - 0.2276 * s + 0.198e-2 * s2;
-    let rtr15 = 1.0 + (sst - 15.0) * alpha0 / (alpha1 + sst);
+    let alpha0 = (c(6.9431) + c(3.2841) * s - c(9.9486e-2) * s2) / (c(84.850) + c(69.024) * s + s2);
+    let alpha1 = c(49.843) - c(0.2276) * s + c(0.198e-2) * s2;
+    let rtr15 = c(1.0) + (sst - c(15.0)) * alpha0 / (alpha1 + sst);
 
     let sig = sig35 * r15 * rtr15;
 
     // permittivity
-    let a0 = f32::exp(A0_COEF[0] * s + A0_COEF[1] * s2 + A0_COEF[2] * s * sst);
+    let a0 = (c(A0_COEF[0]) * s + c(A0_COEF[1]) * s2 + c(A0_COEF[2]) * s * sst).exp();
     let e0s = a0 * e0;
 
-    let b1 = if sst <= 30. {
-        1.0 + s
-            * (B1_COEF[0]
-                + B1_COEF[1] * sst
-                + B1_COEF[2] * sst2
-                + B1_COEF[3] * sst3
-                + B1_COEF[4] * sst4)
+    let b1 = if sst <= c(30.) {
+        c(1.0)
+            + s * (c(B1_COEF[0])
+                + c(B1_COEF[1]) * sst
+                + c(B1_COEF[2]) * sst2
+                + c(B1_COEF[3]) * sst3
+                + c(B1_COEF[4]) * sst4)
     } else {
-        1.0 + s * (9.1873715e-04 + 1.5012396e-04 * (sst - 30.))
+        c(1.0) + s * (c(9.1873715e-04) + c(1.5012396e-04) * (sst - c(30.)))
     };
     let n1s = n1 * b1;
 
-    let a1 = f32::exp(Z[6] * s + Z[7] * s2 + Z[8] * s * sst);
+    let a1 = (z[6] * s + z[7] * s2 + z[8] * s * sst).exp();
     let e1s = e1 * a1;
 
-    let b2 = 1.0 + s * (Z[9] + 0.5 * Z[10] * (sst + 30.));
+    let b2 = c(1.0) + s * (z[9] + c(0.5) * z[10] * (sst + c(30.)));
     let n2s = n2 * b2;
 
-    let a2 = 1.0 + s * (Z[11] + Z[12] * sst);
+    let a2 = c(1.0) + s * (z[11] + z[12] * sst);
     let e2s = e2 * a2;
 
     (e0s, e1s, e2s, n1s, n2s, sig)
@@ -0,0 +1,840 @@
+//! Reading and writing netCDF files in the classic and 64-bit offset formats
+//! (netCDF-3), for batch runs on gridded profiles without the netCDF C
+//! library.
+//!
+//! [`NetcdfFile`] reads the header of a file and then whole variables as
+//! `f32` or `f64` arrays, with the packing attributes `scale_factor` and
+//! `add_offset` applied and the `_FillValue` and `missing_value` values (or the
+//! default fill values of netCDF) as NaN. Variables along the unlimited
+//! dimension are supported. The netCDF-4 format, which is HDF5, and the 64-bit
+//! data format (CDF-5) aren't; `nccopy -k 64-bit-offset in.nc out.nc`
+//! converts a file to a format that's supported.
+//!
+//! [`NetcdfDefinition`] defines the dimensions, variables, and attributes of
+//! a new file, like the define mode of the C library, and creates it as a
+//! [`NetcdfWriter`], whose variables are then written in any order, a range of
+//! elements at a time. The files are in the 64-bit offset format, so they may
+//! be larger than 2 GiB, and have no unlimited dimension.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rss_atmos_rtm::rtm::netcdf::{NetcdfAttribute, NetcdfDefinition, NetcdfFile, NetcdfType};
+//!
+//! let mut input = NetcdfFile::open("profiles.nc".as_ref())?;
+//! let temperature = input.read::<f32>("t")?;
+//!
+//! let mut definition = NetcdfDefinition::new();
+//! definition.add_dimension("point", temperature.shape()[0])?;
+//! definition.add_variable(
+//!     "t_bottom",
+//!     &["point"],
+//!     NetcdfType::Float,
+//!     vec![("units".to_string(), NetcdfAttribute::Text("K".into()))],
+//! )?;
+//! let mut output = definition.create("outputs.nc".as_ref())?;
+//! let bottom: Vec<f32> = temperature.outer_iter().map(|row| row[0]).collect();
+//! output.write("t_bottom", 0, &bottom)?;
+//! # Ok::<(), rss_atmos_rtm::rtm::RtmError>(())
+//! ```
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use ndarray::{ArrayD, IxDyn};
+
+use super::{Float, RtmError};
+
+/// The magic string at the start of a netCDF-3 file, before the version.
+const MAGIC: &[u8] = b"CDF";
+
+/// The signature at the start of an HDF5 file, which is a netCDF-4 file.
+const HDF5_MAGIC: &[u8] = b"\x89HDF";
+
+/// The tags of the lists in the header.
+const NC_DIMENSION: u32 = 0x0A;
+const NC_VARIABLE: u32 = 0x0B;
+const NC_ATTRIBUTE: u32 = 0x0C;
+
+/// The number of records of a file that's still being written.
+const STREAMING: u32 = u32::MAX;
+
+/// The longest name or attribute that's read, to fail cleanly on a corrupt
+/// header instead of allocating its garbage length.
+const MAX_HEADER_ITEM: usize = 1 << 24;
+
+/// The external type of a variable or attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetcdfType {
+    /// 8-bit signed integers.
+    Byte,
+    /// 8-bit characters, for text.
+    Char,
+    /// 16-bit signed integers.
+    Short,
+    /// 32-bit signed integers.
+    Int,
+    /// 32-bit floating point.
+    Float,
+    /// 64-bit floating point.
+    Double,
+}
+
+impl NetcdfType {
+    /// The type with the code `code` in a header, if there is one.
+    fn from_code(code: u32) -> Option<Self> {
+        match code {
+            1 => Some(NetcdfType::Byte),
+            2 => Some(NetcdfType::Char),
+            3 => Some(NetcdfType::Short),
+            4 => Some(NetcdfType::Int),
+            5 => Some(NetcdfType::Float),
+            6 => Some(NetcdfType::Double),
+            _ => None,
+        }
+    }
+
+    /// The code of the type in a header.
+    fn code(self) -> u32 {
+        match self {
+            NetcdfType::Byte => 1,
+            NetcdfType::Char => 2,
+            NetcdfType::Short => 3,
+            NetcdfType::Int => 4,
+            NetcdfType::Float => 5,
+            NetcdfType::Double => 6,
+        }
+    }
+
+    /// The size in bytes of a value.
+    pub fn size(self) -> usize {
+        match self {
+            NetcdfType::Byte | NetcdfType::Char => 1,
+            NetcdfType::Short => 2,
+            NetcdfType::Int | NetcdfType::Float => 4,
+            NetcdfType::Double => 8,
+        }
+    }
+
+    /// The default fill value of the type, which marks values that were never
+    /// written, or `None` for the types that don't have one.
+    fn default_fill(self) -> Option<f64> {
+        match self {
+            NetcdfType::Byte | NetcdfType::Char => None,
+            NetcdfType::Short => Some(-32767.),
+            NetcdfType::Int => Some(-2147483647.),
+            NetcdfType::Float => Some(f64::from(9.969_21e36_f32)),
+            NetcdfType::Double => Some(9.969_209_968_386_869e36),
+        }
+    }
+
+    /// Decode big-endian values of this type as `f64`.
+    fn decode(self, bytes: &[u8]) -> Vec<f64> {
+        match self {
+            NetcdfType::Byte => bytes.iter().map(|&b| f64::from(b as i8)).collect(),
+            NetcdfType::Char => bytes.iter().map(|&b| f64::from(b)).collect(),
+            NetcdfType::Short => bytes
+                .chunks_exact(2)
+                .map(|b| f64::from(i16::from_be_bytes([b[0], b[1]])))
+                .collect(),
+            NetcdfType::Int => bytes
+                .chunks_exact(4)
+                .map(|b| f64::from(i32::from_be_bytes([b[0], b[1], b[2], b[3]])))
+                .collect(),
+            NetcdfType::Float => bytes
+                .chunks_exact(4)
+                .map(|b| f64::from(f32::from_be_bytes([b[0], b[1], b[2], b[3]])))
+                .collect(),
+            NetcdfType::Double => bytes
+                .chunks_exact(8)
+                .map(|b| f64::from_be_bytes(b.try_into().expect("8 bytes")))
+                .collect(),
+        }
+    }
+}
+
+/// The value of an attribute of a file or variable.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NetcdfAttribute {
+    /// Text, stored as characters.
+    Text(String),
+    /// 8-bit signed integers.
+    Byte(Vec<i8>),
+    /// 16-bit signed integers.
+    Short(Vec<i16>),
+    /// 32-bit signed integers.
+    Int(Vec<i32>),
+    /// 32-bit floating-point numbers.
+    Float(Vec<f32>),
+    /// 64-bit floating-point numbers.
+    Double(Vec<f64>),
+}
+
+impl NetcdfAttribute {
+    /// The text of a text attribute, or `None` for numbers.
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            NetcdfAttribute::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// The first number of a numeric attribute, or `None` for text or an
+    /// empty attribute.
+    pub fn number(&self) -> Option<f64> {
+        match self {
+            NetcdfAttribute::Text(_) => None,
+            NetcdfAttribute::Byte(values) => values.first().map(|&v| f64::from(v)),
+            NetcdfAttribute::Short(values) => values.first().map(|&v| f64::from(v)),
+            NetcdfAttribute::Int(values) => values.first().map(|&v| f64::from(v)),
+            NetcdfAttribute::Float(values) => values.first().map(|&v| f64::from(v)),
+            NetcdfAttribute::Double(values) => values.first().copied(),
+        }
+    }
+
+    /// The type and number of values of the attribute.
+    fn nc_type(&self) -> (NetcdfType, usize) {
+        match self {
+            NetcdfAttribute::Text(text) => (NetcdfType::Char, text.len()),
+            NetcdfAttribute::Byte(values) => (NetcdfType::Byte, values.len()),
+            NetcdfAttribute::Short(values) => (NetcdfType::Short, values.len()),
+            NetcdfAttribute::Int(values) => (NetcdfType::Int, values.len()),
+            NetcdfAttribute::Float(values) => (NetcdfType::Float, values.len()),
+            NetcdfAttribute::Double(values) => (NetcdfType::Double, values.len()),
+        }
+    }
+
+    /// Append the big-endian values of the attribute.
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        match self {
+            NetcdfAttribute::Text(text) => bytes.extend(text.as_bytes()),
+            NetcdfAttribute::Byte(values) => bytes.extend(values.iter().map(|&v| v as u8)),
+            NetcdfAttribute::Short(values) => {
+                bytes.extend(values.iter().flat_map(|v| v.to_be_bytes()));
+            }
+            NetcdfAttribute::Int(values) => {
+                bytes.extend(values.iter().flat_map(|v| v.to_be_bytes()));
+            }
+            NetcdfAttribute::Float(values) => {
+                bytes.extend(values.iter().flat_map(|v| v.to_be_bytes()));
+            }
+            NetcdfAttribute::Double(values) => {
+                bytes.extend(values.iter().flat_map(|v| v.to_be_bytes()));
+            }
+        }
+    }
+}
+
+/// A dimension of a netCDF file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dimension {
+    /// Name of the dimension.
+    pub name: String,
+    /// Length of the dimension, which for the unlimited dimension is the
+    /// number of records.
+    pub len: usize,
+    /// Whether this is the unlimited (record) dimension.
+    pub unlimited: bool,
+}
+
+/// A variable of a netCDF file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variable {
+    /// Name of the variable.
+    pub name: String,
+    /// Names of the dimensions of the variable, in order.
+    pub dimensions: Vec<String>,
+    /// Shape of the variable, which has the lengths of its dimensions.
+    pub shape: Vec<usize>,
+    /// Attributes of the variable, in order.
+    pub attributes: Vec<(String, NetcdfAttribute)>,
+    /// External type of the values.
+    pub nc_type: NetcdfType,
+    /// Offset in bytes of the data, or of its first record.
+    begin: u64,
+    /// Whether the variable is along the unlimited dimension.
+    record: bool,
+}
+
+impl Variable {
+    /// The attribute named `name`, if there is one.
+    pub fn attribute(&self, name: &str) -> Option<&NetcdfAttribute> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value)
+    }
+}
+
+/// An error for a file that isn't a valid netCDF-3 file.
+fn invalid(message: impl Into<String>) -> RtmError {
+    RtmError::InvalidNetcdfFile(message.into())
+}
+
+/// Map an error reading a file, where the end of the file means it's
+/// truncated.
+fn read_error(e: std::io::Error) -> RtmError {
+    if e.kind() == ErrorKind::UnexpectedEof {
+        invalid("the file is truncated")
+    } else {
+        RtmError::Io(e)
+    }
+}
+
+/// Round `len` up to a multiple of 4 bytes, as for every item in a file.
+fn padded(len: usize) -> usize {
+    len.div_ceil(4) * 4
+}
+
+/// Reads the items of a header.
+struct HeaderReader<R> {
+    reader: R,
+    /// Whether offsets are 64-bit, as in the 64-bit offset format
+    large_offsets: bool,
+}
+
+impl<R: Read> HeaderReader<R> {
+    fn bytes(&mut self, len: usize) -> Result<Vec<u8>, RtmError> {
+        let mut bytes = vec![0; len];
+        self.reader.read_exact(&mut bytes).map_err(read_error)?;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, RtmError> {
+        let mut bytes = [0; 4];
+        self.reader.read_exact(&mut bytes).map_err(read_error)?;
+        Ok(u32::from_be_bytes(bytes))
+    }
+
+    fn offset(&mut self) -> Result<u64, RtmError> {
+        if self.large_offsets {
+            let mut bytes = [0; 8];
+            self.reader.read_exact(&mut bytes).map_err(read_error)?;
+            Ok(u64::from_be_bytes(bytes))
+        } else {
+            self.u32().map(u64::from)
+        }
+    }
+
+    /// A count of items, which is limited so garbage can't exhaust memory.
+    fn count(&mut self) -> Result<usize, RtmError> {
+        let count = self.u32()? as usize;
+        if count > MAX_HEADER_ITEM {
+            return Err(invalid(format!("a count of {count} items in the header")));
+        }
+        Ok(count)
+    }
+
+    /// The bytes of an item with `len` bytes and its padding.
+    fn padded_bytes(&mut self, len: usize) -> Result<Vec<u8>, RtmError> {
+        let mut bytes = self.bytes(padded(len))?;
+        bytes.truncate(len);
+        Ok(bytes)
+    }
+
+    fn name(&mut self) -> Result<String, RtmError> {
+        let len = self.count()?;
+        String::from_utf8(self.padded_bytes(len)?).map_err(|_| invalid("a name isn't UTF-8"))
+    }
+
+    /// The tag of a list and its number of items, where an absent list has
+    /// no items.
+    fn list(&mut self, tag: u32) -> Result<usize, RtmError> {
+        let found = self.u32()?;
+        let count = self.count()?;
+        match found {
+            0 if count == 0 => Ok(0),
+            found if found == tag => Ok(count),
+            _ => Err(invalid(format!(
+                "expected the list tag {tag:#x}, found {found:#x}"
+            ))),
+        }
+    }
+
+    fn attributes(&mut self) -> Result<Vec<(String, NetcdfAttribute)>, RtmError> {
+        let count = self.list(NC_ATTRIBUTE)?;
+        let mut attributes = Vec::with_capacity(count);
+        for _ in 0..count {
+            let name = self.name()?;
+            let code = self.u32()?;
+            let nc_type = NetcdfType::from_code(code)
+                .ok_or_else(|| invalid(format!("attribute {name} has the type code {code}")))?;
+            let len = self.count()?;
+            let bytes = self.padded_bytes(len * nc_type.size())?;
+            let value = match nc_type {
+                NetcdfType::Char => NetcdfAttribute::Text(
+                    // Text is often NUL-terminated by the C library
+                    String::from_utf8_lossy(&bytes)
+                        .trim_end_matches('\0')
+                        .to_string(),
+                ),
+                NetcdfType::Byte => NetcdfAttribute::Byte(bytes.iter().map(|&b| b as i8).collect()),
+                NetcdfType::Short => NetcdfAttribute::Short(
+                    bytes
+                        .chunks_exact(2)
+                        .map(|b| i16::from_be_bytes([b[0], b[1]]))
+                        .collect(),
+                ),
+                NetcdfType::Int => NetcdfAttribute::Int(
+                    bytes
+                        .chunks_exact(4)
+                        .map(|b| i32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+                        .collect(),
+                ),
+                NetcdfType::Float => NetcdfAttribute::Float(
+                    bytes
+                        .chunks_exact(4)
+                        .map(|b| f32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+                        .collect(),
+                ),
+                NetcdfType::Double => NetcdfAttribute::Double(nc_type.decode(&bytes)),
+            };
+            attributes.push((name, value));
+        }
+        Ok(attributes)
+    }
+}
+
+/// A netCDF-3 file that's open for reading.
+#[derive(Debug)]
+pub struct NetcdfFile {
+    file: File,
+    dimensions: Vec<Dimension>,
+    attributes: Vec<(String, NetcdfAttribute)>,
+    variables: Vec<Variable>,
+    num_records: usize,
+    /// Size in bytes of each record, with a slab of every record variable
+    record_size: u64,
+}
+
+impl NetcdfFile {
+    /// Open the file at `path` and read its header.
+    ///
+    /// Returns [`RtmError::Io`] if it can't be read, and
+    /// [`RtmError::InvalidNetcdfFile`] if it isn't in one of the supported
+    /// formats or its header is invalid.
+    pub fn open(path: &Path) -> Result<Self, RtmError> {
+        let file = File::open(path)?;
+        let mut reader = BufReader::new(&file);
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic).map_err(read_error)?;
+        let large_offsets = match magic {
+            [b'C', b'D', b'F', 1] => false,
+            [b'C', b'D', b'F', 2] => true,
+            [b'C', b'D', b'F', 5] => {
+                return Err(invalid(
+                    "the 64-bit data format (CDF-5) isn't supported, only the classic and \
+                     64-bit offset formats",
+                ))
+            }
+            _ if magic == HDF5_MAGIC => {
+                return Err(invalid(
+                    "netCDF-4 (HDF5) files aren't supported, only netCDF-3 (the classic and \
+                     64-bit offset formats); convert it with \
+                     `nccopy -k 64-bit-offset in.nc out.nc`",
+                ))
+            }
+            _ => return Err(invalid("not a netCDF file")),
+        };
+        let mut header = HeaderReader {
+            reader,
+            large_offsets,
+        };
+
+        let num_records = match header.u32()? {
+            STREAMING => return Err(invalid("the file is still being written")),
+            num_records => num_records as usize,
+        };
+        let num_dimensions = header.list(NC_DIMENSION)?;
+        let mut dimensions = Vec::with_capacity(num_dimensions);
+        for _ in 0..num_dimensions {
+            let name = header.name()?;
+            let len = header.u32()? as usize;
+            dimensions.push(Dimension {
+                name,
+                len: if len == 0 { num_records } else { len },
+                unlimited: len == 0,
+            });
+        }
+        let attributes = header.attributes()?;
+
+        let num_variables = header.list(NC_VARIABLE)?;
+        let mut variables = Vec::with_capacity(num_variables);
+        for _ in 0..num_variables {
+            let name = header.name()?;
+            let num_dims = header.count()?;
+            let mut ids = Vec::with_capacity(num_dims);
+            for _ in 0..num_dims {
+                ids.push(header.u32()? as usize);
+            }
+            let dims = ids
+                .iter()
+                .map(|&id| dimensions.get(id))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| invalid(format!("variable {name} has an unknown dimension")))?;
+            if dims.iter().skip(1).any(|dim| dim.unlimited) {
+                return Err(invalid(format!(
+                    "variable {name} has the unlimited dimension after the first"
+                )));
+            }
+            let attributes = header.attributes()?;
+            let code = header.u32()?;
+            let nc_type = NetcdfType::from_code(code)
+                .ok_or_else(|| invalid(format!("variable {name} has the type code {code}")))?;
+            let _vsize = header.u32()?;
+            let begin = header.offset()?;
+            variables.push(Variable {
+                name,
+                dimensions: dims.iter().map(|dim| dim.name.clone()).collect(),
+                shape: dims.iter().map(|dim| dim.len).collect(),
+                attributes,
+                nc_type,
+                begin,
+                record: dims.first().is_some_and(|dim| dim.unlimited),
+            });
+        }
+
+        // The size of a record is the padded size of a slab of each record
+        // variable, except that a single record variable isn't padded
+        let slab_size = |variable: &Variable| {
+            variable.shape[1..].iter().product::<usize>() * variable.nc_type.size()
+        };
+        let records: Vec<_> = variables.iter().filter(|v| v.record).collect();
+        let record_size = match records.as_slice() {
+            [variable] => slab_size(variable),
+            records => records.iter().map(|v| padded(slab_size(v))).sum(),
+        } as u64;
+
+        Ok(Self {
+            file,
+            dimensions,
+            attributes,
+            variables,
+            num_records,
+            record_size,
+        })
+    }
+
+    /// The dimensions of the file, in order.
+    pub fn dimensions(&self) -> &[Dimension] {
+        &self.dimensions
+    }
+
+    /// The global attributes of the file, in order.
+    pub fn attributes(&self) -> &[(String, NetcdfAttribute)] {
+        &self.attributes
+    }
+
+    /// The variables of the file, in order.
+    pub fn variables(&self) -> &[Variable] {
+        &self.variables
+    }
+
+    /// The variable named `name`, if there is one.
+    pub fn variable(&self, name: &str) -> Option<&Variable> {
+        self.variables.iter().find(|variable| variable.name == name)
+    }
+
+    /// Read all of the values of the variable named `name`, converted to `T`.
+    ///
+    /// The values are unpacked with the `scale_factor` and `add_offset`
+    /// attributes of the variable, if it has them, and values that are equal
+    /// to its `_FillValue` or `missing_value` attributes, or to the default
+    /// fill value of its type if it has neither, are NaN.
+    ///
+    /// Returns [`RtmError::InvalidNetcdfFile`] if there's no such variable,
+    /// it's text, or the file is truncated, and [`RtmError::Io`] if it can't
+    /// be read.
+    pub fn read<T: Float>(&mut self, name: &str) -> Result<ArrayD<T>, RtmError> {
+        let variable = self
+            .variable(name)
+            .ok_or_else(|| invalid(format!("there's no variable named {name}")))?
+            .clone();
+        if variable.nc_type == NetcdfType::Char {
+            return Err(invalid(format!("variable {name} is text, not numbers")));
+        }
+
+        let size = variable.nc_type.size();
+        let len: usize = variable.shape.iter().product();
+        let mut bytes = vec![0; len * size];
+        if variable.record {
+            let slab = len.checked_div(self.num_records).unwrap_or(0) * size;
+            for (record, chunk) in bytes.chunks_exact_mut(slab.max(1)).enumerate() {
+                self.file.seek(SeekFrom::Start(
+                    variable.begin + record as u64 * self.record_size,
+                ))?;
+                self.file.read_exact(chunk).map_err(read_error)?;
+            }
+        } else {
+            self.file.seek(SeekFrom::Start(variable.begin))?;
+            self.file.read_exact(&mut bytes).map_err(read_error)?;
+        }
+
+        let number = |key: &str| variable.attribute(key).and_then(NetcdfAttribute::number);
+        let fill = number("_FillValue").or(variable.nc_type.default_fill());
+        let missing = number("missing_value");
+        let scale = number("scale_factor").unwrap_or(1.);
+        let offset = number("add_offset").unwrap_or(0.);
+        let values = variable
+            .nc_type
+            .decode(&bytes)
+            .into_iter()
+            .map(|value| {
+                if Some(value) == fill || Some(value) == missing {
+                    T::nan()
+                } else {
+                    T::from_f64_lossy(value * scale + offset)
+                }
+            })
+            .collect();
+        Ok(ArrayD::from_shape_vec(IxDyn(&variable.shape), values)
+            .expect("the values have the shape of the variable"))
+    }
+}
+
+/// A value type that can be written to a variable of a [`NetcdfWriter`].
+pub trait NetcdfValue: Copy {
+    /// The external type of a variable with these values.
+    const TYPE: NetcdfType;
+
+    /// Append the big-endian bytes of the value.
+    fn extend_be_bytes(self, bytes: &mut Vec<u8>);
+}
+
+impl NetcdfValue for i32 {
+    const TYPE: NetcdfType = NetcdfType::Int;
+
+    fn extend_be_bytes(self, bytes: &mut Vec<u8>) {
+        bytes.extend(self.to_be_bytes());
+    }
+}
+
+impl NetcdfValue for f32 {
+    const TYPE: NetcdfType = NetcdfType::Float;
+
+    fn extend_be_bytes(self, bytes: &mut Vec<u8>) {
+        bytes.extend(self.to_be_bytes());
+    }
+}
+
+impl NetcdfValue for f64 {
+    const TYPE: NetcdfType = NetcdfType::Double;
+
+    fn extend_be_bytes(self, bytes: &mut Vec<u8>) {
+        bytes.extend(self.to_be_bytes());
+    }
+}
+
+/// A variable of a [`NetcdfDefinition`].
+#[derive(Debug, Clone)]
+struct VariableDefinition {
+    name: String,
+    dimensions: Vec<usize>,
+    nc_type: NetcdfType,
+    attributes: Vec<(String, NetcdfAttribute)>,
+}
+
+/// The dimensions, variables, and attributes of a new netCDF file, which is
+/// written with [`create`](Self::create).
+#[derive(Debug, Clone, Default)]
+pub struct NetcdfDefinition {
+    dimensions: Vec<(String, usize)>,
+    attributes: Vec<(String, NetcdfAttribute)>,
+    variables: Vec<VariableDefinition>,
+}
+
+impl NetcdfDefinition {
+    /// A definition with nothing in it.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a global attribute.
+    pub fn add_attribute(&mut self, name: &str, value: NetcdfAttribute) {
+        self.attributes.push((name.to_string(), value));
+    }
+
+    /// Add a dimension of length `len`.
+    ///
+    /// Returns [`RtmError::InconsistentInputs`] if there's already a
+    /// dimension with the name, or the length is 0, which is reserved for the
+    /// unlimited dimension.
+    pub fn add_dimension(&mut self, name: &str, len: usize) -> Result<(), RtmError> {
+        if len == 0 || self.dimensions.iter().any(|(n, _)| n == name) {
+            return Err(RtmError::InconsistentInputs);
+        }
+        self.dimensions.push((name.to_string(), len));
+        Ok(())
+    }
+
+    /// Add a variable of type `nc_type` along the named dimensions, which
+    /// must already have been added, with the given attributes.
+    ///
+    /// Returns [`RtmError::InconsistentInputs`] if there's already a
+    /// variable with the name, a dimension hasn't been added, or the type is
+    /// [`NetcdfType::Char`].
+    pub fn add_variable(
+        &mut self,
+        name: &str,
+        dimensions: &[&str],
+        nc_type: NetcdfType,
+        attributes: Vec<(String, NetcdfAttribute)>,
+    ) -> Result<(), RtmError> {
+        let dimensions = dimensions
+            .iter()
+            .map(|dim| self.dimensions.iter().position(|(n, _)| n == dim))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(RtmError::InconsistentInputs)?;
+        if nc_type == NetcdfType::Char || self.variables.iter().any(|v| v.name == name) {
+            return Err(RtmError::InconsistentInputs);
+        }
+        self.variables.push(VariableDefinition {
+            name: name.to_string(),
+            dimensions,
+            nc_type,
+            attributes,
+        });
+        Ok(())
+    }
+
+    /// The number of values of a variable.
+    fn len(&self, variable: &VariableDefinition) -> usize {
+        variable
+            .dimensions
+            .iter()
+            .map(|&id| self.dimensions[id].1)
+            .product()
+    }
+
+    /// The header of the file, with the variables' data at `begins`.
+    fn header(&self, begins: &[u64]) -> Vec<u8> {
+        fn name(bytes: &mut Vec<u8>, name: &str) {
+            bytes.extend((name.len() as u32).to_be_bytes());
+            bytes.extend(name.as_bytes());
+            bytes.resize(padded(bytes.len()), 0);
+        }
+        fn attributes(bytes: &mut Vec<u8>, attributes: &[(String, NetcdfAttribute)]) {
+            if attributes.is_empty() {
+                bytes.extend([0; 8]);
+                return;
+            }
+            bytes.extend(NC_ATTRIBUTE.to_be_bytes());
+            bytes.extend((attributes.len() as u32).to_be_bytes());
+            for (key, value) in attributes {
+                name(bytes, key);
+                let (nc_type, len) = value.nc_type();
+                bytes.extend(nc_type.code().to_be_bytes());
+                bytes.extend((len as u32).to_be_bytes());
+                value.encode(bytes);
+                bytes.resize(padded(bytes.len()), 0);
+            }
+        }
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(2);
+        bytes.extend(0_u32.to_be_bytes());
+        if self.dimensions.is_empty() {
+            bytes.extend([0; 8]);
+        } else {
+            bytes.extend(NC_DIMENSION.to_be_bytes());
+            bytes.extend((self.dimensions.len() as u32).to_be_bytes());
+            for (dim, len) in &self.dimensions {
+                name(&mut bytes, dim);
+                bytes.extend((*len as u32).to_be_bytes());
+            }
+        }
+        attributes(&mut bytes, &self.attributes);
+        if self.variables.is_empty() {
+            bytes.extend([0; 8]);
+        } else {
+            bytes.extend(NC_VARIABLE.to_be_bytes());
+            bytes.extend((self.variables.len() as u32).to_be_bytes());
+            for (variable, begin) in self.variables.iter().zip(begins) {
+                name(&mut bytes, &variable.name);
+                bytes.extend((variable.dimensions.len() as u32).to_be_bytes());
+                for &id in &variable.dimensions {
+                    bytes.extend((id as u32).to_be_bytes());
+                }
+                attributes(&mut bytes, &variable.attributes);
+                bytes.extend(variable.nc_type.code().to_be_bytes());
+                // The size of a variable too large to record is ignored
+                let vsize = padded(self.len(variable) * variable.nc_type.size());
+                bytes.extend(u32::try_from(vsize).unwrap_or(u32::MAX).to_be_bytes());
+                bytes.extend(begin.to_be_bytes());
+            }
+        }
+        bytes
+    }
+
+    /// Create the file at `path`, replacing any existing file, and write its
+    /// header.
+    ///
+    /// The values of the variables are all 0 until they're written. Returns
+    /// [`RtmError::Io`] if the file can't be written.
+    pub fn create(self, path: &Path) -> Result<NetcdfWriter, RtmError> {
+        // The header has the same length whatever the offsets are
+        let header_len = self.header(&vec![0; self.variables.len()]).len() as u64;
+        let mut begins = Vec::with_capacity(self.variables.len());
+        let mut end = header_len;
+        for variable in &self.variables {
+            begins.push(end);
+            end += padded(self.len(variable) * variable.nc_type.size()) as u64;
+        }
+
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(&self.header(&begins))?;
+        let file = file.into_inner().map_err(|e| e.into_error())?;
+        file.set_len(end)?;
+        Ok(NetcdfWriter {
+            file,
+            definition: self,
+            begins,
+        })
+    }
+}
+
+/// A new netCDF file, whose variables are written with
+/// [`write`](Self::write).
+#[derive(Debug)]
+pub struct NetcdfWriter {
+    file: File,
+    definition: NetcdfDefinition,
+    begins: Vec<u64>,
+}
+
+impl NetcdfWriter {
+    /// Write `values` to the variable named `name`, starting at the element
+    /// with index `start` of its values in C order.
+    ///
+    /// Returns [`RtmError::InconsistentInputs`] if there's no such variable,
+    /// the values aren't its type, or they go past its end, and
+    /// [`RtmError::Io`] if they can't be written.
+    pub fn write<V: NetcdfValue>(
+        &mut self,
+        name: &str,
+        start: usize,
+        values: &[V],
+    ) -> Result<(), RtmError> {
+        let index = self
+            .definition
+            .variables
+            .iter()
+            .position(|v| v.name == name)
+            .ok_or(RtmError::InconsistentInputs)?;
+        let variable = &self.definition.variables[index];
+        if variable.nc_type != V::TYPE || start + values.len() > self.definition.len(variable) {
+            return Err(RtmError::InconsistentInputs);
+        }
+        let mut bytes = Vec::with_capacity(values.len() * V::TYPE.size());
+        for &value in values {
+            value.extend_be_bytes(&mut bytes);
+        }
+        let offset = self.begins[index] + (start * V::TYPE.size()) as u64;
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.write_all(&bytes)?;
+        Ok(())
+    }
+}
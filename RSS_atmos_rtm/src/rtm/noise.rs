@@ -0,0 +1,67 @@
+//! Gaussian instrument noise, for simulated observations.
+
+use super::{ChannelVec, Float, RtmError};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rand_distr::StandardNormal;
+
+/// Independent Gaussian noise with the NEΔT (noise-equivalent differential
+/// temperature) of each channel as its standard deviation, from a seeded
+/// random number generator.
+///
+/// The noise is drawn in order, so adding it to the same brightness
+/// temperatures in the same order with the same seed gives the same noisy
+/// values.
+#[derive(Debug, Clone)]
+pub struct NedtNoise<T: Float = f32> {
+    /// Standard deviation in K of each channel.
+    nedt: ChannelVec<T>,
+    /// Random number generator for the noise.
+    rng: StdRng,
+}
+
+impl<T: Float> NedtNoise<T> {
+    /// Create the noise for channels with an NEΔT of `nedt` in K, seeding the
+    /// random number generator with `seed`.
+    ///
+    /// Returns [`RtmError::InconsistentInputs`] if `nedt` is empty, or
+    /// [`RtmError::InvalidNedt`] if a value is negative or not finite.
+    pub fn new(nedt: &[T], seed: u64) -> Result<Self, RtmError> {
+        if nedt.is_empty() {
+            return Err(RtmError::InconsistentInputs);
+        }
+        // Written so that NaN is also rejected
+        if let Some((index, &value)) = nedt
+            .iter()
+            .enumerate()
+            .find(|(_, &value)| !(value >= T::zero() && value.is_finite()))
+        {
+            return Err(RtmError::InvalidNedt {
+                index,
+                value: value.into_f64() as f32,
+            });
+        }
+        Ok(Self {
+            nedt: ChannelVec::from_slice(nedt),
+            rng: StdRng::seed_from_u64(seed),
+        })
+    }
+
+    /// The NEΔT of each channel in K.
+    pub fn nedt(&self) -> &[T] {
+        &self.nedt
+    }
+
+    /// Add noise to the brightness temperatures `tb` in K of a single point,
+    /// with one value for each channel, or else return
+    /// [`RtmError::InconsistentInputs`].
+    pub fn add_to(&mut self, tb: &mut [T]) -> Result<(), RtmError> {
+        if tb.len() != self.nedt.len() {
+            return Err(RtmError::InconsistentInputs);
+        }
+        for (tb, &nedt) in tb.iter_mut().zip(&self.nedt) {
+            let noise: f64 = self.rng.sample(StandardNormal);
+            *tb = *tb + T::from_f64_lossy(noise * nedt.into_f64());
+        }
+        Ok(())
+    }
+}
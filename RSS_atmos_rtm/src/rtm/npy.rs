@@ -0,0 +1,471 @@
+//! Reading profiles from `.npy` files in chunks, for inputs that don't fit in
+//! memory.
+//!
+//! The profile arrays are read a chunk of points at a time, in order, with
+//! plain sequential reads of each file, so only one chunk is in memory at once
+//! and the operating system's read-ahead sees a single forward pass. Each
+//! chunk is an [`ArrayProfiles`] source, which is run as usual, e.g., with
+//! [`compute_rtm_source`](super::compute_rtm_source).
+//!
+//! Only little-endian `float32` and `float64` arrays in C order are supported,
+//! which is what `numpy.save` writes on the usual platforms. The values are
+//! converted to the precision of the chunk as they're read.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::num::NonZeroUsize;
+//! use rss_atmos_rtm::rtm::{
+//!     npy::{NpyProfileFiles, NpyProfiles},
+//!     ProfileSource, RtmParameters,
+//! };
+//!
+//! let mut profiles = NpyProfiles::open(&NpyProfileFiles {
+//!     pressure: "pressure.npy".into(),
+//!     temperature: "temperature.npy".into(),
+//!     height: "height.npy".into(),
+//!     specific_humidity: "specific_humidity.npy".into(),
+//!     liquid_content: None,
+//!     surface_temperature: "surface_temperature.npy".into(),
+//!     surface_height: "surface_height.npy".into(),
+//!     surface_dewpoint: "surface_dewpoint.npy".into(),
+//!     surface_pressure: Some("surface_pressure.npy".into()),
+//! })?;
+//! let parameters = RtmParameters::new(&[50.3, 53.74], &[0., 0.])?;
+//! for chunk in profiles.chunks::<f32>(NonZeroUsize::new(100_000).unwrap()) {
+//!     let chunk = chunk?;
+//!     let source = chunk.profiles(None)?;
+//!     for index in 0..source.len() {
+//!         let outputs = source.profile(index)?.inputs()?.run(&parameters);
+//!         // Write the outputs of point `chunk.start() + index`
+//!     }
+//! }
+//! # Ok::<(), rss_atmos_rtm::rtm::RtmError>(())
+//! ```
+
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+};
+
+use ndarray::ArrayView2;
+
+use super::{ArrayProfiles, Float, RtmError};
+
+/// The magic string at the start of a `.npy` file.
+const MAGIC: &[u8] = b"\x93NUMPY";
+
+/// An array in a `.npy` file, which is read a range of rows at a time.
+#[derive(Debug)]
+pub struct NpyArray {
+    file: File,
+    path: PathBuf,
+    dtype: NpyDtype,
+    shape: Vec<usize>,
+    data_offset: u64,
+}
+
+/// The element type of an [`NpyArray`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NpyDtype {
+    F32,
+    F64,
+}
+
+impl NpyDtype {
+    /// Size of an element in bytes.
+    fn size(self) -> usize {
+        match self {
+            NpyDtype::F32 => 4,
+            NpyDtype::F64 => 8,
+        }
+    }
+}
+
+impl NpyArray {
+    /// Open a `.npy` file and read its header.
+    ///
+    /// Returns [`RtmError::InvalidNpyFile`] if the header isn't valid, or the
+    /// array isn't little-endian `float32` or `float64` in C order.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, RtmError> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = File::open(&path)?;
+        let invalid =
+            |message: String| RtmError::InvalidNpyFile(format!("{}: {message}", path.display()));
+
+        let mut preamble = [0; 8];
+        file.read_exact(&mut preamble)
+            .map_err(|_| invalid("file is too short".to_string()))?;
+        if &preamble[..6] != MAGIC {
+            return Err(invalid("not a .npy file".to_string()));
+        }
+        let header_len = match preamble[6] {
+            1 => {
+                let mut len = [0; 2];
+                file.read_exact(&mut len)?;
+                usize::from(u16::from_le_bytes(len))
+            }
+            2 | 3 => {
+                let mut len = [0; 4];
+                file.read_exact(&mut len)?;
+                u32::from_le_bytes(len) as usize
+            }
+            version => return Err(invalid(format!("unsupported format version {version}"))),
+        };
+        let mut header = vec![0; header_len];
+        file.read_exact(&mut header)?;
+        let header = String::from_utf8_lossy(&header);
+
+        let field = |key: &str| {
+            header_field(&header, key).ok_or_else(|| invalid(format!("header has no {key}")))
+        };
+        let dtype = match field("descr")?.trim_matches(['\'', '"']) {
+            "<f4" => NpyDtype::F32,
+            "<f8" => NpyDtype::F64,
+            descr => {
+                return Err(invalid(format!(
+                    "dtype {descr} is not little-endian float32 or float64"
+                )))
+            }
+        };
+        if field("fortran_order")? != "False" {
+            return Err(invalid("array is not in C order".to_string()));
+        }
+        let shape = field("shape")?
+            .trim_matches(['(', ')'])
+            .split(',')
+            .map(str::trim)
+            .filter(|dim| !dim.is_empty())
+            .map(|dim| dim.parse())
+            .collect::<Result<Vec<usize>, _>>()
+            .map_err(|_| invalid("shape is not a tuple of integers".to_string()))?;
+        if shape.is_empty() {
+            return Err(invalid("array is a scalar".to_string()));
+        }
+
+        let data_offset = file.stream_position()?;
+        let size = shape.iter().product::<usize>() * dtype.size();
+        if file.metadata()?.len() < data_offset + size as u64 {
+            return Err(invalid(format!(
+                "file is too short for an array of shape {shape:?}"
+            )));
+        }
+        Ok(Self {
+            file,
+            path,
+            dtype,
+            shape,
+            data_offset,
+        })
+    }
+
+    /// The path of the file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The shape of the array.
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+
+    /// Number of values in each row, the product of the shape after the first
+    /// dimension.
+    fn row_len(&self) -> usize {
+        self.shape[1..].iter().product()
+    }
+
+    /// Read `num_rows` rows (along the first dimension) starting at row
+    /// `start`, in C order, converted to `T`.
+    ///
+    /// Returns [`RtmError::InconsistentInputs`] if the rows are out of bounds.
+    pub fn read_rows<T: Float>(
+        &mut self,
+        start: usize,
+        num_rows: usize,
+    ) -> Result<Vec<T>, RtmError> {
+        if start + num_rows > self.shape[0] {
+            return Err(RtmError::InconsistentInputs);
+        }
+        let row_bytes = self.row_len() * self.dtype.size();
+        self.file.seek(SeekFrom::Start(
+            self.data_offset + (start * row_bytes) as u64,
+        ))?;
+        let mut bytes = vec![0; num_rows * row_bytes];
+        self.file.read_exact(&mut bytes)?;
+        Ok(match self.dtype {
+            NpyDtype::F32 => bytes
+                .chunks_exact(4)
+                .map(|value| {
+                    let value = f32::from_le_bytes(value.try_into().unwrap());
+                    T::from_f64_lossy(value.into())
+                })
+                .collect(),
+            NpyDtype::F64 => bytes
+                .chunks_exact(8)
+                .map(|value| T::from_f64_lossy(f64::from_le_bytes(value.try_into().unwrap())))
+                .collect(),
+        })
+    }
+}
+
+/// The value of `key` in the header of a `.npy` file, a Python dict literal
+/// such as `{'descr': '<f4', 'fortran_order': False, 'shape': (10, 37), }`.
+fn header_field<'a>(header: &'a str, key: &str) -> Option<&'a str> {
+    let start = header.find(&format!("'{key}'"))? + key.len() + 2;
+    let value = header[start..].trim_start().strip_prefix(':')?.trim_start();
+    let end = match value.chars().next()? {
+        '(' => value.find(')')? + 1,
+        quote @ ('\'' | '"') => value[1..].find(quote)? + 2,
+        _ => value.find([',', '}'])?,
+    };
+    Some(value[..end].trim())
+}
+
+/// Paths of the `.npy` files of the profiles, with the same meaning and shapes
+/// as the arguments of [`ArrayProfiles::new`].
+///
+/// The `pressure` is either (`num_levels`, ) for levels shared by all of the
+/// profiles, or (`num_points`, `num_levels`) for levels that vary by profile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NpyProfileFiles {
+    /// Pressure levels in hPa.
+    pub pressure: PathBuf,
+    /// Physical temperature in K, (`num_points`, `num_levels`).
+    pub temperature: PathBuf,
+    /// Geopotential height in m, (`num_points`, `num_levels`).
+    pub height: PathBuf,
+    /// Specific humidity in kg/kg, (`num_points`, `num_levels`).
+    pub specific_humidity: PathBuf,
+    /// Optional liquid water content in kg/kg, (`num_points`, `num_levels`),
+    /// or clear sky if it's not given.
+    pub liquid_content: Option<PathBuf>,
+    /// 2 meter air temperature in K, (`num_points`, ).
+    pub surface_temperature: PathBuf,
+    /// Geopotential height of the surface in m, (`num_points`, ).
+    pub surface_height: PathBuf,
+    /// 2 meter dewpoint in K, (`num_points`, ).
+    pub surface_dewpoint: PathBuf,
+    /// Optional surface pressure in hPa, (`num_points`, ), or else the first
+    /// level is the surface.
+    pub surface_pressure: Option<PathBuf>,
+}
+
+/// Profiles in `.npy` files, which are read a chunk of points at a time.
+#[derive(Debug)]
+pub struct NpyProfiles {
+    pressure: NpyArray,
+    temperature: NpyArray,
+    height: NpyArray,
+    specific_humidity: NpyArray,
+    liquid_content: Option<NpyArray>,
+    surface_temperature: NpyArray,
+    surface_height: NpyArray,
+    surface_dewpoint: NpyArray,
+    surface_pressure: Option<NpyArray>,
+}
+
+impl NpyProfiles {
+    /// Open the files and check that their shapes are consistent.
+    ///
+    /// Returns [`RtmError::InconsistentInputs`] if the shapes don't match, or
+    /// an error from [`NpyArray::open`].
+    pub fn open(files: &NpyProfileFiles) -> Result<Self, RtmError> {
+        let open_optional = |path: &Option<PathBuf>| path.as_ref().map(NpyArray::open).transpose();
+        let profiles = Self {
+            pressure: NpyArray::open(&files.pressure)?,
+            temperature: NpyArray::open(&files.temperature)?,
+            height: NpyArray::open(&files.height)?,
+            specific_humidity: NpyArray::open(&files.specific_humidity)?,
+            liquid_content: open_optional(&files.liquid_content)?,
+            surface_temperature: NpyArray::open(&files.surface_temperature)?,
+            surface_height: NpyArray::open(&files.surface_height)?,
+            surface_dewpoint: NpyArray::open(&files.surface_dewpoint)?,
+            surface_pressure: open_optional(&files.surface_pressure)?,
+        };
+
+        let (num_points, num_levels) = match *profiles.temperature.shape() {
+            [num_points, num_levels] => (num_points, num_levels),
+            _ => return Err(RtmError::InconsistentInputs),
+        };
+        let levels = [
+            &profiles.temperature,
+            &profiles.height,
+            &profiles.specific_humidity,
+        ];
+        let points = [
+            &profiles.surface_temperature,
+            &profiles.surface_height,
+            &profiles.surface_dewpoint,
+        ];
+        let pressure_shape = profiles.pressure.shape();
+        if levels
+            .into_iter()
+            .chain(&profiles.liquid_content)
+            .any(|array| array.shape() != [num_points, num_levels])
+            || points
+                .into_iter()
+                .chain(&profiles.surface_pressure)
+                .any(|array| array.shape() != [num_points])
+            || (pressure_shape != [num_levels] && pressure_shape != [num_points, num_levels])
+        {
+            return Err(RtmError::InconsistentInputs);
+        }
+        Ok(profiles)
+    }
+
+    /// Number of profiles.
+    pub fn num_points(&self) -> usize {
+        self.temperature.shape()[0]
+    }
+
+    /// Number of levels in each profile.
+    pub fn num_levels(&self) -> usize {
+        self.temperature.shape()[1]
+    }
+
+    /// Read the `num_points` profiles starting at `start`.
+    ///
+    /// Returns [`RtmError::InconsistentInputs`] if the profiles are out of
+    /// bounds.
+    pub fn read_chunk<T: Float>(
+        &mut self,
+        start: usize,
+        num_points: usize,
+    ) -> Result<ProfileChunk<T>, RtmError> {
+        let pressure = if self.pressure.shape().len() == 1 {
+            PressureRows::Shared(self.pressure.read_rows(0, self.num_levels())?)
+        } else {
+            PressureRows::PerPoint(self.pressure.read_rows(start, num_points)?)
+        };
+        Ok(ProfileChunk {
+            start,
+            num_points,
+            num_levels: self.num_levels(),
+            pressure,
+            temperature: self.temperature.read_rows(start, num_points)?,
+            height: self.height.read_rows(start, num_points)?,
+            specific_humidity: self.specific_humidity.read_rows(start, num_points)?,
+            liquid_content: match &mut self.liquid_content {
+                Some(array) => array.read_rows(start, num_points)?,
+                None => Vec::new(),
+            },
+            surface_temperature: self.surface_temperature.read_rows(start, num_points)?,
+            surface_height: self.surface_height.read_rows(start, num_points)?,
+            surface_dewpoint: self.surface_dewpoint.read_rows(start, num_points)?,
+            surface_pressure: self
+                .surface_pressure
+                .as_mut()
+                .map(|array| array.read_rows(start, num_points))
+                .transpose()?,
+        })
+    }
+
+    /// Read all of the profiles in order, `chunk_size` points at a time (the
+    /// last chunk may be smaller).
+    pub fn chunks<T: Float>(
+        &mut self,
+        chunk_size: NonZeroUsize,
+    ) -> impl Iterator<Item = Result<ProfileChunk<T>, RtmError>> + '_ {
+        let num_points = self.num_points();
+        (0..num_points)
+            .step_by(chunk_size.get())
+            .map(move |start| self.read_chunk(start, chunk_size.get().min(num_points - start)))
+    }
+}
+
+/// A chunk of profiles read from [`NpyProfiles`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileChunk<T: Float = f32> {
+    start: usize,
+    num_points: usize,
+    num_levels: usize,
+    pressure: PressureRows<T>,
+    temperature: Vec<T>,
+    height: Vec<T>,
+    specific_humidity: Vec<T>,
+    liquid_content: Vec<T>,
+    surface_temperature: Vec<T>,
+    surface_height: Vec<T>,
+    surface_dewpoint: Vec<T>,
+    surface_pressure: Option<Vec<T>>,
+}
+
+/// The pressure levels of a [`ProfileChunk`].
+#[derive(Debug, Clone, PartialEq)]
+enum PressureRows<T> {
+    /// The same levels for all of the profiles.
+    Shared(Vec<T>),
+    /// Levels for each profile, in C order.
+    PerPoint(Vec<T>),
+}
+
+impl<T: Float> ProfileChunk<T> {
+    /// Index of the first profile of the chunk in the files.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Number of profiles in the chunk.
+    pub fn len(&self) -> usize {
+        self.num_points
+    }
+
+    /// Whether the chunk has no profiles.
+    pub fn is_empty(&self) -> bool {
+        self.num_points == 0
+    }
+
+    /// Borrow the profiles of the chunk, with the levels above `top_pressure`
+    /// in hPa discarded if it's given.
+    pub fn profiles(&self, top_pressure: Option<T>) -> Result<ArrayProfiles<'_, T>, RtmError> {
+        let view = |values, num_levels| view(values, self.num_points, num_levels);
+        let temperature = view(&self.temperature, self.num_levels)?;
+        let height = view(&self.height, self.num_levels)?;
+        let specific_humidity = view(&self.specific_humidity, self.num_levels)?;
+        // Clear sky has no liquid content levels
+        let liquid_levels = if self.liquid_content.is_empty() {
+            0
+        } else {
+            self.num_levels
+        };
+        let liquid_content = view(&self.liquid_content, liquid_levels)?;
+        match &self.pressure {
+            PressureRows::Shared(pressure) => ArrayProfiles::new(
+                pressure,
+                temperature,
+                height,
+                specific_humidity,
+                liquid_content,
+                &self.surface_temperature,
+                &self.surface_height,
+                &self.surface_dewpoint,
+                self.surface_pressure.as_deref(),
+                None,
+                None,
+                top_pressure,
+            ),
+            PressureRows::PerPoint(pressure) => ArrayProfiles::new_per_point_pressure(
+                view(pressure, self.num_levels)?,
+                temperature,
+                height,
+                specific_humidity,
+                liquid_content,
+                &self.surface_temperature,
+                &self.surface_height,
+                &self.surface_dewpoint,
+                self.surface_pressure.as_deref(),
+                None,
+                None,
+                top_pressure,
+            ),
+        }
+    }
+}
+
+/// View `values` as a (`num_rows`, `num_cols`) array.
+fn view<T>(values: &[T], num_rows: usize, num_cols: usize) -> Result<ArrayView2<'_, T>, RtmError> {
+    ArrayView2::from_shape((num_rows, num_cols), values).map_err(|_| RtmError::InconsistentInputs)
+}
@@ -0,0 +1,204 @@
+//! The zenith optical depth of each absorber, and its extremes over a band.
+
+use super::{
+    core::absorption_components, BuiltinAbsorption, ChannelVec, Float, RtmError, RtmInputs,
+    RtmParameters,
+};
+
+/// Number of steps of the coarse sweep in [`RtmInputs::opacity_extremum`].
+const SWEEP_STEPS: usize = 200;
+
+/// Number of golden-section iterations that refine the frequency in
+/// [`RtmInputs::opacity_extremum`], narrowing the two steps around the best
+/// frequency of the sweep by a factor of 0.618⁴⁰ ≈ 4e-9.
+const REFINE_ITERATIONS: usize = 40;
+
+/// The zenith optical depth of the whole column for each channel, split by
+/// absorber.
+///
+/// Their sum is the total zenith optical depth, `-ln(tran)` for an incidence
+/// angle of 0°, to within rounding.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OpticalDepths<T: Float = f32> {
+    /// Optical depth of oxygen for each channel.
+    pub oxygen: ChannelVec<T>,
+    /// Optical depth of water vapor for each channel.
+    pub water_vapor: ChannelVec<T>,
+    /// Optical depth of cloud liquid water for each channel.
+    pub cloud: ChannelVec<T>,
+}
+
+impl<T: Float> RtmInputs<T> {
+    /// Compute the zenith optical depth of oxygen, water vapor, and cloud
+    /// liquid water for each channel of `parameters`, to attribute the
+    /// atmospheric opacity to the absorbers.
+    ///
+    /// Each absorber's coefficient is integrated over the layers from the
+    /// surface to the top in the same way as the total absorption in
+    /// [`run`](Self::run), but straight up, so the incidence angles are not
+    /// used. The optical depths are at the frequency of each channel, which
+    /// is the centroid for a passband, and the builtin absorption models are
+    /// always used, with the
+    /// [`absorption_scale`](RtmParameters::absorption_scale) of `parameters`.
+    pub fn zenith_optical_depths(&self, parameters: &RtmParameters<T>) -> OpticalDepths<T> {
+        let levels = self.surface_index..=self.num_levels.get();
+        let z = &self.height[levels.clone()];
+        let mut depths = OpticalDepths {
+            oxygen: ChannelVec::new(),
+            water_vapor: ChannelVec::new(),
+            cloud: ChannelVec::new(),
+        };
+        for &freq in &parameters.frequency {
+            // Converted from Np/km to Np/m as in the RTM
+            let absorption: Vec<[T; 3]> = levels
+                .clone()
+                .map(|i| {
+                    absorption_components(
+                        self.pressure[i],
+                        self.temperature[i],
+                        self.vapor_pressure[i],
+                        self.rho_l[i],
+                        freq,
+                        &parameters.absorption_scale,
+                    )
+                    .map(|component| component * T::lit(1.0e-3))
+                })
+                .collect();
+            // The trapezoid rule over each layer, in double precision as in the
+            // RTM
+            let depth = |component: usize| {
+                let sum: f64 = (1..z.len())
+                    .map(|i| {
+                        0.5 * (absorption[i - 1][component].into_f64()
+                            + absorption[i][component].into_f64())
+                            * (z[i].into_f64() - z[i - 1].into_f64())
+                    })
+                    .sum();
+                T::from_f64_lossy(sum)
+            };
+            depths.oxygen.push(depth(0));
+            depths.water_vapor.push(depth(1));
+            depths.cloud.push(depth(2));
+        }
+        depths
+    }
+}
+
+/// The frequency in a band where the zenith optical depth is largest or
+/// smallest, from [`RtmInputs::opacity_extremum`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OpacityExtremum<T: Float = f32> {
+    /// Frequency in GHz.
+    pub frequency: T,
+    /// Zenith optical depth at that frequency.
+    pub optical_depth: T,
+}
+
+impl<T: Float> RtmInputs<T> {
+    /// Compute the total zenith optical depth at a frequency in GHz.
+    ///
+    /// This is the sum of the [`zenith_optical_depths`](Self::zenith_optical_depths)
+    /// of the absorbers, and `-ln(tran)` of the RTM at an incidence angle of
+    /// 0°, with the absorption models as published (not scaled by an
+    /// [`AbsorptionScale`](super::AbsorptionScale)).
+    pub fn zenith_optical_depth(&self, frequency: T) -> T {
+        T::from_f64_lossy(self.zenith_depth(frequency))
+    }
+
+    /// The zenith optical depth, integrated from the total absorption profile
+    /// in the same way as the layers of the RTM.
+    fn zenith_depth(&self, frequency: T) -> f64 {
+        let tabs = self.absorption_from(self.surface_index, frequency, &BuiltinAbsorption);
+        let z = &self.height[self.surface_index..];
+        (1..z.len())
+            .map(|i| {
+                0.5 * (tabs[i - 1].into_f64() + tabs[i].into_f64())
+                    * (z[i].into_f64() - z[i - 1].into_f64())
+            })
+            .sum()
+    }
+
+    /// Find the frequency from `freq_min` to `freq_max` in GHz where the zenith
+    /// optical depth is largest, if `maximize` is true, or else smallest, for
+    /// placing a channel.
+    ///
+    /// The band is swept in 200 steps, and then the frequency is refined with
+    /// a golden-section search over the step on either side of the best
+    /// frequency of the sweep. A feature narrower than a step can be missed,
+    /// so the band should be narrow enough to resolve the lines of interest.
+    /// The extremum is never worse than the best frequency of the sweep,
+    /// including the ends of the band.
+    ///
+    /// Returns [`RtmError::InvalidFrequencyRange`] if the band is empty, not
+    /// finite, or doesn't have positive frequencies.
+    pub fn opacity_extremum(
+        &self,
+        freq_min: T,
+        freq_max: T,
+        maximize: bool,
+    ) -> Result<OpacityExtremum<T>, RtmError> {
+        let (start, stop) = (freq_min.into_f64(), freq_max.into_f64());
+        let step = (stop - start) / SWEEP_STEPS as f64;
+        // Written so that NaN is also rejected
+        if !(start > 0. && stop > start && stop.is_finite()) {
+            return Err(RtmError::InvalidFrequencyRange { start, stop, step });
+        }
+
+        // The objective is minimized either way
+        let sign = if maximize { -1. } else { 1. };
+        let objective = |freq: f64| sign * self.zenith_depth(T::from_f64_lossy(freq));
+        let frequency = |i: usize| {
+            if i == SWEEP_STEPS {
+                stop
+            } else {
+                start + i as f64 * step
+            }
+        };
+
+        let (best_index, mut best) = (0..=SWEEP_STEPS)
+            .map(|i| (i, objective(frequency(i))))
+            .fold((0, f64::INFINITY), |best, current| {
+                if current.1 < best.1 {
+                    current
+                } else {
+                    best
+                }
+            });
+        let mut best_freq = frequency(best_index);
+
+        // Golden-section search between the neighbors of the best frequency
+        let ratio = (5_f64.sqrt() - 1.) / 2.;
+        let (mut lower, mut upper) = (
+            frequency(best_index.saturating_sub(1)),
+            frequency((best_index + 1).min(SWEEP_STEPS)),
+        );
+        let mut inner_lower = upper - ratio * (upper - lower);
+        let mut inner_upper = lower + ratio * (upper - lower);
+        let (mut value_lower, mut value_upper) = (objective(inner_lower), objective(inner_upper));
+        for _ in 0..REFINE_ITERATIONS {
+            if value_lower < value_upper {
+                upper = inner_upper;
+                (inner_upper, value_upper) = (inner_lower, value_lower);
+                inner_lower = upper - ratio * (upper - lower);
+                value_lower = objective(inner_lower);
+            } else {
+                lower = inner_lower;
+                (inner_lower, value_lower) = (inner_upper, value_upper);
+                inner_upper = lower + ratio * (upper - lower);
+                value_upper = objective(inner_upper);
+            }
+        }
+        for (freq, value) in [(inner_lower, value_lower), (inner_upper, value_upper)] {
+            if value < best {
+                (best_freq, best) = (freq, value);
+            }
+        }
+
+        Ok(OpacityExtremum {
+            frequency: T::from_f64_lossy(best_freq),
+            optical_depth: T::from_f64_lossy(sign * best),
+        })
+    }
+}
@@ -0,0 +1,89 @@
+//! Model and numerical choices of the RTM, gathered in one value.
+
+use std::num::NonZeroUsize;
+
+use super::{
+    threads, AbsorptionBackend, AbsorptionScale, Emission, Float, IntegrationScheme,
+    LayerSumReference, RtmInputs, RtmParameters, TbConvention,
+};
+
+/// The model and numerical choices of the RTM that aren't tied to the channels
+/// or the profiles, so the same choices can be passed around and applied
+/// together.
+///
+/// The defaults are the same as the defaults of [`RtmParameters`] and
+/// [`RtmInputs`]. Apply them with [`RtmParameters::with_options`] and
+/// [`RtmInputs::with_options`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct RtmOptions {
+    /// How the emission of each layer is computed.
+    pub emission: Emission,
+    /// The convention of the output brightness temperatures, or `None` for
+    /// the same convention as the emission.
+    pub tb_convention: Option<TbConvention>,
+    /// How the layer emission is integrated.
+    pub integration_scheme: IntegrationScheme,
+    /// The temperature that the layer emission is summed relative to.
+    pub layer_sum_reference: LayerSumReference,
+    /// Whether to clamp the surface vapor pressure to saturation at the
+    /// surface temperature.
+    pub clamp_surface_saturation: bool,
+    /// Factors on the absorption of each absorber.
+    pub absorption_scale: AbsorptionScale,
+    /// Where the gas absorption is computed for a batch (experimental).
+    pub absorption_backend: AbsorptionBackend,
+    /// The target number of (point, channel) pairs for each worker thread of
+    /// a batch, which chooses the number of threads when it isn't given.
+    pub items_per_thread: NonZeroUsize,
+    /// The most points that a worker thread of a batch runs as one chunk.
+    pub work_chunk_size: NonZeroUsize,
+}
+
+impl Default for RtmOptions {
+    fn default() -> Self {
+        Self {
+            emission: Emission::default(),
+            tb_convention: None,
+            integration_scheme: IntegrationScheme::default(),
+            layer_sum_reference: LayerSumReference::default(),
+            clamp_surface_saturation: false,
+            absorption_scale: AbsorptionScale::default(),
+            absorption_backend: AbsorptionBackend::default(),
+            items_per_thread: threads::default_items_per_thread(),
+            work_chunk_size: threads::default_work_chunk_size(),
+        }
+    }
+}
+
+impl<T: Float> RtmParameters<T> {
+    /// Use the emission, brightness temperature convention, integration
+    /// scheme, layer sum reference, absorption scale, absorption backend, and
+    /// splitting of a batch between the threads of `options`, replacing any
+    /// that were set before.
+    pub fn with_options(mut self, options: &RtmOptions) -> Self {
+        self.emission = options.emission;
+        self.tb_convention = options.tb_convention;
+        self.integration_scheme = options.integration_scheme;
+        self.layer_sum_reference = options.layer_sum_reference;
+        self.absorption_scale = options.absorption_scale;
+        self.absorption_backend = options.absorption_backend;
+        self.items_per_thread = options.items_per_thread;
+        self.work_chunk_size = options.work_chunk_size;
+        self
+    }
+}
+
+impl<T: Float> RtmInputs<T> {
+    /// Apply the choices of `options` for the profile, which is the surface
+    /// saturation clamp (see
+    /// [`with_surface_saturation_clamp`](Self::with_surface_saturation_clamp)).
+    pub fn with_options(self, options: &RtmOptions) -> Self {
+        if options.clamp_surface_saturation {
+            self.with_surface_saturation_clamp()
+        } else {
+            self
+        }
+    }
+}
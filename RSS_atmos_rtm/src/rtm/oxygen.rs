@@ -4,10 +4,31 @@
 
 use std::sync::OnceLock;
 
+use super::Float;
+
 const NLINES: usize = 44;
 
+/// The range of temperatures in K that the model is evaluated at: colder or
+/// warmer layers use the nearest end of the range. The line shapes have powers
+/// of `t_ref / t`, which overflow as `t` goes to 0 and are undefined below it, so
+/// this keeps a single pathological layer finite. The range includes any
+/// atmospheric temperature, and points outside of the narrower
+/// [`QualityFlags::TEMPERATURE_OUT_OF_RANGE`](super::QualityFlags) are flagged
+/// anyway.
+pub(crate) const MODEL_TEMPERATURE_RANGE: std::ops::RangeInclusive<f32> = 100.0..=400.0;
+
+/// The largest absorption coefficient in dB/km that
+/// [`fdabsoxy_1992_modified`] returns. This is hundreds of times the peak of
+/// the 60 GHz band at the surface (about 15 dB/km), so it's only reached for
+/// pathological inputs, such as a frequency of many THz or an enormous
+/// pressure, where the high-frequency term would otherwise overflow to
+/// infinity and make the whole column infinite. A layer at the maximum is
+/// opaque.
+pub(crate) const MAX_ABSORPTION: f32 = 1e4;
+
 /// Oxygen absorption coefficients
-struct OxygenCoefficients {
+#[derive(Debug, Clone)]
+pub(crate) struct OxygenCoefficients {
     f0: [f32; NLINES],
     a1: [f32; NLINES],
     a2: [f32; NLINES],
@@ -18,6 +39,33 @@ struct OxygenCoefficients {
 }
 
 impl OxygenCoefficients {
+    /// The coefficients as published, which are only initialized once.
+    pub(crate) fn builtin() -> &'static Self {
+        static COEF: OnceLock<OxygenCoefficients> = OnceLock::new();
+        COEF.get_or_init(Self::new)
+    }
+
+    /// Each coefficient for all of the lines, in the order `f0`, then `a1`
+    /// to `a6`, for the GPU.
+    #[cfg(feature = "gpu")]
+    pub(crate) fn columns(&self) -> [&[f32]; 7] {
+        [
+            &self.f0, &self.a1, &self.a2, &self.a3, &self.a4, &self.a5, &self.a6,
+        ]
+    }
+
+    /// The coefficients with the line intensities (`a1`) and widths (`a3`)
+    /// changed by the fractions `intensity` and `width`.
+    pub(crate) fn perturbed(intensity: f64, width: f64) -> Self {
+        let mut coef = Self::new();
+        let perturb = |value: &mut f32, fraction: f64| {
+            *value = (f64::from(*value) * (1. + fraction)) as f32;
+        };
+        coef.a1.iter_mut().for_each(|a1| perturb(a1, intensity));
+        coef.a3.iter_mut().for_each(|a3| perturb(a3, width));
+        coef
+    }
+
     /// Initialize the oxygen coefficients.
     #[allow(clippy::excessive_precision)]
     fn new() -> Self {
@@ -96,16 +144,29 @@ impl OxygenCoefficients {
 ///
 /// For a total pressure `p` in hPa, temperature `t` in K, water vapor pressure
 /// `pv` in hPa, and frequency `freq` in GHz, compute the oxygen absorption
-/// coefficient in dB/km.
+/// coefficient in dB/km, with the coefficients `coef` (normally
+/// [`OxygenCoefficients::builtin`]) tabulated at the reference temperature
+/// `t_ref` in K (normally
+/// [`REFERENCE_TEMPERATURE`](super::REFERENCE_TEMPERATURE)).
 ///
 /// From: Atmospheric 60-GHz Oxygen Spectrum:.. Liebe, Rosenkranz, Hufford,
 /// 1992. Modified over the years by Frank Wentz and converted from Fortran to
 /// Rust by Richard Lindsley.
-pub(crate) fn fdabsoxy_1992_modified(p: f32, t: f32, pv: f32, freq: f32) -> f32 {
+///
+/// The temperature is clamped to [`MODEL_TEMPERATURE_RANGE`] and the result to
+/// at most [`MAX_ABSORPTION`], so that extreme but finite inputs give a finite
+/// absorption. NaN inputs still give NaN.
+pub(crate) fn fdabsoxy_1992_modified<T: Float>(
+    coef: &OxygenCoefficients,
+    p: T,
+    t: T,
+    pv: T,
+    freq: T,
+    t_ref: f32,
+) -> T {
     // Many of the variables are retained from the original Fortran
+    let c = T::lit;
 
-    /// Ensure the coefficients are only initialized once.
-    static COEF: OnceLock<OxygenCoefficients> = OnceLock::new();
     let OxygenCoefficients {
         f0,
         a1,
@@ -114,12 +175,21 @@ pub(crate) fn fdabsoxy_1992_modified(p: f32, t: f32, pv: f32, freq: f32) -> f32
         a4,
         a5,
         a6,
-    } = COEF.get_or_init(OxygenCoefficients::new);
-
-    let tht = 300.0 / t;
-    let pwet = 0.1 * pv;
-    let pdry = 0.1 * p - pwet;
-    let xterm = 1.0 - tht;
+    } = coef;
+
+    // A NaN temperature isn't clamped, so that it propagates
+    let (t_min, t_max) = MODEL_TEMPERATURE_RANGE.into_inner();
+    let t = if t < c(t_min) {
+        c(t_min)
+    } else if t > c(t_max) {
+        c(t_max)
+    } else {
+        t
+    };
+    let tht = c(t_ref) / t;
+    let pwet = c(0.1) * pv;
+    let pdry = c(0.1) * p - pwet;
+    let xterm = c(1.0) - tht;
 
     // Rather than doing one loop over the oxygen lines (as in the original
     // Fortran), it works out better to build some lazy iterators, collect an
@@ -129,17 +199,17 @@ pub(crate) fn fdabsoxy_1992_modified(p: f32, t: f32, pv: f32, freq: f32) -> f32
         let ga = a3
             .iter()
             .zip(a4)
-            .map(|(a3, a4)| a3 * (pdry * tht.powf(0.8 - a4) + 1.1 * tht * pwet));
+            .map(|(&a3, &a4)| c(a3) * (pdry * tht.powf(c(0.8 - a4)) + c(1.1) * tht * pwet));
 
         let delta = a5
             .iter()
             .zip(a6)
-            .map(|(a5, a6)| (a5 + a6 * tht) * p * tht.powf(0.8));
+            .map(|(&a5, &a6)| (c(a5) + c(a6) * tht) * p * tht.powf(c(0.8)));
 
-        let mut ff = [0.; NLINES];
-        for (((ff, f0), ga), delta) in ff.iter_mut().zip(f0).zip(ga).zip(delta) {
-            let rnuneg = f0 - freq;
-            let rnupos = f0 + freq;
+        let mut ff = [T::zero(); NLINES];
+        for (((ff, &f0), ga), delta) in ff.iter_mut().zip(f0).zip(ga).zip(delta) {
+            let rnuneg = c(f0) - freq;
+            let rnupos = c(f0) + freq;
             let ga_sq = ga.powi(2);
 
             *ff = (ga - rnuneg * delta) / (ga_sq + rnuneg.powi(2))
@@ -149,24 +219,31 @@ pub(crate) fn fdabsoxy_1992_modified(p: f32, t: f32, pv: f32, freq: f32) -> f32
         ff.iter()
             .zip(a1)
             .zip(a2)
-            .map(|((ff, a1), a2)| f64::from(ff * a1 * f32::exp(a2 * xterm)))
+            .map(|((&ff, &a1), &a2)| (ff * c(a1) * (c(a2) * xterm).exp()).into_f64())
             .sum()
     };
     let sum = sum.max(0.0);
 
     // add nonresonant contribution ("modification 1")
-    let ga = 5.6e-3 * (pdry + 1.1 * pwet) * tht.powf(1.5);
-
-    let zterm = ga * (1. + (freq / ga).powi(2));
-    let apterm = 1.4e-10 * (1.0 - 1.2e-5 * freq.powf(1.5)) * pdry * tht.powf(1.5);
-    let apterm = apterm.max(0.);
-    let sftot = (f64::from(pdry * freq * tht.powi(2))
-        * (f64::from(tht) * sum + 6.14e-4 / f64::from(zterm) + f64::from(apterm)))
-        as f32;
-
-    let gamoxy = 0.1820 * freq * sftot;
-    if freq > 37. {
-        gamoxy + 0.1820 * 26.0e-10 * pdry.powi(2) * tht.powi(3) * (freq - 37.).powf(1.8)
+    let ga = c(5.6e-3) * (pdry + c(1.1) * pwet) * tht.powf(c(1.5));
+
+    let zterm = ga * (c(1.) + (freq / ga).powi(2));
+    let apterm = c(1.4e-10) * (c(1.0) - c(1.2e-5) * freq.powf(c(1.5))) * pdry * tht.powf(c(1.5));
+    let apterm = apterm.max(T::zero());
+    let sftot = T::from_f64_lossy(
+        (pdry * freq * tht.powi(2)).into_f64()
+            * (tht.into_f64() * sum + 6.14e-4 / zterm.into_f64() + apterm.into_f64()),
+    );
+
+    let gamoxy = c(0.1820) * freq * sftot;
+    let gamoxy = if freq > c(37.) {
+        gamoxy + c(0.1820) * c(26.0e-10) * pdry.powi(2) * tht.powi(3) * (freq - c(37.)).powf(c(1.8))
+    } else {
+        gamoxy
+    };
+    // Infinity is clamped too, but NaN isn't
+    if gamoxy > c(MAX_ABSORPTION) {
+        c(MAX_ABSORPTION)
     } else {
         gamoxy
     }
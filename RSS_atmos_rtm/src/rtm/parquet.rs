@@ -0,0 +1,182 @@
+//! Writing the outputs of the RTM as Arrow record batches and Parquet files,
+//! together with coordinates of the points, for analysis with Arrow-based
+//! tools.
+//!
+//! The outputs are arrays with shape (`num_points`, `num_freq`), such as
+//! `tb_up`, and the per-point columns are arrays with one value for each
+//! point, of any Arrow type: the caller's coordinates, such as the time,
+//! latitude, and longitude, and the per-point outputs, such as the quality
+//! flags. They're arranged in one of two [`Layout`]s:
+//!
+//! - [`Layout::Wide`] has a row for each point, with the per-point columns and
+//!   then a column for each output and channel, named `{output}_{channel}`,
+//!   e.g., `tb_up_0`, `tb_up_1`, ...
+//! - [`Layout::Long`] has a row for each point and channel, with the channels
+//!   varying fastest: the `point` and `channel` indices as `uint64`, the
+//!   per-point columns repeated for each channel, and then a column for each
+//!   output, like the Python `AtmoParameters.flat_records()`.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use arrow_array::{ArrayRef, Float64Array};
+//! use ndarray::Array2;
+//! use rss_atmos_rtm::rtm::parquet::{record_batch, write_parquet, Layout};
+//!
+//! let latitude: ArrayRef = Arc::new(Float64Array::from(vec![10., 20.]));
+//! let tb_up = Array2::<f32>::zeros((2, 3));
+//! let batch = record_batch(
+//!     &[("latitude".to_string(), latitude)],
+//!     &[("tb_up".to_string(), tb_up.view())],
+//!     Layout::Wide,
+//! )?;
+//! write_parquet("outputs.parquet".as_ref(), &batch)?;
+//! # Ok::<(), rss_atmos_rtm::rtm::RtmError>(())
+//! ```
+
+use std::{collections::HashSet, fs::File, path::Path, sync::Arc};
+
+use ::parquet::arrow::ArrowWriter;
+use arrow_array::{ArrayRef, Float32Array, Float64Array, RecordBatch, UInt64Array};
+use ndarray::ArrayView2;
+
+use super::{Float, RtmError};
+
+/// How the outputs are arranged in a record batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Layout {
+    /// A row for each point, and a column for each output and channel.
+    #[default]
+    Wide,
+    /// A row for each point and channel, and a column for each output.
+    Long,
+}
+
+impl Layout {
+    /// The name of the layout, as accepted by [`Layout::from_name`].
+    pub fn name(self) -> &'static str {
+        match self {
+            Layout::Wide => "wide",
+            Layout::Long => "long",
+        }
+    }
+
+    /// The layout with the given name, `"wide"` or `"long"`, if there is one.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "wide" => Some(Layout::Wide),
+            "long" => Some(Layout::Long),
+            _ => None,
+        }
+    }
+}
+
+/// A floating-point precision with a corresponding Arrow array type.
+pub trait ArrowFloat: Float {
+    /// Wrap the values in an Arrow array of the same precision.
+    fn arrow_array(values: Vec<Self>) -> ArrayRef;
+}
+
+impl ArrowFloat for f32 {
+    fn arrow_array(values: Vec<Self>) -> ArrayRef {
+        Arc::new(Float32Array::from(values))
+    }
+}
+
+impl ArrowFloat for f64 {
+    fn arrow_array(values: Vec<Self>) -> ArrayRef {
+        Arc::new(Float64Array::from(values))
+    }
+}
+
+/// Arrange the per-point columns and the outputs as a record batch with the
+/// given [`Layout`].
+///
+/// Each of the `point_columns` is a name and an array with one value for each
+/// point, and each of the `outputs` is a name and an array with shape
+/// (`num_points`, `num_freq`). The columns are in the order given, after the
+/// `point` and `channel` indices for [`Layout::Long`]. NaN outputs are written
+/// as NaN, not as nulls.
+///
+/// Returns [`RtmError::InconsistentInputs`] if the columns don't all have the
+/// same number of points, or the outputs the same number of channels, and
+/// [`RtmError::Parquet`] if two columns have the same name.
+pub fn record_batch<T: ArrowFloat>(
+    point_columns: &[(String, ArrayRef)],
+    outputs: &[(String, ArrayView2<'_, T>)],
+    layout: Layout,
+) -> Result<RecordBatch, RtmError> {
+    let (num_points, num_freq) = match (outputs.first(), point_columns.first()) {
+        (Some((_, output)), _) => output.dim(),
+        (None, Some((_, column))) => (column.len(), 0),
+        (None, None) => (0, 0),
+    };
+    if point_columns
+        .iter()
+        .any(|(_, column)| column.len() != num_points)
+        || outputs
+            .iter()
+            .any(|(_, output)| output.dim() != (num_points, num_freq))
+    {
+        return Err(RtmError::InconsistentInputs);
+    }
+
+    let mut columns: Vec<(String, ArrayRef)> = Vec::new();
+    match layout {
+        Layout::Wide => {
+            columns.extend(point_columns.iter().cloned());
+            for (name, output) in outputs {
+                for (channel, values) in output.columns().into_iter().enumerate() {
+                    columns.push((format!("{name}_{channel}"), T::arrow_array(values.to_vec())));
+                }
+            }
+        }
+        Layout::Long => {
+            let num_rows = num_points * num_freq;
+            let point: UInt64Array = (0..num_rows).map(|i| (i / num_freq) as u64).collect();
+            let channel: UInt64Array = (0..num_rows).map(|i| (i % num_freq) as u64).collect();
+            // The per-point values are repeated for each channel of a point,
+            // by taking them at the point index of each row
+            let mut repeated = Vec::with_capacity(point_columns.len());
+            for (name, column) in point_columns {
+                let column = arrow_select::take::take(column, &point, None)
+                    .map_err(|e| RtmError::Parquet(e.to_string()))?;
+                repeated.push((name.clone(), column));
+            }
+            columns.push(("point".to_string(), Arc::new(point)));
+            columns.push(("channel".to_string(), Arc::new(channel)));
+            columns.extend(repeated);
+            for (name, output) in outputs {
+                columns.push((
+                    name.clone(),
+                    T::arrow_array(output.iter().copied().collect()),
+                ));
+            }
+        }
+    }
+
+    let mut names = HashSet::new();
+    if let Some((name, _)) = columns.iter().find(|(name, _)| !names.insert(name)) {
+        return Err(RtmError::Parquet(format!("duplicate column name: {name}")));
+    }
+    RecordBatch::try_from_iter(columns).map_err(|e| RtmError::Parquet(e.to_string()))
+}
+
+/// Write a record batch to a new Parquet file at `path`, replacing any
+/// existing file.
+///
+/// Returns [`RtmError::Io`] if the file can't be created, and
+/// [`RtmError::Parquet`] if it can't be written.
+pub fn write_parquet(path: &Path, batch: &RecordBatch) -> Result<(), RtmError> {
+    let file = File::create(path).map_err(RtmError::Io)?;
+    let mut writer = ArrowWriter::try_new(file, batch.schema(), None)
+        .map_err(|e| RtmError::Parquet(e.to_string()))?;
+    writer
+        .write(batch)
+        .map_err(|e| RtmError::Parquet(e.to_string()))?;
+    writer
+        .close()
+        .map_err(|e| RtmError::Parquet(e.to_string()))?;
+    Ok(())
+}
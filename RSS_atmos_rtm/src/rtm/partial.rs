@@ -0,0 +1,142 @@
+//! Caching the RTM integral over the lower atmosphere.
+
+use super::{
+    core::{lower_sums, seeded_atm_tran, PartialSums},
+    ChannelVec, Float, LevelVec, RtmError, RtmInputs, RtmOutputs, RtmParameters, FREQUENCY_LIMITS,
+};
+
+/// The RTM integrated over the lower part of a profile, for recomputing the
+/// outputs cheaply after perturbing only the upper levels.
+///
+/// This is created by [`RtmInputs::cache_lower`], which integrates the layers
+/// from the surface up to a split level once. Then [`run`](Self::run) only
+/// computes the absorption and integrates the layers above the split level
+/// for each perturbed profile, which is much faster when the split is high in
+/// the atmosphere. The outputs are the same as from [`RtmInputs::run`] to
+/// within the floating-point rounding from summing in a different order.
+#[derive(Debug, Clone)]
+pub struct LowerAtmosphere<T: Float = f32> {
+    /// The inputs the lower part was integrated for.
+    base: RtmInputs<T>,
+    /// The channels the lower part was integrated for.
+    parameters: RtmParameters<T>,
+    /// The monochromatic channels, one for each node of any passbands of
+    /// `parameters`, that the lower part was integrated for.
+    nodes: RtmParameters<T>,
+    /// Index of the split level in the profiles of `base`.
+    split_index: usize,
+    /// Partial sums of the lower layers for each channel.
+    sums: ChannelVec<PartialSums>,
+}
+
+impl<T: Float> RtmInputs<T> {
+    /// Integrate the RTM over the lower part of the profile, up to the split
+    /// level: the highest level with a pressure of at least `split_pressure`
+    /// in hPa (but at least the first level above the surface).
+    ///
+    /// The result can be run on perturbed copies of these inputs that only
+    /// differ above the split level. See [`LowerAtmosphere`].
+    pub fn cache_lower(
+        &self,
+        parameters: &RtmParameters<T>,
+        split_pressure: T,
+    ) -> LowerAtmosphere<T> {
+        let num_lower = self.pressure[self.surface_index + 1..=self.num_levels.get()]
+            .iter()
+            .take_while(|&&p| p >= split_pressure)
+            .count()
+            .max(1);
+        let split_index = self.surface_index + num_lower;
+        let nodes = parameters
+            .passband_nodes()
+            .unwrap_or_else(|| parameters.clone());
+
+        let mut sums = ChannelVec::new();
+        self.for_each_channel(&nodes, |freq, inc, absorption_profile| {
+            sums.push(lower_sums(
+                nodes.integration_scheme,
+                nodes.layer_sum_reference,
+                inc,
+                &self.source_profile(nodes.emission, freq)[..=num_lower],
+                &self.height[self.surface_index..=split_index],
+                &absorption_profile[..=num_lower],
+            ));
+        });
+
+        LowerAtmosphere {
+            base: self.clone(),
+            parameters: parameters.clone(),
+            nodes,
+            split_index,
+            sums,
+        }
+    }
+}
+
+impl<T: Float> LowerAtmosphere<T> {
+    /// Apply the RTM on `inputs`, reusing the integral over the lower part of
+    /// the profile.
+    ///
+    /// The `inputs` must have the same number of levels and surface index as
+    /// the ones this was created from, and identical values on the levels up
+    /// to and including the split level, otherwise this is an
+    /// [`RtmError::InconsistentInputs`]. Inputs with an observer height are
+    /// run in full, without the cache.
+    pub fn run(&self, inputs: &RtmInputs<T>) -> Result<RtmOutputs<T>, RtmError> {
+        let base = &self.base;
+        let lower = base.surface_index..=self.split_index;
+        // NaN values (which are flagged in the quality) match each other
+        let same_lower = |a: &[T], b: &[T]| {
+            a[lower.clone()]
+                .iter()
+                .zip(&b[lower.clone()])
+                .all(|(a, b)| a == b || (a.is_nan() && b.is_nan()))
+        };
+        if inputs.num_levels != base.num_levels
+            || inputs.surface_index != base.surface_index
+            || !same_lower(&inputs.pressure, &base.pressure)
+            || !same_lower(&inputs.temperature, &base.temperature)
+            || !same_lower(&inputs.vapor_pressure, &base.vapor_pressure)
+            || !same_lower(&inputs.rho_l, &base.rho_l)
+            || !same_lower(&inputs.height, &base.height)
+        {
+            return Err(RtmError::InconsistentInputs);
+        }
+        if inputs.observer_height.is_some() {
+            return Ok(inputs.run(&self.parameters));
+        }
+
+        let nodes = &self.nodes;
+        let mut outputs = RtmOutputs::new(inputs.quality_with(nodes, &FREQUENCY_LIMITS));
+        let emission = nodes.emission;
+        let num_lower = self.split_index - inputs.surface_index;
+        let mut absorption_profile: LevelVec<T> = LevelVec::new();
+        let mut profile_freq = None;
+
+        for ((&freq, &inc), lower) in nodes.frequency.iter().zip(&nodes.incidence).zip(&self.sums) {
+            if profile_freq != Some(freq) {
+                absorption_profile =
+                    inputs.absorption_from(self.split_index, freq, &nodes.absorption_scale);
+                profile_freq = Some(freq);
+            }
+            let (tran, tb_up, tb_down) = seeded_atm_tran(
+                nodes.integration_scheme,
+                inc,
+                lower,
+                &inputs.source_profile(emission, freq)[num_lower..],
+                &inputs.height[self.split_index..],
+                &absorption_profile,
+            );
+
+            outputs.tran.push(tran);
+            outputs.tb_up.push(nodes.output_tb(freq, tb_up));
+            outputs.tb_down.push(nodes.output_tb(freq, tb_down));
+        }
+
+        outputs.check_finite();
+        if self.parameters.passbands().is_some() {
+            outputs = self.parameters.integrate_outputs(&outputs);
+        }
+        Ok(outputs)
+    }
+}
@@ -0,0 +1,290 @@
+//! Channels with a finite passband, from a measured spectral response function.
+
+use std::ops::Range;
+
+use super::{Float, RtmError, RtmInputs, RtmOutputs, RtmParameters};
+
+/// The passband of a channel, as frequency nodes in GHz and their quadrature
+/// weights, which sum to 1.
+///
+/// The outputs of the RTM for a channel with a passband are the weighted sums
+/// of the monochromatic outputs at each node. See
+/// [`RtmParameters::from_channels`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Channel<T: Float = f32> {
+    /// Frequencies of the quadrature nodes in GHz.
+    nodes: Vec<T>,
+    /// Weights of the quadrature nodes, which sum to 1.
+    weights: Vec<T>,
+}
+
+impl<T: Float> Channel<T> {
+    /// A channel at a single frequency in GHz.
+    pub fn monochromatic(frequency: T) -> Self {
+        Self {
+            nodes: vec![frequency],
+            weights: vec![T::one()],
+        }
+    }
+
+    /// A channel with the given quadrature nodes (frequencies in GHz) and
+    /// weights. The weights are normalized to sum to 1.
+    ///
+    /// Returns [`RtmError::InvalidSpectralResponse`] if the two slices are
+    /// empty or have different lengths, if a node isn't finite, or if a
+    /// weight is negative or not finite, or they're all zero.
+    pub fn from_quadrature(nodes: &[T], weights: &[T]) -> Result<Self, RtmError> {
+        check_response(nodes, weights)?;
+        let total: f64 = weights.iter().map(|w| w.into_f64()).sum();
+        Ok(Self {
+            nodes: nodes.to_vec(),
+            weights: weights
+                .iter()
+                .map(|&w| T::from_f64_lossy(w.into_f64() / total))
+                .collect(),
+        })
+    }
+
+    /// A channel from a tabulated spectral response function, with the
+    /// relative `response` at each of `frequency` in GHz, resampled to at most
+    /// `num_nodes` quadrature nodes.
+    ///
+    /// The response is linear between the tabulated frequencies, and zero
+    /// outside of them. The range from the first to the last frequency is
+    /// split into `num_nodes` bins of equal width, and each bin becomes a node
+    /// at the response-weighted mean frequency of the bin, with a weight of
+    /// the integral of the response over it. Bins with no response are
+    /// dropped. For a boxcar response, the nodes are the midpoints of the bins
+    /// with equal weights. A single tabulated frequency is a delta function,
+    /// the same as [`monochromatic`](Self::monochromatic).
+    ///
+    /// Returns [`RtmError::InvalidSpectralResponse`] if `num_nodes` is zero, if
+    /// the frequencies aren't finite and strictly increasing, or if the
+    /// response isn't as in [`from_quadrature`](Self::from_quadrature).
+    pub fn from_srf(frequency: &[T], response: &[T], num_nodes: usize) -> Result<Self, RtmError> {
+        check_response(frequency, response)?;
+        if num_nodes == 0 {
+            return Err(RtmError::InvalidSpectralResponse(
+                "the number of nodes must be at least 1".to_string(),
+            ));
+        }
+        if !frequency.windows(2).all(|pair| pair[0] < pair[1]) {
+            return Err(RtmError::InvalidSpectralResponse(
+                "the frequencies must be strictly increasing".to_string(),
+            ));
+        }
+        if frequency.len() == 1 {
+            return Ok(Self::monochromatic(frequency[0]));
+        }
+
+        let f: Vec<f64> = frequency.iter().map(|f| f.into_f64()).collect();
+        let r: Vec<f64> = response.iter().map(|r| r.into_f64()).collect();
+        let (start, stop) = (f[0], f[f.len() - 1]);
+        let width = (stop - start) / num_nodes as f64;
+        let response_at = |freq: f64, segment: usize| {
+            let weight = (freq - f[segment]) / (f[segment + 1] - f[segment]);
+            r[segment] + weight * (r[segment + 1] - r[segment])
+        };
+
+        let mut nodes = Vec::with_capacity(num_nodes);
+        let mut weights = Vec::with_capacity(num_nodes);
+        for bin in 0..num_nodes {
+            let low = start + bin as f64 * width;
+            let high = if bin + 1 == num_nodes {
+                stop
+            } else {
+                low + width
+            };
+            // The integrals of r and f r over the bin, exact for a linear
+            // response within each overlapping segment
+            let (mut integral, mut moment) = (0., 0.);
+            for segment in 0..f.len() - 1 {
+                let (a, b) = (f[segment].max(low), f[segment + 1].min(high));
+                if a >= b {
+                    continue;
+                }
+                let (ra, rb) = (response_at(a, segment), response_at(b, segment));
+                integral += (b - a) * (ra + rb) / 2.;
+                moment += (b - a) * (a * (2. * ra + rb) + b * (ra + 2. * rb)) / 6.;
+            }
+            if integral > 0. {
+                nodes.push(T::from_f64_lossy(moment / integral));
+                weights.push(T::from_f64_lossy(integral));
+            }
+        }
+        Self::from_quadrature(&nodes, &weights)
+    }
+
+    /// Frequencies of the quadrature nodes in GHz.
+    pub fn nodes(&self) -> &[T] {
+        &self.nodes
+    }
+
+    /// Weights of the quadrature nodes, which sum to 1.
+    pub fn weights(&self) -> &[T] {
+        &self.weights
+    }
+
+    /// Shift the nodes by `delta` in GHz.
+    pub(crate) fn shift(&mut self, delta: T) {
+        for node in &mut self.nodes {
+            *node = *node + delta;
+        }
+    }
+
+    /// The centroid frequency of the passband in GHz, the weighted mean of the
+    /// nodes.
+    pub fn centroid(&self) -> T {
+        self.nodes
+            .iter()
+            .zip(&self.weights)
+            .map(|(&node, &weight)| node * weight)
+            .sum()
+    }
+}
+
+/// Check a tabulated response (or quadrature weights) and its frequencies.
+fn check_response<T: Float>(frequency: &[T], response: &[T]) -> Result<(), RtmError> {
+    let invalid = |message: &str| Err(RtmError::InvalidSpectralResponse(message.to_string()));
+    if frequency.is_empty() || frequency.len() != response.len() {
+        return invalid("the frequencies and response must be the same non-zero length");
+    }
+    if frequency.iter().any(|f| !f.is_finite()) {
+        return invalid("the frequencies must be finite");
+    }
+    // Written so that NaN is also rejected
+    if !response.iter().all(|&r| r >= T::zero() && r.is_finite()) {
+        return invalid("the response must be finite and non-negative");
+    }
+    if response.iter().all(|&r| r == T::zero()) {
+        return invalid("the response must not be all zero");
+    }
+    Ok(())
+}
+
+impl<T: Float> RtmParameters<T> {
+    /// Create new RTM parameters from the passband of each channel and the
+    /// Earth incidence angles in degrees.
+    ///
+    /// The methods of [`RtmInputs`] that run the RTM, such as
+    /// [`run`](RtmInputs::run) and [`run_with_levels`](RtmInputs::run_with_levels),
+    /// integrate their outputs for each channel over the nodes of its
+    /// passband, and [`frequencies`](Self::frequencies) returns the centroid
+    /// frequency of each passband. The same requirements on the lengths and
+    /// incidence angles apply as for [`new`](Self::new).
+    pub fn from_channels(channels: &[Channel<T>], eia: &[T]) -> Result<Self, RtmError> {
+        let freqs: Vec<T> = channels.iter().map(Channel::centroid).collect();
+        let mut parameters = Self::new(&freqs, eia)?;
+        parameters.passbands = Some(channels.to_vec());
+        Ok(parameters)
+    }
+
+    /// The passband of each channel, if the parameters were created with
+    /// [`from_channels`](Self::from_channels).
+    pub fn passbands(&self) -> Option<&[Channel<T>]> {
+        self.passbands.as_deref()
+    }
+
+    /// Monochromatic parameters with one channel for each node of each
+    /// passband, and otherwise the same settings, if there are passbands.
+    pub(super) fn passband_nodes(&self) -> Option<Self> {
+        let passbands = self.passbands.as_ref()?;
+        let (frequency, incidence) = passbands
+            .iter()
+            .zip(&self.incidence)
+            .flat_map(|(channel, &inc)| channel.nodes.iter().map(move |&node| (node, inc)))
+            .unzip();
+        Some(Self {
+            frequency,
+            incidence,
+            passbands: None,
+            ..self.clone()
+        })
+    }
+}
+
+impl<T: Float> RtmParameters<T> {
+    /// The index of the channel of each node, in the order of
+    /// [`passband_nodes`](Self::passband_nodes), or of each channel if there
+    /// are no passbands.
+    pub(super) fn node_channels(&self) -> Vec<usize> {
+        match &self.passbands {
+            Some(passbands) => passbands
+                .iter()
+                .enumerate()
+                .flat_map(|(index, channel)| std::iter::repeat(index).take(channel.nodes.len()))
+                .collect(),
+            None => (0..self.frequency.len()).collect(),
+        }
+    }
+
+    /// Call `integrate` with the weights and the range of the nodes of each
+    /// passband, in the order of [`passband_nodes`](Self::passband_nodes).
+    pub(super) fn integrate_nodes<'a, V: 'a>(
+        &'a self,
+        mut integrate: impl FnMut(&[T], Range<usize>) -> V + 'a,
+    ) -> impl Iterator<Item = V> + 'a {
+        let mut first = 0;
+        self.passbands.iter().flatten().map(move |channel| {
+            let nodes = first..first + channel.nodes.len();
+            first = nodes.end;
+            integrate(&channel.weights, nodes)
+        })
+    }
+
+    /// Integrate a value for each node of the passbands into a value for each
+    /// channel.
+    pub(super) fn integrate_channels<C: FromIterator<T>>(&self, values: &[T]) -> C {
+        self.integrate_nodes(|weights, nodes| weighted_sum(weights, &values[nodes]))
+            .collect()
+    }
+
+    /// Integrate a row of values for each node of the passbands into a row for
+    /// each channel.
+    pub(super) fn integrate_rows(&self, rows: &[Vec<T>]) -> Vec<Vec<T>> {
+        self.integrate_nodes(|weights, nodes| {
+            let mut sum = vec![T::zero(); rows.first().map_or(0, Vec::len)];
+            for (&weight, row) in weights.iter().zip(&rows[nodes]) {
+                for (sum, &value) in sum.iter_mut().zip(row) {
+                    *sum = *sum + weight * value;
+                }
+            }
+            sum
+        })
+        .collect()
+    }
+
+    /// Integrate the outputs for each node of the passbands into the outputs
+    /// for each channel.
+    pub(super) fn integrate_outputs(&self, node_outputs: &RtmOutputs<T>) -> RtmOutputs<T> {
+        RtmOutputs {
+            tran: self.integrate_channels(&node_outputs.tran),
+            tb_up: self.integrate_channels(&node_outputs.tb_up),
+            tb_down: self.integrate_channels(&node_outputs.tb_down),
+            quality: node_outputs.quality,
+        }
+    }
+}
+
+/// The sum of `values` weighted by `weights`.
+fn weighted_sum<T: Float>(weights: &[T], values: &[T]) -> T {
+    weights
+        .iter()
+        .zip(values)
+        .map(|(&weight, &value)| weight * value)
+        .sum()
+}
+
+impl<T: Float> RtmInputs<T> {
+    /// Integrate the outputs for the nodes of the passbands of `parameters`
+    /// from `run_nodes`, if there are passbands.
+    pub(crate) fn run_passbands(
+        &self,
+        parameters: &RtmParameters<T>,
+        run_nodes: impl FnOnce(&RtmParameters<T>) -> RtmOutputs<T>,
+    ) -> Option<RtmOutputs<T>> {
+        let node_outputs = run_nodes(&parameters.passband_nodes()?);
+        Some(parameters.integrate_outputs(&node_outputs))
+    }
+}
@@ -0,0 +1,57 @@
+//! Planck function and its inverse.
+//!
+//! Radiances are spectral radiances per unit wavenumber, in mW/m²/sr/cm⁻¹.
+
+use super::Float;
+
+/// First radiation constant, `2 h c²`, in mW/m²/sr/cm⁻⁴
+const C1: f32 = 1.191_042_9e-5;
+
+/// Second radiation constant, `h c / k`, in K cm
+const C2: f32 = 1.438_776_9;
+
+/// Speed of light in cm/ns, to convert from GHz to cm⁻¹
+const C: f32 = 29.979_246;
+
+/// Compute the Planck spectral radiance in mW/m²/sr/cm⁻¹ for a frequency
+/// `freq` in GHz and a temperature `t` in K.
+pub fn planck_radiance<T: Float>(freq: T, t: T) -> T {
+    let wavenumber = freq / T::lit(C);
+    T::lit(C1) * wavenumber.powi(3) / (T::lit(C2) * wavenumber / t).exp_m1()
+}
+
+/// Compute the Planck-equivalent brightness temperature in K for a frequency
+/// `freq` in GHz and a spectral radiance `radiance` in mW/m²/sr/cm⁻¹.
+///
+/// This is the inverse of [`planck_radiance`].
+pub fn planck_brightness_temperature<T: Float>(freq: T, radiance: T) -> T {
+    let wavenumber = freq / T::lit(C);
+    T::lit(C2) * wavenumber / (T::lit(C1) * wavenumber.powi(3) / radiance).ln_1p()
+}
+
+/// Compute the derivative of [`planck_radiance`] with respect to the
+/// temperature, in mW/m²/sr/cm⁻¹/K.
+pub(crate) fn planck_radiance_derivative<T: Float>(freq: T, t: T) -> T {
+    let wavenumber = freq / T::lit(C);
+    let x = T::lit(C2) * wavenumber / t;
+    planck_radiance(freq, t) * x / t * (T::one() + x.exp_m1().recip())
+}
+
+/// Compute the Rayleigh-Jeans spectral radiance in mW/m²/sr/cm⁻¹ for a
+/// frequency `freq` in GHz and a temperature `t` in K.
+///
+/// This is the low-frequency limit of [`planck_radiance`], which is linear in
+/// the temperature.
+pub fn rayleigh_jeans_radiance<T: Float>(freq: T, t: T) -> T {
+    let wavenumber = freq / T::lit(C);
+    T::lit(C1) * wavenumber.powi(2) * t / T::lit(C2)
+}
+
+/// Compute the Rayleigh-Jeans brightness temperature in K for a frequency
+/// `freq` in GHz and a spectral radiance `radiance` in mW/m²/sr/cm⁻¹.
+///
+/// This is the inverse of [`rayleigh_jeans_radiance`].
+pub fn rayleigh_jeans_brightness_temperature<T: Float>(freq: T, radiance: T) -> T {
+    let wavenumber = freq / T::lit(C);
+    T::lit(C2) * radiance / (T::lit(C1) * wavenumber.powi(2))
+}
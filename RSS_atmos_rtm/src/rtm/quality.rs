@@ -0,0 +1,169 @@
+//! Per-profile quality flags.
+//!
+//! The flags are a 32-bit bitfield, set while preparing the inputs
+//! ([`RtmInputs::new`](super::RtmInputs::new)) and while running the RTM
+//! ([`RtmInputs::run`](super::RtmInputs::run)). The bit assignments are:
+//!
+//! | Bit | Value | Name                        | Meaning                                                                                  |
+//! |-----|-------|-----------------------------|------------------------------------------------------------------------------------------|
+//! | 0   | 1     | `SURFACE_HEIGHT_ADJUSTED`   | The surface height was not below the first level above it, so it was moved below it      |
+//! | 1   | 2     | `INPUT_NOT_FINITE`          | An input value used by the RTM was NaN or infinite                                       |
+//! | 2   | 4     | `TEMPERATURE_OUT_OF_RANGE`  | A temperature used by the RTM was outside of 150 K to 350 K                              |
+//! | 3   | 8     | `EXTRAPOLATED_DIELECTRIC`   | Cloud water was present outside of the valid temperature range of the dielectric model   |
+//! | 4   | 16    | `OUTPUT_NOT_FINITE`         | An output value was NaN or infinite                                                      |
+//! | 5   | 32    | `SURFACE_VAPOR_CLAMPED`     | The surface vapor pressure was above saturation, so it was clamped (if enabled)          |
+//! | 6   | 64    | `EXTRAPOLATED_FREQUENCY`    | A frequency was above the validated range of an absorption model that was used           |
+//!
+//! The remaining bits are reserved and are zero. Only the levels above the
+//! surface (and below the top pressure, if any) are considered, since the rest
+//! are not used by the RTM.
+
+use std::ops::{BitOr, BitOrAssign};
+
+/// A bitfield of quality flags for a single profile.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QualityFlags(u32);
+
+impl QualityFlags {
+    /// The surface height was not below the first level above it, so it was
+    /// moved below it, by 0.1 m by default. See
+    /// [`SurfaceHeightPolicy`](super::SurfaceHeightPolicy).
+    pub const SURFACE_HEIGHT_ADJUSTED: Self = Self(1 << 0);
+    /// An input value used by the RTM was NaN or infinite.
+    pub const INPUT_NOT_FINITE: Self = Self(1 << 1);
+    /// A temperature used by the RTM was outside of the expected range.
+    pub const TEMPERATURE_OUT_OF_RANGE: Self = Self(1 << 2);
+    /// Cloud water was present at a temperature outside of the valid range of
+    /// the dielectric model, so it was extrapolated.
+    pub const EXTRAPOLATED_DIELECTRIC: Self = Self(1 << 3);
+    /// An output value was NaN or infinite.
+    pub const OUTPUT_NOT_FINITE: Self = Self(1 << 4);
+    /// The surface vapor pressure from the dewpoint was above saturation at
+    /// the surface temperature, so it was clamped to saturation. This is only
+    /// set by
+    /// [`RtmInputs::with_surface_saturation_clamp`](super::RtmInputs::with_surface_saturation_clamp).
+    pub const SURFACE_VAPOR_CLAMPED: Self = Self(1 << 5);
+    /// A channel frequency was above the validated range of a builtin
+    /// absorption model that was used, so the model was extrapolated. See
+    /// [`FREQUENCY_LIMITS`](super::FREQUENCY_LIMITS).
+    pub const EXTRAPOLATED_FREQUENCY: Self = Self(1 << 6);
+
+    /// Each of the flags with its name, in the order of their bits.
+    pub const NAMED: [(&'static str, Self); 7] = [
+        ("surface_height_adjusted", Self::SURFACE_HEIGHT_ADJUSTED),
+        ("input_not_finite", Self::INPUT_NOT_FINITE),
+        ("temperature_out_of_range", Self::TEMPERATURE_OUT_OF_RANGE),
+        ("extrapolated_dielectric", Self::EXTRAPOLATED_DIELECTRIC),
+        ("output_not_finite", Self::OUTPUT_NOT_FINITE),
+        ("surface_vapor_clamped", Self::SURFACE_VAPOR_CLAMPED),
+        ("extrapolated_frequency", Self::EXTRAPOLATED_FREQUENCY),
+    ];
+
+    /// Range of temperatures in K that are expected for the atmosphere.
+    pub(super) const TEMPERATURE_RANGE: std::ops::RangeInclusive<f64> = 150.0..=350.0;
+    /// Range of temperatures in K for which the pure water dielectric model is
+    /// valid.
+    pub(super) const DIELECTRIC_RANGE: std::ops::RangeInclusive<f64> = 248.16..=313.16;
+
+    /// No flags set.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// The raw bits.
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Whether no flags are set.
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Whether all of the flags in `other` are set.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Set the flags in `other` if `condition` is true.
+    pub(super) fn set_if(&mut self, other: Self, condition: bool) {
+        if condition {
+            *self |= other;
+        }
+    }
+}
+
+impl BitOr for QualityFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for QualityFlags {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// The number of profiles with each quality flag set, to summarize how often
+/// the adjustments and checks fired in a batch without inspecting the flags of
+/// every profile.
+///
+/// These are counted from the flags after the profiles are run, so they cost
+/// nothing while running.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QualityCounts {
+    /// Number of profiles counted.
+    num_profiles: usize,
+    /// Number of profiles with each flag of [`QualityFlags::NAMED`] set.
+    counts: [usize; QualityFlags::NAMED.len()],
+}
+
+impl QualityCounts {
+    /// Count the flags of one more profile.
+    pub fn add(&mut self, quality: QualityFlags) {
+        self.num_profiles += 1;
+        for (count, (_, flag)) in self.counts.iter_mut().zip(QualityFlags::NAMED) {
+            if quality.contains(flag) {
+                *count += 1;
+            }
+        }
+    }
+
+    /// Number of profiles counted.
+    pub fn num_profiles(&self) -> usize {
+        self.num_profiles
+    }
+
+    /// Number of profiles with `flag` set, which is one of the flags (and not
+    /// a combination of them).
+    pub fn count(&self, flag: QualityFlags) -> usize {
+        QualityFlags::NAMED
+            .iter()
+            .zip(self.counts)
+            .find_map(|(&(_, named), count)| (named == flag).then_some(count))
+            .unwrap_or(0)
+    }
+
+    /// The name of each flag and the number of profiles with it set, in the
+    /// order of [`QualityFlags::NAMED`].
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, usize)> + '_ {
+        QualityFlags::NAMED
+            .iter()
+            .zip(self.counts)
+            .map(|(&(name, _), count)| (name, count))
+    }
+}
+
+impl FromIterator<QualityFlags> for QualityCounts {
+    fn from_iter<I: IntoIterator<Item = QualityFlags>>(iter: I) -> Self {
+        let mut counts = Self::default();
+        for quality in iter {
+            counts.add(quality);
+        }
+        counts
+    }
+}
@@ -0,0 +1,307 @@
+//! Reading RTTOV test profiles, for comparisons against RTTOV.
+//!
+//! RTTOV's example programs read their profiles from plain-text files such as
+//! `prof.dat`. Lines starting with `!` are comments, and the rest are numbers
+//! separated by whitespace, read in order without regard to line breaks. The
+//! file starts with the gas units for all of the profiles:
+//!
+//! - 0: ppmv over dry air
+//! - 1: kg/kg over moist air
+//! - 2: ppmv over moist air
+//!
+//! followed by each profile in turn:
+//!
+//! - pressure in hPa on each level, from the top down
+//! - temperature in K on each level
+//! - water vapor on each level, in the gas units
+//! - 2 m temperature (K), 2 m water vapor (gas units), surface pressure (hPa),
+//!   10 m wind u and v (m/s), and wind fetch (m)
+//! - skin temperature (K), salinity, and 5 FASTEM land surface parameters
+//! - surface type and water type
+//! - elevation (km), latitude, and longitude (degrees)
+//! - satellite zenith and azimuth angles, and solar zenith and azimuth angles
+//!   (degrees)
+//! - cloud top pressure (hPa) and cloud fraction
+//!
+//! As in RTTOV, the number of levels isn't in the file and has to be given.
+//! Ozone and the other optional profiles of RTTOV's full profile files are not
+//! supported.
+//!
+//! The profiles don't have heights, so the geopotential height of each level
+//! is integrated hydrostatically from the surface elevation, using the virtual
+//! temperature. There is no cloud liquid water.
+
+use std::path::Path;
+
+use super::{
+    buck_dewpoint,
+    humidity::{EPS_SCALE, R_DRY},
+    specific_humidity_to_vapor_pressure, vapor_pressure_to_specific_humidity, RtmError, RtmInputs,
+};
+
+/// A profile read from an RTTOV profile file, in the same form as the
+/// arguments to [`RtmInputs::new`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RttovProfile {
+    /// Pressure levels in hPa, sorted from high to low pressure.
+    pub pressure: Vec<f32>,
+    /// Physical temperature in K on each level.
+    pub temperature: Vec<f32>,
+    /// Geopotential height in m on each level.
+    pub height: Vec<f32>,
+    /// Specific humidity in kg/kg on each level.
+    pub specific_humidity: Vec<f32>,
+    /// Liquid water content (from clouds) in kg/kg on each level, which is 0.
+    pub liquid_content: Vec<f32>,
+    /// 2 meter air temperature in K.
+    pub surface_temperature: f32,
+    /// Geopotential height at the surface in m.
+    pub surface_height: f32,
+    /// 2 meter dewpoint in K.
+    pub surface_dewpoint: f32,
+    /// Surface pressure in hPa.
+    pub surface_pressure: f32,
+    /// Skin temperature of the surface in K.
+    pub skin_temperature: f32,
+    /// Satellite zenith angle in degrees.
+    pub zenith_angle: f32,
+}
+
+impl RttovProfile {
+    /// Prepare the RTM inputs for this profile, including the skin
+    /// temperature.
+    pub fn inputs(&self) -> Result<RtmInputs, RtmError> {
+        Ok(RtmInputs::new(
+            &self.pressure,
+            self.surface_temperature,
+            &self.temperature,
+            self.surface_height,
+            &self.height,
+            self.surface_dewpoint,
+            &self.specific_humidity,
+            &self.liquid_content,
+            Some(self.surface_pressure),
+            None,
+        )?
+        .with_skin_temperature(self.skin_temperature))
+    }
+}
+
+/// Read the profiles with `num_levels` levels from an RTTOV profile file.
+pub fn read_profiles(
+    path: impl AsRef<Path>,
+    num_levels: usize,
+) -> Result<Vec<RttovProfile>, RtmError> {
+    parse_profiles(&std::fs::read_to_string(path)?, num_levels)
+}
+
+/// Parse the profiles with `num_levels` levels from the contents of an RTTOV
+/// profile file.
+pub fn parse_profiles(text: &str, num_levels: usize) -> Result<Vec<RttovProfile>, RtmError> {
+    let mut values = Values::new(text);
+    let units = values.next()?;
+    let gas_units = match (units.fract() == 0.).then_some(units as i32) {
+        Some(0) => GasUnits::PpmvDry,
+        Some(1) => GasUnits::KgPerKg,
+        Some(2) => GasUnits::PpmvMoist,
+        _ => return Err(values.error(format!("unknown gas units {units}"))),
+    };
+
+    let mut profiles = Vec::new();
+    while !values.is_empty() {
+        profiles.push(values.profile(num_levels, gas_units)?);
+    }
+    Ok(profiles)
+}
+
+/// The units of water vapor in the file.
+#[derive(Debug, Clone, Copy)]
+enum GasUnits {
+    PpmvDry,
+    KgPerKg,
+    PpmvMoist,
+}
+
+impl GasUnits {
+    /// Convert water vapor in these units to specific humidity in kg/kg, at a
+    /// pressure `p` in hPa.
+    fn specific_humidity(self, value: f32, p: f32) -> f32 {
+        match self {
+            GasUnits::PpmvDry => {
+                let vmr = value * 1e-6;
+                vapor_pressure_to_specific_humidity(p * vmr / (1. + vmr), p)
+            }
+            GasUnits::KgPerKg => value,
+            GasUnits::PpmvMoist => vapor_pressure_to_specific_humidity(p * value * 1e-6, p),
+        }
+    }
+}
+
+/// The numbers in a profile file, read in order.
+struct Values<'a> {
+    /// Each value with its line number.
+    tokens: Vec<(usize, &'a str)>,
+    /// Index of the next value in `tokens`.
+    position: usize,
+}
+
+impl<'a> Values<'a> {
+    fn new(text: &'a str) -> Self {
+        let tokens = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim_start().starts_with('!'))
+            .flat_map(|(index, line)| line.split_whitespace().map(move |token| (index + 1, token)))
+            .collect();
+        Self {
+            tokens,
+            position: 0,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.position == self.tokens.len()
+    }
+
+    /// An error at the last value read.
+    fn error(&self, message: String) -> RtmError {
+        let line = match self.position.checked_sub(1) {
+            Some(last) => self.tokens[last].0,
+            None => 0,
+        };
+        RtmError::InvalidProfileFile { line, message }
+    }
+
+    fn next(&mut self) -> Result<f32, RtmError> {
+        let &(_, token) = self
+            .tokens
+            .get(self.position)
+            .ok_or_else(|| self.error("unexpected end of file".to_string()))?;
+        self.position += 1;
+        // Fortran also writes exponents with a D
+        token
+            .replace(['D', 'd'], "E")
+            .parse()
+            .map_err(|_| self.error(format!("expected a number, not {token:?}")))
+    }
+
+    fn take(&mut self, count: usize) -> Result<Vec<f32>, RtmError> {
+        (0..count).map(|_| self.next()).collect()
+    }
+
+    fn profile(
+        &mut self,
+        num_levels: usize,
+        gas_units: GasUnits,
+    ) -> Result<RttovProfile, RtmError> {
+        // The profiles are from the top down, and the RTM wants them from the
+        // bottom up
+        let mut pressure = self.take(num_levels)?;
+        let mut temperature = self.take(num_levels)?;
+        let mut water_vapor = self.take(num_levels)?;
+        pressure.reverse();
+        temperature.reverse();
+        water_vapor.reverse();
+        let specific_humidity: Vec<f32> = water_vapor
+            .iter()
+            .zip(&pressure)
+            .map(|(&value, &p)| gas_units.specific_humidity(value, p))
+            .collect();
+
+        let near_surface = self.take(6)?;
+        let (surface_temperature, surface_water_vapor, surface_pressure) =
+            (near_surface[0], near_surface[1], near_surface[2]);
+        let skin_temperature = self.next()?;
+        let _salinity_and_fastem = self.take(6)?;
+        let _surface_and_water_type = self.take(2)?;
+        let elevation = self.next()?;
+        let _latitude_and_longitude = self.take(2)?;
+        let zenith_angle = self.next()?;
+        let _other_angles = self.take(3)?;
+        let _cloud = self.take(2)?;
+
+        let surface_specific_humidity =
+            gas_units.specific_humidity(surface_water_vapor, surface_pressure);
+        let surface_height = elevation * 1e3;
+        let height = hydrostatic_height(
+            &pressure,
+            &temperature,
+            &specific_humidity,
+            (
+                surface_pressure,
+                surface_temperature,
+                surface_specific_humidity,
+            ),
+            surface_height,
+        );
+        let surface_vapor_pressure =
+            specific_humidity_to_vapor_pressure(surface_specific_humidity, surface_pressure);
+
+        Ok(RttovProfile {
+            liquid_content: vec![0.; num_levels],
+            pressure,
+            temperature,
+            height,
+            specific_humidity,
+            surface_temperature,
+            surface_height,
+            surface_dewpoint: buck_dewpoint(surface_vapor_pressure),
+            surface_pressure,
+            skin_temperature,
+            zenith_angle,
+        })
+    }
+}
+
+/// Integrate the hydrostatic equation for the geopotential height in m of
+/// each level, up and down from the surface, given as (pressure, temperature,
+/// specific humidity), at `surface_height`. Each layer uses the mean virtual
+/// temperature of its two ends.
+fn hydrostatic_height(
+    pressure: &[f32],
+    temperature: &[f32],
+    specific_humidity: &[f32],
+    surface: (f32, f32, f32),
+    surface_height: f32,
+) -> Vec<f32> {
+    /// Standard acceleration of gravity in m/s²
+    const G: f64 = 9.80665;
+    // Specific gas constant for dry air, converted to J/kg/K
+    let r_dry = f64::from(R_DRY) * 1e3;
+    let virtual_temperature =
+        |t: f32, q: f32| f64::from(t) * (1. + f64::from(EPS_SCALE) * f64::from(q));
+    let thickness = |(p0, tv0): (f32, f64), (p1, tv1): (f32, f64)| {
+        r_dry / G * 0.5 * (tv0 + tv1) * (f64::from(p0) / f64::from(p1)).ln()
+    };
+
+    let levels: Vec<(f32, f64)> = pressure
+        .iter()
+        .zip(temperature)
+        .zip(specific_humidity)
+        .map(|((&p, &t), &q)| (p, virtual_temperature(t, q)))
+        .collect();
+    let (surface_pressure, surface_temperature, surface_specific_humidity) = surface;
+    let surface = (
+        surface_pressure,
+        virtual_temperature(surface_temperature, surface_specific_humidity),
+    );
+    let first_above = pressure
+        .iter()
+        .position(|&p| p <= surface_pressure)
+        .unwrap_or(pressure.len());
+
+    let mut height = vec![0.; pressure.len()];
+    let (mut below, mut z) = (surface, f64::from(surface_height));
+    for i in first_above..pressure.len() {
+        z += thickness(below, levels[i]);
+        height[i] = z as f32;
+        below = levels[i];
+    }
+    let (mut above, mut z) = (surface, f64::from(surface_height));
+    for i in (0..first_above).rev() {
+        z -= thickness(levels[i], above);
+        height[i] = z as f32;
+        above = levels[i];
+    }
+    height
+}
@@ -0,0 +1,163 @@
+//! Scan positions and channels of cross-track sounders.
+//!
+//! MSU and AMSU-A view the Earth at a fixed set of scan positions across the
+//! track, and each position has a known Earth incidence angle. The tables here
+//! are for the positions in the order that they're scanned, from one edge of
+//! the swath to the other, and are indexed from 0. The channels are numbered
+//! from 1, as in the instrument documentation.
+//!
+//! The RTM is monochromatic, so each channel is represented by a single
+//! frequency. For the AMSU-A channels with two or four passbands, which are
+//! placed symmetrically about an oxygen line or a point between lines, it's the
+//! center of the passband above and closest to the nominal center frequency.
+//!
+//! # Example
+//!
+//! ```
+//! use rss_atmos_rtm::rtm::{scan_geometry::MSU, standard_atmospheres::TROPICAL, RtmParameters};
+//!
+//! // MSU channel 2 at the edge of the scan
+//! let parameters = RtmParameters::<f32>::for_scan_position(&MSU, &[2], 0)?;
+//! assert_eq!(parameters.incidence_angles(), [56.19]);
+//! let outputs = TROPICAL.profile().inputs()?.run(&parameters);
+//! assert!(outputs.tb_up[0] > 200.);
+//! # Ok::<(), rss_atmos_rtm::rtm::RtmError>(())
+//! ```
+
+use super::{Polarization, RtmError};
+
+/// The channels and scan positions of a cross-track sounder.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScanSensor {
+    /// Short name, as used by the Python interface.
+    pub name: &'static str,
+    /// Frequency in GHz of each channel, starting from channel 1.
+    pub frequencies: &'static [f32],
+    /// Polarization of each channel at nadir, starting from channel 1. Away
+    /// from nadir, the polarization rotates by the scan angle (see
+    /// [`Polarization::mix`]).
+    pub polarizations: &'static [Polarization],
+    /// Scan angle from nadir in degrees of each scan position, which is
+    /// negative for the first half of the scan.
+    pub scan_angles: &'static [f32],
+    /// Earth incidence angle in degrees of each scan position.
+    pub incidence_angles: &'static [f32],
+}
+
+/// The Microwave Sounding Unit, on TIROS-N and NOAA-6 to NOAA-14.
+///
+/// The incidence angles are the ones used for the RSS MSU products.
+pub const MSU: ScanSensor = ScanSensor {
+    name: "msu",
+    frequencies: &[50.30, 53.74, 54.96, 57.95],
+    polarizations: &[
+        Polarization::Vertical,
+        Polarization::Horizontal,
+        Polarization::Vertical,
+        Polarization::Horizontal,
+    ],
+    scan_angles: &[
+        -47.35, -37.88, -28.41, -18.94, -9.47, 0.00, 9.47, 18.94, 28.41, 37.88, 47.35,
+    ],
+    incidence_angles: &[
+        56.19, 43.91, 32.51, 21.51, 10.71, 0.00, 10.71, 21.51, 32.51, 43.91, 56.19,
+    ],
+};
+
+/// The Advanced Microwave Sounding Unit-A, on NOAA-15 to NOAA-19, Aqua, and
+/// MetOp.
+///
+/// The scan positions are 3⅓° apart. The incidence angles are from
+/// [`eia_from_scan`](super::eia_from_scan) for the nominal 833 km orbit.
+pub const AMSU_A: ScanSensor = ScanSensor {
+    name: "amsu_a",
+    frequencies: &[
+        23.8, 31.4, 50.3, 52.8, 53.711, 54.4, 54.94, 55.5, 57.290344, 57.507344, 57.564544,
+        57.590544, 57.602544, 57.608044, 89.0,
+    ],
+    polarizations: &[
+        Polarization::Vertical,
+        Polarization::Vertical,
+        Polarization::Vertical,
+        Polarization::Vertical,
+        Polarization::Horizontal,
+        Polarization::Horizontal,
+        Polarization::Vertical,
+        Polarization::Horizontal,
+        Polarization::Horizontal,
+        Polarization::Horizontal,
+        Polarization::Horizontal,
+        Polarization::Horizontal,
+        Polarization::Horizontal,
+        Polarization::Horizontal,
+        Polarization::Vertical,
+    ],
+    scan_angles: &[
+        -48.33, -45.00, -41.67, -38.33, -35.00, -31.67, -28.33, -25.00, -21.67, -18.33, -15.00,
+        -11.67, -8.33, -5.00, -1.67, 1.67, 5.00, 8.33, 11.67, 15.00, 18.33, 21.67, 25.00, 28.33,
+        31.67, 35.00, 38.33, 41.67, 45.00, 48.33,
+    ],
+    incidence_angles: &[
+        57.64, 53.09, 48.74, 44.53, 40.43, 36.41, 32.46, 28.55, 24.68, 20.83, 17.02, 13.22, 9.43,
+        5.66, 1.88, 1.88, 5.66, 9.43, 13.22, 17.02, 20.83, 24.68, 28.55, 32.46, 36.41, 40.43,
+        44.53, 48.74, 53.09, 57.64,
+    ],
+};
+
+/// All of the sensors.
+pub const ALL: [ScanSensor; 2] = [MSU, AMSU_A];
+
+/// Look up a sensor by its [`name`](ScanSensor::name).
+pub fn by_name(name: &str) -> Option<ScanSensor> {
+    ALL.into_iter().find(|sensor| sensor.name == name)
+}
+
+impl ScanSensor {
+    /// Number of channels.
+    pub fn num_channels(&self) -> usize {
+        self.frequencies.len()
+    }
+
+    /// Number of scan positions.
+    pub fn num_positions(&self) -> usize {
+        self.incidence_angles.len()
+    }
+
+    /// The Earth incidence angle in degrees of the scan position `position`,
+    /// from 0, or [`RtmError::InvalidScanGeometry`] if there's no such
+    /// position.
+    pub fn incidence_angle(&self, position: usize) -> Result<f32, RtmError> {
+        self.incidence_angles.get(position).copied().ok_or_else(|| {
+            RtmError::InvalidScanGeometry(format!(
+                "scan position {position} is not from 0 to {} for {}",
+                self.num_positions() - 1,
+                self.name
+            ))
+        })
+    }
+
+    /// The Earth incidence angle in degrees of each of the scan positions
+    /// `positions`, for instance of each point of a swath.
+    pub fn incidence_angles_at(&self, positions: &[usize]) -> Result<Vec<f32>, RtmError> {
+        positions
+            .iter()
+            .map(|&position| self.incidence_angle(position))
+            .collect()
+    }
+
+    /// The frequency in GHz of channel number `channel`, from 1, or
+    /// [`RtmError::InvalidScanGeometry`] if there's no such channel.
+    pub fn frequency(&self, channel: usize) -> Result<f32, RtmError> {
+        channel
+            .checked_sub(1)
+            .and_then(|index| self.frequencies.get(index))
+            .copied()
+            .ok_or_else(|| {
+                RtmError::InvalidScanGeometry(format!(
+                    "channel {channel} is not from 1 to {} for {}",
+                    self.num_channels(),
+                    self.name
+                ))
+            })
+    }
+}
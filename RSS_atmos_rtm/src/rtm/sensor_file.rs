@@ -0,0 +1,466 @@
+//! Reading sensor definition files, for instruments that aren't in
+//! [`scan_geometry`](super::scan_geometry).
+//!
+//! A sensor definition is a TOML file with the channels and scan positions of
+//! a sensor, so that channel bookkeeping can be kept under version control
+//! rather than in code. Frequencies are in GHz and angles in degrees. The
+//! top-level keys are:
+//!
+//! - `name` (string): a short name for the sensor
+//! - `incidence_angles` (array of floats): the Earth incidence angle of each
+//!   scan position, indexed from 0
+//! - `scan_angles` (array of floats, optional): the scan angle from nadir of
+//!   each scan position, negative for the first half of the scan, with the
+//!   same length as `incidence_angles`
+//! - `nodes_per_passband` (integer, optional): the number of quadrature nodes
+//!   across each passband with a bandwidth, by default 1
+//! - `channels` (array of tables): the channels, in order
+//!
+//! Each of the `[[channels]]` has the keys:
+//!
+//! - `number` (integer): the channel number, which must count up from 1, as in
+//!   the instrument documentation
+//! - `frequency` (float): the center frequency, e.g., of the local oscillator
+//! - `polarization` (string): `"v"` or `"h"`, the polarization at nadir
+//! - `sidebands` (array of floats, optional): the offsets of the passbands
+//!   from the center frequency. Each offset splits every passband into two, at
+//!   plus and minus the offset, so `[0.3222, 0.048]` is the four passbands of
+//!   `frequency ± 0.3222 ± 0.048`. By default, there's a single passband at the
+//!   center frequency.
+//! - `bandwidth` (float, optional): the width of each passband. By default,
+//!   each passband is a single frequency.
+//!
+//! Any other key is an error, to catch typos. Errors are
+//! [`RtmError::InvalidSensorFile`] with the path of the offending key, e.g.,
+//! `channels[2].bandwidth`.
+//!
+//! Each passband is split into `nodes_per_passband` bins of equal width, with
+//! a node of equal weight at the middle of each bin, and all of the passbands
+//! of a channel have the same weight. This is the same as
+//! [`Channel::from_srf`] for a boxcar response.
+//!
+//! Example files for MSU and AMSU-A are in the `data` directory of the crate.
+//!
+//! # Example
+//!
+//! ```
+//! use rss_atmos_rtm::rtm::{sensor_file, RtmParameters};
+//!
+//! let sensor = sensor_file::parse_sensor(
+//!     r#"
+//!     name = "example"
+//!     incidence_angles = [0.0, 30.0]
+//!
+//!     [[channels]]
+//!     number = 1
+//!     frequency = 57.290344
+//!     polarization = "h"
+//!     sidebands = [0.217]
+//!     bandwidth = 0.078
+//!     "#,
+//! )?;
+//! let parameters: RtmParameters = sensor.parameters(&[1], 1)?;
+//! assert_eq!(parameters.incidence_angles(), [30.]);
+//! let channel = &parameters.passbands().unwrap()[0];
+//! assert_eq!(channel.nodes().len(), 2);
+//! # Ok::<(), rss_atmos_rtm::rtm::RtmError>(())
+//! ```
+
+use std::path::Path;
+
+use toml::{Table, Value};
+
+use super::{Channel, Float, Polarization, RtmError, RtmParameters};
+
+/// The channels and scan positions of a sensor, from a sensor definition file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SensorDefinition {
+    /// Short name of the sensor.
+    pub name: String,
+    /// Earth incidence angle in degrees of each scan position.
+    pub incidence_angles: Vec<f64>,
+    /// Scan angle from nadir in degrees of each scan position, if given.
+    pub scan_angles: Option<Vec<f64>>,
+    /// Number of quadrature nodes across each passband with a bandwidth.
+    pub nodes_per_passband: usize,
+    /// The channels, starting from channel 1.
+    pub channels: Vec<ChannelDefinition>,
+}
+
+/// A channel of a [`SensorDefinition`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelDefinition {
+    /// Center frequency in GHz.
+    pub frequency: f64,
+    /// Polarization at nadir.
+    pub polarization: Polarization,
+    /// Offsets in GHz of the sidebands, each of which splits every passband
+    /// into two.
+    pub sidebands: Vec<f64>,
+    /// Width in GHz of each passband, or `None` for a single frequency.
+    pub bandwidth: Option<f64>,
+}
+
+/// Read a sensor definition file.
+pub fn read_sensor(path: impl AsRef<Path>) -> Result<SensorDefinition, RtmError> {
+    parse_sensor(&std::fs::read_to_string(path)?)
+}
+
+/// Parse the contents of a sensor definition file.
+pub fn parse_sensor(text: &str) -> Result<SensorDefinition, RtmError> {
+    let table: Table = text.parse().map_err(|e: toml::de::Error| {
+        let message = match e.span() {
+            Some(span) => {
+                let line = text[..span.start].matches('\n').count() + 1;
+                format!("{} at line {line}", e.message())
+            }
+            None => e.message().to_string(),
+        };
+        RtmError::InvalidSensorFile { key: None, message }
+    })?;
+    SensorDefinition::from_table(&table)
+}
+
+impl SensorDefinition {
+    /// Validate and convert the parsed file.
+    fn from_table(table: &Table) -> Result<Self, RtmError> {
+        check_keys(
+            table,
+            "",
+            &[
+                "name",
+                "incidence_angles",
+                "scan_angles",
+                "nodes_per_passband",
+                "channels",
+            ],
+        )?;
+        let name = match required(table, "", "name")? {
+            Value::String(name) => name.clone(),
+            _ => return Err(invalid("name", "must be a string")),
+        };
+        let incidence_angles =
+            float_array(required(table, "", "incidence_angles")?, "incidence_angles")?;
+        if incidence_angles.is_empty() {
+            return Err(invalid("incidence_angles", "must not be empty"));
+        }
+        if let Some(index) = incidence_angles
+            .iter()
+            .position(|eia| !(0. ..=90.).contains(eia))
+        {
+            return Err(invalid(
+                &format!("incidence_angles[{index}]"),
+                "must be within 0 to 90 degrees",
+            ));
+        }
+        let scan_angles = table
+            .get("scan_angles")
+            .map(|value| float_array(value, "scan_angles"))
+            .transpose()?;
+        if let Some(scan_angles) = &scan_angles {
+            if scan_angles.len() != incidence_angles.len() {
+                return Err(invalid(
+                    "scan_angles",
+                    "must have the same length as incidence_angles",
+                ));
+            }
+        }
+        let nodes_per_passband = match table.get("nodes_per_passband") {
+            None => 1,
+            Some(&Value::Integer(nodes)) if nodes >= 1 => nodes as usize,
+            Some(_) => {
+                return Err(invalid(
+                    "nodes_per_passband",
+                    "must be an integer of at least 1",
+                ))
+            }
+        };
+        let channels = match required(table, "", "channels")? {
+            Value::Array(channels) if !channels.is_empty() => channels
+                .iter()
+                .enumerate()
+                .map(|(index, channel)| ChannelDefinition::from_value(channel, index))
+                .collect::<Result<Vec<_>, _>>()?,
+            _ => return Err(invalid("channels", "must be a non-empty array of tables")),
+        };
+
+        Ok(Self {
+            name,
+            incidence_angles,
+            scan_angles,
+            nodes_per_passband,
+            channels,
+        })
+    }
+
+    /// Number of channels.
+    pub fn num_channels(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// Number of scan positions.
+    pub fn num_positions(&self) -> usize {
+        self.incidence_angles.len()
+    }
+
+    /// The passband of channel number `channel`, from 1, with the quadrature
+    /// nodes described in the [module documentation](self), or
+    /// [`RtmError::InvalidScanGeometry`] if there's no such channel.
+    pub fn channel<T: Float>(&self, channel: usize) -> Result<Channel<T>, RtmError> {
+        let definition = channel
+            .checked_sub(1)
+            .and_then(|index| self.channels.get(index))
+            .ok_or_else(|| {
+                RtmError::InvalidScanGeometry(format!(
+                    "channel {channel} is not from 1 to {} for {}",
+                    self.num_channels(),
+                    self.name
+                ))
+            })?;
+        let nodes: Vec<T> = definition
+            .passband_centers()
+            .into_iter()
+            .flat_map(|center| match definition.bandwidth {
+                Some(bandwidth) => {
+                    let n = self.nodes_per_passband;
+                    (0..n)
+                        .map(|i| center + bandwidth * ((i as f64 + 0.5) / n as f64 - 0.5))
+                        .collect()
+                }
+                None => vec![center],
+            })
+            .map(T::from_f64_lossy)
+            .collect();
+        Channel::from_quadrature(&nodes, &vec![T::one(); nodes.len()])
+    }
+
+    /// The Earth incidence angle in degrees of the scan position `position`,
+    /// from 0, or [`RtmError::InvalidScanGeometry`] if there's no such
+    /// position.
+    pub fn incidence_angle(&self, position: usize) -> Result<f64, RtmError> {
+        self.incidence_angles.get(position).copied().ok_or_else(|| {
+            RtmError::InvalidScanGeometry(format!(
+                "scan position {position} is not from 0 to {} for {}",
+                self.num_positions() - 1,
+                self.name
+            ))
+        })
+    }
+
+    /// Parameters for the passbands of the channels `channels`, numbered from
+    /// 1, at the scan position `position`, numbered from 0. Returns
+    /// [`RtmError::InvalidScanGeometry`] if a channel or the position doesn't
+    /// exist, or [`RtmError::InconsistentInputs`] if there are no channels.
+    pub fn parameters<T: Float>(
+        &self,
+        channels: &[usize],
+        position: usize,
+    ) -> Result<RtmParameters<T>, RtmError> {
+        let channels = channels
+            .iter()
+            .map(|&channel| self.channel(channel))
+            .collect::<Result<Vec<_>, _>>()?;
+        let eia = T::from_f64_lossy(self.incidence_angle(position)?);
+        RtmParameters::from_channels(&channels, &vec![eia; channels.len()])
+    }
+
+    /// Write the definition in the format of a sensor definition file, which
+    /// [`parse_sensor`] reads back to the same definition.
+    pub fn to_toml(&self) -> String {
+        let floats = |values: &[f64]| -> Value {
+            Value::Array(values.iter().map(|&value| Value::Float(value)).collect())
+        };
+        let mut table = Table::new();
+        table.insert("name".to_string(), Value::String(self.name.clone()));
+        table.insert(
+            "incidence_angles".to_string(),
+            floats(&self.incidence_angles),
+        );
+        if let Some(scan_angles) = &self.scan_angles {
+            table.insert("scan_angles".to_string(), floats(scan_angles));
+        }
+        table.insert(
+            "nodes_per_passband".to_string(),
+            Value::Integer(self.nodes_per_passband as i64),
+        );
+        let channels = self
+            .channels
+            .iter()
+            .enumerate()
+            .map(|(index, channel)| {
+                let mut table = Table::new();
+                table.insert("number".to_string(), Value::Integer(index as i64 + 1));
+                table.insert("frequency".to_string(), Value::Float(channel.frequency));
+                table.insert(
+                    "polarization".to_string(),
+                    Value::String(channel.polarization.name().to_string()),
+                );
+                if !channel.sidebands.is_empty() {
+                    table.insert("sidebands".to_string(), floats(&channel.sidebands));
+                }
+                if let Some(bandwidth) = channel.bandwidth {
+                    table.insert("bandwidth".to_string(), Value::Float(bandwidth));
+                }
+                Value::Table(table)
+            })
+            .collect();
+        table.insert("channels".to_string(), Value::Array(channels));
+        table.to_string()
+    }
+}
+
+impl<T: Float> RtmParameters<T> {
+    /// Parameters for the channels `channels` of the sensor defined by the
+    /// contents of a sensor definition file, at the scan position `position`.
+    /// See [`SensorDefinition::parameters`].
+    pub fn from_toml(text: &str, channels: &[usize], position: usize) -> Result<Self, RtmError> {
+        parse_sensor(text)?.parameters(channels, position)
+    }
+}
+
+impl ChannelDefinition {
+    /// Validate and convert the channel at `index` of the `channels` array.
+    fn from_value(value: &Value, index: usize) -> Result<Self, RtmError> {
+        let path = format!("channels[{index}]");
+        let key = |name: &str| format!("{path}.{name}");
+        let Value::Table(table) = value else {
+            return Err(invalid(&path, "must be a table"));
+        };
+        check_keys(
+            table,
+            &path,
+            &[
+                "number",
+                "frequency",
+                "polarization",
+                "sidebands",
+                "bandwidth",
+            ],
+        )?;
+        match required(table, &path, "number")? {
+            &Value::Integer(number) if number == index as i64 + 1 => {}
+            _ => {
+                return Err(invalid(
+                    &key("number"),
+                    &format!("must be {}, counting up from 1", index + 1),
+                ))
+            }
+        }
+        let frequency = float(required(table, &path, "frequency")?)
+            .filter(|&frequency| frequency > 0.)
+            .ok_or_else(|| invalid(&key("frequency"), "must be a positive number"))?;
+        let polarization = match required(table, &path, "polarization")? {
+            Value::String(name) if name == Polarization::Vertical.name() => Polarization::Vertical,
+            Value::String(name) if name == Polarization::Horizontal.name() => {
+                Polarization::Horizontal
+            }
+            _ => return Err(invalid(&key("polarization"), "must be \"v\" or \"h\"")),
+        };
+        let sidebands = match table.get("sidebands") {
+            Some(value) => float_array(value, &key("sidebands"))?,
+            None => Vec::new(),
+        };
+        if let Some(index) = sidebands.iter().position(|&offset| offset <= 0.) {
+            return Err(invalid(
+                &format!("{}[{index}]", key("sidebands")),
+                "must be positive",
+            ));
+        }
+        let bandwidth = table
+            .get("bandwidth")
+            .map(|value| {
+                float(value)
+                    .filter(|&bandwidth| bandwidth > 0.)
+                    .ok_or_else(|| invalid(&key("bandwidth"), "must be a positive number"))
+            })
+            .transpose()?;
+
+        let channel = Self {
+            frequency,
+            polarization,
+            sidebands,
+            bandwidth,
+        };
+        if channel
+            .passband_centers()
+            .iter()
+            .any(|&center| center <= 0.)
+        {
+            return Err(invalid(
+                &key("sidebands"),
+                "must not be wider than the center frequency",
+            ));
+        }
+        Ok(channel)
+    }
+
+    /// The center frequency of each passband in GHz, in increasing order.
+    pub fn passband_centers(&self) -> Vec<f64> {
+        let mut centers = vec![self.frequency];
+        for &offset in &self.sidebands {
+            centers = centers
+                .iter()
+                .flat_map(|&center| [center - offset, center + offset])
+                .collect();
+        }
+        centers.sort_by(f64::total_cmp);
+        centers
+    }
+}
+
+/// An [`RtmError::InvalidSensorFile`] for `key`.
+fn invalid(key: &str, message: &str) -> RtmError {
+    RtmError::InvalidSensorFile {
+        key: Some(key.to_string()),
+        message: message.to_string(),
+    }
+}
+
+/// Join a table's `path` (empty at the top level) and a `key` in it.
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+/// Reject any keys of `table`, at `path`, that aren't `allowed`.
+fn check_keys(table: &Table, path: &str, allowed: &[&str]) -> Result<(), RtmError> {
+    match table.keys().find(|key| !allowed.contains(&key.as_str())) {
+        Some(key) => Err(invalid(&join(path, key), "is not a known key")),
+        None => Ok(()),
+    }
+}
+
+/// The value of a required `key` of `table`, at `path`.
+fn required<'a>(table: &'a Table, path: &str, key: &str) -> Result<&'a Value, RtmError> {
+    table
+        .get(key)
+        .ok_or_else(|| invalid(&join(path, key), "is required"))
+}
+
+/// A finite number, which may be written as an integer.
+fn float(value: &Value) -> Option<f64> {
+    match *value {
+        Value::Float(value) => Some(value),
+        Value::Integer(value) => Some(value as f64),
+        _ => None,
+    }
+    .filter(|value| value.is_finite())
+}
+
+/// An array of finite numbers at `key`.
+fn float_array(value: &Value, key: &str) -> Result<Vec<f64>, RtmError> {
+    let Value::Array(values) = value else {
+        return Err(invalid(key, "must be an array of numbers"));
+    };
+    values
+        .iter()
+        .enumerate()
+        .map(|(index, value)| {
+            float(value)
+                .ok_or_else(|| invalid(&format!("{key}[{index}]"), "must be a finite number"))
+        })
+        .collect()
+}
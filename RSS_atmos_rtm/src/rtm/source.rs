@@ -0,0 +1,342 @@
+//! Sources of input profiles and sinks for the results.
+//!
+//! These decouple the batch driver ([`compute_rtm_source`](super::compute_rtm_source))
+//! from how the profiles are stored, so they don't need to be materialized as
+//! one contiguous array. [`ArrayProfiles`] is the implementation for `ndarray`
+//! arrays, as used by the Python interface.
+
+use ndarray::{s, ArrayView2, Axis};
+
+use super::{Float, RtmError, RtmInputs, RtmOutputs};
+
+/// Borrowed data for a single profile. These are the same as the arguments to
+/// [`RtmInputs::new`].
+#[derive(Debug, Clone, Copy)]
+pub struct ProfileRef<'a, T: Float = f32> {
+    /// Pressure levels in hPa, sorted from high to low pressure.
+    pub pressure: &'a [T],
+    /// Physical temperature in K on each level.
+    pub temperature: &'a [T],
+    /// Geopotential height in m on each level.
+    pub height: &'a [T],
+    /// Specific humidity in kg/kg on each level.
+    pub specific_humidity: &'a [T],
+    /// Liquid water content (from clouds) in kg/kg on each level, or empty for
+    /// clear sky.
+    pub liquid_content: &'a [T],
+    /// 2 meter air temperature in K.
+    pub surface_temperature: T,
+    /// Geopotential height at the surface in m.
+    pub surface_height: T,
+    /// 2 meter dewpoint in K.
+    pub surface_dewpoint: T,
+    /// Optional surface pressure in hPa, or else the first level is the
+    /// surface.
+    pub surface_pressure: Option<T>,
+    /// Optional radiometric skin temperature of the surface in K.
+    pub skin_temperature: Option<T>,
+    /// Optional geometric height of the observer in m.
+    pub observer_height: Option<T>,
+    /// Optional pressure in hPa above which the levels are discarded.
+    pub top_pressure: Option<T>,
+}
+
+impl<T: Float> ProfileRef<'_, T> {
+    /// Prepare the RTM inputs for this profile.
+    pub fn inputs(&self) -> Result<RtmInputs<T>, RtmError> {
+        let inputs = RtmInputs::new(
+            self.pressure,
+            self.surface_temperature,
+            self.temperature,
+            self.surface_height,
+            self.height,
+            self.surface_dewpoint,
+            self.specific_humidity,
+            self.liquid_content,
+            self.surface_pressure,
+            self.top_pressure,
+        )?;
+        let inputs = match self.skin_temperature {
+            Some(skin_temperature) => inputs.with_skin_temperature(skin_temperature),
+            None => inputs,
+        };
+        Ok(match self.observer_height {
+            Some(observer_height) => inputs.with_observer_height(observer_height),
+            None => inputs,
+        })
+    }
+}
+
+/// A source of input profiles that can be read from in parallel.
+pub trait ProfileSource<T: Float = f32>: Sync {
+    /// Number of profiles.
+    fn len(&self) -> usize;
+
+    /// Whether there are no profiles.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Borrow the profile at `index`, which is less than [`len`](Self::len).
+    fn profile(&self, index: usize) -> Result<ProfileRef<'_, T>, RtmError>;
+}
+
+/// A destination for the RTM results, which can be written to in parallel.
+///
+/// The results are written in no particular order. Any function or closure
+/// taking the same arguments as [`write`](Self::write) is also a sink.
+pub trait ResultSink<T: Float = f32>: Sync {
+    /// Accept the result for the profile at `index`.
+    fn write(&self, index: usize, result: Result<RtmOutputs<T>, RtmError>);
+}
+
+impl<T, F> ResultSink<T> for F
+where
+    T: Float,
+    F: Fn(usize, Result<RtmOutputs<T>, RtmError>) + Sync,
+{
+    fn write(&self, index: usize, result: Result<RtmOutputs<T>, RtmError>) {
+        self(index, result)
+    }
+}
+
+/// Profiles stored in `ndarray` arrays, with one row per profile.
+#[derive(Debug, Clone, Copy)]
+pub struct ArrayProfiles<'a, T: Float = f32> {
+    pressure: Pressure<'a, T>,
+    temperature: ArrayView2<'a, T>,
+    height: ArrayView2<'a, T>,
+    specific_humidity: ArrayView2<'a, T>,
+    liquid_content: ArrayView2<'a, T>,
+    surface_temperature: &'a [T],
+    surface_height: &'a [T],
+    surface_dewpoint: &'a [T],
+    surface_pressure: Option<&'a [T]>,
+    skin_temperature: Option<&'a [T]>,
+    observer_height: Option<&'a [T]>,
+    top_pressure: Option<T>,
+}
+
+/// The pressure levels of [`ArrayProfiles`].
+#[derive(Debug, Clone, Copy)]
+enum Pressure<'a, T> {
+    /// The same levels for all of the profiles.
+    Shared(&'a [T]),
+    /// Levels for each profile, with one row per profile.
+    PerPoint(ArrayView2<'a, T>),
+}
+
+impl<'a, T: Float> ArrayProfiles<'a, T> {
+    /// Check the shapes of the arrays and borrow them.
+    ///
+    /// The `pressure` levels are shared by all of the profiles and have a
+    /// length of `num_levels`. The 2d arrays must all be (`num_points`,
+    /// `num_levels`), except that `liquid_content` may be (`num_points`, 0) for
+    /// clear sky (see [`RtmInputs::new`](super::RtmInputs::new)), and the
+    /// surface slices (including the optional surface pressure, skin
+    /// temperature, and observer height) must all have a length of
+    /// `num_points`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        pressure: &'a [T],
+        temperature: ArrayView2<'a, T>,
+        height: ArrayView2<'a, T>,
+        specific_humidity: ArrayView2<'a, T>,
+        liquid_content: ArrayView2<'a, T>,
+        surface_temperature: &'a [T],
+        surface_height: &'a [T],
+        surface_dewpoint: &'a [T],
+        surface_pressure: Option<&'a [T]>,
+        skin_temperature: Option<&'a [T]>,
+        observer_height: Option<&'a [T]>,
+        top_pressure: Option<T>,
+    ) -> Result<Self, RtmError> {
+        Self::with_pressure(
+            Pressure::Shared(pressure),
+            temperature,
+            height,
+            specific_humidity,
+            liquid_content,
+            surface_temperature,
+            surface_height,
+            surface_dewpoint,
+            surface_pressure,
+            skin_temperature,
+            observer_height,
+            top_pressure,
+        )
+    }
+
+    /// Check the shapes of the arrays and borrow them, as in
+    /// [`new`](Self::new), but with different pressure levels for each
+    /// profile.
+    ///
+    /// The `pressure` is (`num_points`, `num_levels`), like the other 2d
+    /// arrays, for model levels such as hybrid sigma-pressure levels where the
+    /// pressure of each level varies from column to column. Each row must be
+    /// sorted from high to low pressure.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_per_point_pressure(
+        pressure: ArrayView2<'a, T>,
+        temperature: ArrayView2<'a, T>,
+        height: ArrayView2<'a, T>,
+        specific_humidity: ArrayView2<'a, T>,
+        liquid_content: ArrayView2<'a, T>,
+        surface_temperature: &'a [T],
+        surface_height: &'a [T],
+        surface_dewpoint: &'a [T],
+        surface_pressure: Option<&'a [T]>,
+        skin_temperature: Option<&'a [T]>,
+        observer_height: Option<&'a [T]>,
+        top_pressure: Option<T>,
+    ) -> Result<Self, RtmError> {
+        if pressure.nrows() != temperature.nrows() {
+            return Err(RtmError::InconsistentInputs);
+        }
+        Self::with_pressure(
+            Pressure::PerPoint(pressure),
+            temperature,
+            height,
+            specific_humidity,
+            liquid_content,
+            surface_temperature,
+            surface_height,
+            surface_dewpoint,
+            surface_pressure,
+            skin_temperature,
+            observer_height,
+            top_pressure,
+        )
+    }
+
+    /// Implementation of [`new`](Self::new) and
+    /// [`new_per_point_pressure`](Self::new_per_point_pressure).
+    #[allow(clippy::too_many_arguments)]
+    fn with_pressure(
+        pressure: Pressure<'a, T>,
+        temperature: ArrayView2<'a, T>,
+        height: ArrayView2<'a, T>,
+        specific_humidity: ArrayView2<'a, T>,
+        liquid_content: ArrayView2<'a, T>,
+        surface_temperature: &'a [T],
+        surface_height: &'a [T],
+        surface_dewpoint: &'a [T],
+        surface_pressure: Option<&'a [T]>,
+        skin_temperature: Option<&'a [T]>,
+        observer_height: Option<&'a [T]>,
+        top_pressure: Option<T>,
+    ) -> Result<Self, RtmError> {
+        let num_levels = match pressure {
+            Pressure::Shared(pressure) => pressure.len(),
+            Pressure::PerPoint(pressure) => pressure.ncols(),
+        };
+        let num_points = temperature.nrows();
+
+        let two_dims = [temperature, height, specific_humidity];
+        if two_dims
+            .iter()
+            .any(|array| array.dim() != (num_points, num_levels))
+            || liquid_content.nrows() != num_points
+            || ![0, num_levels].contains(&liquid_content.ncols())
+        {
+            return Err(RtmError::InconsistentInputs);
+        }
+        let one_dim_points = [surface_temperature, surface_height, surface_dewpoint];
+        if one_dim_points
+            .iter()
+            .chain(surface_pressure.as_ref())
+            .chain(skin_temperature.as_ref())
+            .chain(observer_height.as_ref())
+            .any(|values| values.len() != num_points)
+        {
+            return Err(RtmError::InconsistentInputs);
+        }
+
+        Ok(Self {
+            pressure,
+            temperature,
+            height,
+            specific_humidity,
+            liquid_content,
+            surface_temperature,
+            surface_height,
+            surface_dewpoint,
+            surface_pressure,
+            skin_temperature,
+            observer_height,
+            top_pressure,
+        })
+    }
+
+    /// Number of levels in each profile.
+    pub fn num_levels(&self) -> usize {
+        match self.pressure {
+            Pressure::Shared(pressure) => pressure.len(),
+            Pressure::PerPoint(pressure) => pressure.ncols(),
+        }
+    }
+
+    /// Borrow the profiles with indexes in `range`, e.g., to run the profiles
+    /// a chunk at a time. Index `i` of the result is index `range.start + i`
+    /// of these profiles.
+    ///
+    /// Returns [`RtmError::InconsistentInputs`] if the range isn't within the
+    /// profiles.
+    pub fn points(&self, range: std::ops::Range<usize>) -> Result<Self, RtmError> {
+        if range.start > range.end || range.end > self.len() {
+            return Err(RtmError::InconsistentInputs);
+        }
+        let rows = |array: ArrayView2<'a, T>| array.slice_move(s![range.clone(), ..]);
+        let values = |values: &'a [T]| &values[range.clone()];
+        Ok(Self {
+            pressure: match self.pressure {
+                Pressure::Shared(pressure) => Pressure::Shared(pressure),
+                Pressure::PerPoint(pressure) => Pressure::PerPoint(rows(pressure)),
+            },
+            temperature: rows(self.temperature),
+            height: rows(self.height),
+            specific_humidity: rows(self.specific_humidity),
+            liquid_content: rows(self.liquid_content),
+            surface_temperature: values(self.surface_temperature),
+            surface_height: values(self.surface_height),
+            surface_dewpoint: values(self.surface_dewpoint),
+            surface_pressure: self.surface_pressure.map(values),
+            skin_temperature: self.skin_temperature.map(values),
+            observer_height: self.observer_height.map(values),
+            top_pressure: self.top_pressure,
+        })
+    }
+}
+
+impl<'a, T: Float> ProfileSource<T> for ArrayProfiles<'a, T> {
+    fn len(&self) -> usize {
+        self.surface_temperature.len()
+    }
+
+    fn profile(&self, index: usize) -> Result<ProfileRef<'_, T>, RtmError> {
+        let level_data = |array: ArrayView2<'a, T>| -> Result<&'a [T], RtmError> {
+            array
+                .index_axis_move(Axis(0), index)
+                .to_slice()
+                .ok_or(RtmError::NotContiguous)
+        };
+
+        Ok(ProfileRef {
+            pressure: match self.pressure {
+                Pressure::Shared(pressure) => pressure,
+                Pressure::PerPoint(pressure) => level_data(pressure)?,
+            },
+            temperature: level_data(self.temperature)?,
+            height: level_data(self.height)?,
+            specific_humidity: level_data(self.specific_humidity)?,
+            liquid_content: level_data(self.liquid_content)?,
+            surface_temperature: self.surface_temperature[index],
+            surface_height: self.surface_height[index],
+            surface_dewpoint: self.surface_dewpoint[index],
+            surface_pressure: self.surface_pressure.map(|p| p[index]),
+            skin_temperature: self.skin_temperature.map(|t| t[index]),
+            observer_height: self.observer_height.map(|z| z[index]),
+            top_pressure: self.top_pressure,
+        })
+    }
+}
@@ -0,0 +1,376 @@
+//! Standard atmosphere profiles, for tests and examples.
+//!
+//! These are the US Standard Atmosphere 1976 and idealized versions of the five
+//! AFGL reference atmospheres (Anderson et al., 1986): tropical, mid-latitude
+//! summer and winter, and subarctic summer and winter. Each is defined by a few
+//! constants: the surface pressure, the temperature at a set of nodes in
+//! height (linear in between), and the surface water vapor. The profiles on the
+//! [`LEVEL_HEIGHTS`] are then computed from these:
+//!
+//! - The pressure is hydrostatic, integrated up from the surface.
+//! - The water vapor volume mixing ratio decreases exponentially from the
+//!   surface value with a scale height of 3 km, but the relative humidity is
+//!   at most 80% and the volume mixing ratio is at least a stratospheric value
+//!   of 4 ppmv.
+//! - There is no cloud liquid water.
+//!
+//! For the US Standard Atmosphere 1976, the temperature nodes are the exact
+//! definition (up to 80 km, in geopotential height). For the AFGL
+//! atmospheres, they approximate the tabulated temperatures to within a few K,
+//! and the water vapor is a smooth approximation of the tabulated profile with
+//! the same surface value. They are meant to be realistic, not to reproduce the
+//! AFGL tables exactly.
+//!
+//! There's also an [`isothermal`] atmosphere at any temperature, for which the
+//! RTM outputs have a closed form ([`isothermal_tb`]).
+//!
+//! # Example
+//!
+//! ```
+//! use rss_atmos_rtm::rtm::{standard_atmospheres::TROPICAL, RtmParameters};
+//!
+//! let inputs = TROPICAL.profile().inputs()?;
+//! let outputs = inputs.run(&RtmParameters::new(&[23.8], &[0.])?);
+//! assert!(outputs.tb_up[0] > 0.);
+//! # Ok::<(), rss_atmos_rtm::rtm::RtmError>(())
+//! ```
+
+use super::{buck_dewpoint, buck_vap, vapor_pressure_to_specific_humidity, RtmError, RtmInputs};
+
+/// Geopotential height in km of each profile level, above the surface at 0 km.
+pub const LEVEL_HEIGHTS: [f32; 50] = [
+    0.25, 0.5, 0.75, 1., 2., 3., 4., 5., 6., 7., 8., 9., 10., 11., 12., 13., 14., 15., 16., 17.,
+    18., 19., 20., 21., 22., 23., 24., 25., 27.5, 30., 32.5, 35., 37.5, 40., 42.5, 45., 47.5, 50.,
+    52.5, 55., 57.5, 60., 62.5, 65., 67.5, 70., 72.5, 75., 77.5, 80.,
+];
+
+/// Scale height in km of the water vapor volume mixing ratio.
+const VAPOR_SCALE_HEIGHT: f64 = 3.;
+
+/// Stratospheric water vapor volume mixing ratio in ppmv.
+const STRATOSPHERIC_VAPOR: f64 = 4.;
+
+/// Upper limit of the relative humidity (over liquid water).
+const MAX_RELATIVE_HUMIDITY: f32 = 0.8;
+
+/// The definition of a standard atmosphere.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StandardAtmosphere {
+    /// Short name, as used by the Python interface.
+    pub name: &'static str,
+    /// Surface pressure in hPa.
+    pub surface_pressure: f32,
+    /// Pairs of (geopotential height in km, temperature in K), sorted by
+    /// height from the surface (0 km) to at least the top level (80 km).
+    pub temperature_nodes: &'static [(f32, f32)],
+    /// Water vapor volume mixing ratio at the surface in ppmv.
+    pub surface_water_vapor: f32,
+}
+
+/// US Standard Atmosphere 1976.
+pub const US_STANDARD_1976: StandardAtmosphere = StandardAtmosphere {
+    name: "us_standard_1976",
+    surface_pressure: 1013.25,
+    temperature_nodes: &[
+        (0., 288.15),
+        (11., 216.65),
+        (20., 216.65),
+        (32., 228.65),
+        (47., 270.65),
+        (51., 270.65),
+        (71., 214.65),
+        (80., 196.65),
+    ],
+    surface_water_vapor: 7745.,
+};
+
+/// AFGL tropical atmosphere (15° N, annual average).
+pub const TROPICAL: StandardAtmosphere = StandardAtmosphere {
+    name: "tropical",
+    surface_pressure: 1013.,
+    temperature_nodes: &[
+        (0., 299.7),
+        (3., 283.7),
+        (16., 197.0),
+        (17., 194.8),
+        (22., 214.6),
+        (25., 221.4),
+        (47.5, 269.6),
+        (50., 270.2),
+        (80., 184.8),
+    ],
+    surface_water_vapor: 25930.,
+};
+
+/// AFGL mid-latitude summer atmosphere (45° N, July).
+pub const MID_LATITUDE_SUMMER: StandardAtmosphere = StandardAtmosphere {
+    name: "mid_latitude_summer",
+    surface_pressure: 1013.,
+    temperature_nodes: &[
+        (0., 294.2),
+        (4., 273.2),
+        (13., 215.8),
+        (17., 215.7),
+        (25., 225.1),
+        (47.5, 275.2),
+        (50., 275.7),
+        (80., 174.1),
+    ],
+    surface_water_vapor: 18760.,
+};
+
+/// AFGL mid-latitude winter atmosphere (45° N, January).
+pub const MID_LATITUDE_WINTER: StandardAtmosphere = StandardAtmosphere {
+    name: "mid_latitude_winter",
+    surface_pressure: 1018.,
+    temperature_nodes: &[
+        (0., 272.2),
+        (3., 261.7),
+        (10., 219.7),
+        (19., 215.2),
+        (27.5, 215.5),
+        (47.5, 259.1),
+        (50., 259.1),
+        (80., 213.1),
+    ],
+    surface_water_vapor: 4316.,
+};
+
+/// AFGL subarctic summer atmosphere (60° N, July).
+pub const SUBARCTIC_SUMMER: StandardAtmosphere = StandardAtmosphere {
+    name: "subarctic_summer",
+    surface_pressure: 1010.,
+    temperature_nodes: &[
+        (0., 287.2),
+        (5., 260.1),
+        (10., 225.2),
+        (23., 225.2),
+        (47.5, 277.2),
+        (50., 276.2),
+        (80., 197.0),
+    ],
+    surface_water_vapor: 11830.,
+};
+
+/// AFGL subarctic winter atmosphere (60° N, January).
+pub const SUBARCTIC_WINTER: StandardAtmosphere = StandardAtmosphere {
+    name: "subarctic_winter",
+    surface_pressure: 1013.,
+    temperature_nodes: &[
+        (0., 257.2),
+        (1., 259.1),
+        (3., 252.7),
+        (8., 220.6),
+        (9., 217.2),
+        (25., 217.2),
+        (47.5, 265.7),
+        (50., 265.7),
+        (80., 210.0),
+    ],
+    surface_water_vapor: 1405.,
+};
+
+/// All of the standard atmospheres.
+pub const ALL: [StandardAtmosphere; 6] = [
+    US_STANDARD_1976,
+    TROPICAL,
+    MID_LATITUDE_SUMMER,
+    MID_LATITUDE_WINTER,
+    SUBARCTIC_SUMMER,
+    SUBARCTIC_WINTER,
+];
+
+/// An isothermal atmosphere at `temperature` in K, on the [`LEVEL_HEIGHTS`].
+///
+/// The surface pressure and water vapor are those of the
+/// [`US_STANDARD_1976`] atmosphere, and the pressure is hydrostatic for the
+/// constant temperature. The relative humidity is at most 80%, as for the
+/// standard atmospheres, so there's less water vapor for a cold atmosphere.
+///
+/// The RTM has a closed form for an isothermal atmosphere, [`isothermal_tb`],
+/// whatever the absorption, which makes this an analytic check of the
+/// integration.
+pub fn isothermal(temperature: f32) -> StandardProfile {
+    US_STANDARD_1976.profile_with(|_| f64::from(temperature))
+}
+
+/// The brightness temperature in K of an isothermal atmosphere at
+/// `temperature` in K with a transmissivity of `tran`: `temperature * (1 -
+/// tran)`.
+///
+/// Every layer emits at the same temperature, so the emission of the layers,
+/// each attenuated by those in front of it, adds up to the temperature times
+/// the emissivity of the whole atmosphere, `1 - tran`, for any absorption
+/// profile. This is both `tb_up` and `tb_down` from the RTM for an
+/// [`isothermal`] atmosphere, in the default Rayleigh-Jeans convention. With
+/// [`Emission::Planck`](super::Emission::Planck), the same holds for the
+/// radiances instead.
+///
+/// ```
+/// use rss_atmos_rtm::rtm::{standard_atmospheres, RtmParameters};
+///
+/// let inputs = standard_atmospheres::isothermal(250.).inputs()?;
+/// let outputs = inputs.run(&RtmParameters::new(&[53.74], &[30.])?);
+/// let expected = standard_atmospheres::isothermal_tb(250., outputs.tran[0]);
+/// assert!((outputs.tb_up[0] - expected).abs() < 0.01);
+/// assert!((outputs.tb_down[0] - expected).abs() < 0.01);
+/// # Ok::<(), rss_atmos_rtm::rtm::RtmError>(())
+/// ```
+pub fn isothermal_tb(temperature: f32, tran: f32) -> f32 {
+    temperature * (1. - tran)
+}
+
+/// Look up a standard atmosphere by its [`name`](StandardAtmosphere::name).
+pub fn by_name(name: &str) -> Option<StandardAtmosphere> {
+    ALL.into_iter().find(|atmosphere| atmosphere.name == name)
+}
+
+/// A standard atmosphere on the [`LEVEL_HEIGHTS`], in the same form as the
+/// arguments to [`RtmInputs::new`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct StandardProfile {
+    /// Pressure levels in hPa, sorted from high to low pressure.
+    pub pressure: Vec<f32>,
+    /// Physical temperature in K on each level.
+    pub temperature: Vec<f32>,
+    /// Geopotential height in m on each level.
+    pub height: Vec<f32>,
+    /// Specific humidity in kg/kg on each level.
+    pub specific_humidity: Vec<f32>,
+    /// Liquid water content (from clouds) in kg/kg on each level, which is 0.
+    pub liquid_content: Vec<f32>,
+    /// Air temperature at the surface in K.
+    pub surface_temperature: f32,
+    /// Geopotential height at the surface in m, which is 0.
+    pub surface_height: f32,
+    /// Dewpoint at the surface in K.
+    pub surface_dewpoint: f32,
+    /// Surface pressure in hPa.
+    pub surface_pressure: f32,
+}
+
+impl StandardProfile {
+    /// Prepare the RTM inputs for this profile.
+    pub fn inputs(&self) -> Result<RtmInputs, RtmError> {
+        RtmInputs::new(
+            &self.pressure,
+            self.surface_temperature,
+            &self.temperature,
+            self.surface_height,
+            &self.height,
+            self.surface_dewpoint,
+            &self.specific_humidity,
+            &self.liquid_content,
+            Some(self.surface_pressure),
+            None,
+        )
+    }
+}
+
+impl StandardAtmosphere {
+    /// Temperature in K at a geopotential height in km, interpolated
+    /// linearly between the nodes.
+    pub fn temperature_at(&self, height: f32) -> f32 {
+        self.interpolate_temperature(f64::from(height)) as f32
+    }
+
+    /// Compute the profile on the [`LEVEL_HEIGHTS`].
+    pub fn profile(&self) -> StandardProfile {
+        self.profile_with(|height| self.interpolate_temperature(height))
+    }
+
+    /// Compute the profile on the [`LEVEL_HEIGHTS`], with the temperature in K
+    /// at a geopotential height in km from `temperature_at` instead of the
+    /// nodes.
+    fn profile_with(&self, temperature_at: impl Fn(f64) -> f64) -> StandardProfile {
+        /// Standard acceleration of gravity in m/s²
+        const G: f64 = 9.80665;
+        /// Specific gas constant for dry air in J/kg/K, as in the US Standard
+        /// Atmosphere 1976
+        const R_AIR: f64 = 287.053;
+
+        // Integrate the hydrostatic equation, d ln(p) / dz = -g / (R T), from
+        // the surface. The temperature is linear in height between each pair
+        // of the nodes and levels, so each step is exact.
+        let mut steps: Vec<f64> = self
+            .temperature_nodes
+            .iter()
+            .map(|&(z, _)| f64::from(z))
+            .chain(LEVEL_HEIGHTS.iter().map(|&z| f64::from(z)))
+            .filter(|&z| z <= f64::from(LEVEL_HEIGHTS[LEVEL_HEIGHTS.len() - 1]))
+            .collect();
+        steps.sort_by(f64::total_cmp);
+        steps.dedup();
+
+        let mut ln_p = f64::from(self.surface_pressure).ln();
+        let mut z_prev = 0.;
+        let mut pressure = Vec::with_capacity(LEVEL_HEIGHTS.len());
+        for z in steps {
+            let t_prev = temperature_at(z_prev);
+            let t = temperature_at(z);
+            let dz = (z - z_prev) * 1e3;
+            ln_p -= if (t - t_prev).abs() > 1e-6 {
+                G * dz / (R_AIR * (t - t_prev)) * (t / t_prev).ln()
+            } else {
+                G * dz / (R_AIR * t)
+            };
+            z_prev = z;
+            if LEVEL_HEIGHTS.iter().any(|&level| f64::from(level) == z) {
+                pressure.push(ln_p.exp() as f32);
+            }
+        }
+
+        // The water vapor pressure in hPa at a height in km, temperature in K,
+        // and pressure in hPa
+        let vapor_pressure = |z: f32, t: f32, p: f32| -> f32 {
+            let ppmv =
+                f64::from(self.surface_water_vapor) * (-f64::from(z) / VAPOR_SCALE_HEIGHT).exp();
+            let pv = f32::min(
+                (ppmv * 1e-6) as f32 * p,
+                MAX_RELATIVE_HUMIDITY * buck_vap(t),
+            );
+            f32::max(pv, (STRATOSPHERIC_VAPOR * 1e-6) as f32 * p)
+        };
+
+        let temperature: Vec<f32> = LEVEL_HEIGHTS
+            .iter()
+            .map(|&z| temperature_at(f64::from(z)) as f32)
+            .collect();
+        let specific_humidity = LEVEL_HEIGHTS
+            .iter()
+            .zip(&temperature)
+            .zip(&pressure)
+            .map(|((&z, &t), &p)| vapor_pressure_to_specific_humidity(vapor_pressure(z, t, p), p))
+            .collect();
+
+        let surface_temperature = temperature_at(0.) as f32;
+        let surface_vapor_pressure = vapor_pressure(0., surface_temperature, self.surface_pressure);
+
+        StandardProfile {
+            pressure,
+            temperature,
+            height: LEVEL_HEIGHTS.iter().map(|&z| z * 1e3).collect(),
+            specific_humidity,
+            liquid_content: vec![0.; LEVEL_HEIGHTS.len()],
+            surface_temperature,
+            surface_height: 0.,
+            surface_dewpoint: buck_dewpoint(surface_vapor_pressure),
+            surface_pressure: self.surface_pressure,
+        }
+    }
+
+    /// Interpolate the temperature in K linearly between the two nodes around
+    /// a height in km, or extrapolate from the first or last two nodes.
+    fn interpolate_temperature(&self, height: f64) -> f64 {
+        let nodes = self.temperature_nodes;
+        let upper = nodes
+            .iter()
+            .position(|&(z, _)| f64::from(z) > height)
+            .unwrap_or(nodes.len() - 1)
+            .clamp(1, nodes.len() - 1);
+        let (z0, t0) = nodes[upper - 1];
+        let (z1, t1) = nodes[upper];
+        let (z0, t0, z1, t1) = (f64::from(z0), f64::from(t0), f64::from(z1), f64::from(t1));
+        t0 + (height - z0) * (t1 - t0) / (z1 - z0)
+    }
+}
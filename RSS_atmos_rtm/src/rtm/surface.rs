@@ -0,0 +1,542 @@
+//! Emissivity of the surface below the atmosphere.
+//!
+//! The emissivity of the ocean is computed here. The sea water dielectric
+//! constant is from the Meissner and Wentz model that is also used for the
+//! liquid cloud absorption, and the reflectivity of a flat (specular) surface
+//! is from the Fresnel equations. A wind-roughened surface is modeled as tilted
+//! flat facets plus foam. The emissivities of land and sea ice are given, e.g.,
+//! from an atlas.
+
+use num_complex::Complex;
+
+use super::{liquid_cloud::meissner, Float, RtmError};
+
+/// Brightness temperature of the cosmic background in K, which is reflected
+/// by the surface.
+const T_COLD: f32 = 2.73;
+
+/// Number of slopes in each direction for the facet integral. The slopes span
+/// ±4 standard deviations, and the emissivity is converged to 2e-5 (a few mK)
+/// up to 60° incidence, where the facets near the horizon limit it.
+const NUM_SLOPES: usize = 33;
+
+/// Polarization of a channel at the surface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Polarization {
+    /// Vertical polarization, in the plane of incidence.
+    Vertical,
+    /// Horizontal polarization, parallel to the surface.
+    Horizontal,
+}
+
+impl Polarization {
+    /// A short name for the polarization, as used by the Python interface.
+    pub fn name(self) -> &'static str {
+        match self {
+            Polarization::Vertical => "v",
+            Polarization::Horizontal => "h",
+        }
+    }
+
+    /// Select this polarization from a tuple `(v, h)`, as returned by
+    /// [`ocean_emissivity`].
+    pub fn select<T>(self, (v, h): (T, T)) -> T {
+        match self {
+            Polarization::Vertical => v,
+            Polarization::Horizontal => h,
+        }
+    }
+
+    /// Mix a tuple `(v, h)` for a feedhorn with this polarization that is
+    /// rotated by `angle` in degrees relative to the plane of incidence.
+    ///
+    /// The mix is `cos²φ v + sin²φ h` for vertical polarization, and
+    /// `cos²φ h + sin²φ v` for horizontal polarization. An angle of 0 is the
+    /// same as [`select`](Self::select). For a cross-track scanner, such as MSU, whose
+    /// polarization at nadir is fixed relative to the scan direction, the
+    /// rotation is the scan angle from nadir.
+    pub fn mix<T: Float>(self, (v, h): (T, T), angle: T) -> T {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let (same, cross) = match self {
+            Polarization::Vertical => (v, h),
+            Polarization::Horizontal => (h, v),
+        };
+        cos * cos * same + sin * sin * cross
+    }
+}
+
+/// How the surface reflects the downwelling from the atmosphere, for
+/// [`run_toa`](super::RtmInputs::run_toa). This is set with
+/// [`RtmParameters::with_reflection`](super::RtmParameters::with_reflection).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Reflection {
+    /// Mirror-like reflection of the downwelling from the single direction of
+    /// the specular reflection of the observation. This is the usual model for
+    /// the ocean.
+    #[default]
+    Specular,
+    /// Diffuse reflection of the downwelling averaged over the hemisphere and
+    /// weighted by the cosine of the zenith angle, which is more realistic for
+    /// rough land. The average is by Gaussian quadrature in the cosine of the
+    /// zenith angle, with `quadrature_order` zenith angles (at least 1). Each
+    /// angle is another integration of the radiative transfer through the same
+    /// absorption profile.
+    Lambertian {
+        /// Number of zenith angles for the quadrature.
+        quadrature_order: usize,
+    },
+}
+
+impl Reflection {
+    /// A short name for the reflection model, as used by the Python interface.
+    pub fn name(self) -> &'static str {
+        match self {
+            Reflection::Specular => "specular",
+            Reflection::Lambertian { .. } => "lambertian",
+        }
+    }
+}
+
+/// How the emissivity of a wind-roughened ocean is modeled, for
+/// [`run_toa`](super::RtmInputs::run_toa). This is set with
+/// [`RtmParameters::with_ocean_model`](super::RtmParameters::with_ocean_model),
+/// and only matters for an ocean with a wind speed: without one, the ocean is
+/// always flat.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OceanModel {
+    /// Tilted flat facets plus foam, from [`rough_ocean_emissivity`].
+    #[default]
+    Rough,
+    /// A flat (specular) surface plus foam, from
+    /// [`foam_ocean_emissivity`]. This leaves out the roughness, which mostly
+    /// raises the H-pol emissivity at the larger incidence angles.
+    SpecularFoam,
+}
+
+impl OceanModel {
+    /// A short name for the ocean model, as used by the Python interface.
+    pub fn name(self) -> &'static str {
+        match self {
+            OceanModel::Rough => "rough",
+            OceanModel::SpecularFoam => "specular_foam",
+        }
+    }
+
+    /// The emissivity `(emis_v, emis_h)` of the ocean for this model, as in
+    /// [`rough_ocean_emissivity`] or [`foam_ocean_emissivity`], or of a flat
+    /// sea as in [`ocean_emissivity`] if there's no `wind_speed`.
+    pub fn emissivity<T: Float>(
+        self,
+        freq: T,
+        inc: T,
+        sst: T,
+        salinity: T,
+        wind_speed: Option<T>,
+    ) -> (T, T) {
+        match (self, wind_speed) {
+            (_, None) => ocean_emissivity(freq, inc, sst, salinity),
+            (OceanModel::Rough, Some(wind_speed)) => {
+                rough_ocean_emissivity(freq, inc, sst, salinity, wind_speed)
+            }
+            (OceanModel::SpecularFoam, Some(wind_speed)) => {
+                foam_ocean_emissivity(freq, inc, sst, salinity, wind_speed)
+            }
+        }
+    }
+}
+
+/// Nodes and weights `(mu, weight)` of the Gauss-Legendre quadrature of
+/// `order` on the interval from 0 to 1. The weights sum to 1.
+pub(super) fn gauss_legendre(order: usize) -> Vec<(f64, f64)> {
+    let n = order as f64;
+    (1..=order)
+        .map(|i| {
+            // Newton's method for the ith root of the Legendre polynomial
+            // P_n(x) on -1 to 1, starting from an asymptotic estimate
+            let mut x = (std::f64::consts::PI * (i as f64 - 0.25) / (n + 0.5)).cos();
+            let mut derivative = 1.;
+            for _ in 0..100 {
+                let (mut p_prev, mut p) = (1., x);
+                for k in 2..=order {
+                    let k = k as f64;
+                    (p_prev, p) = (p, ((2. * k - 1.) * x * p - (k - 1.) * p_prev) / k);
+                }
+                derivative = n * (x * p - p_prev) / (x * x - 1.);
+                let step = p / derivative;
+                x -= step;
+                if step.abs() < 1e-15 {
+                    break;
+                }
+            }
+            let weight = 2. / ((1. - x * x) * derivative * derivative);
+            (0.5 * (1. + x), 0.5 * weight)
+        })
+        .collect()
+}
+
+/// The surface below a point, for
+/// [`run_toa`](super::RtmInputs::run_toa).
+///
+/// The emissivities of land and sea ice are given for each channel, and must
+/// be from 0 to 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Surface<'a, T: Float = f32> {
+    /// The ocean, with the emissivity from [`ocean_emissivity`], or from the
+    /// [`OceanModel`] of the parameters if there's a wind speed. It may be partly
+    /// covered by sea ice, in which case the emissivity is blended linearly
+    /// between the open water and the ice.
+    Ocean {
+        /// Salinity in parts per thousand.
+        salinity: T,
+        /// Optional 10 m wind speed in m/s.
+        wind_speed: Option<T>,
+        /// Fraction of the surface covered by sea ice, from 0 to 1.
+        ice_concentration: T,
+        /// Emissivity of the sea ice for each channel, which is needed if
+        /// `ice_concentration` is more than 0.
+        ice_emissivity: Option<&'a [T]>,
+    },
+    /// Land, with the emissivity for each channel.
+    Land {
+        /// Emissivity for each channel.
+        emissivity: &'a [T],
+    },
+    /// Sea ice that fully covers the ocean, with the emissivity for each
+    /// channel.
+    SeaIce {
+        /// Emissivity for each channel.
+        emissivity: &'a [T],
+    },
+}
+
+impl<T: Float> Surface<'_, T> {
+    /// Ice-free ocean with a salinity of 35 ppt and no wind.
+    pub fn open_ocean() -> Self {
+        Surface::Ocean {
+            salinity: T::lit(35.),
+            wind_speed: None,
+            ice_concentration: T::zero(),
+            ice_emissivity: None,
+        }
+    }
+
+    /// Check that the surface inputs are valid for `num_channels` channels.
+    ///
+    /// The given emissivities must have a value for each channel, and they and
+    /// the ice concentration must be from 0 to 1. The salinity must not be
+    /// negative.
+    pub fn check(&self, num_channels: usize) -> Result<(), RtmError> {
+        let check_emissivity = |name: &str, emissivity: &[T]| {
+            if emissivity.len() != num_channels {
+                return Err(RtmError::InconsistentInputs);
+            }
+            match emissivity
+                .iter()
+                .position(|value| !(T::zero()..=T::one()).contains(value))
+            {
+                Some(channel) => Err(RtmError::InvalidSurface(format!(
+                    "{name} emissivity {} for channel {channel} is outside of the range 0 to 1",
+                    emissivity[channel]
+                ))),
+                None => Ok(()),
+            }
+        };
+
+        match *self {
+            Surface::Ocean {
+                salinity,
+                ice_concentration,
+                ice_emissivity,
+                ..
+            } => {
+                if !(T::zero()..).contains(&salinity) {
+                    return Err(RtmError::InvalidSurface(format!(
+                        "salinity {salinity} is negative"
+                    )));
+                }
+                if !(T::zero()..=T::one()).contains(&ice_concentration) {
+                    return Err(RtmError::InvalidSurface(format!(
+                        "sea ice concentration {ice_concentration} is outside of the range 0 to 1"
+                    )));
+                }
+                match ice_emissivity {
+                    Some(emissivity) => check_emissivity("sea ice", emissivity),
+                    None if ice_concentration > T::zero() => {
+                        Err(RtmError::MissingInput("ice_emissivity"))
+                    }
+                    None => Ok(()),
+                }
+            }
+            Surface::Land { emissivity } => check_emissivity("land", emissivity),
+            Surface::SeaIce { emissivity } => check_emissivity("sea ice", emissivity),
+        }
+    }
+
+    /// The emissivities `(emis_v, emis_h)` for channel index `channel`, at a
+    /// frequency `freq` in GHz and Earth incidence angle `inc` in degrees, for
+    /// a surface at a temperature `t_surface` in K, with the open water from
+    /// `ocean_model`. The given emissivities of land and sea ice are
+    /// unpolarized.
+    pub(crate) fn emissivity(
+        &self,
+        ocean_model: OceanModel,
+        channel: usize,
+        freq: T,
+        inc: T,
+        t_surface: T,
+    ) -> (T, T) {
+        match *self {
+            Surface::Ocean {
+                salinity,
+                wind_speed,
+                ice_concentration,
+                ice_emissivity,
+            } => {
+                let (water_v, water_h) =
+                    ocean_model.emissivity(freq, inc, t_surface, salinity, wind_speed);
+                match ice_emissivity {
+                    Some(ice) if ice_concentration > T::zero() => (
+                        water_v + ice_concentration * (ice[channel] - water_v),
+                        water_h + ice_concentration * (ice[channel] - water_h),
+                    ),
+                    _ => (water_v, water_h),
+                }
+            }
+            Surface::Land { emissivity } | Surface::SeaIce { emissivity } => {
+                (emissivity[channel], emissivity[channel])
+            }
+        }
+    }
+}
+
+/// Compute the emissivity of a flat sea surface.
+///
+/// For a frequency `freq` in GHz, an Earth incidence angle `inc` in degrees,
+/// an SST `sst` in K, and a salinity `salinity` in parts per thousand, compute
+/// the specular emissivity for vertical and horizontal polarization. Returns
+/// the tuple `(emis_v, emis_h)`.
+///
+/// The dielectric constant is fit for SSTs from -2 °C to 34 °C (or 271.16 K to
+/// 307.16 K) and salinities from 0 to 40 ppt, and is extrapolated outside that.
+/// The effects of wind, such as roughness and foam, aren't included; see
+/// [`rough_ocean_emissivity`] and [`foam_ocean_emissivity`].
+pub fn ocean_emissivity<T: Float>(freq: T, inc: T, sst: T, salinity: T) -> (T, T) {
+    let permittivity = meissner(freq, sst, salinity);
+    fresnel_emissivity(permittivity, inc.to_radians().cos())
+}
+
+/// Compute the emissivity of a wind-roughened sea surface.
+///
+/// This is [`ocean_emissivity`] with an isotropic correction for the 10 m wind
+/// speed `wind_speed` in m/s. Returns the tuple `(emis_v, emis_h)`.
+///
+/// The surface is modeled with two components:
+///
+/// - Large-scale roughness: the surface is a set of flat facets with Gaussian
+///   distributed slopes, each with the Fresnel emissivity at its local
+///   incidence angle, rotated into the V/H basis of the observer and weighted
+///   by its area projected toward the observer. The total slope variance is
+///   `5.12e-3 * wind_speed` from Cox and Munk (1954), without their intercept
+///   of 0.003 so that a calm sea is exactly the flat sea.
+/// - Foam: a fraction [`foam_fraction`] of the surface is covered by foam,
+///   as in [`foam_ocean_emissivity`].
+///
+/// This is a physical model rather than a fit to satellite observations like
+/// the Meissner and Wentz (2012) model, and it doesn't include the
+/// small-scale (capillary wave) scattering, shadowing, or the dependence on
+/// wind direction. It gets the magnitude and the increase with wind speed
+/// right, with the largest increase for H-pol, but it should be expected to
+/// be off by a few hundredths in emissivity (several K in TB) at high wind
+/// speeds. The wind speed is meant to be from 0 to 40 m/s, but both fits are
+/// extrapolated above about 20 m/s, where the foam coverage grows quickly and
+/// reaches 1 at about 39 m/s. Negative wind speeds give NaN.
+pub fn rough_ocean_emissivity<T: Float>(
+    freq: T,
+    inc: T,
+    sst: T,
+    salinity: T,
+    wind_speed: T,
+) -> (T, T) {
+    let c = T::lit;
+    let permittivity = meissner(freq, sst, salinity);
+    let (sin_inc, cos_inc) = inc.to_radians().sin_cos();
+
+    // Direction toward the observer, and the global H-pol direction
+    // perpendicular to it and the vertical (which is arbitrary at nadir)
+    let look = [sin_inc, T::zero(), cos_inc];
+    let global_h = unit(cross(look, [T::zero(), T::zero(), T::one()])).unwrap_or([
+        T::zero(),
+        T::one(),
+        T::zero(),
+    ]);
+
+    // The slope in each direction has half of the total variance
+    let slope_std = (c(5.12e-3) * wind_speed / c(2.)).sqrt();
+    let step = c(8.) / T::from(NUM_SLOPES - 1).unwrap();
+    let (mut sum_v, mut sum_h, mut sum_weight) = (T::zero(), T::zero(), T::zero());
+    for i in 0..NUM_SLOPES {
+        let x = c(-4.) + step * T::from(i).unwrap();
+        for j in 0..NUM_SLOPES {
+            let y = c(-4.) + step * T::from(j).unwrap();
+            let normal = unit([-slope_std * x, -slope_std * y, T::one()]).unwrap();
+            let cos_local = dot(look, normal);
+            if cos_local <= T::zero() {
+                // The facet faces away from the observer
+                continue;
+            }
+            // Gaussian slope probability times the projected facet area
+            let weight = (-(x * x + y * y) / c(2.)).exp() * cos_local / normal[2];
+            let (emis_v, emis_h) = fresnel_emissivity(permittivity, cos_local);
+            // The fraction of the local H-pol that is global H-pol
+            let h_fraction = unit(cross(look, normal)).map_or(T::one(), |local_h| {
+                let projection = dot(global_h, local_h);
+                projection * projection
+            });
+            sum_v = sum_v + weight * (emis_v * h_fraction + emis_h * (T::one() - h_fraction));
+            sum_h = sum_h + weight * (emis_h * h_fraction + emis_v * (T::one() - h_fraction));
+            sum_weight = sum_weight + weight;
+        }
+    }
+
+    with_foam((sum_v / sum_weight, sum_h / sum_weight), wind_speed)
+}
+
+/// Compute the emissivity of a flat sea surface partly covered by foam.
+///
+/// This is the specular [`ocean_emissivity`] of the open water, blended with
+/// the emissivity of foam by the fraction of the surface that it covers for
+/// the 10 m wind speed `wind_speed` in m/s, [`foam_fraction`]. Returns the
+/// tuple `(emis_v, emis_h)`.
+///
+/// Foam is a layer of air bubbles that matches the impedance of the water to
+/// the air, so its emissivity is near 1 at microwave frequencies for both
+/// polarizations, and it's taken to be a blackbody: for a foam fraction `f`,
+/// the emissivity is `e + f (1 - e)` for the specular emissivity `e`. This
+/// raises the emissivity the most for H-pol, whose specular emissivity is the
+/// lowest. Unlike [`rough_ocean_emissivity`], the open water is flat, so the
+/// wind only acts through the foam, which is small below about 7 m/s. A wind
+/// speed of 0 is the same as a flat sea.
+pub fn foam_ocean_emissivity<T: Float>(
+    freq: T,
+    inc: T,
+    sst: T,
+    salinity: T,
+    wind_speed: T,
+) -> (T, T) {
+    with_foam(ocean_emissivity(freq, inc, sst, salinity), wind_speed)
+}
+
+/// The fraction of the sea surface covered by foam (whitecaps) for the 10 m
+/// wind speed `wind_speed` in m/s.
+///
+/// This is `3.84e-6 * wind_speed^3.41`, from Monahan and O'Muircheartaigh
+/// (1980), capped at 1. The fit is from wind speeds up to about 20 m/s, and is
+/// extrapolated above that; it reaches 1 at about 39 m/s. Negative wind
+/// speeds give NaN.
+pub fn foam_fraction<T: Float>(wind_speed: T) -> T {
+    (T::lit(3.84e-6) * wind_speed.powf(T::lit(3.41))).min(T::one())
+}
+
+/// Blend the emissivities `(emis_v, emis_h)` of the open water with those of
+/// blackbody foam, by the [`foam_fraction`] for `wind_speed`.
+fn with_foam<T: Float>((emis_v, emis_h): (T, T), wind_speed: T) -> (T, T) {
+    let foam = foam_fraction(wind_speed);
+    let blend = |emis: T| emis + foam * (T::one() - emis);
+    (blend(emis_v), blend(emis_h))
+}
+
+/// Compute the brightness temperature at the top of the atmosphere.
+///
+/// For the RTM outputs `tran`, `tb_up`, and `tb_down` of a channel, a surface
+/// `emissivity` for the same channel, and the emitting temperature of the
+/// surface `surface_temperature` in K, compute the brightness temperature in K
+/// observed from space. The surface reflects the downwelling and the cosmic
+/// background (2.73 K) specularly. This assumes the default Rayleigh-Jeans
+/// convention, where the brightness temperatures add linearly.
+pub fn toa_brightness_temperature<T: Float>(
+    tran: T,
+    tb_up: T,
+    tb_down: T,
+    emissivity: T,
+    surface_temperature: T,
+) -> T {
+    toa_from_sky(
+        tran,
+        tb_up,
+        specular_sky(tran, tb_down),
+        emissivity,
+        surface_temperature,
+    )
+}
+
+/// The brightness temperature in K of the sky seen in specular reflection: the
+/// downwelling `tb_down` plus the cosmic background attenuated by `tran`.
+pub(super) fn specular_sky<T: Float>(tran: T, tb_down: T) -> T {
+    tb_down + tran * T::lit(T_COLD)
+}
+
+/// As [`toa_brightness_temperature`], but with the brightness temperature `sky`
+/// reflected by a perfectly reflecting surface.
+pub(super) fn toa_from_sky<T: Float>(
+    tran: T,
+    tb_up: T,
+    sky: T,
+    emissivity: T,
+    surface_temperature: T,
+) -> T {
+    tb_up + tran * (emissivity * surface_temperature + (T::one() - emissivity) * sky)
+}
+
+/// Remove the atmosphere from a brightness temperature observed at the top of
+/// the atmosphere, the inverse of [`toa_brightness_temperature`].
+///
+/// For an observed brightness temperature `tb_toa` in K, the RTM outputs
+/// `tran`, `tb_up`, and `tb_down` of the channel, and the surface `emissivity`
+/// for the channel, compute the brightness temperature in K emitted by the
+/// surface, `emissivity * surface_temperature`. The atmospheric upwelling and
+/// the surface's reflection of the downwelling and the cosmic background are
+/// removed, and the result is corrected for the attenuation of the atmosphere:
+///
+/// `(tb_toa - tb_up) / tran - (1 - emissivity) * (tb_down + tran * 2.73)`
+///
+/// This is undefined for an opaque atmosphere, where `tran` is 0, and amplifies
+/// the error in `tb_toa` by `1 / tran`. It assumes the Rayleigh-Jeans
+/// convention, as for [`toa_brightness_temperature`].
+pub fn remove_atmosphere<T: Float>(tb_toa: T, tran: T, tb_up: T, tb_down: T, emissivity: T) -> T {
+    (tb_toa - tb_up) / tran - (T::one() - emissivity) * specular_sky(tran, tb_down)
+}
+
+/// The Fresnel emissivity `(emis_v, emis_h)` of a flat surface with a complex
+/// `permittivity`, for a cosine `cos_inc` of the incidence angle.
+fn fresnel_emissivity<T: Float>(permittivity: Complex<T>, cos_inc: T) -> (T, T) {
+    let sin_inc_squared = T::one() - cos_inc * cos_inc;
+    let cos_inc = Complex::from(cos_inc);
+    // The cosine of the transmitted angle, times the refractive index
+    let transmitted = (permittivity - sin_inc_squared).sqrt();
+
+    let r_v = (permittivity * cos_inc - transmitted) / (permittivity * cos_inc + transmitted);
+    let r_h = (cos_inc - transmitted) / (cos_inc + transmitted);
+
+    (T::one() - r_v.norm_sqr(), T::one() - r_h.norm_sqr())
+}
+
+fn dot<T: Float>(a: [T; 3], b: [T; 3]) -> T {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross<T: Float>(a: [T; 3], b: [T; 3]) -> [T; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+/// Normalize a vector, or `None` if it's (nearly) zero.
+fn unit<T: Float>(a: [T; 3]) -> Option<[T; 3]> {
+    let norm = dot(a, a).sqrt();
+    (norm > T::lit(1e-6)).then(|| a.map(|value| value / norm))
+}
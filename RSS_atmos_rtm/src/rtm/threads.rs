@@ -0,0 +1,100 @@
+//! Choosing the number of worker threads for a batch, and how the points are
+//! split between them.
+
+use std::num::NonZeroUsize;
+
+/// The default target number of (point, channel) pairs for each worker thread,
+/// for [`adaptive_num_threads`]. A pair takes roughly 10 to 30 µs for a
+/// typical profile, so this is a few ms of work for each thread.
+pub const DEFAULT_ITEMS_PER_THREAD: usize = 512;
+
+/// The default most points that a worker thread runs in a row without another
+/// thread being able to take over the rest.
+///
+/// Rayon splits the points of a batch into about one range for each thread,
+/// which are only split further when another thread runs out of work, and a
+/// range that a thread has started can't be split. So if the expensive points
+/// (such as the cloudy part of a granule, or profiles with many more levels)
+/// are together, one thread can be left running them long after the others
+/// have finished. Splitting the points into chunks of at most this many evens
+/// out the load, at a small cost for each chunk.
+///
+/// A chunk of a typical profile is a few ms of work for each channel, which is
+/// much more than the cost of stealing it. This value is from that estimate,
+/// and hasn't been checked against the `work_chunks` benchmark on a machine
+/// with several CPUs, so it can be changed for each call with
+/// [`RtmParameters::with_work_chunk_size`](super::RtmParameters::with_work_chunk_size)
+/// (or [`RtmOptions::work_chunk_size`](super::RtmOptions::work_chunk_size)).
+pub const DEFAULT_WORK_CHUNK_SIZE: usize = 32;
+
+/// [`DEFAULT_ITEMS_PER_THREAD`], which isn't zero.
+pub(crate) fn default_items_per_thread() -> NonZeroUsize {
+    NonZeroUsize::new(DEFAULT_ITEMS_PER_THREAD).unwrap()
+}
+
+/// [`DEFAULT_WORK_CHUNK_SIZE`], which isn't zero.
+pub(crate) fn default_work_chunk_size() -> NonZeroUsize {
+    NonZeroUsize::new(DEFAULT_WORK_CHUNK_SIZE).unwrap()
+}
+
+/// The number of worker threads for a batch of `num_points` points with
+/// `num_freq` channels, when the number isn't given, for a target of
+/// `items_per_thread` (point, channel) pairs for each thread.
+///
+/// Starting a thread and splitting the work over it has a cost, which for a
+/// small batch, such as a granule of a few hundred points, can be more than
+/// the work itself. A process that runs many batches at once, e.g., under a
+/// scheduler like Dask, is also oversubscribed if each batch uses every CPU.
+/// So this is
+///
+/// ```text
+/// min(max_threads, ceil(num_points * num_freq / items_per_thread))
+/// ```
+///
+/// but at least 1, where `max_threads` is the number of threads that Rayon
+/// would use by default (the `RAYON_NUM_THREADS` environment variable if it's
+/// set, otherwise the available parallelism). A large batch uses
+/// `max_threads`, the same as without this. A larger `items_per_thread` uses
+/// fewer threads for small batches, and the default is
+/// [`DEFAULT_ITEMS_PER_THREAD`].
+///
+/// This is used for `None` threads by [`compute_rtm_batch`],
+/// [`compute_rtm_source`], [`estimate_cost`], and the Python, C, and
+/// command-line interfaces, with the target of the parameters (see
+/// [`RtmParameters::with_items_per_thread`]). An explicit number of threads is
+/// always used as it is.
+///
+/// [`compute_rtm_batch`]: super::compute_rtm_batch
+/// [`compute_rtm_source`]: super::compute_rtm_source
+/// [`estimate_cost`]: super::estimate_cost
+/// [`RtmParameters::with_items_per_thread`]: super::RtmParameters::with_items_per_thread
+pub fn adaptive_num_threads(
+    num_points: usize,
+    num_freq: usize,
+    items_per_thread: NonZeroUsize,
+) -> NonZeroUsize {
+    let num_items = num_points.saturating_mul(num_freq.max(1));
+    let wanted = num_items.div_ceil(items_per_thread.get());
+    NonZeroUsize::new(wanted.min(max_threads().get())).unwrap_or(NonZeroUsize::MIN)
+}
+
+/// The number of threads of a batch: `num_threads` if it's given, otherwise
+/// the [`adaptive_num_threads`].
+#[cfg(feature = "parallel")]
+pub(crate) fn num_threads_or_adaptive(
+    num_threads: Option<NonZeroUsize>,
+    num_points: usize,
+    num_freq: usize,
+    items_per_thread: NonZeroUsize,
+) -> NonZeroUsize {
+    num_threads.unwrap_or_else(|| adaptive_num_threads(num_points, num_freq, items_per_thread))
+}
+
+/// The number of threads that Rayon uses by default.
+fn max_threads() -> NonZeroUsize {
+    std::env::var("RAYON_NUM_THREADS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .or_else(|| std::thread::available_parallelism().ok())
+        .unwrap_or(NonZeroUsize::MIN)
+}
@@ -4,9 +4,13 @@
 
 use std::sync::OnceLock;
 
+use super::Float;
+
 const NLINES: usize = 15;
 
-struct WaterVaporCoefficients {
+/// Water vapor absorption coefficients
+#[derive(Debug, Clone)]
+pub(crate) struct WaterVaporCoefficients {
     f0: [f32; NLINES],
     b1: [f32; NLINES],
     b2: [f32; NLINES],
@@ -17,6 +21,35 @@ struct WaterVaporCoefficients {
 }
 
 impl WaterVaporCoefficients {
+    /// The coefficients as published, which are only initialized once.
+    pub(crate) fn builtin() -> &'static Self {
+        static COEF: OnceLock<WaterVaporCoefficients> = OnceLock::new();
+        COEF.get_or_init(Self::new)
+    }
+
+    /// Each coefficient for all of the lines, in the order `f0`, then `b1`
+    /// to `b6`, for the GPU.
+    #[cfg(feature = "gpu")]
+    pub(crate) fn columns(&self) -> [&[f32]; 7] {
+        [
+            &self.f0, &self.b1, &self.b2, &self.b3, &self.b4, &self.b5, &self.b6,
+        ]
+    }
+
+    /// The coefficients with the line intensities (`b1`) and widths (`b3`)
+    /// changed by the fractions `intensity` and `width`. Since the
+    /// self-broadened widths are relative to `b3`, they change by the same
+    /// fraction.
+    pub(crate) fn perturbed(intensity: f64, width: f64) -> Self {
+        let mut coef = Self::new();
+        let perturb = |value: &mut f32, fraction: f64| {
+            *value = (f64::from(*value) * (1. + fraction)) as f32;
+        };
+        coef.b1.iter_mut().for_each(|b1| perturb(b1, intensity));
+        coef.b3.iter_mut().for_each(|b3| perturb(b3, width));
+        coef
+    }
+
     /// Initialize the water vapor coefficients.
     #[allow(clippy::excessive_precision)]
     fn new() -> Self {
@@ -89,16 +122,32 @@ impl WaterVaporCoefficients {
 ///
 /// For a total pressure `p` in hPa, temperature `t` in K, water vapor pressure
 /// `pv` in hPa, and frequency `freq` in GHz, compute the water vapor absorption
-/// coefficient in dB/km.
+/// coefficient in dB/km, with the coefficients `coef` (normally
+/// [`WaterVaporCoefficients::builtin`]) tabulated at the reference temperature
+/// `t_ref` in K (normally
+/// [`REFERENCE_TEMPERATURE`](super::REFERENCE_TEMPERATURE)).
+///
+/// The line absorption is scaled by `line_scale` and the continuum (both the
+/// foreign and self-broadened terms) by `continuum_scale`, which are 1 for the
+/// model as published.
 ///
 /// From: P.W. Rosenkranz, Radio Science v.33, pp.919-928 (1998). Modified by
 /// Frank Wentz over the years and converted from Fortran to Rust by Richard
 /// Lindsley.
-pub(crate) fn abh2o_rk_modified(p: f32, t: f32, pv: f32, freq: f32) -> f32 {
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn abh2o_rk_modified<T: Float>(
+    coef: &WaterVaporCoefficients,
+    p: T,
+    t: T,
+    pv: T,
+    freq: T,
+    line_scale: f64,
+    continuum_scale: f64,
+    t_ref: f32,
+) -> T {
     // Many of the variables are retained from the original Fortran
+    let c = T::lit;
 
-    /// Ensure the coefficients are only initialized once.
-    static COEF: OnceLock<WaterVaporCoefficients> = OnceLock::new();
     let WaterVaporCoefficients {
         f0,
         b1,
@@ -107,72 +156,75 @@ pub(crate) fn abh2o_rk_modified(p: f32, t: f32, pv: f32, freq: f32) -> f32 {
         b4,
         b5,
         b6,
-    } = COEF.get_or_init(WaterVaporCoefficients::new);
+    } = coef;
 
-    if pv <= 0. {
-        return 0.;
+    if pv <= T::zero() {
+        return T::zero();
     }
 
-    let pwet = 0.1 * pv;
-    let pdry = 0.1 * p - pwet;
-    let tht = 300. / t;
-    let xterm = 1. - tht;
+    let pwet = c(0.1) * pv;
+    let pdry = c(0.1) * p - pwet;
+    let tht = c(t_ref) / t;
+    let xterm = c(1.) - tht;
     let freq_sq = freq.powi(2);
 
     let sum: f64 = (0..NLINES)
         .map(|i| {
-            let f0sq = f0[i].powi(2);
-            let ga = b3[i] * (pdry * tht.powf(b4[i]) + b5[i] * pwet * tht.powf(b6[i]));
+            let f0sq = c(f0[i].powi(2));
+            let ga = c(b3[i]) * (pdry * tht.powf(c(b4[i])) + c(b5[i]) * pwet * tht.powf(c(b6[i])));
             let ga_sq = ga.powi(2);
-            let s = b1[i] * f32::exp(b2[i] * xterm);
-            let rnuneg = f0[i] - freq;
-            let rnupos = f0[i] + freq;
+            let s = c(b1[i]) * (c(b2[i]) * xterm).exp();
+            let rnuneg = c(f0[i]) - freq;
+            let rnupos = c(f0[i]) + freq;
 
             // use clough's definition of local line contribution
-            let base = ga / (562_500. + ga_sq);
+            let base = ga / (c(562_500.) + ga_sq);
 
             if i != 0 {
                 let mut sum = 0.;
-                if rnuneg.abs() < 750. {
-                    sum += f64::from(s * (ga / (ga_sq + rnuneg.powi(2)) - base));
+                if rnuneg.abs() < c(750.) {
+                    sum += (s * (ga / (ga_sq + rnuneg.powi(2)) - base)).into_f64();
                 }
-                if rnupos.abs() <= 750. {
-                    sum += f64::from(s * (ga / (ga_sq + rnupos.powi(2)) - base));
+                if rnupos.abs() <= c(750.) {
+                    sum += (s * (ga / (ga_sq + rnupos.powi(2)) - base)).into_f64();
                 }
                 sum
             } else {
                 // modification 2
-                let chi = if freq < 19. {
-                    let u = f32::clamp((freq - 19.).abs() / 16.5, 0., 1.);
-                    0.07 * ga + 0.93 * ga * u.powi(2) * (3. - 2. * u)
+                let chi = if freq < c(19.) {
+                    let u = ((freq - c(19.)).abs() / c(16.5)).clamp(T::zero(), T::one());
+                    c(0.07) * ga + c(0.93) * ga * u.powi(2) * (c(3.) - c(2.) * u)
                 } else {
-                    0.07 * ga
+                    c(0.07) * ga
                 };
 
                 let chi_sq = chi.powi(2);
-                f64::from(
-                    s * 2. * ((ga - chi) * freq_sq + (ga + chi) * (f0sq + ga_sq - chi_sq))
-                        / ((freq_sq - f0sq - ga_sq + chi_sq).powi(2) + 4. * freq_sq * ga_sq),
-                )
+                (s * c(2.) * ((ga - chi) * freq_sq + (ga + chi) * (f0sq + ga_sq - chi_sq))
+                    / ((freq_sq - f0sq - ga_sq + chi_sq).powi(2) + c(4.) * freq_sq * ga_sq))
+                    .into_f64()
             }
         })
         .sum();
     let sum = sum.max(0.);
 
-    let ffac = if freq < 90. {
-        1. + 0.1 * ((90. - freq) / 90.).powf(1.4)
+    let ffac = if freq < c(90.) {
+        c(1.) + c(0.1) * ((c(90.) - freq) / c(90.)).powf(c(1.4))
     } else {
-        1.
+        c(1.)
     };
 
     // modification 3
     let sftot = pwet
         * freq
-        * tht.powf(3.5)
-        * (sum
-            + f64::from(ffac * 1.1 * 1.2957246e-6 * pdry / tht.sqrt())
-            + f64::from(0.348 * (freq.powf(0.15)) * 4.2952193e-5 * pwet * tht.powi(4)))
-            as f32;
-
-    0.1820 * freq * sftot
+        * tht.powf(c(3.5))
+        * T::from_f64_lossy(
+            line_scale * sum
+                + continuum_scale
+                    * (ffac * c(1.1) * c(1.2957246e-6) * pdry / tht.sqrt()).into_f64()
+                + continuum_scale
+                    * (c(0.348) * (freq.powf(c(0.15))) * c(4.2952193e-5) * pwet * tht.powi(4))
+                        .into_f64(),
+        );
+
+    c(0.1820) * freq * sftot
 }
@@ -0,0 +1,381 @@
+//! Writing outputs to a Zarr group on the local filesystem, a chunk of points
+//! at a time, so that the outputs of a large run never have to be in memory at
+//! once.
+//!
+//! The group is in Zarr format 2, which zarr-python, xarray, and dask all
+//! read. Each array is chunked only along its first axis, the points, and the
+//! chunks are uncompressed little-endian C-order data, one file per chunk.
+//! Each array also records the names of its dimensions as
+//! `_ARRAY_DIMENSIONS`, as xarray expects, and the metadata of the group and
+//! its arrays is consolidated in `.zmetadata` for object stores.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::num::NonZeroUsize;
+//! use ndarray::Array2;
+//! use rss_atmos_rtm::rtm::zarr::{ZarrAttribute, ZarrGroup};
+//!
+//! let mut group = ZarrGroup::create(
+//!     "outputs.zarr".as_ref(),
+//!     vec![("title".to_string(), ZarrAttribute::Text("RTM outputs".into()))],
+//! )?;
+//! let tb_up = group.create_array::<f32>(
+//!     "tb_up",
+//!     &[1000, 3],
+//!     NonZeroUsize::new(100).unwrap(),
+//!     &["point", "channel"],
+//!     vec![("units".to_string(), ZarrAttribute::Text("K".into()))],
+//! )?;
+//! for chunk in 0..tb_up.num_chunks() {
+//!     let values = Array2::<f32>::zeros((100, 3));
+//!     tb_up.write_chunk(chunk, values.view().into_dyn())?;
+//! }
+//! # Ok::<(), rss_atmos_rtm::rtm::RtmError>(())
+//! ```
+
+use std::{
+    fmt::Write as _,
+    fs,
+    marker::PhantomData,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+};
+
+use ndarray::{ArrayViewD, Axis};
+
+use super::RtmError;
+
+/// An element type of a Zarr array.
+pub trait ZarrElement: Copy {
+    /// The Zarr (NumPy) type string, e.g., `<f4`.
+    const DTYPE: &'static str;
+
+    /// The fill value of missing data, as JSON.
+    const FILL_VALUE: &'static str;
+
+    /// The value of missing data, which pads the last chunk.
+    fn fill() -> Self;
+
+    /// Append the little-endian bytes of the value.
+    fn extend_le_bytes(self, bytes: &mut Vec<u8>);
+}
+
+macro_rules! zarr_element {
+    ($($element:ty => $dtype:literal, $fill_value:literal, $fill:expr;)*) => {
+        $(
+            impl ZarrElement for $element {
+                const DTYPE: &'static str = $dtype;
+                const FILL_VALUE: &'static str = $fill_value;
+
+                fn fill() -> Self {
+                    $fill
+                }
+
+                fn extend_le_bytes(self, bytes: &mut Vec<u8>) {
+                    bytes.extend(self.to_le_bytes());
+                }
+            }
+        )*
+    };
+}
+
+zarr_element! {
+    f32 => "<f4", "\"NaN\"", f32::NAN;
+    f64 => "<f8", "\"NaN\"", f64::NAN;
+    i32 => "<i4", "0", 0;
+    u32 => "<u4", "0", 0;
+}
+
+/// The value of an attribute of a group or array, which is stored as JSON.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ZarrAttribute {
+    /// A string.
+    Text(String),
+    /// A number, where NaN and infinities are stored as `null`.
+    Number(f64),
+    /// A list of numbers, stored as for [`ZarrAttribute::Number`].
+    Numbers(Vec<f64>),
+    /// A mapping of names to nested attributes.
+    Map(Vec<(String, ZarrAttribute)>),
+}
+
+impl ZarrAttribute {
+    /// Append the attribute as JSON.
+    fn write_json(&self, json: &mut String) {
+        match self {
+            ZarrAttribute::Text(text) => write_json_string(json, text),
+            ZarrAttribute::Number(value) => write_json_number(json, *value),
+            ZarrAttribute::Numbers(values) => {
+                json.push('[');
+                for (i, &value) in values.iter().enumerate() {
+                    if i > 0 {
+                        json.push_str(", ");
+                    }
+                    write_json_number(json, value);
+                }
+                json.push(']');
+            }
+            ZarrAttribute::Map(entries) => write_json_map(json, entries),
+        }
+    }
+}
+
+/// Append a string as JSON, with the escapes it needs.
+fn write_json_string(json: &mut String, text: &str) {
+    json.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if u32::from(c) < 0x20 => {
+                write!(json, "\\u{:04x}", u32::from(c)).expect("writing to a string");
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+/// Append a number as JSON, which has no NaN or infinity.
+fn write_json_number(json: &mut String, value: f64) {
+    if value.is_finite() {
+        write!(json, "{value:?}").expect("writing to a string");
+    } else {
+        json.push_str("null");
+    }
+}
+
+/// Append a mapping as a JSON object.
+fn write_json_map(json: &mut String, entries: &[(String, ZarrAttribute)]) {
+    json.push('{');
+    for (i, (name, value)) in entries.iter().enumerate() {
+        if i > 0 {
+            json.push_str(", ");
+        }
+        write_json_string(json, name);
+        json.push_str(": ");
+        value.write_json(json);
+    }
+    json.push('}');
+}
+
+/// Write a metadata file, which is JSON.
+fn write_metadata(path: &Path, json: &str) -> Result<(), RtmError> {
+    fs::write(path, json).map_err(RtmError::Io)
+}
+
+/// A Zarr group in a directory, whose arrays are created with
+/// [`create_array`](Self::create_array).
+#[derive(Debug)]
+pub struct ZarrGroup {
+    path: PathBuf,
+    /// The JSON of each metadata file, by its path relative to the group, for
+    /// the consolidated metadata
+    metadata: Vec<(String, String)>,
+}
+
+impl ZarrGroup {
+    /// Create a new group in the directory `path`, which is created if needed,
+    /// with the given attributes.
+    ///
+    /// An existing group at `path` is overwritten, but any arrays in it that
+    /// aren't created again are left in place. Returns [`RtmError::Io`] if the
+    /// directory or its metadata can't be written.
+    pub fn create(path: &Path, attributes: Vec<(String, ZarrAttribute)>) -> Result<Self, RtmError> {
+        fs::create_dir_all(path).map_err(RtmError::Io)?;
+        let mut group = Self {
+            path: path.to_path_buf(),
+            metadata: Vec::new(),
+        };
+        group.add_metadata(".zgroup", "{\"zarr_format\": 2}".to_string())?;
+        let mut json = String::new();
+        write_json_map(&mut json, &attributes);
+        group.add_metadata(".zattrs", json)?;
+        Ok(group)
+    }
+
+    /// The directory of the group.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Create an array named `name` in the group, with the given shape,
+    /// chunked along the first axis every `chunk_rows` rows, and the names of
+    /// its `dimensions` and its attributes.
+    ///
+    /// All of the chunks are missing (the fill value) until they're written.
+    /// Returns [`RtmError::InconsistentInputs`] if the shape is empty or
+    /// doesn't have one dimension name per axis, and [`RtmError::Io`] if the
+    /// array can't be created.
+    pub fn create_array<T: ZarrElement>(
+        &mut self,
+        name: &str,
+        shape: &[usize],
+        chunk_rows: NonZeroUsize,
+        dimensions: &[&str],
+        attributes: Vec<(String, ZarrAttribute)>,
+    ) -> Result<ZarrArray<T>, RtmError> {
+        if shape.is_empty() || dimensions.len() != shape.len() || name.contains(['/', '\\']) {
+            return Err(RtmError::InconsistentInputs);
+        }
+        let path = self.path.join(name);
+        fs::create_dir_all(&path).map_err(RtmError::Io)?;
+
+        let mut chunks = shape.to_vec();
+        chunks[0] = chunk_rows.get();
+        let list = |values: &[usize]| {
+            values
+                .iter()
+                .map(usize::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let zarray = format!(
+            "{{\"zarr_format\": 2, \"shape\": [{}], \"chunks\": [{}], \"dtype\": \"{}\", \
+             \"compressor\": null, \"fill_value\": {}, \"order\": \"C\", \"filters\": null, \
+             \"dimension_separator\": \".\"}}",
+            list(shape),
+            list(&chunks),
+            T::DTYPE,
+            T::FILL_VALUE,
+        );
+        self.add_metadata(&format!("{name}/.zarray"), zarray)?;
+
+        let mut json = String::from("{\"_ARRAY_DIMENSIONS\": [");
+        for (i, dimension) in dimensions.iter().enumerate() {
+            if i > 0 {
+                json.push_str(", ");
+            }
+            write_json_string(&mut json, dimension);
+        }
+        json.push(']');
+        for (name, value) in &attributes {
+            json.push_str(", ");
+            write_json_string(&mut json, name);
+            json.push_str(": ");
+            value.write_json(&mut json);
+        }
+        json.push('}');
+        self.add_metadata(&format!("{name}/.zattrs"), json)?;
+
+        Ok(ZarrArray {
+            path,
+            shape: shape.to_vec(),
+            chunk_rows,
+            element: PhantomData,
+        })
+    }
+
+    /// Write a metadata file of the group or an array, and rewrite the
+    /// consolidated metadata with it.
+    fn add_metadata(&mut self, key: &str, json: String) -> Result<(), RtmError> {
+        write_metadata(&self.path.join(key), &json)?;
+        match self.metadata.iter_mut().find(|(k, _)| k == key) {
+            Some((_, existing)) => *existing = json,
+            None => self.metadata.push((key.to_string(), json)),
+        }
+
+        let mut consolidated = String::from("{\"zarr_consolidated_format\": 1, \"metadata\": {");
+        for (i, (key, json)) in self.metadata.iter().enumerate() {
+            if i > 0 {
+                consolidated.push_str(", ");
+            }
+            write_json_string(&mut consolidated, key);
+            consolidated.push_str(": ");
+            consolidated.push_str(json);
+        }
+        consolidated.push_str("}}");
+        write_metadata(&self.path.join(".zmetadata"), &consolidated)
+    }
+}
+
+/// An array of a [`ZarrGroup`], which is written a chunk of rows at a time.
+///
+/// The chunks can be written in any order, and from several threads at once.
+#[derive(Debug)]
+pub struct ZarrArray<T> {
+    path: PathBuf,
+    shape: Vec<usize>,
+    chunk_rows: NonZeroUsize,
+    element: PhantomData<T>,
+}
+
+impl<T: ZarrElement> ZarrArray<T> {
+    /// The shape of the array.
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+
+    /// The number of rows in each chunk.
+    pub fn chunk_rows(&self) -> NonZeroUsize {
+        self.chunk_rows
+    }
+
+    /// The number of chunks along the first axis.
+    pub fn num_chunks(&self) -> usize {
+        self.shape[0].div_ceil(self.chunk_rows.get())
+    }
+
+    /// The rows of the chunk with index `chunk`, which is less than
+    /// [`num_chunks`](Self::num_chunks).
+    pub fn chunk_range(&self, chunk: usize) -> std::ops::Range<usize> {
+        let start = chunk * self.chunk_rows.get();
+        start..(start + self.chunk_rows.get()).min(self.shape[0])
+    }
+
+    /// Write the chunk with index `chunk`, whose values are the rows
+    /// [`chunk_range`](Self::chunk_range) of the array, replacing any earlier
+    /// values of the chunk.
+    ///
+    /// The last chunk may have fewer rows than the others, and it's padded
+    /// with the fill value in the file. Returns
+    /// [`RtmError::InconsistentInputs`] if the chunk doesn't exist or the
+    /// values don't have its shape, and [`RtmError::Io`] if it can't be
+    /// written.
+    pub fn write_chunk(&self, chunk: usize, values: ArrayViewD<'_, T>) -> Result<(), RtmError> {
+        let rows = self.chunk_range(chunk);
+        let mut expected = self.shape.clone();
+        expected[0] = rows.len();
+        if chunk >= self.num_chunks() || values.shape() != expected {
+            return Err(RtmError::InconsistentInputs);
+        }
+
+        let row_len: usize = self.shape[1..].iter().product();
+        let mut bytes =
+            Vec::with_capacity(self.chunk_rows.get() * row_len * std::mem::size_of::<T>());
+        // Iterating over a view is in logical (C) order, whatever its strides
+        for &value in &values {
+            value.extend_le_bytes(&mut bytes);
+        }
+        for _ in 0..(self.chunk_rows.get() - rows.len()) * row_len {
+            T::fill().extend_le_bytes(&mut bytes);
+        }
+
+        let key = std::iter::once(chunk.to_string())
+            .chain(std::iter::repeat("0".to_string()).take(self.shape.len() - 1))
+            .collect::<Vec<_>>()
+            .join(".");
+        fs::write(self.path.join(key), bytes).map_err(RtmError::Io)
+    }
+
+    /// Write a whole array that's small enough to be in memory, such as a
+    /// coordinate, one chunk at a time.
+    ///
+    /// Returns [`RtmError::InconsistentInputs`] if the values don't have the
+    /// shape of the array, and [`RtmError::Io`] if they can't be written.
+    pub fn write_all(&self, values: ArrayViewD<'_, T>) -> Result<(), RtmError> {
+        if values.shape() != self.shape {
+            return Err(RtmError::InconsistentInputs);
+        }
+        for chunk in 0..self.num_chunks() {
+            let rows = self.chunk_range(chunk);
+            let values = values.slice_axis(Axis(0), (rows.start..rows.end).into());
+            self.write_chunk(chunk, values)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,108 @@
+//! JavaScript interface for a WebAssembly build
+//!
+//! NOTE: like the Python and C interfaces, this module is only the interface
+//! between Rust and JavaScript. The real work happens in the
+//! [`rtm`](crate::rtm) module.
+//!
+//! With the `wasm` feature (and without the default features, since there's
+//! no Python or thread pool in a browser), this is built for
+//! `wasm32-unknown-unknown` with `wasm-pack`:
+//!
+//! ```text
+//! wasm-pack build --no-default-features --features wasm
+//! ```
+//!
+//! The arrays are `Float32Array`s, and the outputs are a
+//! [`SingleProfileOutputs`] with `tran`, `tbUp`, and `tbDown` properties. For
+//! example:
+//!
+//! ```js
+//! import { compute_single_profile } from "rss_atmos_rtm";
+//!
+//! const outputs = compute_single_profile(
+//!   pressure, temperature, height, specificHumidity, new Float32Array(),
+//!   288.0, 0.0, 280.0, 1010.0,
+//!   new Float32Array([23.8, 53.74]), new Float32Array([0.0, 30.0]),
+//! );
+//! console.log(outputs.tbUp);
+//! ```
+
+use wasm_bindgen::prelude::*;
+
+use crate::rtm::{RtmError, RtmInputs, RtmParameters};
+
+/// The outputs of [`compute_single_profile`], one value for each channel.
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SingleProfileOutputs {
+    tran: Vec<f32>,
+    tb_up: Vec<f32>,
+    tb_down: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl SingleProfileOutputs {
+    /// Total atmospheric transmissivity.
+    #[wasm_bindgen(getter)]
+    pub fn tran(&self) -> Vec<f32> {
+        self.tran.clone()
+    }
+
+    /// Upwelling brightness temperature in K.
+    #[wasm_bindgen(getter, js_name = tbUp)]
+    pub fn tb_up(&self) -> Vec<f32> {
+        self.tb_up.clone()
+    }
+
+    /// Downwelling brightness temperature in K.
+    #[wasm_bindgen(getter, js_name = tbDown)]
+    pub fn tb_down(&self) -> Vec<f32> {
+        self.tb_down.clone()
+    }
+}
+
+/// Run the RTM for one profile, for the channels with the `frequency` in GHz
+/// and the Earth `incidence_angle` in degrees.
+///
+/// The inputs are the same as for [`RtmInputs::new`], in the same units. The
+/// `liquid_content` may be empty for clear sky, and the `surface_pressure`
+/// may be `undefined` for the first level. Invalid inputs or channels throw
+/// an `Error` with the message.
+#[allow(clippy::too_many_arguments)]
+#[wasm_bindgen]
+pub fn compute_single_profile(
+    pressure: &[f32],
+    temperature: &[f32],
+    height: &[f32],
+    specific_humidity: &[f32],
+    liquid_content: &[f32],
+    surface_temperature: f32,
+    surface_height: f32,
+    surface_dewpoint: f32,
+    surface_pressure: Option<f32>,
+    frequency: &[f32],
+    incidence_angle: &[f32],
+) -> Result<SingleProfileOutputs, JsError> {
+    let run = || {
+        let parameters = RtmParameters::new(frequency, incidence_angle)?;
+        let inputs = RtmInputs::new(
+            pressure,
+            surface_temperature,
+            temperature,
+            surface_height,
+            height,
+            surface_dewpoint,
+            specific_humidity,
+            liquid_content,
+            surface_pressure,
+            None,
+        )?;
+        let outputs = inputs.run(&parameters);
+        Ok::<_, RtmError>(SingleProfileOutputs {
+            tran: outputs.tran.to_vec(),
+            tb_up: outputs.tb_up.to_vec(),
+            tb_down: outputs.tb_down.to_vec(),
+        })
+    };
+    run().map_err(JsError::from)
+}
@@ -0,0 +1,140 @@
+//! Compile and run the C program in `tests/capi/rtm_test.c` against the C
+//! interface, and compare its outputs to the Rust API.
+#![cfg(all(feature = "capi", unix))]
+
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use approx::assert_relative_eq;
+use rss_atmos_rtm::rtm::{RtmInputs, RtmParameters};
+
+/// Build the shared library from the current source, with the `capi` feature,
+/// and return the directory with it and the generated header.
+///
+/// The library that `cargo test` leaves in the target directory can be from an
+/// earlier build with other features, since it's shared between them, so it's
+/// built here in a target directory of its own.
+fn build_library() -> PathBuf {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let target_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("capi");
+    let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let output = Command::new(cargo)
+        .args([
+            "rustc",
+            "--lib",
+            "--crate-type",
+            "cdylib",
+            "--no-default-features",
+            "--features",
+            "capi",
+        ])
+        .arg("--manifest-path")
+        .arg(manifest_dir.join("Cargo.toml"))
+        .arg("--target-dir")
+        .arg(&target_dir)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "the library doesn't build:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    target_dir.join("debug")
+}
+
+#[test]
+fn c_program_matches_rust() {
+    let lib_dir = build_library();
+    let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/capi/rtm_test.c");
+    let program = env::temp_dir().join(format!("rss_atmos_rtm_capi_{}", std::process::id()));
+    let compiler = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let output = Command::new(compiler)
+        .arg(&source)
+        .arg("-I")
+        .arg(&lib_dir)
+        .arg("-L")
+        .arg(&lib_dir)
+        .args(["-lrss_atmos_rtm", "-lm"])
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-o")
+        .arg(&program)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "the C program doesn't compile:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Cargo puts its own library directories on the search path of the tests,
+    // which would take precedence over the rpath
+    let output = Command::new(&program)
+        .env_remove("LD_LIBRARY_PATH")
+        .output()
+        .unwrap();
+    std::fs::remove_file(&program).unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // The same points as in the C program
+    let pressure = [1000., 850., 700., 500., 300.];
+    let points = [
+        (
+            [288., 280., 272., 255., 230.],
+            [110., 1460., 3010., 5570., 9160.],
+            [8e-3, 5e-3, 3e-3, 1e-3, 1e-4],
+            [0.; 5],
+            (288., 280., 1010.),
+        ),
+        (
+            [298., 290., 280., 265., 240.],
+            [100., 1480., 3080., 5700., 9500.],
+            [1.8e-2, 1.2e-2, 6e-3, 2e-3, 3e-4],
+            [0., 2e-4, 1e-4, 0., 0.],
+            (299., 295., 1005.),
+        ),
+    ];
+    let parameters = RtmParameters::new(&[23.8, 53.74, 89.], &[0., 30., 53.]).unwrap();
+    let expected: Vec<_> = points
+        .iter()
+        .map(
+            |(temperature, height, specific_humidity, liquid_content, surface)| {
+                let (surface_temperature, surface_dewpoint, surface_pressure) = *surface;
+                RtmInputs::new(
+                    &pressure,
+                    surface_temperature,
+                    temperature,
+                    0.,
+                    height,
+                    surface_dewpoint,
+                    specific_humidity,
+                    liquid_content,
+                    Some(surface_pressure),
+                    None,
+                )
+                .unwrap()
+                .run(&parameters)
+            },
+        )
+        .collect();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(lines.len(), points.len() * parameters.len());
+    for line in lines {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let point: usize = fields[0].parse().unwrap();
+        let channel: usize = fields[1].parse().unwrap();
+        let value = |index: usize| fields[index].parse::<f32>().unwrap();
+        let outputs = &expected[point];
+        assert_relative_eq!(value(2), outputs.tran[channel]);
+        assert_relative_eq!(value(3), outputs.tb_up[channel]);
+        assert_relative_eq!(value(4), outputs.tb_down[channel]);
+    }
+}
@@ -0,0 +1,114 @@
+/*
+ * Run the RTM through the C interface, for the tests in `tests/capi.rs`.
+ *
+ * This runs two points of a known profile and prints "point channel tran tb_up
+ * tb_down" for each, to be compared against the Rust API. It also checks the
+ * errors itself, exiting with a nonzero status if any check fails.
+ */
+
+#include <math.h>
+#include <stdio.h>
+#include <string.h>
+
+#include "rss_atmos_rtm.h"
+
+#define N_POINTS 2
+#define N_LEVELS 5
+#define N_FREQ 3
+
+static int failures = 0;
+
+static void check(int condition, const char *what) {
+    if (!condition) {
+        fprintf(stderr, "check failed: %s\n", what);
+        failures++;
+    }
+}
+
+int main(void) {
+    char error[256];
+    const float frequency[N_FREQ] = {23.8f, 53.74f, 89.0f};
+    const float incidence_angle[N_FREQ] = {0.0f, 30.0f, 53.0f};
+
+    const float pressure[N_LEVELS] = {1000.0f, 850.0f, 700.0f, 500.0f, 300.0f};
+    const float temperature[N_POINTS][N_LEVELS] = {
+        {288.0f, 280.0f, 272.0f, 255.0f, 230.0f},
+        {298.0f, 290.0f, 280.0f, 265.0f, 240.0f},
+    };
+    const float height[N_POINTS][N_LEVELS] = {
+        {110.0f, 1460.0f, 3010.0f, 5570.0f, 9160.0f},
+        {100.0f, 1480.0f, 3080.0f, 5700.0f, 9500.0f},
+    };
+    const float specific_humidity[N_POINTS][N_LEVELS] = {
+        {8e-3f, 5e-3f, 3e-3f, 1e-3f, 1e-4f},
+        {1.8e-2f, 1.2e-2f, 6e-3f, 2e-3f, 3e-4f},
+    };
+    const float liquid_content[N_POINTS][N_LEVELS] = {
+        {0.0f, 0.0f, 0.0f, 0.0f, 0.0f},
+        {0.0f, 2e-4f, 1e-4f, 0.0f, 0.0f},
+    };
+    const float surface_temperature[N_POINTS] = {288.0f, 299.0f};
+    const float surface_height[N_POINTS] = {0.0f, 0.0f};
+    const float surface_dewpoint[N_POINTS] = {280.0f, 295.0f};
+    const float surface_pressure[N_POINTS] = {1010.0f, 1005.0f};
+    float tran[N_POINTS][N_FREQ], tb_up[N_POINTS][N_FREQ], tb_down[N_POINTS][N_FREQ];
+
+    RtmParams *params =
+        rtm_params_create(frequency, incidence_angle, N_FREQ, error, sizeof(error));
+    if (params == NULL) {
+        fprintf(stderr, "rtm_params_create: %s\n", error);
+        return 1;
+    }
+
+    int status = rtm_compute(
+        params, N_POINTS, N_LEVELS, pressure, &temperature[0][0], &height[0][0],
+        &specific_humidity[0][0], &liquid_content[0][0], surface_temperature,
+        surface_height, surface_dewpoint, surface_pressure, &tran[0][0], &tb_up[0][0],
+        &tb_down[0][0], 2, error, sizeof(error));
+    if (status != RTM_OK) {
+        fprintf(stderr, "rtm_compute: %s\n", error);
+        return 1;
+    }
+    for (int point = 0; point < N_POINTS; point++) {
+        for (int channel = 0; channel < N_FREQ; channel++) {
+            printf("%d %d %.9g %.9g %.9g\n", point, channel, tran[point][channel],
+                   tb_up[point][channel], tb_down[point][channel]);
+        }
+    }
+
+    /* A point without a surface fails, but the other is still run */
+    const float no_surface[N_POINTS] = {1010.0f, NAN};
+    status = rtm_compute(params, N_POINTS, N_LEVELS, pressure, &temperature[0][0],
+                         &height[0][0], &specific_humidity[0][0], NULL,
+                         surface_temperature, surface_height, surface_dewpoint,
+                         no_surface, &tran[0][0], &tb_up[0][0], &tb_down[0][0], 0,
+                         error, sizeof(error));
+    check(status == RTM_ERROR_INPUT, "a failed point is an input error");
+    check(strstr(error, "1 of 2 points failed, the first at index 1") != NULL,
+          "the failed point is in the message");
+    check(isnan(tb_up[1][0]) && !isnan(tb_up[0][0]), "only the failed point is NaN");
+
+    /* A missing array is an error, with the message truncated to the buffer */
+    char small[8];
+    status = rtm_compute(params, N_POINTS, N_LEVELS, pressure, NULL, &height[0][0],
+                         &specific_humidity[0][0], NULL, surface_temperature,
+                         surface_height, surface_dewpoint, NULL, &tran[0][0],
+                         &tb_up[0][0], &tb_down[0][0], 0, small, sizeof(small));
+    check(status == RTM_ERROR_INPUT, "a NULL array is an input error");
+    check(strcmp(small, "tempera") == 0, "the message is truncated");
+    status = rtm_compute(NULL, N_POINTS, N_LEVELS, pressure, &temperature[0][0],
+                         &height[0][0], &specific_humidity[0][0], NULL,
+                         surface_temperature, surface_height, surface_dewpoint, NULL,
+                         &tran[0][0], &tb_up[0][0], &tb_down[0][0], 0, NULL, 0);
+    check(status == RTM_ERROR_INPUT, "NULL params are an input error");
+    rtm_params_destroy(params);
+
+    /* Invalid channels */
+    const float invalid_angle[N_FREQ] = {0.0f, 30.0f, 95.0f};
+    params = rtm_params_create(frequency, invalid_angle, N_FREQ, error, sizeof(error));
+    check(params == NULL, "an invalid incidence angle is rejected");
+    check(strstr(error, "incidence") != NULL, "the message names the incidence angle");
+    rtm_params_destroy(NULL);
+
+    return failures == 0 ? 0 : 1;
+}
@@ -0,0 +1,156 @@
+//! Running the `rss-rtm` program on a small netCDF file of profiles, and
+//! comparing its outputs with a stored reference.
+//!
+//! `tests/data/cli/profiles.nc` is written by `make_profiles.py` in the same
+//! directory, and `reference.nc` is the output of `rss-rtm` for it with the
+//! arguments of [`run`], for MSU at scan position 3.
+#![cfg(feature = "cli")]
+
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+use rss_atmos_rtm::rtm::netcdf::NetcdfFile;
+
+const DATA: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/cli");
+
+/// Run the program with the profiles, the MSU sensor, and `args`.
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rss-rtm"))
+        .arg(Path::new(DATA).join("profiles.nc"))
+        .arg("--sensor")
+        .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/data/msu.toml"))
+        .args(["--scan-position", "3", "--quiet"])
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// A path for an output file of a test.
+fn output_path(test: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "rss_atmos_rtm_cli_{}_{test}.nc",
+        std::process::id()
+    ))
+}
+
+fn mapping() -> String {
+    format!("{DATA}/mapping.toml")
+}
+
+#[test]
+fn reads_the_profiles() {
+    let mut input = NetcdfFile::open(&Path::new(DATA).join("profiles.nc")).unwrap();
+    let time = &input.dimensions()[0];
+    assert_eq!(
+        (time.name.as_str(), time.len, time.unlimited),
+        ("time", 2, true)
+    );
+
+    // The record variables are read from each record, and the packed
+    // specific humidity is unpacked
+    let q = input.read::<f64>("q").unwrap();
+    assert_eq!(q.shape(), [2, 17, 3]);
+    for time in 0..2 {
+        assert!((q[[time, 0, 0]] - 0.012).abs() < 1e-9);
+        assert!((q[[time, 0, 2]] - 0.016).abs() < 1e-9);
+    }
+    let t = input.read::<f32>("t").unwrap();
+    assert_eq!(t[[1, 0, 0]] - t[[0, 0, 0]], -3.);
+    assert_eq!(input.read::<f32>("level").unwrap()[16], 10.);
+}
+
+#[test]
+fn matches_the_reference() {
+    let path = output_path("reference");
+    let output = run(&[
+        path.to_str().unwrap(),
+        "--mapping",
+        &mapping(),
+        "--chunk-size",
+        "4",
+        "--work-chunk-size",
+        "1",
+    ]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(output.stderr.is_empty());
+
+    let mut reference = NetcdfFile::open(&Path::new(DATA).join("reference.nc")).unwrap();
+    let mut outputs = NetcdfFile::open(&path).unwrap();
+    assert_eq!(outputs.dimensions(), reference.dimensions());
+    let variables = reference.variables().to_vec();
+    assert_eq!(outputs.variables().len(), variables.len());
+    for variable in variables {
+        let output = outputs.variable(&variable.name).unwrap();
+        assert_eq!(output.dimensions, variable.dimensions);
+        // The fill values are NaN, which aren't equal to themselves
+        assert_eq!(
+            format!("{:?}", output.attributes),
+            format!("{:?}", variable.attributes)
+        );
+        let expected = reference.read::<f64>(&variable.name).unwrap();
+        let values = outputs.read::<f64>(&variable.name).unwrap();
+        for (value, expected) in values.iter().zip(&expected) {
+            assert!(
+                (value - expected).abs() <= 1e-5 * expected.abs().max(1.),
+                "{}: {value} != {expected}",
+                variable.name
+            );
+        }
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn validate_only() {
+    let output = run(&["--mapping", &mapping(), "--validate-only"]);
+    assert!(output.status.success(), "{output:?}");
+}
+
+#[test]
+fn exit_codes() {
+    // The variables aren't named as the inputs without the mapping
+    let path = output_path("exit_codes");
+    let output = run(&[path.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("no variable named temperature"));
+    let output = run(&[
+        "--validate-only",
+        "--channels",
+        "5",
+        "--mapping",
+        &mapping(),
+    ]);
+    assert_eq!(output.status.code(), Some(2));
+
+    // An output that can't be created is a runtime failure
+    let missing = std::env::temp_dir().join("rss_atmos_rtm_cli_missing/outputs.nc");
+    let output = run(&["--mapping", &mapping(), missing.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn netcdf4_is_unsupported() {
+    let help = Command::new(env!("CARGO_BIN_EXE_rss-rtm"))
+        .arg("--help")
+        .output()
+        .unwrap();
+    assert!(String::from_utf8_lossy(&help.stdout).contains("netCDF-4"));
+
+    // Only the signature of an HDF5 file is needed to tell it apart
+    let input = output_path("netcdf4_input");
+    std::fs::write(&input, b"\x89HDF\r\n\x1a\n\0\0\0\0").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_rss-rtm"))
+        .arg(&input)
+        .arg("--sensor")
+        .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/data/msu.toml"))
+        .arg("--validate-only")
+        .output()
+        .unwrap();
+    std::fs::remove_file(&input).unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("netCDF-4"), "{stderr}");
+    assert!(stderr.contains("nccopy -k 64-bit-offset"), "{stderr}");
+}
@@ -0,0 +1,151 @@
+"""Write `profiles.nc`, the small netCDF file of profiles for the tests of the
+`rss-rtm` command-line program.
+
+This is a minimal writer of the classic netCDF format with only the standard
+library, independent of the reader in `rtm::netcdf`. The profiles are a
+standard atmosphere for 2 times and 3 sites, along the unlimited `time`
+dimension, with the levels between the time and the site, and with the
+specific humidity packed as shorts, to exercise the unpacking and reordering.
+
+Run with `python3 tests/data/cli/make_profiles.py` from the crate directory.
+"""
+
+import math
+import struct
+from pathlib import Path
+
+LEVELS = [1000, 925, 850, 700, 600, 500, 400, 300, 250, 200, 150, 100, 70, 50, 30, 20, 10]
+NUM_TIMES = 2
+NUM_SITES = 3
+Q_SCALE = 1e-6
+
+NC_CHAR, NC_SHORT, NC_INT, NC_FLOAT, NC_DOUBLE = 2, 3, 4, 5, 6
+FORMATS = {NC_SHORT: ">h", NC_INT: ">i", NC_FLOAT: ">f", NC_DOUBLE: ">d"}
+
+
+def height(p: float) -> float:
+    """Geopotential height in m of the pressure `p` in hPa."""
+    if p >= 226.32:
+        return 44330.8 * (1 - (p / 1013.25) ** 0.190263)
+    return 11000 + 6341.6 * math.log(226.32 / p)
+
+
+def temperature(z: float, time: int, site: int) -> float:
+    """Temperature in K at the height `z` in m."""
+    offset = 4.0 * site - 3.0 * time
+    return max(288.15 - 0.0065 * z, 216.65) + offset
+
+
+def pad(data: bytes) -> bytes:
+    return data + b"\0" * (-len(data) % 4)
+
+
+def name(text: str) -> bytes:
+    return struct.pack(">i", len(text)) + pad(text.encode())
+
+
+def attributes(attrs: list[tuple[str, int, list]]) -> bytes:
+    if not attrs:
+        return b"\0" * 8
+    out = struct.pack(">ii", 0x0C, len(attrs))
+    for key, nc_type, values in attrs:
+        if nc_type == NC_CHAR:
+            data = values.encode()
+        else:
+            data = b"".join(struct.pack(FORMATS[nc_type], v) for v in values)
+        out += name(key) + struct.pack(">ii", nc_type, len(values)) + pad(data)
+    return out
+
+
+def text(value: str) -> tuple[int, str]:
+    return (NC_CHAR, value)
+
+
+def main() -> None:
+    # (name, dimension ids, attributes, type, values in C order by record)
+    dims = [("time", 0), ("level", len(LEVELS)), ("site", NUM_SITES)]
+    profile = {"t": [], "z": [], "q": []}
+    surface = {"t2m": [], "z_sfc": [], "d2m": [], "sp": []}
+    for time in range(NUM_TIMES):
+        records = {key: [] for key in [*profile, *surface]}
+        for p in LEVELS:
+            for site in range(NUM_SITES):
+                z = height(p)
+                records["t"].append(temperature(z, time, site))
+                records["z"].append(z)
+                q = (0.012 + 0.002 * site) * (p / 1000) ** 3
+                records["q"].append(round(q / Q_SCALE))
+        for site in range(NUM_SITES):
+            z_sfc = 50.0 * site
+            sp = 1013.25 * (1 - z_sfc / 44330.8) ** 5.255
+            t2m = temperature(z_sfc, time, site) + 1.0
+            records["t2m"].append(t2m)
+            records["z_sfc"].append(z_sfc)
+            records["d2m"].append(t2m - 8.0)
+            records["sp"].append(sp)
+        for key, values in records.items():
+            (profile if key in profile else surface)[key].append(values)
+
+    units = lambda u: [("units", *text(u))]  # noqa: E731
+    fixed = [
+        ("level", [1], units("hPa"), NC_FLOAT, LEVELS),
+        ("site", [2], units("1"), NC_INT, list(range(NUM_SITES))),
+    ]
+    records = [
+        ("time", [0], units("hours since 2000-01-01"), NC_DOUBLE, [[6.0 * t] for t in range(NUM_TIMES)]),
+        ("t", [0, 1, 2], units("K"), NC_FLOAT, profile["t"]),
+        ("z", [0, 1, 2], units("m"), NC_FLOAT, profile["z"]),
+        (
+            "q",
+            [0, 1, 2],
+            units("kg/kg")
+            + [("scale_factor", NC_DOUBLE, [Q_SCALE]), ("_FillValue", NC_SHORT, [-32767])],
+            NC_SHORT,
+            profile["q"],
+        ),
+        ("t2m", [0, 2], units("K"), NC_FLOAT, surface["t2m"]),
+        ("z_sfc", [0, 2], units("m"), NC_FLOAT, surface["z_sfc"]),
+        ("d2m", [0, 2], units("K"), NC_FLOAT, surface["d2m"]),
+        ("sp", [0, 2], units("hPa"), NC_FLOAT, surface["sp"]),
+    ]
+
+    def data(nc_type: int, values: list) -> bytes:
+        return b"".join(struct.pack(FORMATS[nc_type], v) for v in values)
+
+    # The padded size of each fixed variable, or of one record of each record
+    # variable, which is all of its data for the record
+    variables = fixed + records
+    vsizes = [len(pad(data(v[3], v[4]))) for v in fixed]
+    vsizes += [len(pad(data(v[3], v[4][0]))) for v in records]
+
+    def header(begins: list[int]) -> bytes:
+        out = b"CDF\x01" + struct.pack(">i", NUM_TIMES)
+        out += struct.pack(">ii", 0x0A, len(dims))
+        for dim, length in dims:
+            out += name(dim) + struct.pack(">i", length)
+        out += attributes([("title", *text("profiles for the rss-rtm tests"))])
+        out += struct.pack(">ii", 0x0B, len(variables))
+        for (var, ids, attrs, nc_type, _), vsize, begin in zip(variables, vsizes, begins):
+            out += name(var) + struct.pack(">i", len(ids))
+            out += b"".join(struct.pack(">i", i) for i in ids)
+            out += attributes(attrs)
+            out += struct.pack(">iii", nc_type, vsize, begin)
+        return out
+
+    # The data of each fixed variable, and then each record in turn
+    begins, offset = [], len(header([0] * len(variables)))
+    for vsize in vsizes:
+        begins.append(offset)
+        offset += vsize
+
+    out = header(begins)
+    for var in fixed:
+        out += pad(data(var[3], var[4]))
+    for time in range(NUM_TIMES):
+        for var in records:
+            out += pad(data(var[3], var[4][time]))
+    Path(__file__).with_name("profiles.nc").write_bytes(out)
+
+
+if __name__ == "__main__":
+    main()
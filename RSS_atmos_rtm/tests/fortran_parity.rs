@@ -0,0 +1,258 @@
+//! Parity of the RTM with reference outputs for about 100 ERA5 profiles at the
+//! MSU frequencies.
+//!
+//! The reference inputs and outputs are CSV files in `tests/data/fortran_parity`,
+//! in the format described in the README there. The outputs are meant to be
+//! from the original Fortran, but the Fortran isn't part of this repository, so
+//! the committed outputs are from the original Rust port instead, with each
+//! channel run on its own. Until they're replaced by the Fortran outputs, the
+//! test catches changes from the port, but doesn't show parity with the
+//! Fortran. Run it with the summary of the differences with:
+//!
+//! ```text
+//! cargo test --test fortran_parity -- --nocapture
+//! ```
+
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use rss_atmos_rtm::rtm::{RtmInputs, RtmOutputs, RtmParameters};
+
+/// Maximum absolute difference of the brightness temperatures in K.
+const TB_TOLERANCE: f32 = 0.05;
+
+/// Maximum absolute difference of the transmissivity.
+const TRAN_TOLERANCE: f32 = 5e-4;
+
+/// The input profile for a single point.
+#[derive(Debug, Default)]
+struct Profile {
+    pressure: Vec<f32>,
+    temperature: Vec<f32>,
+    height: Vec<f32>,
+    specific_humidity: Vec<f32>,
+    liquid_content: Vec<f32>,
+    /// (temperature, height, dewpoint, pressure)
+    surface: Option<[f32; 4]>,
+}
+
+impl Profile {
+    fn inputs(&self, id: &str) -> RtmInputs {
+        let [surface_temperature, surface_height, surface_dewpoint, surface_pressure] = self
+            .surface
+            .unwrap_or_else(|| panic!("profile {id} has no surface values"));
+        RtmInputs::new(
+            &self.pressure,
+            surface_temperature,
+            &self.temperature,
+            surface_height,
+            &self.height,
+            surface_dewpoint,
+            &self.specific_humidity,
+            &self.liquid_content,
+            Some(surface_pressure),
+            None,
+        )
+        .unwrap_or_else(|e| panic!("profile {id}: {e}"))
+    }
+}
+
+/// The reference outputs for a single point and channel.
+#[derive(Debug)]
+struct Reference {
+    frequency: f32,
+    incidence: f32,
+    tran: f32,
+    tb_up: f32,
+    tb_down: f32,
+}
+
+/// Read a CSV file with a header row, checking the column names, and return
+/// the profile ID and the floating-point values of each row.
+fn read_csv(name: &str, columns: &[&str]) -> Vec<(String, Vec<f32>)> {
+    let path = data_dir().join(name);
+    let contents = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("couldn't read {}: {e}", path.display()));
+    let mut lines = contents.lines().filter(|line| !line.trim().is_empty());
+
+    let header: Vec<&str> = lines
+        .next()
+        .unwrap_or_else(|| panic!("{name} is empty"))
+        .split(',')
+        .map(str::trim)
+        .collect();
+    assert_eq!(header[0], "profile", "first column of {name}");
+    assert_eq!(&header[1..], columns, "columns of {name}");
+
+    lines
+        .enumerate()
+        .map(|(line_number, line)| {
+            let mut fields = line.split(',').map(str::trim);
+            let id = fields.next().unwrap().to_string();
+            let values: Vec<f32> = fields
+                .map(|field| {
+                    field.parse().unwrap_or_else(|e| {
+                        panic!("{name} line {}: {field:?}: {e}", line_number + 2)
+                    })
+                })
+                .collect();
+            assert_eq!(
+                values.len(),
+                columns.len(),
+                "{name} line {}",
+                line_number + 2
+            );
+            (id, values)
+        })
+        .collect()
+}
+
+fn data_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data/fortran_parity")
+}
+
+/// Read the reference profiles and outputs, keyed by profile ID.
+fn read_references() -> BTreeMap<String, (Profile, Vec<Reference>)> {
+    let mut references: BTreeMap<String, (Profile, Vec<Reference>)> = BTreeMap::new();
+
+    let columns = [
+        "pressure",
+        "temperature",
+        "height",
+        "specific_humidity",
+        "liquid_content",
+    ];
+    for (id, values) in read_csv("profiles.csv", &columns) {
+        let (profile, _) = references.entry(id).or_default();
+        profile.pressure.push(values[0]);
+        profile.temperature.push(values[1]);
+        profile.height.push(values[2]);
+        profile.specific_humidity.push(values[3]);
+        profile.liquid_content.push(values[4]);
+    }
+
+    let columns = [
+        "surface_temperature",
+        "surface_height",
+        "surface_dewpoint",
+        "surface_pressure",
+    ];
+    for (id, values) in read_csv("surface.csv", &columns) {
+        let (profile, _) = references
+            .get_mut(&id)
+            .unwrap_or_else(|| panic!("surface.csv: unknown profile {id}"));
+        profile.surface = Some([values[0], values[1], values[2], values[3]]);
+    }
+
+    let columns = ["frequency", "incidence", "tran", "tb_up", "tb_down"];
+    for (id, values) in read_csv("outputs.csv", &columns) {
+        let (_, outputs) = references
+            .get_mut(&id)
+            .unwrap_or_else(|| panic!("outputs.csv: unknown profile {id}"));
+        outputs.push(Reference {
+            frequency: values[0],
+            incidence: values[1],
+            tran: values[2],
+            tb_up: values[3],
+            tb_down: values[4],
+        });
+    }
+
+    references
+}
+
+/// Running statistics of the differences from the reference.
+#[derive(Debug, Default)]
+struct Differences {
+    count: usize,
+    max_abs: f32,
+    sum_squares: f64,
+}
+
+impl Differences {
+    fn add(&mut self, rust: f32, reference: f32) {
+        let difference = rust - reference;
+        self.count += 1;
+        self.max_abs = self.max_abs.max(difference.abs());
+        self.sum_squares += f64::from(difference).powi(2);
+    }
+
+    fn rms(&self) -> f64 {
+        (self.sum_squares / self.count as f64).sqrt()
+    }
+}
+
+/// Compare each profile's outputs to the reference, with all of its channels run
+/// together (the multi-frequency path, which reuses the absorption profile
+/// between channels of the same frequency) and each channel run on its own.
+#[test]
+fn fortran_parity() {
+    let references = read_references();
+    assert!(!references.is_empty(), "no reference profiles");
+
+    // Keyed by (path, quantity, frequency in kHz)
+    let mut differences: BTreeMap<(&str, &str, u32), Differences> = BTreeMap::new();
+    let mut failures = Vec::new();
+
+    for (id, (profile, expected)) in &references {
+        let inputs = profile.inputs(id);
+        let frequencies: Vec<f32> = expected.iter().map(|r| r.frequency).collect();
+        let incidences: Vec<f32> = expected.iter().map(|r| r.incidence).collect();
+
+        let multiple = inputs.run(&RtmParameters::new(&frequencies, &incidences).unwrap());
+        let single: Vec<RtmOutputs> = expected
+            .iter()
+            .map(|r| inputs.run(&RtmParameters::new(&[r.frequency], &[r.incidence]).unwrap()))
+            .collect();
+
+        for (channel, reference) in expected.iter().enumerate() {
+            let outputs = [
+                ("multiple", &multiple, channel),
+                ("single", &single[channel], 0),
+            ];
+            for (path, outputs, index) in outputs {
+                let compared = [
+                    ("tran", outputs.tran[index], reference.tran, TRAN_TOLERANCE),
+                    ("tb_up", outputs.tb_up[index], reference.tb_up, TB_TOLERANCE),
+                    (
+                        "tb_down",
+                        outputs.tb_down[index],
+                        reference.tb_down,
+                        TB_TOLERANCE,
+                    ),
+                ];
+                for (quantity, rust, expected, tolerance) in compared {
+                    let key = (path, quantity, (reference.frequency * 1e3).round() as u32);
+                    differences.entry(key).or_default().add(rust, expected);
+                    if (rust - expected).abs() > tolerance {
+                        failures.push(format!(
+                            "profile {id}, {} GHz at {}°, {path}: {quantity} is {rust}, \
+                             expected {expected}",
+                            reference.frequency, reference.incidence
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    println!(
+        "{:<10} {:<8} {:>11} {:>6} {:>11} {:>11}",
+        "path", "quantity", "freq (GHz)", "count", "max abs", "rms"
+    );
+    for ((path, quantity, freq), stats) in &differences {
+        println!(
+            "{path:<10} {quantity:<8} {:>11.3} {:>6} {:>11.3e} {:>11.3e}",
+            f64::from(*freq) * 1e-3,
+            stats.count,
+            stats.max_abs,
+            stats.rms()
+        );
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} differences exceed the tolerance:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
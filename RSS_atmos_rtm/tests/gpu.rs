@@ -0,0 +1,158 @@
+//! Compare the RTM with the gas absorption on a GPU to the CPU.
+//!
+//! The tests pass without checking anything if there's no GPU adapter, which is
+//! printed. With Mesa installed, the llvmpipe software adapter is enough.
+#![cfg(feature = "gpu")]
+
+use std::num::NonZeroUsize;
+
+use approx::assert_abs_diff_eq;
+use rss_atmos_rtm::rtm::{
+    compute_rtm_batch, gpu::GpuAbsorption, standard_atmospheres::ALL, AbsorptionBackend,
+    AbsorptionScale, Channel, RtmInputs, RtmParameters,
+};
+
+/// Channels spanning the absorption features: water vapor lines at 22 and 183
+/// GHz, the oxygen complex near 60 GHz, window channels, and the 118 GHz
+/// oxygen line.
+const FREQUENCIES: [f32; 10] = [
+    6.8, 23.8, 36.5, 50.3, 53.74, 57.95, 89., 118.75, 150., 190.31,
+];
+
+/// The documented tolerances of the GPU backend (see `rtm::gpu`).
+const TRAN_EPSILON: f32 = 1e-4;
+const TB_EPSILON: f32 = 0.01;
+
+/// The shared GPU, or `None` (which is printed) if there's no adapter.
+fn gpu() -> Option<&'static GpuAbsorption> {
+    let gpu = GpuAbsorption::shared();
+    match gpu {
+        Some(gpu) => println!("GPU adapter: {}", gpu.adapter_name()),
+        None => println!("no GPU adapter, so nothing is checked"),
+    }
+    gpu
+}
+
+/// The standard atmospheres, and each of them with a low cloud.
+fn inputs() -> Vec<RtmInputs> {
+    ALL.iter()
+        .flat_map(|atmosphere| {
+            let clear = atmosphere.profile();
+            let mut cloudy = clear.clone();
+            for (liquid, &height) in cloudy.liquid_content.iter_mut().zip(&clear.height) {
+                if (1000. ..2000.).contains(&height) {
+                    *liquid = 2e-4;
+                }
+            }
+            [clear.inputs().unwrap(), cloudy.inputs().unwrap()]
+        })
+        .collect()
+}
+
+/// Run the batch on the GPU and the CPU, and check that they agree within the
+/// tolerances.
+fn check(gpu: &GpuAbsorption, inputs: &[RtmInputs], parameters: &RtmParameters) {
+    let num_threads = NonZeroUsize::new(2);
+    let outputs = gpu
+        .compute_rtm_batch(inputs, parameters, num_threads)
+        .unwrap();
+    let expected = compute_rtm_batch(inputs, parameters, num_threads).unwrap();
+    assert_eq!(outputs.len(), expected.len());
+    for (outputs, expected) in outputs.iter().zip(&expected) {
+        assert_eq!(outputs.quality, expected.quality);
+        for channel in 0..parameters.len() {
+            assert_abs_diff_eq!(
+                outputs.tran[channel],
+                expected.tran[channel],
+                epsilon = TRAN_EPSILON
+            );
+            assert_abs_diff_eq!(
+                outputs.tb_up[channel],
+                expected.tb_up[channel],
+                epsilon = TB_EPSILON
+            );
+            assert_abs_diff_eq!(
+                outputs.tb_down[channel],
+                expected.tb_down[channel],
+                epsilon = TB_EPSILON
+            );
+        }
+    }
+}
+
+#[test]
+fn scaled_absorption_matches_cpu() {
+    let Some(gpu) = gpu() else {
+        return;
+    };
+    let parameters = RtmParameters::new(&FREQUENCIES, &[0.; 10])
+        .unwrap()
+        .with_absorption_scale(AbsorptionScale {
+            oxygen: 0.9,
+            water_vapor_lines: 1.1,
+            water_vapor_continuum: 2.,
+            cloud: 0.5,
+        });
+    check(gpu, &inputs(), &parameters);
+}
+
+#[test]
+fn outputs_match_cpu() {
+    let Some(gpu) = gpu() else {
+        return;
+    };
+    let incidence: Vec<f32> = (0..FREQUENCIES.len()).map(|i| 6. * i as f32).collect();
+    let parameters = RtmParameters::new(&FREQUENCIES, &incidence).unwrap();
+    check(gpu, &inputs(), &parameters);
+
+    // A repeated frequency at different incidence angles, as for MSU
+    let parameters = RtmParameters::new(&[53.74, 53.74, 23.8], &[0., 47.7, 47.7]).unwrap();
+    check(gpu, &inputs(), &parameters);
+}
+
+#[test]
+fn passbands_match_cpu() {
+    let Some(gpu) = gpu() else {
+        return;
+    };
+    let channels = [
+        Channel::from_quadrature(&[50.2, 50.3, 50.4], &[0.25, 0.5, 0.25]).unwrap(),
+        Channel::monochromatic(23.8),
+        Channel::from_quadrature(&[183.31 - 7., 183.31 + 7.], &[0.5, 0.5]).unwrap(),
+    ];
+    let parameters = RtmParameters::from_channels(&channels, &[0., 30., 50.]).unwrap();
+    check(gpu, &inputs(), &parameters);
+}
+
+#[test]
+fn backend_selection() {
+    let inputs = inputs();
+    let parameters = RtmParameters::new(&FREQUENCIES, &[0.; 10]).unwrap();
+    let gpu = parameters
+        .clone()
+        .with_absorption_backend(AbsorptionBackend::Gpu);
+    assert_eq!(gpu.absorption_backend(), AbsorptionBackend::Gpu);
+
+    // Whether or not there's an adapter, the outputs are within the tolerance
+    // of the CPU
+    let outputs = compute_rtm_batch(&inputs, &gpu, None).unwrap();
+    let expected = compute_rtm_batch(&inputs, &parameters, None).unwrap();
+    for (outputs, expected) in outputs.iter().zip(&expected) {
+        assert!(outputs.approx_eq(expected, 0., TB_EPSILON));
+    }
+    assert!(compute_rtm_batch(&[], &gpu, None).unwrap().is_empty());
+
+    // A non-finite input is run on the CPU
+    let mut profile = ALL[0].profile();
+    profile.temperature[10] = f32::NAN;
+    let nan = profile.inputs().unwrap();
+    if let Some(gpu) = GpuAbsorption::shared() {
+        let outputs = gpu
+            .compute_rtm_batch(std::slice::from_ref(&nan), &parameters, None)
+            .unwrap();
+        let expected = nan.run(&parameters);
+        assert_eq!(outputs[0].quality, expected.quality);
+        let bits = |values: &[f32]| values.iter().map(|v| v.to_bits()).collect::<Vec<_>>();
+        assert_eq!(bits(&outputs[0].tb_up), bits(&expected.tb_up));
+    }
+}
@@ -0,0 +1,219 @@
+//! Physical invariants of the RTM outputs over random, physically reasonable
+//! profiles, with `proptest`.
+//!
+//! Each case is one of the standard atmospheres with random perturbations: a
+//! temperature offset with a bounded change in lapse rate, scaled water vapor,
+//! a shifted surface, and optionally a low cloud. The cases are generated from
+//! a fixed seed, so the tests are deterministic. When a property fails,
+//! `proptest` shrinks the perturbations toward the unperturbed atmosphere and
+//! prints the smallest failing profile in full, so that it can be reproduced
+//! in a unit test.
+
+use proptest::{
+    prelude::*,
+    test_runner::{Config, RngSeed},
+};
+use rss_atmos_rtm::rtm::{
+    standard_atmospheres::{StandardProfile, ALL},
+    RtmInputs, RtmParameters,
+};
+
+/// Channels spanning the absorption features: water vapor lines at 22 and 183
+/// GHz, the oxygen complex near 60 GHz, and window channels. The 183 GHz
+/// channel is in the wing of the line, since at the line center the
+/// transmissivity of a moist atmosphere underflows to 0 in single precision.
+const FREQUENCIES: [f32; 7] = [23.8, 50.3, 53.74, 54.96, 57.95, 89., 190.31];
+
+/// 100 cases for each property, from a fixed seed, without saving the
+/// failures to a file.
+fn config() -> Config {
+    Config {
+        cases: 100,
+        rng_seed: RngSeed::Fixed(0),
+        failure_persistence: None,
+        ..Config::default()
+    }
+}
+
+/// A standard atmosphere with random perturbations, which all shrink toward
+/// zero.
+fn perturbed_profile() -> impl Strategy<Value = StandardProfile> {
+    (0..ALL.len())
+        .prop_flat_map(|atmosphere| {
+            let num_levels = ALL[atmosphere].profile().pressure.len();
+            (
+                Just(atmosphere),
+                // The temperature offset of the first level in K, and its
+                // change to each level from the one below
+                -5_f32..5.,
+                prop::collection::vec(-1_f32..1., num_levels),
+                // The surface temperature offset in K
+                -5_f32..5.,
+                // The natural log of the factor the specific humidity is
+                // scaled by
+                -1_f32..0.3,
+                // The shift of the surface pressure in hPa
+                -60_f32..10.,
+                // The lowest level with cloud, the number of levels above it
+                // with cloud, and its liquid water content in kg/kg
+                prop::option::of((0..8_usize, 1..5_usize, 1e-5_f32..3e-4)),
+            )
+        })
+        .prop_map(
+            |(
+                atmosphere,
+                offset,
+                lapse_changes,
+                surface_temperature_offset,
+                log_humidity_scale,
+                surface_pressure_offset,
+                cloud,
+            )| {
+                let mut profile = ALL[atmosphere].profile();
+                let mut offset = offset;
+                for (t, change) in profile.temperature.iter_mut().zip(lapse_changes) {
+                    offset += change;
+                    *t += offset;
+                }
+                let humidity_scale = log_humidity_scale.exp();
+                for q in &mut profile.specific_humidity {
+                    *q *= humidity_scale;
+                }
+                profile.surface_temperature += surface_temperature_offset;
+                profile.surface_dewpoint = f32::min(
+                    profile.surface_dewpoint + surface_temperature_offset,
+                    profile.surface_temperature,
+                );
+                profile.surface_pressure += surface_pressure_offset;
+                if let Some((bottom, num_levels, liquid_content)) = cloud {
+                    for value in &mut profile.liquid_content[bottom..=bottom + num_levels] {
+                        *value = liquid_content;
+                    }
+                }
+                profile
+            },
+        )
+}
+
+fn inputs(profile: &StandardProfile) -> Result<RtmInputs, TestCaseError> {
+    profile
+        .inputs()
+        .map_err(|e| TestCaseError::fail(format!("preparing the inputs: {e}")))
+}
+
+fn parameters(incidence: f32) -> RtmParameters {
+    RtmParameters::new(&FREQUENCIES, &[incidence; FREQUENCIES.len()]).unwrap()
+}
+
+proptest! {
+    #![proptest_config(config())]
+
+    /// The transmissivity is in (0, 1] and the brightness temperatures are
+    /// between 0 K and the warmest temperature in the profile.
+    #[test]
+    fn outputs_are_bounded(profile in perturbed_profile()) {
+        let max_temperature = profile
+            .temperature
+            .iter()
+            .fold(profile.surface_temperature, |max, &t| max.max(t));
+        let inputs = inputs(&profile)?;
+        for incidence in [0., 55.] {
+            let outputs = inputs.run(&parameters(incidence));
+            for (i, freq) in FREQUENCIES.iter().enumerate() {
+                let tran = outputs.tran[i];
+                prop_assert!(
+                    tran > 0. && tran <= 1.,
+                    "{} GHz at {}°: tran is {}",
+                    freq,
+                    incidence,
+                    tran
+                );
+                for (name, tb) in [("tb_up", outputs.tb_up[i]), ("tb_down", outputs.tb_down[i])] {
+                    prop_assert!(
+                        (0. ..=max_temperature).contains(&tb),
+                        "{} GHz at {}°: {} is {}, outside of 0 to {} K",
+                        freq,
+                        incidence,
+                        name,
+                        tb,
+                        max_temperature
+                    );
+                }
+            }
+        }
+    }
+
+    /// At 23.8 GHz, the downwelling increases with the water vapor.
+    #[test]
+    fn tb_down_increases_with_water_vapor(profile in perturbed_profile()) {
+        let parameters = RtmParameters::new(&[23.8], &[0.]).unwrap();
+        let mut previous: Option<(f32, f32)> = None;
+        for scale in [0.5, 0.75, 1., 1.25, 1.5] {
+            let mut moist = profile.clone();
+            for q in &mut moist.specific_humidity {
+                *q *= scale;
+            }
+            let tb_down = inputs(&moist)?.run(&parameters).tb_down[0];
+            if let Some((previous_scale, previous_tb_down)) = previous {
+                prop_assert!(
+                    tb_down > previous_tb_down,
+                    "tb_down is {} with the water vapor scaled by {} but {} when scaled by {}",
+                    previous_tb_down,
+                    previous_scale,
+                    tb_down,
+                    scale
+                );
+            }
+            previous = Some((scale, tb_down));
+        }
+    }
+
+    /// The transmissivity decreases as the incidence angle increases.
+    #[test]
+    fn tran_decreases_with_incidence(profile in perturbed_profile()) {
+        let inputs = inputs(&profile)?;
+        let incidences = [0., 20., 40., 55., 70., 85.];
+        let trans: Vec<_> = incidences
+            .iter()
+            .map(|&incidence| inputs.run(&parameters(incidence)).tran)
+            .collect();
+        for (i, freq) in FREQUENCIES.iter().enumerate() {
+            for j in 1..incidences.len() {
+                prop_assert!(
+                    trans[j][i] <= trans[j - 1][i],
+                    "{} GHz: tran is {} at {}° but {} at {}°",
+                    freq,
+                    trans[j - 1][i],
+                    incidences[j - 1],
+                    trans[j][i],
+                    incidences[j]
+                );
+            }
+        }
+    }
+
+    /// Adding cloud liquid water never increases the transmissivity.
+    #[test]
+    fn cloud_does_not_increase_tran(profile in perturbed_profile()) {
+        let parameters = parameters(0.);
+        let tran = inputs(&profile)?.run(&parameters).tran;
+
+        let mut cloudy = profile.clone();
+        for (value, &p) in cloudy.liquid_content.iter_mut().zip(&profile.pressure) {
+            if (700. ..=900.).contains(&p) {
+                *value += 2e-4;
+            }
+        }
+        let cloudy_tran = inputs(&cloudy)?.run(&parameters).tran;
+
+        for (i, freq) in FREQUENCIES.iter().enumerate() {
+            prop_assert!(
+                cloudy_tran[i] <= tran[i],
+                "{} GHz: tran is {} with cloud but {} without",
+                freq,
+                cloudy_tran[i],
+                tran[i]
+            );
+        }
+    }
+}
@@ -0,0 +1,299 @@
+//! Reading profiles from `.npy` files in chunks, compared with running the
+//! same profiles from memory.
+//!
+//! The files are written to a temporary directory by a minimal writer of the
+//! `.npy` format, for the standard atmospheres.
+#![cfg(feature = "io")]
+
+use std::{fs, num::NonZeroUsize, path::PathBuf};
+
+use ndarray::{Array1, Array2};
+use rss_atmos_rtm::rtm::{
+    npy::{NpyArray, NpyProfileFiles, NpyProfiles},
+    standard_atmospheres::ALL,
+    ArrayProfiles, ProfileSource, RtmError, RtmOutputs, RtmParameters,
+};
+
+const FREQUENCIES: [f32; 4] = [23.8, 50.3, 53.74, 57.95];
+
+/// The element type written to a file.
+#[derive(Debug, Clone, Copy)]
+enum Dtype {
+    F32,
+    F64,
+}
+
+/// Write `values` with the given shape to a `.npy` file in format version 1.0.
+fn write_npy(path: &PathBuf, shape: &[usize], values: &[f32], dtype: Dtype) {
+    let (descr, data) = match dtype {
+        Dtype::F32 => (
+            "<f4",
+            values
+                .iter()
+                .flat_map(|value| value.to_le_bytes())
+                .collect(),
+        ),
+        Dtype::F64 => (
+            "<f8",
+            values
+                .iter()
+                .flat_map(|&value| f64::from(value).to_le_bytes())
+                .collect::<Vec<_>>(),
+        ),
+    };
+    let shape = match shape {
+        [length] => format!("({length},)"),
+        _ => format!(
+            "({})",
+            shape
+                .iter()
+                .map(usize::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    let mut header = format!("{{'descr': '{descr}', 'fortran_order': False, 'shape': {shape}, }}");
+    // The header is padded so that the data is aligned to 64 bytes
+    while (10 + header.len() + 1) % 64 != 0 {
+        header.push(' ');
+    }
+    header.push('\n');
+
+    let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+    bytes.extend((header.len() as u16).to_le_bytes());
+    bytes.extend(header.as_bytes());
+    bytes.extend(data);
+    fs::write(path, bytes).unwrap();
+}
+
+/// The profiles of the standard atmospheres, repeated to `num_points`, as
+/// arrays with one row per profile.
+struct Profiles {
+    pressure: Array2<f32>,
+    temperature: Array2<f32>,
+    height: Array2<f32>,
+    specific_humidity: Array2<f32>,
+    liquid_content: Array2<f32>,
+    surface_temperature: Array1<f32>,
+    surface_height: Array1<f32>,
+    surface_dewpoint: Array1<f32>,
+    surface_pressure: Array1<f32>,
+}
+
+impl Profiles {
+    fn new(num_points: usize) -> Self {
+        let profiles: Vec<_> = (0..num_points)
+            .map(|index| ALL[index % ALL.len()].profile())
+            .collect();
+        let num_levels = profiles[0].pressure.len();
+        let rows = |values: fn(&_) -> &Vec<f32>| {
+            Array2::from_shape_fn((num_points, num_levels), |(point, level)| {
+                values(&profiles[point])[level]
+            })
+        };
+        let mut liquid_content = Array2::zeros((num_points, num_levels));
+        // A low cloud in every other profile
+        for point in (0..num_points).step_by(2) {
+            liquid_content[[point, 3]] = 2e-4;
+        }
+        Self {
+            pressure: rows(|profile| &profile.pressure),
+            temperature: rows(|profile| &profile.temperature),
+            height: rows(|profile| &profile.height),
+            specific_humidity: rows(|profile| &profile.specific_humidity),
+            liquid_content,
+            surface_temperature: profiles.iter().map(|p| p.surface_temperature).collect(),
+            surface_height: profiles.iter().map(|p| p.surface_height).collect(),
+            surface_dewpoint: profiles.iter().map(|p| p.surface_dewpoint).collect(),
+            surface_pressure: profiles.iter().map(|p| p.surface_pressure).collect(),
+        }
+    }
+
+    /// Write the profiles to `.npy` files in a new temporary directory named
+    /// after the test, with the pressure levels of the first profile shared
+    /// by all of the profiles if `shared_pressure` is set.
+    fn write(&self, test: &str, dtype: Dtype, shared_pressure: bool) -> NpyProfileFiles {
+        let dir =
+            std::env::temp_dir().join(format!("rss_atmos_rtm_npy_{}_{test}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let write_2d = |name: &str, array: &Array2<f32>| {
+            let path = dir.join(format!("{name}.npy"));
+            write_npy(&path, array.shape(), array.as_slice().unwrap(), dtype);
+            path
+        };
+        let write_1d = |name: &str, array: &Array1<f32>| {
+            let path = dir.join(format!("{name}.npy"));
+            write_npy(&path, array.shape(), array.as_slice().unwrap(), dtype);
+            path
+        };
+        NpyProfileFiles {
+            pressure: if shared_pressure {
+                write_1d("pressure", &self.pressure.row(0).to_owned())
+            } else {
+                write_2d("pressure", &self.pressure)
+            },
+            temperature: write_2d("temperature", &self.temperature),
+            height: write_2d("height", &self.height),
+            specific_humidity: write_2d("specific_humidity", &self.specific_humidity),
+            liquid_content: Some(write_2d("liquid_content", &self.liquid_content)),
+            surface_temperature: write_1d("surface_temperature", &self.surface_temperature),
+            surface_height: write_1d("surface_height", &self.surface_height),
+            surface_dewpoint: write_1d("surface_dewpoint", &self.surface_dewpoint),
+            surface_pressure: Some(write_1d("surface_pressure", &self.surface_pressure)),
+        }
+    }
+
+    /// Run the profiles from memory.
+    fn run(&self, shared_pressure: bool, parameters: &RtmParameters) -> Outputs {
+        let pressure = self.pressure.row(0).to_vec();
+        let source = if shared_pressure {
+            ArrayProfiles::new(
+                &pressure,
+                self.temperature.view(),
+                self.height.view(),
+                self.specific_humidity.view(),
+                self.liquid_content.view(),
+                self.surface_temperature.as_slice().unwrap(),
+                self.surface_height.as_slice().unwrap(),
+                self.surface_dewpoint.as_slice().unwrap(),
+                self.surface_pressure.as_slice(),
+                None,
+                None,
+                None,
+            )
+        } else {
+            ArrayProfiles::new_per_point_pressure(
+                self.pressure.view(),
+                self.temperature.view(),
+                self.height.view(),
+                self.specific_humidity.view(),
+                self.liquid_content.view(),
+                self.surface_temperature.as_slice().unwrap(),
+                self.surface_height.as_slice().unwrap(),
+                self.surface_dewpoint.as_slice().unwrap(),
+                self.surface_pressure.as_slice(),
+                None,
+                None,
+                None,
+            )
+        }
+        .unwrap();
+        run_source(&source, parameters)
+    }
+}
+
+/// The outputs of each profile as (transmissivity, upwelling,
+/// downwelling, quality flags).
+type Outputs = Vec<(Vec<f32>, Vec<f32>, Vec<f32>, u32)>;
+
+fn run_source(source: &ArrayProfiles<'_>, parameters: &RtmParameters) -> Outputs {
+    (0..source.len())
+        .map(|index| {
+            let RtmOutputs {
+                tran,
+                tb_up,
+                tb_down,
+                quality,
+            } = source
+                .profile(index)
+                .unwrap()
+                .inputs()
+                .unwrap()
+                .run(parameters);
+            (
+                tran.to_vec(),
+                tb_up.to_vec(),
+                tb_down.to_vec(),
+                quality.bits(),
+            )
+        })
+        .collect()
+}
+
+/// Run the profiles in the files, `chunk_size` at a time.
+fn run_chunks(files: &NpyProfileFiles, chunk_size: usize, parameters: &RtmParameters) -> Outputs {
+    let mut profiles = NpyProfiles::open(files).unwrap();
+    let mut outputs = Vec::new();
+    for chunk in profiles.chunks::<f32>(NonZeroUsize::new(chunk_size).unwrap()) {
+        let chunk = chunk.unwrap();
+        assert_eq!(chunk.start(), outputs.len());
+        outputs.extend(run_source(&chunk.profiles(None).unwrap(), parameters));
+    }
+    outputs
+}
+
+#[test]
+fn chunks_match_in_memory_run() {
+    let parameters = RtmParameters::new(&FREQUENCIES, &[0., 20., 40., 55.]).unwrap();
+    let profiles = Profiles::new(11);
+    for shared_pressure in [false, true] {
+        let files = profiles.write(
+            &format!("chunks_{shared_pressure}"),
+            Dtype::F32,
+            shared_pressure,
+        );
+        let expected = profiles.run(shared_pressure, &parameters);
+        // Chunks that divide the points evenly or not, and a single chunk
+        for chunk_size in [1, 3, 11, 100] {
+            assert_eq!(
+                run_chunks(&files, chunk_size, &parameters),
+                expected,
+                "shared pressure {shared_pressure}, chunk size {chunk_size}"
+            );
+        }
+    }
+}
+
+#[test]
+fn float64_files_are_converted() {
+    let parameters = RtmParameters::new(&FREQUENCIES, &[0.; 4]).unwrap();
+    let profiles = Profiles::new(5);
+    // The values are all exact in float32, so the conversion is exact too
+    let files = profiles.write("float64", Dtype::F64, false);
+    assert_eq!(
+        run_chunks(&files, 2, &parameters),
+        profiles.run(false, &parameters)
+    );
+
+    let temperature = NpyArray::open(&files.temperature).unwrap();
+    assert_eq!(temperature.shape(), profiles.temperature.shape());
+}
+
+#[test]
+fn invalid_files_are_rejected() {
+    let profiles = Profiles::new(4);
+    let files = profiles.write("invalid", Dtype::F32, false);
+
+    let not_npy = files.temperature.with_file_name("not_npy.npy");
+    fs::write(&not_npy, b"temperature,height\n").unwrap();
+    assert!(matches!(
+        NpyArray::open(&not_npy),
+        Err(RtmError::InvalidNpyFile(_))
+    ));
+
+    // Truncated data
+    let truncated = files.temperature.with_file_name("truncated.npy");
+    let bytes = fs::read(&files.temperature).unwrap();
+    fs::write(&truncated, &bytes[..bytes.len() - 4]).unwrap();
+    assert!(matches!(
+        NpyArray::open(&truncated),
+        Err(RtmError::InvalidNpyFile(_))
+    ));
+
+    // Inconsistent shapes
+    let inconsistent = NpyProfileFiles {
+        height: files.surface_height.clone(),
+        ..files.clone()
+    };
+    assert!(matches!(
+        NpyProfiles::open(&inconsistent),
+        Err(RtmError::InconsistentInputs)
+    ));
+
+    // Rows out of bounds
+    let mut profiles = NpyProfiles::open(&files).unwrap();
+    assert!(matches!(
+        profiles.read_chunk::<f32>(3, 2),
+        Err(RtmError::InconsistentInputs)
+    ));
+}
@@ -0,0 +1,124 @@
+//! Writing the outputs as Parquet, with coordinates of the points, and reading
+//! them back.
+#![cfg(feature = "arrow")]
+
+use std::{fs::File, sync::Arc};
+
+use arrow_array::{
+    cast::AsArray,
+    types::{Float32Type, Float64Type, UInt64Type},
+    ArrayRef, Float64Array, RecordBatch, TimestampSecondArray,
+};
+use arrow_schema::{DataType, TimeUnit};
+use ndarray::Array2;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use rss_atmos_rtm::rtm::{
+    parquet::{record_batch, write_parquet, Layout},
+    standard_atmospheres::ALL,
+    RtmError, RtmParameters,
+};
+
+/// The `tb_up` of each standard atmosphere, and a latitude and time for each.
+fn outputs() -> (Array2<f32>, Vec<(String, ArrayRef)>) {
+    let parameters = RtmParameters::new(&[23.8, 50.3, 57.95], &[0., 20., 40.]).unwrap();
+    let mut tb_up = Array2::zeros((ALL.len(), parameters.len()));
+    for (mut row, atmosphere) in tb_up.rows_mut().into_iter().zip(ALL) {
+        let outputs = atmosphere.profile().inputs().unwrap().run(&parameters);
+        row.assign(&ndarray::ArrayView1::from(outputs.tb_up.as_slice()));
+    }
+    let latitude: Float64Array = (0..ALL.len()).map(|i| i as f64 * 10.).collect();
+    let time: TimestampSecondArray = (0..ALL.len()).map(|i| Some(i as i64 * 60)).collect();
+    let coords = vec![
+        ("time".to_string(), Arc::new(time) as ArrayRef),
+        ("latitude".to_string(), Arc::new(latitude) as ArrayRef),
+    ];
+    (tb_up, coords)
+}
+
+/// Write the batch to a temporary file and read it back.
+fn round_trip(batch: &RecordBatch, name: &str) -> RecordBatch {
+    let path = std::env::temp_dir().join(format!(
+        "rss_atmos_rtm_{}_{name}.parquet",
+        std::process::id()
+    ));
+    write_parquet(&path, batch).unwrap();
+    let mut reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap())
+        .unwrap()
+        .build()
+        .unwrap();
+    let read = reader.next().unwrap().unwrap();
+    assert!(reader.next().is_none());
+    std::fs::remove_file(&path).unwrap();
+    read
+}
+
+#[test]
+fn wide_layout() {
+    let (tb_up, coords) = outputs();
+    let batch = record_batch(
+        &coords,
+        &[("tb_up".to_string(), tb_up.view())],
+        Layout::Wide,
+    )
+    .unwrap();
+    let read = round_trip(&batch, "wide");
+    assert_eq!(read, batch);
+
+    let schema = read.schema();
+    let names: Vec<_> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+    assert_eq!(names, ["time", "latitude", "tb_up_0", "tb_up_1", "tb_up_2"]);
+    assert_eq!(
+        schema.field(0).data_type(),
+        &DataType::Timestamp(TimeUnit::Second, None)
+    );
+    assert_eq!(read.num_rows(), ALL.len());
+    for channel in 0..3 {
+        let column = read.column(2 + channel).as_primitive::<Float32Type>();
+        assert_eq!(column.values().to_vec(), tb_up.column(channel).to_vec());
+    }
+}
+
+#[test]
+fn long_layout() {
+    let (tb_up, coords) = outputs();
+    let batch = record_batch(
+        &coords,
+        &[("tb_up".to_string(), tb_up.view())],
+        Layout::Long,
+    )
+    .unwrap();
+    let read = round_trip(&batch, "long");
+    assert_eq!(read, batch);
+    assert_eq!(read.num_rows(), ALL.len() * 3);
+
+    // The channels vary fastest, and the coordinates are repeated for each
+    let point = read.column(0).as_primitive::<UInt64Type>();
+    let channel = read.column(1).as_primitive::<UInt64Type>();
+    let latitude = read.column(3).as_primitive::<Float64Type>();
+    let values = read.column(4).as_primitive::<Float32Type>();
+    for row in 0..read.num_rows() {
+        let (i, j) = (point.value(row) as usize, channel.value(row) as usize);
+        assert_eq!((i, j), (row / 3, row % 3));
+        assert_eq!(latitude.value(row), i as f64 * 10.);
+        assert_eq!(values.value(row), tb_up[[i, j]]);
+    }
+}
+
+#[test]
+fn inconsistent_columns_are_rejected() {
+    let (tb_up, coords) = outputs();
+    let outputs = [("tb_up".to_string(), tb_up.view())];
+    let short = tb_up.slice(ndarray::s![1.., ..]);
+    assert!(matches!(
+        record_batch(&coords, &[("tb_up".to_string(), short)], Layout::Wide),
+        Err(RtmError::InconsistentInputs)
+    ));
+
+    let duplicate = [coords[0].clone(), ("time".to_string(), coords[1].1.clone())];
+    for layout in [Layout::Wide, Layout::Long] {
+        assert!(matches!(
+            record_batch(&duplicate, &outputs, layout),
+            Err(RtmError::Parquet(_))
+        ));
+    }
+}
@@ -0,0 +1,461 @@
+//! Use the RTM only through the public Rust API.
+
+use std::{num::NonZeroUsize, sync::Mutex};
+
+use approx::{assert_abs_diff_eq, assert_relative_eq};
+use ndarray::Array2;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use rss_atmos_rtm::rtm::{
+    compute_rtm_batch, compute_rtm_source, standard_atmospheres, ArrayProfiles, ProfileRef,
+    ProfileSource, RtmError, RtmInputs, RtmOutputs, RtmParameters,
+};
+
+/// A US standard atmosphere-like profile on 19 pressure levels, with a low
+/// cloud.
+fn standard_profile(surface_temperature: f32) -> Result<RtmInputs, RtmError> {
+    let levels = [
+        1000., 950., 900., 850., 800., 700., 600., 500., 400., 300., 250., 200., 150., 100., 70.,
+        50., 30., 20., 10.,
+    ];
+    let height: Vec<f32> = levels
+        .iter()
+        .map(|p: &f32| -7500. * (p / 1013.25).ln())
+        .collect();
+    let temperature: Vec<f32> = height
+        .iter()
+        .map(|z| f32::max(288. - 6.5e-3 * z, 216.65))
+        .collect();
+    let specific_humidity: Vec<f32> = height.iter().map(|z| 0.01 * (-z / 2000.).exp()).collect();
+    let liquid_content: Vec<f32> = levels
+        .iter()
+        .map(|&p| {
+            if (750. ..=850.).contains(&p) {
+                1e-4
+            } else {
+                0.
+            }
+        })
+        .collect();
+
+    RtmInputs::new(
+        &levels,
+        surface_temperature,
+        &temperature,
+        50.,
+        &height,
+        283.,
+        &specific_humidity,
+        &liquid_content,
+        Some(1005.),
+        None,
+    )
+}
+
+/// Pinned from the output of this version of the RTM.
+#[test]
+fn known_result() {
+    let parameters = RtmParameters::new(&[23.8, 50.3, 53.74], &[0., 0., 0.]).unwrap();
+    let inputs = standard_profile(289.).unwrap();
+    let RtmOutputs {
+        tran,
+        tb_up,
+        tb_down,
+        quality,
+    } = inputs.run(&parameters);
+
+    let expected_tran = [0.8749144, 0.64373946, 0.096528165];
+    let expected_tb_up = [34.216248, 93.11471, 220.9029];
+    let expected_tb_down = [34.32957, 94.88638, 245.41783];
+    for channel in 0..3 {
+        assert_relative_eq!(tran[channel], expected_tran[channel], max_relative = 1e-4);
+        assert_relative_eq!(tb_up[channel], expected_tb_up[channel], max_relative = 1e-4);
+        assert_relative_eq!(
+            tb_down[channel],
+            expected_tb_down[channel],
+            max_relative = 1e-4
+        );
+    }
+    assert!(quality.is_empty());
+}
+
+/// Reference outputs for the MSU channels at nadir over the standard
+/// atmospheres, pinned from the output of this version of the RTM.
+#[test]
+fn standard_atmospheres_msu() {
+    let parameters = RtmParameters::new(&[50.3, 53.74, 54.96, 57.95], &[0.; 4]).unwrap();
+    // (atmosphere, tran, tb_up, tb_down)
+    type Reference = (&'static str, [f32; 4], [f32; 4], [f32; 4]);
+    #[rustfmt::skip]
+    let expected: [Reference; 6] = [
+        ("us_standard_1976", [0.683486, 0.100787, 0.00224317, 9.43064e-13], [82.392, 221.281, 227.189, 217.887], [83.784, 244.583, 279.368, 285.667]),
+        ("tropical", [0.640798, 0.0908449, 0.00203490, 2.63236e-12], [98.499, 232.065, 229.639, 206.678], [100.412, 259.281, 292.067, 297.488]),
+        ("mid_latitude_summer", [0.659376, 0.0936654, 0.00207192, 2.64218e-12], [92.075, 229.963, 232.535, 219.488], [93.617, 254.079, 286.839, 292.102]),
+        ("mid_latitude_winter", [0.679509, 0.100685, 0.00219164, 4.22514e-13], [81.102, 217.300, 225.732, 216.428], [82.202, 235.573, 267.066, 271.006]),
+        ("subarctic_summer", [0.674598, 0.0982182, 0.00219535, 1.83120e-12], [85.786, 225.109, 232.903, 226.340], [87.093, 246.490, 279.728, 285.134]),
+        ("subarctic_winter", [0.676677, 0.104281, 0.00235108, 1.77067e-13], [78.947, 210.435, 222.140, 217.676], [79.907, 225.572, 255.720, 257.697]),
+    ];
+
+    for (name, expected_tran, expected_tb_up, expected_tb_down) in expected {
+        let atmosphere = standard_atmospheres::by_name(name).unwrap();
+        let outputs = atmosphere.profile().inputs().unwrap().run(&parameters);
+        assert!(outputs.quality.is_empty());
+        for channel in 0..4 {
+            assert_relative_eq!(
+                outputs.tran[channel],
+                expected_tran[channel],
+                max_relative = 1e-4
+            );
+            assert_abs_diff_eq!(
+                outputs.tb_up[channel],
+                expected_tb_up[channel],
+                epsilon = 0.01
+            );
+            assert_abs_diff_eq!(
+                outputs.tb_down[channel],
+                expected_tb_down[channel],
+                epsilon = 0.01
+            );
+        }
+    }
+}
+
+#[test]
+fn batch() {
+    let parameters = RtmParameters::new(&[23.8, 50.3], &[0., 45.]).unwrap();
+    let inputs: Vec<_> = standard_atmospheres::ALL
+        .iter()
+        .map(|atmosphere| atmosphere.profile().inputs().unwrap())
+        .collect();
+
+    let outputs = compute_rtm_batch(&inputs, &parameters, NonZeroUsize::new(2)).unwrap();
+    assert_eq!(outputs.len(), inputs.len());
+    for (inputs, outputs) in inputs.iter().zip(&outputs) {
+        let expected = inputs.run(&parameters);
+        assert_eq!(outputs.tran, expected.tran);
+        assert_eq!(outputs.tb_up, expected.tb_up);
+        assert_eq!(outputs.tb_down, expected.tb_down);
+    }
+}
+
+#[test]
+fn errors() {
+    assert!(matches!(
+        RtmParameters::new(&[23.8], &[95.]),
+        Err(RtmError::InvalidIncidence { index: 0, .. })
+    ));
+    assert!(matches!(
+        RtmInputs::new(&[], 288., &[], 0., &[], 280., &[], &[], Some(1010.), None),
+        Err(RtmError::InconsistentInputs)
+    ));
+}
+
+/// A toy source that generates the profiles on demand: each one is the same
+/// atmosphere, but with a different surface temperature.
+struct Generator {
+    levels: Vec<f32>,
+    temperature: Vec<f32>,
+    height: Vec<f32>,
+    specific_humidity: Vec<f32>,
+    liquid_content: Vec<f32>,
+    num_points: usize,
+}
+
+impl Generator {
+    fn new(num_points: usize) -> Self {
+        let levels: Vec<f32> = vec![1000., 850., 700., 500., 300., 200., 100., 50., 10.];
+        let height: Vec<f32> = levels.iter().map(|p| -7500. * (p / 1013.25).ln()).collect();
+        let temperature = height
+            .iter()
+            .map(|z| f32::max(288. - 6.5e-3 * z, 216.65))
+            .collect();
+        let specific_humidity = height.iter().map(|z| 0.01 * (-z / 2000.).exp()).collect();
+        let liquid_content = vec![0., 1e-4, 0., 0., 0., 0., 0., 0., 0.];
+        Self {
+            levels,
+            temperature,
+            height,
+            specific_humidity,
+            liquid_content,
+            num_points,
+        }
+    }
+
+    fn surface_temperature(index: usize) -> f32 {
+        270. + index as f32
+    }
+}
+
+impl ProfileSource for Generator {
+    fn len(&self) -> usize {
+        self.num_points
+    }
+
+    fn profile(&self, index: usize) -> Result<ProfileRef<'_>, RtmError> {
+        Ok(ProfileRef {
+            pressure: &self.levels,
+            temperature: &self.temperature,
+            height: &self.height,
+            specific_humidity: &self.specific_humidity,
+            liquid_content: &self.liquid_content,
+            surface_temperature: Self::surface_temperature(index),
+            surface_height: 20.,
+            surface_dewpoint: Self::surface_temperature(index) - 5.,
+            surface_pressure: Some(1010.),
+            skin_temperature: None,
+            observer_height: None,
+            top_pressure: None,
+        })
+    }
+}
+
+/// A custom profile source gives the same results as the same profiles in
+/// arrays.
+#[test]
+fn profile_source() {
+    let parameters = RtmParameters::new(&[23.8, 50.3, 53.74], &[0., 30., 53.1]).unwrap();
+    let generator = Generator::new(40);
+    let num_points = generator.len();
+    let num_levels = generator.levels.len();
+
+    let run = |source: &dyn ProfileSource| {
+        let results = Mutex::new(vec![None; num_points]);
+        compute_rtm_source(
+            source,
+            &parameters,
+            &|index, result: Result<RtmOutputs, RtmError>| {
+                results.lock().unwrap()[index] = Some(result.unwrap().tb_up);
+            },
+            NonZeroUsize::new(3),
+        )
+        .unwrap();
+        results.into_inner().unwrap()
+    };
+
+    let broadcast = |profile: &[f32]| {
+        Array2::from_shape_fn((num_points, num_levels), |(_, level)| profile[level])
+    };
+    let temperature = broadcast(&generator.temperature);
+    let height = broadcast(&generator.height);
+    let specific_humidity = broadcast(&generator.specific_humidity);
+    let liquid_content = broadcast(&generator.liquid_content);
+    let surface_temperature: Vec<f32> = (0..num_points)
+        .map(Generator::surface_temperature)
+        .collect();
+    let surface_dewpoint: Vec<f32> = surface_temperature.iter().map(|t| t - 5.).collect();
+    let surface_height = vec![20.; num_points];
+    let surface_pressure = vec![1010.; num_points];
+    let arrays = ArrayProfiles::new(
+        &generator.levels,
+        temperature.view(),
+        height.view(),
+        specific_humidity.view(),
+        liquid_content.view(),
+        &surface_temperature,
+        &surface_height,
+        &surface_dewpoint,
+        Some(surface_pressure.as_slice()),
+        None,
+        None,
+        None,
+    )
+    .unwrap();
+
+    let generated = run(&generator);
+    assert_eq!(generated, run(&arrays));
+    for (index, tb_up) in generated.into_iter().enumerate() {
+        let expected = generator.profile(index).unwrap().inputs().unwrap();
+        assert_eq!(tb_up.unwrap(), expected.run(&parameters).tb_up);
+    }
+
+    // The array shapes are checked
+    assert!(matches!(
+        ArrayProfiles::new(
+            &generator.levels[1..],
+            temperature.view(),
+            height.view(),
+            specific_humidity.view(),
+            liquid_content.view(),
+            &surface_temperature,
+            &surface_height,
+            &surface_dewpoint,
+            Some(surface_pressure.as_slice()),
+            None,
+            None,
+            None,
+        ),
+        Err(RtmError::InconsistentInputs)
+    ));
+}
+
+/// Profiles with their own pressure levels give the same results as the same
+/// profiles run one at a time.
+#[test]
+fn per_point_pressure() {
+    let parameters = RtmParameters::new(&[23.8, 50.3, 53.74], &[0., 30., 53.1]).unwrap();
+    let generator = Generator::new(8);
+    let num_points = generator.len();
+    let num_levels = generator.levels.len();
+
+    // Squash the levels towards the surface by a different amount for each
+    // point, as for terrain-following model levels
+    let scale = |point: usize| 1. - 0.02 * point as f32;
+    let pressure = Array2::from_shape_fn((num_points, num_levels), |(point, level)| {
+        generator.levels[level] * scale(point)
+    });
+    let broadcast = |profile: &[f32]| {
+        Array2::from_shape_fn((num_points, num_levels), |(_, level)| profile[level])
+    };
+    let temperature = broadcast(&generator.temperature);
+    let height = broadcast(&generator.height);
+    let specific_humidity = broadcast(&generator.specific_humidity);
+    let liquid_content = broadcast(&generator.liquid_content);
+    let surface_temperature = vec![285.; num_points];
+    let surface_dewpoint = vec![280.; num_points];
+    let surface_height = vec![20.; num_points];
+    let surface_pressure: Vec<f32> = (0..num_points).map(|point| 1010. * scale(point)).collect();
+    let arrays = ArrayProfiles::new_per_point_pressure(
+        pressure.view(),
+        temperature.view(),
+        height.view(),
+        specific_humidity.view(),
+        liquid_content.view(),
+        &surface_temperature,
+        &surface_height,
+        &surface_dewpoint,
+        Some(surface_pressure.as_slice()),
+        None,
+        None,
+        None,
+    )
+    .unwrap();
+    assert_eq!(arrays.num_levels(), num_levels);
+
+    for point in 0..num_points {
+        let expected = RtmInputs::new(
+            pressure.row(point).as_slice().unwrap(),
+            surface_temperature[point],
+            &generator.temperature,
+            surface_height[point],
+            &generator.height,
+            surface_dewpoint[point],
+            &generator.specific_humidity,
+            &generator.liquid_content,
+            Some(surface_pressure[point]),
+            None,
+        )
+        .unwrap()
+        .run(&parameters);
+        let actual = arrays
+            .profile(point)
+            .unwrap()
+            .inputs()
+            .unwrap()
+            .run(&parameters);
+        assert_eq!(actual.tb_up, expected.tb_up);
+        assert_eq!(actual.tb_down, expected.tb_down);
+        assert_eq!(actual.tran, expected.tran);
+    }
+
+    // The outputs differ from point to point only because of the pressure
+    let tb_up: Vec<_> = (0..num_points)
+        .map(|point| {
+            let inputs = arrays.profile(point).unwrap().inputs().unwrap();
+            inputs.run(&parameters).tb_up
+        })
+        .collect();
+    assert!(tb_up.windows(2).all(|pair| pair[0] != pair[1]));
+
+    // The number of rows of the pressure is checked too
+    assert!(matches!(
+        ArrayProfiles::new_per_point_pressure(
+            pressure.slice(ndarray::s![1.., ..]),
+            temperature.view(),
+            height.view(),
+            specific_humidity.view(),
+            liquid_content.view(),
+            &surface_temperature,
+            &surface_height,
+            &surface_dewpoint,
+            Some(surface_pressure.as_slice()),
+            None,
+            None,
+            None,
+        ),
+        Err(RtmError::InconsistentInputs)
+    ));
+}
+
+/// The outputs are bit-for-bit identical for any number of threads and on
+/// repeated runs, both for a batch and for a profile source.
+#[test]
+#[cfg(feature = "parallel")]
+fn deterministic_across_thread_counts() {
+    let parameters = RtmParameters::new(
+        &[23.8, 50.3, 53.74, 54.96, 57.95, 89., 183.31],
+        &[0., 53.1, 53.1, 53.1, 53.1, 30., 45.],
+    )
+    .unwrap();
+    let generator = Generator::new(300);
+    let inputs: Vec<RtmInputs> = (0..generator.len())
+        .map(|index| generator.profile(index).unwrap().inputs().unwrap())
+        .collect();
+
+    let bits = |outputs: &[RtmOutputs]| -> Vec<u32> {
+        outputs
+            .iter()
+            .flat_map(|o| {
+                let values = o.tran.iter().chain(&o.tb_up).chain(&o.tb_down);
+                values
+                    .map(|value| value.to_bits())
+                    .chain([o.quality.bits()])
+            })
+            .collect()
+    };
+    let batch = |num_threads| {
+        bits(&compute_rtm_batch(&inputs, &parameters, NonZeroUsize::new(num_threads)).unwrap())
+    };
+    let source = |num_threads| {
+        let results = Mutex::new((0..generator.len()).map(|_| None).collect::<Vec<_>>());
+        compute_rtm_source(
+            &generator,
+            &parameters,
+            &|index, result: Result<RtmOutputs, RtmError>| {
+                results.lock().unwrap()[index] = Some(result.unwrap());
+            },
+            NonZeroUsize::new(num_threads),
+        )
+        .unwrap();
+        let outputs: Vec<RtmOutputs> = results
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(Option::unwrap)
+            .collect();
+        bits(&outputs)
+    };
+
+    // The streaming API, as used by the Python interface
+    let streaming = |num_threads| {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .build()
+            .unwrap();
+        let mut results = Vec::new();
+        pool.install(|| {
+            rss_atmos_rtm::rtm::compute_rtm_iter(&parameters, inputs.par_iter().cloned().map(Ok))
+                .map(Result::unwrap)
+                .collect_into_vec(&mut results)
+        });
+        bits(&results)
+    };
+
+    let reference = batch(1);
+    for num_threads in [1, 2, 3, 8, 64] {
+        for _ in 0..2 {
+            assert_eq!(batch(num_threads), reference, "{num_threads} threads");
+            assert_eq!(source(num_threads), reference, "{num_threads} threads");
+            assert_eq!(streaming(num_threads), reference, "{num_threads} threads");
+        }
+    }
+}
@@ -0,0 +1,92 @@
+"""The `Diagnostics` counters, each driven by a crafted input.
+
+Run with `pytest tests/python` after installing the package.
+"""
+
+import numpy as np
+import pytest
+from rss_atmos_rtm.rss_atmos_rtm import (
+    RtmOptions,
+    RtmParameters,
+    compute_rtm,
+    compute_rtm_dict,
+    standard_atmosphere,
+)
+
+NUM_POINTS = 4
+PARAMETERS = RtmParameters([23.8, 53.74], [0.0, 30.0])
+
+
+def test_clean() -> None:
+    """Nothing fires for a standard atmosphere."""
+    diagnostics = compute_rtm_dict(
+        standard_atmosphere("tropical", num_points=NUM_POINTS),
+        parameters=PARAMETERS,
+    ).diagnostics
+    assert diagnostics.num_points == NUM_POINTS
+    assert diagnostics.points_failed == 0
+    assert diagnostics.points_skipped == 0
+    assert diagnostics.clamped == {"surface_vapor_pressure": 0}
+    assert diagnostics.layers_fixed == 0
+
+
+def test_clamped() -> None:
+    """A dewpoint above the air temperature is clamped to saturation."""
+    profiles = standard_atmosphere("tropical", num_points=NUM_POINTS)
+    profiles["surface_dewpoint"][1] = profiles["surface_temperature"][1] + 5
+    diagnostics = compute_rtm_dict(
+        profiles,
+        parameters=PARAMETERS,
+        options=RtmOptions(clamp_surface_saturation=True),
+    ).diagnostics
+    assert diagnostics.clamped == {"surface_vapor_pressure": 1}
+    assert diagnostics.counts["surface_vapor_clamped"] == 1
+
+    # Without the clamp, nothing is clamped
+    diagnostics = compute_rtm_dict(profiles, parameters=PARAMETERS).diagnostics
+    assert diagnostics.clamped == {"surface_vapor_pressure": 0}
+
+
+def test_layers_fixed() -> None:
+    """A surface above every level makes a layer of negative thickness, which
+    is fixed by moving the surface down."""
+    profiles = standard_atmosphere("tropical", num_points=NUM_POINTS)
+    profiles["surface_height"][2] = profiles["height"][2].max() + 1
+    diagnostics = compute_rtm_dict(profiles, parameters=PARAMETERS).diagnostics
+    assert diagnostics.layers_fixed == 1
+    assert diagnostics.num_points == NUM_POINTS
+
+
+def test_points_failed() -> None:
+    """A surface pressure below every level fails, and the exception has the
+    diagnostics of the rest."""
+    profiles = standard_atmosphere("tropical", num_points=NUM_POINTS)
+    profiles["surface_pressure"][3] = 0.0
+    with pytest.raises(ValueError) as e:
+        compute_rtm_dict(profiles, parameters=PARAMETERS)
+    diagnostics = e.value.diagnostics
+    assert diagnostics.points_failed == 1
+    assert diagnostics.points_skipped == 0
+    assert diagnostics.num_points == NUM_POINTS - 1
+
+
+def test_points_skipped() -> None:
+    """An exception from the absorption model cancels the remaining points."""
+    profiles = standard_atmosphere("tropical", num_points=NUM_POINTS)
+
+    def absorption_model(*_args: float) -> float:
+        raise RuntimeError("no absorption")
+
+    with pytest.raises(RuntimeError) as e:
+        compute_rtm(
+            **profiles,
+            incidence_angle=np.array([0.0], dtype=np.float32),
+            frequency=np.array([23.8], dtype=np.float32),
+            num_threads=1,
+            absorption_model=absorption_model,
+        )
+    diagnostics = e.value.diagnostics
+    # With one thread, the first point raises and the others aren't run
+    assert diagnostics.points_skipped == NUM_POINTS - 1
+    assert diagnostics.points_failed == 0
+    assert diagnostics.num_points == 1
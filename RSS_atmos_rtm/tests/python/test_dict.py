@@ -0,0 +1,61 @@
+"""Computing the RTM from a dict of named arrays.
+
+Run with `pytest tests/python` after installing the package.
+"""
+
+import numpy as np
+import pytest
+from rss_atmos_rtm.rss_atmos_rtm import (
+    RtmOptions,
+    RtmParameters,
+    compute_rtm,
+    compute_rtm_dict,
+    standard_atmosphere,
+)
+
+OPTIONS = RtmOptions(verbosity="quiet")
+PARAMETERS = RtmParameters([23.8, 53.74], [0.0, 30.0])
+
+
+def test_same_as_compute_rtm() -> None:
+    """The dict gives the same outputs as the positional arguments."""
+    profiles = standard_atmosphere("subarctic_winter", num_points=4)
+    outputs = compute_rtm_dict(profiles, parameters=PARAMETERS, options=OPTIONS)
+    expected = compute_rtm(
+        profiles["pressure"],
+        profiles["temperature"],
+        profiles["height"],
+        profiles["specific_humidity"],
+        profiles["liquid_content"],
+        profiles["surface_temperature"],
+        profiles["surface_height"],
+        profiles["surface_dewpoint"],
+        profiles["surface_pressure"],
+        None,
+        None,
+        None,
+        parameters=PARAMETERS,
+        options=OPTIONS,
+    )
+    np.testing.assert_array_equal(outputs.tb_up, expected.tb_up)
+    np.testing.assert_array_equal(outputs.tb_down, expected.tb_down)
+
+    # The channels can also be in the dict
+    profiles["incidence_angle"] = np.array([0.0, 30.0], dtype=np.float32)
+    profiles["frequency"] = np.array([23.8, 53.74], dtype=np.float32)
+    outputs = compute_rtm_dict(profiles, options=OPTIONS)
+    np.testing.assert_array_equal(outputs.tb_up, expected.tb_up)
+
+
+def test_missing_and_unknown_keys() -> None:
+    """Missing keys are all named, and unknown keys are rejected."""
+    profiles = standard_atmosphere("tropical")
+    del profiles["surface_dewpoint"]
+    del profiles["height"]
+    with pytest.raises(KeyError, match="'height', 'surface_dewpoint'"):
+        compute_rtm_dict(profiles, parameters=PARAMETERS)
+
+    profiles = standard_atmosphere("tropical")
+    profiles["surface_dew_point"] = profiles.pop("surface_dewpoint")
+    with pytest.raises(ValueError, match="'surface_dew_point'"):
+        compute_rtm_dict(profiles, parameters=PARAMETERS)
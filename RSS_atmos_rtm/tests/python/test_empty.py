@@ -0,0 +1,56 @@
+"""Running the RTM with no points or no channels.
+
+Run with `pytest tests/python` after installing the package.
+"""
+
+import numpy as np
+import pytest
+from rss_atmos_rtm.rss_atmos_rtm import (
+    RtmOptions,
+    RtmParameters,
+    compute_rtm_dict,
+    standard_atmosphere,
+)
+
+
+def no_points() -> dict:
+    """The profiles of a standard atmosphere, without any points."""
+    profiles = standard_atmosphere("tropical", num_points=1)
+    return {
+        name: values if name == "pressure" else values[:0]
+        for name, values in profiles.items()
+    }
+
+
+def test_no_points() -> None:
+    """Empty profiles give empty outputs, with a row for each point."""
+    outputs = compute_rtm_dict(
+        no_points(),
+        parameters=RtmParameters([23.8, 53.74], [0.0, 30.0]),
+        options=RtmOptions(quality_flags=True, profile_timing=True),
+    )
+    for values in [outputs.tran, outputs.tb_up, outputs.tb_down]:
+        assert values.shape == (0, 2)
+    assert outputs.quality_flags.shape == (0,)
+    assert outputs.num_levels_integrated.shape == (0,)
+    assert outputs.profile_time.shape == (0,)
+    assert outputs.diagnostics.num_points == 0
+
+
+def test_no_points_inconsistent() -> None:
+    """The shapes are still checked without any points."""
+    profiles = no_points()
+    profiles["temperature"] = np.zeros((0, 3), dtype=np.float32)
+    with pytest.raises(ValueError):
+        compute_rtm_dict(profiles, parameters=RtmParameters([23.8], [0.0]))
+
+
+def test_no_channels() -> None:
+    """There must be at least one channel."""
+    with pytest.raises(ValueError):
+        RtmParameters([], [])
+    profiles = standard_atmosphere("tropical", num_points=2)
+    profiles["frequency"] = np.array([], dtype=np.float32)
+    profiles["incidence_angle"] = np.array([], dtype=np.float32)
+    with pytest.raises(ValueError):
+        compute_rtm_dict(profiles)
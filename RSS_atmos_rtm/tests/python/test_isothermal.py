@@ -0,0 +1,31 @@
+"""The analytic brightness temperatures of an isothermal atmosphere.
+
+Run with `pytest tests/python` after installing the package.
+"""
+
+import numpy as np
+import pytest
+from rss_atmos_rtm.rss_atmos_rtm import (
+    RtmParameters,
+    compute_isothermal_tb,
+    compute_rtm_dict,
+    isothermal_atmosphere,
+)
+
+
+@pytest.mark.parametrize("temperature", [180.0, 250.0, 300.0])
+def test_isothermal_tb(temperature: float) -> None:
+    """Both `tb_up` and `tb_down` are `T * (1 - tran)`."""
+    profiles = isothermal_atmosphere(temperature, num_points=3)
+    assert profiles["temperature"].shape == (3, 50)
+    assert np.all(profiles["temperature"] == np.float32(temperature))
+    outputs = compute_rtm_dict(
+        profiles,
+        parameters=RtmParameters([6.8, 23.8, 53.74, 118.75], [0.0, 30.0, 45.0, 60.0]),
+    )
+    expected = compute_isothermal_tb(
+        np.array(temperature, dtype=np.float32), outputs.tran
+    )
+    assert expected.shape == outputs.tran.shape
+    np.testing.assert_allclose(outputs.tb_up, expected, rtol=1e-4)
+    np.testing.assert_allclose(outputs.tb_down, expected, rtol=1e-4)
@@ -0,0 +1,62 @@
+"""The progress of `compute_rtm` is logged to Python logging with structured
+fields in the `extra` of each record.
+
+Run with `pytest tests/python` after installing the package.
+"""
+
+import logging
+
+from rss_atmos_rtm.rss_atmos_rtm import (
+    RtmOptions,
+    RtmParameters,
+    compute_rtm_dict,
+    standard_atmosphere,
+)
+
+
+class CapturingHandler(logging.Handler):
+    """Keeps the records it handles."""
+
+    def __init__(self) -> None:
+        super().__init__()
+        self.records: list[logging.LogRecord] = []
+
+    def emit(self, record: logging.LogRecord) -> None:
+        self.records.append(record)
+
+
+def test_progress_fields() -> None:
+    logger = logging.getLogger("rss_atmos_rtm.python")
+    handler = CapturingHandler()
+    previous_level = logger.level
+    logger.addHandler(handler)
+    logger.setLevel(logging.INFO)
+    try:
+        num_points = 20
+        compute_rtm_dict(
+            standard_atmosphere("tropical", num_points=num_points),
+            parameters=RtmParameters([23.8, 53.74], [0.0, 30.0]),
+            options=RtmOptions(verbosity="progress"),
+        )
+    finally:
+        logger.removeHandler(handler)
+        logger.setLevel(previous_level)
+
+    progress = [
+        record
+        for record in handler.records
+        if record.getMessage().startswith("Completed RTM")
+    ]
+    assert progress
+    for record in progress:
+        assert record.total == num_points
+        assert 0 <= record.completed <= num_points
+        assert record.percent == record.completed / num_points * 100
+        assert record.elapsed_s >= 0
+        if record.completed == 0:
+            assert record.eta_s is None
+        else:
+            assert record.eta_s >= 0
+    # The last record is at the end, with nothing remaining
+    assert progress[-1].completed == num_points
+    assert progress[-1].eta_s == 0
@@ -0,0 +1,104 @@
+"""Writing the outputs into memory-mapped `.npy` files.
+
+Run with `pytest tests/python` after installing the package.
+"""
+
+from pathlib import Path
+
+import numpy as np
+import pytest
+from rss_atmos_rtm.rss_atmos_rtm import (
+    RtmOptions,
+    compute_rtm_npy,
+    compute_rtm_npy_into,
+    standard_atmosphere,
+)
+
+NUM_POINTS = 7
+FREQUENCY = np.array([23.8, 50.3, 53.74], dtype=np.float32)
+INCIDENCE_ANGLE = np.array([0.0, 30.0, 45.0], dtype=np.float32)
+NAMES = [
+    "pressure",
+    "temperature",
+    "height",
+    "specific_humidity",
+    "liquid_content",
+    "surface_temperature",
+    "surface_height",
+    "surface_dewpoint",
+    "surface_pressure",
+]
+
+
+def profile_files(directory: Path) -> list[Path]:
+    """Save the profiles of a few points as `.npy` files and return their
+    paths, in the order of the arguments."""
+    profiles = standard_atmosphere("tropical", num_points=NUM_POINTS)
+    paths = []
+    for name in NAMES:
+        path = directory / f"{name}.npy"
+        np.save(path, profiles[name].astype(np.float32))
+        paths.append(path)
+    return paths
+
+
+def open_outputs(directory: Path, shape: tuple[int, int]) -> list[np.memmap]:
+    """Create the output `.npy` files as memmaps."""
+    return [
+        np.lib.format.open_memmap(
+            directory / f"{name}_out.npy", mode="w+", dtype=np.float32, shape=shape
+        )
+        for name in ["tran", "tb_up", "tb_down"]
+    ]
+
+
+def test_matches_in_memory(tmp_path: Path) -> None:
+    """The rows written into the memmaps are the outputs of `compute_rtm_npy`,
+    for chunks that don't divide the points evenly."""
+    files = profile_files(tmp_path)
+    outputs = open_outputs(tmp_path, (NUM_POINTS, len(FREQUENCY)))
+    diagnostics = compute_rtm_npy_into(
+        *outputs,
+        *files,
+        INCIDENCE_ANGLE,
+        FREQUENCY,
+        num_threads=2,
+        chunk_size=3,
+        options=RtmOptions(verbosity="quiet"),
+    )
+    assert diagnostics.num_points == NUM_POINTS
+    for array in outputs:
+        array.flush()
+
+    expected = compute_rtm_npy(*files, INCIDENCE_ANGLE, FREQUENCY, num_threads=1)
+    for name, array in zip(["tran", "tb_up", "tb_down"], outputs):
+        np.testing.assert_array_equal(array, getattr(expected, name))
+        np.testing.assert_array_equal(
+            np.load(tmp_path / f"{name}_out.npy"), getattr(expected, name)
+        )
+
+
+def test_invalid(tmp_path: Path) -> None:
+    """Wrong shapes, overlapping outputs, and unsupported options raise."""
+    files = profile_files(tmp_path)
+    tran, tb_up, tb_down = open_outputs(tmp_path, (NUM_POINTS, len(FREQUENCY)))
+    with pytest.raises(ValueError, match="shape"):
+        compute_rtm_npy_into(
+            tran[1:], tb_up[1:], tb_down[1:], *files, INCIDENCE_ANGLE, FREQUENCY
+        )
+    with pytest.raises(Exception):
+        compute_rtm_npy_into(tran, tran, tb_down, *files, INCIDENCE_ANGLE, FREQUENCY)
+    with pytest.raises(ValueError, match="not supported"):
+        compute_rtm_npy_into(
+            tran,
+            tb_up,
+            tb_down,
+            *files,
+            INCIDENCE_ANGLE,
+            FREQUENCY,
+            options=RtmOptions(quality_flags=True),
+        )
+    with pytest.raises(ValueError, match="chunk_size"):
+        compute_rtm_npy_into(
+            tran, tb_up, tb_down, *files, INCIDENCE_ANGLE, FREQUENCY, chunk_size=0
+        )
@@ -0,0 +1,127 @@
+"""Writing the outputs to Parquet and reading them back with pyarrow.
+
+Run with `pytest tests/python` after installing the package, which must be
+built with the `arrow` feature.
+"""
+
+from pathlib import Path
+
+import numpy as np
+import pytest
+from rss_atmos_rtm.rss_atmos_rtm import (
+    AtmoParameters,
+    RtmOptions,
+    RtmParameters,
+    compute_rtm,
+    standard_atmosphere,
+)
+
+pq = pytest.importorskip("pyarrow.parquet")
+pa = pytest.importorskip("pyarrow")
+
+NUM_POINTS = 6
+
+
+def run_rtm() -> AtmoParameters:
+    """The outputs for a few points, with quality flags."""
+    profiles = standard_atmosphere("mid_latitude_summer", num_points=NUM_POINTS)
+    return compute_rtm(
+        profiles["pressure"],
+        profiles["temperature"],
+        profiles["height"],
+        profiles["specific_humidity"],
+        profiles["liquid_content"],
+        profiles["surface_temperature"],
+        profiles["surface_height"],
+        profiles["surface_dewpoint"],
+        profiles["surface_pressure"],
+        None,
+        None,
+        1,
+        parameters=RtmParameters([23.8, 53.74], [0.0, 30.0]),
+        options=RtmOptions(quality_flags=True, verbosity="quiet"),
+    )
+
+
+def coords() -> dict[str, np.ndarray]:
+    """Coordinates of the points, of several dtypes."""
+    return {
+        "time": np.datetime64("2024-01-01T00:00:00", "ns")
+        + np.arange(NUM_POINTS).astype("timedelta64[s]"),
+        "lat": np.linspace(-30.0, 30.0, NUM_POINTS),
+        "lon": np.linspace(0.0, 50.0, NUM_POINTS, dtype=np.float32),
+        "scan_position": np.arange(NUM_POINTS, dtype=np.int16),
+    }
+
+
+def test_wide(tmp_path: Path) -> None:
+    """A row for each point, and a column for each output and channel."""
+    outputs = run_rtm()
+    path = tmp_path / "wide.parquet"
+    outputs.to_parquet(path, coords=coords(), layout="wide")
+
+    table = pq.read_table(path)
+    assert table.num_rows == NUM_POINTS
+    assert table.column_names == [
+        "time",
+        "lat",
+        "lon",
+        "scan_position",
+        "quality_flags",
+        "num_levels_integrated",
+        "tran_0",
+        "tran_1",
+        "tb_up_0",
+        "tb_up_1",
+        "tb_down_0",
+        "tb_down_1",
+    ]
+    assert table.schema.field("time").type == pa.timestamp("ns")
+    assert table.schema.field("lon").type == pa.float32()
+    assert table.schema.field("scan_position").type == pa.int16()
+    assert table.schema.field("quality_flags").type == pa.uint32()
+    assert table.schema.field("tb_up_0").type == pa.float32()
+
+    np.testing.assert_array_equal(table["time"].to_numpy(), coords()["time"])
+    np.testing.assert_array_equal(table["lat"].to_numpy(), coords()["lat"])
+    np.testing.assert_array_equal(
+        table["quality_flags"].to_numpy(), outputs.quality_flags
+    )
+    for channel in range(2):
+        np.testing.assert_array_equal(
+            table[f"tb_up_{channel}"].to_numpy(), outputs.tb_up[:, channel]
+        )
+        np.testing.assert_array_equal(
+            table[f"tran_{channel}"].to_numpy(), outputs.tran[:, channel]
+        )
+
+
+def test_long(tmp_path: Path) -> None:
+    """A row for each point and channel, the same as the flat records."""
+    outputs = run_rtm()
+    path = tmp_path / "long.parquet"
+    outputs.to_parquet(path, coords=coords(), layout="long")
+
+    table = pq.read_table(path)
+    assert table.num_rows == NUM_POINTS * 2
+    assert table.column_names[:3] == ["point", "channel", "time"]
+    assert table.schema.field("point").type == pa.uint64()
+
+    records = outputs.flat_records()
+    for name, values in records.items():
+        np.testing.assert_array_equal(table[name].to_numpy(), values)
+    np.testing.assert_array_equal(
+        table["lat"].to_numpy(), np.repeat(coords()["lat"], 2)
+    )
+
+
+def test_invalid(tmp_path: Path) -> None:
+    """Coordinates of the wrong length or name, and unknown layouts, raise."""
+    outputs = run_rtm()
+    path = tmp_path / "invalid.parquet"
+    with pytest.raises(ValueError, match="layout"):
+        outputs.to_parquet(path, layout="tall")
+    with pytest.raises(ValueError):
+        outputs.to_parquet(path, coords={"lat": np.zeros(NUM_POINTS + 1)})
+    with pytest.raises(ValueError, match="duplicate"):
+        outputs.to_parquet(path, coords={"tran_0": np.zeros(NUM_POINTS)})
@@ -0,0 +1,75 @@
+"""Pickling the configuration and running `compute_rtm_block` in a worker.
+
+Run with `pytest tests/python` after installing the package.
+"""
+
+import multiprocessing
+import pickle
+
+import numpy as np
+from rss_atmos_rtm.rss_atmos_rtm import (
+    Channel,
+    RtmOptions,
+    RtmParameters,
+    compute_rtm,
+    compute_rtm_block,
+    standard_atmosphere,
+)
+
+
+def test_pickle_round_trip() -> None:
+    """The configuration is the same after pickling."""
+    options = RtmOptions(
+        emission="planck",
+        layer_sum_reference="surface",
+        top_pressure=10.0,
+        verbosity="quiet",
+        cloud_scale=1.1,
+    )
+    unpickled = pickle.loads(pickle.dumps(options))  # noqa: S301
+    assert unpickled == options
+    assert unpickled.layer_sum_reference == "surface"
+
+    parameters = RtmParameters([50.3, 53.74], [0.0, 30.0], frequency_offset=[1.0, -2.0])
+    unpickled = pickle.loads(pickle.dumps(parameters))  # noqa: S301
+    np.testing.assert_array_equal(unpickled.frequency, parameters.frequency)
+    np.testing.assert_array_equal(unpickled.incidence_angle, parameters.incidence_angle)
+
+    channel = Channel.from_quadrature([53.6, 53.8], [1.0, 3.0])
+    parameters = RtmParameters.from_channels([channel], [10.0])
+    unpickled = pickle.loads(pickle.dumps(parameters))  # noqa: S301
+    np.testing.assert_allclose(unpickled.frequency, parameters.frequency)
+
+
+def test_block_in_spawned_process() -> None:
+    """A block run in a spawned process matches `compute_rtm` here."""
+    profiles = standard_atmosphere("tropical", num_points=20)
+    parameters = RtmParameters([23.8, 53.74, 57.95], [0.0, 20.0, 40.0])
+    options = RtmOptions(verbosity="quiet", cloud_scale=1.1)
+    args = (
+        profiles["pressure"],
+        profiles["temperature"],
+        profiles["height"],
+        profiles["specific_humidity"],
+        profiles["liquid_content"],
+        profiles["surface_temperature"],
+        profiles["surface_height"],
+        profiles["surface_dewpoint"],
+        profiles["surface_pressure"],
+    )
+
+    with multiprocessing.get_context("spawn").Pool(1) as pool:
+        block = pool.apply(
+            compute_rtm_block,
+            (*args, pickle.loads(pickle.dumps(parameters))),  # noqa: S301
+            {"options": options, "num_threads": 2},
+        )
+
+    expected = compute_rtm(
+        *args, None, None, 2, parameters=parameters, options=options
+    )
+    assert block.shape == (20, 3, 3)
+    assert block.dtype == np.float32
+    np.testing.assert_array_equal(block[..., 0], expected.tran)
+    np.testing.assert_array_equal(block[..., 1], expected.tb_up)
+    np.testing.assert_array_equal(block[..., 2], expected.tb_down)
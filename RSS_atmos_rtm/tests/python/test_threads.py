@@ -0,0 +1,63 @@
+"""Choosing the number of worker threads from the size of the batch, and the
+chunks of points that they run.
+
+Run with `pytest tests/python` after installing the package.
+"""
+
+import numpy as np
+import pytest
+from rss_atmos_rtm.rss_atmos_rtm import (
+    RtmOptions,
+    RtmParameters,
+    compute_rtm_dict,
+    standard_atmosphere,
+)
+
+OPTIONS = RtmOptions(verbosity="quiet")
+PARAMETERS = RtmParameters([23.8, 53.74], [0.0, 30.0])
+
+
+def test_defaults() -> None:
+    assert OPTIONS.items_per_thread == 512
+    assert OPTIONS.work_chunk_size == 32
+
+
+def test_outputs_independent_of_target() -> None:
+    """The outputs are the same for any target, which only changes the number
+    of threads."""
+    profiles = standard_atmosphere("tropical", num_points=50)
+    expected = compute_rtm_dict(
+        profiles, num_threads=1, parameters=PARAMETERS, options=OPTIONS
+    )
+    options = RtmOptions(verbosity="quiet", items_per_thread=1)
+    assert options.items_per_thread == 1
+    outputs = compute_rtm_dict(profiles, parameters=PARAMETERS, options=options)
+    np.testing.assert_array_equal(outputs.tb_up, expected.tb_up)
+    np.testing.assert_array_equal(outputs.tb_down, expected.tb_down)
+
+
+def test_invalid_target() -> None:
+    with pytest.raises(ValueError, match="positive"):
+        RtmOptions(items_per_thread=0)
+
+
+def test_outputs_independent_of_chunk_size() -> None:
+    """The outputs are the same for any chunk size, which only changes how the
+    points are split between the threads."""
+    profiles = standard_atmosphere("tropical", num_points=50)
+    expected = compute_rtm_dict(
+        profiles, num_threads=1, parameters=PARAMETERS, options=OPTIONS
+    )
+    for work_chunk_size in [1, 7, 2**62]:
+        options = RtmOptions(verbosity="quiet", work_chunk_size=work_chunk_size)
+        assert options.work_chunk_size == work_chunk_size
+        outputs = compute_rtm_dict(
+            profiles, num_threads=4, parameters=PARAMETERS, options=options
+        )
+        np.testing.assert_array_equal(outputs.tb_up, expected.tb_up)
+        np.testing.assert_array_equal(outputs.tb_down, expected.tb_down)
+
+
+def test_invalid_chunk_size() -> None:
+    with pytest.raises(ValueError, match="positive"):
+        RtmOptions(work_chunk_size=0)
@@ -0,0 +1,88 @@
+"""Writing the outputs to a Zarr store and reading them back with zarr-python
+and xarray.
+
+Run with `pytest tests/python` after installing the package.
+"""
+
+from pathlib import Path
+
+import numpy as np
+import pytest
+from rss_atmos_rtm.rss_atmos_rtm import (
+    RtmOptions,
+    RtmParameters,
+    compute_rtm_block,
+    compute_rtm_to_zarr,
+    get_model_info,
+    standard_atmosphere,
+)
+
+zarr = pytest.importorskip("zarr")
+xr = pytest.importorskip("xarray")
+
+NUM_POINTS = 7
+PARAMETERS = RtmParameters([23.8, 50.3, 53.74], [0.0, 30.0, 45.0])
+
+
+def profile_args() -> list:
+    """The positional profile arguments, for a few points."""
+    profiles = standard_atmosphere("tropical", num_points=NUM_POINTS)
+    return [
+        profiles[name]
+        for name in [
+            "pressure",
+            "temperature",
+            "height",
+            "specific_humidity",
+            "liquid_content",
+            "surface_temperature",
+            "surface_height",
+            "surface_dewpoint",
+            "surface_pressure",
+        ]
+    ]
+
+
+def test_matches_in_memory(tmp_path: Path) -> None:
+    """The chunks written to the store are the outputs of a single run."""
+    store = tmp_path / "outputs.zarr"
+    diagnostics = compute_rtm_to_zarr(
+        store,
+        *profile_args(),
+        PARAMETERS,
+        num_threads=1,
+        chunk_points=3,
+        options=RtmOptions(quality_flags=True, verbosity="quiet"),
+    )
+    assert diagnostics.num_points == NUM_POINTS
+    expected = compute_rtm_block(*profile_args(), PARAMETERS, num_threads=1)
+
+    group = zarr.open_group(store, mode="r")
+    assert group["tb_up"].shape == (NUM_POINTS, 3)
+    assert group["tb_up"].chunks == (3, 3)
+    for index, name in enumerate(["tran", "tb_up", "tb_down"]):
+        np.testing.assert_array_equal(group[name][:], expected[:, :, index])
+    assert group["tb_up"].attrs["units"] == "K"
+    assert group.attrs["version"] == get_model_info()["version"]
+    assert group.attrs["spectroscopy"] == get_model_info()["spectroscopy"]
+
+    ds = xr.open_zarr(store)
+    assert ds["tb_up"].dims == ("point", "channel")
+    np.testing.assert_allclose(ds["frequency"].values, [23.8, 50.3, 53.74], rtol=1e-6)
+    np.testing.assert_allclose(ds["incidence_angle"].values, [0.0, 30.0, 45.0])
+    assert ds["quality_flags"].dtype == np.uint32
+    assert ds["num_levels_integrated"].dims == ("point",)
+
+
+def test_invalid(tmp_path: Path) -> None:
+    """Unsupported options and empty chunks raise."""
+    store = tmp_path / "invalid.zarr"
+    with pytest.raises(ValueError, match="chunk_points"):
+        compute_rtm_to_zarr(store, *profile_args(), PARAMETERS, chunk_points=0)
+    with pytest.raises(ValueError, match="not supported"):
+        compute_rtm_to_zarr(
+            store,
+            *profile_args(),
+            PARAMETERS,
+            options=RtmOptions(effective_temperature=True),
+        )
@@ -0,0 +1,184 @@
+//! Read RTTOV-format profiles and pin the RTM outputs for them.
+//!
+//! The profiles in `tests/data/rttov/prof.dat` are synthetic profiles in the
+//! format of RTTOV's example profile files, on the RTTOV 54 levels. The
+//! outputs are pinned so that any change to the RTM or to the reader that
+//! affects them is noticed, and so that they can be compared with RTTOV run
+//! on the same file.
+#![cfg(feature = "io")]
+
+use approx::{assert_abs_diff_eq, assert_relative_eq};
+use rss_atmos_rtm::rtm::{
+    dewpoint_to_vapor_pressure,
+    rttov::{parse_profiles, read_profiles},
+    specific_humidity_to_vapor_pressure, vapor_pressure_to_specific_humidity, QualityFlags,
+    RtmError, RtmParameters,
+};
+
+const PROFILE_FILE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/rttov/prof.dat");
+const NUM_LEVELS: usize = 54;
+const FREQUENCIES: [f32; 6] = [23.8, 50.3, 53.74, 57.95, 89., 183.31];
+
+#[test]
+fn read_bundled_profiles() {
+    let profiles = read_profiles(PROFILE_FILE, NUM_LEVELS).unwrap();
+    assert_eq!(profiles.len(), 2);
+
+    for profile in &profiles {
+        // The levels are reversed to go from the bottom up
+        assert_eq!(profile.pressure.len(), NUM_LEVELS);
+        assert_eq!(profile.pressure[0], 1050.);
+        assert_eq!(profile.pressure[NUM_LEVELS - 1], 0.005);
+        assert!(profile.height.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(profile.liquid_content.iter().all(|&value| value == 0.));
+
+        // The levels below the surface are below its elevation, and those
+        // above it are above
+        for (&p, &z) in profile.pressure.iter().zip(&profile.height) {
+            assert_eq!(p > profile.surface_pressure, z < profile.surface_height);
+        }
+        assert!(profile.surface_dewpoint < profile.surface_temperature);
+    }
+
+    let first = &profiles[0];
+    assert_eq!(first.surface_temperature, 294.);
+    assert_eq!(first.surface_pressure, 1013.);
+    assert_eq!(first.skin_temperature, 293.5);
+    assert_eq!(first.zenith_angle, 0.);
+    assert_eq!(profiles[1].zenith_angle, 45.);
+    // About 5.9 km at 500 hPa
+    let mid = first.pressure.iter().position(|&p| p < 500.).unwrap();
+    assert_abs_diff_eq!(first.height[mid], 5871., epsilon = 1.);
+}
+
+/// The outputs for the bundled profiles at the satellite zenith angle of each.
+#[test]
+fn pinned_outputs() {
+    #[rustfmt::skip]
+    let expected: [[[f32; 6]; 3]; 2] = [
+        [
+            [0.896_065_5, 0.682_507_75, 0.097_329_49, 0., 0.810_893_2, 0.],
+            [29.413_59, 85.306_85, 228.069_56, 218.271_32, 53.269_726, 256.256_77],
+            [29.480_188, 86.680_13, 251.871_2, 291.720_55, 53.540_005, 293.247_1],
+        ],
+        [
+            [0.803_115_4, 0.567_079_5, 0.035_910_133, 0., 0.661_378_26, 0.],
+            [56.900_574, 118.007_225, 240.481_93, 207.206_39, 97.536_58, 252.642_96],
+            [57.141_666, 121.016_32, 277.655_36, 298.230_74, 98.412_46, 299.396_24],
+        ],
+    ];
+
+    for (profile, expected) in read_profiles(PROFILE_FILE, NUM_LEVELS)
+        .unwrap()
+        .iter()
+        .zip(expected)
+    {
+        let parameters =
+            RtmParameters::new(&FREQUENCIES, &[profile.zenith_angle; FREQUENCIES.len()]).unwrap();
+        let outputs = profile.inputs().unwrap().run(&parameters);
+        assert_eq!(outputs.quality, QualityFlags::empty());
+        for i in 0..FREQUENCIES.len() {
+            assert_abs_diff_eq!(outputs.tran[i], expected[0][i], epsilon = 1e-5);
+            assert_abs_diff_eq!(outputs.tb_up[i], expected[1][i], epsilon = 0.01);
+            assert_abs_diff_eq!(outputs.tb_down[i], expected[2][i], epsilon = 0.01);
+        }
+    }
+}
+
+/// A single profile with two levels, with the water vapor in `gas_units`.
+fn two_level_file(gas_units: u32, water_vapor: [f32; 2], surface_water_vapor: f32) -> String {
+    format!(
+        "! Gas units\n\
+         {gas_units}\n\
+         ! Pressure, temperature, and water vapor, from the top down\n\
+         500.0 1000.0\n\
+         250.0 290.0\n\
+         {} {}\n\
+         ! Near-surface variables\n\
+         290.0 {surface_water_vapor} 990.0 0.0 0.0 100000.\n\
+         290.5 35.0 3.0 5.0 15.0 0.1 0.3\n\
+         1 1\n\
+         0.1 45.0 0.0\n\
+         30.0 0.0 0.0 0.0\n\
+         500.0 0.0\n",
+        water_vapor[0], water_vapor[1]
+    )
+}
+
+#[test]
+fn gas_units() {
+    let p = [1000., 500.];
+    let q = [1e-2, 1e-4];
+    let surface_q = 8e-3;
+    let kg_per_kg = &parse_profiles(&two_level_file(1, [q[1], q[0]], surface_q), 2).unwrap()[0];
+    assert_eq!(kg_per_kg.specific_humidity, q);
+    assert_eq!(kg_per_kg.surface_height, 100.);
+
+    // The same humidity as volume mixing ratios over moist and dry air
+    fn ppmv_moist(q: f32, p: f32) -> f32 {
+        1e6 * specific_humidity_to_vapor_pressure(q, p) / p
+    }
+    fn ppmv_dry(q: f32, p: f32) -> f32 {
+        let pv = specific_humidity_to_vapor_pressure(q, p);
+        1e6 * pv / (p - pv)
+    }
+    type ToPpmv = fn(f32, f32) -> f32;
+    let conversions: [(u32, ToPpmv); 2] = [(2, ppmv_moist), (0, ppmv_dry)];
+    for (gas_units, ppmv) in conversions {
+        let file = two_level_file(
+            gas_units,
+            [ppmv(q[1], p[1]), ppmv(q[0], p[0])],
+            ppmv(surface_q, 990.),
+        );
+        let profile = &parse_profiles(&file, 2).unwrap()[0];
+        for i in 0..2 {
+            assert_relative_eq!(
+                profile.specific_humidity[i],
+                kg_per_kg.specific_humidity[i],
+                max_relative = 1e-5
+            );
+        }
+        assert_relative_eq!(
+            profile.surface_dewpoint,
+            kg_per_kg.surface_dewpoint,
+            max_relative = 1e-5
+        );
+    }
+    assert_relative_eq!(
+        vapor_pressure_to_specific_humidity(
+            dewpoint_to_vapor_pressure(kg_per_kg.surface_dewpoint),
+            990.
+        ),
+        surface_q,
+        max_relative = 1e-4
+    );
+}
+
+#[test]
+fn invalid_files() {
+    let line_of = |result: Result<_, RtmError>| match result {
+        Err(RtmError::InvalidProfileFile { line, .. }) => line,
+        other => panic!("expected an invalid file, got {other:?}"),
+    };
+
+    let valid = two_level_file(1, [1e-4, 1e-2], 8e-3);
+    assert_eq!(
+        line_of(parse_profiles(&valid.replace("\n1\n", "\n3\n"), 2)),
+        2
+    );
+    assert_eq!(
+        line_of(parse_profiles(&valid.replace("250.0", "cold"), 2)),
+        5
+    );
+    // Truncated in the middle of the last profile, after the values on line 12
+    assert_eq!(
+        line_of(parse_profiles(&valid.replace("500.0 0.0\n", ""), 2)),
+        12
+    );
+    // The wrong number of levels runs into the surface values
+    assert!(parse_profiles(&valid, 3).is_err());
+    assert!(matches!(
+        read_profiles("does/not/exist", 2),
+        Err(RtmError::Io(_))
+    ));
+}
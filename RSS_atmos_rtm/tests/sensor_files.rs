@@ -0,0 +1,138 @@
+//! Read the sensor definition files in `data` and check them against the
+//! built-in scan geometry.
+#![cfg(feature = "io")]
+
+use approx::assert_relative_eq;
+use rss_atmos_rtm::rtm::{
+    scan_geometry::{ScanSensor, AMSU_A, MSU},
+    sensor_file::{parse_sensor, read_sensor},
+    RtmError, RtmParameters,
+};
+
+/// Path of a file in the `data` directory.
+fn data_file(name: &str) -> String {
+    format!("{}/data/{name}", env!("CARGO_MANIFEST_DIR"))
+}
+
+/// The example files have the same channels and scan geometry as the tables
+/// of `scan_geometry`, and the monochromatic frequency of each channel there
+/// is the center of a passband.
+#[test]
+fn bundled_sensors_match_scan_geometry() {
+    for (file, table) in [("msu.toml", MSU), ("amsu_a.toml", AMSU_A)] {
+        let sensor = read_sensor(data_file(file)).unwrap();
+        let ScanSensor {
+            name,
+            frequencies,
+            polarizations,
+            scan_angles,
+            incidence_angles,
+        } = table;
+        assert_eq!(sensor.name, name);
+        assert_eq!(sensor.num_channels(), frequencies.len());
+        for (index, channel) in sensor.channels.iter().enumerate() {
+            assert_eq!(channel.polarization, polarizations[index]);
+            let centers = channel.passband_centers();
+            assert!(
+                centers
+                    .iter()
+                    .any(|&center| (center - f64::from(frequencies[index])).abs() < 1e-5),
+                "{name} channel {}",
+                index + 1
+            );
+        }
+        let to_f32 =
+            |values: &[f64]| -> Vec<f32> { values.iter().map(|&value| value as f32).collect() };
+        assert_eq!(to_f32(&sensor.incidence_angles), incidence_angles);
+        assert_eq!(to_f32(sensor.scan_angles.as_deref().unwrap()), scan_angles);
+    }
+}
+
+/// Writing a definition and reading it back gives the same definition.
+#[test]
+fn sensor_round_trip() {
+    for file in ["msu.toml", "amsu_a.toml"] {
+        let sensor = read_sensor(data_file(file)).unwrap();
+        assert_eq!(parse_sensor(&sensor.to_toml()).unwrap(), sensor);
+    }
+}
+
+/// The AMSU-A channels have 4 quadrature nodes across each passband, and the
+/// passbands are symmetric about the center frequency.
+#[test]
+fn sensor_passbands() {
+    let text = std::fs::read_to_string(data_file("amsu_a.toml")).unwrap();
+    let parameters = RtmParameters::<f64>::from_toml(&text, &[1, 5, 11], 0).unwrap();
+    assert_eq!(parameters.incidence_angles(), [57.64; 3]);
+    let passbands = parameters.passbands().unwrap();
+    let num_nodes: Vec<_> = passbands
+        .iter()
+        .map(|channel| channel.nodes().len())
+        .collect();
+    assert_eq!(num_nodes, [4, 8, 16]);
+    // The quadrature is symmetric about the center frequency
+    assert_relative_eq!(passbands[1].centroid(), 53.596, max_relative = 1e-12);
+    assert_relative_eq!(passbands[2].centroid(), 57.290344, max_relative = 1e-12);
+    // The nodes of channel 1 are the midpoints of 4 bins across 270 MHz
+    assert_relative_eq!(
+        passbands[0].nodes()[0],
+        23.8 - 0.135 + 0.03375,
+        max_relative = 1e-12
+    );
+
+    assert!(matches!(
+        RtmParameters::<f32>::from_toml(&text, &[16], 0),
+        Err(RtmError::InvalidScanGeometry(_))
+    ));
+    assert!(matches!(
+        RtmParameters::<f32>::from_toml(&text, &[1], 30),
+        Err(RtmError::InvalidScanGeometry(_))
+    ));
+}
+
+/// Invalid files are rejected with the path of the offending key.
+#[test]
+fn invalid_sensor_files() {
+    let key_of = |text: &str| match parse_sensor(text) {
+        Err(RtmError::InvalidSensorFile { key, .. }) => key,
+        other => panic!("expected an invalid sensor file, got {other:?}"),
+    };
+    let channel = "[[channels]]\nnumber = 1\nfrequency = 50.3\npolarization = \"v\"\n";
+    let valid = format!("name = \"test\"\nincidence_angles = [0.0]\n{channel}");
+    assert!(parse_sensor(&valid).is_ok());
+
+    assert_eq!(key_of("name = "), None);
+    assert_eq!(
+        key_of(&format!("incidence_angles = [0.0]\n{channel}")).as_deref(),
+        Some("name")
+    );
+    assert_eq!(
+        key_of(&format!("{valid}bandwith = 0.2\n")).as_deref(),
+        Some("channels[0].bandwith")
+    );
+    assert_eq!(
+        key_of(&format!("{valid}bandwidth = -0.2\n")).as_deref(),
+        Some("channels[0].bandwidth")
+    );
+    assert_eq!(
+        key_of(&format!("{valid}sidebands = [0.1, 0.0]\n")).as_deref(),
+        Some("channels[0].sidebands[1]")
+    );
+    assert_eq!(
+        key_of(&format!("{valid}{channel}")).as_deref(),
+        Some("channels[1].number")
+    );
+    let second = channel.replace("= 1", "= 2").replace("\"v\"", "\"x\"");
+    assert_eq!(
+        key_of(&format!("{valid}{second}")).as_deref(),
+        Some("channels[1].polarization")
+    );
+    assert_eq!(
+        key_of(&valid.replace("[0.0]", "[0.0, 95.0]")).as_deref(),
+        Some("incidence_angles[1]")
+    );
+    assert_eq!(
+        key_of(&format!("scan_angles = [0.0, 1.0]\n{valid}")).as_deref(),
+        Some("scan_angles")
+    );
+}
@@ -0,0 +1,120 @@
+//! Run a standard atmosphere through the JavaScript interface and compare it
+//! to the native outputs in `tests/data/wasm/tropical.csv`.
+//!
+//! The reference file is from a native build of `RtmInputs::run`, so on the
+//! native target this checks that the file is up to date, and on `wasm32` it
+//! checks that the WebAssembly build matches. Run it in Node.js with:
+//!
+//! ```text
+//! wasm-pack test --node --no-default-features --features wasm
+//! ```
+#![cfg(feature = "wasm")]
+
+use approx::assert_relative_eq;
+use rss_atmos_rtm::{
+    rtm::{standard_atmospheres::TROPICAL, RtmParameters},
+    wasm::compute_single_profile,
+};
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen_test::wasm_bindgen_test as test;
+
+/// The native outputs, with a header row and a row of "frequency,
+/// incidence_angle, tran, tb_up, tb_down" for each channel.
+const REFERENCE: &str = include_str!("data/wasm/tropical.csv");
+
+/// Maximum relative difference from the reference. The math functions in
+/// WebAssembly aren't the system's, so the results aren't bit-for-bit the same.
+const TOLERANCE: f32 = 1e-5;
+
+/// Maximum absolute difference of the transmissivity, for the opaque channels.
+const TRAN_EPSILON: f32 = 1e-6;
+
+/// The columns of each row of the reference file.
+fn reference() -> Vec<[f32; 5]> {
+    let mut lines = REFERENCE.lines();
+    assert_eq!(
+        lines.next(),
+        Some("frequency,incidence_angle,tran,tb_up,tb_down")
+    );
+    lines
+        .map(|line| {
+            let values: Vec<f32> = line.split(',').map(|v| v.parse().unwrap()).collect();
+            values.try_into().unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn standard_atmosphere_matches_native() {
+    let reference = reference();
+    let frequency: Vec<f32> = reference.iter().map(|row| row[0]).collect();
+    let incidence_angle: Vec<f32> = reference.iter().map(|row| row[1]).collect();
+
+    let profile = TROPICAL.profile();
+    let outputs = compute_single_profile(
+        &profile.pressure,
+        &profile.temperature,
+        &profile.height,
+        &profile.specific_humidity,
+        &[],
+        profile.surface_temperature,
+        profile.surface_height,
+        profile.surface_dewpoint,
+        Some(profile.surface_pressure),
+        &frequency,
+        &incidence_angle,
+    )
+    .unwrap();
+
+    for (channel, row) in reference.iter().enumerate() {
+        assert_relative_eq!(
+            outputs.tran()[channel],
+            row[2],
+            epsilon = TRAN_EPSILON,
+            max_relative = TOLERANCE
+        );
+        assert_relative_eq!(outputs.tb_up()[channel], row[3], max_relative = TOLERANCE);
+        assert_relative_eq!(outputs.tb_down()[channel], row[4], max_relative = TOLERANCE);
+    }
+}
+
+/// The reference file is the output of the Rust API.
+#[test]
+fn reference_is_native() {
+    let reference = reference();
+    let frequency: Vec<f32> = reference.iter().map(|row| row[0]).collect();
+    let incidence_angle: Vec<f32> = reference.iter().map(|row| row[1]).collect();
+    let parameters = RtmParameters::new(&frequency, &incidence_angle).unwrap();
+    let outputs = TROPICAL.profile().inputs().unwrap().run(&parameters);
+    for (channel, row) in reference.iter().enumerate() {
+        assert_relative_eq!(
+            outputs.tran[channel],
+            row[2],
+            epsilon = TRAN_EPSILON,
+            max_relative = TOLERANCE
+        );
+        assert_relative_eq!(outputs.tb_up[channel], row[3], max_relative = TOLERANCE);
+        assert_relative_eq!(outputs.tb_down[channel], row[4], max_relative = TOLERANCE);
+    }
+}
+
+/// Invalid channels are an error, which is thrown in JavaScript.
+#[cfg(target_arch = "wasm32")]
+#[test]
+fn invalid_channels() {
+    let profile = TROPICAL.profile();
+    let result = compute_single_profile(
+        &profile.pressure,
+        &profile.temperature,
+        &profile.height,
+        &profile.specific_humidity,
+        &[],
+        profile.surface_temperature,
+        profile.surface_height,
+        profile.surface_dewpoint,
+        None,
+        &[23.8],
+        &[95.],
+    );
+    assert!(result.is_err());
+}
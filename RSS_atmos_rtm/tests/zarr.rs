@@ -0,0 +1,152 @@
+//! Writing the outputs to a Zarr store a chunk of points at a time, and
+//! reading the chunks back, compared with running all of the points at once.
+#![cfg(feature = "io")]
+
+use std::{fs, num::NonZeroUsize, path::Path};
+
+use ndarray::{Array1, Array2, ArrayView1};
+use rss_atmos_rtm::rtm::{
+    standard_atmospheres::ALL,
+    zarr::{ZarrAttribute, ZarrGroup},
+    ArrayProfiles, ProfileSource, RtmError, RtmParameters,
+};
+
+/// The `tb_up` of each point of `source`, with a row for each point.
+fn tb_up(source: &ArrayProfiles<'_>, parameters: &RtmParameters) -> Array2<f32> {
+    let mut tb_up = Array2::zeros((source.len(), parameters.len()));
+    for (point, mut row) in tb_up.rows_mut().into_iter().enumerate() {
+        let outputs = source
+            .profile(point)
+            .unwrap()
+            .inputs()
+            .unwrap()
+            .run(parameters);
+        row.assign(&ArrayView1::from(outputs.tb_up.as_slice()));
+    }
+    tb_up
+}
+
+/// Read a chunk file of float32 values.
+fn read_chunk(path: &Path) -> Vec<f32> {
+    fs::read(path)
+        .unwrap()
+        .chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+        .collect()
+}
+
+#[test]
+fn chunks_match_a_single_run() {
+    // The standard atmospheres repeated to 7 points, in chunks of 3, so the
+    // last chunk is partial
+    let num_points = 7;
+    let chunk_rows = 3;
+    let profiles: Vec<_> = (0..num_points)
+        .map(|point| ALL[point % ALL.len()].profile())
+        .collect();
+    let num_levels = profiles[0].pressure.len();
+    let rows = |values: fn(&_) -> &Vec<f32>| {
+        Array2::from_shape_fn((num_points, num_levels), |(point, level)| {
+            values(&profiles[point])[level]
+        })
+    };
+    let temperature = rows(|profile| &profile.temperature);
+    let height = rows(|profile| &profile.height);
+    let specific_humidity = rows(|profile| &profile.specific_humidity);
+    let liquid_content = Array2::zeros((num_points, 0));
+    let surface = |value: fn(&_) -> f32| profiles.iter().map(value).collect::<Array1<f32>>();
+    let surface_temperature = surface(|profile| profile.surface_temperature);
+    let surface_height = surface(|profile| profile.surface_height);
+    let surface_dewpoint = surface(|profile| profile.surface_dewpoint);
+    let surface_pressure = surface(|profile| profile.surface_pressure);
+    let source = ArrayProfiles::new(
+        &profiles[0].pressure,
+        temperature.view(),
+        height.view(),
+        specific_humidity.view(),
+        liquid_content.view(),
+        surface_temperature.as_slice().unwrap(),
+        surface_height.as_slice().unwrap(),
+        surface_dewpoint.as_slice().unwrap(),
+        Some(surface_pressure.as_slice().unwrap()),
+        None,
+        None,
+        None,
+    )
+    .unwrap();
+    let parameters = RtmParameters::new(&[23.8, 50.3, 57.95], &[0., 20., 40.]).unwrap();
+    let expected = tb_up(&source, &parameters);
+
+    let dir = std::env::temp_dir().join(format!("rss_atmos_rtm_{}.zarr", std::process::id()));
+    let mut group = ZarrGroup::create(
+        &dir,
+        vec![(
+            "model".to_string(),
+            ZarrAttribute::Map(vec![(
+                "version".to_string(),
+                ZarrAttribute::Text("1.0".into()),
+            )]),
+        )],
+    )
+    .unwrap();
+    let array = group
+        .create_array::<f32>(
+            "tb_up",
+            &[num_points, parameters.len()],
+            NonZeroUsize::new(chunk_rows).unwrap(),
+            &["point", "channel"],
+            vec![("units".to_string(), ZarrAttribute::Text("K".into()))],
+        )
+        .unwrap();
+    assert_eq!(array.num_chunks(), 3);
+    for chunk in 0..array.num_chunks() {
+        let range = array.chunk_range(chunk);
+        let values = tb_up(&source.points(range).unwrap(), &parameters);
+        array.write_chunk(chunk, values.view().into_dyn()).unwrap();
+    }
+
+    for chunk in 0..array.num_chunks() {
+        let values = read_chunk(&dir.join(format!("tb_up/{chunk}.0")));
+        assert_eq!(values.len(), chunk_rows * parameters.len());
+        for (offset, row) in values.chunks_exact(parameters.len()).enumerate() {
+            let point = chunk * chunk_rows + offset;
+            if point < num_points {
+                assert_eq!(row, expected.row(point).as_slice().unwrap());
+            } else {
+                // The last chunk is padded with the fill value
+                assert!(row.iter().all(|value| value.is_nan()));
+            }
+        }
+    }
+
+    let zarray = fs::read_to_string(dir.join("tb_up/.zarray")).unwrap();
+    assert!(zarray.contains("\"shape\": [7, 3]"));
+    assert!(zarray.contains("\"chunks\": [3, 3]"));
+    assert!(zarray.contains("\"dtype\": \"<f4\""));
+    let zattrs = fs::read_to_string(dir.join("tb_up/.zattrs")).unwrap();
+    assert_eq!(
+        zattrs,
+        "{\"_ARRAY_DIMENSIONS\": [\"point\", \"channel\"], \"units\": \"K\"}"
+    );
+    let consolidated = fs::read_to_string(dir.join(".zmetadata")).unwrap();
+    for key in [".zgroup", ".zattrs", "tb_up/.zarray", "tb_up/.zattrs"] {
+        assert!(consolidated.contains(&format!("\"{key}\": {{")));
+    }
+    assert!(consolidated.contains("{\"model\": {\"version\": \"1.0\"}}"));
+
+    // Chunks that don't exist or have the wrong shape
+    let row = expected.slice(ndarray::s![..1, ..]).into_dyn();
+    assert!(matches!(
+        array.write_chunk(3, row.clone()),
+        Err(RtmError::InconsistentInputs)
+    ));
+    assert!(matches!(
+        array.write_chunk(0, row),
+        Err(RtmError::InconsistentInputs)
+    ));
+    assert!(matches!(
+        source.points(5..8),
+        Err(RtmError::InconsistentInputs)
+    ));
+    fs::remove_dir_all(&dir).unwrap();
+}